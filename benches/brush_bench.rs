@@ -2,7 +2,7 @@ use criterion::{Criterion, criterion_group, criterion_main};
 use eframe::egui::Color32;
 use rayon::ThreadPoolBuilder;
 use rusty_painter::{
-    brush_engine::{brush::Brush, stroke::StrokeState},
+    brush_engine::{brush::Brush, engine::{BrushEngine, StrokeTarget}, stroke::DabEngine},
     canvas::{canvas::Canvas, history::UndoAction},
     utils::vector::Vec2,
 };
@@ -17,44 +17,42 @@ fn bench_soft_dab(c: &mut Criterion) {
         Color32::from_rgba_unmultiplied(0, 0, 0, 255),
         20.0,
     );
-    let mut undo_action = UndoAction { tiles: Vec::new(), selection: None, transform: None };
+    let mut undo_action = UndoAction::new("Bench");
     let mut modified_tiles = HashSet::new();
 
     // Warm up the mask cache and tile allocation so the measurement focuses on per-dab work.
-    let mut stroke = StrokeState::new();
-    stroke.add_point(
-        &pool,
-        &canvas,
+    let mut stroke = DabEngine::new();
+    stroke.begin_stroke();
+    stroke.add_sample(
+        &mut StrokeTarget {
+            pool: &pool,
+            canvas: &canvas,
+            selection: None,
+            undo_action: &mut undo_action,
+            modified_tiles: &mut modified_tiles,
+        },
         &mut brush,
         Vec2 { x: 256.0, y: 256.0 },
-        &mut undo_action,
-        &mut modified_tiles,
     );
     undo_action.tiles.clear();
     modified_tiles.clear();
 
     c.bench_function("soft_dab_512px", |b| {
         b.iter(|| {
-            let mut stroke = StrokeState::new();
+            let mut stroke = DabEngine::new();
             undo_action.tiles.clear();
             modified_tiles.clear();
+            let mut target = StrokeTarget {
+                pool: &pool,
+                canvas: &canvas,
+                selection: None,
+                undo_action: &mut undo_action,
+                modified_tiles: &mut modified_tiles,
+            };
 
-            stroke.add_point(
-                &pool,
-                &canvas,
-                &mut brush,
-                Vec2 { x: 256.0, y: 256.0 },
-                &mut undo_action,
-                &mut modified_tiles,
-            );
-            stroke.add_point(
-                &pool,
-                &canvas,
-                &mut brush,
-                Vec2 { x: 280.0, y: 256.0 },
-                &mut undo_action,
-                &mut modified_tiles,
-            );
+            stroke.begin_stroke();
+            stroke.add_sample(&mut target, &mut brush, Vec2 { x: 256.0, y: 256.0 });
+            stroke.add_sample(&mut target, &mut brush, Vec2 { x: 280.0, y: 256.0 });
         });
     });
 }
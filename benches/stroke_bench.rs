@@ -0,0 +1,32 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rusty_painter::app::bench::{BRUSH_DIAMETERS, StrokeReplay, recorded_stroke};
+
+/// Replays the recorded scribble per brush size; compare against a saved baseline to catch regressions.
+fn bench_stroke_replay(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stroke_replay");
+    group.sample_size(10);
+    // The stress test also covers 8192, which is too slow for repeated sampling
+    for canvas_size in [1024, 4096] {
+        let points = recorded_stroke(canvas_size);
+        group.throughput(Throughput::Elements(points.len() as u64));
+        for diameter in BRUSH_DIAMETERS {
+            let mut replay = StrokeReplay::new(canvas_size, diameter, 4);
+            replay.paint(&points[..2]);
+            replay.composite();
+            group.bench_with_input(
+                BenchmarkId::new(format!("{canvas_size}px"), diameter),
+                &points,
+                |b, points| {
+                    b.iter(|| {
+                        replay.paint(points);
+                        replay.composite()
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_stroke_replay);
+criterion_main!(benches);
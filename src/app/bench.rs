@@ -0,0 +1,142 @@
+//! Headless stress test behind `--bench`: replays the same scripted strokes with several brush
+//! sizes on several canvas sizes and prints the `utils::profiler` timings for each run.
+//!
+//! The criterion benches in `benches/stroke_bench.rs` drive the same [`StrokeReplay`], so both
+//! measure identical work.
+use eframe::egui::{Color32, ColorImage};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::app::render_helper::with_gutter;
+use crate::app::state::DEFAULT_TILE_SIZE;
+use crate::brush_engine::brush::Brush;
+use crate::brush_engine::engine::{BrushEngine, StrokeTarget};
+use crate::brush_engine::stroke::DabEngine;
+use crate::canvas::canvas::Canvas;
+use crate::canvas::history::UndoAction;
+use crate::utils::profiler;
+use crate::utils::vector::Vec2;
+
+pub const CANVAS_SIZES: [usize; 3] = [1024, 4096, 8192];
+pub const BRUSH_DIAMETERS: [f32; 4] = [8.0, 32.0, 128.0, 512.0];
+/// Pointer samples per recorded stroke.
+const STROKE_SAMPLES: usize = 1500;
+
+/// A looping scribble across the middle of a square canvas, sampled like pointer events.
+/// Deterministic so runs can be compared.
+pub fn recorded_stroke(canvas_size: usize) -> Vec<Vec2> {
+    let center = canvas_size as f32 * 0.5;
+    let radius = canvas_size as f32 * 0.4;
+    (0..STROKE_SAMPLES)
+        .map(|i| {
+            let t = i as f32 / STROKE_SAMPLES as f32 * std::f32::consts::TAU;
+            Vec2::new(center + radius * (3.0 * t).sin(), center + radius * (2.0 * t + 0.5).sin())
+        })
+        .collect()
+}
+
+/// A canvas and brush that strokes can be replayed onto without the UI.
+pub struct StrokeReplay {
+    pool: ThreadPool,
+    pub canvas: Canvas,
+    pub brush: Brush,
+    modified_tiles: HashSet<(usize, usize)>,
+}
+
+impl StrokeReplay {
+    pub fn new(canvas_size: usize, diameter: f32, threads: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to build thread pool");
+        Self {
+            pool,
+            canvas: Canvas::new(canvas_size, canvas_size, Color32::WHITE, DEFAULT_TILE_SIZE),
+            brush: Brush::new(diameter, 50.0, Color32::from_rgb(20, 40, 90), 10.0),
+            modified_tiles: HashSet::new(),
+        }
+    }
+
+    /// Paint one stroke through `points` with undo snapshots, as the brush tool does.
+    pub fn paint(&mut self, points: &[Vec2]) {
+        let mut undo_action = UndoAction::new("Bench");
+        let mut target = StrokeTarget {
+            pool: &self.pool,
+            canvas: &self.canvas,
+            selection: None,
+            undo_action: &mut undo_action,
+            modified_tiles: &mut self.modified_tiles,
+        };
+        let mut stroke = DabEngine::new();
+        stroke.begin_stroke();
+        for &point in points {
+            stroke.add_sample(&mut target, &mut self.brush, point);
+        }
+        stroke.end_stroke(&mut target, &mut self.brush);
+    }
+
+    /// Composite every tile touched since the last call and pack it with its atlas gutter,
+    /// which is the CPU side of a texture upload. Returns the number of tiles.
+    pub fn composite(&mut self) -> usize {
+        let (width, height) = (self.canvas.width(), self.canvas.height());
+        let tiles: Vec<(usize, usize)> = self.modified_tiles.drain().collect();
+        let tile_size = self.canvas.tile_size();
+        for &(tx, ty) in &tiles {
+            let (x, y) = (tx * tile_size, ty * tile_size);
+            let (w, h) = (tile_size.min(width - x), tile_size.min(height - y));
+            let mut img = ColorImage::new([w, h], Color32::TRANSPARENT);
+            self.canvas.write_region_to_color_image(x, y, w, h, &mut img, 1);
+            std::hint::black_box(with_gutter(&img));
+        }
+        tiles.len()
+    }
+}
+
+/// Run every canvas size and brush diameter once and print the timings.
+/// GPU uploads need a window, so "pack" covers only the CPU work before them.
+pub fn run() {
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+    println!("Rusty Painter stress test ({threads} threads, {STROKE_SAMPLES} samples per stroke)");
+    println!(
+        "{:>6} {:>6} {:>8} {:>10} {:>12} {:>10} {:>10} {:>8} {:>12} {:>10}",
+        "canvas", "brush", "dabs", "paint", "dabs/s", "batch avg", "batch max", "tiles", "composite", "pack"
+    );
+    profiler::set_enabled(true);
+    for canvas_size in CANVAS_SIZES {
+        let points = recorded_stroke(canvas_size);
+        for diameter in BRUSH_DIAMETERS {
+            let mut replay = StrokeReplay::new(canvas_size, diameter, threads);
+            // Warm the dab mask cache so the first dab's mask build is not counted
+            replay.paint(&points[..2]);
+            replay.composite();
+            profiler::take_report();
+
+            let start = Instant::now();
+            replay.paint(&points);
+            let paint = start.elapsed();
+            let tiles = replay.composite();
+
+            let report = profiler::take_report();
+            let total = |name: &str| report.iter().find(|r| r.name == name).map_or(Duration::ZERO, |r| r.total);
+            let dabs = report.iter().find(|r| r.name == "dab").map_or(0, |r| r.count);
+            // Soft dabs are painted per input segment, so that is where their time goes
+            let batch = report.iter().find(|r| r.name == "dab batch");
+            let rate = dabs as f64 / paint.as_secs_f64().max(1e-9);
+            println!(
+                "{:>6} {:>6} {:>8} {:>10} {:>12.0} {:>10} {:>10} {:>8} {:>12} {:>10}",
+                canvas_size,
+                diameter,
+                dabs,
+                format!("{:.1?}", paint),
+                rate,
+                format!("{:.1?}", batch.map_or(Duration::ZERO, |r| r.avg)),
+                format!("{:.1?}", batch.map_or(Duration::ZERO, |r| r.max)),
+                tiles,
+                format!("{:.1?}", total("region_to_color_image")),
+                format!("{:.1?}", total("texture_pack")),
+            );
+        }
+    }
+    profiler::set_enabled(false);
+}
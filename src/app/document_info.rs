@@ -0,0 +1,210 @@
+//! Title, author, description, timestamps and painting statistics of the open document.
+//!
+//! There is no project file, so the properties travel inside the images: PNG exports (and the
+//! session file) get `tEXt`/`iTXt` chunks with the standard keywords, JPEG exports an EXIF block.
+//! Opening a PNG reads the chunks back.
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SOFTWARE: &str = "Rusty Painter";
+/// Non-standard keyword; PNG only defines "Creation Time".
+const MODIFICATION_TIME: &str = "Modification Time";
+/// Non-standard keyword holding [`DocumentStats`].
+const STATISTICS: &str = "Painting Statistics";
+
+/// How much work went into a document, for process breakdowns.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocumentStats {
+    /// Seconds spent with a stroke in progress.
+    pub painting_time: f64,
+    /// Strokes that changed pixels.
+    pub strokes: u64,
+    /// Undo steps taken.
+    pub undos: u64,
+    /// Dabs stamped by the strokes that were kept.
+    pub dabs: u64,
+}
+
+impl DocumentStats {
+    /// `strokes=N undos=N dabs=N seconds=N`, the form kept in the PNG text chunk.
+    fn to_text(&self) -> String {
+        format!("strokes={} undos={} dabs={} seconds={}", self.strokes, self.undos, self.dabs, self.painting_time.round() as u64)
+    }
+
+    /// Inverse of [`DocumentStats::to_text`]; unknown or malformed fields are left at zero.
+    fn parse(text: &str) -> Self {
+        let mut stats = Self::default();
+        for (key, value) in text.split_whitespace().filter_map(|field| field.split_once('=')) {
+            let Ok(value) = value.parse::<u64>() else {
+                continue;
+            };
+            match key {
+                "strokes" => stats.strokes = value,
+                "undos" => stats.undos = value,
+                "dabs" => stats.dabs = value,
+                "seconds" => stats.painting_time = value as f64,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocumentInfo {
+    pub title: String,
+    pub author: String,
+    pub description: String,
+    /// Unix seconds.
+    pub created: u64,
+    /// Unix seconds of the last edit.
+    pub modified: u64,
+    pub stats: DocumentStats,
+}
+
+impl DocumentInfo {
+    /// Empty properties for a document created now.
+    pub fn new() -> Self {
+        let now = now();
+        Self { created: now, modified: now, ..Self::default() }
+    }
+
+    /// Record an edit.
+    pub fn touch(&mut self) {
+        self.modified = now();
+    }
+
+    /// PNG text chunks, skipping empty fields.
+    pub fn text_chunks(&self) -> Vec<(&'static str, String)> {
+        let mut chunks: Vec<(&'static str, String)> = [
+            ("Title", self.title.clone()),
+            ("Author", self.author.clone()),
+            ("Description", self.description.clone()),
+        ]
+        .into_iter()
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+        chunks.push(("Creation Time", format_time(self.created)));
+        chunks.push((MODIFICATION_TIME, format_time(self.modified)));
+        chunks.push((STATISTICS, self.stats.to_text()));
+        chunks.push(("Software", SOFTWARE.to_string()));
+        chunks
+    }
+
+    /// Properties stored in a PNG's text chunks, or `None` when it has none of ours.
+    pub fn read_png(path: &Path) -> Option<Self> {
+        let file = std::fs::File::open(path).ok()?;
+        let reader = png::Decoder::new(std::io::BufReader::new(file)).read_info().ok()?;
+        let info = reader.info();
+        let latin1 = info.uncompressed_latin1_text.iter().map(|t| (t.keyword.clone(), t.text.clone()));
+        let utf8 = info
+            .utf8_text
+            .iter()
+            .filter_map(|t| Some((t.keyword.clone(), t.get_text().ok()?)));
+        let mut doc = Self::new();
+        let mut found = false;
+        for (keyword, text) in latin1.chain(utf8) {
+            match keyword.as_str() {
+                "Title" => doc.title = text,
+                "Author" => doc.author = text,
+                "Description" => doc.description = text,
+                "Creation Time" => doc.created = parse_time(&text).unwrap_or(doc.created),
+                MODIFICATION_TIME => doc.modified = parse_time(&text).unwrap_or(doc.modified),
+                STATISTICS => doc.stats = DocumentStats::parse(&text),
+                _ => continue,
+            }
+            found = true;
+        }
+        found.then_some(doc)
+    }
+
+    /// A little-endian TIFF block with DocumentName, ImageDescription, Artist, DateTime and
+    /// Software, as JPEG encoders expect after the `Exif\0\0` marker.
+    pub fn exif(&self) -> Vec<u8> {
+        let date = format_time(self.modified).replace('-', ":");
+        let date = date.trim_end_matches(" UTC").to_string();
+        // Tags must be in ascending order
+        let entries: Vec<(u16, String)> = [
+            (0x010D, self.title.clone()),
+            (0x010E, self.description.clone()),
+            (0x0131, SOFTWARE.to_string()),
+            (0x0132, date),
+            (0x013B, self.author.clone()),
+        ]
+        .into_iter()
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+
+        let ifd_size = 2 + entries.len() * 12 + 4;
+        let mut data_offset = 8 + ifd_size;
+        let mut out = b"II*\0".to_vec();
+        out.extend_from_slice(&8u32.to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        let mut data = Vec::new();
+        for (tag, text) in &entries {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(0);
+            out.extend_from_slice(&tag.to_le_bytes());
+            // ASCII
+            out.extend_from_slice(&2u16.to_le_bytes());
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            if bytes.len() <= 4 {
+                bytes.resize(4, 0);
+                out.extend_from_slice(&bytes);
+            } else {
+                out.extend_from_slice(&(data_offset as u32).to_le_bytes());
+                data_offset += bytes.len();
+                data.extend_from_slice(&bytes);
+            }
+        }
+        // No next IFD
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&data);
+        out
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn format_time(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+/// `H h MM min SS s`, leaving out the hours under an hour.
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.max(0.0).round() as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 { format!("{h} h {m:02} min {s:02} s") } else { format!("{m} min {s:02} s") }
+}
+
+/// Inverse of [`format_time`].
+fn parse_time(text: &str) -> Option<u64> {
+    let (date, time) = text.trim().trim_end_matches(" UTC").split_once(' ')?;
+    let mut d = date.split('-').map(|n| n.parse::<i64>().ok());
+    let (year, month, day) = (d.next()??, d.next()??, d.next()??);
+    let mut t = time.split(':').map(|n| n.parse::<i64>().ok());
+    let (h, m, s) = (t.next()??, t.next()??, t.next()??);
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u64::try_from(days * 86_400 + h * 3600 + m * 60 + s).ok()
+}
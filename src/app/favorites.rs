@@ -0,0 +1,34 @@
+//! Colors pinned to the favorites bar, stored next to the brush tips.
+//!
+//! One color per line as `#RRGGBBAA` (unmultiplied). Brushes on the bar are the
+//! starred presets, which `preset_meta` already keeps.
+use eframe::egui::Color32;
+use std::path::Path;
+
+pub const FILE_NAME: &str = "favorite_colors.meta";
+
+pub fn load(dir: &Path) -> Vec<Color32> {
+    let Ok(contents) = std::fs::read_to_string(dir.join(FILE_NAME)) else {
+        return Vec::new();
+    };
+    contents.lines().filter_map(|line| parse_hex(line.trim())).collect()
+}
+
+pub fn save(dir: &Path, colors: &[Color32]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    for color in colors {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        contents.push_str(&format!("#{r:02X}{g:02X}{b:02X}{a:02X}\n"));
+    }
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(FILE_NAME), contents)
+}
+
+fn parse_hex(line: &str) -> Option<Color32> {
+    let hex = line.strip_prefix('#')?;
+    if hex.len() != 8 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color32::from_rgba_unmultiplied(channel(0)?, channel(2)?, channel(4)?, channel(6)?))
+}
@@ -1,9 +1,55 @@
 use crate::PainterApp;
-use crate::app::tools::Tool;
+use crate::app::navigation::NavAction;
+use crate::app::tools::{Tool, ToolKey};
+use crate::app::keybindings::Command;
+use crate::app::state::ROTATION_SNAP_DEGREES;
+use crate::brush_engine::brush_options::BlendMode;
 use crate::tablet::TabletPhase;
-use crate::selection::transform::TransformState;
+use crate::selection::{DragModifiers, SelectionType};
+use crate::selection::transform::{Affine, TransformInfo, TransformState};
 use eframe::egui;
 
+/// A tool key released sooner than this after its press is a tap, which keeps the new tool.
+const TOOL_TAP_SECONDS: f64 = 0.3;
+/// How many earlier tools the switcher remembers.
+const TOOL_HISTORY: usize = 16;
+
+/// A tool as the tool keys see it: the eraser key changes the brush blend mode, not the tool.
+#[derive(Clone, Copy)]
+struct ToolState {
+    tool: Tool,
+    blend_mode: BlendMode,
+}
+
+/// A tool key that is down.
+struct HeldToolKey {
+    key: egui::Key,
+    pressed_at: f64,
+    /// False when the key named the tool already in use; a tap then switches back instead.
+    switched: bool,
+    /// The canvas was used while the key was down, so releasing it always switches back.
+    used: bool,
+}
+
+/// Press and release tracking of the tool keys, with the tools they switched away from.
+#[derive(Default)]
+pub struct ToolSwitcher {
+    /// Earlier tools, most recent last.
+    previous: Vec<ToolState>,
+    held: Option<HeldToolKey>,
+    /// A held key came up mid-stroke; switch back once the stroke is done.
+    switch_back_pending: bool,
+    /// Tool key pressed this frame, from the keybindings.
+    pressed: Option<ToolKey>,
+}
+
+impl ToolSwitcher {
+    /// Note a tool key press; the input handler switches tools on it this frame.
+    pub(crate) fn press(&mut self, target: ToolKey) {
+        self.pressed = Some(target);
+    }
+}
+
 pub fn handle_input(
     app: &mut PainterApp,
     ctx: &egui::Context,
@@ -11,14 +57,56 @@ pub fn handle_input(
     origin: egui::Pos2,
     canvas_center: egui::Pos2,
 ) {
+    handle_tool_keys(app, ctx);
+
     if let Some(tablet) = &mut app.tablet {
         let scale = ctx.input(|i| i.pixels_per_point());
         for sample in tablet.poll(scale) {
+            // Pen-only mode ignores fingers, pucks and the emulated mouse entirely
+            if app.pen_only && !sample.is_pen {
+                continue;
+            }
             let pos = egui::Pos2::new(sample.pos[0], sample.pos[1]);
             let (canvas_pos, inside) = app.screen_to_canvas(pos, origin, canvas_center);
-            if !inside {
+            match sample.phase {
+                TabletPhase::Hover => {
+                    app.pen_hover = Some(pos);
+                    let warm = (inside && app.active_tool == Tool::Brush).then_some(canvas_pos);
+                    app.prewarm_tiles_around(warm);
+                    ctx.request_repaint();
+                    continue;
+                }
+                TabletPhase::Leave => {
+                    app.pen_hover = None;
+                    app.prewarm_tiles_around(None);
+                    ctx.request_repaint();
+                    continue;
+                }
+                _ => {}
+            }
+            // The wrap preview shows pixels away from where they live, so it is look-only
+            if app.view_settings.wrap_preview {
                 continue;
             }
+            if sample.phase == TabletPhase::Down
+                && (crate::ui::rulers::covers(app, response.rect, pos) || crate::ui::filters::covers(app, pos))
+            {
+                continue;
+            }
+            // Keep feeding an active stroke off-canvas so re-entry follows the real path
+            let continues_stroke = sample.phase == TabletPhase::Move
+                && app.stroke.is_some()
+                && !app.clamp_strokes_to_canvas;
+            if !inside && !continues_stroke {
+                continue;
+            }
+            let canvas_pos = if continues_stroke {
+                app.stroke_position(pos, origin, canvas_center)
+            } else {
+                canvas_pos
+            };
+            app.brush.pressure = sample.pressure;
+            app.brush.sample_time = Some(sample.time);
             if sample.phase == TabletPhase::Down {
                 match app.active_tool {
                     Tool::Brush => app.start_stroke(canvas_pos),
@@ -26,39 +114,36 @@ pub fn handle_input(
                     Tool::Transform(ref mut info) => {
                         info.start_pos = Some(canvas_pos);
                     }
+                    Tool::EditPath => app.begin_path_edit(canvas_pos),
+                    Tool::Move => app.begin_layer_move(canvas_pos),
+                    Tool::Eyedropper => app.pick_color(canvas_pos),
                 }
             } else if sample.phase == TabletPhase::Move {
                 match app.active_tool {
                     Tool::Brush => {
-                        if let Some(stroke) = &mut app.stroke {
+                        if app.stroke.is_some() {
                             let base_diam = app.brush.brush_options.diameter;
                             app.brush.brush_options.diameter = (base_diam * sample.pressure).max(1.0);
-                            let prev = stroke.last_pos.unwrap_or(canvas_pos);
-                            stroke.add_point(
-                                &app.pool,
-                                &app.canvas,
-                                &mut app.brush,
-                                if app.selection_manager.has_selection() { Some(&app.selection_manager) } else { None },
-                                canvas_pos,
-                                app.current_undo_action.as_mut().unwrap(),
-                                &mut app.modified_tiles,
-                            );
-                            app.mark_segment_dirty(prev, canvas_pos, app.brush.brush_options.diameter / 2.0);
+                            app.continue_stroke(canvas_pos);
                             app.brush.brush_options.diameter = base_diam;
                         } else {
                             app.start_stroke(canvas_pos);
                         }
                     }
                     Tool::Select(_) => {
-                        app.selection_manager.update_selection(canvas_pos);
+                        app.selection_manager.update_selection(canvas_pos, drag_modifiers(ctx));
                     }
                     Tool::Transform(ref mut info) => {
                         if let Some(start) = info.start_pos {
-                             let delta = canvas_pos - start;
-                             info.offset = info.offset + delta;
-                             info.start_pos = Some(canvas_pos);
+                             // Keep what snapping held back for the next sample
+                             let moved = app.selection_manager.pixel_snap.snap_point(info.offset + (canvas_pos - start)) - info.offset;
+                             info.offset = info.offset + moved;
+                             info.start_pos = Some(start + moved);
                         }
                     }
+                    Tool::EditPath => app.drag_path_point(canvas_pos),
+                    Tool::Move => app.drag_layer_move(canvas_pos),
+                    Tool::Eyedropper => app.pick_color(canvas_pos),
                 }
             } else if sample.phase == TabletPhase::Up {
                 let mut transform_to_apply = None;
@@ -72,72 +157,74 @@ pub fn handle_input(
                             info.offset = crate::utils::vector::Vec2::new(0.0, 0.0);
                         }
                     }
+                    Tool::EditPath => app.end_path_edit(),
+                    Tool::Move => app.end_layer_move(),
+                    Tool::Eyedropper => {}
                 }
                 if let Some(offset) = transform_to_apply {
-                     let mut action = crate::canvas::history::UndoAction { 
-                         tiles: Vec::new(),
-                         selection: Some(app.selection_manager.current_shape.clone()),
-                         transform: None,
-                     };
-                     app.canvas.apply_transform(offset, 0.0, crate::utils::vector::Vec2::new(1.0, 1.0), crate::utils::vector::Vec2::new(0.0, 0.0), if app.selection_manager.has_selection() { Some(&app.selection_manager) } else { None }, Some(&mut action));
+                     let mut action = crate::canvas::history::UndoAction { selection: Some(app.selection_manager.shape().cloned()), ..crate::canvas::history::UndoAction::new("Move") };
+                     let affine = Affine::translation(offset);
+                     app.canvas.apply_transform(affine, app.transform_interpolation, if app.selection_manager.has_selection() { Some(&app.selection_manager) } else { None }, Some(&mut action));
                      if !action.tiles.is_empty() {
                          if let Some(history) = app.histories.get_mut(app.canvas.active_layer_idx) {
                              history.push_action(action);
                          }
+                         app.mark_untracked_change("transform");
                      }
-                     app.mark_all_tiles_dirty();
-                     app.selection_manager.apply_transform(offset, 0.0, crate::utils::vector::Vec2::new(1.0, 1.0), crate::utils::vector::Vec2::new(0.0, 0.0));
+                     app.selection_manager.apply_transform(affine);
                 }
             }
         }
     }
 
     let events = ctx.input(|i| i.events.clone());
+    if app.tablet.is_none() {
+        app.pointer_pressure.update(&events);
+    }
+    app.finger_touches.update(&events);
+    // A palm or mouse next to the pen must not draw; the stylus paints through the tablet path
+    let pen_near = app.tablet.as_ref().is_some_and(|t| t.pen_in_proximity());
+    let reject_pointer = app.pen_only && (pen_near || app.finger_touches.any_down());
+
+    // Keys belong to a panel widget reached with Tab, not to the canvas
+    let panel_focused = ctx.memory(|m| m.focused().is_some_and(|id| id != response.id));
 
     for event in events {
+        if panel_focused && matches!(event, egui::Event::Key { .. }) {
+            continue;
+        }
         match event {
             egui::Event::PointerButton {
                 pos,
                 button,
                 pressed,
-                ..
+                modifiers,
             } => {
+                if navigate(app, ctx, button, pressed, modifiers, response.hovered()) {
+                    continue;
+                }
                 let canvas_pos = app.screen_to_canvas(pos, origin, canvas_center);
                 match button {
-                    egui::PointerButton::Primary => {
-                        app.is_primary_down = pressed;
-                        let (space_down, secondary_down) = ctx.input(|i| {
-                            (
-                                i.key_down(egui::Key::Space),
-                                i.pointer.button_down(egui::PointerButton::Secondary),
-                            )
-                        });
-                        if pressed && space_down {
+                    egui::PointerButton::Primary if pressed && reject_pointer => {
+                        app.pointer_rejected = true;
+                        // Fingers still pan
+                        if app.finger_touches.any_down() && response.hovered() {
                             app.is_panning = true;
                         }
-                        if !pressed && !secondary_down {
-                            app.is_panning = false;
-                        }
-
-                        if pressed && !app.is_panning && response.hovered() {
-                            if canvas_pos.1 {
-                                if let Tool::Transform(_) = app.active_tool {
-                                    if app.selection_manager.has_selection() && app.floating_layer_idx.is_none() {
-                                        if let Some(idx) = app.canvas.float_selection(&app.selection_manager) {
-                                            app.floating_layer_idx = Some(idx);
-                                            
-                                            // Capture original pixels
-                                            app.floating_buffer = Some(app.canvas.capture_layer_pixels(idx));
-
-                                            // Sync app state with new layer
-                                            app.histories.push(crate::canvas::history::History::new());
-                                            app.layer_caches.push(std::collections::HashMap::new());
-                                            app.layer_cache_dirty.push(std::collections::HashSet::new());
-                                            app.layer_ui_colors.push(eframe::egui::Color32::from_gray(40));
+                    }
+                    egui::PointerButton::Primary if !pressed && app.pointer_rejected => {
+                        app.pointer_rejected = false;
+                        app.is_panning = false;
+                    }
+                    egui::PointerButton::Primary => {
+                        app.is_primary_down = pressed;
 
-                                            app.mark_all_tiles_dirty();
-                                        }
-                                    }
+                        if pressed && !app.is_panning && response.hovered() && !app.view_settings.wrap_preview {
+                            // Transform handles can sit outside the canvas, and moved layers too
+                            let is_transform = matches!(app.active_tool, Tool::Transform(_));
+                            if canvas_pos.1 || is_transform || app.active_tool == Tool::Move {
+                                if is_transform && app.floating_layer_idx.is_none() {
+                                    app.float_for_transform();
                                 }
 
                                 match app.active_tool {
@@ -145,10 +232,24 @@ pub fn handle_input(
                                     Tool::Select(t) => {
                                         app.selection_manager.start_selection(canvas_pos.0, t)
                                     }
-                                    Tool::Transform(ref mut info) => {
-                                        info.start_pos = Some(canvas_pos.0);
-                                        info.state = info.hit_test(canvas_pos.0, app.zoom);
+                                    Tool::Transform(mut info) => {
+                                        if let Some(session) = app.transform_session.as_mut() {
+                                            session.drag_from = Some(TransformInfo { start_pos: None, state: TransformState::None, ..info });
+                                        }
+                                        let unclamped = app.screen_to_canvas_unclamped(pos, origin, canvas_center);
+                                        info.start_pos = Some(unclamped);
+                                        info.state = info.hit_test(pos, unclamped, |p| app.canvas_to_screen(p, origin, canvas_center));
+                                        app.active_tool = Tool::Transform(info);
+                                    }
+                                    Tool::EditPath => {
+                                        let unclamped = app.screen_to_canvas_unclamped(pos, origin, canvas_center);
+                                        app.begin_path_edit(unclamped);
+                                    }
+                                    Tool::Move => {
+                                        let unclamped = app.screen_to_canvas_unclamped(pos, origin, canvas_center);
+                                        app.begin_layer_move(unclamped);
                                     }
+                                    Tool::Eyedropper => app.pick_color(canvas_pos.0),
                                 }
                             }
                         } else if !pressed {
@@ -160,134 +261,113 @@ pub fn handle_input(
                                     info.start_pos = None;
                                     info.state = TransformState::None;
                                     if info.offset.x != 0.0 || info.offset.y != 0.0 || info.rotation != 0.0 || info.scale.x != 1.0 || info.scale.y != 1.0 {
-                                        let center = if let Some(b) = info.bounds { crate::utils::vector::Vec2::new(b.center().x, b.center().y) } else { crate::utils::vector::Vec2::new(0.0, 0.0) };
-                                        
                                         // If we have a floating buffer, we use preview_transform instead of apply_transform
                                         // And we DO NOT reset the info
-                                        if let Some(buffer) = &app.floating_buffer {
-                                            if let Some(idx) = app.floating_layer_idx {
-                                                app.canvas.preview_transform(idx, buffer, info.offset, info.rotation, info.scale, center);
-                                                app.mark_all_tiles_dirty();
-                                                // Do not reset info
-                                            }
+                                        if app.floating_buffer.is_some() && app.floating_layer_idx.is_some() {
+                                            // Final render of the live preview; do not reset info
+                                            app.transform_preview_pending = true;
                                         } else {
                                             // Fallback for non-floating transforms (if any)
                                             let captured_info = *info;
-                                            transform_to_apply = Some((info.offset, info.rotation, info.scale, center, captured_info));
+                                            transform_to_apply = Some((info.affine(), captured_info));
                                             
                                             info.offset = crate::utils::vector::Vec2::new(0.0, 0.0);
                                             info.rotation = 0.0;
                                             info.scale = crate::utils::vector::Vec2::new(1.0, 1.0);
                                             info.bounds = None;
+                                            info.pivot = None;
                                         }
                                     }
                                 }
+                                Tool::EditPath => app.end_path_edit(),
+                                Tool::Move => app.end_layer_move(),
+                                Tool::Eyedropper => {}
                             }
-                            if let Some((offset, rotation, scale, center, captured_info)) = transform_to_apply {
-                                 let mut action = crate::canvas::history::UndoAction { 
-                                     tiles: Vec::new(),
-                                     selection: Some(app.selection_manager.current_shape.clone()),
+                            app.record_transform_step();
+                            if let Some((affine, captured_info)) = transform_to_apply {
+                                 let mut action = crate::canvas::history::UndoAction {
+                                     selection: Some(app.selection_manager.shape().cloned()),
                                      transform: Some(captured_info),
+                                     ..crate::canvas::history::UndoAction::new("Transform")
                                  };
-                                 app.canvas.apply_transform(offset, rotation, scale, center, if app.selection_manager.has_selection() { Some(&app.selection_manager) } else { None }, Some(&mut action));
+                                 app.canvas.apply_transform(affine, app.transform_interpolation, if app.selection_manager.has_selection() { Some(&app.selection_manager) } else { None }, Some(&mut action));
                                  if !action.tiles.is_empty() {
                                      if let Some(history) = app.histories.get_mut(app.canvas.active_layer_idx) {
                                          history.push_action(action);
                                      }
+                                     app.mark_untracked_change("transform");
                                  }
-                                 app.mark_all_tiles_dirty();
-                                 app.selection_manager.apply_transform(offset, rotation, scale, center);
+                                 app.selection_manager.apply_transform(affine);
                             }
                         }
                     }
-                    egui::PointerButton::Secondary => {
-                        if pressed && response.hovered() {
-                            app.is_panning = true;
-                        }
-                        if !pressed {
-                            app.is_panning = false;
-                        }
-                    }
-                    egui::PointerButton::Middle => {
-                        if pressed && response.hovered() {
-                            app.is_rotating = true;
-                        }
-                        if !pressed {
-                            app.is_rotating = false;
-                        }
-                    }
                     _ => {}
                 }
             }
 
-            egui::Event::Key { key, pressed, .. } => {
-                if pressed && key == egui::Key::Enter {
-                     if let Some(idx) = app.floating_layer_idx {
-                         // Apply final transform if needed (though preview should have done it)
-                         
-                         let mut action = crate::canvas::history::UndoAction { 
-                             tiles: Vec::new(),
-                             selection: Some(app.selection_manager.current_shape.clone()),
-                             transform: None, // We are committing, so transform is reset
-                         };
-                         
-                         
-                         app.canvas.merge_layer_down(idx);
-                         app.floating_layer_idx = None;
-                         app.floating_buffer = None; // Clear buffer
-                         app.selection_manager.clear_selection();
-                         
-                         // Reset transform tool
-                         if let Tool::Transform(ref mut info) = app.active_tool {
-                             *info = crate::selection::transform::TransformInfo::default();
-                         }
-                         
-                         // Sync app state with removed layer
-                         if idx < app.histories.len() {
-                             app.histories.remove(idx);
-                             app.layer_caches.remove(idx);
-                             app.layer_cache_dirty.remove(idx);
-                             app.layer_ui_colors.remove(idx);
-                         }
+            // Enter and Escape go through the keybindings, see `PainterApp::confirm` and `PainterApp::cancel`
+            egui::Event::Key { key: egui::Key::Backspace, pressed: true, .. } if app.selection_manager.is_placing_polygon() => {
+                app.selection_manager.remove_last_polygon_vertex();
+                ctx.request_repaint();
+            }
 
-                         app.mark_all_tiles_dirty();
-                     }
-                }
+            egui::Event::Key { key, pressed: true, modifiers, .. }
+                if matches!(app.active_tool, Tool::Select(_))
+                    && app.selection_manager.has_selection()
+                    && matches!(key, egui::Key::ArrowLeft | egui::Key::ArrowRight | egui::Key::ArrowUp | egui::Key::ArrowDown) =>
+            {
+                // Arrow keys nudge the selection outline, Shift for bigger steps
+                let step = if modifiers.shift { 10.0 } else { 1.0 };
+                let (dx, dy) = match key {
+                    egui::Key::ArrowLeft => (-step, 0.0),
+                    egui::Key::ArrowRight => (step, 0.0),
+                    egui::Key::ArrowUp => (0.0, -step),
+                    _ => (0.0, step),
+                };
+                app.selection_manager.translate(crate::utils::vector::Vec2::new(dx, dy));
+                ctx.request_repaint();
             }
 
             egui::Event::PointerMoved(pos) => {
                 let delta = ctx.input(|i| i.pointer.delta());
                 if app.is_rotating {
-                    app.rotation += delta.x * -0.005;
+                    app.rotation_drag += delta.x * -0.005;
+                    app.rotation = if app.view_settings.snap_rotation {
+                        let step = ROTATION_SNAP_DEGREES.to_radians();
+                        (app.rotation_drag / step).round() * step
+                    } else {
+                        app.rotation_drag
+                    };
                     ctx.request_repaint();
                 } else if app.is_panning {
                     app.offset.x += delta.x;
                     app.offset.y += delta.y;
+                    app.clamp_view_offset(response.rect);
+                    ctx.request_repaint();
+                } else if app.is_zooming {
+                    app.zoom = (app.zoom * (1.0 - delta.y * 0.005)).clamp(0.1, 20.0);
+                    app.clamp_view_offset(response.rect);
                     ctx.request_repaint();
                 } else {
                     let (clamped, is_inside) = app.screen_to_canvas(pos, origin, canvas_center);
+                    let unclamped = app.screen_to_canvas_unclamped(pos, origin, canvas_center);
                     match app.active_tool {
+                        Tool::Brush if reject_pointer => {}
                         Tool::Brush => {
                             if app.is_drawing {
-                                if let Some(stroke) = &mut app.stroke {
-                                    let prev = stroke.last_pos.unwrap_or(clamped);
-                                    stroke.add_point(
-                                        &app.pool,
-                                        &app.canvas,
-                                        &mut app.brush,
-                                        if app.selection_manager.has_selection() { Some(&app.selection_manager) } else { None },
-                                        clamped,
-                                        app.current_undo_action.as_mut().unwrap(),
-                                        &mut app.modified_tiles,
-                                    );
-                                    app.mark_segment_dirty(
-                                        prev,
-                                        clamped,
-                                        app.brush.brush_options.diameter / 2.0,
-                                    );
+                                let stroke_pos = app.stroke_position(pos, origin, canvas_center);
+                                let base_diam = app.brush.brush_options.diameter;
+                                // Native pen pressure stands in when the tablet backend is missing
+                                if let (None, Some(pressure)) = (&app.tablet, app.pointer_pressure.pressure()) {
+                                    app.brush.brush_options.diameter = (base_diam * pressure).max(1.0);
+                                    app.brush.pressure = pressure;
+                                    app.brush.sample_time = Some(ctx.input(|i| i.time));
                                 }
+                                app.continue_stroke(stroke_pos);
+                                app.brush.brush_options.diameter = base_diam;
                             } else if app.is_primary_down
                                 && !app.is_panning
+                                && app.dragging_guide.is_none()
                                 && response.hovered()
                             {
                                 if is_inside {
@@ -297,28 +377,34 @@ pub fn handle_input(
                         }
                         Tool::Select(_) => {
                             if app.selection_manager.is_dragging {
-                                app.selection_manager.update_selection(clamped);
+                                app.selection_manager.update_selection(clamped, drag_modifiers(ctx));
+                                ctx.request_repaint();
+                            } else if app.selection_manager.is_placing_polygon() {
+                                app.selection_manager.polygon_cursor = Some(clamped);
                                 ctx.request_repaint();
                             }
                         }
 
                         Tool::Transform(ref mut info) => {
                             if let Some(start) = info.start_pos {
-                                let current = clamped;
+                                // Unclamped so handles keep following the pointer past the canvas edge
+                                let current = unclamped;
                                 let delta = current - start;
-                                
+                                let mut next_start = current;
+
                                 match info.state {
                                     TransformState::Moving => {
-                                        info.offset = info.offset + delta;
+                                        // Keep what snapping held back for the next move
+                                        let moved = app.selection_manager.pixel_snap.snap_point(info.offset + delta) - info.offset;
+                                        info.offset = info.offset + moved;
+                                        next_start = start + moved;
                                     }
-                                    TransformState::Rotating => {
-                                        if let Some(bounds) = info.bounds {
-                                            let center = crate::utils::vector::Vec2::new(bounds.center().x, bounds.center().y) + info.offset;
-                                            let start_vec = start - center;
-                                            let current_vec = current - center;
-                                            let angle = current_vec.y.atan2(current_vec.x) - start_vec.y.atan2(start_vec.x);
-                                            info.rotation += angle;
-                                        }
+                                    TransformState::Rotating if info.bounds.is_some() => {
+                                        let center = info.center() + info.offset;
+                                        let start_vec = start - center;
+                                        let current_vec = current - center;
+                                        let angle = current_vec.y.atan2(current_vec.x) - start_vec.y.atan2(start_vec.x);
+                                        info.rotation += angle;
                                     }
                                     TransformState::Scaling(idx) => {
                                         if let Some(bounds) = info.bounds {
@@ -326,31 +412,46 @@ pub fn handle_input(
                                             let dx = delta.x * cos_r + delta.y * sin_r;
                                             let dy = -delta.x * sin_r + delta.y * cos_r;
                                             
-                                            let mut scale_delta = crate::utils::vector::Vec2::new(0.0, 0.0);
-                                            match idx {
-                                                0 => { scale_delta.x = -dx; scale_delta.y = -dy; }
-                                                1 => { scale_delta.y = -dy; }
-                                                2 => { scale_delta.x = dx; scale_delta.y = -dy; }
-                                                3 => { scale_delta.x = dx; }
-                                                4 => { scale_delta.x = dx; scale_delta.y = dy; }
-                                                5 => { scale_delta.y = dy; }
-                                                6 => { scale_delta.x = -dx; scale_delta.y = dy; }
-                                                7 => { scale_delta.x = -dx; }
-                                                _ => {}
-                                            }
-                                            
-                                            let w = bounds.width();
-                                            let h = bounds.height();
-                                            if w > 0.0 { info.scale.x += scale_delta.x / (w * 0.5); }
-                                            if h > 0.0 { info.scale.y += scale_delta.y / (h * 0.5); }
+                                            // Handle position relative to the pivot, in untransformed space
+                                            let handle = info.handle_anchor(idx).unwrap_or(bounds.center());
+                                            let center = info.center();
+                                            let hx = handle.x - center.x;
+                                            let hy = handle.y - center.y;
+
+                                            // Edge handles only scale along their own axis
+                                            if idx != 1 && idx != 5 && hx.abs() > 0.5 { info.scale.x += dx / hx; }
+                                            if idx != 3 && idx != 7 && hy.abs() > 0.5 { info.scale.y += dy / hy; }
                                         }
                                     }
+                                    TransformState::MovingPivot => {
+                                        info.move_pivot(current);
+                                    }
                                     _ => {}
                                 }
-                                info.start_pos = Some(current);
+                                info.start_pos = Some(next_start);
+                                if app.floating_layer_idx.is_some() && info.state != TransformState::MovingPivot {
+                                    app.transform_preview_pending = true;
+                                }
                                 ctx.request_repaint();
                             }
                         }
+                        Tool::EditPath => {
+                            if app.path_drag.is_some() {
+                                app.drag_path_point(unclamped);
+                                ctx.request_repaint();
+                            }
+                        }
+                        Tool::Move => {
+                            if app.layer_move.is_some() {
+                                app.drag_layer_move(unclamped);
+                                ctx.request_repaint();
+                            }
+                        }
+                        Tool::Eyedropper => {
+                            if app.is_primary_down && is_inside {
+                                app.pick_color(clamped);
+                            }
+                        }
                     }
                 }
             }
@@ -364,6 +465,7 @@ pub fn handle_input(
                     };
                     let zoom_factor = (1.0 - scroll * 0.1_f32).clamp(0.5_f32, 2.0_f32);
                     app.zoom = (app.zoom * zoom_factor).clamp(0.1, 20.0);
+                    app.clamp_view_offset(response.rect);
                     ctx.request_repaint();
                 }
             }
@@ -371,4 +473,164 @@ pub fn handle_input(
             _ => {}
         }
     }
+
+    if response.double_clicked() && app.selection_manager.is_placing_polygon() {
+        app.selection_manager.close_polygon();
+        ctx.request_repaint();
+    }
+
+    // Re-render the floating preview at most once per frame while dragging
+    if app.transform_preview_pending {
+        app.transform_preview_pending = false;
+        app.refresh_transform_preview();
+    }
+}
+
+/// Tool keys: a tap switches to the tool and stays there, holding the key switches only until
+/// it is released. Tapping the key of the tool in use goes back to the previous tool.
+fn handle_tool_keys(app: &mut PainterApp, ctx: &egui::Context) {
+    let busy = app.is_primary_down || app.stroke.is_some() || app.layer_move.is_some() || app.path_drag.is_some();
+    let switcher = &mut app.tool_switcher;
+    if let Some(held) = switcher.held.as_mut() {
+        held.used |= busy;
+    }
+    let pressed = switcher.pressed.take();
+    let (now, released) = ctx.input(|i| (i.time, switcher.held.as_ref().is_some_and(|held| !i.key_down(held.key))));
+
+    if released {
+        let held = switcher.held.take().expect("released implies a held key");
+        let tap = now - held.pressed_at < TOOL_TAP_SECONDS && !held.used;
+        if held.switched && !tap {
+            switcher.switch_back_pending = true;
+        } else if !held.switched && tap {
+            restore_previous_tool(app);
+        }
+        ctx.request_repaint();
+    }
+    if app.tool_switcher.switch_back_pending && !busy {
+        app.tool_switcher.switch_back_pending = false;
+        restore_previous_tool(app);
+    }
+
+    // Key repeats of the held key arrive as presses too
+    let Some(target) = pressed.filter(|_| !busy && app.tool_switcher.held.is_none()) else {
+        return;
+    };
+    let Some(key) = app.keybindings.key(Command::SwitchTool(target)) else {
+        return;
+    };
+    let current = ToolState { tool: app.active_tool, blend_mode: app.brush.brush_options.blend_mode };
+    let switched = !tool_key_active(current, target);
+    if switched {
+        let next = tool_key_state(current, target, &app.tool_switcher.previous);
+        let previous = &mut app.tool_switcher.previous;
+        previous.push(current);
+        if previous.len() > TOOL_HISTORY {
+            previous.remove(0);
+        }
+        set_tool_state(app, next);
+    }
+    app.tool_switcher.held = Some(HeldToolKey { key, pressed_at: now, switched, used: false });
+    ctx.request_repaint();
+}
+
+/// Whether `target` is what the user is already using.
+fn tool_key_active(current: ToolState, target: ToolKey) -> bool {
+    let erasing = current.blend_mode.is_eraser();
+    match target {
+        ToolKey::Brush => current.tool == Tool::Brush && !erasing,
+        ToolKey::Eraser => current.tool == Tool::Brush && erasing,
+        ToolKey::Select => matches!(current.tool, Tool::Select(_)),
+        ToolKey::Transform => matches!(current.tool, Tool::Transform(_)),
+        ToolKey::Path => current.tool == Tool::EditPath,
+        ToolKey::Move => current.tool == Tool::Move,
+        ToolKey::Eyedropper => current.tool == Tool::Eyedropper,
+    }
+}
+
+/// The state a tool key switches to from `current`. The brush gets back the blend mode it had
+/// before the eraser, and the select key the selection shape used last.
+fn tool_key_state(current: ToolState, target: ToolKey, previous: &[ToolState]) -> ToolState {
+    let tool = match target {
+        ToolKey::Brush | ToolKey::Eraser => Tool::Brush,
+        ToolKey::Select => {
+            let shape = previous.iter().rev().find_map(|s| match s.tool {
+                Tool::Select(t) => Some(t),
+                _ => None,
+            });
+            Tool::Select(shape.unwrap_or(SelectionType::Rectangle))
+        }
+        ToolKey::Transform => Tool::Transform(TransformInfo::default()),
+        ToolKey::Path => Tool::EditPath,
+        ToolKey::Move => Tool::Move,
+        ToolKey::Eyedropper => Tool::Eyedropper,
+    };
+    let blend_mode = match target {
+        ToolKey::Eraser if !current.blend_mode.is_eraser() => BlendMode::Eraser,
+        ToolKey::Brush if current.blend_mode.is_eraser() => previous
+            .iter()
+            .rev()
+            .map(|s| s.blend_mode)
+            .find(|m| !m.is_eraser())
+            .unwrap_or(BlendMode::Normal),
+        _ => current.blend_mode,
+    };
+    ToolState { tool, blend_mode }
+}
+
+fn set_tool_state(app: &mut PainterApp, state: ToolState) {
+    app.active_tool = state.tool;
+    if app.brush.brush_options.blend_mode != state.blend_mode {
+        app.brush.brush_options.blend_mode = state.blend_mode;
+        app.brush_preview.dirty = true;
+    }
+}
+
+fn restore_previous_tool(app: &mut PainterApp) {
+    if let Some(state) = app.tool_switcher.previous.pop() {
+        set_tool_state(app, state);
+    }
+}
+
+/// Start or end a pan, rotate or zoom drag from the navigation bindings. Returns whether the
+/// button event was used for that.
+fn navigate(
+    app: &mut PainterApp,
+    ctx: &egui::Context,
+    button: egui::PointerButton,
+    pressed: bool,
+    modifiers: egui::Modifiers,
+    hovered: bool,
+) -> bool {
+    if !pressed {
+        if app.nav_button != Some(button) {
+            return false;
+        }
+        app.nav_button = None;
+        app.is_panning = false;
+        app.is_rotating = false;
+        app.is_zooming = false;
+        return true;
+    }
+    if !hovered || app.nav_button.is_some() {
+        return false;
+    }
+    let Some(action) = app.navigation.action_for(button, modifiers, |key| ctx.input(|i| i.key_down(key))) else {
+        return false;
+    };
+    app.nav_button = Some(button);
+    match action {
+        NavAction::Pan => app.is_panning = true,
+        NavAction::Rotate => {
+            app.is_rotating = true;
+            app.rotation_drag = app.rotation;
+        }
+        NavAction::Zoom => app.is_zooming = true,
+    }
+    true
+}
+
+/// Shift squares up a new rectangle selection and Alt grows the shape from where the drag started.
+fn drag_modifiers(ctx: &egui::Context) -> DragModifiers {
+    ctx.input(|i| DragModifiers { constrain: i.modifiers.shift, from_center: i.modifiers.alt })
 }
@@ -0,0 +1,633 @@
+//! Crash-safe journal of recent actions, stored next to the brush tips.
+//!
+//! Each action is one line, handed to a writer thread that appends and flushes it right away so
+//! the file survives a crash without the UI waiting on the disk. Lines start with a Unix timestamp, then the action and its parameters; strokes carry
+//! the brush settings and every sample so they can be replayed. Document changes that can't be
+//! replayed are journaled as untracked, and recovery stops before the first of them. The file is
+//! trimmed back to the newest `MAX_ENTRIES` lines once it holds twice as many, but never past the
+//! last document base, so recovery keeps working in long sessions.
+use eframe::egui::Color32;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::brush_engine::brush::{Brush, BrushType};
+use crate::brush_engine::brush_options::BlendMode;
+use crate::utils::vector::Vec2;
+
+pub const FILE_NAME: &str = "action_journal.log";
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JournalEntry {
+    NewDocument { width: usize, height: usize, tile_size: usize, background: Color32 },
+    OpenImage(PathBuf),
+    RestoreSession,
+    /// The composite was written to the session file on close.
+    SessionSaved,
+    Stroke(JournalStroke),
+    ClearLayer { layer: usize },
+    Undo { layer: usize },
+    Redo { layer: usize },
+    AddLayer,
+    RemoveLayer { layer: usize },
+    /// A change to the document that recovery can't redo, e.g. a filter or a layer merge.
+    /// Replaying stops before it rather than rebuild something the user never had.
+    Untracked(String),
+    /// Logged for diagnosis only, never replayed.
+    Note(String),
+}
+
+/// One brush stroke with the settings it was painted with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalStroke {
+    pub layer: usize,
+    pub brush_type: BrushType,
+    pub blend_mode: BlendMode,
+    pub diameter: f32,
+    pub hardness: f32,
+    pub spacing: f32,
+    pub flow: f32,
+    pub opacity: f32,
+    pub color: Color32,
+    pub pixel_perfect: bool,
+    pub anti_aliasing: bool,
+    /// Position, diameter and pressure of every sample.
+    pub points: Vec<(Vec2, f32, f32)>,
+}
+
+impl JournalStroke {
+    /// The brush replay paints this stroke with: the journaled settings on a plain brush.
+    pub fn brush(&self) -> Brush {
+        let mut brush = Brush::new(self.diameter, self.hardness, self.color, self.spacing);
+        let options = &mut brush.brush_options;
+        options.flow = self.flow;
+        options.opacity = self.opacity;
+        options.blend_mode = self.blend_mode;
+        brush.brush_type = self.brush_type;
+        brush.pixel_perfect = self.pixel_perfect;
+        brush.anti_aliasing = self.anti_aliasing;
+        brush
+    }
+}
+
+/// The first setting of `brush` that the journal doesn't record and that differs from the plain
+/// brush replay paints with, named for the untracked entry its strokes get instead.
+fn unjournaled_setting(brush: &Brush) -> Option<&'static str> {
+    let plain = Brush::new(1.0, 100.0, Color32::BLACK, 10.0);
+    let (options, base) = (&brush.brush_options, &plain.brush_options);
+    let differs = [
+        ("stroke_engine", brush.engine != plain.engine),
+        ("stroke_softness", options.softness_selector != base.softness_selector || options.softness_curve != base.softness_curve),
+        ("stroke_tip", options.pixel_shape != base.pixel_shape),
+        ("stroke_dither", options.dither != base.dither),
+        ("stroke_pressure_flow", options.pressure_flow != base.pressure_flow),
+        ("stroke_tip_rotation", options.follow_direction != base.follow_direction || options.random_rotation != base.random_rotation),
+        ("stroke_grain", options.grain != base.grain),
+        ("stroke_jitter", brush.jitter != plain.jitter),
+        ("stroke_stabilizer", brush.stabilizer_algorithm != plain.stabilizer_algorithm),
+        ("stroke_airbrush", brush.airbrush != plain.airbrush),
+    ];
+    differs.into_iter().find_map(|(name, differs)| differs.then_some(name))
+}
+
+impl JournalEntry {
+    /// Whether the entry starts a document that the entries after it apply to.
+    pub fn is_base(&self) -> bool {
+        matches!(
+            self,
+            Self::NewDocument { .. } | Self::OpenImage(_) | Self::RestoreSession | Self::SessionSaved
+        )
+    }
+
+    fn to_line(&self) -> String {
+        match self {
+            Self::NewDocument { width, height, tile_size, background } => {
+                let [r, g, b, a] = background.to_array();
+                format!("new_document {width}x{height} tile={tile_size} background={r:02x}{g:02x}{b:02x}{a:02x}")
+            }
+            Self::OpenImage(path) => format!("open_image {}", path.display()),
+            Self::RestoreSession => "restore_session".to_string(),
+            Self::SessionSaved => "session_saved".to_string(),
+            Self::Stroke(s) => {
+                let [r, g, b, a] = s.color.to_array();
+                let points: Vec<String> = s
+                    .points
+                    .iter()
+                    .map(|(p, d, pressure)| format!("{:.2},{:.2},{:.2},{:.3}", p.x, p.y, d, pressure))
+                    .collect();
+                format!(
+                    "stroke layer={} type={:?} blend={:?} d={} hardness={} spacing={} flow={} opacity={} color={:02x}{:02x}{:02x}{:02x} pixel_perfect={} aa={} points={}",
+                    s.layer, s.brush_type, s.blend_mode, s.diameter, s.hardness, s.spacing, s.flow, s.opacity, r, g, b, a,
+                    s.pixel_perfect, s.anti_aliasing, points.join(";")
+                )
+            }
+            Self::ClearLayer { layer } => format!("clear_layer {layer}"),
+            Self::Undo { layer } => format!("undo {layer}"),
+            Self::Redo { layer } => format!("redo {layer}"),
+            Self::AddLayer => "add_layer".to_string(),
+            Self::RemoveLayer { layer } => format!("remove_layer {layer}"),
+            Self::Untracked(action) => format!("untracked {action}"),
+            Self::Note(text) => format!("note {text}"),
+        }
+    }
+
+    /// Parse a journal line without its timestamp.
+    fn parse(line: &str) -> Option<Self> {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let index = || rest.trim().parse().ok();
+        Some(match kind {
+            "new_document" => parse_new_document(rest)?,
+            "open_image" => Self::OpenImage(PathBuf::from(rest)),
+            "restore_session" => Self::RestoreSession,
+            "session_saved" => Self::SessionSaved,
+            "stroke" => Self::Stroke(parse_stroke(rest)?),
+            "clear_layer" => Self::ClearLayer { layer: index()? },
+            "undo" => Self::Undo { layer: index()? },
+            "redo" => Self::Redo { layer: index()? },
+            "add_layer" => Self::AddLayer,
+            "remove_layer" => Self::RemoveLayer { layer: index()? },
+            "untracked" => Self::Untracked(rest.to_string()),
+            "note" => Self::Note(rest.to_string()),
+            _ => return None,
+        })
+    }
+
+    /// Parse a whole journal line, timestamp first.
+    fn parse_line(line: &str) -> Option<Self> {
+        line.split_once(' ').and_then(|(_, rest)| Self::parse(rest))
+    }
+}
+
+/// `WxH`, then the tile size and paper color; journals from before those were recorded get the
+/// automatic tile size (0) and white paper.
+fn parse_new_document(fields: &str) -> Option<JournalEntry> {
+    let mut fields = fields.split(' ');
+    let (w, h) = fields.next()?.split_once('x')?;
+    let (mut tile_size, mut background) = (0, Color32::WHITE);
+    for field in fields {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "tile" => tile_size = value.parse().ok()?,
+            "background" => {
+                let rgba = u32::from_str_radix(value, 16).ok()?.to_be_bytes();
+                background = Color32::from_rgba_premultiplied(rgba[0], rgba[1], rgba[2], rgba[3]);
+            }
+            _ => {}
+        }
+    }
+    Some(JournalEntry::NewDocument { width: w.parse().ok()?, height: h.parse().ok()?, tile_size, background })
+}
+
+fn parse_stroke(fields: &str) -> Option<JournalStroke> {
+    let mut stroke = JournalStroke {
+        layer: 0,
+        brush_type: BrushType::Soft,
+        blend_mode: BlendMode::Normal,
+        diameter: 1.0,
+        hardness: 100.0,
+        spacing: 10.0,
+        flow: 100.0,
+        opacity: 1.0,
+        color: Color32::BLACK,
+        pixel_perfect: false,
+        anti_aliasing: true,
+        points: Vec::new(),
+    };
+    for field in fields.split(' ') {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "layer" => stroke.layer = value.parse().ok()?,
+            "type" => stroke.brush_type = if value == "Pixel" { BrushType::Pixel } else { BrushType::Soft },
+            "blend" => stroke.blend_mode = BlendMode::ALL.into_iter().find(|m| format!("{m:?}") == value)?,
+            "d" => stroke.diameter = value.parse().ok()?,
+            "hardness" => stroke.hardness = value.parse().ok()?,
+            "spacing" => stroke.spacing = value.parse().ok()?,
+            "flow" => stroke.flow = value.parse().ok()?,
+            "opacity" => stroke.opacity = value.parse().ok()?,
+            "pixel_perfect" => stroke.pixel_perfect = value.parse().ok()?,
+            "aa" => stroke.anti_aliasing = value.parse().ok()?,
+            "color" => {
+                let rgba = u32::from_str_radix(value, 16).ok()?.to_be_bytes();
+                stroke.color = Color32::from_rgba_premultiplied(rgba[0], rgba[1], rgba[2], rgba[3]);
+            }
+            "points" => {
+                for point in value.split(';').filter(|p| !p.is_empty()) {
+                    let v: Vec<f32> = point.split(',').filter_map(|n| n.parse().ok()).collect();
+                    let [x, y, d, pressure] = v[..] else {
+                        return None;
+                    };
+                    stroke.points.push((Vec2::new(x, y), d, pressure));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(stroke)
+}
+
+/// The journal file as the writer thread sees it, kept open between lines.
+struct JournalFile {
+    path: PathBuf,
+    /// Open for appending, or `None` until the next line after a trim or a failed write.
+    file: Option<File>,
+    /// Lines in the file, to know when to trim it.
+    lines: usize,
+    /// Line count that triggers the next trim. Lines since the last document base are never
+    /// trimmed, so a long session moves this up instead of rewriting the file on every action.
+    trim_at: usize,
+}
+
+impl JournalFile {
+    fn new(path: PathBuf, lines: usize) -> Self {
+        Self { path, file: None, lines, trim_at: (lines + MAX_ENTRIES).max(MAX_ENTRIES * 2) }
+    }
+
+    /// Append lines as they arrive until the journal is dropped. Lines queued while one is
+    /// being written share a single sync.
+    fn run(mut self, queue: Receiver<String>) {
+        while let Ok(line) = queue.recv() {
+            self.append(&line);
+            while let Ok(line) = queue.try_recv() {
+                self.append(&line);
+            }
+            if let Some(Err(e)) = self.file.as_ref().map(File::sync_data) {
+                log::error!("Failed to write the action journal: {}", e);
+            }
+        }
+    }
+
+    fn append(&mut self, line: &str) {
+        if self.file.is_none() {
+            match OpenOptions::new().create(true).append(true).open(&self.path) {
+                Ok(file) => self.file = Some(file),
+                Err(e) => return log::error!("Failed to open the action journal: {}", e),
+            }
+        }
+        if let Some(Err(e)) = self.file.as_mut().map(|file| file.write_all(line.as_bytes())) {
+            log::error!("Failed to write the action journal: {}", e);
+            self.file = None;
+            return;
+        }
+        self.lines += 1;
+        if self.lines >= self.trim_at {
+            self.trim();
+        }
+    }
+
+    /// Keep only the newest `MAX_ENTRIES` lines, or everything since the last document base if
+    /// that is more, swapping the file in atomically.
+    fn trim(&mut self) {
+        let contents = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let lines: Vec<&str> = contents.lines().collect();
+        let last_base = lines.iter().rposition(|line| JournalEntry::parse_line(line).is_some_and(|e| e.is_base()));
+        let start = lines.len().saturating_sub(MAX_ENTRIES).min(last_base.unwrap_or(usize::MAX));
+        self.trim_at = (lines.len() - start + MAX_ENTRIES).max(MAX_ENTRIES * 2);
+        if start == 0 {
+            self.lines = lines.len();
+            return;
+        }
+        let kept = &lines[start..];
+        let tmp = self.path.with_extension("log.tmp");
+        let written = File::create(&tmp)
+            .and_then(|mut file| {
+                for line in kept {
+                    writeln!(file, "{line}")?;
+                }
+                file.sync_all()
+            })
+            .and_then(|_| std::fs::rename(&tmp, &self.path));
+        match written {
+            Ok(()) => {
+                self.lines = kept.len();
+                // The open handle still points at the replaced file
+                self.file = None;
+            }
+            Err(e) => log::error!("Failed to trim the action journal: {}", e),
+        }
+    }
+}
+
+/// Collects actions as they happen and queues them for the writer thread.
+pub struct ActionJournal {
+    /// Lines for the writer thread, taken on drop so it can finish.
+    queue: Option<Sender<String>>,
+    writer: Option<JoinHandle<()>>,
+    /// The stroke being painted, written once it ends.
+    pending: Option<JournalStroke>,
+    /// Why the stroke being painted can't be replayed, if it can't. It is journaled as this
+    /// untracked action instead.
+    pending_untracked: Option<&'static str>,
+    /// Entries after the last document base, read at launch, up to the first untracked one.
+    /// Empty when the previous run ended normally.
+    recoverable: Vec<JournalEntry>,
+    /// The untracked action recovery has to stop before, if any.
+    recovery_stop: Option<String>,
+}
+
+impl ActionJournal {
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(FILE_NAME);
+        let contents = std::fs::read_to_string(&path).unwrap_or_default();
+        let entries: Vec<JournalEntry> = contents.lines().filter_map(JournalEntry::parse_line).collect();
+        let base = entries.iter().rposition(JournalEntry::is_base);
+        let mut recoverable = match base {
+            Some(i) if entries[i + 1..].iter().any(|e| !matches!(e, JournalEntry::Note(_))) => entries[i..].to_vec(),
+            _ => Vec::new(),
+        };
+        let mut recovery_stop = None;
+        if let Some(stop) = recoverable.iter().position(|e| matches!(e, JournalEntry::Untracked(_))) {
+            if let JournalEntry::Untracked(action) = &recoverable[stop] {
+                recovery_stop = Some(action.clone());
+            }
+            recoverable.truncate(stop);
+        }
+        let file = JournalFile::new(path, contents.lines().count());
+        let (queue, lines) = mpsc::channel();
+        let writer = std::thread::Builder::new()
+            .name("action-journal".to_string())
+            .spawn(move || file.run(lines))
+            .map_err(|e| log::error!("Failed to start the action journal writer: {}", e))
+            .ok();
+        Self { queue: Some(queue), writer, pending: None, pending_untracked: None, recoverable, recovery_stop }
+    }
+
+    /// Actions the previous run did after its last document base, starting with that base.
+    pub fn recoverable(&self) -> &[JournalEntry] {
+        &self.recoverable
+    }
+
+    /// The first action after the last base that recovery can't replay, which it stops before.
+    pub fn recovery_stop(&self) -> Option<&str> {
+        self.recovery_stop.as_deref()
+    }
+
+    /// Hand out the recoverable actions, once.
+    pub fn take_recoverable(&mut self) -> Vec<JournalEntry> {
+        self.recovery_stop = None;
+        std::mem::take(&mut self.recoverable)
+    }
+
+    pub fn record(&mut self, entry: JournalEntry) {
+        if entry.is_base() {
+            self.recoverable.clear();
+            self.recovery_stop = None;
+        }
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let line = format!("{stamp} {}\n", entry.to_line());
+        if let Some(queue) = &self.queue {
+            let _ = queue.send(line);
+        }
+    }
+
+    /// Start collecting a stroke on `layer` painted with `brush`.
+    pub fn begin_stroke(&mut self, layer: usize, brush: &Brush) {
+        let options = &brush.brush_options;
+        self.pending = Some(JournalStroke {
+            layer,
+            brush_type: brush.brush_type,
+            blend_mode: options.blend_mode,
+            diameter: options.diameter,
+            hardness: options.hardness,
+            spacing: options.spacing,
+            flow: options.flow,
+            opacity: options.opacity,
+            color: options.color,
+            pixel_perfect: brush.pixel_perfect,
+            anti_aliasing: brush.anti_aliasing,
+            points: Vec::new(),
+        });
+        self.pending_untracked = unjournaled_setting(brush);
+    }
+
+    /// Journal the stroke being painted as the untracked action `reason`, for strokes that
+    /// depend on something besides the brush that replay can't reproduce.
+    pub fn untrack_stroke(&mut self, reason: &'static str) {
+        if self.pending.is_some() {
+            self.pending_untracked = Some(reason);
+        }
+    }
+
+    pub fn stroke_point(&mut self, pos: Vec2, diameter: f32, pressure: f32) {
+        if let Some(stroke) = &mut self.pending {
+            stroke.points.push((pos, diameter, pressure));
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        let untracked = self.pending_untracked.take();
+        let Some(stroke) = self.pending.take().filter(|s| !s.points.is_empty()) else {
+            return;
+        };
+        match untracked {
+            Some(reason) => self.record(JournalEntry::Untracked(reason.to_string())),
+            None => self.record(JournalEntry::Stroke(stroke)),
+        }
+    }
+
+    /// Drop the stroke being collected without writing it.
+    pub fn discard_stroke(&mut self) {
+        self.pending = None;
+        self.pending_untracked = None;
+    }
+}
+
+impl Drop for ActionJournal {
+    /// Let the writer thread write out what is still queued before the app exits.
+    fn drop(&mut self) {
+        self.queue = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brush_engine::brush_options::Dither;
+
+    /// An empty directory of its own for each test, since they run in parallel.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rusty-painter-journal-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_journal(dir: &Path, entries: &[JournalEntry]) {
+        let lines: String = entries.iter().map(|e| format!("1700000000 {}\n", e.to_line())).collect();
+        std::fs::write(dir.join(FILE_NAME), lines).unwrap();
+    }
+
+    fn new_document() -> JournalEntry {
+        JournalEntry::NewDocument { width: 800, height: 600, tile_size: 128, background: Color32::from_rgb(250, 240, 230) }
+    }
+
+    #[test]
+    fn entries_round_trip_through_lines() {
+        let entries = [
+            new_document(),
+            JournalEntry::OpenImage(PathBuf::from("/tmp/some picture.png")),
+            JournalEntry::RestoreSession,
+            JournalEntry::SessionSaved,
+            JournalEntry::Stroke(JournalStroke {
+                layer: 2,
+                brush_type: BrushType::Pixel,
+                blend_mode: BlendMode::ALL[1],
+                diameter: 12.5,
+                hardness: 80.0,
+                spacing: 5.0,
+                flow: 60.0,
+                opacity: 0.75,
+                color: Color32::from_rgba_premultiplied(10, 20, 30, 40),
+                pixel_perfect: true,
+                anti_aliasing: false,
+                points: vec![(Vec2::new(1.5, 2.25), 12.5, 0.5), (Vec2::new(3.0, 4.0), 10.0, 0.25)],
+            }),
+            JournalEntry::ClearLayer { layer: 1 },
+            JournalEntry::Undo { layer: 3 },
+            JournalEntry::Redo { layer: 3 },
+            JournalEntry::AddLayer,
+            JournalEntry::RemoveLayer { layer: 4 },
+            JournalEntry::Untracked("filter Blur".to_string()),
+            JournalEntry::Note("tablet lost".to_string()),
+        ];
+        for entry in entries {
+            assert_eq!(JournalEntry::parse(&entry.to_line()), Some(entry));
+        }
+    }
+
+    #[test]
+    fn old_new_document_lines_get_defaults() {
+        let parsed = JournalEntry::parse_line("1700000000 new_document 640x480");
+        assert_eq!(
+            parsed,
+            Some(JournalEntry::NewDocument { width: 640, height: 480, tile_size: 0, background: Color32::WHITE })
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        assert_eq!(JournalEntry::parse_line(""), None);
+        assert_eq!(JournalEntry::parse_line("1700000000 paint_everything"), None);
+        assert_eq!(JournalEntry::parse_line("1700000000 undo x"), None);
+        assert_eq!(JournalEntry::parse_line("1700000000 new_document 640"), None);
+        assert_eq!(JournalEntry::parse_line("1700000000 stroke layer=0 points=1,2,3"), None);
+    }
+
+    #[test]
+    fn recovery_starts_at_the_last_base_and_stops_before_untracked() {
+        let dir = scratch_dir("recovery");
+        write_journal(
+            &dir,
+            &[
+                new_document(),
+                JournalEntry::AddLayer,
+                JournalEntry::OpenImage(PathBuf::from("a.png")),
+                JournalEntry::AddLayer,
+                JournalEntry::Untracked("merge_down".to_string()),
+                JournalEntry::AddLayer,
+            ],
+        );
+        let journal = ActionJournal::load(&dir);
+        assert_eq!(journal.recoverable(), [JournalEntry::OpenImage(PathBuf::from("a.png")), JournalEntry::AddLayer]);
+        assert_eq!(journal.recovery_stop(), Some("merge_down"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clean_exits_leave_nothing_to_recover() {
+        let dir = scratch_dir("clean");
+        write_journal(&dir, &[new_document(), JournalEntry::AddLayer, JournalEntry::SessionSaved, JournalEntry::Note("bye".to_string())]);
+        assert!(ActionJournal::load(&dir).recoverable().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// A journal of `len` lines with one document base at `base`.
+    fn trimmed(name: &str, len: usize, base: usize) -> Vec<String> {
+        let dir = scratch_dir(name);
+        let entries: Vec<JournalEntry> = (0..len).map(|i| if i == base { new_document() } else { JournalEntry::AddLayer }).collect();
+        write_journal(&dir, &entries);
+        let mut file = JournalFile::new(dir.join(FILE_NAME), len);
+        file.trim();
+        let lines: Vec<String> = std::fs::read_to_string(dir.join(FILE_NAME)).unwrap().lines().map(str::to_string).collect();
+        assert_eq!(file.lines, lines.len());
+        let _ = std::fs::remove_dir_all(&dir);
+        lines
+    }
+
+    #[test]
+    fn trim_keeps_the_newest_entries() {
+        let lines = trimmed("trim_newest", MAX_ENTRIES + 200, 300);
+        assert_eq!(lines.len(), MAX_ENTRIES);
+        assert_eq!(JournalEntry::parse_line(&lines[100]), Some(new_document()));
+    }
+
+    #[test]
+    fn trim_never_drops_the_last_base() {
+        let lines = trimmed("trim_base", MAX_ENTRIES + 200, 150);
+        assert_eq!(lines.len(), MAX_ENTRIES + 50);
+        assert_eq!(JournalEntry::parse_line(&lines[0]), Some(new_document()));
+
+        let lines = trimmed("trim_base_first", MAX_ENTRIES + 200, 0);
+        assert_eq!(lines.len(), MAX_ENTRIES + 200);
+    }
+
+    /// What the journal in `dir` holds once its writer has finished.
+    fn written(dir: &Path, journal: ActionJournal) -> Vec<JournalEntry> {
+        drop(journal);
+        std::fs::read_to_string(dir.join(FILE_NAME)).unwrap().lines().filter_map(JournalEntry::parse_line).collect()
+    }
+
+    #[test]
+    fn queued_entries_are_written_by_the_time_the_journal_drops() {
+        let dir = scratch_dir("writer");
+        let mut journal = ActionJournal::load(&dir);
+        journal.record(new_document());
+        journal.record(JournalEntry::AddLayer);
+        journal.record(JournalEntry::ClearLayer { layer: 1 });
+        assert_eq!(written(&dir, journal), [new_document(), JournalEntry::AddLayer, JournalEntry::ClearLayer { layer: 1 }]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn strokes_replay_with_the_brush_they_were_painted_with_or_are_untracked() {
+        let dir = scratch_dir("untracked_stroke");
+        let mut journal = ActionJournal::load(&dir);
+        let mut brush = Brush::new_pixel(3.0, Color32::RED);
+        brush.brush_options.opacity = 0.5;
+        journal.begin_stroke(0, &brush);
+        journal.stroke_point(Vec2 { x: 1.0, y: 2.0 }, 3.0, 1.0);
+        journal.end_stroke();
+
+        brush.brush_options.dither = Dither::Bayer;
+        journal.begin_stroke(0, &brush);
+        journal.stroke_point(Vec2 { x: 1.0, y: 2.0 }, 3.0, 1.0);
+        journal.end_stroke();
+
+        brush.brush_options.dither = Dither::Off;
+        journal.begin_stroke(0, &brush);
+        journal.untrack_stroke("stroke_paint_behind");
+        journal.stroke_point(Vec2 { x: 1.0, y: 2.0 }, 3.0, 1.0);
+        journal.end_stroke();
+
+        let entries = written(&dir, journal);
+        let JournalEntry::Stroke(stroke) = &entries[0] else {
+            panic!("expected a stroke, got {:?}", entries[0]);
+        };
+        let replayed = stroke.brush();
+        assert_eq!(replayed.brush_type, brush.brush_type);
+        assert_eq!((replayed.pixel_perfect, replayed.anti_aliasing), (true, false));
+        assert_eq!(replayed.brush_options.opacity, 0.5);
+        assert_eq!(unjournaled_setting(&replayed), None);
+        assert_eq!(
+            entries[1..],
+            [JournalEntry::Untracked("stroke_dither".to_string()), JournalEntry::Untracked("stroke_paint_behind".to_string())]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::app::tools::ToolKey;
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+/// Application commands that can be triggered from the keyboard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Undo,
+    Redo,
+    ClearLayer,
+    Deselect,
+    /// Escape: backs out of whatever is in progress, see `PainterApp::cancel`.
+    Cancel,
+    /// Enter: finishes whatever is in progress, see `PainterApp::confirm`.
+    Confirm,
+    PasteImage,
+    CopyMerged,
+    ExportSelection,
+    WarmerColor,
+    CoolerColor,
+    LighterColor,
+    DarkerColor,
+    RecenterView,
+    /// Unmodified tool keys; the input handler tells taps from holds.
+    SwitchTool(ToolKey),
+}
+
+/// Maps keyboard shortcuts to commands.
+pub struct Keybindings {
+    // Checked in order, so more specific shortcuts must come first
+    bindings: Vec<(KeyboardShortcut, Command)>,
+    /// A paste event already ran the paste for the paste key that is down, so its release
+    /// must not paste again.
+    pasted_on_press: bool,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z), Command::Redo),
+                (KeyboardShortcut::new(Modifiers::COMMAND, Key::Z), Command::Undo),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::Delete), Command::ClearLayer),
+                (KeyboardShortcut::new(Modifiers::COMMAND, Key::D), Command::Deselect),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::Escape), Command::Cancel),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::Enter), Command::Confirm),
+                (KeyboardShortcut::new(Modifiers::COMMAND, Key::V), Command::PasteImage),
+                (KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::C), Command::CopyMerged),
+                (KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::E), Command::ExportSelection),
+                (KeyboardShortcut::new(Modifiers::SHIFT, Key::L), Command::WarmerColor),
+                (KeyboardShortcut::new(Modifiers::SHIFT, Key::K), Command::CoolerColor),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::L), Command::LighterColor),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::K), Command::DarkerColor),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::Home), Command::RecenterView),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::B), Command::SwitchTool(ToolKey::Brush)),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::E), Command::SwitchTool(ToolKey::Eraser)),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::S), Command::SwitchTool(ToolKey::Select)),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::T), Command::SwitchTool(ToolKey::Transform)),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::P), Command::SwitchTool(ToolKey::Path)),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::M), Command::SwitchTool(ToolKey::Move)),
+                (KeyboardShortcut::new(Modifiers::NONE, Key::I), Command::SwitchTool(ToolKey::Eyedropper)),
+            ],
+            pasted_on_press: false,
+        }
+    }
+}
+
+impl Keybindings {
+    /// Consume pressed shortcuts and return the commands they map to.
+    /// Nothing fires while a text field has focus.
+    pub fn triggered(&mut self, ctx: &egui::Context) -> Vec<Command> {
+        if ctx.wants_keyboard_input() {
+            return Vec::new();
+        }
+        let (bindings, pasted_on_press) = (&self.bindings, &mut self.pasted_on_press);
+        ctx.input_mut(|i| {
+            let mut commands: Vec<Command> = bindings
+                .iter()
+                .filter(|(shortcut, _)| i.consume_shortcut(shortcut))
+                .map(|(_, command)| *command)
+                .collect();
+            // The windowing backend turns every paste shortcut (Ctrl+V, Shift+Insert, the Paste
+            // key, the V key of non-Latin layouts) into a paste event, but only when the clipboard
+            // holds text; a clipboard with just an image leaves nothing but the key release
+            let paste_event = i.events.iter().any(|e| matches!(e, egui::Event::Paste(_)));
+            let paste_released = i.events.iter().any(|e| {
+                matches!(e, egui::Event::Key { key: Key::V, pressed: false, modifiers, .. } if modifiers.command)
+            });
+            let paste = paste_event || (paste_released && !*pasted_on_press);
+            let paste_key_up = i.events.iter().any(|e| {
+                matches!(e, egui::Event::Key { key: Key::V | Key::Insert | Key::Paste, pressed: false, .. })
+            });
+            if paste_key_up {
+                *pasted_on_press = false;
+            } else if paste_event {
+                *pasted_on_press = true;
+            }
+            if paste && !commands.contains(&Command::PasteImage) {
+                commands.push(Command::PasteImage);
+            }
+            // Copy and cut presses likewise only arrive as clipboard events, so look those up
+            // as the key they came from with the modifiers held
+            let clipboard_keys: Vec<Key> = i
+                .events
+                .iter()
+                .filter_map(|e| match e {
+                    egui::Event::Copy => Some(Key::C),
+                    egui::Event::Cut => Some(Key::X),
+                    _ => None,
+                })
+                .collect();
+            for key in clipboard_keys {
+                let bound = bindings
+                    .iter()
+                    .find(|(shortcut, _)| shortcut.logical_key == key && i.modifiers.matches_exact(shortcut.modifiers));
+                if let Some(&(_, command)) = bound.filter(|(_, command)| !commands.contains(command)) {
+                    commands.push(command);
+                }
+            }
+            commands
+        })
+    }
+
+    /// Human-readable shortcut for a command, for menu labels.
+    pub fn shortcut_text(&self, ctx: &egui::Context, command: Command) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(_, c)| *c == command)
+            .map(|(shortcut, _)| ctx.format_shortcut(shortcut))
+    }
+
+    /// The key bound to a command, for commands that watch their key being held.
+    pub fn key(&self, command: Command) -> Option<Key> {
+        self.bindings.iter().find(|(_, c)| *c == command).map(|(shortcut, _)| shortcut.logical_key)
+    }
+}
@@ -1,13 +1,20 @@
+use crate::utils::i18n::tr;
 use crate::{PainterApp, ui};
 use eframe::egui;
-use egui_dock::{DockArea, DockState, NodeIndex, TabViewer};
+use egui_dock::{DockArea, DockState, NodeIndex, SurfaceIndex, TabViewer};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum ToolTab {
     BrushSettings,
     BrushPresets,
     ColorPicker,
+    Palette,
     Layers,
+    History,
+    Timeline,
+    View,
+    Histogram,
+    Channels,
 }
 
 impl ToolTab {
@@ -16,34 +23,56 @@ impl ToolTab {
             ToolTab::BrushSettings => "Brush Settings",
             ToolTab::BrushPresets => "Brush Presets",
             ToolTab::ColorPicker => "Color Picker",
+            ToolTab::Palette => "Palette",
             ToolTab::Layers => "Layers",
+            ToolTab::History => "History",
+            ToolTab::Timeline => "Timeline",
+            ToolTab::View => "View",
+            ToolTab::Histogram => "Histogram",
+            ToolTab::Channels => "Channels",
         }
     }
 }
 
+/// Which side panel a tab was popped out of, so closing its window docks it back there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DockSide {
+    Left,
+    Right,
+}
+
+/// A dock tab shown in its own OS window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FloatingPanel {
+    pub(crate) tab: ToolTab,
+    pub(crate) home: DockSide,
+}
+
 pub(crate) fn default_left_dock() -> DockState<ToolTab> {
     let mut dock = DockState::new(vec![ToolTab::BrushSettings]);
     dock.main_surface_mut()
-        .split_below(NodeIndex::root(), 0.6, vec![ToolTab::BrushPresets]);
+        .split_below(NodeIndex::root(), 0.6, vec![ToolTab::BrushPresets, ToolTab::View]);
     dock
 }
 
 pub(crate) fn default_right_dock() -> DockState<ToolTab> {
-    let mut dock = DockState::new(vec![ToolTab::Layers]);
+    let mut dock = DockState::new(vec![ToolTab::Layers, ToolTab::History, ToolTab::Timeline, ToolTab::Histogram, ToolTab::Channels]);
     dock.main_surface_mut()
-        .split_above(NodeIndex::root(), 0.45, vec![ToolTab::ColorPicker]);
+        .split_above(NodeIndex::root(), 0.45, vec![ToolTab::ColorPicker, ToolTab::Palette]);
     dock
 }
 
 struct ToolTabViewer<'a> {
     app: &'a mut PainterApp,
+    /// Tab whose "Pop Out" entry was picked this frame.
+    pop_out: Option<ToolTab>,
 }
 
 impl<'a> TabViewer for ToolTabViewer<'a> {
     type Tab = ToolTab;
 
     fn title(&mut self, tab: &mut Self::Tab) -> egui::WidgetText {
-        tab.title().into()
+        tr(tab.title()).into()
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, tab: &mut Self::Tab) {
@@ -58,7 +87,7 @@ impl<'a> TabViewer for ToolTabViewer<'a> {
                 )
             }
             ToolTab::BrushPresets => {
-                ui::brush_list::brush_list_panel(
+                let meta_changed = ui::brush_list::brush_list_panel(
                     ui,
                     &mut self.app.brush,
                     &mut self.app.presets,
@@ -66,15 +95,31 @@ impl<'a> TabViewer for ToolTabViewer<'a> {
                     &self.app.pool,
                     &mut self.app.show_new_preset_modal,
                     &mut self.app.new_preset_name,
-                )
+                );
+                if meta_changed {
+                    self.app.save_preset_meta();
+                }
             }
             ToolTab::ColorPicker => {
-                ui::color_picker::color_picker_panel(ui, &mut self.app.brush, self.app.color_model)
+                ui::color_picker::color_picker_panel(ui, &mut self.app.brush, self.app.color_model, &mut self.app.color_nudge)
             }
+            ToolTab::Palette => ui::palette::palette_panel(ui, self.app),
             ToolTab::Layers => {
                 let ctx = ui.ctx().clone();
                 ui::layers::layers_panel(&ctx, ui, self.app);
             }
+            ToolTab::History => ui::history::history_panel(ui, self.app),
+            ToolTab::Timeline => ui::timeline::timeline_panel(ui, self.app),
+            ToolTab::View => ui::view::view_panel(ui, self.app),
+            ToolTab::Histogram => ui::histogram::histogram_panel(ui, self.app),
+            ToolTab::Channels => ui::channels::channels_panel(ui, self.app),
+        }
+    }
+
+    fn context_menu(&mut self, ui: &mut egui::Ui, tab: &mut Self::Tab, _surface: SurfaceIndex, _node: NodeIndex) {
+        if ui.button(tr("Pop Out to Window")).clicked() {
+            self.pop_out = Some(*tab);
+            ui.close_menu();
         }
     }
 
@@ -94,12 +139,7 @@ pub(crate) fn show_tool_docks(app: &mut PainterApp, ctx: &egui::Context) {
         .min_width(260.0)
         .show(ctx, |ui| {
             ui.set_min_width(260.0);
-            let mut dock_state = std::mem::replace(&mut app.dock_left, DockState::new(Vec::new()));
-            {
-                let mut viewer = ToolTabViewer { app };
-                DockArea::new(&mut dock_state).show_inside(ui, &mut viewer);
-            }
-            app.dock_left = dock_state;
+            show_dock(app, ui, DockSide::Left);
         });
 
     egui::SidePanel::right("tool_dock_right")
@@ -108,11 +148,73 @@ pub(crate) fn show_tool_docks(app: &mut PainterApp, ctx: &egui::Context) {
         .min_width(240.0)
         .show(ctx, |ui| {
             ui.set_min_width(240.0);
-            let mut dock_state = std::mem::replace(&mut app.dock_right, DockState::new(Vec::new()));
-            {
-                let mut viewer = ToolTabViewer { app };
-                DockArea::new(&mut dock_state).show_inside(ui, &mut viewer);
+            show_dock(app, ui, DockSide::Right);
+        });
+
+    show_floating_panels(app, ctx);
+}
+
+fn dock_mut(app: &mut PainterApp, side: DockSide) -> &mut DockState<ToolTab> {
+    match side {
+        DockSide::Left => &mut app.dock_left,
+        DockSide::Right => &mut app.dock_right,
+    }
+}
+
+fn show_dock(app: &mut PainterApp, ui: &mut egui::Ui, side: DockSide) {
+    let mut dock_state = std::mem::replace(dock_mut(app, side), DockState::new(Vec::new()));
+    let pop_out = {
+        let mut viewer = ToolTabViewer { app, pop_out: None };
+        DockArea::new(&mut dock_state).show_inside(ui, &mut viewer);
+        viewer.pop_out
+    };
+    if let Some((tab, location)) = pop_out.and_then(|tab| Some((tab, dock_state.find_tab(&tab)?))) {
+        dock_state.remove_tab(location);
+        app.floating_panels.push(FloatingPanel { tab, home: side });
+    }
+    *dock_mut(app, side) = dock_state;
+}
+
+/// Put a floating tab back into the side panel it came from.
+pub(crate) fn dock_panel(app: &mut PainterApp, panel: FloatingPanel) {
+    app.floating_panels.retain(|p| p.tab != panel.tab);
+    dock_mut(app, panel.home).push_to_first_leaf(panel.tab);
+}
+
+/// Each popped-out tab gets its own native window, which can sit on another monitor. Backends
+/// without multi-viewport support get an in-app window instead.
+fn show_floating_panels(app: &mut PainterApp, ctx: &egui::Context) {
+    let mut redock = Vec::new();
+    for panel in app.floating_panels.clone() {
+        let mut tab = panel.tab;
+        let title = tr(tab.title());
+        let builder = egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size([360.0, 520.0])
+            .with_min_inner_size([240.0, 200.0]);
+        ctx.show_viewport_immediate(egui::ViewportId::from_hash_of(("tool_panel", tab)), builder, |ctx, class| {
+            let mut viewer = ToolTabViewer { app: &mut *app, pop_out: None };
+            if class == egui::ViewportClass::Embedded {
+                let mut open = true;
+                egui::Window::new(title)
+                    .id(egui::Id::new(("tool_panel_window", tab)))
+                    .open(&mut open)
+                    .default_size([360.0, 520.0])
+                    .show(ctx, |ui| viewer.ui(ui, &mut tab));
+                if !open {
+                    redock.push(panel);
+                }
+                return;
+            }
+            egui::CentralPanel::default().show(ctx, |ui| {
+                egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| viewer.ui(ui, &mut tab));
+            });
+            if ctx.input(|i| i.viewport().close_requested()) {
+                redock.push(panel);
             }
-            app.dock_right = dock_state;
         });
+    }
+    for panel in redock {
+        dock_panel(app, panel);
+    }
 }
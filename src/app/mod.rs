@@ -3,6 +3,13 @@ pub mod painter;
 pub mod state;
 pub mod render_helper;
 pub mod input_handler;
+pub mod bench;
+pub mod favorites;
+pub mod recent_files;
+pub mod journal;
+pub mod document_info;
+pub mod keybindings;
+pub mod navigation;
 pub mod tools;
 
 pub use painter::PainterApp;
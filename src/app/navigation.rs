@@ -0,0 +1,200 @@
+//! Mouse button and key combinations that pan, rotate or zoom the view, stored next to the
+//! brush tips.
+//!
+//! One binding per line as `action = trigger`, e.g. `rotate = Shift+Space+Left`: the
+//! modifiers and held key come first, the mouse button that drags last.
+use eframe::egui::{Key, Modifiers, PointerButton};
+use std::path::Path;
+
+pub const FILE_NAME: &str = "navigation.meta";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavAction {
+    Pan,
+    /// Horizontal drag turns the view.
+    Rotate,
+    /// Vertical drag zooms, up to zoom in.
+    Zoom,
+}
+
+impl NavAction {
+    pub const ALL: [Self; 3] = [Self::Pan, Self::Rotate, Self::Zoom];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Pan => "Pan",
+            Self::Rotate => "Rotate",
+            Self::Zoom => "Zoom",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Pan => "pan",
+            Self::Rotate => "rotate",
+            Self::Zoom => "zoom",
+        }
+    }
+}
+
+/// A mouse button dragged while the modifiers (and optionally a key) are held.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NavTrigger {
+    pub button: PointerButton,
+    pub key: Option<Key>,
+    pub modifiers: Modifiers,
+}
+
+impl NavTrigger {
+    pub const fn drag(button: PointerButton) -> Self {
+        Self { button, key: None, modifiers: Modifiers::NONE }
+    }
+
+    pub const fn key_drag(modifiers: Modifiers, key: Key) -> Self {
+        Self { button: PointerButton::Primary, key: Some(key), modifiers }
+    }
+
+    /// Triggers offered in the settings. A plain left drag paints, so it is never one of them.
+    pub const CHOICES: [Self; 10] = [
+        Self::drag(PointerButton::Secondary),
+        Self::drag(PointerButton::Middle),
+        Self::drag(PointerButton::Extra1),
+        Self::drag(PointerButton::Extra2),
+        Self::key_drag(Modifiers::NONE, Key::Space),
+        Self::key_drag(Modifiers::SHIFT, Key::Space),
+        Self::key_drag(Modifiers::COMMAND, Key::Space),
+        Self::key_drag(Modifiers::ALT, Key::Space),
+        Self { button: PointerButton::Primary, key: None, modifiers: Modifiers::ALT },
+        Self { button: PointerButton::Middle, key: None, modifiers: Modifiers::SHIFT },
+    ];
+
+    /// Whether pressing `button` with `modifiers`, while `key_down` reports held keys, starts
+    /// this trigger. Modifiers must match exactly so Space+drag doesn't swallow Shift+Space+drag.
+    pub fn matches(&self, button: PointerButton, modifiers: Modifiers, key_down: impl Fn(Key) -> bool) -> bool {
+        button == self.button
+            && modifiers.matches_exact(self.modifiers)
+            && self.key.is_none_or(key_down)
+    }
+
+    fn modifier_names(&self) -> Vec<&'static str> {
+        let m = self.modifiers;
+        [(m.command || m.ctrl, "Ctrl"), (m.shift, "Shift"), (m.alt, "Alt")]
+            .into_iter()
+            .filter_map(|(held, name)| held.then_some(name))
+            .collect()
+    }
+
+    /// Settings-file form, see the module docs.
+    fn to_text(self) -> String {
+        let mut parts = self.modifier_names();
+        if let Some(key) = self.key {
+            parts.push(key.name());
+        }
+        parts.push(button_name(self.button));
+        parts.join("+")
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let button = match parts.pop()? {
+            "Left" => PointerButton::Primary,
+            "Right" => PointerButton::Secondary,
+            "Middle" => PointerButton::Middle,
+            "Back" => PointerButton::Extra1,
+            "Forward" => PointerButton::Extra2,
+            _ => return None,
+        };
+        let mut trigger = Self::drag(button);
+        for part in parts {
+            match part {
+                "Ctrl" => trigger.modifiers = trigger.modifiers | Modifiers::COMMAND,
+                "Shift" => trigger.modifiers = trigger.modifiers | Modifiers::SHIFT,
+                "Alt" => trigger.modifiers = trigger.modifiers | Modifiers::ALT,
+                name => trigger.key = Some(Key::from_name(name)?),
+            }
+        }
+        Some(trigger)
+    }
+
+    /// Short description for the settings, e.g. "Shift+Space+drag" or "Middle drag".
+    pub fn label(&self) -> String {
+        let mut parts = self.modifier_names();
+        if let Some(key) = self.key {
+            parts.push(key.name());
+        }
+        let drag = match self.button {
+            PointerButton::Primary => "drag",
+            PointerButton::Secondary => "Right drag",
+            PointerButton::Middle => "Middle drag",
+            PointerButton::Extra1 => "Back button drag",
+            PointerButton::Extra2 => "Forward button drag",
+        };
+        parts.push(crate::utils::i18n::tr(drag));
+        parts.join("+")
+    }
+}
+
+fn button_name(button: PointerButton) -> &'static str {
+    match button {
+        PointerButton::Primary => "Left",
+        PointerButton::Secondary => "Right",
+        PointerButton::Middle => "Middle",
+        PointerButton::Extra1 => "Back",
+        PointerButton::Extra2 => "Forward",
+    }
+}
+
+/// View navigation bindings, checked in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavigationBindings {
+    pub bindings: Vec<(NavTrigger, NavAction)>,
+}
+
+impl Default for NavigationBindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (NavTrigger::key_drag(Modifiers::NONE, Key::Space), NavAction::Pan),
+                (NavTrigger::key_drag(Modifiers::SHIFT, Key::Space), NavAction::Rotate),
+                (NavTrigger::key_drag(Modifiers::COMMAND, Key::Space), NavAction::Zoom),
+                (NavTrigger::drag(PointerButton::Secondary), NavAction::Pan),
+                (NavTrigger::drag(PointerButton::Middle), NavAction::Rotate),
+            ],
+        }
+    }
+}
+
+impl NavigationBindings {
+    /// Saved bindings, or the defaults when there are none.
+    pub fn load(dir: &Path) -> Self {
+        let Ok(contents) = std::fs::read_to_string(dir.join(FILE_NAME)) else {
+            return Self::default();
+        };
+        let bindings: Vec<(NavTrigger, NavAction)> = contents
+            .lines()
+            .filter_map(|line| {
+                let (action, trigger) = line.split_once(" = ")?;
+                let action = NavAction::ALL.into_iter().find(|a| a.key() == action.trim())?;
+                Some((NavTrigger::parse(trigger)?, action))
+            })
+            .collect();
+        if bindings.is_empty() { Self::default() } else { Self { bindings } }
+    }
+
+    pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+        let mut contents = String::new();
+        for (trigger, action) in &self.bindings {
+            contents.push_str(&format!("{} = {}\n", action.key(), trigger.to_text()));
+        }
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(FILE_NAME), contents)
+    }
+
+    /// The action a press of `button` starts, if any.
+    pub fn action_for(&self, button: PointerButton, modifiers: Modifiers, key_down: impl Fn(Key) -> bool) -> Option<NavAction> {
+        self.bindings
+            .iter()
+            .find(|(trigger, _)| trigger.matches(button, modifiers, &key_down))
+            .map(|(_, action)| *action)
+    }
+}
@@ -1,32 +1,49 @@
 use super::{
+    document_info::DocumentInfo,
+    favorites,
+    journal::{ActionJournal, JournalEntry, JournalStroke},
+    recent_files::{self, RecentFiles},
+    keybindings::{Command, Keybindings},
+    navigation::{NavAction, NavigationBindings},
     layout::{self, ToolTab},
-    state::{CanvasTile, ColorModel, NewCanvasSettings, TextureAtlas, TILE_SIZE, ATLAS_SIZE},
+    state::{CanvasTile, ColorModel, NewCanvasSettings, TextureAtlas, DEFAULT_TILE_SIZE, TILE_SIZES, ATLAS_SIZE, ATLAS_GUTTER, DEFAULT_CANVAS_SIZE, DEFAULT_DPI, EyedropperSettings, Guide, TransformSession, atlas_slot, auto_tile_size},
 };
 use crate::{
-    brush_engine::{brush::{Brush, BrushPreset}, stroke::StrokeState},
+    brush_engine::{brush::{Brush, BrushPreset, StabilizerAlgorithm}, preset_meta, engine::{BrushEngine, StrokeTarget}, smoothing::{self, StrokeSample}},
     canvas::{
-        canvas::Canvas,
-        history::{History, UndoAction},
+        vector::VectorStroke,
+        animation::Animation,
+        canvas::{Canvas, LayerLabel},
+        history::{CanvasCrop, CanvasSnapshot, CompositeAction, History, UndoAction},
     },
-    tablet::TabletInput,
+    tablet::{FingerTouches, PointerPressure, TabletInput},
     ui,
     ui::brush_settings::BrushPreviewState,
+    ui::export_modal::ExportScope,
     utils::vector::Vec2,
 };
 use crate::app::render_helper;
-use crate::app::input_handler;
+use crate::utils::color::{ColorDeficiency, ColorNudge};
+use crate::app::input_handler::{self, ToolSwitcher};
+use crate::utils::exporter::{ExportFormat, save_color_image_with_info};
+use crate::utils::file_dialog;
+use crate::utils::i18n::tr;
+use crate::utils::profiler::ScopeTimer;
+use crate::styling::theme::Theme;
 use crate::brush_engine::brush_options::{BlendMode, PixelBrushShape};
 use eframe::egui;
 use eframe::egui::{Color32, TextureOptions};
 use egui_dock::DockState;
 use rayon::{ThreadPool, ThreadPoolBuilder};
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc};
 use std::thread;
 // use std::time::Duration;
 
 use crate::selection::{SelectionManager};
+use crate::selection::transform::{HANDLE_RADIUS, Interpolation, TransformState};
 
 
 
@@ -36,44 +53,141 @@ pub struct PainterApp {
     pub(crate) brush: Brush,
     pub(crate) brush_preview: BrushPreviewState,
     pub(crate) presets: Vec<BrushPreset>,
+    /// Colors pinned to the favorites bar.
+    pub(crate) favorite_colors: Vec<Color32>,
+    pub(crate) show_favorites_bar: bool,
     pub(crate) active_tool: super::tools::Tool,
+    pub(crate) transform_interpolation: Interpolation,
     pub(crate) selection_manager: SelectionManager,
-    pub(crate) preset_previews: HashMap<String, egui::TextureHandle>,
+    /// Preset thumbnails keyed by `BrushPreset::thumbnail_key`.
+    pub(crate) preset_previews: HashMap<u64, egui::TextureHandle>,
     pub(crate) show_new_preset_modal: bool,
     pub(crate) new_preset_name: String,
-    pub(crate) stroke: Option<StrokeState>,
+    /// Engine painting the stroke in progress, created from the brush's engine kind.
+    pub(crate) stroke: Option<Box<dyn BrushEngine>>,
+    /// Every input sample of the stroke in progress as it arrived, for post-stroke smoothing.
+    pub(crate) stroke_samples: Vec<StrokeSample>,
     pub(crate) is_drawing: bool,
+    /// When the stroke in progress began, for the painting time statistic.
+    pub(crate) stroke_started: Option<std::time::Instant>,
+    /// When the airbrush last sprayed, or the pointer last moved, during the stroke in progress.
+    pub(crate) airbrush_last: Option<std::time::Instant>,
+    /// Screen-pixel brush size set aside while a stroke paints with its canvas equivalent.
+    pub(crate) screen_diameter: Option<f32>,
 
     pub(crate) brushes_path: PathBuf,
+    pub(crate) recent_files: RecentFiles,
+    /// Recent actions on disk, for diagnosing crashes and replaying lost work.
+    pub(crate) journal: ActionJournal,
+    /// Destination and thumbnail of a running canvas export, added to the recent files once it succeeds.
+    pub(crate) pending_recent: Option<(PathBuf, egui::ColorImage)>,
     pub(crate) loaded_brush_tips: Vec<(String, PixelBrushShape, Option<egui::TextureHandle>)>, // Name, Shape, Optional Preview Texture
 
     pub(crate) histories: Vec<History>,
+    /// Undo steps of the whole document, like trims, reachable from whichever layer is active.
+    pub(crate) document_history: History,
+    pub(crate) snapshots: Vec<CanvasSnapshot>,
+    /// Flipbook frames; the canvas holds the one being edited.
+    pub(crate) animation: Animation,
+    pub(crate) playback: Option<ui::timeline::Playback>,
+    pub(crate) histogram: ui::histogram::HistogramState,
+    pub(crate) new_snapshot_name: String,
+    /// Named selections stored on the document.
+    pub(crate) selection_channels: Vec<crate::selection::SelectionChannel>,
+    pub(crate) new_channel_name: String,
     pub(crate) current_undo_action: Option<UndoAction>,
     pub(crate) modified_tiles: HashSet<(usize, usize)>,
 
     pub(crate) tiles: Vec<CanvasTile>,
     pub(crate) atlases: Vec<TextureAtlas>,
     pub(crate) tiles_x: usize,
+    /// False while the start screen is shown and no canvas has been created or opened yet.
+    pub(crate) document_open: bool,
     pub(crate) tiles_y: usize,
     pub(crate) layer_caches: Vec<HashMap<(usize, usize), egui::ColorImage>>,
     pub(crate) layer_cache_dirty: Vec<HashSet<(usize, usize)>>,
     pub(crate) layer_ui_colors: Vec<Color32>,
     pub(crate) layer_dragging: Option<usize>,
+    /// The layers panel lists only layers carrying this color label.
+    pub(crate) layer_label_filter: Option<LayerLabel>,
+    /// Extra layers picked with Ctrl/Shift-click for bulk operations (the active layer is implied).
+    pub(crate) selected_layers: BTreeSet<usize>,
     pub(crate) floating_layer_idx: Option<usize>,
     pub(crate) floating_buffer: Option<HashMap<(i32, i32), Vec<Color32>>>,
+    /// The floating layer holds a pasted image rather than pixels lifted from the layer below.
+    pub(crate) floating_pasted: bool,
+    /// Undo steps of the floating transform in progress, collapsed into one entry on commit.
+    pub(crate) transform_session: Option<TransformSession>,
+    /// Dots per inch of the document, used to show the rulers in physical units.
+    pub(crate) document_dpi: f32,
+    /// Title, author and notes, embedded in exports and the session file.
+    pub(crate) document_info: DocumentInfo,
+    /// Edits made since the document was opened, created or last exported in full.
+    pub(crate) unsaved_changes: bool,
+    /// File the document was opened from or last exported to.
+    pub(crate) document_path: Option<PathBuf>,
+    /// Window title last sent to the OS, so it is only sent again when it changes.
+    window_title: String,
+    /// Closing was held back for unsaved changes and the Save/Discard/Cancel prompt is up.
+    pub(crate) show_close_prompt: bool,
+    /// Close the window once the export started from the close prompt succeeds.
+    pub(crate) close_after_save: bool,
+    pub(crate) show_document_properties: bool,
+    pub(crate) show_statistics: bool,
+    pub(crate) guides: Vec<Guide>,
+    pub(crate) dragging_guide: Option<usize>,
 
     pub(crate) zoom: f32,
     pub(crate) offset: Vec2,
     pub(crate) first_frame: bool,
+    /// Center the canvas in the viewport on the next frame, keeping zoom and rotation.
+    pub(crate) recenter_pending: bool,
     pub(crate) use_masked_brush: bool,
+    /// Write cold layer tiles to a scratch file once their pixels exceed the budget.
+    pub(crate) tile_paging: bool,
+    /// Megabytes of layer pixels kept in memory while paging is on.
+    pub(crate) tile_paging_budget_mb: usize,
+    pub(crate) last_paging_pass: std::time::Instant,
+    /// Paging pass running on the background pool, reporting how many tiles it paged out.
+    pub(crate) paging_pass: Option<std::sync::mpsc::Receiver<usize>>,
+    pub(crate) clamp_strokes_to_canvas: bool,
+    pub(crate) keybindings: Keybindings,
+    pub(crate) color_nudge: ColorNudge,
+    pub(crate) show_profiler_hud: bool,
+    pub(crate) theme: Theme,
+    pub(crate) profiler_hud: ui::profiler_hud::ProfilerHud,
+    pub(crate) filter_dialog: Option<ui::filters::FilterDialog>,
+    pub(crate) show_stroke_selection: bool,
+    pub(crate) background_dialog: Option<ui::background_color::BackgroundColorDialog>,
+    pub(crate) trim_dialog: Option<ui::trim::TrimDialog>,
+    pub(crate) transform_preview_pending: bool,
+    pub(crate) stroke_selection: ui::stroke_selection::StrokeSelectionSettings,
+    pub(crate) stroke_selection_job: Option<ui::stroke_selection::StrokeSelectionJob>,
     pub(crate) thread_count: usize,
+    pub(crate) background_thread_count: usize,
     pub(crate) max_threads: usize,
-    pub(crate) pool: ThreadPool,
+    /// Work the user is waiting on: stroke dabs, brush previews, display compositing.
+    pub(crate) pool: Arc<ThreadPool>,
+    /// Long jobs: filters, stroking selections, histogram sampling, exports. Kept apart so
+    /// they never take threads from a stroke.
+    pub(crate) background_pool: Arc<ThreadPool>,
     pub(crate) is_panning: bool,
     pub(crate) is_rotating: bool,
+    /// Dragging the view zoom from a navigation binding.
+    pub(crate) is_zooming: bool,
+    /// Button holding the current pan/rotate/zoom drag.
+    pub(crate) nav_button: Option<egui::PointerButton>,
+    pub(crate) navigation: NavigationBindings,
     pub(crate) rotation: f32,
+    /// Unsnapped angle accumulated during a middle-mouse rotation drag.
+    pub(crate) rotation_drag: f32,
+    pub(crate) view_settings: crate::app::state::ViewSettings,
+    /// Filter the canvas atlases were last uploaded with.
+    pub(crate) atlas_filter: egui::TextureFilter,
     pub(crate) is_primary_down: bool,
     pub(crate) disable_lod: bool,
+    /// Debug view outlining which tiles of the active layer hold a pixel buffer.
+    pub(crate) show_tile_overlay: bool,
     // pub(crate) force_full_upload: bool,
     pub(crate) show_new_canvas_modal: bool,
     pub(crate) show_export_modal: bool,
@@ -82,6 +196,8 @@ pub struct PainterApp {
     pub(crate) export_message: Option<String>,
     pub(crate) export_in_progress: bool,
     pub(crate) export_task: Option<std::thread::JoinHandle<Result<String, String>>>,
+    /// Canvas rows still to be flattened for a streamed PNG export.
+    pub(crate) export_bands: Option<ui::export_modal::ExportBands>,
     pub(crate) export_progress: f32,
     pub(crate) export_progress_rx: Option<mpsc::Receiver<crate::ui::export_modal::ExportProgress>>,
     pub(crate) color_model: ColorModel,
@@ -89,15 +205,57 @@ pub struct PainterApp {
     pub(crate) show_general_settings: bool,
     pub(crate) dock_left: DockState<ToolTab>,
     pub(crate) dock_right: DockState<ToolTab>,
+    /// Dock tabs popped out into their own windows.
+    pub(crate) floating_panels: Vec<layout::FloatingPanel>,
     pub(crate) tablet: Option<TabletInput>,
+    /// Pen pressure from native pointer events, used when `tablet` is unavailable.
+    pub(crate) pointer_pressure: PointerPressure,
+    /// Pen-only mode: while a stylus is near, mouse and touch presses don't draw and a finger
+    /// pans the view.
+    pub(crate) pen_only: bool,
+    pub(crate) finger_touches: FingerTouches,
+    /// The current primary press was rejected by pen-only mode and is ignored until released.
+    pub(crate) pointer_rejected: bool,
+    /// Screen position of a hovering stylus, for the brush outline.
+    pub(crate) pen_hover: Option<egui::Pos2>,
+    /// Active-layer tiles allocated ahead of contact under the hovering stylus, with their layer.
+    pub(crate) prewarmed_tiles: (usize, HashSet<(i32, i32)>),
+    /// Layer and stroke picked with the path tool.
+    pub(crate) path_selection: Option<(usize, usize)>,
+    /// Stroke and sample being dragged by the path tool, with the last pointer position.
+    pub(crate) path_drag: Option<(usize, usize, Vec2)>,
+    /// Paths of the active vector layer before the edit in progress, for its undo step.
+    pub(crate) path_edit_before: Option<Vec<VectorStroke>>,
+    /// Move tool drag: the press position and how far the layers have moved since.
+    pub(crate) layer_move: Option<(Vec2, (i32, i32))>,
+    /// Sample size and source of the Eyedropper tool.
+    pub(crate) eyedropper: EyedropperSettings,
+    /// Tool key presses and the tools they switched away from.
+    pub(crate) tool_switcher: ToolSwitcher,
 }
 
+/// Flattened composite written on exit, in the brushes folder.
+const SESSION_FILE: &str = "last_session.png";
+
+/// Input samples searched for where the stabilized line has got to.
+const GHOST_SAMPLES: usize = 256;
+
+/// Brush outlines smaller than this many screen pixels get a crosshair pointer instead.
+const MIN_OUTLINE_RADIUS: f32 = 3.0;
+
+/// Application name in the window title.
+pub const APP_TITLE: &str = "Rust Dab Painter";
+/// Time between passes that page cold tiles out while tile paging is on.
+const PAGING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Most airbrush dabs sprayed in one frame, so a stalled frame doesn't flood one spot.
+const MAX_AIRBRUSH_BURST: u32 = 8;
+
 impl PainterApp {
-    /// Initialize the UI, canvas, thread pool and GPU atlases.
+    /// Initialize the UI and thread pool. The app opens on the start screen; tiles and GPU
+    /// atlases are only created once a document is (see `rebuild_canvas`).
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let canvas_w = 4000;
-        let canvas_h = 4000;
-        let canvas = Canvas::new(canvas_w, canvas_h, Color32::WHITE, TILE_SIZE);
+        // Placeholder until a document is opened. Layers allocate lazily, so this costs nothing
+        let canvas = Canvas::new(DEFAULT_CANVAS_SIZE, DEFAULT_CANVAS_SIZE, Color32::WHITE, DEFAULT_TILE_SIZE);
         let layer_count = canvas.layers.len();
         let new_canvas = NewCanvasSettings::from_canvas(&canvas);
         let color_model = new_canvas.color_model;
@@ -105,69 +263,108 @@ impl PainterApp {
         let black = Color32::from_rgba_unmultiplied(0, 0, 0, 255);
         let brush = Brush::new(24.0, 20.0, black, 25.0);
 
-        let presets = vec![
-            BrushPreset {
-                name: "Pencil (Sketch)".to_string(),
-                brush: {
+        let mut presets = vec![
+            BrushPreset::new(
+                "Pencil (Sketch)",
+                {
                     let mut b = Brush::new(6.0, 60.0, black, 10.0);
                     b.brush_options.flow = 30.0;
                     b.brush_options.opacity = 0.8;
                     b.jitter = 0.5;
                     b
                 },
-            },
-            BrushPreset {
-                name: "Ink Pen".to_string(),
-                brush: {
+            ),
+            BrushPreset::new(
+                "Ink Pen",
+                {
                     let mut b = Brush::new(8.0, 100.0, black, 5.0);
                     b.stabilizer = 0.2;
                     b.brush_options.flow = 100.0;
                     b
                 },
-            },
-            BrushPreset {
-                name: "Soft Airbrush".to_string(),
-                brush: {
+            ),
+            BrushPreset::new(
+                "Soft Airbrush",
+                {
                     let mut b = Brush::new(50.0, 0.0, black, 10.0);
+                    b.airbrush = true;
                     b.brush_options.flow = 8.0;
                     b.brush_options.opacity = 0.6;
                     b
                 },
-            },
-            BrushPreset {
-                name: "Hard Round".to_string(),
-                brush: Brush::new(20.0, 100.0, black, 10.0),
-            },
-            BrushPreset {
-                name: "Eraser (Soft)".to_string(),
-                brush: {
+            ),
+            BrushPreset::new(
+                "Hard Round",
+                Brush::new(20.0, 100.0, black, 10.0),
+            ),
+            BrushPreset::new(
+                "Eraser (Soft)",
+                {
                     let mut b = Brush::new(40.0, 20.0, black, 10.0);
                     b.brush_options.blend_mode = BlendMode::Eraser;
                     b.brush_options.opacity = 0.8;
                     b
                 },
-            },
-            BrushPreset {
-                name: "Eraser (Hard)".to_string(),
-                brush: {
+            ),
+            BrushPreset::new(
+                "Eraser (Hard)",
+                {
                     let mut b = Brush::new(20.0, 100.0, black, 5.0);
                     b.brush_options.blend_mode = BlendMode::Eraser;
                     b
                 },
-            },
-            BrushPreset {
-                name: "Chalk".to_string(),
-                brush: {
+            ),
+            BrushPreset::new(
+                "Eraser (Pressure)",
+                {
+                    let mut b = Brush::new(30.0, 60.0, black, 5.0);
+                    b.brush_options.blend_mode = BlendMode::Eraser;
+                    b.brush_options.pressure_flow = true;
+                    b
+                },
+            ),
+            BrushPreset::new(
+                "Eraser (Soften)",
+                {
+                    let mut b = Brush::new(40.0, 30.0, black, 5.0);
+                    b.brush_options.blend_mode = BlendMode::Soften;
+                    b.brush_options.opacity = 0.5;
+                    b.brush_options.flow = 30.0;
+                    b.brush_options.pressure_flow = true;
+                    b
+                },
+            ),
+            BrushPreset::new(
+                "Marker (Multiply)",
+                {
+                    let mut b = Brush::new(24.0, 70.0, black, 8.0);
+                    b.brush_options.blend_mode = BlendMode::Multiply;
+                    b.brush_options.opacity = 0.5;
+                    b
+                },
+            ),
+            BrushPreset::new(
+                "Glow (Add)",
+                {
+                    let mut b = Brush::new(60.0, 0.0, Color32::from_rgb(255, 160, 60), 10.0);
+                    b.brush_options.blend_mode = BlendMode::Add;
+                    b.brush_options.flow = 20.0;
+                    b
+                },
+            ),
+            BrushPreset::new(
+                "Chalk",
+                {
                     let mut b = Brush::new(30.0, 80.0, black, 40.0);
                     b.jitter = 5.0;
                     b.brush_options.flow = 50.0;
                     b
                 },
-            },
-            BrushPreset {
-                name: "Pixel Art".to_string(),
-                brush: Brush::new_pixel(1.0, black),
-            },
+            ),
+            BrushPreset::new(
+                "Pixel Art",
+                Brush::new_pixel(1.0, black),
+            ),
         ];
 
         let max_threads = thread::available_parallelism()
@@ -175,104 +372,130 @@ impl PainterApp {
             .unwrap_or(8)
             .max(1);
         let thread_count = max_threads;
-        let pool = ThreadPoolBuilder::new()
-            .num_threads(thread_count)
-            .build()
-            .expect("failed to build thread pool");
+        let background_thread_count = (max_threads / 2).max(1);
+        let pool = build_pool(thread_count, "interactive").expect("failed to build thread pool");
+        let background_pool = build_pool(background_thread_count, "background").expect("failed to build thread pool");
 
-        let tiles_x = (canvas_w + TILE_SIZE - 1) / TILE_SIZE;
-        let tiles_y = (canvas_h + TILE_SIZE - 1) / TILE_SIZE;
         debug_assert!(
-            ATLAS_SIZE % TILE_SIZE == 0,
-            "ATLAS_SIZE must be divisible by TILE_SIZE for clean packing"
+            TILE_SIZES.iter().all(|&size| ATLAS_SIZE >= atlas_slot(size)),
+            "ATLAS_SIZE must fit at least one tile of every size with its gutter"
         );
 
-        let atlas_cols = (ATLAS_SIZE / TILE_SIZE).max(1);
-        let atlas_capacity = atlas_cols * atlas_cols;
-        let total_tiles = tiles_x * tiles_y;
-        let atlas_count = (total_tiles + atlas_capacity - 1) / atlas_capacity;
-
-        let mut atlases = Vec::new();
-        for idx in 0..atlas_count {
-            let img = egui::ColorImage::new([ATLAS_SIZE, ATLAS_SIZE], Color32::TRANSPARENT);
-            let texture = cc.egui_ctx.load_texture(
-                format!("canvas_atlas_{}", idx),
-                img,
-                TextureOptions::LINEAR,
-            );
-            atlases.push(TextureAtlas { texture });
-        }
-
-        let mut tiles = Vec::new();
-
-        for ty in 0..tiles_y {
-            for tx in 0..tiles_x {
-                let flat_idx = ty * tiles_x + tx;
-                let atlas_idx = flat_idx / atlas_capacity;
-                let atlas_local = flat_idx % atlas_capacity;
-                let atlas_tile_x = (atlas_local % atlas_cols) * TILE_SIZE;
-                let atlas_tile_y = (atlas_local / atlas_cols) * TILE_SIZE;
-                let tile_w = TILE_SIZE.min(canvas_w - tx * TILE_SIZE);
-                let tile_h = TILE_SIZE.min(canvas_h - ty * TILE_SIZE);
-                tiles.push(CanvasTile {
-                    dirty: true,
-                    atlas_idx,
-                    atlas_x: atlas_tile_x,
-                    atlas_y: atlas_tile_y,
-                    pixel_w: tile_w,
-                    pixel_h: tile_h,
-                    tx,
-                    ty,
-                });
-            }
-        }
-
         let dock_left = layout::default_left_dock();
         let dock_right = layout::default_right_dock();
 
         let brushes_path = std::env::current_dir()
             .unwrap_or_else(|_| PathBuf::from("."))
             .join("brushes");
+        preset_meta::load(&brushes_path, &mut presets);
+        let favorite_colors = favorites::load(&brushes_path);
+        let recent_files = RecentFiles::load(&brushes_path);
+        let journal = ActionJournal::load(&brushes_path);
+        crate::utils::i18n::load(&brushes_path);
+        let theme = Theme::load(&brushes_path);
+        let navigation = NavigationBindings::load(&brushes_path);
+        crate::styling::apply_global_style(&cc.egui_ctx, &theme);
 
         let mut app = Self {
             canvas,
             brush,
             brush_preview: BrushPreviewState::default(),
             presets,
+            favorite_colors,
+            show_favorites_bar: true,
             active_tool: super::tools::Tool::Brush,
+            transform_interpolation: Interpolation::Nearest,
             selection_manager: SelectionManager::new(),
             preset_previews: HashMap::new(),
             show_new_preset_modal: false,
             new_preset_name: String::new(),
             stroke: None,
+            stroke_samples: Vec::new(),
+            screen_diameter: None,
             is_drawing: false,
+            stroke_started: None,
+            airbrush_last: None,
             is_panning: false,
             is_rotating: false,
+            is_zooming: false,
+            nav_button: None,
+            navigation,
             rotation: 0.0,
+            rotation_drag: 0.0,
+            view_settings: crate::app::state::ViewSettings::default(),
+            atlas_filter: egui::TextureFilter::Linear,
             is_primary_down: false,
             brushes_path,
+            recent_files,
+            journal,
+            pending_recent: None,
             loaded_brush_tips: Vec::new(),
             histories: (0..layer_count).map(|_| History::new()).collect(),
+            document_history: History::new(),
+            snapshots: Vec::new(),
+            animation: Animation::new(),
+            playback: None,
+            histogram: ui::histogram::HistogramState::new(),
+            new_snapshot_name: String::new(),
+            selection_channels: Vec::new(),
+            new_channel_name: String::new(),
             current_undo_action: None,
             modified_tiles: HashSet::new(),
-            tiles,
-            atlases,
-            tiles_x,
-            tiles_y,
+            tiles: Vec::new(),
+            atlases: Vec::new(),
+            tiles_x: 0,
+            tiles_y: 0,
+            document_open: false,
             layer_caches: vec![HashMap::new(); layer_count],
             layer_cache_dirty: vec![HashSet::new(); layer_count],
             layer_ui_colors: vec![Color32::from_gray(40); layer_count],
             layer_dragging: None,
+            layer_label_filter: None,
+            selected_layers: BTreeSet::new(),
             floating_layer_idx: None,
             floating_buffer: None,
+            floating_pasted: false,
+            transform_session: None,
+            document_dpi: DEFAULT_DPI,
+            document_info: DocumentInfo::new(),
+            unsaved_changes: false,
+            document_path: None,
+            window_title: String::new(),
+            show_close_prompt: false,
+            close_after_save: false,
+            show_document_properties: false,
+            show_statistics: false,
+            guides: Vec::new(),
+            dragging_guide: None,
             zoom: 1.0,
             offset: Vec2 { x: 300.0, y: 100.0 },
             first_frame: true,
+            recenter_pending: false,
             use_masked_brush: true,
+            tile_paging: false,
+            tile_paging_budget_mb: 2048,
+            last_paging_pass: std::time::Instant::now(),
+            paging_pass: None,
+            clamp_strokes_to_canvas: false,
+            keybindings: Keybindings::default(),
+            color_nudge: ColorNudge::default(),
+            show_profiler_hud: false,
+            theme,
+            profiler_hud: ui::profiler_hud::ProfilerHud::new(),
+            filter_dialog: None,
+            show_stroke_selection: false,
+            background_dialog: None,
+            trim_dialog: None,
+            transform_preview_pending: false,
+            stroke_selection: ui::stroke_selection::StrokeSelectionSettings::default(),
+            stroke_selection_job: None,
             thread_count,
+            background_thread_count,
             max_threads,
-            pool,
+            pool: Arc::new(pool),
+            background_pool: Arc::new(background_pool),
             disable_lod: true,
+            show_tile_overlay: false,
             // force_full_upload: false,
             show_new_canvas_modal: false,
             show_export_modal: false,
@@ -281,6 +504,7 @@ impl PainterApp {
             export_message: None,
             export_in_progress: false,
             export_task: None,
+            export_bands: None,
             export_progress: 0.0,
             export_progress_rx: None,
             color_model,
@@ -288,13 +512,33 @@ impl PainterApp {
             show_general_settings: false,
             dock_left,
             dock_right,
+            floating_panels: Vec::new(),
             tablet: TabletInput::new(cc),
+            pointer_pressure: PointerPressure::default(),
+            pen_only: false,
+            finger_touches: FingerTouches::default(),
+            pointer_rejected: false,
+            pen_hover: None,
+            prewarmed_tiles: (0, HashSet::new()),
+            path_selection: None,
+            path_drag: None,
+            path_edit_before: None,
+            layer_move: None,
+            eyedropper: EyedropperSettings::default(),
+            tool_switcher: ToolSwitcher::default(),
         };
 
         app.load_brush_tips(cc.egui_ctx.clone());
         app
     }
 
+    /// Persist preset tags and favorites into the brushes folder.
+    pub(crate) fn save_preset_meta(&self) {
+        if let Err(e) = preset_meta::save(&self.brushes_path, &self.presets) {
+            log::error!("Failed to save preset tags: {:?}", e);
+        }
+    }
+
     pub fn load_brush_tips(&mut self, ctx: egui::Context) {
         // Create directory if it doesn't exist
         if !self.brushes_path.exists() {
@@ -306,87 +550,90 @@ impl PainterApp {
         if let Ok(entries) = std::fs::read_dir(&self.brushes_path) {
             for entry in entries.flatten() {
                 let path = entry.path();
-                if path.is_file() {
-                    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                        if ["png", "jpg", "jpeg", "bmp"].contains(&ext.to_lowercase().as_str()) {
-                            if let Ok(img) = image::open(&path) {
-                                let img = img.to_luma8();
-                                let width = img.width() as usize;
-                                let height = img.height() as usize;
-                                let data = img.into_raw();
-                                
-                                let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
-                                let shape = PixelBrushShape::Custom { width, height, data: data.clone() };
-                                
-                                // Create UI texture for the tip
-                                // Invert for display if needed, but usually brush tips are white on black or alpha.
-                                // PixelBrushShape uses 0-255 as alpha mask.
-                                let mut pixels = Vec::with_capacity(width * height);
-                                for &alpha in &data {
-                                    pixels.push(Color32::from_white_alpha(alpha));
-                                }
-                                let texture_img = egui::ColorImage {
-                                    size: [width, height],
-                                    pixels,
-                                };
-                                let texture = ctx.load_texture(
-                                    format!("brush_tip_{}", name),
-                                    texture_img,
-                                    TextureOptions::NEAREST,
-                                );
-
-                                self.loaded_brush_tips.push((name, shape, Some(texture)));
-                            }
-                        }
-                    }
+                if path.is_file()
+                    && let Some(ext) = path.extension().and_then(|s| s.to_str())
+                    && file_dialog::BRUSH_TIP_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+                    && let Ok(img) = image::open(&path)
+                {
+                    let width = img.width() as usize;
+                    let height = img.height() as usize;
+                    let rgba = img.to_rgba8();
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+                    // Transparent images with colored pixels stamp their own colors;
+                    // everything else is a grayscale alpha mask painted in the brush color.
+                    let is_image_tip = img.color().has_alpha()
+                        && rgba.pixels().any(|p| p[3] > 0 && (p[0] != p[1] || p[1] != p[2]));
+                    let (shape, pixels) = if is_image_tip {
+                        let data: Vec<Color32> = rgba
+                            .pixels()
+                            .map(|p| Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3]))
+                            .collect();
+                        (PixelBrushShape::Image { width, height, data: data.clone() }, data)
+                    } else {
+                        let data = img.to_luma8().into_raw();
+                        // Create UI texture for the tip
+                        // Invert for display if needed, but usually brush tips are white on black or alpha.
+                        // PixelBrushShape uses 0-255 as alpha mask.
+                        let pixels = data.iter().map(|&alpha| Color32::from_white_alpha(alpha)).collect();
+                        (PixelBrushShape::Custom { width, height, data }, pixels)
+                    };
+                    let texture_img = egui::ColorImage {
+                        size: [width, height],
+                        pixels,
+                    };
+                    let texture = ctx.load_texture(
+                        format!("brush_tip_{}", name),
+                        texture_img,
+                        TextureOptions::NEAREST,
+                    );
+
+                    self.loaded_brush_tips.push((name, shape, Some(texture)));
                 }
             }
         }
         self.loaded_brush_tips.sort_by(|a, b| a.0.cmp(&b.0));
     }
 
-    /// Mark all tiles that intersect a stroke segment as dirty so they re-upload to the atlas.
-    pub(crate) fn mark_segment_dirty(&mut self, start: Vec2, end: Vec2, radius: f32) {
-        let r_i32 = radius.ceil() as i32;
-
-        let min_x_f = start.x.min(end.x).floor() as i32 - r_i32;
-        let max_x_f = start.x.max(end.x).ceil() as i32 + r_i32;
-        let min_y_f = start.y.min(end.y).floor() as i32 - r_i32;
-        let max_y_f = start.y.max(end.y).ceil() as i32 + r_i32;
-
-        let canvas_w = self.canvas.width() as i32;
-        let canvas_h = self.canvas.height() as i32;
-
-        if max_x_f < 0 || min_x_f >= canvas_w || max_y_f < 0 || min_y_f >= canvas_h {
-            return;
+    /// Allocate the active layer's tiles under a hovering brush so the first dab
+    /// doesn't pay for them, and release earlier ones the pen moved away from
+    /// if they are still empty. `None` releases everything.
+    pub(crate) fn prewarm_tiles_around(&mut self, center: Option<Vec2>) {
+        let layer_idx = self.canvas.active_layer_idx;
+        let mut wanted = HashSet::new();
+        let locked = self.canvas.layers.get(layer_idx).is_none_or(|l| l.locked);
+        if let (Some(center), false) = (center, locked) {
+            let tile_size = self.canvas.tile_size() as f32;
+            let radius = self.brush.brush_options.canvas_diameter(self.zoom) / 2.0;
+            let max_tx = (self.canvas.width() as f32 / tile_size).ceil() as i32 - 1;
+            let max_ty = (self.canvas.height() as f32 / tile_size).ceil() as i32 - 1;
+            let min_tx = (((center.x - radius) / tile_size).floor() as i32).max(0);
+            let min_ty = (((center.y - radius) / tile_size).floor() as i32).max(0);
+            let hi_tx = (((center.x + radius) / tile_size).floor() as i32).min(max_tx);
+            let hi_ty = (((center.y + radius) / tile_size).floor() as i32).min(max_ty);
+            for ty in min_ty..=hi_ty {
+                for tx in min_tx..=hi_tx {
+                    wanted.insert((tx, ty));
+                }
+            }
         }
 
-        let min_x = min_x_f.max(0) as usize;
-        let max_x = max_x_f.min(canvas_w - 1) as usize;
-        let min_y = min_y_f.max(0) as usize;
-        let max_y = max_y_f.min(canvas_h - 1) as usize;
-
-        if min_x > max_x || min_y > max_y {
-            return;
+        let (prev_layer, prev) = std::mem::take(&mut self.prewarmed_tiles);
+        for &(tx, ty) in &prev {
+            if prev_layer != layer_idx || !wanted.contains(&(tx, ty)) {
+                self.canvas.refresh_tile_empty(prev_layer, tx, ty);
+            }
         }
-
-        let min_tx = min_x / TILE_SIZE;
-        let max_tx = max_x / TILE_SIZE;
-        let min_ty = min_y / TILE_SIZE;
-        let max_ty = max_y / TILE_SIZE;
-
-        for ty in min_ty..=max_ty {
-            for tx in min_tx..=max_tx {
-                if let Some(tile) = self.tile_mut(tx, ty) {
-                    tile.dirty = true;
-                    self.canvas.ensure_tile_exists(tx, ty);
-                }
+        for &(tx, ty) in &wanted {
+            if prev_layer != layer_idx || !prev.contains(&(tx, ty)) {
+                self.canvas.ensure_layer_tile_exists_i32(layer_idx, tx, ty);
             }
         }
+        self.prewarmed_tiles = (layer_idx, wanted);
     }
 
     /// Get a mutable reference to a tile entry if coordinates are valid.
-    fn tile_mut(&mut self, tx: usize, ty: usize) -> Option<&mut CanvasTile> {
+    pub(crate) fn tile_mut(&mut self, tx: usize, ty: usize) -> Option<&mut CanvasTile> {
         if tx >= self.tiles_x || ty >= self.tiles_y {
             return None;
         }
@@ -400,395 +647,1872 @@ impl PainterApp {
         if self.canvas.layers.get(self.canvas.active_layer_idx).map(|l| l.locked).unwrap_or(false) {
             return;
         }
+        self.bake_layer_offset(self.canvas.active_layer_idx);
 
-        self.stroke = Some(StrokeState::new());
+        // Screen-sized brushes keep the size they had on screen when the stroke began
+        let options = &mut self.brush.brush_options;
+        if options.screen_size && self.screen_diameter.is_none() {
+            self.screen_diameter = Some(options.diameter);
+            options.diameter = options.canvas_diameter(self.zoom);
+        }
+
+        self.begin_engine_stroke();
         self.is_drawing = true;
-        self.current_undo_action = Some(UndoAction { tiles: Vec::new(), selection: None, transform: None });
+        self.stroke_started = Some(std::time::Instant::now());
+        self.begin_journal_stroke();
+        self.continue_stroke(pos);
+    }
+
+    /// Start collecting the stroke for the journal, which writes it as untracked if it depends
+    /// on document state that replay doesn't restore.
+    fn begin_journal_stroke(&mut self) {
+        let idx = self.canvas.active_layer_idx;
+        self.journal.begin_stroke(idx, &self.brush);
+        if self.canvas.palette.constraint().is_some() {
+            self.journal.untrack_stroke("stroke_palette_constraint");
+        } else if self.canvas.layers.get(idx).is_some_and(|l| l.paint_behind) {
+            self.journal.untrack_stroke("stroke_paint_behind");
+        }
+    }
+
+    /// Start a fresh engine for the brush along with an empty undo step for it to fill.
+    fn begin_engine_stroke(&mut self) {
+        let mut engine = self.brush.engine.create();
+        engine.begin_stroke();
+        self.stroke = Some(engine);
+        self.stroke_samples.clear();
+        self.brush.dab_count = 0;
+        self.airbrush_last = None;
+        self.current_undo_action = Some(UndoAction::new("Brush stroke"));
         self.modified_tiles.clear();
+    }
 
-        if let Some(stroke) = &mut self.stroke {
-            stroke.add_point(
-                &self.pool,
-                &self.canvas,
-                &mut self.brush,
-                if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None },
-                pos,
-                self.current_undo_action.as_mut().unwrap(),
-                &mut self.modified_tiles,
-            );
-            self.mark_segment_dirty(pos, pos, self.brush.brush_options.diameter / 2.0);
+    /// Extend the current stroke to `pos` with the brush's current diameter and pressure.
+    pub(crate) fn continue_stroke(&mut self, pos: Vec2) {
+        let (Some(stroke), Some(undo_action)) = (&mut self.stroke, self.current_undo_action.as_mut()) else {
+            return;
+        };
+        let mut target = StrokeTarget {
+            pool: &self.pool,
+            canvas: &self.canvas,
+            selection: if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None },
+            undo_action,
+            modified_tiles: &mut self.modified_tiles,
+        };
+        stroke.add_sample(&mut target, &mut self.brush, pos);
+        // Moving paints on its own; the airbrush only sprays once the pointer rests
+        if self.stroke_samples.last().is_none_or(|&(last, _, _)| last != pos) {
+            self.airbrush_last = Some(std::time::Instant::now());
+        }
+        self.stroke_samples.push((pos, self.brush.brush_options.diameter, self.brush.pressure));
+        self.journal.stroke_point(pos, self.brush.brush_options.diameter, self.brush.pressure);
+    }
+
+    /// Spray the dabs the airbrush owes since it last sprayed, at the last sample's size and pressure.
+    fn spray_airbrush(&mut self) {
+        let (Some(stroke), Some(undo_action), Some(&(_, diameter, pressure))) =
+            (&mut self.stroke, self.current_undo_action.as_mut(), self.stroke_samples.last())
+        else {
+            return;
+        };
+        let now = std::time::Instant::now();
+        let last = *self.airbrush_last.get_or_insert(now);
+        let interval = 1.0 / self.brush.airbrush_rate.max(1.0);
+        let due = ((now - last).as_secs_f32() / interval) as u32;
+        if due == 0 {
+            return;
         }
+        self.airbrush_last = Some(last + std::time::Duration::from_secs_f32(due as f32 * interval));
+
+        let mut target = StrokeTarget {
+            pool: &self.pool,
+            canvas: &self.canvas,
+            selection: if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None },
+            undo_action,
+            modified_tiles: &mut self.modified_tiles,
+        };
+        let (base_diameter, base_pressure) = (self.brush.brush_options.diameter, self.brush.pressure);
+        self.brush.brush_options.diameter = diameter;
+        self.brush.pressure = pressure;
+        stroke.dwell(&mut target, &mut self.brush, due.min(MAX_AIRBRUSH_BURST));
+        self.brush.brush_options.diameter = base_diameter;
+        self.brush.pressure = base_pressure;
     }
 
     /// Finalize the current stroke and push it to the undo stack.
     pub(crate) fn finish_stroke(&mut self) {
-        if let Some(stroke) = &mut self.stroke {
-            stroke.end();
+        let mut samples = self.end_stroke_state();
+        if self.brush.post_smoothing > 0.0 {
+            samples = self.redraw_smoothed(samples);
+        }
+        self.record_vector_stroke(samples);
+        // Dabs only ever clear the empty flag; erasing can leave tiles with nothing in them
+        let layer_idx = self.canvas.active_layer_idx;
+        for &(tx, ty) in &self.modified_tiles {
+            self.canvas.refresh_tile_empty(layer_idx, tx as i32, ty as i32);
         }
         if let Some(action) = self.current_undo_action.take() {
             if !action.tiles.is_empty() {
                 if let Some(hist) = self.active_history_mut() {
                     hist.push_action(action);
                 }
+                let stats = &mut self.document_info.stats;
+                stats.strokes += 1;
+                stats.dabs += self.brush.dab_count;
+                self.mark_modified();
             }
         }
+        if let Some(started) = self.stroke_started.take() {
+            self.document_info.stats.painting_time += started.elapsed().as_secs_f64();
+        }
+        if self.selection_manager.has_selection() {
+            // Replay has no selection to clip the stroke to
+            self.journal.discard_stroke();
+            self.journal.record(JournalEntry::Untracked("stroke_in_selection".into()));
+        } else {
+            self.journal.end_stroke();
+        }
         self.stroke = None;
         self.is_drawing = false;
+        self.brush.pressure = 1.0;
+        self.brush.sample_time = None;
+        if let Some(diameter) = self.screen_diameter.take() {
+            self.brush.brush_options.diameter = diameter;
+        }
     }
 
-    /// Rotate a point around a center by the given cos/sin pair.
-    pub(crate) fn rotate_point(point: egui::Pos2, center: egui::Pos2, cos: f32, sin: f32) -> egui::Pos2 {
-        let delta = point - center;
-        egui::Pos2::new(
-            center.x + delta.x * cos - delta.y * sin,
-            center.y + delta.x * sin + delta.y * cos,
-        )
+    /// Let the engine paint whatever it held back and close the stroke, returning its raw samples.
+    fn end_stroke_state(&mut self) -> Vec<StrokeSample> {
+        let (Some(stroke), Some(undo_action)) = (&mut self.stroke, self.current_undo_action.as_mut()) else {
+            return Vec::new();
+        };
+        let mut target = StrokeTarget {
+            pool: &self.pool,
+            canvas: &self.canvas,
+            selection: if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None },
+            undo_action,
+            modified_tiles: &mut self.modified_tiles,
+        };
+        stroke.end_stroke(&mut target, &mut self.brush);
+        std::mem::take(&mut self.stroke_samples)
     }
 
-    /// Convert a screen-space position into canvas space considering zoom and rotation.
-    pub(crate) fn screen_to_canvas(
-        &self,
-        pos: egui::Pos2,
-        origin: egui::Pos2,
-        canvas_center: egui::Pos2,
-    ) -> (Vec2, bool) {
-        let cos = self.rotation.cos();
-        let sin = self.rotation.sin();
-        let delta = pos - canvas_center;
-        let unrotated = egui::Vec2::new(
-            delta.x * cos + delta.y * sin,
-            -delta.x * sin + delta.y * cos,
-        );
-        let point_world = canvas_center + unrotated;
-        let canvas_point = (point_world - origin) / self.zoom;
-        let clamped = Vec2 {
-            x: canvas_point.x.clamp(0.0, self.canvas.width() as f32),
-            y: canvas_point.y.clamp(0.0, self.canvas.height() as f32),
+    /// Put back the pixels under the stroke just drawn and paint it again along a fitted curve,
+    /// returning the samples that ended up painted.
+    fn redraw_smoothed(&mut self, samples: Vec<StrokeSample>) -> Vec<StrokeSample> {
+        let smoothed = smoothing::smooth_stroke(&samples, self.brush.post_smoothing);
+        if smoothed.is_empty() {
+            return samples;
+        }
+        let Some(mut raw) = self.current_undo_action.take() else {
+            return samples;
         };
-        let is_inside = canvas_point.x >= 0.0
-            && canvas_point.y >= 0.0
-            && canvas_point.x <= self.canvas.width() as f32
-            && canvas_point.y <= self.canvas.height() as f32;
-        (clamped, is_inside)
+        raw.swap_tiles(&mut self.canvas);
+
+        // Starting over replaces the raw samples in the journal with the smoothed ones
+        self.begin_journal_stroke();
+        self.begin_engine_stroke();
+        let (base_diameter, base_pressure) = (self.brush.brush_options.diameter, self.brush.pressure);
+        // Smoothed samples carry no input times
+        self.brush.sample_time = None;
+        for &(pos, diameter, pressure) in &smoothed {
+            self.brush.brush_options.diameter = diameter;
+            self.brush.pressure = pressure;
+            self.continue_stroke(pos);
+        }
+        self.brush.brush_options.diameter = base_diameter;
+        self.brush.pressure = base_pressure;
+        self.end_stroke_state()
     }
 
-    /// Recreate the canvas, tile metadata, atlases and undo history with new dimensions.
-    fn rebuild_canvas(
-        &mut self,
-        ctx: &egui::Context,
-        width: usize,
-        height: usize,
-        background: Color32,
-    ) {
-        self.canvas = Canvas::new(width, height, background, TILE_SIZE);
-        let layer_count = self.canvas.layers.len();
-        self.histories = (0..layer_count).map(|_| History::new()).collect();
-        self.layer_caches = vec![HashMap::new(); layer_count];
-        self.layer_cache_dirty = vec![HashSet::new(); layer_count];
-        self.layer_ui_colors = vec![Color32::from_gray(40); layer_count];
-        self.layer_dragging = None;
-        self.current_undo_action = None;
-        self.modified_tiles.clear();
-        self.stroke = None;
-        self.is_drawing = false;
-        self.is_panning = false;
-        self.is_rotating = false;
-        self.is_primary_down = false;
-
-        self.tiles_x = (width + TILE_SIZE - 1) / TILE_SIZE;
-        self.tiles_y = (height + TILE_SIZE - 1) / TILE_SIZE;
-
-        let atlas_cols = (ATLAS_SIZE / TILE_SIZE).max(1);
-        let atlas_capacity = atlas_cols * atlas_cols;
-        let total_tiles = self.tiles_x * self.tiles_y;
-        let atlas_count = (total_tiles + atlas_capacity - 1) / atlas_capacity;
-
-        self.texture_generation = self.texture_generation.wrapping_add(1);
-        self.atlases.clear();
-        for idx in 0..atlas_count {
-            let img = egui::ColorImage::new([ATLAS_SIZE, ATLAS_SIZE], Color32::TRANSPARENT);
-            let texture = ctx.load_texture(
-                format!("canvas_atlas_{}_{}", self.texture_generation, idx),
-                img,
-                TextureOptions::NEAREST,
-            );
-            self.atlases.push(TextureAtlas { texture });
+    /// Keep a stroke finished on a vector layer as an editable path.
+    fn record_vector_stroke(&mut self, samples: Vec<StrokeSample>) {
+        let layer_idx = self.canvas.active_layer_idx;
+        let (Some(vector), Some(action)) = (
+            self.canvas.layers.get_mut(layer_idx).and_then(|l| l.vector.as_mut()),
+            self.current_undo_action.as_mut(),
+        ) else {
+            return;
+        };
+        if samples.is_empty() || action.tiles.is_empty() {
+            return;
         }
+        action.vector = Some((layer_idx, vector.strokes.clone()));
+        vector.strokes.push(VectorStroke::new(samples, &self.brush));
+    }
 
-        self.tiles.clear();
-        for ty in 0..self.tiles_y {
-            for tx in 0..self.tiles_x {
-                let flat_idx = ty * self.tiles_x + tx;
-                let atlas_idx = flat_idx / atlas_capacity;
-                let atlas_local = flat_idx % atlas_capacity;
-                let atlas_tile_x = (atlas_local % atlas_cols) * TILE_SIZE;
-                let atlas_tile_y = (atlas_local / atlas_cols) * TILE_SIZE;
-                let tile_w = TILE_SIZE.min(width - tx * TILE_SIZE);
-                let tile_h = TILE_SIZE.min(height - ty * TILE_SIZE);
-                self.tiles.push(CanvasTile {
-                    dirty: true,
-                    atlas_idx,
-                    atlas_x: atlas_tile_x,
-                    atlas_y: atlas_tile_y,
-                    pixel_w: tile_w,
-                    pixel_h: tile_h,
-                    tx,
-                    ty,
-                });
+    /// Redraw every path of a vector layer into its tiles, recording the replaced pixels in
+    /// `action`. Paths ignore the selection, which may have changed since they were drawn.
+    fn rasterize_vector_layer(&mut self, layer_idx: usize, action: &mut UndoAction) {
+        let Some(strokes) = self.canvas.layers.get(layer_idx).and_then(|l| l.vector.as_ref()).map(|v| v.strokes.clone()) else {
+            return;
+        };
+        // Tiles the clear already snapshotted must not be snapshotted again by the dabs
+        let cleared = self.canvas.clear_layer(layer_idx, None, action);
+        let mut modified: HashSet<(usize, usize)> = cleared
+            .iter()
+            .filter(|&&(tx, ty)| tx >= 0 && ty >= 0)
+            .map(|&(tx, ty)| (tx as usize, ty as usize))
+            .collect();
+        // Dabs land on the active layer
+        let active = std::mem::replace(&mut self.canvas.active_layer_idx, layer_idx);
+        for stroke in strokes {
+            let mut brush = stroke.brush;
+            brush.sample_time = None;
+            let mut engine = brush.engine.create();
+            let mut target = StrokeTarget { pool: &self.pool, canvas: &self.canvas, selection: None, undo_action: action, modified_tiles: &mut modified };
+            engine.begin_stroke();
+            for &(pos, diameter, pressure) in &stroke.points {
+                brush.brush_options.diameter = (diameter * stroke.width).max(1.0);
+                brush.pressure = pressure;
+                engine.add_sample(&mut target, &mut brush, pos);
             }
+            engine.end_stroke(&mut target, &mut brush);
         }
+        self.canvas.active_layer_idx = active;
 
-        self.offset = Vec2 { x: 0.0, y: 0.0 };
-        self.zoom = 1.0;
-        self.rotation = 0.0;
-        self.first_frame = true;
-    }
-
-    pub(crate) fn apply_new_canvas(&mut self, ctx: &egui::Context) {
-        let (width, height) = self.new_canvas.dimensions_in_pixels();
-        self.color_model = self.new_canvas.color_model;
-        let background = self.new_canvas.background_color32(self.color_model);
-        self.rebuild_canvas(ctx, width, height, background);
-        self.brush.brush_options.color = Self::convert_color_for_model(self.brush.brush_options.color, self.color_model);
+        let mut coords: Vec<(i32, i32)> = modified.into_iter().map(|(tx, ty)| (tx as i32, ty as i32)).collect();
+        for &(tx, ty) in &coords {
+            self.canvas.refresh_tile_empty(layer_idx, tx, ty);
+        }
+        coords.extend(cleared);
+        self.canvas.invalidate_composite_tiles(&coords);
     }
 
-    fn convert_color_for_model(color: Color32, model: ColorModel) -> Color32 {
-        match model {
-            ColorModel::Rgba => color,
-            ColorModel::Grayscale => color,
+    /// Redraw the active vector layer after its paths changed from `before`, as one undo step.
+    pub(crate) fn commit_vector_edit(&mut self, before: Vec<VectorStroke>, label: &'static str) {
+        let layer_idx = self.canvas.active_layer_idx;
+        let mut action = UndoAction { vector: Some((layer_idx, before)), ..UndoAction::new(label) };
+        self.rasterize_vector_layer(layer_idx, &mut action);
+        if let Some(hist) = self.active_history_mut() {
+            hist.push_action(action);
         }
+        self.mark_untracked_change(label);
     }
 
-    #[allow(dead_code)]
-    fn layer_tile_image(
-        layer_idx: usize,
-        tx: usize,
-        ty: usize,
-        canvas: &Canvas,
-        layer_caches: &mut [HashMap<(usize, usize), egui::ColorImage>],
-        layer_cache_dirty: &mut [HashSet<(usize, usize)>],
-    ) -> egui::ColorImage {
-        if let Some(dirty) = layer_cache_dirty.get_mut(layer_idx) {
-            if dirty.remove(&(tx, ty)) {
-                layer_caches
-                    .get_mut(layer_idx)
-                    .and_then(|m| m.remove(&(tx, ty)));
-            }
+    /// Pick the path point under `pos` on the active vector layer and start dragging it.
+    pub(crate) fn begin_path_edit(&mut self, pos: Vec2) {
+        self.bake_layer_offset(self.canvas.active_layer_idx);
+        let radius = HANDLE_RADIUS * 2.0 / self.zoom;
+        let Some(vector) = self.canvas.layers.get(self.canvas.active_layer_idx).and_then(|l| l.vector.as_ref()) else {
+            return;
+        };
+        let hit = vector.hit_test(pos, radius);
+        self.path_selection = hit.map(|(stroke, _)| (self.canvas.active_layer_idx, stroke));
+        if let Some((stroke, point)) = hit {
+            self.path_drag = Some((stroke, point, pos));
+            self.path_edit_before = Some(vector.strokes.clone());
         }
+    }
 
-        if let Some(img) = layer_caches.get(layer_idx).and_then(|m| m.get(&(tx, ty))) {
-            return img.clone();
+    /// Move the dragged path point to `pos`.
+    pub(crate) fn drag_path_point(&mut self, pos: Vec2) {
+        let Some((stroke, point, last)) = self.path_drag.as_mut() else {
+            return;
+        };
+        let delta = pos - *last;
+        *last = pos;
+        if let Some(stroke) = self
+            .canvas
+            .layers
+            .get_mut(self.canvas.active_layer_idx)
+            .and_then(|l| l.vector.as_mut())
+            .and_then(|v| v.strokes.get_mut(*stroke))
+        {
+            stroke.bend(*point, delta);
         }
+    }
 
-        let tile_w = TILE_SIZE.min(canvas.width() - tx * TILE_SIZE);
-        let tile_h = TILE_SIZE.min(canvas.height() - ty * TILE_SIZE);
-        let mut img = egui::ColorImage::new([tile_w, tile_h], Color32::TRANSPARENT);
-
-        if let Some(data) = canvas.get_layer_tile_data(layer_idx, tx as i32, ty as i32) {
-            let tile_size = canvas.tile_size();
-            for y in 0..tile_h {
-                for x in 0..tile_w {
-                    let src_idx = y * tile_size + x;
-                    img.pixels[y * tile_w + x] = data[src_idx];
-                }
-            }
-        } else if layer_idx == 0 {
-            for px in &mut img.pixels {
-                *px = canvas.clear_color();
-            }
+    /// Release the dragged path point and redraw the layer along the new path.
+    pub(crate) fn end_path_edit(&mut self) {
+        if self.path_drag.take().is_none() {
+            return;
         }
-
-        if let Some(cache) = layer_caches.get_mut(layer_idx) {
-            cache.insert((tx, ty), img.clone());
+        if let Some(before) = self.path_edit_before.take() {
+            self.commit_vector_edit(before, "Edit path");
         }
-        img
     }
 
-    fn active_history_mut(&mut self) -> Option<&mut History> {
-        self.histories.get_mut(self.canvas.active_layer_idx)
+    /// Undo the latest action on the active layer and refresh the touched tiles.
+    pub(crate) fn undo(&mut self) {
+        self.step_history(false);
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn ensure_layer_history_len(&mut self) {
-        let target = self.canvas.layers.len();
-        if self.histories.len() < target {
-            self.histories
-                .extend((self.histories.len()..target).map(|_| History::new()));
-        } else if self.histories.len() > target {
-            self.histories.truncate(target);
-        }
+    /// Redo the next action on the active layer and refresh the touched tiles.
+    pub(crate) fn redo(&mut self) {
+        self.step_history(true);
     }
 
-    pub(crate) fn mark_all_tiles_dirty(&mut self) {
-        for tile in &mut self.tiles {
-            tile.dirty = true;
+    fn step_history(&mut self, redo: bool) {
+        let active_idx = self.canvas.active_layer_idx;
+        // Undoing past the first transform step puts the lifted pixels back
+        let floating = self.floating_layer_idx == Some(active_idx);
+        if floating && !redo && self.histories.get(active_idx).is_none_or(|h| h.undo_len() == 0) {
+            self.cancel_floating();
+            return;
         }
-    }
-
-    pub(crate) fn mark_layer_tiles_with_data_dirty(&mut self, layer_idx: usize) {
-        let tiles_x = self.tiles_x;
-        let tiles_y = self.tiles_y;
-        for ty in 0..tiles_y {
-            for tx in 0..tiles_x {
-                let has_data = self
-                    .canvas
-                    .lock_layer_tile_if_exists(layer_idx, tx, ty)
-                    .map(|cell_arc| cell_arc.lock().unwrap().data.is_some())
-                    .unwrap_or(false);
-                if has_data {
-                    if let Some(tile) = self.tile_mut(tx, ty) {
-                        tile.dirty = true;
-                    }
+        self.journal.record(if redo { JournalEntry::Redo { layer: active_idx } } else { JournalEntry::Undo { layer: active_idx } });
+        // Undo takes the newer of the layer's and the document's next steps, redo the older
+        let layer_next = self.histories.get(active_idx).and_then(|h| h.next_order(redo));
+        let history = match (self.document_history.next_order(redo), layer_next) {
+            (Some(doc), Some(layer)) if (doc < layer) == redo => Some(&mut self.document_history),
+            (Some(_), None) => Some(&mut self.document_history),
+            _ => self.histories.get_mut(active_idx),
+        };
+        let crop = history.as_ref().and_then(|h| h.next_crop(redo));
+        let affected = history
+            .map(|h| {
+                if redo {
+                    h.redo(&mut self.canvas, &mut self.selection_manager, &mut self.active_tool)
+                } else {
+                    h.undo(&mut self.canvas, &mut self.selection_manager, &mut self.active_tool)
                 }
+            })
+            .unwrap_or_default();
+        if let Some(crop) = crop {
+            self.follow_crop(crop.x, crop.y);
+        }
+        if !affected.is_empty() || crop.is_some() {
+            if !redo {
+                self.document_info.stats.undos += 1;
+            }
+            self.mark_modified();
+        }
+
+        // Reset transform tool state if active so it recalculates bounds
+        // Only reset if the undo action didn't restore a transform state
+        if let super::tools::Tool::Transform(ref mut info) = self.active_tool {
+            if info.bounds.is_none() && info.rotation == 0.0 && info.offset.x == 0.0 && info.offset.y == 0.0 {
+                 *info = crate::selection::transform::TransformInfo::default();
             }
         }
+        if floating {
+            self.transform_preview_pending = true;
+        }
     }
 
-    pub(crate) fn reorder_layers(&mut self, from: usize, to: usize) {
-        let len = self.canvas.layers.len();
-        if from >= len {
+    /// Shrink the canvas to the paint on the visible layers plus `padding` pixels on each
+    /// side, as one undo step. The padding never grows the canvas past its current edges.
+    /// Not for animations, see [`PainterApp::can_trim`].
+    pub(crate) fn trim_canvas(&mut self, padding: usize) {
+        if self.is_drawing || self.floating_layer_idx.is_some() || !self.can_trim() {
             return;
         }
-        let to = to.min(len.saturating_sub(1));
-        if from == to {
+        self.bake_layer_offsets();
+        let Some(bounds) = self.canvas.visible_content_bounds() else {
             return;
+        };
+        let canvas_rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(self.canvas.width() as f32, self.canvas.height() as f32));
+        let rect = bounds.expand(padding as f32).intersect(canvas_rect);
+        if rect == canvas_rect {
+            return;
+        }
+        let (x, y) = (rect.min.x as i32, rect.min.y as i32);
+        let (width, height) = (self.canvas.width(), self.canvas.height());
+        self.canvas.crop(x, y, rect.width() as usize, rect.height() as usize);
+        self.follow_crop(x, y);
+
+        // The selection is dropped rather than moved, and comes back on undo
+        let selection = self.selection_manager.shape().is_some().then(|| self.selection_manager.replace_shape(None));
+        let crop = CanvasCrop { x: -x, y: -y, width, height };
+        let action = UndoAction { selection, crop: Some(crop), ..UndoAction::new("Trim") };
+        self.document_history.push_action(action);
+        self.mark_untracked_change(&format!("trim {}x{} at {},{}", rect.width(), rect.height(), x, y));
+    }
+
+    /// Whether the canvas can be trimmed. A trim is an undo step of the document, and undoing
+    /// it couldn't restore the other frames, which keep their own undo stacks, so animations
+    /// can't be trimmed.
+    pub(crate) fn can_trim(&self) -> bool {
+        self.animation.frame_count() <= 1
+    }
+
+    /// Move what is kept in canvas coordinates outside the canvas along with a crop that put
+    /// the pixel at (`x`, `y`) at the origin: undo stacks and guides. Snapshots keep their own
+    /// size and come back uncropped.
+    fn follow_crop(&mut self, x: i32, y: i32) {
+        let tile_size = self.canvas.tile_size();
+        for history in &mut self.histories {
+            history.shift(-x, -y, tile_size);
+        }
+        for guide in &mut self.guides {
+            guide.position -= if guide.vertical { x } else { y } as f32;
+        }
+    }
+
+    /// Erase the active layer (or the selected part of it) as an undoable action.
+    pub(crate) fn clear_active_layer(&mut self) {
+        let layer_idx = self.canvas.active_layer_idx;
+        let Some(layer) = self.canvas.layers.get(layer_idx) else {
+            return;
+        };
+        if layer.locked || self.is_drawing {
+            return;
+        }
+        let is_adjustment = layer.adjustment.is_some();
+        self.bake_layer_offset(layer_idx);
+
+        // Vector layers drop the paths instead, those touching the selection if there is one
+        if let Some(vector) = self.canvas.layers[layer_idx].vector.as_mut() {
+            let before = vector.strokes.clone();
+            if self.selection_manager.has_selection() {
+                let selection = &self.selection_manager;
+                vector.strokes.retain(|s| s.points.iter().all(|p| selection.coverage(p.0) <= 0.0));
+            } else {
+                vector.strokes.clear();
+            }
+            if vector.strokes.len() != before.len() {
+                self.path_selection = None;
+                self.commit_vector_edit(before, "Clear layer");
+            }
+            return;
+        }
+
+        let mut action = UndoAction::new("Clear layer");
+        let selection = if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None };
+        self.canvas.clear_layer(layer_idx, selection, &mut action);
+        if action.tiles.is_empty() {
+            return;
+        }
+        if let Some(hist) = self.active_history_mut() {
+            hist.push_action(action);
+        }
+        if self.selection_manager.has_selection() {
+            // Replay has no selection to limit the clear to
+            self.mark_untracked_change("clear_selection");
+        } else {
+            self.journal.record(JournalEntry::ClearLayer { layer: layer_idx });
+            self.mark_modified();
+        }
+
+        // An adjustment's mask reaches every layer below it
+        if is_adjustment {
+            self.mark_all_tiles_dirty();
+        }
+    }
+
+    /// Paste the clipboard image as a floating layer centered on the canvas, ready to place
+    /// with the transform tool and commit with Enter.
+    pub(crate) fn paste_image(&mut self) {
+        if self.floating_layer_idx.is_some() || self.is_drawing {
+            return;
+        }
+        let image = match crate::utils::clipboard::read_image() {
+            Ok(image) => image,
+            Err(err) => {
+                log::warn!("Nothing to paste: {}", err);
+                return;
+            }
+        };
+        let origin = (
+            (self.canvas.width() as i32 - image.size[0] as i32) / 2,
+            (self.canvas.height() as i32 - image.size[1] as i32) / 2,
+        );
+        let Some(idx) = self.canvas.float_image(&image, origin) else {
+            return;
+        };
+
+        self.transform_session = Some(TransformSession {
+            selection: self.selection_manager.shape().cloned(),
+            lift: UndoAction::new("Lift"),
+            drag_from: None,
+        });
+        self.floating_layer_idx = Some(idx);
+        self.floating_buffer = Some(self.canvas.capture_layer_pixels(idx));
+        self.floating_pasted = true;
+        self.histories.insert(idx, History::new());
+        self.layer_caches.insert(idx, HashMap::new());
+        self.layer_cache_dirty.insert(idx, HashSet::new());
+        self.layer_ui_colors.insert(idx, Color32::from_gray(40));
+        self.selected_layers.clear();
+        self.selection_manager.clear_selection();
+        self.active_tool = super::tools::Tool::Transform(crate::selection::transform::TransformInfo::default());
+        self.mark_all_tiles_dirty();
+    }
+
+    /// Put the visible layers, flattened, on the system clipboard: just the selected pixels when
+    /// there is a selection, trimmed and with its soft edge in the alpha, else the whole canvas.
+    pub(crate) fn copy_merged(&mut self) {
+        let image = if self.selection_manager.has_selection() {
+            let Some(image) = crate::utils::exporter::selection_image(&self.canvas, &self.selection_manager) else {
+                log::warn!("Nothing to copy: the selection lies outside the canvas");
+                return;
+            };
+            image
+        } else {
+            let (width, height) = (self.canvas.width(), self.canvas.height());
+            let mut image = egui::ColorImage::new([width, height], Color32::TRANSPARENT);
+            self.canvas.write_region_to_color_image(0, 0, width, height, &mut image, 1);
+            image
+        };
+        if let Err(err) = crate::utils::clipboard::write_image(&image) {
+            log::error!("Failed to copy to the clipboard: {}", err);
+        }
+    }
+
+    /// Lift the selection, or the whole active layer when nothing is selected, into a floating
+    /// layer for the transform tool.
+    pub(crate) fn float_for_transform(&mut self) {
+        let source_idx = self.canvas.active_layer_idx;
+        self.bake_layer_offset(source_idx);
+        let before = self.canvas.capture_layer_pixels(source_idx);
+        let floated = if self.selection_manager.has_selection() {
+            self.canvas.float_selection(&self.selection_manager)
+        } else {
+            self.canvas.float_layer()
+        };
+        let Some(idx) = floated else {
+            return;
+        };
+        let mut lift = UndoAction::new("Lift");
+        lift.record_changed_tiles(&self.canvas, source_idx, &before);
+        self.transform_session = Some(TransformSession { selection: self.selection_manager.shape().cloned(), lift, drag_from: None });
+
+        self.floating_layer_idx = Some(idx);
+        self.floating_pasted = false;
+        self.floating_buffer = Some(self.canvas.capture_layer_pixels(idx));
+        self.histories.insert(idx, History::new());
+        self.layer_caches.insert(idx, HashMap::new());
+        self.layer_cache_dirty.insert(idx, HashSet::new());
+        self.layer_ui_colors.insert(idx, Color32::from_gray(40));
+        self.selected_layers.clear();
+        self.mark_all_tiles_dirty();
+    }
+
+    /// Record the transform drag that just ended on the floating layer's history, so it can
+    /// be stepped back before the transform is committed.
+    pub(crate) fn record_transform_step(&mut self) {
+        let (Some(idx), Some(session), super::tools::Tool::Transform(info)) = (self.floating_layer_idx, self.transform_session.as_mut(), self.active_tool) else {
+            return;
+        };
+        let Some(from) = session.drag_from.take() else {
+            return;
+        };
+        if (from.offset, from.rotation, from.scale, from.pivot) == (info.offset, info.rotation, info.scale, info.pivot) {
+            return;
+        }
+        let action = UndoAction { transform: Some(from), ..UndoAction::new("Transform step") };
+        if let Some(hist) = self.histories.get_mut(idx) {
+            hist.push_action(action);
+        }
+    }
+
+    /// Merge the floating layer down where it was placed and reset the transform tool. The
+    /// lift and the placement go on the layer's history as one entry that also brings back
+    /// the selection.
+    pub(crate) fn commit_floating(&mut self) {
+        let Some(idx) = self.floating_layer_idx else {
+            return;
+        };
+        if self.transform_preview_pending {
+            self.transform_preview_pending = false;
+            self.refresh_transform_preview();
+        }
+        let session = self.transform_session.take();
+        let target_idx = idx.saturating_sub(1);
+        self.bake_layer_offset(target_idx);
+        let before = session.as_ref().map(|_| self.canvas.capture_layer_pixels(target_idx));
+        // Merging onto a faded background fades the paper color with it
+        let clear_color = self.canvas.clear_color();
+        // Lifted pixels that never moved go back exactly as they were
+        let unmoved = !self.floating_pasted && self.floating_buffer.as_ref() == Some(&self.canvas.capture_layer_pixels(idx));
+        if unmoved {
+            self.canvas.put_back_lifted(idx);
+        } else {
+            self.canvas.merge_layer_down(idx);
+        }
+        self.selection_manager.clear_selection();
+        self.end_floating(idx);
+
+        let (Some(session), Some(before)) = (session, before) else {
+            return;
+        };
+        let mut place = UndoAction::new("Place");
+        place.record_changed_tiles(&self.canvas, target_idx, &before);
+        place.background = (self.canvas.clear_color() != clear_color).then_some(clear_color);
+        if session.lift.tiles.is_empty() && place.tiles.is_empty() && place.background.is_none() {
+            return;
+        }
+        let action = UndoAction {
+            selection: Some(session.selection),
+            composite: Some(CompositeAction { steps: vec![session.lift, place] }),
+            ..UndoAction::new("Transform")
+        };
+        if let Some(hist) = self.histories.get_mut(target_idx) {
+            hist.push_action(action);
+        }
+        self.mark_untracked_change("transform");
+    }
+
+    /// Drop the floating layer's transform: lifted pixels go back where they came from
+    /// and a pasted image is discarded.
+    pub(crate) fn cancel_floating(&mut self) {
+        let (Some(idx), Some(buffer)) = (self.floating_layer_idx, self.floating_buffer.take()) else {
+            return;
+        };
+        if self.floating_pasted {
+            self.canvas.layers.remove(idx);
+        } else {
+            let untransformed = crate::selection::transform::Affine::translation(Vec2::new(0.0, 0.0));
+            self.canvas.preview_transform(idx, &buffer, untransformed, crate::selection::transform::Interpolation::Nearest);
+            self.canvas.put_back_lifted(idx);
+        }
+        self.end_floating(idx);
+    }
+
+    /// Forget the floating layer at `idx` after the canvas dropped it.
+    fn end_floating(&mut self, idx: usize) {
+        // Back on the layer the pixels went to, where the transform's undo step lives
+        self.canvas.active_layer_idx = idx.saturating_sub(1);
+        self.floating_layer_idx = None;
+        self.floating_buffer = None;
+        self.transform_session = None;
+        self.floating_pasted = false;
+        self.transform_preview_pending = false;
+        if let super::tools::Tool::Transform(ref mut info) = self.active_tool {
+            *info = crate::selection::transform::TransformInfo::default();
+        }
+        if idx < self.histories.len() {
+            self.histories.remove(idx);
+            self.layer_caches.remove(idx);
+            self.layer_cache_dirty.remove(idx);
+            self.layer_ui_colors.remove(idx);
+        }
+        self.selected_layers.clear();
+        self.mark_all_tiles_dirty();
+    }
+
+    /// Execute a command triggered by a shortcut or menu entry.
+    pub(crate) fn run_command(&mut self, command: Command) {
+        if let Command::SwitchTool(target) = command {
+            self.tool_switcher.press(target);
+            return;
+        }
+        // Undo or clearing underneath a filter preview would fight with its captured pixels
+        if self.filter_dialog.is_some() {
+            return;
+        }
+        match command {
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::ClearLayer => self.clear_active_layer(),
+            Command::Deselect if self.floating_layer_idx.is_some() => self.cancel_floating(),
+            Command::Deselect => self.selection_manager.clear_selection(),
+            Command::Cancel => self.cancel(),
+            Command::Confirm => self.confirm(),
+            Command::PasteImage => self.paste_image(),
+            Command::CopyMerged => self.copy_merged(),
+            Command::ExportSelection => self.open_export_selection(),
+            Command::WarmerColor => self.nudge_color(1.0, 0.0),
+            Command::CoolerColor => self.nudge_color(-1.0, 0.0),
+            Command::LighterColor => self.nudge_color(0.0, 1.0),
+            Command::DarkerColor => self.nudge_color(0.0, -1.0),
+            Command::RecenterView => self.recenter_pending = true,
+            Command::SwitchTool(_) => {}
+        }
+    }
+
+    /// Escape: back out of the innermost thing in progress, only dropping the selection when
+    /// nothing else is going on. A polygon being placed goes first, then a floating transform.
+    pub(crate) fn cancel(&mut self) {
+        if self.selection_manager.is_placing_polygon() {
+            self.selection_manager.cancel_polygon();
+        } else if self.floating_layer_idx.is_some() {
+            self.cancel_floating();
+        } else {
+            self.selection_manager.clear_selection();
+        }
+    }
+
+    /// Enter: finish the innermost thing in progress, in the same order as [`Self::cancel`].
+    pub(crate) fn confirm(&mut self) {
+        if self.selection_manager.is_placing_polygon() {
+            self.selection_manager.close_polygon();
+        } else if self.floating_layer_idx.is_some() {
+            self.commit_floating();
+        }
+    }
+
+    /// Step the brush color warmer/cooler and lighter/darker by the configured nudge sizes.
+    /// Grayscale documents only take the lightness part.
+    pub(crate) fn nudge_color(&mut self, warmth: f32, lightness: f32) {
+        let warmth = if self.color_model == ColorModel::Grayscale { 0.0 } else { warmth };
+        let color = self.color_nudge.apply(self.brush.brush_options.color, warmth, lightness);
+        self.brush.brush_options.color = Self::convert_color_for_model(color, self.color_model);
+    }
+
+    /// Set the brush color from the canvas at `pos`, averaged over the eyedropper's sample size.
+    /// Transparent spots leave the color alone.
+    pub(crate) fn pick_color(&mut self, pos: Vec2) {
+        let layer = (!self.eyedropper.all_layers).then_some(self.canvas.active_layer_idx);
+        let radius = self.eyedropper.size.radius();
+        let Some(color) = self.canvas.sample_color(pos.x.floor() as i32, pos.y.floor() as i32, radius, layer) else {
+            return;
+        };
+        self.brush.brush_options.color = Self::convert_color_for_model(color, self.color_model);
+        self.brush.is_changed = true;
+        self.brush_preview.dirty = true;
+    }
+
+    /// Open the export dialog for the selected pixels only.
+    pub(crate) fn open_export_selection(&mut self) {
+        if !self.selection_manager.has_selection() {
+            return;
+        }
+        self.open_export(ExportScope::Selection);
+    }
+
+    /// Show the export dialog for the canvas, the selection or the animation frames.
+    pub(crate) fn open_export(&mut self, scope: ExportScope) {
+        self.export_settings.scope = scope;
+        self.export_settings.chosen_path = None;
+        self.export_settings.base_name = self
+            .document_path
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .map_or_else(|| "export".to_string(), |stem| stem.to_string_lossy().into_owned());
+        self.export_message = None;
+        self.show_export_modal = true;
+    }
+
+    /// Re-render the floating layer under the current transform.
+    pub(crate) fn refresh_transform_preview(&mut self) {
+        let super::tools::Tool::Transform(info) = self.active_tool else {
+            return;
+        };
+        let (Some(idx), Some(buffer)) = (self.floating_layer_idx, &self.floating_buffer) else {
+            return;
+        };
+        let interpolation = self.transform_interpolation;
+        let canvas = &mut self.canvas;
+        self.pool.install(|| {
+            canvas.preview_transform(idx, buffer, info.affine(), interpolation)
+        });
+    }
+
+    /// Store a named copy of the whole document.
+    pub(crate) fn take_snapshot(&mut self, name: String) {
+        let snapshot = self.canvas.snapshot(name);
+        self.snapshots.push(snapshot);
+    }
+
+    /// Restore a stored snapshot; per-layer undo stacks are reset since they no longer line up.
+    pub(crate) fn restore_snapshot(&mut self, idx: usize) {
+        let Some(snapshot) = self.snapshots.get(idx) else {
+            return;
+        };
+        self.canvas.restore_snapshot(snapshot);
+
+        let layer_count = self.canvas.layers.len();
+        self.histories = (0..layer_count).map(|_| History::new()).collect();
+        self.document_history = History::new();
+        self.layer_caches = vec![HashMap::new(); layer_count];
+        self.layer_cache_dirty = vec![HashSet::new(); layer_count];
+        self.layer_ui_colors.resize(layer_count, Color32::from_gray(40));
+        self.selected_layers.clear();
+        self.floating_layer_idx = None;
+        self.floating_buffer = None;
+        self.transform_session = None;
+        self.current_undo_action = None;
+        self.stroke = None;
+        self.is_drawing = false;
+        self.stroke_started = None;
+        self.mark_all_tiles_dirty();
+        self.mark_untracked_change("restore_snapshot");
+    }
+
+    /// Frames can't change under an unfinished stroke, transform or filter preview.
+    pub(crate) fn can_switch_frames(&self) -> bool {
+        !self.is_drawing && self.floating_layer_idx.is_none() && self.filter_dialog.is_none()
+    }
+
+    /// Make frame `idx` the one being edited.
+    pub(crate) fn switch_frame(&mut self, idx: usize) {
+        if !self.can_switch_frames() || idx == self.animation.current() || idx >= self.animation.frame_count() {
+            return;
+        }
+        let histories = std::mem::take(&mut self.histories);
+        let histories = self.animation.switch_to(&mut self.canvas, idx, histories).unwrap_or_default();
+        self.load_frame_state(histories);
+        self.journal.record(JournalEntry::Untracked(format!("switch_frame {idx}")));
+    }
+
+    /// Add a frame after the current one, blank or as a copy, and switch to it.
+    pub(crate) fn add_frame(&mut self, duplicate: bool) {
+        if !self.can_switch_frames() {
+            return;
+        }
+        let histories = std::mem::take(&mut self.histories);
+        let histories = self.animation.insert_frame(&mut self.canvas, duplicate, histories);
+        self.playback = None;
+        self.load_frame_state(histories);
+        self.mark_untracked_change("add_frame");
+    }
+
+    pub(crate) fn delete_frame(&mut self) {
+        if !self.can_switch_frames() {
+            return;
+        }
+        if let Some(histories) = self.animation.delete_frame(&mut self.canvas) {
+            self.playback = None;
+            self.load_frame_state(histories);
+            self.mark_untracked_change("delete_frame");
+        }
+    }
+
+    /// Resync the per-layer state with a frame that was just loaded into the canvas.
+    fn load_frame_state(&mut self, mut histories: Vec<History>) {
+        let layer_count = self.canvas.layers.len();
+        histories.resize_with(layer_count, History::new);
+        self.histories = histories;
+        // Document steps like trims can't be undone on one frame alone
+        self.document_history = History::new();
+        self.layer_caches = vec![HashMap::new(); layer_count];
+        self.layer_cache_dirty = vec![HashSet::new(); layer_count];
+        self.layer_ui_colors.resize(layer_count, Color32::from_gray(40));
+        self.selected_layers.clear();
+        self.current_undo_action = None;
+        self.stroke = None;
+        self.mark_all_tiles_dirty();
+    }
+
+    /// Pull the view back so at least `keep_canvas_visible` screen pixels of the (rotated)
+    /// canvas stay inside `viewport`, the rect the view offset is measured from.
+    pub(crate) fn clamp_view_offset(&mut self, viewport: egui::Rect) {
+        let margin = self.view_settings.keep_canvas_visible;
+        if margin <= 0.0 {
+            return;
+        }
+        let size = egui::vec2(self.canvas.width() as f32, self.canvas.height() as f32) * self.zoom;
+        let (cos, sin) = (self.rotation.cos().abs(), self.rotation.sin().abs());
+        // Half extents of the rotated canvas, which turns about its center
+        let half = egui::vec2(size.x * cos + size.y * sin, size.x * sin + size.y * cos) * 0.5;
+        let center = egui::vec2(self.offset.x, self.offset.y) + size * 0.5;
+        let keep = egui::vec2(margin.min(half.x * 2.0), margin.min(half.y * 2.0));
+        let cx = center.x.clamp(keep.x - half.x, viewport.width() - keep.x + half.x);
+        let cy = center.y.clamp(keep.y - half.y, viewport.height() - keep.y + half.y);
+        self.offset = Vec2::new(cx - size.x * 0.5, cy - size.y * 0.5);
+    }
+
+    /// Rotate a point around a center by the given cos/sin pair.
+    pub(crate) fn rotate_point(point: egui::Pos2, center: egui::Pos2, cos: f32, sin: f32) -> egui::Pos2 {
+        let delta = point - center;
+        egui::Pos2::new(
+            center.x + delta.x * cos - delta.y * sin,
+            center.y + delta.x * sin + delta.y * cos,
+        )
+    }
+
+    /// Convert a screen-space position into canvas space without clamping to the canvas rect.
+    pub(crate) fn screen_to_canvas_unclamped(
+        &self,
+        pos: egui::Pos2,
+        origin: egui::Pos2,
+        canvas_center: egui::Pos2,
+    ) -> Vec2 {
+        let cos = self.rotation.cos();
+        let sin = self.rotation.sin();
+        let delta = pos - canvas_center;
+        let unrotated = egui::Vec2::new(
+            delta.x * cos + delta.y * sin,
+            -delta.x * sin + delta.y * cos,
+        );
+        let point_world = canvas_center + unrotated;
+        let canvas_point = (point_world - origin) / self.zoom;
+        Vec2::new(canvas_point.x, canvas_point.y)
+    }
+
+    /// Canvas position for brush samples: clamped to the edge or left free so dabs clip naturally.
+    pub(crate) fn stroke_position(
+        &self,
+        pos: egui::Pos2,
+        origin: egui::Pos2,
+        canvas_center: egui::Pos2,
+    ) -> Vec2 {
+        if self.clamp_strokes_to_canvas {
+            self.screen_to_canvas(pos, origin, canvas_center).0
+        } else {
+            self.screen_to_canvas_unclamped(pos, origin, canvas_center)
+        }
+    }
+
+    /// Convert a screen-space position into canvas space considering zoom and rotation.
+    pub(crate) fn screen_to_canvas(
+        &self,
+        pos: egui::Pos2,
+        origin: egui::Pos2,
+        canvas_center: egui::Pos2,
+    ) -> (Vec2, bool) {
+        let canvas_point = self.screen_to_canvas_unclamped(pos, origin, canvas_center);
+        let clamped = Vec2 {
+            x: canvas_point.x.clamp(0.0, self.canvas.width() as f32),
+            y: canvas_point.y.clamp(0.0, self.canvas.height() as f32),
+        };
+        let is_inside = canvas_point.x >= 0.0
+            && canvas_point.y >= 0.0
+            && canvas_point.x <= self.canvas.width() as f32
+            && canvas_point.y <= self.canvas.height() as f32;
+        (clamped, is_inside)
+    }
+
+    /// Recreate the canvas, tile metadata, atlases and undo history with new dimensions.
+    fn rebuild_canvas(
+        &mut self,
+        ctx: &egui::Context,
+        width: usize,
+        height: usize,
+        background: Color32,
+        tile_size: usize,
+    ) {
+        self.canvas = Canvas::new(width, height, background, tile_size);
+        let layer_count = self.canvas.layers.len();
+        self.histories = (0..layer_count).map(|_| History::new()).collect();
+        self.document_history = History::new();
+        self.snapshots.clear();
+        self.selection_channels.clear();
+        self.animation = Animation::new();
+        self.playback = None;
+        self.layer_caches = vec![HashMap::new(); layer_count];
+        self.layer_cache_dirty = vec![HashSet::new(); layer_count];
+        self.layer_ui_colors = vec![Color32::from_gray(40); layer_count];
+        self.layer_dragging = None;
+        self.layer_label_filter = None;
+        self.selected_layers.clear();
+        self.current_undo_action = None;
+        self.modified_tiles.clear();
+        self.stroke = None;
+        self.is_drawing = false;
+        self.stroke_started = None;
+        self.is_panning = false;
+        self.is_rotating = false;
+        self.is_zooming = false;
+        self.nav_button = None;
+        self.is_primary_down = false;
+        self.document_dpi = DEFAULT_DPI;
+        self.document_info = DocumentInfo::new();
+        self.unsaved_changes = false;
+        self.document_path = None;
+        self.guides.clear();
+        self.dragging_guide = None;
+
+        self.rebuild_atlases(ctx);
+        self.offset = Vec2 { x: 0.0, y: 0.0 };
+        self.zoom = 1.0;
+        self.rotation = 0.0;
+        self.document_open = true;
+    }
+
+    /// Whether the display tiles still cover the canvas exactly.
+    fn atlases_match_canvas(&self) -> bool {
+        let (width, height, tile_size) = (self.canvas.width(), self.canvas.height(), self.canvas.tile_size());
+        self.tiles_x == width.div_ceil(tile_size)
+            && self.tiles_y == height.div_ceil(tile_size)
+            && self.tiles.last().is_some_and(|tile| {
+                tile.tx * tile_size + tile.pixel_w == width && tile.ty * tile_size + tile.pixel_h == height
+            })
+    }
+
+    /// Recreate the atlases and display tiles for the canvas size, and fit the canvas in the view.
+    fn rebuild_atlases(&mut self, ctx: &egui::Context) {
+        let (width, height, tile_size) = (self.canvas.width(), self.canvas.height(), self.canvas.tile_size());
+        self.tiles_x = width.div_ceil(tile_size);
+        self.tiles_y = height.div_ceil(tile_size);
+
+        let slot = atlas_slot(tile_size);
+        let atlas_cols = (ATLAS_SIZE / slot).max(1);
+        let atlas_capacity = atlas_cols * atlas_cols;
+        let total_tiles = self.tiles_x * self.tiles_y;
+        let atlas_count = (total_tiles + atlas_capacity - 1) / atlas_capacity;
+
+        self.texture_generation = self.texture_generation.wrapping_add(1);
+        self.atlases.clear();
+        for idx in 0..atlas_count {
+            let img = egui::ColorImage::new([ATLAS_SIZE, ATLAS_SIZE], Color32::TRANSPARENT);
+            let texture = ctx.load_texture(
+                format!("canvas_atlas_{}_{}", self.texture_generation, idx),
+                img,
+                TextureOptions::NEAREST,
+            );
+            self.atlases.push(TextureAtlas { texture });
+        }
+
+        self.tiles.clear();
+        for ty in 0..self.tiles_y {
+            for tx in 0..self.tiles_x {
+                let flat_idx = ty * self.tiles_x + tx;
+                let atlas_idx = flat_idx / atlas_capacity;
+                let atlas_local = flat_idx % atlas_capacity;
+                let atlas_tile_x = (atlas_local % atlas_cols) * slot + ATLAS_GUTTER;
+                let atlas_tile_y = (atlas_local / atlas_cols) * slot + ATLAS_GUTTER;
+                let tile_w = tile_size.min(width - tx * tile_size);
+                let tile_h = tile_size.min(height - ty * tile_size);
+                self.tiles.push(CanvasTile {
+                    dirty: true,
+                    atlas_idx,
+                    atlas_x: atlas_tile_x,
+                    atlas_y: atlas_tile_y,
+                    pixel_w: tile_w,
+                    pixel_h: tile_h,
+                    tx,
+                    ty,
+                });
+            }
+        }
+
+        self.layer_caches.iter_mut().for_each(HashMap::clear);
+        self.histogram = ui::histogram::HistogramState::new();
+        self.first_frame = true;
+    }
+
+    pub(crate) fn apply_new_canvas(&mut self, ctx: &egui::Context) {
+        let (width, height) = self.new_canvas.dimensions_in_pixels();
+        self.color_model = self.new_canvas.color_model;
+        let background = self.new_canvas.background_color32(self.color_model);
+        let tile_size = self.new_canvas.tile_size.unwrap_or_else(|| auto_tile_size(width, height, self.thread_count));
+        self.rebuild_canvas(ctx, width, height, background, tile_size);
+        self.document_dpi = self.new_canvas.resolution;
+        self.journal.record(JournalEntry::NewDocument { width, height, tile_size, background });
+        self.brush.brush_options.color = Self::convert_color_for_model(self.brush.brush_options.color, self.color_model);
+    }
+
+    /// Folder file dialogs start in: the document's, else that of the most recent file, else
+    /// the user's Pictures folder.
+    pub(crate) fn dialog_dir(&self) -> Option<PathBuf> {
+        self.document_path
+            .iter()
+            .chain(self.recent_files.entries().first())
+            .find_map(|p| p.parent().filter(|dir| dir.is_dir()).map(Path::to_path_buf))
+            .or_else(file_dialog::pictures_dir)
+    }
+
+    /// Ask for images and copy them into the brushes folder as new brush tips. Names already
+    /// taken get a number appended rather than replacing the existing tip.
+    pub(crate) fn import_brush_tips(&mut self, ctx: &egui::Context) {
+        let Some(paths) = file_dialog::open_brush_tips(file_dialog::pictures_dir().as_deref()) else {
+            return;
+        };
+        let _ = std::fs::create_dir_all(&self.brushes_path);
+        for path in paths {
+            let (Some(stem), Some(ext)) = (path.file_stem(), path.extension()) else {
+                continue;
+            };
+            let (stem, ext) = (stem.to_string_lossy(), ext.to_string_lossy());
+            let mut target = self.brushes_path.join(format!("{stem}.{ext}"));
+            let mut n = 2;
+            while target.exists() {
+                target = self.brushes_path.join(format!("{stem} {n}.{ext}"));
+                n += 1;
+            }
+            if let Err(e) = std::fs::copy(&path, &target) {
+                log::error!("Failed to import brush tip {}: {}", path.display(), e);
+            }
+        }
+        self.load_brush_tips(ctx.clone());
+    }
+
+    /// Ask for an image file and open it as a new document.
+    pub(crate) fn open_image_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(path) = file_dialog::open_image(self.dialog_dir().as_deref()) {
+            self.open_image(ctx, &path);
+        }
+    }
+
+    /// Replace the document with an image file and add it to the recent files.
+    /// Files that fail to load are dropped from the recent list.
+    pub(crate) fn open_image(&mut self, ctx: &egui::Context, path: &Path) {
+        if self.is_drawing || self.floating_layer_idx.is_some() {
+            return;
+        }
+        match self.load_image_document(ctx, path) {
+            Ok(()) => {
+                self.journal.record(JournalEntry::OpenImage(path.to_path_buf()));
+                self.recent_files.add(path, recent_files::thumbnail(&self.canvas));
+            }
+            Err(e) => {
+                log::error!("Failed to open {}: {}", path.display(), e);
+                self.recent_files.remove(path);
+            }
+        }
+    }
+
+    /// A white background with the image on Layer 1.
+    fn load_image_document(&mut self, ctx: &egui::Context, path: &Path) -> Result<(), String> {
+        let img = image::open(path).map_err(|e| e.to_string())?.to_rgba8();
+        let size = [img.width() as usize, img.height() as usize];
+        let image = egui::ColorImage::from_rgba_unmultiplied(size, img.as_raw());
+        self.color_model = ColorModel::Rgba;
+        self.rebuild_canvas(ctx, size[0], size[1], Color32::WHITE, auto_tile_size(size[0], size[1], self.thread_count));
+        self.canvas.load_image_into_layer(1, &image);
+        if let Some(info) = DocumentInfo::read_png(path) {
+            self.document_info = info;
+        }
+        self.document_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Start a blank document of the given size with the settings from the new canvas dialog.
+    pub(crate) fn new_canvas_with_size(&mut self, ctx: &egui::Context, width: usize, height: usize) {
+        self.new_canvas.unit = crate::CanvasUnit::Pixels;
+        self.new_canvas.width = width as f32;
+        self.new_canvas.height = height as f32;
+        self.new_canvas.sync_orientation();
+        self.apply_new_canvas(ctx);
+    }
+
+    /// Record an edit to the document, for its modification time and the unsaved-changes prompt.
+    pub(crate) fn mark_modified(&mut self) {
+        self.document_info.touch();
+        self.unsaved_changes = true;
+    }
+
+    /// Record an edit that recovery can't replay, so replaying the journal stops before it.
+    pub(crate) fn mark_untracked_change(&mut self, action: &str) {
+        self.journal.record(JournalEntry::Untracked(action.to_string()));
+        self.mark_modified();
+    }
+
+    /// Name shown in the window title and the close prompt: the document title, else the
+    /// file it came from or was exported to.
+    pub(crate) fn document_name(&self) -> String {
+        if !self.document_info.title.is_empty() {
+            return self.document_info.title.clone();
+        }
+        self.document_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| tr("Untitled").to_string())
+    }
+
+    /// Show the document name in the window title, with an asterisk while there are unsaved changes.
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let title = if self.document_open {
+            let marker = if self.unsaved_changes { "*" } else { "" };
+            format!("{}{} - {}", marker, self.document_name(), APP_TITLE)
+        } else {
+            APP_TITLE.to_string()
+        };
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    pub(crate) fn session_path(&self) -> PathBuf {
+        self.brushes_path.join(SESSION_FILE)
+    }
+
+    /// Flatten the open document into the session file so the start screen can restore it.
+    fn save_session(&mut self) {
+        if !self.document_open {
+            return;
+        }
+        let (w, h) = (self.canvas.width(), self.canvas.height());
+        let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
+        self.canvas.write_region_to_color_image(0, 0, w, h, &mut img, 1);
+        match save_color_image_with_info(img, self.session_path(), ExportFormat::PNG, &self.document_info) {
+            Ok(()) => self.journal.record(JournalEntry::SessionSaved),
+            Err(e) => log::error!("Failed to save the session: {}", e),
+        }
+    }
+
+    /// Reopen the composite saved when the app last closed.
+    pub(crate) fn restore_session(&mut self, ctx: &egui::Context) {
+        let path = self.session_path();
+        match self.load_image_document(ctx, &path) {
+            Ok(()) => {
+                // The session file is ours, not the user's
+                self.document_path = None;
+                self.journal.record(JournalEntry::RestoreSession);
+            }
+            Err(e) => log::error!("Failed to restore the last session: {}", e),
+        }
+    }
+
+    /// Rebuild the document the previous run was working on from the action journal: reopen
+    /// its base (a new canvas, an opened image or the session file), then replay the strokes,
+    /// clears, undos and layer changes made on it since, up to the first change the journal
+    /// couldn't capture. Strokes are replayed with the brush they journaled; strokes whose
+    /// brush had settings the journal doesn't keep were journaled as untracked.
+    pub(crate) fn recover_from_journal(&mut self, ctx: &egui::Context) {
+        let entries = self.journal.take_recoverable();
+        let Some((base, actions)) = entries.split_first() else {
+            return;
+        };
+        match base {
+            JournalEntry::NewDocument { width, height, tile_size, background } => {
+                let tile_size = if *tile_size == 0 { auto_tile_size(*width, *height, self.thread_count) } else { *tile_size };
+                self.rebuild_canvas(ctx, *width, *height, *background, tile_size);
+                // The recovered document is the base for anything done from here on
+                self.journal.record(base.clone());
+            }
+            JournalEntry::OpenImage(path) => self.open_image(ctx, path),
+            _ => self.restore_session(ctx),
+        }
+        if !self.document_open {
+            return;
+        }
+        let brush = self.brush.clone();
+        for action in actions {
+            let layers = self.canvas.layers.len();
+            match action {
+                JournalEntry::Stroke(stroke) => {
+                    self.canvas.active_layer_idx = stroke.layer.min(layers - 1);
+                    self.replay_stroke(stroke);
+                }
+                JournalEntry::ClearLayer { layer } => {
+                    self.canvas.active_layer_idx = (*layer).min(layers - 1);
+                    self.clear_active_layer();
+                }
+                JournalEntry::Undo { layer } | JournalEntry::Redo { layer } => {
+                    self.canvas.active_layer_idx = (*layer).min(layers - 1);
+                    self.step_history(matches!(action, JournalEntry::Redo { .. }));
+                }
+                JournalEntry::AddLayer => self.add_layer(),
+                JournalEntry::RemoveLayer { layer } if layers > 1 => self.remove_layer(*layer),
+                _ => {}
+            }
+        }
+        self.brush = brush;
+        self.mark_all_tiles_dirty();
+    }
+
+    fn replay_stroke(&mut self, stroke: &JournalStroke) {
+        let Some(((first, diameter, pressure), rest)) = stroke.points.split_first() else {
+            return;
+        };
+        // Journaled strokes were recorded after smoothing, which the plain brush doesn't do
+        self.brush = stroke.brush();
+        self.brush.brush_options.diameter = *diameter;
+        self.brush.pressure = *pressure;
+        self.brush.sample_time = None;
+        self.start_stroke(*first);
+        for &(pos, diameter, pressure) in rest {
+            self.brush.brush_options.diameter = diameter;
+            self.brush.pressure = pressure;
+            self.continue_stroke(pos);
+        }
+        self.finish_stroke();
+    }
+
+    fn convert_color_for_model(color: Color32, model: ColorModel) -> Color32 {
+        match model {
+            ColorModel::Rgba => color,
+            ColorModel::Grayscale => color,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn layer_tile_image(
+        layer_idx: usize,
+        tx: usize,
+        ty: usize,
+        canvas: &Canvas,
+        layer_caches: &mut [HashMap<(usize, usize), egui::ColorImage>],
+        layer_cache_dirty: &mut [HashSet<(usize, usize)>],
+    ) -> egui::ColorImage {
+        if let Some(dirty) = layer_cache_dirty.get_mut(layer_idx) {
+            if dirty.remove(&(tx, ty)) {
+                layer_caches
+                    .get_mut(layer_idx)
+                    .and_then(|m| m.remove(&(tx, ty)));
+            }
+        }
+
+        if let Some(img) = layer_caches.get(layer_idx).and_then(|m| m.get(&(tx, ty))) {
+            return img.clone();
+        }
+
+        let tile_size = canvas.tile_size();
+        let tile_w = tile_size.min(canvas.width() - tx * tile_size);
+        let tile_h = tile_size.min(canvas.height() - ty * tile_size);
+        let mut img = egui::ColorImage::new([tile_w, tile_h], Color32::TRANSPARENT);
+
+        if let Some(data) = canvas.get_layer_tile_data(layer_idx, tx as i32, ty as i32) {
+            for y in 0..tile_h {
+                for x in 0..tile_w {
+                    let src_idx = y * tile_size + x;
+                    img.pixels[y * tile_w + x] = data[src_idx];
+                }
+            }
+        } else if layer_idx == 0 {
+            for px in &mut img.pixels {
+                *px = canvas.clear_color();
+            }
         }
 
-        let layer = self.canvas.layers.remove(from);
-        self.canvas.layers.insert(to, layer);
+        if let Some(cache) = layer_caches.get_mut(layer_idx) {
+            cache.insert((tx, ty), img.clone());
+        }
+        img
+    }
 
-        let hist = self.histories.remove(from);
-        self.histories.insert(to, hist);
+    /// Bake a moved layer's offset into its tiles before its pixels are edited, shifting its
+    /// undo steps along so they still line up with the tiles.
+    pub(crate) fn bake_layer_offset(&mut self, idx: usize) {
+        let Some((dx, dy)) = self.canvas.bake_layer_offset(idx) else {
+            return;
+        };
+        let tile_size = self.canvas.tile_size();
+        if let Some(history) = self.histories.get_mut(idx) {
+            history.shift(dx, dy, tile_size);
+        }
+        self.mark_all_tiles_dirty();
+    }
 
-        let cache = self.layer_caches.remove(from);
-        self.layer_caches.insert(to, cache);
+    /// Bake every layer's offset, for operations that read tiles across the whole stack.
+    pub(crate) fn bake_layer_offsets(&mut self) {
+        for idx in 0..self.canvas.layers.len() {
+            self.bake_layer_offset(idx);
+        }
+    }
 
-        let cache_dirty = self.layer_cache_dirty.remove(from);
-        self.layer_cache_dirty.insert(to, cache_dirty);
+    /// Start a Move tool drag at `pos`.
+    pub(crate) fn begin_layer_move(&mut self, pos: Vec2) {
+        if self.floating_layer_idx.is_none() && !self.layers_to_move().is_empty() {
+            self.layer_move = Some((pos, (0, 0)));
+        }
+    }
 
-        let ui_color = self.layer_ui_colors.remove(from);
-        self.layer_ui_colors.insert(to, ui_color);
+    /// Move the active layer, or the layer selection, so it follows the pointer at `pos`.
+    /// Layers only move by whole pixels, through their offset, so nothing is resampled.
+    pub(crate) fn drag_layer_move(&mut self, pos: Vec2) {
+        let Some((start, moved)) = self.layer_move else {
+            return;
+        };
+        let target = ((pos.x - start.x).round() as i32, (pos.y - start.y).round() as i32);
+        let (dx, dy) = (target.0 - moved.0, target.1 - moved.1);
+        if (dx, dy) == (0, 0) {
+            return;
+        }
+        for idx in self.layers_to_move() {
+            self.canvas.offset_layer(idx, dx, dy);
+        }
+        self.layer_move = Some((start, target));
+        self.mark_all_tiles_dirty();
+    }
 
-        let active = self.canvas.active_layer_idx;
-        self.canvas.active_layer_idx = if active == from {
-            to
-        } else if from < active && active <= to {
-            active - 1
-        } else if to <= active && active < from {
-            active + 1
+    /// Finish a Move tool drag as one undo step on the active layer.
+    pub(crate) fn end_layer_move(&mut self) {
+        let Some((_, moved)) = self.layer_move.take() else {
+            return;
+        };
+        if moved == (0, 0) {
+            return;
+        }
+        let layers = self.layers_to_move();
+        let action = match layers.as_slice() {
+            [idx] => UndoAction { offset: Some((*idx, moved)), ..UndoAction::new("Move layer") },
+            _ => {
+                let steps = layers.iter().map(|&idx| UndoAction { offset: Some((idx, moved)), ..UndoAction::new("Move layer") }).collect();
+                UndoAction { composite: Some(CompositeAction { steps }), ..UndoAction::new("Move layers") }
+            }
+        };
+        if let Some(hist) = self.active_history_mut() {
+            hist.push_action(action);
+        }
+        self.mark_untracked_change(&format!("move_layers {:?} by {},{}", layers, moved.0, moved.1));
+    }
+
+    /// Layers the Move tool moves: the layer selection, leaving out the background and
+    /// locked layers.
+    fn layers_to_move(&self) -> Vec<usize> {
+        self.layer_selection().into_iter().filter(|&idx| idx != 0 && !self.canvas.layers[idx].locked).collect()
+    }
+
+    fn active_history_mut(&mut self) -> Option<&mut History> {
+        self.histories.get_mut(self.canvas.active_layer_idx)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn ensure_layer_history_len(&mut self) {
+        let target = self.canvas.layers.len();
+        if self.histories.len() < target {
+            self.histories
+                .extend((self.histories.len()..target).map(|_| History::new()));
+        } else if self.histories.len() > target {
+            self.histories.truncate(target);
+        }
+    }
+
+    /// Drop every flattened composite and redisplay the whole canvas.
+    pub(crate) fn mark_all_tiles_dirty(&mut self) {
+        self.canvas.invalidate_composite();
+    }
+
+    /// Switch the color vision preview; only the displayed tiles are re-rendered.
+    pub(crate) fn set_color_deficiency(&mut self, mode: ColorDeficiency) {
+        if self.view_settings.color_deficiency != mode {
+            self.view_settings.color_deficiency = mode;
+            self.mark_all_tiles_dirty();
+        }
+    }
+
+    /// Move the contiguous block of layers `from` so it starts at index `to`.
+    pub(crate) fn reorder_layers(&mut self, from: Range<usize>, to: usize) {
+        let len = self.canvas.layers.len();
+        if from.is_empty() || from.end > len {
+            return;
+        }
+        let to = to.min(len - from.len());
+        if from.start == to {
+            return;
+        }
+
+        let mut order: Vec<usize> = (0..len).filter(|i| !from.contains(i)).collect();
+        order.splice(to..to, from);
+        self.apply_layer_order(&order);
+    }
+
+    /// Gather the given layers into one block, in stack order, ending where a
+    /// single dragged layer dropped at `target` would.
+    pub(crate) fn move_layer_block(&mut self, layers: &BTreeSet<usize>, target: usize) {
+        let len = self.canvas.layers.len();
+        let (Some(&lowest), Some(&highest)) = (layers.first(), layers.last()) else {
+            return;
+        };
+        if highest >= len {
+            return;
+        }
+        let rest: Vec<usize> = (0..len).filter(|i| !layers.contains(i)).collect();
+        let start = if target > highest {
+            // Moving up: the top of the block lands on the target
+            target + 1 - layers.len()
         } else {
-            active
+            target.min(lowest)
         };
+        let start = start.min(rest.len());
+
+        let mut order = rest;
+        order.splice(start..start, layers.iter().copied());
+        self.apply_layer_order(&order);
+    }
+
+    /// Rearrange the layer stack so new position `i` holds the layer previously at `order[i]`,
+    /// keeping the per-layer vectors, the active layer and the layer selection in step.
+    fn apply_layer_order(&mut self, order: &[usize]) {
+        if order.len() != self.canvas.layers.len() || order.iter().enumerate().all(|(i, &from)| i == from) {
+            return;
+        }
+        permute(&mut self.canvas.layers, order);
+        permute(&mut self.histories, order);
+        permute(&mut self.layer_caches, order);
+        permute(&mut self.layer_cache_dirty, order);
+        permute(&mut self.layer_ui_colors, order);
+
+        let new_index = |old: usize| order.iter().position(|&i| i == old);
+        if let Some(active) = new_index(self.canvas.active_layer_idx) {
+            self.canvas.active_layer_idx = active;
+        }
+        self.selected_layers = self.selected_layers.iter().filter_map(|&i| new_index(i)).collect();
 
         self.mark_all_tiles_dirty();
+        self.mark_untracked_change(&format!("reorder_layers {:?}", order));
     }
 
-    pub fn draw_transform_overlay(&mut self, painter: &egui::Painter, origin: egui::Pos2) {
-        if let super::tools::Tool::Transform(ref mut info) = self.active_tool {
-            // If bounds are not set, try to set them
-            if info.bounds.is_none() {
-                info.bounds = self.canvas.get_content_bounds(self.canvas.active_layer_idx, if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None });
-            }
-
-            if let Some(bounds) = info.bounds {
-                let center = Vec2::new(bounds.center().x, bounds.center().y);
-                let (sin_r, cos_r) = info.rotation.sin_cos();
-                
-                // Helper to transform a point
-                let transform_point = |p: egui::Pos2| -> egui::Pos2 {
-                    let dx = p.x - center.x;
-                    let dy = p.y - center.y;
-                    
-                    let sx = dx * info.scale.x;
-                    let sy = dy * info.scale.y;
-                    
-                    let rx = sx * cos_r - sy * sin_r;
-                    let ry = sx * sin_r + sy * cos_r;
-                    
-                    let tx = rx + center.x + info.offset.x;
-                    let ty = ry + center.y + info.offset.y;
-                    
-                    egui::pos2(
-                        origin.x + tx * self.zoom,
-                        origin.y + ty * self.zoom
-                    )
-                };
+    /// Layers targeted by bulk operations: the multi-selection plus the active layer.
+    pub(crate) fn layer_selection(&self) -> BTreeSet<usize> {
+        let mut layers = self.selected_layers.clone();
+        layers.insert(self.canvas.active_layer_idx);
+        layers.retain(|&i| i < self.canvas.layers.len());
+        layers
+    }
+
+    /// Add an empty layer on top with its own history and caches.
+    pub(crate) fn add_layer(&mut self) {
+        self.canvas.add_layer();
+        self.histories.push(History::new());
+        self.layer_caches.push(HashMap::new());
+        self.layer_cache_dirty.push(HashSet::new());
+        self.layer_ui_colors.push(egui::Color32::from_gray(40));
+        self.canvas.active_layer_idx = self.canvas.layers.len() - 1;
+        self.selected_layers.clear();
+        self.journal.record(JournalEntry::AddLayer);
+        self.mark_modified();
+    }
+
+    /// Add an empty vector layer on top of the stack and make it active.
+    pub(crate) fn add_vector_layer(&mut self) {
+        self.canvas.add_vector_layer();
+        self.histories.push(History::new());
+        self.layer_caches.push(HashMap::new());
+        self.layer_cache_dirty.push(HashSet::new());
+        self.layer_ui_colors.push(egui::Color32::from_gray(40));
+        self.selected_layers.clear();
+        self.path_selection = None;
+        self.mark_untracked_change("add_vector_layer");
+    }
+
+    /// Delete a layer along with its history and caches.
+    pub(crate) fn remove_layer(&mut self, idx: usize) {
+        if idx >= self.canvas.layers.len() {
+            return;
+        }
+        self.journal.record(JournalEntry::RemoveLayer { layer: idx });
+        self.canvas.layer_changed(idx);
+        self.canvas.layers.remove(idx);
+        if idx < self.histories.len() {
+            self.histories.remove(idx);
+        }
+        if idx < self.layer_caches.len() {
+            self.layer_caches.remove(idx);
+        }
+        if idx < self.layer_cache_dirty.len() {
+            self.layer_cache_dirty.remove(idx);
+        }
+        if idx < self.layer_ui_colors.len() {
+            self.layer_ui_colors.remove(idx);
+        }
+        let active = self.canvas.active_layer_idx;
+        if active > idx || active >= self.canvas.layers.len() {
+            self.canvas.active_layer_idx = active.saturating_sub(1);
+        }
+        self.selected_layers = self
+            .selected_layers
+            .iter()
+            .filter(|&&i| i != idx)
+            .map(|&i| if i > idx { i - 1 } else { i })
+            .collect();
+        self.mark_modified();
+    }
+
+    /// Delete every selected layer except the background, keeping at least one layer.
+    pub(crate) fn delete_selected_layers(&mut self) {
+        for idx in self.layer_selection().into_iter().rev() {
+            if idx == 0 || self.canvas.layers.len() <= 1 {
+                continue;
+            }
+            self.remove_layer(idx);
+        }
+        self.selected_layers.clear();
+        self.mark_all_tiles_dirty();
+    }
+
+    /// Flatten the selected layers into the lowest one of them.
+    pub(crate) fn merge_selected_layers(&mut self) {
+        let layers = self.layer_selection();
+        let (Some(&lowest), true) = (layers.first(), layers.len() > 1) else {
+            return;
+        };
+        for &idx in &layers {
+            self.bake_layer_offset(idx);
+        }
+        // Stack the selection directly above its lowest layer, then fold it down from the top
+        self.move_layer_block(&layers, lowest);
+        for idx in (lowest + 1..lowest + layers.len()).rev() {
+            self.canvas.merge_layer_down(idx);
+            self.histories.remove(idx);
+            self.layer_caches.remove(idx);
+            self.layer_cache_dirty.remove(idx);
+            self.layer_ui_colors.remove(idx);
+        }
+        // Merged pixels invalidate undo snapshots taken on the lowest layer
+        self.histories[lowest] = History::new();
+        self.canvas.active_layer_idx = lowest;
+        self.selected_layers.clear();
+        self.mark_all_tiles_dirty();
+        self.mark_untracked_change(&format!("merge_layers {:?}", layers));
+    }
+
+    /// Outline the paths of the active vector layer while the path tool is active, with handles
+    /// on the picked one.
+    fn draw_path_overlay(&self, painter: &egui::Painter, origin: egui::Pos2, canvas_center: egui::Pos2) {
+        let layer_idx = self.canvas.active_layer_idx;
+        let Some(vector) = self.canvas.layers.get(layer_idx).and_then(|l| l.vector.as_ref()) else {
+            return;
+        };
+        if self.active_tool != super::tools::Tool::EditPath {
+            return;
+        }
+        let selected = self.path_selection.filter(|&(layer, _)| layer == layer_idx).map(|(_, stroke)| stroke);
+        let accent = painter.ctx().style().visuals.selection.bg_fill;
+        for (i, stroke) in vector.strokes.iter().enumerate() {
+            let points: Vec<egui::Pos2> =
+                stroke.points.iter().map(|p| self.canvas_to_screen(p.0, origin, canvas_center)).collect();
+            if selected != Some(i) {
+                painter.add(egui::Shape::line(points.clone(), egui::Stroke::new(2.0, Color32::BLACK)));
+                painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, Color32::WHITE)));
+                continue;
+            }
+            painter.add(egui::Shape::line(points.clone(), egui::Stroke::new(2.0, accent)));
+            // Samples are dense, so only mark those a handle's width apart
+            let mut last: Option<egui::Pos2> = None;
+            for p in points {
+                if last.is_none_or(|l| l.distance(p) >= HANDLE_RADIUS * 3.0) {
+                    painter.circle_filled(p, HANDLE_RADIUS, Color32::WHITE);
+                    painter.circle_stroke(p, HANDLE_RADIUS, egui::Stroke::new(1.0, accent));
+                    last = Some(p);
+                }
+            }
+        }
+    }
+
+    /// Draw the transform box, handles and pivot at a constant screen size, and show
+    /// which action a press would start under the pointer.
+    pub fn draw_transform_overlay(&mut self, painter: &egui::Painter, origin: egui::Pos2, canvas_center: egui::Pos2) {
+        let info = match self.active_tool {
+            super::tools::Tool::Transform(ref mut info) => {
+                // If bounds are not set, try to set them
+                if info.bounds.is_none() {
+                    let selection = if self.selection_manager.has_selection() { Some(&self.selection_manager) } else { None };
+                    info.bounds = self.canvas.get_content_bounds(self.canvas.active_layer_idx, selection);
+                }
+                *info
+            }
+            _ => return,
+        };
+        let Some(handles) = info.handles() else {
+            return;
+        };
+        let to_screen = |p: Vec2| self.canvas_to_screen(p, origin, canvas_center);
+        let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 120, 255));
+
+        // Draw box
+        for i in (0..8).step_by(2) {
+            painter.line_segment([to_screen(handles[i]), to_screen(handles[(i + 2) % 8])], stroke);
+        }
 
-                let corners = [
-                    bounds.min, // Top-Left
-                    eframe::egui::pos2(bounds.max.x, bounds.min.y), // Top-Right
-                    bounds.max, // Bottom-Right
-                    eframe::egui::pos2(bounds.min.x, bounds.max.y), // Bottom-Left
-                ];
-                
-                let t_corners: Vec<egui::Pos2> = corners.iter().map(|&c| transform_point(c)).collect();
-                
-                // Draw box
-                let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 120, 255));
-                painter.line_segment([t_corners[0], t_corners[1]], stroke);
-                painter.line_segment([t_corners[1], t_corners[2]], stroke);
-                painter.line_segment([t_corners[2], t_corners[3]], stroke);
-                painter.line_segment([t_corners[3], t_corners[0]], stroke);
-                
-                // Draw handles
-                let handle_points = [
-                    bounds.min, // Top-Left
-                    eframe::egui::pos2(bounds.center().x, bounds.min.y), // Top-Center
-                    eframe::egui::pos2(bounds.max.x, bounds.min.y), // Top-Right
-                    eframe::egui::pos2(bounds.max.x, bounds.center().y), // Right-Center
-                    bounds.max, // Bottom-Right
-                    eframe::egui::pos2(bounds.center().x, bounds.max.y), // Bottom-Center
-                    eframe::egui::pos2(bounds.min.x, bounds.max.y), // Bottom-Left
-                    eframe::egui::pos2(bounds.min.x, bounds.center().y), // Left-Center
-                ];
-                
-                for p in handle_points {
-                    let tp = transform_point(p);
-                    painter.circle_filled(tp, 4.0, egui::Color32::WHITE);
-                    painter.circle_stroke(tp, 4.0, stroke);
+        // Draw handles; a box that is small on screen only gets its corners
+        let roomy = info.show_edge_handles(to_screen);
+        for (i, &handle) in handles.iter().enumerate() {
+            if roomy || i % 2 == 0 {
+                let tp = to_screen(handle);
+                painter.circle_filled(tp, HANDLE_RADIUS, egui::Color32::WHITE);
+                painter.circle_stroke(tp, HANDLE_RADIUS, stroke);
+            }
+        }
+
+        // Pivot handle
+        if roomy {
+            let pivot = to_screen(info.center() + info.offset);
+            painter.circle_filled(pivot, 6.0, egui::Color32::from_black_alpha(80));
+            painter.circle_stroke(pivot, 6.0, stroke);
+            painter.line_segment([pivot - egui::vec2(9.0, 0.0), pivot + egui::vec2(9.0, 0.0)], stroke);
+            painter.line_segment([pivot - egui::vec2(0.0, 9.0), pivot + egui::vec2(0.0, 9.0)], stroke);
+        }
+
+        // Cursor feedback: the running action while dragging, otherwise what a press would grab
+        let ctx = painter.ctx();
+        let Some(pointer) = ctx.pointer_hover_pos() else {
+            return;
+        };
+        let state = if info.start_pos.is_some() {
+            info.state
+        } else {
+            let canvas_pos = self.screen_to_canvas_unclamped(pointer, origin, canvas_center);
+            info.hit_test(pointer, canvas_pos, to_screen)
+        };
+        let cursor = match state {
+            TransformState::Moving => egui::CursorIcon::Move,
+            TransformState::MovingPivot => egui::CursorIcon::Crosshair,
+            TransformState::Scaling(i) => {
+                // Pick the resize arrow closest to the handle's direction from the box center
+                let box_center = to_screen(handles[0]).lerp(to_screen(handles[4]), 0.5);
+                let dir = to_screen(handles[i]) - box_center;
+                let angle = dir.y.atan2(dir.x).to_degrees().rem_euclid(180.0);
+                match ((angle + 22.5) / 45.0) as usize % 4 {
+                    0 => egui::CursorIcon::ResizeHorizontal,
+                    1 => egui::CursorIcon::ResizeNwSe,
+                    2 => egui::CursorIcon::ResizeVertical,
+                    _ => egui::CursorIcon::ResizeNeSw,
+                }
+            }
+            TransformState::Rotating => {
+                painter.text(
+                    pointer + egui::vec2(12.0, -12.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    "⟳",
+                    egui::FontId::proportional(16.0),
+                    egui::Color32::WHITE,
+                );
+                egui::CursorIcon::Crosshair
+            }
+            TransformState::None => return,
+        };
+        ctx.set_cursor_icon(cursor);
+    }
+
+    /// Pick the pointer icon over the canvas: the view drag in progress (or the one a held key
+    /// would start), else what the active tool would do under the pointer. Painting hides the
+    /// pointer behind the brush outline. Transform handles get their cursors from
+    /// [`Self::draw_transform_overlay`], which runs after this.
+    fn update_canvas_cursor(&self, painter: &egui::Painter, view: &render_helper::CanvasView) {
+        use super::tools::Tool;
+        let ctx = painter.ctx();
+        let navigating = self.is_panning || self.is_rotating || self.is_zooming;
+        let pointer = match (ctx.pointer_hover_pos(), self.pen_hover) {
+            // The pen's own hover position, until a stroke moves it on without hover samples
+            (_, Some(pen)) if self.stroke.is_none() => pen,
+            (Some(pos), _) if navigating || view.response.hovered() || view.response.dragged() => pos,
+            _ => return,
+        };
+        // Rotating has no system cursor, so a glyph rides along with the grabbing hand
+        let rotate_glyph = || {
+            painter.text(pointer + egui::vec2(12.0, -12.0), egui::Align2::LEFT_BOTTOM, "⟳", egui::FontId::proportional(16.0), Color32::WHITE);
+        };
+        let held_nav = ctx.input(|i| self.navigation.action_for(egui::PointerButton::Primary, i.modifiers, |key| i.key_down(key)));
+        let nav = if self.is_panning {
+            Some(NavAction::Pan)
+        } else if self.is_rotating {
+            Some(NavAction::Rotate)
+        } else if self.is_zooming {
+            Some(NavAction::Zoom)
+        } else {
+            held_nav
+        };
+        if let Some(action) = nav {
+            let cursor = match action {
+                NavAction::Pan if navigating => egui::CursorIcon::Grabbing,
+                NavAction::Pan => egui::CursorIcon::Grab,
+                NavAction::Rotate => {
+                    rotate_glyph();
+                    egui::CursorIcon::Grabbing
+                }
+                NavAction::Zoom => egui::CursorIcon::ZoomIn,
+            };
+            ctx.set_cursor_icon(cursor);
+            return;
+        }
+        if self.view_settings.wrap_preview {
+            return;
+        }
+
+        let canvas_pos = self.screen_to_canvas_unclamped(pointer, view.origin, view.canvas_center);
+        let cursor = match self.active_tool {
+            Tool::Brush => {
+                if self.canvas.layers.get(self.canvas.active_layer_idx).is_some_and(|l| l.locked) {
+                    egui::CursorIcon::NotAllowed
+                } else if self.draw_brush_outline(painter, pointer) {
+                    egui::CursorIcon::None
+                } else {
+                    egui::CursorIcon::Crosshair
+                }
+            }
+            Tool::Select(_) if self.selection_manager.is_moving() => egui::CursorIcon::Grabbing,
+            Tool::Select(_)
+                if !self.selection_manager.is_placing_polygon()
+                    && self.selection_manager.has_selection()
+                    && self.selection_manager.contains(canvas_pos) =>
+            {
+                egui::CursorIcon::Move
+            }
+            Tool::Select(_) | Tool::Eyedropper => egui::CursorIcon::Crosshair,
+            Tool::Transform(_) => return,
+            Tool::EditPath if self.path_drag.is_some() => egui::CursorIcon::Grabbing,
+            Tool::EditPath => {
+                let over_point = self
+                    .canvas
+                    .layers
+                    .get(self.canvas.active_layer_idx)
+                    .and_then(|l| l.vector.as_ref())
+                    .is_some_and(|v| v.hit_test(canvas_pos, HANDLE_RADIUS * 2.0 / self.zoom).is_some());
+                if !over_point {
+                    return;
                 }
+                egui::CursorIcon::Grab
+            }
+            Tool::Move if self.layer_move.is_some() => egui::CursorIcon::Grabbing,
+            Tool::Move if self.layers_to_move().is_empty() => egui::CursorIcon::NotAllowed,
+            Tool::Move => egui::CursorIcon::Move,
+        };
+        ctx.set_cursor_icon(cursor);
+    }
+
+    /// While a stabilizer holds the line back, trace the input it hasn't caught up with yet as a
+    /// faint line from the end of the painted stroke, so it's clear where the stroke is heading.
+    /// It goes away with the stroke when the pointer is released.
+    fn draw_stabilizer_ghost(&self, painter: &egui::Painter, view: &render_helper::CanvasView) {
+        if self.brush.stabilizer_algorithm == StabilizerAlgorithm::None || self.brush.pixel_perfect {
+            return;
+        }
+        let Some(head) = self.stroke.as_ref().and_then(|stroke| stroke.stroke_head()) else {
+            return;
+        };
+        let recent = &self.stroke_samples[self.stroke_samples.len().saturating_sub(GHOST_SAMPLES)..];
+        // The stroke has caught up with input up to the sample nearest its head; later ones win ties
+        let mut caught_up = 0;
+        let mut nearest = f32::MAX;
+        for (i, &(pos, _, _)) in recent.iter().enumerate() {
+            let d = (pos - head).length();
+            if d <= nearest {
+                nearest = d;
+                caught_up = i;
             }
         }
+        let to_screen = |p: Vec2| self.canvas_to_screen(p, view.origin, view.canvas_center);
+        let points: Vec<egui::Pos2> = std::iter::once(head)
+            .chain(recent.iter().skip(caught_up + 1).map(|&(pos, _, _)| pos))
+            .map(to_screen)
+            .collect();
+        if points.len() < 2 {
+            return;
+        }
+        painter.add(egui::Shape::line(points.clone(), egui::Stroke::new(3.0, Color32::from_black_alpha(50))));
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, Color32::from_white_alpha(140))));
+    }
+
+    /// Outline the brush footprint around `pos`, with its size while no stroke is under way.
+    /// Returns false without drawing when the brush is too small on screen to aim by.
+    fn draw_brush_outline(&self, painter: &egui::Painter, pos: egui::Pos2) -> bool {
+        let diameter = self.brush.brush_options.canvas_diameter(self.zoom);
+        let radius = diameter / 2.0 * self.zoom;
+        if radius < MIN_OUTLINE_RADIUS {
+            return false;
+        }
+        painter.circle_stroke(pos, radius, egui::Stroke::new(2.0, Color32::BLACK));
+        painter.circle_stroke(pos, radius, egui::Stroke::new(1.0, Color32::WHITE));
+        if self.stroke.is_none() {
+            let label = format!("{:.0} px ({:.0} px on screen)", diameter, diameter * self.zoom);
+            let anchor = pos + egui::vec2(radius, radius) * std::f32::consts::FRAC_1_SQRT_2 + egui::vec2(6.0, 6.0);
+            let font = egui::FontId::proportional(11.0);
+            painter.text(anchor + egui::vec2(1.0, 1.0), egui::Align2::LEFT_TOP, &label, font.clone(), Color32::BLACK);
+            painter.text(anchor, egui::Align2::LEFT_TOP, label, font, Color32::WHITE);
+        }
+        true
+    }
+
+    /// Screen position of a canvas point, considering zoom and view rotation.
+    pub(crate) fn canvas_to_screen(&self, p: Vec2, origin: egui::Pos2, canvas_center: egui::Pos2) -> egui::Pos2 {
+        let unrotated = origin + egui::vec2(p.x, p.y) * self.zoom;
+        Self::rotate_point(unrotated, canvas_center, self.rotation.cos(), self.rotation.sin())
     }
 }
 
 impl eframe::App for PainterApp {
     /// Handle UI, input, painting updates, and tile uploads each frame.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Handle Undo/Redo
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Z)) {
-            let active_idx = self.canvas.active_layer_idx;
-            let affected = if ctx.input(|i| i.modifiers.shift) {
-                self.histories
-                    .get_mut(active_idx)
-                    .map(|h| h.redo(&self.canvas, &mut self.selection_manager, &mut self.active_tool))
-                    .unwrap_or_default()
+        if ctx.input(|i| i.viewport().close_requested()) {
+            if self.unsaved_changes && self.document_open {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                self.show_close_prompt = true;
             } else {
-                self.histories
-                    .get_mut(active_idx)
-                    .map(|h| h.undo(&self.canvas, &mut self.selection_manager, &mut self.active_tool))
-                    .unwrap_or_default()
-            };
-
-            for (tx, ty) in affected {
-                if tx >= 0 && ty >= 0 {
-                    if let Some(tile) = self.tile_mut(tx as usize, ty as usize) {
-                        tile.dirty = true;
-                    }
-                }
+                self.save_session();
+                crate::canvas::tile_pager::remove_scratch_file();
             }
+        }
+        self.update_window_title(ctx);
+        if !self.document_open {
+            ui::start_screen::start_screen(self, ctx);
+            ui::canvas_creation::canvas_creation_modal(self, ctx);
+            return;
+        }
 
-            // Reset transform tool state if active so it recalculates bounds
-            // Only reset if the undo action didn't restore a transform state
-            if let super::tools::Tool::Transform(ref mut info) = self.active_tool {
-                if info.bounds.is_none() && info.rotation == 0.0 && info.offset.x == 0.0 && info.offset.y == 0.0 {
-                     *info = crate::selection::transform::TransformInfo::default();
-                }
-            }
+        // The previous frame's timer has dropped by now, so its scopes are complete
+        if self.show_profiler_hud {
+            self.profiler_hud.record_frame(ctx.input(|i| i.unstable_dt));
+        }
+        let _frame_timer = ScopeTimer::new("frame");
 
+        // A streamed export reads the canvas over several frames, so edits wait until it is done
+        let canvas_busy = self.export_bands.is_some();
+        for command in self.keybindings.triggered(ctx).into_iter().filter(|_| !canvas_busy) {
+            self.run_command(command);
             ctx.request_repaint();
         }
 
+        ui::export_modal::feed_export_bands(self, ctx);
+        ui::export_modal::export_lock_overlay(self, ctx);
+
         // Poll export tasks
         if let Some(handle) = self.export_task.as_ref() {
             if handle.is_finished() {
@@ -798,10 +2522,20 @@ impl eframe::App for PainterApp {
                     .and_then(|h| h.join().ok())
                     .unwrap_or_else(|| Err("Export thread panicked".to_string()));
                 self.export_in_progress = false;
+                let pending_recent = self.pending_recent.take();
                 match result {
                     Ok(msg) => {
                         self.export_message = Some(msg);
                         self.show_export_modal = false;
+                        // Only whole-canvas exports are remembered, and they count as saving
+                        if let Some((path, thumbnail)) = pending_recent {
+                            self.recent_files.add(&path, thumbnail);
+                            self.document_path = Some(path);
+                            self.unsaved_changes = false;
+                            if self.close_after_save {
+                                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                            }
+                        }
                     }
                     Err(err) => {
                         self.export_message = Some(err);
@@ -809,6 +2543,10 @@ impl eframe::App for PainterApp {
                 }
             }
         }
+        // Saving from the close prompt was given up on
+        if self.close_after_save && !self.show_export_modal && !self.export_in_progress {
+            self.close_after_save = false;
+        }
 
         // Drain progress updates
         if let Some(rx) = &self.export_progress_rx {
@@ -821,12 +2559,52 @@ impl eframe::App for PainterApp {
         }
 
         ui::top_bar::top_bar(self, ctx);
+        ui::favorites_bar::favorites_bar(self, ctx);
+        ui::tool_options::tool_options_bar(self, ctx);
 
         layout::show_tool_docks(self, ctx);
 
-        egui::CentralPanel::default().show(ctx, |ui| {
+        // Leaving the transform tool places the floating pixels instead of stranding them
+        if self.floating_layer_idx.is_some() && !matches!(self.active_tool, super::tools::Tool::Transform(_)) {
+            self.commit_floating();
+        }
+
+        // Cold tiles go out to the scratch file between strokes, at most once a second
+        // on the background pool; a pass still running there is left to finish first
+        let paging_running = self
+            .paging_pass
+            .as_ref()
+            .is_some_and(|pass| matches!(pass.try_recv(), Err(std::sync::mpsc::TryRecvError::Empty)));
+        if !paging_running {
+            self.paging_pass = None;
+        }
+        if self.tile_paging
+            && !paging_running
+            && !self.is_drawing
+            && !canvas_busy
+            && self.last_paging_pass.elapsed() >= PAGING_INTERVAL
+        {
+            self.last_paging_pass = std::time::Instant::now();
+            self.paging_pass = Some(self.canvas.page_out_cold_tiles(self.tile_paging_budget_mb << 20, &self.background_pool));
+        }
+
+        // Onion skins of the neighbouring frames are flattened on the background pool
+        if self.animation.poll_onion(&self.background_pool) {
+            self.mark_all_tiles_dirty();
+        }
+        if self.animation.onion_pending() {
+            ctx.request_repaint();
+        }
+
+        // Undoing or redoing a trim resizes the canvas under the display tiles
+        if !self.atlases_match_canvas() {
+            self.rebuild_atlases(ctx);
+        }
+
+        let backdrop = egui::Frame::central_panel(&ctx.style()).fill(self.theme.backdrop());
+        egui::CentralPanel::default().frame(backdrop).show(ctx, |ui| {
+            let available = ui.available_size();
             if self.first_frame {
-                let available = ui.available_size();
                 let canvas_w = self.canvas.width() as f32;
                 let canvas_h = self.canvas.height() as f32;
 
@@ -841,29 +2619,57 @@ impl eframe::App for PainterApp {
                 };
                 self.first_frame = false;
             }
+            if self.recenter_pending {
+                let canvas_size = egui::vec2(self.canvas.width() as f32, self.canvas.height() as f32) * self.zoom;
+                let offset = (available - canvas_size) * 0.5;
+                self.offset = Vec2 {
+                    x: offset.x,
+                    y: offset.y,
+                };
+                self.recenter_pending = false;
+            }
 
-            render_helper::update_dirty_textures(self);
-            let view = render_helper::draw_canvas(self, ui);
+            {
+                let _timer = ScopeTimer::new("update_textures");
+                render_helper::update_dirty_textures(self);
+            }
+            let view = {
+                let _timer = ScopeTimer::new("draw_canvas");
+                render_helper::draw_canvas(self, ui)
+            };
+            if self.show_tile_overlay {
+                render_helper::draw_tile_overlay(self, ui.painter(), &view);
+            }
 
-            input_handler::handle_input(
-                self,
-                ctx,
-                &view.response,
-                view.origin,
-                view.canvas_center,
-            );
+            if !canvas_busy {
+                let _timer = ScopeTimer::new("input");
+                input_handler::handle_input(
+                    self,
+                    ctx,
+                    &view.response,
+                    view.origin,
+                    view.canvas_center,
+                );
+            }
 
             if self.is_drawing {
+                if self.brush.airbrush {
+                    self.spray_airbrush();
+                }
                 ctx.request_repaint();
             }
 
+            self.draw_stabilizer_ghost(ui.painter(), &view);
+            self.update_canvas_cursor(ui.painter(), &view);
+
             // Always draw selection overlay, but pass transform info if active
             let transform_info = if let super::tools::Tool::Transform(ref info) = self.active_tool {
                 Some(info)
             } else {
                 None
             };
-            if !matches!(self.active_tool, super::tools::Tool::Transform(_)) {
+            // Overlays are in canvas coordinates and would not line up with the rolled view
+            if !matches!(self.active_tool, super::tools::Tool::Transform(_)) && !self.view_settings.wrap_preview {
                 self.selection_manager.draw_overlay(
                     ui.painter(),
                     self.zoom,
@@ -873,24 +2679,55 @@ impl eframe::App for PainterApp {
                 );
             }
 
-            self.draw_transform_overlay(ui.painter(), view.origin);
+            // Size of a rectangle or circle selection while it is dragged out
+            if let (Some(size), Some(pointer)) = (self.selection_manager.drag_size(), ctx.pointer_hover_pos()) {
+                let label = format!("{:.0} × {:.0} px", size.x, size.y);
+                let anchor = pointer + egui::vec2(14.0, 14.0);
+                let font = egui::FontId::proportional(11.0);
+                ui.painter().text(anchor + egui::vec2(1.0, 1.0), egui::Align2::LEFT_TOP, &label, font.clone(), Color32::BLACK);
+                ui.painter().text(anchor, egui::Align2::LEFT_TOP, label, font, Color32::WHITE);
+            }
 
-            if ui.input(|i| i.key_pressed(egui::Key::C)) {
-                self.canvas.clear(Color32::WHITE);
-                for tile in &mut self.tiles {
-                    tile.dirty = true;
-                }
-                ctx.request_repaint();
+            self.draw_transform_overlay(ui.painter(), view.origin, view.canvas_center);
+            if !self.view_settings.wrap_preview {
+                self.draw_path_overlay(ui.painter(), view.origin, view.canvas_center);
+            }
+            if !self.view_settings.wrap_preview {
+                ui::filters::split_overlay(ui, self, &view);
+                ui::overlay_guides::overlay_guides(ui, self, &view);
+                ui::rulers::rulers(ui, self, &view);
             }
 
-            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
-                self.selection_manager.clear_selection();
-                ctx.request_repaint();
+            if self.view_settings.wrap_preview {
+                let anchor = view.response.rect.center_top() + egui::vec2(0.0, 8.0);
+                let font = egui::FontId::proportional(13.0);
+                let label = "Wrap preview (offset 50%): painting paused";
+                ui.painter().text(anchor + egui::vec2(1.0, 1.0), egui::Align2::CENTER_TOP, label, font.clone(), Color32::BLACK);
+                ui.painter().text(anchor, egui::Align2::CENTER_TOP, label, font, Color32::WHITE);
             }
         });
 
         ui::canvas_creation::canvas_creation_modal(self, ctx);
         ui::general_settings::general_settings_modal(self, ctx);
         ui::export_modal::export_modal(self, ctx);
+        ui::filters::filter_modal(self, ctx);
+        ui::stroke_selection::stroke_selection_modal(self, ctx);
+        ui::background_color::background_color_modal(self, ctx);
+        ui::trim::trim_modal(self, ctx);
+        ui::document_properties::document_properties_modal(self, ctx);
+        ui::statistics::statistics_modal(self, ctx);
+        ui::close_prompt::close_prompt_modal(self, ctx);
+        ui::profiler_hud::profiler_hud(self, ctx);
     }
 }
+
+/// A pool of `threads` workers named after their `role`, so profilers can tell the pools apart.
+pub(crate) fn build_pool(threads: usize, role: &'static str) -> Result<ThreadPool, rayon::ThreadPoolBuildError> {
+    ThreadPoolBuilder::new().num_threads(threads).thread_name(move |i| format!("{role}-{i}")).build()
+}
+
+/// Reorder `items` so position `i` holds the element previously at `order[i]`.
+fn permute<T>(items: &mut Vec<T>, order: &[usize]) {
+    let mut old: Vec<Option<T>> = std::mem::take(items).into_iter().map(Some).collect();
+    *items = order.iter().filter_map(|&i| old.get_mut(i).and_then(Option::take)).collect();
+}
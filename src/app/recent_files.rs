@@ -0,0 +1,124 @@
+//! Recently opened and exported images, stored next to the brush tips.
+//!
+//! The list is one path per line, newest first. Each entry gets a small PNG of the
+//! composite in `thumbnails/`, named after a hash of the path.
+use eframe::egui::{self, Color32, ColorImage, TextureHandle, TextureOptions};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::canvas::canvas::Canvas;
+use crate::utils::exporter::{ExportFormat, save_color_image};
+
+pub const FILE_NAME: &str = "recent_files.meta";
+const THUMBNAIL_DIR: &str = "thumbnails";
+const MAX_ENTRIES: usize = 12;
+/// Longest edge of a stored thumbnail in pixels.
+pub const THUMBNAIL_SIZE: usize = 96;
+
+pub struct RecentFiles {
+    dir: PathBuf,
+    entries: Vec<PathBuf>,
+    /// Loaded lazily; `None` once a thumbnail turned out to be missing.
+    textures: HashMap<PathBuf, Option<TextureHandle>>,
+}
+
+impl RecentFiles {
+    pub fn load(dir: &Path) -> Self {
+        let entries = std::fs::read_to_string(dir.join(FILE_NAME))
+            .map(|contents| {
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            dir: dir.to_path_buf(),
+            entries,
+            textures: HashMap::new(),
+        }
+    }
+
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    /// Move `path` to the front of the list and store `thumbnail` for it.
+    pub fn add(&mut self, path: &Path, thumbnail: ColorImage) {
+        self.entries.retain(|p| p != path);
+        self.entries.insert(0, path.to_path_buf());
+        for dropped in self.entries.drain(MAX_ENTRIES.min(self.entries.len())..) {
+            let _ = std::fs::remove_file(thumbnail_path(&self.dir, &dropped));
+            self.textures.remove(&dropped);
+        }
+        let thumb_path = thumbnail_path(&self.dir, path);
+        let saved = std::fs::create_dir_all(self.dir.join(THUMBNAIL_DIR))
+            .map_err(|e| e.to_string())
+            .and_then(|_| save_color_image(thumbnail, &thumb_path, ExportFormat::PNG));
+        if let Err(e) = saved {
+            log::error!("Failed to save thumbnail for {}: {}", path.display(), e);
+        }
+        self.textures.remove(path);
+        self.save();
+    }
+
+    /// Forget an entry, e.g. after the file could not be opened.
+    pub fn remove(&mut self, path: &Path) {
+        self.entries.retain(|p| p != path);
+        self.textures.remove(path);
+        let _ = std::fs::remove_file(thumbnail_path(&self.dir, path));
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        for path in std::mem::take(&mut self.entries) {
+            let _ = std::fs::remove_file(thumbnail_path(&self.dir, &path));
+        }
+        self.textures.clear();
+        self.save();
+    }
+
+    /// Thumbnail texture for an entry, loaded from disk the first time it is asked for.
+    pub fn thumbnail(&mut self, ctx: &egui::Context, path: &Path) -> Option<&TextureHandle> {
+        let dir = &self.dir;
+        self.textures
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let img = image::open(thumbnail_path(dir, path)).ok()?.to_rgba8();
+                let size = [img.width() as usize, img.height() as usize];
+                let color = ColorImage::from_rgba_unmultiplied(size, img.as_raw());
+                Some(ctx.load_texture(format!("recent_thumbnail_{}", path.display()), color, TextureOptions::LINEAR))
+            })
+            .as_ref()
+    }
+
+    fn save(&self) {
+        let mut contents = String::new();
+        for path in &self.entries {
+            contents.push_str(&path.to_string_lossy());
+            contents.push('\n');
+        }
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(self.dir.join(FILE_NAME), contents))
+            .unwrap_or_else(|e| log::error!("Failed to save recent files: {:?}", e));
+    }
+}
+
+/// Downsampled composite of the canvas for the recent files list.
+pub fn thumbnail(canvas: &Canvas) -> ColorImage {
+    let (w, h) = (canvas.width(), canvas.height());
+    let step = w.max(h).div_ceil(THUMBNAIL_SIZE).max(1);
+    let mut img = ColorImage::new([0, 0], Color32::TRANSPARENT);
+    canvas.write_region_to_color_image(0, 0, w, h, &mut img, step);
+    img
+}
+
+fn thumbnail_path(dir: &Path, path: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    dir.join(THUMBNAIL_DIR).join(format!("{:016x}.png", hasher.finish()))
+}
@@ -1,5 +1,5 @@
 use crate::PainterApp;
-use crate::app::state::{ATLAS_SIZE, TILE_SIZE};
+use crate::app::state::{ATLAS_GUTTER, ATLAS_SIZE};
 use crate::utils::profiler::ScopeTimer;
 use eframe::egui::{self, Color32, TextureOptions};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
@@ -13,6 +13,27 @@ pub struct CanvasView {
 }
 
 pub fn update_dirty_textures(app: &mut PainterApp) {
+    // Texture options only reach the GPU with an upload, so re-send every tile when the filter changes
+    let filter = app.view_settings.filter_for_zoom(app.zoom);
+    if filter != app.atlas_filter {
+        app.atlas_filter = filter;
+        app.mark_all_tiles_dirty();
+    }
+    let changed = app.canvas.take_dirty();
+    if changed.is_everything() {
+        app.tiles.iter_mut().for_each(|tile| tile.dirty = true);
+    }
+    for (tx, ty) in changed.tiles() {
+        if let Some(tile) = app.tile_mut(tx, ty) {
+            tile.dirty = true;
+        }
+    }
+    let options = TextureOptions {
+        magnification: filter,
+        minification: filter,
+        ..TextureOptions::NEAREST
+    };
+
     let lod_step = if app.disable_lod {
         1
     } else if app.zoom < 1.0 {
@@ -20,9 +41,13 @@ pub fn update_dirty_textures(app: &mut PainterApp) {
     } else {
         1
     }
-    .clamp(1, TILE_SIZE);
+    .clamp(1, app.canvas.tile_size());
 
     let canvas_ref = &app.canvas;
+    let tile_size = canvas_ref.tile_size();
+    let onion_layers = app.animation.onion_layers();
+    let color_deficiency = app.view_settings.color_deficiency;
+    let filter_preview = app.filter_dialog.as_ref().and_then(|dialog| dialog.layer_preview());
     let dirty_images: Vec<(usize, egui::ColorImage)> = app.pool.install(|| {
         app.tiles
             .iter()
@@ -31,30 +56,35 @@ pub fn update_dirty_textures(app: &mut PainterApp) {
             .collect::<Vec<_>>()
             .par_iter()
             .map(|(idx, tile)| {
-                let x = tile.tx * TILE_SIZE;
-                let y = tile.ty * TILE_SIZE;
-                let w = TILE_SIZE.min(canvas_ref.width() - x);
-                let h = TILE_SIZE.min(canvas_ref.height() - y);
+                let x = tile.tx * tile_size;
+                let y = tile.ty * tile_size;
+                let w = tile_size.min(canvas_ref.width() - x);
+                let h = tile_size.min(canvas_ref.height() - y);
 
                 let out_w = (w + lod_step - 1) / lod_step;
                 let out_h = (h + lod_step - 1) / lod_step;
                 let mut img = egui::ColorImage::new([out_w, out_h], Color32::TRANSPARENT);
-                canvas_ref.write_region_to_color_image(x, y, w, h, &mut img, lod_step);
+                canvas_ref.write_tile_with_preview(tile.tx, tile.ty, &mut img, lod_step, filter_preview.as_ref());
+                for onion in onion_layers {
+                    onion.overlay(tile.tx as i32, tile.ty as i32, tile_size, lod_step, &mut img);
+                }
+                color_deficiency.apply(&mut img.pixels);
                 (*idx, img)
             })
             .collect()
     });
 
     for (idx, img) in dirty_images {
+        app.histogram.invalidate_tile(idx);
         if let Some(tile) = app.tiles.get_mut(idx) {
             let _timer = ScopeTimer::new("texture_set");
             let img_w = img.size[0];
             let img_h = img.size[1];
             if let Some(atlas) = app.atlases.get_mut(tile.atlas_idx) {
                 atlas.texture.set_partial(
-                    [tile.atlas_x, tile.atlas_y],
-                    img,
-                    TextureOptions::NEAREST,
+                    [tile.atlas_x - ATLAS_GUTTER, tile.atlas_y - ATLAS_GUTTER],
+                    with_gutter(&img),
+                    options,
                 );
             }
             tile.pixel_w = img_w;
@@ -69,6 +99,7 @@ pub fn draw_canvas(app: &mut PainterApp, ui: &mut egui::Ui) -> CanvasView {
     let canvas_size = desired_size * app.zoom;
     let (rect, response) =
         ui.allocate_at_least(ui.available_size(), egui::Sense::click_and_drag());
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Other, true, crate::utils::i18n::tr("Canvas")));
 
     let origin = rect.min + egui::vec2(app.offset.x, app.offset.y);
     let canvas_center = origin + canvas_size * 0.5;
@@ -81,60 +112,79 @@ pub fn draw_canvas(app: &mut PainterApp, ui: &mut egui::Ui) -> CanvasView {
         .map(|atlas| egui::Mesh::with_texture(atlas.texture.id()))
         .collect();
 
-    let half_texel = 0.5 / ATLAS_SIZE as f32;
+    let (canvas_w, canvas_h) = (app.canvas.width(), app.canvas.height());
+    // Wrap preview rolls the view by half the canvas so the edges meet in the middle
+    let (shift_x, shift_y) = if app.view_settings.wrap_preview { (canvas_w / 2, canvas_h / 2) } else { (0, 0) };
+    // Screen directions pointing out of the top, right, bottom and left canvas edges
+    let quarter = app.rotation.rem_euclid(std::f32::consts::FRAC_PI_2);
+    let tilted = (1e-4..=std::f32::consts::FRAC_PI_2 - 1e-4).contains(&quarter);
+    let feather = app.view_settings.smooth_rotated_edges && tilted;
+    let outward = [(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0)]
+        .map(|(x, y)| PainterApp::rotate_point(canvas_center + egui::vec2(x, y), canvas_center, cos, sin) - canvas_center);
 
+    let tile_size = app.canvas.tile_size();
     for tile in &app.tiles {
-        let x = (tile.tx * TILE_SIZE) as f32 * app.zoom;
-        let y = (tile.ty * TILE_SIZE) as f32 * app.zoom;
+        let x0 = tile.tx * tile_size;
+        let y0 = tile.ty * tile_size;
+        let tile_w = tile_size.min(canvas_w - x0);
+        let tile_h = tile_size.min(canvas_h - y0);
 
-        let tile_w =
-            (TILE_SIZE.min(app.canvas.width() - tile.tx * TILE_SIZE)) as f32 * app.zoom;
-        let tile_h =
-            (TILE_SIZE.min(app.canvas.height() - tile.ty * TILE_SIZE)) as f32 * app.zoom;
+        let Some(mesh) = meshes.get_mut(tile.atlas_idx) else {
+            continue;
+        };
+        for (sx0, sx1, dx) in wrap_spans(x0, x0 + tile_w, shift_x, canvas_w) {
+            for (sy0, sy1, dy) in wrap_spans(y0, y0 + tile_h, shift_y, canvas_h) {
+                let tile_rect = egui::Rect::from_min_max(
+                    origin + egui::vec2((sx0 as isize + dx) as f32, (sy0 as isize + dy) as f32) * app.zoom,
+                    origin + egui::vec2((sx1 as isize + dx) as f32, (sy1 as isize + dy) as f32) * app.zoom,
+                );
 
-        let tile_rect = egui::Rect::from_min_size(
-            origin + egui::vec2(x, y),
-            egui::vec2(tile_w, tile_h),
-        );
+                let corners = [
+                    PainterApp::rotate_point(tile_rect.left_top(), canvas_center, cos, sin),
+                    PainterApp::rotate_point(tile_rect.right_top(), canvas_center, cos, sin),
+                    PainterApp::rotate_point(tile_rect.right_bottom(), canvas_center, cos, sin),
+                    PainterApp::rotate_point(tile_rect.left_bottom(), canvas_center, cos, sin),
+                ];
 
-        let corners = [
-            PainterApp::rotate_point(tile_rect.left_top(), canvas_center, cos, sin),
-            PainterApp::rotate_point(tile_rect.right_top(), canvas_center, cos, sin),
-            PainterApp::rotate_point(tile_rect.right_bottom(), canvas_center, cos, sin),
-            PainterApp::rotate_point(tile_rect.left_bottom(), canvas_center, cos, sin),
-        ];
+                // Texel range of this piece; the atlas gutter keeps filtering at the edges in the tile
+                let texel_u = |px: usize| tile.atlas_x as f32 + (px - x0) as f32 * tile.pixel_w as f32 / tile_w as f32;
+                let texel_v = |py: usize| tile.atlas_y as f32 + (py - y0) as f32 * tile.pixel_h as f32 / tile_h as f32;
+                let u0 = texel_u(sx0) / ATLAS_SIZE as f32;
+                let v0 = texel_v(sy0) / ATLAS_SIZE as f32;
+                let u1 = texel_u(sx1) / ATLAS_SIZE as f32;
+                let v1 = texel_v(sy1) / ATLAS_SIZE as f32;
 
-        let u0 = (tile.atlas_x as f32 + half_texel) / ATLAS_SIZE as f32;
-        let v0 = (tile.atlas_y as f32 + half_texel) / ATLAS_SIZE as f32;
-        let u1 =
-            (tile.atlas_x as f32 + tile.pixel_w as f32 - half_texel) / ATLAS_SIZE as f32;
-        let v1 =
-            (tile.atlas_y as f32 + tile.pixel_h as f32 - half_texel) / ATLAS_SIZE as f32;
-
-        let uv_coords = [
-            egui::Pos2::new(u0, v0),
-            egui::Pos2::new(u1, v0),
-            egui::Pos2::new(u1, v1),
-            egui::Pos2::new(u0, v1),
-        ];
+                let uv_coords = [
+                    egui::Pos2::new(u0, v0),
+                    egui::Pos2::new(u1, v0),
+                    egui::Pos2::new(u1, v1),
+                    egui::Pos2::new(u0, v1),
+                ];
+
+                let base = mesh.vertices.len() as u32;
+                for (corner, uv) in corners.iter().zip(uv_coords.iter()) {
+                    mesh.vertices.push(egui::epaint::Vertex {
+                        pos: *corner,
+                        uv: *uv,
+                        color: Color32::WHITE,
+                    });
+                }
+                mesh.indices.extend_from_slice(&[
+                    base,
+                    base + 1,
+                    base + 2,
+                    base,
+                    base + 2,
+                    base + 3,
+                ]);
 
-        if let Some(mesh) = meshes.get_mut(tile.atlas_idx) {
-            let base = mesh.vertices.len() as u32;
-            for (corner, uv) in corners.iter().zip(uv_coords.iter()) {
-                mesh.vertices.push(egui::epaint::Vertex {
-                    pos: *corner,
-                    uv: *uv,
-                    color: Color32::WHITE,
-                });
+                if feather {
+                    let (left, top) = (sx0 as isize + dx, sy0 as isize + dy);
+                    let (right, bottom) = (sx1 as isize + dx, sy1 as isize + dy);
+                    let on_border = [top == 0, right == canvas_w as isize, bottom == canvas_h as isize, left == 0];
+                    push_edge_feather(mesh, &corners, &uv_coords, on_border, outward);
+                }
             }
-            mesh.indices.extend_from_slice(&[
-                base,
-                base + 1,
-                base + 2,
-                base,
-                base + 2,
-                base + 3,
-            ]);
         }
     }
 
@@ -152,3 +202,100 @@ pub fn draw_canvas(app: &mut PainterApp, ui: &mut egui::Ui) -> CanvasView {
         response,
     }
 }
+
+/// Copy of a tile image with its edge texels repeated `ATLAS_GUTTER` times on every side.
+pub(crate) fn with_gutter(img: &egui::ColorImage) -> egui::ColorImage {
+    let _timer = ScopeTimer::new("texture_pack");
+    let [w, h] = img.size;
+    let (padded_w, padded_h) = (w + 2 * ATLAS_GUTTER, h + 2 * ATLAS_GUTTER);
+    let mut pixels = Vec::with_capacity(padded_w * padded_h);
+    for y in 0..padded_h {
+        let src_y = y.saturating_sub(ATLAS_GUTTER).min(h - 1);
+        let row = &img.pixels[src_y * w..(src_y + 1) * w];
+        pixels.extend(std::iter::repeat_n(row[0], ATLAS_GUTTER));
+        pixels.extend_from_slice(row);
+        pixels.extend(std::iter::repeat_n(row[w - 1], ATLAS_GUTTER));
+    }
+    egui::ColorImage {
+        size: [padded_w, padded_h],
+        pixels,
+    }
+}
+
+/// Screen points the feathered canvas border reaches outwards.
+const EDGE_FEATHER: f32 = 1.0;
+
+/// Add a strip outside each border side of a tile quad that repeats the edge texels and fades
+/// to transparent. Triangles are not antialiased, so without it a rotated canvas edge is jagged.
+/// The strips are widened at the ends so neighbouring sides meet at the canvas corners.
+fn push_edge_feather(
+    mesh: &mut egui::Mesh,
+    corners: &[egui::Pos2; 4],
+    uvs: &[egui::Pos2; 4],
+    on_border: [bool; 4],
+    outward: [egui::Vec2; 4],
+) {
+    for side in 0..4 {
+        if !on_border[side] {
+            continue;
+        }
+        let (a, b) = (side, (side + 1) % 4);
+        let tangent = (corners[b] - corners[a]).normalized();
+        let out = outward[side] * EDGE_FEATHER;
+        let base = mesh.vertices.len() as u32;
+        let vertices = [
+            (corners[a], uvs[a], Color32::WHITE),
+            (corners[b], uvs[b], Color32::WHITE),
+            (corners[b] + out + tangent * EDGE_FEATHER, uvs[b], Color32::TRANSPARENT),
+            (corners[a] + out - tangent * EDGE_FEATHER, uvs[a], Color32::TRANSPARENT),
+        ];
+        for (pos, uv, color) in vertices {
+            mesh.vertices.push(egui::epaint::Vertex { pos, uv, color });
+        }
+        mesh.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+}
+
+/// Split the canvas span `start..end` where it crosses the wrap seam after shifting it by
+/// `shift` within `0..size`. Each piece comes with the offset to draw it at.
+fn wrap_spans(start: usize, end: usize, shift: usize, size: usize) -> impl Iterator<Item = (usize, usize, isize)> {
+    let seam = size - shift;
+    let before = (start < seam).then(|| (start, end.min(seam), shift as isize));
+    let after = (end > seam).then(|| (start.max(seam), end, shift as isize - size as isize));
+    before.into_iter().chain(after)
+}
+
+/// Outline the active layer's tile buffers: green ones hold pixels, orange ones are allocated but empty.
+pub fn draw_tile_overlay(app: &PainterApp, painter: &egui::Painter, view: &CanvasView) {
+    let tile_size = app.canvas.tile_size() as f32 * app.zoom;
+    let (cos, sin) = (app.rotation.cos(), app.rotation.sin());
+    let tiles = app.canvas.allocated_tiles(app.canvas.active_layer_idx);
+    let empty = tiles.iter().filter(|(_, is_empty)| *is_empty).count();
+
+    for ((tx, ty), is_empty) in &tiles {
+        let rect = egui::Rect::from_min_size(
+            view.origin + egui::vec2(*tx as f32, *ty as f32) * tile_size,
+            egui::vec2(tile_size, tile_size),
+        );
+        let corners = [rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom()]
+            .map(|p| PainterApp::rotate_point(p, view.canvas_center, cos, sin));
+        let color = if *is_empty {
+            Color32::from_rgb(255, 150, 30)
+        } else {
+            Color32::from_rgb(60, 200, 90)
+        };
+        painter.add(egui::Shape::convex_polygon(
+            corners.to_vec(),
+            color.gamma_multiply(0.15),
+            egui::Stroke::new(1.0, color),
+        ));
+    }
+
+    painter.text(
+        view.response.rect.left_top() + egui::vec2(8.0, 8.0),
+        egui::Align2::LEFT_TOP,
+        format!("{} tiles allocated, {} empty", tiles.len(), empty),
+        egui::FontId::monospace(12.0),
+        Color32::WHITE,
+    );
+}
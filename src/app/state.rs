@@ -1,8 +1,53 @@
 use crate::canvas::canvas::Canvas;
-use eframe::egui::{Color32, TextureHandle};
+use crate::canvas::history::UndoAction;
+use crate::selection::SelectionShape;
+use crate::selection::transform::TransformInfo;
+use crate::utils::color::ColorDeficiency;
+use eframe::egui::{Color32, TextureFilter, TextureHandle};
 
-pub const TILE_SIZE: usize = 64;
+/// Tile edge for canvases made outside a document, like previews and benchmarks.
+pub const DEFAULT_TILE_SIZE: usize = 64;
+/// Tile edges a document can be split into.
+pub const TILE_SIZES: [usize; 4] = [64, 128, 256, 512];
+/// Tile count above which automatic selection moves to bigger tiles.
+const MAX_AUTO_TILES: usize = 16_384;
 pub const ATLAS_SIZE: usize = 2048;
+/// Texels of duplicated tile edge around each tile in the atlas, so linear filtering at a
+/// tile border never samples the unrelated tile packed next to it.
+pub const ATLAS_GUTTER: usize = 1;
+
+/// Atlas space taken by one tile including its gutter.
+pub const fn atlas_slot(tile_size: usize) -> usize {
+    tile_size + 2 * ATLAS_GUTTER
+}
+
+/// Tile edge for a new document. Small tiles keep dabs cheap to snapshot and composite, but
+/// huge canvases would need hundreds of thousands of them, so the size steps up until the tile
+/// map is manageable, as long as every thread still gets plenty of tiles in whole-canvas passes.
+pub fn auto_tile_size(width: usize, height: usize, threads: usize) -> usize {
+    let area = width * height;
+    let min_tiles = threads.max(1) * 16;
+    let mut size = TILE_SIZES[0];
+    for &next in &TILE_SIZES[1..] {
+        if area / (size * size) <= MAX_AUTO_TILES || area / (next * next) < min_tiles {
+            break;
+        }
+        size = next;
+    }
+    size
+}
+/// Edge length of the canvas suggested before any document exists.
+pub const DEFAULT_CANVAS_SIZE: usize = 4000;
+
+/// Sizes offered on the start screen, in pixels.
+pub const CANVAS_PRESETS: [(&str, usize, usize); 6] = [
+    ("Square", 2048, 2048),
+    ("Full HD", 1920, 1080),
+    ("4K UHD", 3840, 2160),
+    ("A4 @ 300 dpi", 2480, 3508),
+    ("Large Square", DEFAULT_CANVAS_SIZE, DEFAULT_CANVAS_SIZE),
+    ("Pixel Art", 64, 64),
+];
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CanvasUnit {
@@ -18,14 +63,28 @@ pub enum Orientation {
     Landscape,
 }
 
+/// Fill of the background layer. It can also be changed after creation with Edit > Background Color.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BackgroundChoice {
+    /// No paper: unpainted areas stay fully transparent, including in exports.
     Transparent,
     White,
     Black,
     Custom,
 }
 
+impl BackgroundChoice {
+    /// Fill color for this choice, using `custom` for `Custom`.
+    pub fn color32(self, custom: Color32) -> Color32 {
+        match self {
+            BackgroundChoice::Transparent => Color32::TRANSPARENT,
+            BackgroundChoice::White => Color32::WHITE,
+            BackgroundChoice::Black => Color32::BLACK,
+            BackgroundChoice::Custom => custom,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ColorModel {
     Rgba,
@@ -51,6 +110,8 @@ pub struct NewCanvasSettings {
     pub custom_bg: Color32,
     pub color_model: ColorModel,
     pub color_depth: ColorDepth,
+    /// Tile edge in pixels, or `None` to pick one from the size and thread count.
+    pub tile_size: Option<usize>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -61,6 +122,7 @@ pub enum PaintBackend {
 pub struct CanvasTile {
     pub dirty: bool,
     pub atlas_idx: usize,
+    /// Top-left texel of the tile's pixels in its atlas, inside the gutter.
     pub atlas_x: usize,
     pub atlas_y: usize,
     pub pixel_w: usize,
@@ -73,7 +135,191 @@ pub struct TextureAtlas {
     pub texture: TextureHandle,
 }
 
+/// Viewport quality, navigation and display preferences. None of these touch the artwork.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewSettings {
+    /// Canvas texture filter when zoomed in past 100%.
+    pub zoom_in_filter: TextureFilter,
+    /// Canvas texture filter at 100% and below.
+    pub zoom_out_filter: TextureFilter,
+    /// Snap middle-mouse rotation to `ROTATION_SNAP_DEGREES` steps.
+    pub snap_rotation: bool,
+    /// Color vision simulation applied to the displayed composite only.
+    pub color_deficiency: ColorDeficiency,
+    /// Rulers along the top and left of the viewport.
+    pub show_rulers: bool,
+    pub ruler_unit: CanvasUnit,
+    /// Draw the canvas rolled by half its size (wrapping) to proof tileable seams.
+    /// Painting tools are paused while it is on.
+    pub wrap_preview: bool,
+    /// Fade the canvas border over a screen pixel while the view is rotated, hiding the
+    /// stair-stepped edge of the canvas mesh.
+    pub smooth_rotated_edges: bool,
+    /// Composition guides drawn over the canvas.
+    pub overlays: OverlayGuides,
+    /// Screen pixels of the canvas that panning and zooming always leave in the viewport;
+    /// 0 lets the canvas leave it entirely.
+    pub keep_canvas_visible: f32,
+}
+
+/// Composition aids drawn over the canvas in the viewport only; exports never include them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OverlayGuides {
+    /// Rule-of-thirds grid.
+    pub thirds: bool,
+    /// Cross through the middle.
+    pub center: bool,
+    /// Action-safe and title-safe frames.
+    pub safe_areas: bool,
+    /// Inset of the action-safe frame on each side, as a fraction of the width or height.
+    pub action_safe: f32,
+    /// Inset of the title-safe frame on each side, as a fraction of the width or height.
+    pub title_safe: f32,
+    /// Width and height of a crop preview; the canvas outside it is shaded like a letterbox.
+    /// The other guides follow the crop when there is one.
+    pub aspect: Option<(f32, f32)>,
+}
+
+impl OverlayGuides {
+    /// Crop ratios offered in the menus.
+    pub const ASPECT_PRESETS: [(&'static str, (f32, f32)); 6] = [
+        ("16:9", (16.0, 9.0)),
+        ("2.39:1", (2.39, 1.0)),
+        ("4:3", (4.0, 3.0)),
+        ("1:1", (1.0, 1.0)),
+        ("4:5", (4.0, 5.0)),
+        ("9:16", (9.0, 16.0)),
+    ];
+
+    pub fn any(&self) -> bool {
+        self.thirds || self.center || self.safe_areas || self.aspect.is_some()
+    }
+
+    /// Largest rectangle of the crop ratio centered on a `width` × `height` canvas, or the
+    /// whole canvas without a crop: `(x, y, width, height)` in canvas pixels.
+    pub fn frame(&self, width: f32, height: f32) -> (f32, f32, f32, f32) {
+        let Some((aw, ah)) = self.aspect.filter(|&(aw, ah)| aw > 0.0 && ah > 0.0) else {
+            return (0.0, 0.0, width, height);
+        };
+        let ratio = aw / ah;
+        if width / height > ratio {
+            let w = height * ratio;
+            ((width - w) / 2.0, 0.0, w, height)
+        } else {
+            let h = width / ratio;
+            (0.0, (height - h) / 2.0, width, h)
+        }
+    }
+}
+
+impl Default for OverlayGuides {
+    fn default() -> Self {
+        Self { thirds: false, center: false, safe_areas: false, action_safe: 0.05, title_safe: 0.1, aspect: None }
+    }
+}
+
+pub const ROTATION_SNAP_DEGREES: f32 = 15.0;
+/// Resolution assumed for documents that do not carry one, such as opened images.
+pub const DEFAULT_DPI: f32 = 72.0;
+
+/// A straight guide line across the canvas, dragged out of a ruler.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Guide {
+    /// Vertical guides come from the left ruler and sit at an x coordinate.
+    pub vertical: bool,
+    /// Canvas pixels from the left or top edge.
+    pub position: f32,
+}
+
+/// Square of pixels the eyedropper averages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SampleSize {
+    #[default]
+    Point,
+    Average3,
+    Average5,
+    Average11,
+}
+
+impl SampleSize {
+    pub const ALL: [SampleSize; 4] = [SampleSize::Point, SampleSize::Average3, SampleSize::Average5, SampleSize::Average11];
+
+    /// Pixels sampled on each side of the picked one.
+    pub fn radius(self) -> usize {
+        match self {
+            SampleSize::Point => 0,
+            SampleSize::Average3 => 1,
+            SampleSize::Average5 => 2,
+            SampleSize::Average11 => 5,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SampleSize::Point => "1 px",
+            SampleSize::Average3 => "3×3 average",
+            SampleSize::Average5 => "5×5 average",
+            SampleSize::Average11 => "11×11 average",
+        }
+    }
+}
+
+/// Eyedropper tool options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EyedropperSettings {
+    pub size: SampleSize,
+    /// Pick from what is shown rather than from the active layer alone.
+    pub all_layers: bool,
+}
+
+impl Default for EyedropperSettings {
+    fn default() -> Self {
+        Self { size: SampleSize::Point, all_layers: true }
+    }
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            zoom_in_filter: TextureFilter::Nearest,
+            zoom_out_filter: TextureFilter::Linear,
+            snap_rotation: false,
+            color_deficiency: ColorDeficiency::None,
+            show_rulers: false,
+            ruler_unit: CanvasUnit::Pixels,
+            wrap_preview: false,
+            smooth_rotated_edges: true,
+            overlays: OverlayGuides::default(),
+            keep_canvas_visible: 64.0,
+        }
+    }
+}
+
+impl ViewSettings {
+    pub fn filter_for_zoom(&self, zoom: f32) -> TextureFilter {
+        if zoom > 1.0 { self.zoom_in_filter } else { self.zoom_out_filter }
+    }
+}
+
 impl CanvasUnit {
+    pub const ALL: [CanvasUnit; 4] = [
+        CanvasUnit::Pixels,
+        CanvasUnit::Inches,
+        CanvasUnit::Millimeters,
+        CanvasUnit::Centimeters,
+    ];
+
+    /// Canvas pixels in one unit at `dpi`.
+    pub fn pixels_per_unit(&self, dpi: f32) -> f32 {
+        let dpi = dpi.max(1.0);
+        match self {
+            CanvasUnit::Pixels => 1.0,
+            CanvasUnit::Inches => dpi,
+            CanvasUnit::Millimeters => dpi / 25.4,
+            CanvasUnit::Centimeters => dpi / 2.54,
+        }
+    }
+
     pub fn label(&self) -> &'static str {
         match self {
             CanvasUnit::Pixels => "px",
@@ -104,12 +350,18 @@ impl NewCanvasSettings {
             custom_bg: Color32::WHITE,
             color_model: ColorModel::Rgba,
             color_depth: ColorDepth::Bit8,
+            tile_size: None,
         }
     }
 
     pub fn sync_from_canvas(&mut self, canvas: &Canvas) {
         self.width = canvas.width() as f32;
         self.height = canvas.height() as f32;
+        self.sync_orientation();
+    }
+
+    /// Match the orientation to the current width and height.
+    pub fn sync_orientation(&mut self) {
         self.orientation = if self.width >= self.height {
             Orientation::Landscape
         } else {
@@ -118,15 +370,7 @@ impl NewCanvasSettings {
     }
 
     pub fn dimensions_in_pixels(&self) -> (usize, usize) {
-        let dpi = self.resolution.max(1.0);
-        let to_px = |value: f32| -> f32 {
-            match self.unit {
-                CanvasUnit::Pixels => value,
-                CanvasUnit::Inches => value * dpi,
-                CanvasUnit::Millimeters => value / 25.4 * dpi,
-                CanvasUnit::Centimeters => value / 2.54 * dpi,
-            }
-        };
+        let to_px = |value: f32| -> f32 { value * self.unit.pixels_per_unit(self.resolution) };
 
         let mut w = to_px(self.width.max(1.0));
         let mut h = to_px(self.height.max(1.0));
@@ -141,12 +385,7 @@ impl NewCanvasSettings {
     }
 
     pub fn background_color32(&self, model: ColorModel) -> Color32 {
-        let base = match self.background {
-            BackgroundChoice::Transparent => Color32::TRANSPARENT,
-            BackgroundChoice::White => Color32::WHITE,
-            BackgroundChoice::Black => Color32::BLACK,
-            BackgroundChoice::Custom => self.custom_bg,
-        };
+        let base = self.background.color32(self.custom_bg);
 
         let color = base;
         match model {
@@ -174,3 +413,14 @@ pub fn parse_backend_arg() -> PaintBackend {
     }
     backend
 }
+
+/// Undo bookkeeping for a floating transform, from the lift to the commit that turns it into
+/// a single history entry on the layer below.
+pub struct TransformSession {
+    /// Selection before the session, put back when the whole session is undone.
+    pub selection: Option<SelectionShape>,
+    /// Pixels lifted off the layer below; empty for a pasted image.
+    pub lift: UndoAction,
+    /// Transform when the current drag began, recorded as a step once the drag ends.
+    pub drag_from: Option<TransformInfo>,
+}
@@ -6,4 +6,22 @@ pub enum Tool {
     Brush,
     Select(SelectionType),
     Transform(TransformInfo),
-}
\ No newline at end of file
+    /// Drag points of the strokes on a vector layer.
+    EditPath,
+    /// Drag the active layer (or the layer selection) around by whole pixels.
+    Move,
+    /// Pick the brush color from the canvas.
+    Eyedropper,
+}
+/// What a tool hotkey switches to, bound in [`crate::app::keybindings::Keybindings`]: tapped
+/// to stay, held to switch back on release. The eraser is the brush painting in eraser mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolKey {
+    Brush,
+    Eraser,
+    Select,
+    Transform,
+    Path,
+    Move,
+    Eyedropper,
+}
@@ -1,17 +1,23 @@
-use crate::{brush_engine::{brush_options::{BlendMode, PixelBrushShape}, hardness::SoftnessSelector}, canvas::{
-    canvas::{Canvas, alpha_over, blend_erase},
+use crate::{brush_engine::brush_options::{BlendMode, Dither, PixelBrushShape, grain_factor}, canvas::{
+    canvas::{Canvas, alpha_over_batch, blend_soften},
     history::{TileSnapshot, UndoAction},
 }, selection::SelectionManager};
-use crate::utils::vector::Vec2;
+use crate::utils::{profiler::ScopeTimer, vector::Vec2};
 use eframe::egui::Color32;
 use rayon::ThreadPool;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use super::brush_options::BrushOptions;
+use super::dab_mask::{DabMask, DabMaskCache};
+use super::engine::EngineKind;
 
 /// Sample custom mask with nearest neighbor interpolation
 #[inline]
-fn sample_custom_mask_nn(dx: f32, dy: f32, diameter: f32, width: usize, height: usize, mask: &[u8]) -> (bool, f32) {
+pub(super) fn sample_custom_mask_nn(dx: f32, dy: f32, diameter: f32, width: usize, height: usize, mask: &[u8]) -> (bool, f32) {
     let r = diameter / 2.0;
     let nx = (dx + r) / diameter;
     let ny = (dy + r) / diameter;
@@ -28,14 +34,40 @@ fn sample_custom_mask_nn(dx: f32, dy: f32, diameter: f32, width: usize, height:
     (false, 0.0)
 }
 
+/// Sample an image tip with nearest neighbor interpolation; transparent outside the tip.
+#[inline]
+pub(super) fn sample_image_nn(dx: f32, dy: f32, diameter: f32, width: usize, height: usize, data: &[Color32]) -> Color32 {
+    let r = diameter / 2.0;
+    let nx = (dx + r) / diameter;
+    let ny = (dy + r) / diameter;
+    if (0.0..1.0).contains(&nx) && (0.0..1.0).contains(&ny) {
+        let ix = (nx * width as f32).floor() as usize;
+        let iy = (ny * height as f32).floor() as usize;
+        if let Some(&px) = data.get(iy * width + ix) {
+            return px;
+        }
+    }
+    Color32::TRANSPARENT
+}
+
+/// Map an offset from the dab center into the tip's own frame for a dab turned by `angle`.
+#[inline]
+pub(super) fn unrotate(dx: f32, dy: f32, angle: f32) -> (f32, f32) {
+    if angle == 0.0 {
+        return (dx, dy);
+    }
+    let (sin, cos) = angle.sin_cos();
+    (dx * cos + dy * sin, dy * cos - dx * sin)
+}
+
 /// Available shapes for how a brush applies paint.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum BrushType {
     Soft,
     Pixel,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum StabilizerAlgorithm {
     None,
     Simple,
@@ -54,11 +86,50 @@ struct TileRegion {
     height: usize,
 }
 
+/// A soft dab waiting to be painted, with its footprint placed on the canvas.
+#[derive(Clone)]
+struct QueuedDab {
+    mask: Arc<DabMask>,
+    /// Canvas position of the mask's first column and row.
+    min_x: i32,
+    min_y: i32,
+    /// Opacity, flow and pressure for this dab.
+    flow_alpha: f32,
+}
+
+impl QueuedDab {
+    /// Pixel bounds `(start_x, start_y, end_x, end_y)` of the dab inside the canvas, inclusive.
+    fn clip(&self, canvas_w: i32, canvas_h: i32) -> Option<(usize, usize, usize, usize)> {
+        let max_x = self.min_x + self.mask.side as i32 - 1;
+        let max_y = self.min_y + self.mask.side as i32 - 1;
+        if max_x < 0 || max_y < 0 || self.min_x >= canvas_w || self.min_y >= canvas_h {
+            return None;
+        }
+        Some((self.min_x.max(0) as usize, self.min_y.max(0) as usize, max_x.min(canvas_w - 1) as usize, max_y.min(canvas_h - 1) as usize))
+    }
+}
+
+/// Soft dabs of the current input segment, painted together tile by tile.
+#[derive(Clone, Default)]
+struct DabBatch {
+    dabs: Vec<QueuedDab>,
+}
+
+impl fmt::Debug for DabBatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DabBatch")
+            .field("dabs", &self.dabs.len())
+            .finish()
+    }
+}
+
 /// User-facing brush configuration and scratch buffers.
 #[derive(Clone, Debug)]
 pub struct Brush {
     pub brush_options: BrushOptions,
     pub is_changed: bool,
+    /// Backend that paints this brush's strokes.
+    pub engine: EngineKind,
     pub brush_type: BrushType,
     pub pixel_perfect: bool,
     pub anti_aliasing: bool,
@@ -67,6 +138,25 @@ pub struct Brush {
     pub stabilizer_algorithm: StabilizerAlgorithm,
     pub stabilizer_mass: f32, // 0.01..1.0
     pub stabilizer_drag: f32, // 0.0..1.0
+    /// Strength (0..1) of the curve fitted over a finished stroke; 0 keeps the stroke as drawn.
+    pub post_smoothing: f32,
+    /// Keep spraying while the pointer rests with the button down, so paint builds up over time.
+    pub airbrush: bool,
+    /// Dabs per second sprayed in airbrush mode.
+    pub airbrush_rate: f32,
+    /// Soft dab footprints for the current parameters.
+    pub(crate) dab_masks: DabMaskCache,
+    /// Tip rotation in radians for the next dab, chosen by the stroke.
+    pub(crate) dab_angle: f32,
+    /// Pen pressure (0..1) of the current input sample.
+    pub(crate) pressure: f32,
+    /// Input time in seconds of the current sample, when the device reports one. Lets the
+    /// engine ease pressure between samples the way the pen actually moved.
+    pub(crate) sample_time: Option<f64>,
+    /// Dabs stamped since the painter last reset it, for the document statistics.
+    pub(crate) dab_count: u64,
+    /// Soft dabs waiting for [`Self::flush_dabs`].
+    pending_dabs: DabBatch,
 }
 
 impl Brush {
@@ -82,7 +172,17 @@ impl Brush {
             stabilizer_algorithm: StabilizerAlgorithm::None,
             stabilizer_mass: 0.1,
             stabilizer_drag: 0.5,
+            post_smoothing: 0.0,
+            airbrush: false,
+            airbrush_rate: 30.0,
             is_changed: false,
+            engine: EngineKind::Dab,
+            dab_masks: DabMaskCache::default(),
+            dab_angle: 0.0,
+            pressure: 1.0,
+            sample_time: None,
+            dab_count: 0,
+            pending_dabs: DabBatch::default(),
         }
     }
 
@@ -99,11 +199,22 @@ impl Brush {
             stabilizer_algorithm: StabilizerAlgorithm::None,
             stabilizer_mass: 0.1,
             stabilizer_drag: 0.5,
+            post_smoothing: 0.0,
+            airbrush: false,
+            airbrush_rate: 30.0,
             is_changed: false,
+            engine: EngineKind::Dab,
+            dab_masks: DabMaskCache::default(),
+            dab_angle: 0.0,
+            pressure: 1.0,
+            sample_time: None,
+            dab_count: 0,
+            pending_dabs: DabBatch::default(),
         }
     }
 
-    /// Paint a single dab with the currently selected brush type.
+    /// Paint a single dab with the currently selected brush type. Soft dabs are queued and
+    /// land on the canvas with the next [`Self::flush_dabs`].
     pub(crate) fn dab(
         &mut self,
         pool: &ThreadPool,
@@ -113,10 +224,35 @@ impl Brush {
         undo_action: &mut UndoAction,
         modified_tiles: &mut HashSet<(usize, usize)>,
     ) {
+        let _timer = ScopeTimer::new("dab");
+        self.dab_count += 1;
         match self.brush_type {
-            BrushType::Soft => self.soft_dab(pool, canvas, selection, center, undo_action, modified_tiles),
+            BrushType::Soft => self.soft_dab(canvas, center),
             BrushType::Pixel => self.pixel_dab(pool, canvas, selection, center, undo_action, modified_tiles),
         }
+        let reach = Vec2::new(1.0, 1.0) * (self.brush_options.diameter / 2.0 + 1.0);
+        canvas.mark_dirty_rect(center - reach, center + reach);
+    }
+
+    /// Blend mode for the active layer: layers set to paint behind turn any painting mode into Behind.
+    fn blend_mode_for(&self, canvas: &Canvas) -> BlendMode {
+        let mode = self.brush_options.blend_mode;
+        let behind = canvas.layers.get(canvas.active_layer_idx).is_some_and(|l| l.paint_behind);
+        if behind && !mode.is_eraser() { BlendMode::Behind } else { mode }
+    }
+
+    /// Alpha multiplier for each dab from opacity, flow and pen pressure. Soften caps
+    /// the whole stroke with opacity instead, so it is left out for that mode.
+    fn dab_strength(&self, blend_mode: BlendMode) -> f32 {
+        let options = &self.brush_options;
+        let opacity = if blend_mode == BlendMode::Soften { 1.0 } else { options.opacity };
+        let pressure = if options.pressure_flow { self.pressure } else { 1.0 };
+        opacity * (options.flow / 100.0) * pressure
+    }
+
+    /// Rotation applied to the tip; round and square shapes are never turned.
+    fn dab_rotation(&self) -> f32 {
+        if self.brush_options.has_tip_image() { self.dab_angle } else { 0.0 }
     }
 
     /// Snapshot tiles about to be modified so undo can restore them later.
@@ -170,7 +306,10 @@ impl Brush {
         modified_tiles: &mut HashSet<(usize, usize)>,
     ) {
         let r = self.brush_options.diameter / 2.0;
-        let r_ceil = r.ceil() as i32;
+        let angle = self.dab_rotation();
+        // A turned tip reaches into the corners of its bounding square
+        let reach = if angle != 0.0 { r * std::f32::consts::SQRT_2 } else { r };
+        let r_ceil = reach.ceil() as i32;
 
         let min_x = (center.x.floor() as i32) - r_ceil;
         let max_x = (center.x.floor() as i32) + r_ceil;
@@ -223,10 +362,14 @@ impl Brush {
 
         self.snapshot_tiles(canvas, &regions, undo_action, modified_tiles);
 
+        let palette = canvas.palette.constraint();
+        let blend_mode = self.blend_mode_for(canvas);
+
         let src_base = self.brush_options.color;
-        let src_alpha =
-            (self.brush_options.color.a() as f32 * self.brush_options.opacity * (self.brush_options.flow / 100.0)).clamp(0.0, 1.0);
-        
+        let src_alpha = (self.brush_options.color.a() as f32 * self.dab_strength(blend_mode)).clamp(0.0, 1.0);
+        let opacity = self.brush_options.opacity;
+        let originals = (blend_mode == BlendMode::Soften).then(|| stroke_start_pixels(canvas.active_layer_idx, undo_action));
+
         // Pre-compute common shape data
         let r_sq = r * r;
         let custom_data_ref = match &self.brush_options.pixel_shape {
@@ -242,6 +385,7 @@ impl Brush {
                     Some(d) => d,
                     None => continue,
                 };
+                let original = originals.as_ref().and_then(|o| o.get(&(tx, ty)).copied());
 
                 let tile_x0 = tx * tile_size;
                 let tile_y0 = ty * tile_size;
@@ -253,15 +397,18 @@ impl Brush {
                 for gy in overlap_min_y..=overlap_max_y {
                     let dy = gy as f32 + 0.5 - center.y;
                     for gx in overlap_min_x..=overlap_max_x {
-                        let dx = gx as f32 + 0.5 - center.x;
+                        let (dx, dy) = unrotate(gx as f32 + 0.5 - center.x, dy, angle);
 
-                        if let Some(sel) = selection {
-                            if !sel.contains(Vec2 { x: gx as f32 + 0.5, y: gy as f32 + 0.5 }) {
-                                continue;
-                            }
+                        let coverage = match selection {
+                            Some(sel) => sel.coverage(Vec2 { x: gx as f32 + 0.5, y: gy as f32 + 0.5 }),
+                            None => 1.0,
+                        };
+                        if coverage <= 0.0 {
+                            continue;
                         }
 
-                        let (in_shape, alpha_mod) = match self.brush_options.pixel_shape {
+                        let mut src_rgb = [src_base.r(), src_base.g(), src_base.b()];
+                        let (in_shape, alpha_mod) = match &self.brush_options.pixel_shape {
                             PixelBrushShape::Circle => (dx * dx + dy * dy <= r_sq, 1.0),
                             PixelBrushShape::Square => (dx.abs() <= r && dy.abs() <= r, 1.0),
                             PixelBrushShape::Custom { .. } => {
@@ -271,6 +418,12 @@ impl Brush {
                                     (false, 0.0)
                                 }
                             }
+                            PixelBrushShape::Image { width, height, data } => {
+                                let px = sample_image_nn(dx, dy, self.brush_options.diameter, *width, *height, data);
+                                let [r, g, b, a] = px.to_srgba_unmultiplied();
+                                src_rgb = [r, g, b];
+                                (a > 0, a as f32 / 255.0)
+                            }
                         };
 
                         if in_shape {
@@ -281,20 +434,26 @@ impl Brush {
                             let dst = data[idx];
                             
                             // Combine base alpha with shape alpha (if any)
-                            let final_alpha = src_alpha * alpha_mod;
+                            let final_alpha = src_alpha * alpha_mod * coverage;
                             
                             let src_color = Color32::from_rgba_unmultiplied(
-                                src_base.r(),
-                                src_base.g(),
-                                src_base.b(),
+                                src_rgb[0],
+                                src_rgb[1],
+                                src_rgb[2],
                                 (final_alpha * 255.0).round().clamp(0.0, 255.0) as u8,
                             );
 
-                            let blended = match self.brush_options.blend_mode {
-                                BlendMode::Normal => alpha_over(src_color, dst),
-                                BlendMode::Eraser => blend_erase(src_color, dst),
+                            let blended = match original {
+                                Some(original) => {
+                                    let floor = original[idx].a() as f32 * (1.0 - opacity * alpha_mod * coverage);
+                                    blend_soften(src_color, dst, floor)
+                                }
+                                None => blend_mode.apply(src_color, dst),
+                            };
+                            data[idx] = match palette {
+                                Some(palette) => palette.constrain(blended),
+                                None => blended,
                             };
-                            data[idx] = blended;
                         }
                     }
                 }
@@ -304,69 +463,68 @@ impl Brush {
         }
     }
 
-    /// Render a soft, anti-aliased dab using the cached mask and parallel tiling.
-    fn soft_dab(
+    /// Queue a soft, anti-aliased dab using the cached mask. It is painted by the next
+    /// [`Self::flush_dabs`], together with the other dabs of the same input segment.
+    fn soft_dab(&mut self, canvas: &Canvas, center: Vec2) {
+        // The footprint is cached per sub-pixel phase, so snap the center to that grid
+        let (center_x, center_y) = DabMaskCache::snap_center(&self.brush_options, center.x, center.y);
+        let angle = self.dab_rotation();
+        let mask = self.dab_masks.get(&self.brush_options, self.anti_aliasing, angle, center_x, center_y);
+        // Pressure changes from dab to dab, so the strength is taken now
+        let blend_mode = self.blend_mode_for(canvas);
+        self.pending_dabs.dabs.push(QueuedDab {
+            min_x: (center_x.floor() as i32) - mask.radius,
+            min_y: (center_y.floor() as i32) - mask.radius,
+            flow_alpha: self.dab_strength(blend_mode),
+            mask,
+        });
+    }
+
+    /// Paint the queued soft dabs.
+    ///
+    /// Dabs are grouped by the tiles they touch, and each tile is locked once and gets its dabs
+    /// in stroke order, so the result matches painting them one at a time while small spacing
+    /// no longer costs a parallel pass and a round of tile locks per dab.
+    pub(crate) fn flush_dabs(
         &mut self,
-        _pool: &ThreadPool,
+        pool: &ThreadPool,
         canvas: &Canvas,
         selection: Option<&SelectionManager>,
-        center: Vec2,
         undo_action: &mut UndoAction,
         modified_tiles: &mut HashSet<(usize, usize)>,
     ) {
-        let r = self.brush_options.diameter / 2.0;
-        let r_sq = r * r;
-        let r_ceil = r.ceil() as i32;
-
-        let min_x = (center.x.floor() as i32) - r_ceil;
-        let max_x = (center.x.floor() as i32) + r_ceil;
-        let min_y = (center.y.floor() as i32) - r_ceil;
-        let max_y = (center.y.floor() as i32) + r_ceil;
+        if self.pending_dabs.dabs.is_empty() {
+            return;
+        }
+        let _timer = ScopeTimer::new("dab batch");
+        let dabs = std::mem::take(&mut self.pending_dabs.dabs);
 
         let tile_size = canvas.tile_size();
         let canvas_w = canvas.width() as i32;
         let canvas_h = canvas.height() as i32;
 
-        if max_x < 0 || max_y < 0 || min_x >= canvas_w || min_y >= canvas_h {
-            return;
+        // Indices of the dabs touching each tile, in the order they were stamped
+        let mut by_tile: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (i, dab) in dabs.iter().enumerate() {
+            let Some((start_x, start_y, end_x, end_y)) = dab.clip(canvas_w, canvas_h) else {
+                continue;
+            };
+            for ty in start_y / tile_size..=end_y / tile_size {
+                for tx in start_x / tile_size..=end_x / tile_size {
+                    by_tile.entry((tx, ty)).or_default().push(i);
+                }
+            }
         }
-
-        let start_x = min_x.max(0) as usize;
-        let start_y = min_y.max(0) as usize;
-        let end_x = max_x.min(canvas_w - 1) as usize;
-        let end_y = max_y.min(canvas_h - 1) as usize;
-
-        if start_x > end_x || start_y > end_y {
+        if by_tile.is_empty() {
             return;
         }
+        let mut tiles: Vec<((usize, usize), Vec<usize>)> = by_tile.into_iter().collect();
+        tiles.sort_unstable_by_key(|((tx, ty), _)| (*ty, *tx));
 
-        let min_tx = start_x / tile_size;
-        let max_tx = end_x / tile_size;
-        let min_ty = start_y / tile_size;
-        let max_ty = end_y / tile_size;
-
-        let tiles: Vec<(usize, usize)> = (min_ty..=max_ty)
-            .flat_map(|ty| (min_tx..=max_tx).map(move |tx| (tx, ty)))
+        let regions: Vec<TileRegion> = tiles
+            .iter()
+            .map(|((tx, ty), _)| TileRegion { tx: *tx, ty: *ty, x0: 0, y0: 0, width: tile_size, height: tile_size })
             .collect();
-
-        let mut regions = Vec::with_capacity(tiles.len());
-        for (tx, ty) in &tiles {
-            let tile_x0 = tx * tile_size;
-            let tile_y0 = ty * tile_size;
-            let overlap_min_x = start_x.max(tile_x0);
-            let overlap_max_x = end_x.min(tile_x0 + tile_size - 1);
-            let overlap_min_y = start_y.max(tile_y0);
-            let overlap_max_y = end_y.min(tile_y0 + tile_size - 1);
-            regions.push(TileRegion {
-                tx: *tx,
-                ty: *ty,
-                x0: overlap_min_x - tile_x0,
-                y0: overlap_min_y - tile_y0,
-                width: overlap_max_x - overlap_min_x + 1,
-                height: overlap_max_y - overlap_min_y + 1,
-            });
-        }
-
         self.snapshot_tiles(canvas, &regions, undo_action, modified_tiles);
 
         // Pre-compute values outside loops
@@ -375,130 +533,19 @@ impl Brush {
         let sg = base_color.g();
         let sb = base_color.b();
         let base_alpha = base_color.a() as f32 / 255.0;
-        let flow_alpha = self.brush_options.opacity * (self.brush_options.flow / 100.0);
-        let blend_mode = self.brush_options.blend_mode;
-        let anti_aliasing = self.anti_aliasing;
-        let hardness_val = (self.brush_options.hardness / 100.0).clamp(0.0, 0.999);
-        let softness_selector = self.brush_options.softness_selector;
-        let softness_curve = &self.brush_options.softness_curve;
-        let pixel_shape = &self.brush_options.pixel_shape;
-        let diameter = self.brush_options.diameter;
-
-        let center_x = center.x;
-        let center_y = center.y;
-        let start_x = start_x;
-        let start_y = start_y;
-        let end_x = end_x;
-        let end_y = end_y;
-
-        // Pre-compute fade values
-        let fade_start = (r - 1.0).max(0.0);
-        let fade_width = r - fade_start;
-        let inv_fade_width = if fade_width > 0.0 { 1.0 / fade_width } else { 0.0 };
-
-        // Helper to get base alpha factor for a given point (dx, dy) and radius r
-        let get_base_alpha = |dx: f32, dy: f32, radius: f32, shape: &PixelBrushShape| -> f32 {
-            match shape {
-                PixelBrushShape::Circle => {
-                    let dist = (dx * dx + dy * dy).sqrt();
-                    let t = dist / radius;
-                    if dist >= radius {
-                        0.0
-                    } else {
-                        match softness_selector {
-                            SoftnessSelector::Gaussian => {
-                                if t < hardness_val {
-                                    1.0
-                                } else {
-                                    let v = (t - hardness_val) / (1.0 - hardness_val);
-                                    let falloff = 1.0 - v.clamp(0.0, 1.0);
-                                    let f2 = falloff * falloff;
-                                    f2 * (3.0 - 2.0 * falloff)
-                                }
-                            }
-                            SoftnessSelector::Curve => softness_curve.eval(t),
-                        }
-                    }
-                }
-                PixelBrushShape::Square => {
-                    let dist_x = dx.abs();
-                    let dist_y = dy.abs();
-                    let dist = dist_x.max(dist_y);
-                    let t = dist / radius;
-                    if dist >= radius {
-                        0.0
-                    } else {
-                        match softness_selector {
-                            SoftnessSelector::Gaussian => {
-                                if t < hardness_val {
-                                    1.0
-                                } else {
-                                    let v = (t - hardness_val) / (1.0 - hardness_val);
-                                    let falloff = 1.0 - v.clamp(0.0, 1.0);
-                                    let f2 = falloff * falloff;
-                                    f2 * (3.0 - 2.0 * falloff)
-                                }
-                            }
-                            SoftnessSelector::Curve => softness_curve.eval(t),
-                        }
-                    }
-                }
-                PixelBrushShape::Custom { width, height, data } => {
-                    // Bilinear interpolation for smooth custom brushes
-                    let nx = (dx + radius) / (radius * 2.0);
-                    let ny = (dy + radius) / (radius * 2.0);
-
-                    if nx >= 0.0 && nx < 1.0 && ny >= 0.0 && ny < 1.0 {
-                        let tx = nx * (*width as f32);
-                        let ty = ny * (*height as f32);
-
-                        let x0 = tx.floor() as usize;
-                        let y0 = ty.floor() as usize;
-                        let x1 = (x0 + 1).min(width - 1);
-                        let y1 = (y0 + 1).min(height - 1);
-
-                        let fx = tx - x0 as f32;
-                        let fy = ty - y0 as f32;
-                        
-                        let get_pixel = |x: usize, y: usize| -> f32 {
-                            if x < *width && y < *height {
-                                data[y * width + x] as f32 / 255.0
-                            } else {
-                                0.0
-                            }
-                        };
-
-                        let c00 = get_pixel(x0, y0);
-                        let c10 = get_pixel(x1, y0);
-                        let c01 = get_pixel(x0, y1);
-                        let c11 = get_pixel(x1, y1);
-
-                        c00 * (1.0 - fx) * (1.0 - fy) +
-                         c10 * fx * (1.0 - fy) +
-                         c01 * (1.0 - fx) * fy +
-                         c11 * fx * fy
-                    } else {
-                        0.0
-                    }
-                }
-            }
-        };
-
-        // Pre-calculate alpha at the fade start boundary
-        let _alpha_at_fade_start = get_base_alpha(fade_start, 0.0, r, &pixel_shape);
-
-        _pool.install(|| {
-            tiles.par_iter().for_each(|(tx, ty)| {
+        let blend_mode = self.blend_mode_for(canvas);
+        let opacity = self.brush_options.opacity;
+        let originals = (blend_mode == BlendMode::Soften).then(|| stroke_start_pixels(canvas.active_layer_idx, undo_action));
+        let dither = self.brush_options.dither;
+        let (grain, grain_scale) = (self.brush_options.grain, self.brush_options.grain_scale);
+        let palette = canvas.palette.constraint();
+        // Plain paint over the whole row can go through the SIMD blend
+        let batch_rows = selection.is_none() && dither == Dither::Off && blend_mode == BlendMode::Normal && palette.is_none();
+
+        pool.install(|| {
+            tiles.par_iter().for_each(|((tx, ty), dab_indices)| {
                 let tile_x0 = tx * tile_size;
                 let tile_y0 = ty * tile_size;
-                let tile_x1 = tile_x0 + tile_size;
-                let tile_y1 = tile_y0 + tile_size;
-
-                // Check if tile is reasonably close to center (bounding box check)
-                if center_x < (tile_x0 as f32 - r) || center_x > (tile_x1 as f32 + r) ||
-                   center_y < (tile_y0 as f32 - r) || center_y > (tile_y1 as f32 + r) {
-                    return;
-                }
 
                 if let Some(tile_arc) = canvas.lock_tile(*tx, *ty) {
                     let mut tile = tile_arc.lock().unwrap();
@@ -506,87 +553,110 @@ impl Brush {
                         Some(d) => d,
                         None => return,
                     };
+                    let original = originals.as_ref().and_then(|o| o.get(&(*tx, *ty)).copied());
 
-                    let overlap_min_x = start_x.max(tile_x0);
-                    let overlap_max_x = end_x.min(tile_x0 + tile_size - 1);
-                    let overlap_min_y = start_y.max(tile_y0);
-                    let overlap_max_y = end_y.min(tile_y0 + tile_size - 1);
+                    let mut src_row = Vec::with_capacity(tile_size);
+                    let mut out_row = Vec::with_capacity(tile_size);
 
-                    for gy in overlap_min_y..=overlap_max_y {
-                        for gx in overlap_min_x..=overlap_max_x {
-                            let pdx = gx as f32 + 0.5 - center_x;
-                            let pdy = gy as f32 + 0.5 - center_y;
+                    for dab in dab_indices.iter().map(|&i| &dabs[i]) {
+                        let Some((start_x, start_y, end_x, end_y)) = dab.clip(canvas_w, canvas_h) else {
+                            continue;
+                        };
+                        let (mask, min_x, min_y, flow_alpha) = (&dab.mask, dab.min_x, dab.min_y, dab.flow_alpha);
 
-                            if let Some(sel) = selection {
-                                if !sel.contains(Vec2 { x: gx as f32 + 0.5, y: gy as f32 + 0.5 }) {
-                                    continue;
-                                }
+                        let overlap_min_x = start_x.max(tile_x0);
+                        let overlap_max_x = end_x.min(tile_x0 + tile_size - 1);
+                        let overlap_min_y = start_y.max(tile_y0);
+                        let overlap_max_y = end_y.min(tile_y0 + tile_size - 1);
+
+                        for gy in overlap_min_y..=overlap_max_y {
+                            let my = (gy as i32 - min_y) as usize;
+                            // Skip rows the dab doesn't reach and trim the rest to the painted span
+                            let Some((first, last)) = mask.spans[my] else {
+                                continue;
+                            };
+                            let row_min_x = overlap_min_x.max((min_x + first as i32) as usize);
+                            let row_max_x = overlap_max_x.min((min_x + last as i32) as usize);
+                            if row_min_x > row_max_x {
+                                continue;
                             }
-                            
-                            let alpha_factor = if anti_aliasing {
-                                // Anti-aliased path (smooth, uses get_base_alpha and AA fade)
-                                let base_alpha_at_pixel = get_base_alpha(pdx, pdy, r, &pixel_shape);
-                                
-                                if base_alpha_at_pixel <= 0.0 { // Early exit if inner shape is transparent
-                                    0.0
-                                } else {
-                                    // Apply the 1.5 pixel outer fade to the base alpha
-                                    let dist_for_aa = match pixel_shape { // Distance metric for the AA fade
-                                        PixelBrushShape::Circle => (pdx * pdx + pdy * pdy).sqrt(),
-                                        PixelBrushShape::Square => pdx.abs().max(pdy.abs()),
-                                        PixelBrushShape::Custom { .. } => pdx.abs().max(pdy.abs()), // Use square for AA distance for custom
-                                    };
-                                    
-                                    if dist_for_aa >= r { // Beyond brush radius, fully transparent
-                                        0.0
-                                    } else if dist_for_aa > fade_start { // Within AA fade zone
-                                        let fraction = (dist_for_aa - fade_start) * inv_fade_width;
-                                        base_alpha_at_pixel * (1.0 - fraction) // Blend base alpha with fade
-                                    } else { // Solid interior
-                                        base_alpha_at_pixel
-                                    }
+                            let mask_row = &mask.alpha[my * mask.side..(my + 1) * mask.side];
+                            let mask_x = |gx: usize| mask_row[(gx as i32 - min_x) as usize];
+                            // Image tips bring their own color per pixel
+                            let color_row = (!mask.colors.is_empty()).then(|| &mask.colors[my * mask.side..(my + 1) * mask.side]);
+                            let rgb = |gx: usize| match color_row {
+                                Some(row) => {
+                                    let c = row[(gx as i32 - min_x) as usize];
+                                    (c.r(), c.g(), c.b())
                                 }
-                            } else {
-                                // Non-anti-aliased path (hard edges)
-                                let (in_shape, alpha_mod) = match &pixel_shape {
-                                    PixelBrushShape::Circle => {
-                                        ((pdx * pdx + pdy * pdy) <= r_sq, 1.0)
-                                    }
-                                    PixelBrushShape::Square => {
-                                        (pdx.abs() <= r && pdy.abs() <= r, 1.0)
-                                    }
-                                    PixelBrushShape::Custom { width, height, data } => {
-                                        sample_custom_mask_nn(pdx, pdy, diameter, *width, *height, data)
-                                    }
-                                };
-                                if in_shape { alpha_mod } else { 0.0 }
+                                None => (sr, sg, sb),
                             };
-
-                            if alpha_factor <= 0.0 {
+                            let row_start = (gy - tile_y0) * tile_size;
+
+                            if batch_rows {
+                                src_row.clear();
+                                src_row.extend((row_min_x..=row_max_x).map(|gx| {
+                                    let src_a = (base_alpha * flow_alpha * mask_x(gx) * grain_factor(grain, grain_scale, gx, gy)).clamp(0.0, 1.0);
+                                    let (r, g, b) = rgb(gx);
+                                    Color32::from_rgba_unmultiplied(r, g, b, (src_a * 255.0).round() as u8)
+                                }));
+                                let dst_row = &mut data[row_start + row_min_x - tile_x0..=row_start + row_max_x - tile_x0];
+                                out_row.resize(src_row.len(), Color32::TRANSPARENT);
+                                alpha_over_batch(&src_row, dst_row, &mut out_row);
+                                // Leave untouched pixels bit-identical (custom tips may have holes)
+                                for ((dst, out), src) in dst_row.iter_mut().zip(&out_row).zip(&src_row) {
+                                    if src.a() > 0 {
+                                        *dst = *out;
+                                    }
+                                }
                                 continue;
                             }
 
-                            let src_a = (base_alpha * flow_alpha * alpha_factor).clamp(0.0, 1.0);
-                            if src_a <= 0.0 {
-                                continue;
-                            }
-                            let src = Color32::from_rgba_unmultiplied(
-                                sr,
-                                sg,
-                                sb,
-                                (src_a * 255.0).round().clamp(0.0, 255.0) as u8,
-                            );
+                            for gx in row_min_x..=row_max_x {
+                                let alpha_factor = mask_x(gx);
+                                if alpha_factor <= 0.0 {
+                                    continue;
+                                }
 
-                            let local_y = gy - tile_y0;
-                            let local_x = gx - tile_x0;
-                            let idx = local_y * tile_size + local_x;
+                                let coverage = match selection {
+                                    Some(sel) => sel.coverage(Vec2 { x: gx as f32 + 0.5, y: gy as f32 + 0.5 }),
+                                    None => 1.0,
+                                };
+                                if coverage <= 0.0 {
+                                    continue;
+                                }
 
-                            let dst = data[idx];
-                            let blended = match blend_mode {
-                                BlendMode::Normal => alpha_over(src, dst),
-                                BlendMode::Eraser => blend_erase(src, dst),
-                            };
-                            data[idx] = blended;
+                                let grain_alpha = grain_factor(grain, grain_scale, gx, gy);
+                                let mut src_a = (base_alpha * flow_alpha * alpha_factor * coverage * grain_alpha).clamp(0.0, 1.0);
+                                if dither != Dither::Off {
+                                    // Keep the pixel fully opaque or skip it
+                                    src_a = if src_a > dither.threshold(gx, gy) { 1.0 } else { 0.0 };
+                                }
+                                if src_a <= 0.0 {
+                                    continue;
+                                }
+                                let (r, g, b) = rgb(gx);
+                                let src = Color32::from_rgba_unmultiplied(
+                                    r,
+                                    g,
+                                    b,
+                                    (src_a * 255.0).round().clamp(0.0, 255.0) as u8,
+                                );
+
+                                let idx = row_start + gx - tile_x0;
+                                let dst = data[idx];
+                                let blended = match original {
+                                    Some(original) => {
+                                        let floor = original[idx].a() as f32 * (1.0 - opacity * alpha_factor * coverage);
+                                        blend_soften(src, dst, floor)
+                                    }
+                                    None => blend_mode.apply(src, dst),
+                                };
+                                data[idx] = match palette {
+                                    Some(palette) => palette.constrain(blended),
+                                    None => blended,
+                                };
+                            }
                         }
                     }
                     // Mark tile as dirty (not empty) after modifications
@@ -597,9 +667,73 @@ impl Brush {
     }
 }
 
+/// Pixels of the active layer's tiles as they were when the stroke first touched them.
+fn stroke_start_pixels(layer_idx: usize, undo_action: &UndoAction) -> HashMap<(usize, usize), &[Color32]> {
+    undo_action
+        .tiles
+        .iter()
+        .filter(|t| t.layer_idx == layer_idx)
+        .map(|t| ((t.tx as usize, t.ty as usize), t.data.as_slice()))
+        .collect()
+}
+
 /// Named preset that can be displayed in the UI and cloned into the active brush.
 #[derive(Clone, Debug)]
 pub struct BrushPreset {
     pub name: String,
     pub brush: Brush,
+    /// Free-form labels used to filter the preset list.
+    pub tags: Vec<String>,
+    pub favorite: bool,
+}
+
+impl BrushPreset {
+    pub fn new(name: impl Into<String>, brush: Brush) -> Self {
+        Self {
+            name: name.into(),
+            brush,
+            tags: Vec::new(),
+            favorite: false,
+        }
+    }
+
+    /// Whether the preset matches a search query on its name or any tag (case-insensitive).
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || self.name.to_lowercase().contains(&query)
+            || self.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+    }
+
+    /// Hash of everything that shows up in a preset thumbnail.
+    ///
+    /// Size and color are left out because thumbnails are drawn at a fixed size in white.
+    pub fn thumbnail_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let brush = &self.brush;
+        let options = &brush.brush_options;
+        brush.engine.hash(&mut hasher);
+        brush.brush_type.hash(&mut hasher);
+        brush.pixel_perfect.hash(&mut hasher);
+        brush.anti_aliasing.hash(&mut hasher);
+        brush.jitter.to_bits().hash(&mut hasher);
+        options.hardness.to_bits().hash(&mut hasher);
+        options.softness_selector.hash(&mut hasher);
+        for point in &options.softness_curve.points {
+            point.x.to_bits().hash(&mut hasher);
+            point.y.to_bits().hash(&mut hasher);
+        }
+        options.pixel_shape.hash(&mut hasher);
+        options.spacing.to_bits().hash(&mut hasher);
+        options.flow.to_bits().hash(&mut hasher);
+        options.opacity.to_bits().hash(&mut hasher);
+        options.blend_mode.hash(&mut hasher);
+        options.dither.hash(&mut hasher);
+        options.pressure_flow.hash(&mut hasher);
+        options.follow_direction.hash(&mut hasher);
+        options.random_rotation.to_bits().hash(&mut hasher);
+        options.grain.to_bits().hash(&mut hasher);
+        options.grain_scale.to_bits().hash(&mut hasher);
+        hasher.finish()
+    }
 }
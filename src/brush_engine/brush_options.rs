@@ -2,8 +2,10 @@ use eframe::egui::Color32;
 
 use crate::brush_engine::hardness::SoftnessSelector;
 use crate::brush_engine::hardness::SoftnessCurve;
+use crate::canvas::noise::fractal_noise;
+use crate::canvas::canvas::{alpha_over, blend_add, blend_behind, blend_color, blend_erase, blend_multiply, debug_assert_premultiplied};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum PixelBrushShape {
     Circle,
     Square,
@@ -12,18 +14,128 @@ pub enum PixelBrushShape {
         height: usize,
         data: Vec<u8>, // 0-255 mask
     },
+    /// Full-color stamp whose pixels (premultiplied) replace the brush color.
+    Image {
+        width: usize,
+        height: usize,
+        data: Vec<Color32>,
+    },
 }
 
 /// Blending strategy for how source color affects the destination.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum BlendMode {
     Normal,
     Eraser,
+    /// Eraser that only lowers alpha part of the way: each stroke removes at most
+    /// `opacity` of what the pixel had when it started, at a rate set by flow.
+    Soften,
+    Multiply,
+    /// Linear dodge.
+    Add,
+    Color,
+    /// Only paints into transparent areas.
+    Behind,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 7] = [
+        BlendMode::Normal,
+        BlendMode::Eraser,
+        BlendMode::Soften,
+        BlendMode::Multiply,
+        BlendMode::Add,
+        BlendMode::Color,
+        BlendMode::Behind,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Eraser => "Eraser",
+            BlendMode::Soften => "Soften (partial erase)",
+            BlendMode::Multiply => "Multiply",
+            BlendMode::Add => "Add",
+            BlendMode::Color => "Color",
+            BlendMode::Behind => "Behind",
+        }
+    }
+
+    /// Whether the mode removes paint rather than adding it.
+    pub fn is_eraser(self) -> bool {
+        matches!(self, BlendMode::Eraser | BlendMode::Soften)
+    }
+
+    /// Blend one premultiplied source pixel onto the destination.
+    /// Soften needs the stroke's starting alpha for its floor, see `blend_soften`; here it erases freely.
+    /// Debug builds check that both pixels and the result are premultiplied.
+    #[inline]
+    pub fn apply(self, src: Color32, dst: Color32) -> Color32 {
+        debug_assert_premultiplied(src, "source");
+        debug_assert_premultiplied(dst, "destination");
+        let out = match self {
+            BlendMode::Normal => alpha_over(src, dst),
+            BlendMode::Eraser | BlendMode::Soften => blend_erase(src, dst),
+            BlendMode::Multiply => blend_multiply(src, dst),
+            BlendMode::Add => blend_add(src, dst),
+            BlendMode::Color => blend_color(src, dst),
+            BlendMode::Behind => blend_behind(src, dst),
+        };
+        debug_assert_premultiplied(out, "blended");
+        out
+    }
+}
+
+/// Threshold pattern that turns the soft falloff into fully opaque pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Dither {
+    Off,
+    /// Ordered 4x4 Bayer matrix, gives the classic cross-hatched ramps.
+    Bayer,
+    /// Interleaved gradient noise, a cheap stand-in for blue noise with no visible grid.
+    Noise,
+}
+
+impl Dither {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Dither::Off => "Off",
+            Dither::Bayer => "Bayer",
+            Dither::Noise => "Noise",
+        }
+    }
+
+    /// Threshold in (0, 1) for the canvas pixel at (x, y). Fixed per pixel so overlapping dabs agree.
+    #[inline]
+    pub fn threshold(&self, x: usize, y: usize) -> f32 {
+        const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+        match self {
+            Dither::Off => 0.0,
+            Dither::Bayer => (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0,
+            Dither::Noise => {
+                let v = 52.982_918 * (0.067_110_56 * x as f32 + 0.005_837_15 * y as f32).fract();
+                v.fract().clamp(0.001, 0.999)
+            }
+        }
+    }
+}
+
+/// Alpha multiplier for grain at the canvas pixel (x, y); 1 where the noise leaves paint untouched.
+#[inline]
+pub fn grain_factor(strength: f32, scale: f32, x: usize, y: usize) -> f32 {
+    if strength <= 0.0 {
+        return 1.0;
+    }
+    let scale = scale.max(0.5);
+    let n = fractal_noise((x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale, 3);
+    1.0 - strength.min(1.0) * n
 }
 
 #[derive(Clone, Debug)]
 pub struct BrushOptions {
     pub diameter: f32,
+    /// `diameter` is in screen pixels and converted by the zoom when a stroke starts.
+    pub screen_size: bool,
     pub hardness: f32, // 0..100
     pub softness_selector: SoftnessSelector,
     pub softness_curve: SoftnessCurve,
@@ -33,13 +145,36 @@ pub struct BrushOptions {
     pub flow: f32,    // 0..100
     pub opacity: f32, // 0..1
     pub blend_mode: BlendMode,
+    /// Soft brushes only: paint the falloff as a dither pattern of opaque pixels.
+    pub dither: Dither,
+    /// Pen pressure also scales flow, e.g. for erasers that remove more when pressed harder.
+    pub pressure_flow: bool,
+    /// Custom and image tips: rotate each dab to the direction the stroke is travelling.
+    pub follow_direction: bool,
+    /// Custom and image tips: turn each dab randomly by up to this many degrees either way.
+    pub random_rotation: f32,
+    /// Soft brushes only: how much canvas-space noise thins the paint, 0 for none.
+    pub grain: f32,
+    /// Size of a grain cell in canvas pixels.
+    pub grain_scale: f32,
 }
 
 impl BrushOptions {
+    /// Diameter in canvas pixels at the given zoom.
+    pub fn canvas_diameter(&self, zoom: f32) -> f32 {
+        if self.screen_size { (self.diameter / zoom).max(1.0) } else { self.diameter }
+    }
+
+    /// Whether the tip is a bitmap that can be rotated per dab.
+    pub fn has_tip_image(&self) -> bool {
+        matches!(self.pixel_shape, PixelBrushShape::Custom { .. } | PixelBrushShape::Image { .. })
+    }
+
     /// Create a standard soft brush with the given radius, hardness, base color and spacing.
     pub fn new(diameter: f32, hardness: f32, color: Color32, spacing: f32) -> Self {
         Self {
             diameter,
+            screen_size: false,
             hardness,
             softness_selector: SoftnessSelector::Gaussian,
             softness_curve: SoftnessCurve::default(),
@@ -49,6 +184,12 @@ impl BrushOptions {
             flow: 100.0,
             opacity: 1.0,
             blend_mode: BlendMode::Normal,
+            dither: Dither::Off,
+            pressure_flow: false,
+            follow_direction: false,
+            random_rotation: 0.0,
+            grain: 0.0,
+            grain_scale: 4.0,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,441 @@
+//! Precomputed soft dab footprints.
+//!
+//! The shape falloff only depends on the brush parameters and where the dab
+//! center falls inside its pixel, so it is evaluated once per combination and
+//! reused by every dab of a stroke.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use eframe::egui::Color32;
+
+use super::brush::{sample_custom_mask_nn, sample_image_nn, unrotate};
+use super::brush_options::{BrushOptions, PixelBrushShape};
+use super::hardness::{SoftnessCurve, SoftnessSelector};
+
+/// Sub-pixel positions per axis for small brushes; large dabs snap to whole pixels.
+const SUBPIXEL_STEPS: f32 = 4.0;
+const SUBPIXEL_MAX_DIAMETER: f32 = 64.0;
+/// Memory the masks of recently used brush parameters may hold together.
+const MASK_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Everything the footprint depends on besides the sub-pixel phase.
+#[derive(Clone, Debug, PartialEq)]
+struct MaskKey {
+    /// Rounded with [`quantize_diameter`].
+    diameter: f32,
+    hardness: f32,
+    softness_selector: SoftnessSelector,
+    softness_curve: SoftnessCurve,
+    pixel_shape: PixelBrushShape,
+    anti_aliasing: bool,
+    /// Tip rotation in radians, rounded to whole degrees.
+    angle: f32,
+}
+
+impl MaskKey {
+    fn new(options: &BrushOptions, anti_aliasing: bool, angle: f32) -> Self {
+        Self {
+            diameter: quantize_diameter(options.diameter),
+            hardness: options.hardness,
+            softness_selector: options.softness_selector,
+            softness_curve: options.softness_curve.clone(),
+            pixel_shape: options.pixel_shape.clone(),
+            anti_aliasing,
+            angle: quantize_angle(angle),
+        }
+    }
+
+    /// Whether this is the key [`Self::new`] would build, without cloning the options.
+    /// The cheap fields go first so most misses never compare the curve or the tip image.
+    fn matches(&self, options: &BrushOptions, anti_aliasing: bool, angle: f32) -> bool {
+        self.diameter == quantize_diameter(options.diameter)
+            && self.angle == quantize_angle(angle)
+            && self.anti_aliasing == anti_aliasing
+            && self.hardness == options.hardness
+            && self.softness_selector == options.softness_selector
+            && self.softness_curve == options.softness_curve
+            && self.pixel_shape == options.pixel_shape
+    }
+}
+
+/// Shape alpha of a dab over its square bounding box of `side` x `side` pixels.
+pub(crate) struct DabMask {
+    pub side: usize,
+    /// Offset from the floored (snapped) center to the first row/column.
+    pub radius: i32,
+    pub alpha: Vec<f32>,
+    /// Unmultiplied tip color per pixel for image tips; empty for masks painted in the brush color.
+    pub colors: Vec<Color32>,
+    /// First and last column with non-zero alpha in each row; `None` for empty rows.
+    pub spans: Vec<Option<(usize, usize)>>,
+}
+
+impl DabMask {
+    fn new(key: &MaskKey, phase_x: f32, phase_y: f32) -> Self {
+        let r = key.diameter / 2.0;
+        // A turned tip reaches into the corners of its bounding square
+        let reach = if key.angle != 0.0 { r * std::f32::consts::SQRT_2 } else { r };
+        let radius = reach.ceil() as i32;
+        let side = (2 * radius + 1) as usize;
+        let is_image = matches!(key.pixel_shape, PixelBrushShape::Image { .. });
+
+        let mut alpha = vec![0.0; side * side];
+        let mut colors = if is_image { vec![Color32::TRANSPARENT; side * side] } else { Vec::new() };
+        let mut spans = vec![None; side];
+        for (j, span) in spans.iter_mut().enumerate() {
+            for i in 0..side {
+                let (pdx, pdy) = unrotate(
+                    (i as i32 - radius) as f32 + 0.5 - phase_x,
+                    (j as i32 - radius) as f32 + 0.5 - phase_y,
+                    key.angle,
+                );
+                let a = dab_alpha(key, pdx, pdy, r);
+                if a > 0.0 {
+                    alpha[j * side + i] = a;
+                    *span = Some(span.map_or((i, i), |(first, _)| (first, i)));
+                    if is_image {
+                        colors[j * side + i] = dab_color(key, pdx, pdy, r);
+                    }
+                }
+            }
+        }
+
+        Self { side, radius, alpha, colors, spans }
+    }
+
+    /// Memory held by the mask's buffers.
+    fn bytes(&self) -> usize {
+        self.alpha.len() * size_of::<f32>()
+            + self.colors.len() * size_of::<Color32>()
+            + self.spans.len() * size_of::<Option<(usize, usize)>>()
+    }
+}
+
+/// Masks for one set of brush parameters, one per sub-pixel phase.
+#[derive(Clone)]
+struct CachedMasks {
+    key: MaskKey,
+    masks: HashMap<(u8, u8), Arc<DabMask>>,
+    /// Memory held by `masks`.
+    bytes: usize,
+}
+
+/// Masks for recently used brush parameters, least recently used first. Pressure-driven size
+/// and random rotation change the parameters from dab to dab, so several sets are kept, within
+/// [`MASK_CACHE_BYTES`].
+#[derive(Clone, Default)]
+pub(crate) struct DabMaskCache {
+    entries: Vec<CachedMasks>,
+}
+
+impl fmt::Debug for DabMaskCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DabMaskCache")
+            .field("keys", &self.entries.len())
+            .field("masks", &self.entries.iter().map(|e| e.masks.len()).sum::<usize>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DabMaskCache {
+    /// Snap a dab center to the cached sub-pixel grid for this brush size.
+    pub fn snap_center(options: &BrushOptions, x: f32, y: f32) -> (f32, f32) {
+        let steps = subpixel_steps(quantize_diameter(options.diameter));
+        ((x * steps).round() / steps, (y * steps).round() / steps)
+    }
+
+    /// Mask for a center already passed through `snap_center`, built on first use. Parameters
+    /// used least recently lose their masks once the cache outgrows its budget.
+    pub fn get(&mut self, options: &BrushOptions, anti_aliasing: bool, angle: f32, center_x: f32, center_y: f32) -> Arc<DabMask> {
+        match self.entries.iter().rposition(|e| e.key.matches(options, anti_aliasing, angle)) {
+            Some(i) => {
+                let entry = self.entries.remove(i);
+                self.entries.push(entry);
+            }
+            None => self.entries.push(CachedMasks {
+                key: MaskKey::new(options, anti_aliasing, angle),
+                masks: HashMap::new(),
+                bytes: 0,
+            }),
+        }
+        let entry = self.entries.last_mut().expect("the parameters' entry was just moved or pushed last");
+
+        let steps = subpixel_steps(entry.key.diameter);
+        let phase_x = center_x - center_x.floor();
+        let phase_y = center_y - center_y.floor();
+        let slot = ((phase_x * steps).round() as u8, (phase_y * steps).round() as u8);
+        if let Some(mask) = entry.masks.get(&slot) {
+            return mask.clone();
+        }
+        let mask = Arc::new(DabMask::new(&entry.key, phase_x, phase_y));
+        entry.bytes += mask.bytes();
+        entry.masks.insert(slot, mask.clone());
+        self.evict();
+        mask
+    }
+
+    /// Drop the least recently used parameters until the masks fit the budget, always keeping
+    /// the ones in use.
+    fn evict(&mut self) {
+        let mut total: usize = self.entries.iter().map(|e| e.bytes).sum();
+        while total > MASK_CACHE_BYTES && self.entries.len() > 1 {
+            total -= self.entries.remove(0).bytes;
+        }
+    }
+}
+
+/// Shape alpha along the row through the dab center, one value per pixel from edge to edge,
+/// for plotting the falloff a brush actually paints.
+pub(crate) fn cross_section(options: &BrushOptions, anti_aliasing: bool) -> Vec<f32> {
+    let key = MaskKey::new(options, anti_aliasing, 0.0);
+    let r = key.diameter / 2.0;
+    let radius = r.ceil() as i32;
+    (-radius..=radius).map(|i| dab_alpha(&key, i as f32, 0.0, r)).collect()
+}
+
+/// Diameter a mask is built for: eighths of a pixel for small brushes, else steps of 0.5%,
+/// finer than a pressure-driven size change can show and coarse enough for dabs to share masks.
+fn quantize_diameter(diameter: f32) -> f32 {
+    if diameter <= 16.0 {
+        (diameter * 8.0).round() / 8.0
+    } else {
+        ((diameter.ln() * 200.0).round() / 200.0).exp()
+    }
+}
+
+/// Whole degrees are plenty and let nearby stroke directions share a mask.
+fn quantize_angle(angle: f32) -> f32 {
+    angle.to_degrees().round().to_radians()
+}
+
+fn subpixel_steps(diameter: f32) -> f32 {
+    if diameter <= SUBPIXEL_MAX_DIAMETER { SUBPIXEL_STEPS } else { 1.0 }
+}
+
+/// Hardness falloff at normalized distance `t` from the center.
+#[inline]
+fn falloff(key: &MaskKey, t: f32) -> f32 {
+    match key.softness_selector {
+        SoftnessSelector::Gaussian => {
+            let hardness = (key.hardness / 100.0).clamp(0.0, 0.999);
+            if t < hardness {
+                1.0
+            } else {
+                let v = (t - hardness) / (1.0 - hardness);
+                let falloff = 1.0 - v.clamp(0.0, 1.0);
+                let f2 = falloff * falloff;
+                f2 * (3.0 - 2.0 * falloff)
+            }
+        }
+        SoftnessSelector::Curve => key.softness_curve.eval(t),
+    }
+}
+
+/// Shape alpha before the anti-aliasing edge fade.
+fn base_alpha(key: &MaskKey, dx: f32, dy: f32, radius: f32) -> f32 {
+    match &key.pixel_shape {
+        PixelBrushShape::Circle | PixelBrushShape::Square => {
+            let dist = match key.pixel_shape {
+                PixelBrushShape::Circle => (dx * dx + dy * dy).sqrt(),
+                _ => dx.abs().max(dy.abs()),
+            };
+            if dist >= radius { 0.0 } else { falloff(key, dist / radius) }
+        }
+        PixelBrushShape::Image { width, height, data } => {
+            sample_image_bilinear(dx, dy, radius, *width, *height, data).a() as f32 / 255.0
+        }
+        PixelBrushShape::Custom { width, height, data } => {
+            // Bilinear interpolation for smooth custom brushes
+            let nx = (dx + radius) / (radius * 2.0);
+            let ny = (dy + radius) / (radius * 2.0);
+            if !(0.0..1.0).contains(&nx) || !(0.0..1.0).contains(&ny) {
+                return 0.0;
+            }
+            let tx = nx * (*width as f32);
+            let ty = ny * (*height as f32);
+
+            let x0 = tx.floor() as usize;
+            let y0 = ty.floor() as usize;
+            let x1 = (x0 + 1).min(width - 1);
+            let y1 = (y0 + 1).min(height - 1);
+
+            let fx = tx - x0 as f32;
+            let fy = ty - y0 as f32;
+
+            let get_pixel = |x: usize, y: usize| -> f32 {
+                if x < *width && y < *height {
+                    data[y * width + x] as f32 / 255.0
+                } else {
+                    0.0
+                }
+            };
+
+            get_pixel(x0, y0) * (1.0 - fx) * (1.0 - fy)
+                + get_pixel(x1, y0) * fx * (1.0 - fy)
+                + get_pixel(x0, y1) * (1.0 - fx) * fy
+                + get_pixel(x1, y1) * fx * fy
+        }
+    }
+}
+
+/// Premultiplied image tip color at (dx, dy), bilinearly filtered; transparent outside the tip.
+fn sample_image_bilinear(dx: f32, dy: f32, radius: f32, width: usize, height: usize, data: &[Color32]) -> Color32 {
+    let nx = (dx + radius) / (radius * 2.0);
+    let ny = (dy + radius) / (radius * 2.0);
+    if !(0.0..1.0).contains(&nx) || !(0.0..1.0).contains(&ny) {
+        return Color32::TRANSPARENT;
+    }
+    let tx = nx * width as f32;
+    let ty = ny * height as f32;
+    let x0 = tx.floor() as usize;
+    let y0 = ty.floor() as usize;
+    let x1 = (x0 + 1).min(width - 1);
+    let y1 = (y0 + 1).min(height - 1);
+    let fx = tx - x0 as f32;
+    let fy = ty - y0 as f32;
+
+    let mut out = [0.0f32; 4];
+    for (x, y, w) in [
+        (x0, y0, (1.0 - fx) * (1.0 - fy)),
+        (x1, y0, fx * (1.0 - fy)),
+        (x0, y1, (1.0 - fx) * fy),
+        (x1, y1, fx * fy),
+    ] {
+        let px = data.get(y * width + x).copied().unwrap_or(Color32::TRANSPARENT);
+        for (o, c) in out.iter_mut().zip(px.to_array()) {
+            *o += c as f32 * w;
+        }
+    }
+    let [r, g, b, a] = out.map(|c| c.round().clamp(0.0, 255.0) as u8);
+    Color32::from_rgba_premultiplied(r, g, b, a)
+}
+
+/// Unmultiplied color an image tip paints at offset (pdx, pdy), stored opaque.
+fn dab_color(key: &MaskKey, pdx: f32, pdy: f32, r: f32) -> Color32 {
+    let PixelBrushShape::Image { width, height, data } = &key.pixel_shape else {
+        return Color32::TRANSPARENT;
+    };
+    let px = if key.anti_aliasing {
+        sample_image_bilinear(pdx, pdy, r, *width, *height, data)
+    } else {
+        sample_image_nn(pdx, pdy, key.diameter, *width, *height, data)
+    };
+    let [red, green, blue, _] = px.to_srgba_unmultiplied();
+    Color32::from_rgb(red, green, blue)
+}
+
+/// Final shape alpha of the dab at offset (pdx, pdy) from its center.
+fn dab_alpha(key: &MaskKey, pdx: f32, pdy: f32, r: f32) -> f32 {
+    if !key.anti_aliasing {
+        // Hard edges
+        return match &key.pixel_shape {
+            PixelBrushShape::Circle => if pdx * pdx + pdy * pdy <= r * r { 1.0 } else { 0.0 },
+            PixelBrushShape::Square => if pdx.abs() <= r && pdy.abs() <= r { 1.0 } else { 0.0 },
+            PixelBrushShape::Custom { width, height, data } => {
+                let (in_shape, alpha) = sample_custom_mask_nn(pdx, pdy, key.diameter, *width, *height, data);
+                if in_shape { alpha } else { 0.0 }
+            }
+            PixelBrushShape::Image { width, height, data } => {
+                sample_image_nn(pdx, pdy, key.diameter, *width, *height, data).a() as f32 / 255.0
+            }
+        };
+    }
+
+    let base = base_alpha(key, pdx, pdy, r);
+    if base <= 0.0 {
+        return 0.0;
+    }
+    // Fade the outermost pixel so the edge stays smooth
+    let dist = match key.pixel_shape {
+        PixelBrushShape::Circle => (pdx * pdx + pdy * pdy).sqrt(),
+        // Custom and image tips use the square metric for the edge fade
+        PixelBrushShape::Square | PixelBrushShape::Custom { .. } | PixelBrushShape::Image { .. } => pdx.abs().max(pdy.abs()),
+    };
+    let fade_start = (r - 1.0).max(0.0);
+    let fade_width = r - fade_start;
+    if dist >= r {
+        0.0
+    } else if dist > fade_start && fade_width > 0.0 {
+        base * (1.0 - (dist - fade_start) / fade_width)
+    } else {
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(diameter: f32) -> BrushOptions {
+        BrushOptions::new(diameter, 50.0, Color32::BLACK, 10.0)
+    }
+
+    #[test]
+    fn small_diameters_quantize_to_eighths() {
+        assert_eq!(quantize_diameter(3.06), 3.0);
+        assert_eq!(quantize_diameter(3.07), 3.125);
+        assert_eq!(quantize_diameter(16.0), 16.0);
+    }
+
+    #[test]
+    fn large_diameters_quantize_in_relative_steps() {
+        assert_eq!(quantize_diameter(200.0), quantize_diameter(200.2));
+        assert_ne!(quantize_diameter(200.0), quantize_diameter(204.0));
+        for d in (17..2000).step_by(7).map(|d| d as f32) {
+            assert!((quantize_diameter(d) - d).abs() / d < 0.0026, "{d} quantized to {}", quantize_diameter(d));
+        }
+    }
+
+    #[test]
+    fn keys_match_within_quantization() {
+        let key = MaskKey::new(&options(200.0), true, 0.3);
+        assert!(key.matches(&options(200.2), true, 0.301));
+        assert!(!key.matches(&options(204.0), true, 0.3));
+        assert!(!key.matches(&options(200.0), false, 0.3));
+        assert!(!key.matches(&options(200.0), true, 0.4));
+        let mut harder = options(200.0);
+        harder.hardness = 80.0;
+        assert!(!key.matches(&harder, true, 0.3));
+    }
+
+    #[test]
+    fn centers_snap_to_the_subpixel_grid() {
+        assert_eq!(DabMaskCache::snap_center(&options(10.0), 3.3, 7.9), (3.25, 8.0));
+        assert_eq!(DabMaskCache::snap_center(&options(100.0), 3.3, 7.9), (3.0, 8.0));
+    }
+
+    #[test]
+    fn reused_parameters_move_to_the_back() {
+        let mut cache = DabMaskCache::default();
+        let (a, b) = (options(10.0), options(20.0));
+        let first = cache.get(&a, true, 0.0, 5.0, 5.0);
+        cache.get(&b, true, 0.0, 5.0, 5.0);
+        let again = cache.get(&a, true, 0.0, 5.0, 5.0);
+        assert!(Arc::ptr_eq(&first, &again));
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.entries[0].key.matches(&b, true, 0.0));
+        assert!(cache.entries[1].key.matches(&a, true, 0.0));
+    }
+
+    #[test]
+    fn eviction_drops_the_least_recently_used() {
+        let mut cache = DabMaskCache::default();
+        for diameter in [10.0, 20.0, 30.0] {
+            cache.entries.push(CachedMasks {
+                key: MaskKey::new(&options(diameter), true, 0.0),
+                masks: HashMap::new(),
+                bytes: MASK_CACHE_BYTES / 2,
+            });
+        }
+        cache.evict();
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.entries[0].key.matches(&options(20.0), true, 0.0));
+
+        // The parameters in use stay even when they alone are over budget
+        cache.entries[1].bytes = MASK_CACHE_BYTES * 2;
+        cache.evict();
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.entries[0].key.matches(&options(30.0), true, 0.0));
+    }
+}
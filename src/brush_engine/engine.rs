@@ -0,0 +1,63 @@
+//! Brush engines turn the samples of a stroke into paint on the canvas.
+//!
+//! Every brush names the engine that paints it, so presets can use different backends. The
+//! painter only drives strokes through [`BrushEngine`]; the built-in dab engine lives in
+//! [`crate::brush_engine::stroke`].
+use crate::brush_engine::brush::Brush;
+use crate::brush_engine::stroke::DabEngine;
+use crate::canvas::canvas::Canvas;
+use crate::canvas::history::UndoAction;
+use crate::selection::SelectionManager;
+use crate::utils::vector::Vec2;
+use rayon::ThreadPool;
+use std::collections::HashSet;
+
+/// Where a stroke paints, and where it records what it changed.
+pub struct StrokeTarget<'a> {
+    pub pool: &'a ThreadPool,
+    pub canvas: &'a Canvas,
+    /// Paint is limited to the selection when there is one.
+    pub selection: Option<&'a SelectionManager>,
+    /// Receives a snapshot of every tile before it is first painted.
+    pub undo_action: &'a mut UndoAction,
+    /// Tiles painted so far, in tile coordinates.
+    pub modified_tiles: &'a mut HashSet<(usize, usize)>,
+}
+
+/// A backend that paints strokes. One engine instance lives for a single stroke.
+pub trait BrushEngine {
+    /// Reset per-stroke state before the first sample.
+    fn begin_stroke(&mut self);
+
+    /// Paint up to `pos`, a canvas coordinate, with the brush's current diameter and pressure.
+    fn add_sample(&mut self, target: &mut StrokeTarget, brush: &mut Brush, pos: Vec2);
+
+    /// Paint `dabs` more dabs where the stroke rests, for airbrushes that build up over time.
+    fn dwell(&mut self, target: &mut StrokeTarget, brush: &mut Brush, dabs: u32);
+
+    /// Paint anything still held back and close the stroke.
+    fn end_stroke(&mut self, target: &mut StrokeTarget, brush: &mut Brush);
+
+    /// Canvas point the painted line has reached so far, which trails the input while a
+    /// stabilizer smooths it. `None` before the first sample or for engines that don't say.
+    fn stroke_head(&self) -> Option<Vec2> {
+        None
+    }
+}
+
+/// Engines a brush can be painted with.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EngineKind {
+    /// Soft or pixel dabs stamped along the path.
+    #[default]
+    Dab,
+}
+
+impl EngineKind {
+    /// A fresh engine of this kind, ready for [`BrushEngine::begin_stroke`].
+    pub fn create(self) -> Box<dyn BrushEngine> {
+        match self {
+            EngineKind::Dab => Box::new(DabEngine::new()),
+        }
+    }
+}
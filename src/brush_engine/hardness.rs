@@ -1,5 +1,5 @@
 /// Option for how the brush softness falloff is calculated.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SoftnessSelector {
     Gaussian,
     Curve,
@@ -2,4 +2,8 @@
 pub mod brush;
 pub mod hardness;
 pub mod brush_options;
+pub(crate) mod dab_mask;
+pub mod engine;
+pub mod preset_meta;
+pub mod smoothing;
 pub mod stroke;
\ No newline at end of file
@@ -0,0 +1,45 @@
+//! Tags and favorites for brush presets, stored next to the brush tips.
+//!
+//! One line per preset: `name<TAB>favorite<TAB>tag,tag,...`. Presets are matched
+//! by name, so entries for presets that no longer exist are simply ignored.
+use super::brush::BrushPreset;
+use std::path::Path;
+
+pub const FILE_NAME: &str = "presets.meta";
+
+/// Apply saved tags and favorites to the matching presets.
+pub fn load(dir: &Path, presets: &mut [BrushPreset]) {
+    let Ok(contents) = std::fs::read_to_string(dir.join(FILE_NAME)) else {
+        return;
+    };
+    for line in contents.lines() {
+        let mut fields = line.split('\t');
+        let (Some(name), Some(favorite)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(preset) = presets.iter_mut().find(|p| p.name == name) else {
+            continue;
+        };
+        preset.favorite = favorite == "1";
+        preset.tags = fields
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+}
+
+pub fn save(dir: &Path, presets: &[BrushPreset]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    for preset in presets {
+        // Keep the line format intact whatever the user typed
+        let name = preset.name.replace(['\t', '\n', '\r'], " ");
+        let tags: Vec<String> = preset.tags.iter().map(|t| t.replace(['\t', '\n', '\r', ','], " ")).collect();
+        contents.push_str(&format!("{}\t{}\t{}\n", name, if preset.favorite { 1 } else { 0 }, tags.join(",")));
+    }
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(FILE_NAME), contents)
+}
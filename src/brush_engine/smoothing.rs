@@ -0,0 +1,108 @@
+//! Post-stroke correction: once a stroke is finished, a smooth curve is fitted through its raw
+//! samples and painted in place of the hand-drawn path.
+use crate::utils::vector::Vec2;
+
+/// Position, diameter and pressure of one stroke sample.
+pub type StrokeSample = (Vec2, f32, f32);
+
+/// A smoothed copy of `samples`, resampled about every canvas pixel. `strength` (0..1) sets how
+/// much wobble is evened out; the first and last samples stay where they were. Strokes too
+/// short to fit come back empty.
+pub fn smooth_stroke(samples: &[StrokeSample], strength: f32) -> Vec<StrokeSample> {
+    let (Some(&first), Some(&last)) = (samples.first(), samples.last()) else {
+        return Vec::new();
+    };
+    if samples.len() < 3 || strength <= 0.0 {
+        return Vec::new();
+    }
+
+    // Samples closer together than the gap are hand jitter, not shape
+    let gap = 1.0 + strength * 8.0;
+    let mut controls = vec![first];
+    for &sample in &samples[1..samples.len() - 1] {
+        if (sample.0 - controls[controls.len() - 1].0).length() >= gap {
+            controls.push(sample);
+        }
+    }
+    controls.push(last);
+
+    // Pull each inner point toward its neighbours' midpoint
+    let passes = (strength * 6.0).ceil() as usize;
+    for _ in 0..passes {
+        let previous = controls.clone();
+        for i in 1..controls.len() - 1 {
+            let mid = (previous[i - 1].0 + previous[i + 1].0) * 0.5;
+            controls[i].0 = previous[i].0 + (mid - previous[i].0) * 0.5;
+        }
+    }
+
+    // Catmull-Rom through the relaxed points, with the ends repeated as their own neighbours
+    let mut out = vec![controls[0]];
+    for i in 0..controls.len() - 1 {
+        let p0 = controls[i.saturating_sub(1)].0;
+        let (p1, d1, pr1) = controls[i];
+        let (p2, d2, pr2) = controls[i + 1];
+        let p3 = controls[(i + 2).min(controls.len() - 1)].0;
+        let steps = ((p2 - p1).length().ceil() as usize).max(1);
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            out.push((catmull_rom(p0, p1, p2, p3, t), d1 + (d2 - d1) * t, pr1 + (pr2 - pr1) * t));
+        }
+    }
+    out
+}
+
+/// Point at `t` on the uniform Catmull-Rom segment between `p1` and `p2`.
+fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zigzag(len: usize) -> Vec<StrokeSample> {
+        (0..len)
+            .map(|i| {
+                let wobble = if i % 2 == 0 { 2.0 } else { -2.0 };
+                (Vec2::new(i as f32 * 4.0, 50.0 + wobble), 10.0, 0.5)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn short_or_unsmoothed_strokes_come_back_empty() {
+        assert!(smooth_stroke(&[], 0.5).is_empty());
+        assert!(smooth_stroke(&zigzag(2), 0.5).is_empty());
+        assert!(smooth_stroke(&zigzag(10), 0.0).is_empty());
+    }
+
+    #[test]
+    fn ends_stay_in_place() {
+        let samples = zigzag(20);
+        let smoothed = smooth_stroke(&samples, 0.7);
+        assert_eq!(smoothed.first(), samples.first());
+        assert_eq!(smoothed.last().map(|s| s.0), samples.last().map(|s| s.0));
+    }
+
+    #[test]
+    fn resampled_about_every_pixel() {
+        let smoothed = smooth_stroke(&zigzag(20), 0.5);
+        for pair in smoothed.windows(2) {
+            assert!((pair[1].0 - pair[0].0).length() <= 1.5);
+        }
+    }
+
+    #[test]
+    fn wobble_is_evened_out() {
+        let samples = zigzag(40);
+        let smoothed = smooth_stroke(&samples, 1.0);
+        // Away from the pinned ends the path stays close to the line the zigzag follows
+        let inner = &smoothed[smoothed.len() / 4..smoothed.len() * 3 / 4];
+        let worst = inner.iter().map(|s| (s.0.y - 50.0).abs()).fold(0.0, f32::max);
+        assert!(worst < 1.0, "still {worst} px off the line");
+    }
+}
@@ -1,44 +1,74 @@
 use crate::brush_engine::brush::{Brush, StabilizerAlgorithm};
-use crate::canvas::canvas::Canvas;
-use crate::canvas::history::UndoAction;
-use crate::selection::SelectionManager;
+use crate::brush_engine::engine::{BrushEngine, StrokeTarget};
 use crate::utils::{profiler::ScopeTimer, vector::Vec2};
-use rayon::ThreadPool;
-use std::collections::HashSet;
 use rand::Rng;
 
+/// The built-in engine: stamps soft or pixel dabs along the stroke at the brush's spacing.
 /// Tracks per-stroke state like the last position and spacing accumulator.
-pub struct StrokeState {
+pub struct DabEngine {
     pub last_pos: Option<Vec2>,
     pub velocity: Vec2,
     dist_until_next_blit: f32,
     stroke_timer: Option<ScopeTimer>,
+    /// Last pixel painted in pixel-perfect mode.
+    last_pixel: Option<(i32, i32)>,
+    /// Pixel-perfect pixel held back until the next one shows whether it is an L corner.
+    pending_pixel: Option<(i32, i32)>,
+    /// Direction of travel in radians between the last two stroke points.
+    tangent: f32,
+    /// Diameter and pressure at `last_pos`, where the next segment starts from.
+    last_diameter: f32,
+    last_pressure: f32,
+    /// Input time of `last_pos`, when the device reported one.
+    last_time: Option<f64>,
+    /// Pressure change per second over the previous segment, so the next one eases out of it.
+    pressure_rate: f32,
 }
 
-impl StrokeState {
-    /// Create an empty stroke state and start the profiling timer.
+impl DabEngine {
+    /// Create an engine with no stroke in progress.
     pub fn new() -> Self {
         Self {
             last_pos: None,
             velocity: Vec2 { x: 0.0, y: 0.0 },
             dist_until_next_blit: 0.0,
-            stroke_timer: Some(ScopeTimer::new("stroke")),
+            stroke_timer: None,
+            last_pixel: None,
+            pending_pixel: None,
+            tangent: 0.0,
+            last_diameter: 0.0,
+            last_pressure: 1.0,
+            last_time: None,
+            pressure_rate: 0.0,
         }
     }
 
+    /// Tip rotation for the next dab: the stroke tangent when following it, plus any random turn.
+    fn dab_angle(&self, brush: &Brush) -> f32 {
+        let options = &brush.brush_options;
+        let mut angle = if options.follow_direction { self.tangent } else { 0.0 };
+        if options.random_rotation > 0.0 {
+            let spread = options.random_rotation.to_radians();
+            angle += rand::rng().random_range(-spread..=spread);
+        }
+        angle
+    }
+
+    /// Stamp one dab at `pos`, turned for the current direction of travel.
+    fn stamp(&self, target: &mut StrokeTarget, brush: &mut Brush, pos: Vec2) {
+        brush.dab_angle = self.dab_angle(brush);
+        brush.dab(target.pool, target.canvas, target.selection, pos, target.undo_action, target.modified_tiles);
+    }
+
+    /// Paint the dabs the brush queued for the segment just stamped.
+    fn flush(&self, target: &mut StrokeTarget, brush: &mut Brush) {
+        brush.flush_dabs(target.pool, target.canvas, target.selection, target.undo_action, target.modified_tiles);
+    }
+
     /// Add a new sample to the stroke, interpolating dabs based on spacing and jitter.
-    pub fn add_point(
-        &mut self,
-        pool: &ThreadPool,
-        canvas: &Canvas,
-        brush: &mut Brush,
-        selection: Option<&SelectionManager>,
-        raw_pos: Vec2,
-        undo_action: &mut UndoAction,
-        modified_tiles: &mut HashSet<(usize, usize)>,
-    ) {
+    fn add_point(&mut self, target: &mut StrokeTarget, brush: &mut Brush, raw_pos: Vec2) {
         if brush.pixel_perfect {
-            self.add_point_pixel_perfect(pool, canvas, brush, selection, raw_pos, undo_action, modified_tiles);
+            self.add_point_pixel_perfect(target, brush, raw_pos);
             return;
         }
 
@@ -83,39 +113,39 @@ impl StrokeState {
             }
         };
 
-        let spacing_dist = (brush.brush_options.spacing / 100.0) * brush.brush_options.diameter;
-        let spacing_dist = spacing_dist.max(0.5); // Avoid infinite loops
-
         if let Some(prev) = self.last_pos {
             let delta = pos - prev;
-            let mut dist_left = delta.length();
+            let length = delta.length();
+            let (end_diameter, end_pressure) = (brush.brush_options.diameter, brush.pressure);
+            let ease = self.pressure_ease(brush);
+            self.advance_pressure(brush);
 
-            if dist_left == 0.0 {
+            if length == 0.0 {
                 return;
             }
 
-            let unit_step = delta / dist_left;
+            let unit_step = delta / length;
+            let mut dist_left = length;
             let mut cur_pos = prev;
+            self.tangent = delta.y.atan2(delta.x);
 
             while dist_left >= self.dist_until_next_blit {
                 // Take a step to the next blit point.
                 cur_pos = cur_pos + unit_step * self.dist_until_next_blit;
                 dist_left -= self.dist_until_next_blit;
 
+                // Size and pressure for this point along the segment, not the sample's
+                let w = ease.at(1.0 - dist_left / length);
+                brush.brush_options.diameter = lerp(ease.from_diameter, end_diameter, w);
+                brush.pressure = lerp(ease.from_pressure, end_pressure, w);
+
                 // Blit.
-                let mut p = cur_pos;
-                if brush.jitter > 0.0 {
-                    let mut rng = rand::rng();
-                    let jitter_amount = (brush.jitter / 100.0) * brush.brush_options.diameter;
-                    let jx = rng.random_range(-jitter_amount..=jitter_amount);
-                    let jy = rng.random_range(-jitter_amount..=jitter_amount);
-                    p.x += jx;
-                    p.y += jy;
-                }
-                brush.dab(pool, canvas, selection, p, undo_action, modified_tiles);
+                self.stamp(target, brush, jittered(brush, cur_pos));
 
-                self.dist_until_next_blit = spacing_dist;
+                self.dist_until_next_blit = spacing_distance(brush);
             }
+            brush.brush_options.diameter = end_diameter;
+            brush.pressure = end_pressure;
 
             // Take the partial step to land at the sample.
             self.dist_until_next_blit -= dist_left;
@@ -129,27 +159,51 @@ impl StrokeState {
                 p.x += jx;
                 p.y += jy;
             }
-            brush.dab(pool, canvas, selection, p, undo_action, modified_tiles);
-            self.dist_until_next_blit = spacing_dist;
+            self.stamp(target, brush, p);
+            self.dist_until_next_blit = spacing_distance(brush);
+            self.advance_pressure(brush);
         }
 
         self.last_pos = Some(pos);
     }
 
+    /// How diameter and pressure move from the last sample to the brush's current one.
+    ///
+    /// Without input times this is a straight blend along the segment. With them, the blend
+    /// starts at the pressure's previous rate of change, so a pen easing off carries the same
+    /// taper through the segment instead of turning a corner at each sample.
+    fn pressure_ease(&self, brush: &Brush) -> PressureEase {
+        let mut start_slope = 1.0;
+        let dp = brush.pressure - self.last_pressure;
+        if let (Some(t0), Some(t1)) = (self.last_time, brush.sample_time) {
+            let dt = (t1 - t0) as f32;
+            if dt > 0.0 && dp.abs() > 1e-4 {
+                // Steeper starts would overshoot the sample, and negative ones double back
+                start_slope = (self.pressure_rate * dt / dp).clamp(0.0, 2.8);
+            }
+        }
+        PressureEase { from_diameter: self.last_diameter, from_pressure: self.last_pressure, start_slope }
+    }
+
+    /// Move the segment start up to the brush's current sample.
+    fn advance_pressure(&mut self, brush: &Brush) {
+        self.pressure_rate = match (self.last_time, brush.sample_time) {
+            (Some(t0), Some(t1)) if t1 > t0 => (brush.pressure - self.last_pressure) / (t1 - t0) as f32,
+            // Samples in the same instant keep the rate of the last real step
+            (Some(t0), Some(t1)) if t1 == t0 => self.pressure_rate,
+            _ => 0.0,
+        };
+        self.last_diameter = brush.brush_options.diameter;
+        self.last_pressure = brush.pressure;
+        self.last_time = brush.sample_time;
+    }
+
     /// Pixel-perfect Bresenham line stepping to avoid gaps when snapping to pixels.
-    fn add_point_pixel_perfect(
-        &mut self,
-        pool: &ThreadPool,
-        canvas: &Canvas,
-        brush: &mut Brush,
-        selection: Option<&SelectionManager>,
-        pos: Vec2,
-        undo_action: &mut UndoAction,
-        modified_tiles: &mut HashSet<(usize, usize)>,
-    ) {
+    fn add_point_pixel_perfect(&mut self, target: &mut StrokeTarget, brush: &mut Brush, pos: Vec2) {
         let x1 = pos.x.floor() as i32;
         let y1 = pos.y.floor() as i32;
 
+        let mut line = Vec::new();
         if let Some(prev) = self.last_pos {
             let x0 = prev.x.floor() as i32;
             let y0 = prev.y.floor() as i32;
@@ -157,6 +211,7 @@ impl StrokeState {
             if x0 == x1 && y0 == y1 {
                 return;
             }
+            self.tangent = ((y1 - y0) as f32).atan2((x1 - x0) as f32);
 
             let dx = (x1 - x0).abs();
             let dy = -(y1 - y0).abs();
@@ -168,17 +223,7 @@ impl StrokeState {
             let mut y = y0;
 
             loop {
-                brush.dab(
-                    pool,
-                    canvas,
-                    selection,
-                    Vec2 {
-                        x: x as f32 + 0.5,
-                        y: y as f32 + 0.5,
-                    },
-                    undo_action,
-                    modified_tiles,
-                );
+                line.push((x, y));
 
                 if x == x1 && y == y1 {
                     break;
@@ -194,26 +239,174 @@ impl StrokeState {
                 }
             }
         } else {
-            brush.dab(
-                pool,
-                canvas,
-                selection,
-                Vec2 {
-                    x: x1 as f32 + 0.5,
-                    y: y1 as f32 + 0.5,
-                },
-                undo_action,
-                modified_tiles,
-            );
+            line.push((x1, y1));
+        }
+
+        for pixel in line {
+            if let Some((x, y)) = self.push_pixel(pixel) {
+                self.stamp(target, brush, Vec2 { x: x as f32 + 0.5, y: y as f32 + 0.5 });
+            }
         }
         self.last_pos = Some(pos);
     }
 
-    /// Reset the stroke state and emit the profiling metric.
-    pub fn end(&mut self) {
+    /// Queue a pixel-perfect pixel and return the one that is now safe to paint, if any.
+    ///
+    /// The previous pixel is dropped instead when it forms an L corner between
+    /// its neighbours, so turns stay one pixel wide.
+    fn push_pixel(&mut self, pixel: (i32, i32)) -> Option<(i32, i32)> {
+        if self.pending_pixel == Some(pixel) || self.last_pixel == Some(pixel) {
+            return None;
+        }
+        let pending = self.pending_pixel.replace(pixel)?;
+        match self.last_pixel {
+            Some(last) if is_l_corner(last, pending, pixel) => None,
+            _ => {
+                self.last_pixel = Some(pending);
+                Some(pending)
+            }
+        }
+    }
+
+    /// Paint the pixel still held back by pixel-perfect mode.
+    fn flush_pixel_perfect(&mut self, target: &mut StrokeTarget, brush: &mut Brush) {
+        let Some((x, y)) = self.pending_pixel.take() else {
+            return;
+        };
+        self.stamp(target, brush, Vec2 { x: x as f32 + 0.5, y: y as f32 + 0.5 });
+        self.last_pixel = Some((x, y));
+    }
+}
+
+impl Default for DabEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BrushEngine for DabEngine {
+    /// Reset the stroke state and start the profiling timer.
+    fn begin_stroke(&mut self) {
+        *self = Self::new();
+        self.stroke_timer = Some(ScopeTimer::detached("stroke"));
+    }
+
+    fn add_sample(&mut self, target: &mut StrokeTarget, brush: &mut Brush, pos: Vec2) {
+        self.add_point(target, brush, pos);
+        self.flush(target, brush);
+    }
+
+    /// Stamp at the last stroke point. Pixel-perfect strokes don't build up, so they skip it.
+    fn dwell(&mut self, target: &mut StrokeTarget, brush: &mut Brush, dabs: u32) {
+        let Some(pos) = self.last_pos else {
+            return;
+        };
+        if brush.pixel_perfect {
+            return;
+        }
+        for _ in 0..dabs {
+            self.stamp(target, brush, jittered(brush, pos));
+        }
+        self.flush(target, brush);
+    }
+
+    /// Paint the held-back pixel-perfect pixel, reset the stroke state and emit the profiling metric.
+    fn end_stroke(&mut self, target: &mut StrokeTarget, brush: &mut Brush) {
+        self.flush_pixel_perfect(target, brush);
+        self.flush(target, brush);
         self.last_pos = None;
+        self.last_pixel = None;
+        self.tangent = 0.0;
         self.dist_until_next_blit = 0.0;
         // Drop the timer so stroke-level duration is reported when the stroke ends.
         self.stroke_timer.take();
     }
+
+    fn stroke_head(&self) -> Option<Vec2> {
+        self.last_pos
+    }
+}
+
+/// Blend of diameter and pressure across one segment between samples.
+struct PressureEase {
+    from_diameter: f32,
+    from_pressure: f32,
+    /// Rate of the blend at the start of the segment; 1 is a straight blend.
+    start_slope: f32,
+}
+
+impl PressureEase {
+    /// Blend weight at fraction `s` of the segment: a cubic Hermite from 0 to 1 that leaves
+    /// the start at `start_slope` and arrives at the sample with slope 1.
+    fn at(&self, s: f32) -> f32 {
+        let (s2, s3) = (s * s, s * s * s);
+        let h10 = s3 - 2.0 * s2 + s;
+        let h01 = -2.0 * s3 + 3.0 * s2;
+        let h11 = s3 - s2;
+        (h10 * self.start_slope + h01 + h11).clamp(0.0, 1.0)
+    }
+}
+
+/// `pos` moved by a random offset of up to the brush's jitter, a percentage of its size.
+fn jittered(brush: &Brush, mut pos: Vec2) -> Vec2 {
+    if brush.jitter > 0.0 {
+        let mut rng = rand::rng();
+        let jitter_amount = (brush.jitter / 100.0) * brush.brush_options.diameter;
+        pos.x += rng.random_range(-jitter_amount..=jitter_amount);
+        pos.y += rng.random_range(-jitter_amount..=jitter_amount);
+    }
+    pos
+}
+
+/// Distance to the next dab at the brush's current size.
+fn spacing_distance(brush: &Brush) -> f32 {
+    // Avoid infinite loops
+    ((brush.brush_options.spacing / 100.0) * brush.brush_options.diameter).max(0.5)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// True when `b` is the corner pixel of an L between diagonal neighbours `a` and `c`.
+fn is_l_corner(a: (i32, i32), b: (i32, i32), c: (i32, i32)) -> bool {
+    (a.0 - c.0).abs() == 1
+        && (a.1 - c.1).abs() == 1
+        && ((b.0 == a.0 && b.1 == c.1) || (b.0 == c.0 && b.1 == a.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ease(start_slope: f32) -> PressureEase {
+        PressureEase { from_diameter: 10.0, from_pressure: 0.5, start_slope }
+    }
+
+    #[test]
+    fn ease_runs_from_the_last_sample_to_the_next() {
+        for slope in [0.0, 1.0, 2.8] {
+            assert_eq!(ease(slope).at(0.0), 0.0);
+            assert!((ease(slope).at(1.0) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn unit_slope_is_a_straight_blend() {
+        for i in 0..=10 {
+            let s = i as f32 / 10.0;
+            assert!((ease(1.0).at(s) - s).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn start_slope_carries_the_previous_rate() {
+        // A pressure that was barely changing starts slowly, a fast one starts ahead
+        assert!(ease(0.0).at(0.2) < 0.2);
+        assert!(ease(2.8).at(0.2) > 0.2);
+        for slope in [0.0, 1.0, 2.8] {
+            let weights: Vec<f32> = (0..=20).map(|i| ease(slope).at(i as f32 / 20.0)).collect();
+            assert!(weights.windows(2).all(|w| w[1] >= w[0]), "slope {slope} doubles back");
+        }
+    }
 }
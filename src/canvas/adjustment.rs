@@ -0,0 +1,242 @@
+use eframe::egui::Rgba;
+use eframe::egui::ecolor::{gamma_from_linear, linear_from_gamma};
+
+/// Position (0..1 luminance) and sRGB color of one gradient map stop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradientStop {
+    pub pos: f32,
+    pub color: [u8; 3],
+}
+
+/// Multi-stop gradient that luminance is mapped onto, with a 256-entry lookup table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GradientMap {
+    stops: Vec<GradientStop>,
+    lut: Box<[[f32; 3]; 256]>,
+}
+
+impl GradientMap {
+    pub fn new(stops: Vec<GradientStop>) -> Self {
+        let mut map = Self { stops, lut: Box::new([[0.0; 3]; 256]) };
+        map.rebuild_lut();
+        map
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    /// Edit the stops in place; the lookup table is rebuilt afterwards.
+    pub fn edit_stops<R>(&mut self, f: impl FnOnce(&mut Vec<GradientStop>) -> R) -> R {
+        let result = f(&mut self.stops);
+        self.rebuild_lut();
+        result
+    }
+
+    /// sRGB color (channels in 0..1) the gradient assigns to a luminance value.
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        self.lut[(t.clamp(0.0, 1.0) * 255.0).round() as usize]
+    }
+
+    fn rebuild_lut(&mut self) {
+        let mut sorted = self.stops.clone();
+        sorted.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+        let to_f = |c: [u8; 3]| c.map(|v| v as f32 / 255.0);
+
+        for (i, entry) in self.lut.iter_mut().enumerate() {
+            let t = i as f32 / 255.0;
+            *entry = match sorted.iter().position(|s| s.pos >= t) {
+                None => sorted.last().map_or([t; 3], |s| to_f(s.color)),
+                Some(0) => to_f(sorted[0].color),
+                Some(j) => {
+                    let (a, b) = (sorted[j - 1], sorted[j]);
+                    let span = (b.pos - a.pos).max(1e-6);
+                    let f = (t - a.pos) / span;
+                    let (ca, cb) = (to_f(a.color), to_f(b.color));
+                    [0, 1, 2].map(|k| ca[k] + (cb[k] - ca[k]) * f)
+                }
+            };
+        }
+    }
+}
+
+impl Default for GradientMap {
+    fn default() -> Self {
+        Self::new(vec![
+            GradientStop { pos: 0.0, color: [20, 16, 48] },
+            GradientStop { pos: 0.5, color: [196, 72, 64] },
+            GradientStop { pos: 1.0, color: [255, 236, 180] },
+        ])
+    }
+}
+
+/// Filter applied by an adjustment layer to everything composited below it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdjustmentKind {
+    BrightnessContrast {
+        brightness: f32, // -1..1
+        contrast: f32,   // -1..1
+    },
+    HueSaturation {
+        hue: f32,        // degrees, -180..180
+        saturation: f32, // -1..1
+        lightness: f32,  // -1..1
+    },
+    Levels {
+        in_black: f32,  // 0..1
+        in_white: f32,  // 0..1
+        gamma: f32,     // 0.1..10
+        out_black: f32, // 0..1
+        out_white: f32, // 0..1
+    },
+    GradientMap(GradientMap),
+}
+
+impl AdjustmentKind {
+    pub fn default_brightness_contrast() -> Self {
+        AdjustmentKind::BrightnessContrast { brightness: 0.0, contrast: 0.0 }
+    }
+
+    pub fn default_hue_saturation() -> Self {
+        AdjustmentKind::HueSaturation { hue: 0.0, saturation: 0.0, lightness: 0.0 }
+    }
+
+    pub fn default_levels() -> Self {
+        AdjustmentKind::Levels {
+            in_black: 0.0,
+            in_white: 1.0,
+            gamma: 1.0,
+            out_black: 0.0,
+            out_white: 1.0,
+        }
+    }
+
+    pub fn default_gradient_map() -> Self {
+        AdjustmentKind::GradientMap(GradientMap::default())
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AdjustmentKind::BrightnessContrast { .. } => "Brightness/Contrast",
+            AdjustmentKind::HueSaturation { .. } => "Hue/Saturation",
+            AdjustmentKind::Levels { .. } => "Levels",
+            AdjustmentKind::GradientMap(_) => "Gradient Map",
+        }
+    }
+
+    /// Adjust a single unmultiplied sRGB color (channels in 0..1).
+    fn apply_srgb(&self, rgb: [f32; 3]) -> [f32; 3] {
+        match *self {
+            AdjustmentKind::GradientMap(ref map) => {
+                let [r, g, b] = rgb;
+                map.sample(0.2126 * r + 0.7152 * g + 0.0722 * b)
+            }
+            AdjustmentKind::BrightnessContrast { brightness, contrast } => {
+                // Map contrast -1..1 onto a 0..inf slope, keeping 0 as identity.
+                let c = contrast.clamp(-0.99, 0.99);
+                let slope = (1.0 + c) / (1.0 - c);
+                rgb.map(|v| ((v - 0.5) * slope + 0.5 + brightness).clamp(0.0, 1.0))
+            }
+            AdjustmentKind::HueSaturation { hue, saturation, lightness } => {
+                let (h, s, v) = rgb_to_hsv(rgb);
+                let h = (h + hue / 360.0).rem_euclid(1.0);
+                let s = (s * (1.0 + saturation)).clamp(0.0, 1.0);
+                let [r, g, b] = hsv_to_rgb(h, s, v);
+                let lift = |c: f32| {
+                    if lightness >= 0.0 {
+                        c + (1.0 - c) * lightness
+                    } else {
+                        c * (1.0 + lightness)
+                    }
+                };
+                [lift(r), lift(g), lift(b)]
+            }
+            AdjustmentKind::Levels { in_black, in_white, gamma, out_black, out_white } => {
+                let range = (in_white - in_black).max(1e-4);
+                let inv_gamma = 1.0 / gamma.max(0.01);
+                rgb.map(|v| {
+                    let t = ((v - in_black) / range).clamp(0.0, 1.0).powf(inv_gamma);
+                    out_black + t * (out_white - out_black)
+                })
+            }
+        }
+    }
+}
+
+/// Adjustment layer payload: the filter plus whether the layer pixels act as a mask.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adjustment {
+    pub kind: AdjustmentKind,
+    /// When true, the alpha painted on the layer restricts where the filter applies.
+    pub use_mask: bool,
+}
+
+impl Adjustment {
+    pub fn new(kind: AdjustmentKind) -> Self {
+        Self { kind, use_mask: false }
+    }
+
+    /// Apply the filter to a premultiplied linear composite, blended in by `amount` (0..1).
+    #[inline]
+    pub fn apply(&self, composite: Rgba, amount: f32) -> Rgba {
+        let a = composite.a();
+        if a <= 0.0 || amount <= 0.0 {
+            return composite;
+        }
+
+        let inv_a = 1.0 / a;
+        let srgb = [
+            gamma_from_linear((composite.r() * inv_a).clamp(0.0, 1.0)),
+            gamma_from_linear((composite.g() * inv_a).clamp(0.0, 1.0)),
+            gamma_from_linear((composite.b() * inv_a).clamp(0.0, 1.0)),
+        ];
+        let [r, g, b] = self.kind.apply_srgb(srgb);
+        let adjusted = Rgba::from_rgba_premultiplied(
+            linear_from_gamma(r.clamp(0.0, 1.0)) * a,
+            linear_from_gamma(g.clamp(0.0, 1.0)) * a,
+            linear_from_gamma(b.clamp(0.0, 1.0)) * a,
+            a,
+        );
+
+        if amount >= 1.0 {
+            adjusted
+        } else {
+            composite * (1.0 - amount) + adjusted * amount
+        }
+    }
+}
+
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let h = if delta <= f32::EPSILON {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / delta + 2.0) / 6.0
+    } else {
+        ((r - g) / delta + 4.0) / 6.0
+    };
+    let s = if max <= f32::EPSILON { 0.0 } else { delta / max };
+    (h, s, max)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [f32; 3] {
+    let i = (h * 6.0).floor();
+    let f = h * 6.0 - i;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+
+    match (i as i32).rem_euclid(6) {
+        0 => [v, t, p],
+        1 => [q, v, p],
+        2 => [p, v, t],
+        3 => [p, q, v],
+        4 => [t, p, v],
+        _ => [v, p, q],
+    }
+}
@@ -0,0 +1,322 @@
+//! Flipbook animation: the document is a sequence of frames, each with its own layer stack.
+//!
+//! Only the frame being edited lives in the canvas. The others are kept as
+//! snapshots together with their undo stacks and swapped in when selected.
+use std::collections::HashMap;
+use std::sync::{Arc, mpsc};
+
+use eframe::egui::{Color32, ColorImage};
+
+use crate::canvas::canvas::{Canvas, alpha_over, shift_tiles};
+use crate::canvas::history::{CanvasSnapshot, History, LayerSnapshot};
+use crate::canvas::vector::VectorLayer;
+
+const PREV_TINT: Color32 = Color32::from_rgb(230, 60, 60);
+const NEXT_TINT: Color32 = Color32::from_rgb(50, 140, 240);
+
+struct Frame {
+    /// `None` for the frame being edited, whose layers live in the canvas. Shared with
+    /// background jobs flattening the frame.
+    layers: Option<Arc<CanvasSnapshot>>,
+    histories: Vec<History>,
+}
+
+/// Faded copies of the neighbouring frames shown while drawing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OnionSkin {
+    pub enabled: bool,
+    pub before: usize,
+    pub after: usize,
+    /// Opacity of the nearest frame; farther ones fade out linearly.
+    pub opacity: f32,
+}
+
+/// Coverage of one neighbouring frame, drawn as a tinted silhouette.
+pub struct OnionLayer {
+    /// Flattened alpha of the frame's paint layers per tile (the paper is left out).
+    tiles: HashMap<(i32, i32), Vec<u8>>,
+    tint: Color32,
+    opacity: f32,
+}
+
+impl OnionLayer {
+    /// Blend this layer over a rendered canvas tile, sampled every `step` pixels.
+    pub fn overlay(&self, tx: i32, ty: i32, tile_size: usize, step: usize, img: &mut ColorImage) {
+        let Some(alpha) = self.tiles.get(&(tx, ty)) else {
+            return;
+        };
+        let [w, h] = img.size;
+        for y in 0..h {
+            for x in 0..w {
+                let a = alpha[y * step * tile_size + x * step] as f32 / 255.0 * self.opacity;
+                if a <= 0.0 {
+                    continue;
+                }
+                let src = Color32::from_rgba_unmultiplied(self.tint.r(), self.tint.g(), self.tint.b(), (a * 255.0).round() as u8);
+                let px = &mut img.pixels[y * w + x];
+                *px = alpha_over(src, *px);
+            }
+        }
+    }
+}
+
+pub struct Animation {
+    frames: Vec<Frame>,
+    current: usize,
+    pub fps: f32,
+    pub onion: OnionSkin,
+    onion_layers: Vec<OnionLayer>,
+    /// Whether `onion_layers` no longer match the frames and settings.
+    onion_stale: bool,
+    /// Onion layers being flattened on the background pool.
+    onion_job: Option<mpsc::Receiver<Vec<OnionLayer>>>,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Animation {
+    /// A single-frame document, which is what every canvas starts as.
+    pub fn new() -> Self {
+        Self {
+            frames: vec![Frame { layers: None, histories: Vec::new() }],
+            current: 0,
+            fps: 12.0,
+            onion: OnionSkin {
+                enabled: true,
+                before: 1,
+                after: 1,
+                opacity: 0.3,
+            },
+            onion_layers: Vec::new(),
+            onion_stale: false,
+            onion_job: None,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Store the canvas and `histories` as the current frame and load frame `idx`.
+    /// Returns the undo stacks of the loaded frame, or `None` if nothing changed.
+    pub fn switch_to(&mut self, canvas: &mut Canvas, idx: usize, histories: Vec<History>) -> Option<Vec<History>> {
+        if idx == self.current || idx >= self.frames.len() {
+            return None;
+        }
+        self.frames[self.current] = Frame {
+            layers: Some(Arc::new(canvas.snapshot(String::new()))),
+            histories,
+        };
+        let target = std::mem::replace(&mut self.frames[idx], Frame { layers: None, histories: Vec::new() });
+        if let Some(layers) = &target.layers {
+            canvas.restore_layers(layers);
+        }
+        self.current = idx;
+        self.onion_layers.clear();
+        self.refresh_onion();
+        Some(target.histories)
+    }
+
+    /// Insert a frame after the current one and switch to it. A blank frame keeps
+    /// the layer structure (names, visibility, opacity) without any pixels.
+    pub fn insert_frame(&mut self, canvas: &mut Canvas, duplicate: bool, histories: Vec<History>) -> Vec<History> {
+        let layers = if duplicate {
+            canvas.snapshot(String::new())
+        } else {
+            blank_frame(canvas)
+        };
+        self.frames.insert(self.current + 1, Frame { layers: Some(Arc::new(layers)), histories: Vec::new() });
+        self.switch_to(canvas, self.current + 1, histories).unwrap_or_default()
+    }
+
+    /// Drop the current frame and load its neighbour. Returns `None` for the last remaining frame.
+    pub fn delete_frame(&mut self, canvas: &mut Canvas) -> Option<Vec<History>> {
+        if self.frames.len() <= 1 {
+            return None;
+        }
+        self.frames.remove(self.current);
+        self.current = self.current.min(self.frames.len() - 1);
+        let target = std::mem::replace(&mut self.frames[self.current], Frame { layers: None, histories: Vec::new() });
+        if let Some(layers) = &target.layers {
+            canvas.restore_layers(layers);
+        }
+        self.onion_layers.clear();
+        self.refresh_onion();
+        Some(target.histories)
+    }
+
+    /// Everything needed to flatten the frames on a background job: the frame being
+    /// edited shares `canvas`'s tiles, the others their stored snapshots.
+    pub fn frame_sources(&self, canvas: &Canvas) -> FrameSources {
+        FrameSources {
+            canvas: canvas.shared_view(),
+            frames: self.frames.iter().map(|f| f.layers.clone()).collect(),
+        }
+    }
+
+    /// Onion skin layers for the current frame; empty when onion skinning is off.
+    pub fn onion_layers(&self) -> &[OnionLayer] {
+        &self.onion_layers
+    }
+
+    /// Rebuild the onion skins after switching frames or changing their settings. The
+    /// flattening runs on the background pool, see [`Animation::poll_onion`].
+    pub fn refresh_onion(&mut self) {
+        if !self.onion.enabled {
+            self.onion_layers.clear();
+            self.onion_job = None;
+        }
+        self.onion_stale = self.onion.enabled;
+    }
+
+    /// Whether onion skins are being flattened, so the caller keeps polling.
+    pub fn onion_pending(&self) -> bool {
+        self.onion_stale || self.onion_job.is_some()
+    }
+
+    /// Take in the onion skins of a finished job and start one if they went stale.
+    /// Returns whether new onion skins arrived, and the canvas needs redrawing.
+    pub fn poll_onion(&mut self, pool: &rayon::ThreadPool) -> bool {
+        let mut arrived = false;
+        if let Some(job) = &self.onion_job {
+            match job.try_recv() {
+                Ok(layers) => {
+                    self.onion_job = None;
+                    // Frames or settings that changed meanwhile need another pass
+                    if !self.onion_stale {
+                        self.onion_layers = layers;
+                        arrived = true;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => self.onion_job = None,
+            }
+        }
+        if self.onion_stale {
+            self.onion_stale = false;
+            self.onion_job = Some(self.spawn_onion(pool));
+        }
+        arrived
+    }
+
+    /// Flatten the neighbouring frames' coverage on `pool`.
+    fn spawn_onion(&self, pool: &rayon::ThreadPool) -> mpsc::Receiver<Vec<OnionLayer>> {
+        let before = (1..=self.onion.before).filter_map(|d| {
+            let idx = self.current.checked_sub(d)?;
+            Some((idx, d, self.onion.before, PREV_TINT))
+        });
+        let after = (1..=self.onion.after).map(|d| (self.current + d, d, self.onion.after, NEXT_TINT));
+        let sources: Vec<(Arc<CanvasSnapshot>, Color32, f32)> = before
+            .chain(after)
+            .filter_map(|(idx, distance, range, tint)| {
+                let layers = self.frames.get(idx)?.layers.clone()?;
+                let fade = 1.0 - (distance - 1) as f32 / range as f32;
+                Some((layers, tint, self.onion.opacity * fade))
+            })
+            .collect();
+        let (done, receiver) = mpsc::channel();
+        pool.spawn(move || {
+            let layers = sources
+                .into_iter()
+                .map(|(layers, tint, opacity)| OnionLayer { tiles: flatten_alpha(&layers.layers), tint, opacity })
+                .collect();
+            let _ = done.send(layers);
+        });
+        receiver
+    }
+}
+
+/// The frames of an animation as a background job reads them, see [`Animation::frame_sources`].
+pub struct FrameSources {
+    canvas: Canvas,
+    frames: Vec<Option<Arc<CanvasSnapshot>>>,
+}
+
+impl FrameSources {
+    /// Flatten every frame in order, downsampled by `step`.
+    pub fn render_all(&self, step: usize) -> Vec<ColorImage> {
+        (0..self.frames.len()).map(|idx| self.render(idx, step)).collect()
+    }
+
+    /// Flatten one frame, downsampled by `step`.
+    pub fn render(&self, idx: usize, step: usize) -> ColorImage {
+        let canvas = &self.canvas;
+        let (w, h) = (canvas.width(), canvas.height());
+        let mut img = ColorImage::new([w, h], Color32::TRANSPARENT);
+        match self.frames.get(idx).and_then(|f| f.as_ref()) {
+            Some(layers) => {
+                let mut scratch = Canvas::new(w, h, Color32::TRANSPARENT, canvas.tile_size());
+                scratch.replace_clear_color(canvas.clear_color());
+                scratch.restore_layers(layers);
+                scratch.write_region_to_color_image(0, 0, w, h, &mut img, step);
+            }
+            None => canvas.write_region_to_color_image(0, 0, w, h, &mut img, step),
+        }
+        img
+    }
+}
+
+/// The canvas's layer stack with every tile and vector stroke dropped.
+fn blank_frame(canvas: &Canvas) -> CanvasSnapshot {
+    CanvasSnapshot {
+        name: String::new(),
+        width: canvas.width(),
+        height: canvas.height(),
+        clear_color: canvas.clear_color(),
+        layers: canvas
+            .layers
+            .iter()
+            .map(|layer| LayerSnapshot {
+                name: layer.name.clone(),
+                visible: layer.visible,
+                opacity: layer.opacity,
+                locked: layer.locked,
+                paint_behind: layer.paint_behind,
+                adjustment: layer.adjustment.clone(),
+                // Vector layers stay vector layers, but start without strokes like the tiles
+                vector: layer.vector.as_ref().map(|_| VectorLayer::default()),
+                label: layer.label,
+                offset: layer.offset,
+                tiles: HashMap::new(),
+            })
+            .collect(),
+        active_layer_idx: canvas.active_layer_idx,
+    }
+}
+
+/// Combined alpha of the visible paint layers above the background, per tile.
+fn flatten_alpha(layers: &[LayerSnapshot]) -> HashMap<(i32, i32), Vec<u8>> {
+    let mut out: HashMap<(i32, i32), Vec<f32>> = HashMap::new();
+    for layer in layers.iter().skip(1) {
+        if !layer.visible || layer.adjustment.is_some() || layer.opacity <= 0.0 {
+            continue;
+        }
+        // Tiles are square, so any one of them gives the tile size
+        let shifted = match layer.tiles.values().next() {
+            Some(data) if layer.offset != (0, 0) => {
+                let tile_size = (data.len() as f64).sqrt() as usize;
+                Some(shift_tiles(&layer.tiles, layer.offset.0, layer.offset.1, tile_size, Color32::TRANSPARENT))
+            }
+            _ => None,
+        };
+        for (&coord, data) in shifted.as_ref().unwrap_or(&layer.tiles) {
+            let acc = out.entry(coord).or_insert_with(|| vec![0.0; data.len()]);
+            for (a, px) in acc.iter_mut().zip(data) {
+                let src = px.a() as f32 / 255.0 * layer.opacity;
+                *a = src + *a * (1.0 - src);
+            }
+        }
+    }
+    out.into_iter()
+        .filter(|(_, acc)| acc.iter().any(|&a| a > 0.0))
+        .map(|(coord, acc)| (coord, acc.iter().map(|a| (a * 255.0).round() as u8).collect()))
+        .collect()
+}
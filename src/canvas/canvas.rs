@@ -1,15 +1,26 @@
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use eframe::egui::{Color32, ColorImage, Rgba};
+use eframe::egui::{Color32, ColorImage, Rgba, ecolor};
+use rayon::prelude::*;
 use wide::f32x4;
 
-use crate::utils::color::{Color, ColorManipulation};
 use crate::utils::profiler::ScopeTimer;
 use crate::utils::vector::Vec2;
-use crate::canvas::history::UndoAction;
+use crate::canvas::adjustment::Adjustment;
+use crate::canvas::composite_cache::{CompositeCache, CompositeTile, LayerKey, StackKey};
+use crate::canvas::dirty::DirtyRegion;
+use crate::canvas::palette::Palette;
+use crate::canvas::tile_pager;
+use crate::canvas::tile_store::{TileRef, TileStore};
+use crate::canvas::vector::VectorLayer;
+use crate::canvas::history::{CanvasSnapshot, LayerSnapshot, TileSnapshot, UndoAction};
 use crate::selection::SelectionManager;
+use crate::selection::transform::{Affine, Interpolation};
 
 // Gamma correction lookup table (4096 entries for high precision)
 static GAMMA_LUT: OnceLock<[u8; 4096]> = OnceLock::new();
@@ -49,6 +60,60 @@ fn rgba_to_color32_fast(rgba: Rgba) -> Color32 {
     )
 }
 
+/// Color tag a layer can carry to group it with related layers in the layers panel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LayerLabel {
+    #[default]
+    None,
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Violet,
+    Gray,
+}
+
+impl LayerLabel {
+    pub const ALL: [LayerLabel; 8] = [
+        LayerLabel::None,
+        LayerLabel::Red,
+        LayerLabel::Orange,
+        LayerLabel::Yellow,
+        LayerLabel::Green,
+        LayerLabel::Blue,
+        LayerLabel::Violet,
+        LayerLabel::Gray,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LayerLabel::None => "None",
+            LayerLabel::Red => "Red",
+            LayerLabel::Orange => "Orange",
+            LayerLabel::Yellow => "Yellow",
+            LayerLabel::Green => "Green",
+            LayerLabel::Blue => "Blue",
+            LayerLabel::Violet => "Violet",
+            LayerLabel::Gray => "Gray",
+        }
+    }
+
+    /// Swatch shown for the label, or `None` for unlabelled layers.
+    pub fn color(self) -> Option<Color32> {
+        match self {
+            LayerLabel::None => None,
+            LayerLabel::Red => Some(Color32::from_rgb(220, 70, 70)),
+            LayerLabel::Orange => Some(Color32::from_rgb(235, 140, 50)),
+            LayerLabel::Yellow => Some(Color32::from_rgb(230, 200, 60)),
+            LayerLabel::Green => Some(Color32::from_rgb(90, 180, 90)),
+            LayerLabel::Blue => Some(Color32::from_rgb(70, 130, 220)),
+            LayerLabel::Violet => Some(Color32::from_rgb(150, 100, 210)),
+            LayerLabel::Gray => Some(Color32::from_gray(140)),
+        }
+    }
+}
+
 #[derive(Debug)]
 /// Single painting layer with its own opacity, visibility and tile storage.
 pub struct Layer {
@@ -56,22 +121,76 @@ pub struct Layer {
     pub visible: bool,
     pub opacity: f32, // 0..1
     pub locked: bool,
-    tiles: Mutex<HashMap<(i32, i32), Arc<Mutex<TileCell>>>>,
+    /// Strokes only fill transparent pixels, leaving existing paint on top.
+    pub paint_behind: bool,
+    /// When set, the layer filters the composite below instead of painting pixels.
+    pub adjustment: Option<Adjustment>,
+    /// When set, the tiles are a rasterization of these editable strokes.
+    pub vector: Option<VectorLayer>,
+    /// Color tag for sorting layers in the layers panel; never affects the pixels.
+    pub label: LayerLabel,
+    /// Whole-pixel translation applied when compositing, so moving a layer never touches
+    /// its tiles. Pixel edits work in tile coordinates and need it baked in first, see
+    /// [`Canvas::bake_layer_offset`]. The background layer always stays at (0, 0).
+    pub offset: (i32, i32),
+    /// Shared so a paging pass can work through it on a background thread.
+    tiles: Arc<TileStore>,
+    /// Unique for the lifetime of the process; lets caches notice layers being added, removed or moved.
+    id: u64,
 }
 
 impl Layer {
     /// Allocate a new layer backing store but keep tile data lazy.
     fn new(name: String, _width: usize, _height: usize, _tile_size: usize) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
         Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             name,
             visible: true,
             opacity: 1.0,
             locked: false,
-            tiles: Mutex::new(HashMap::new()),
+            paint_behind: false,
+            adjustment: None,
+            vector: None,
+            label: LayerLabel::None,
+            offset: (0, 0),
+            tiles: Arc::default(),
+        }
+    }
+
+    /// Blend one of this layer's pixels over `composite`, both linear and premultiplied. The
+    /// display and merges both go through here, so a merge bakes exactly what was shown.
+    #[inline]
+    fn blend_over(&self, src: Rgba, composite: Rgba) -> Rgba {
+        if src.a() == 0.0 {
+            return composite;
+        }
+        let src = if self.opacity < 1.0 { src * self.opacity } else { src };
+        src + composite * (1.0 - src.a())
+    }
+
+    /// Strength of the adjustment at a pixel given the layer's (optional) mask alpha.
+    #[inline]
+    fn adjustment_amount(&self, mask_alpha: Option<f32>) -> f32 {
+        match &self.adjustment {
+            Some(adj) if adj.use_mask => self.opacity * mask_alpha.unwrap_or(0.0),
+            Some(_) => self.opacity,
+            None => 0.0,
         }
     }
 }
 
+/// Per-pixel coverage of the tiles a fill touches, by tile coordinate.
+pub type CoverageMasks = Vec<((i32, i32), Vec<f32>)>;
+
+/// Pixels shown in place of a layer's own from canvas x `from_x` rightwards, for previews
+/// that leave the layer untouched until they are applied.
+pub struct LayerPreview {
+    pub layer_idx: usize,
+    pub tiles: Arc<HashMap<(i32, i32), Vec<Color32>>>,
+    pub from_x: f32,
+}
+
 /// Main drawing surface that owns tile grids and blending rules across layers.
 pub struct Canvas {
     width: usize,
@@ -83,6 +202,11 @@ pub struct Canvas {
 
     pub layers: Vec<Layer>,
     pub active_layer_idx: usize,
+    /// Document palette; constrains brush output when enabled.
+    pub palette: Palette,
+    composite_cache: Mutex<CompositeCache>,
+    /// Tiles changed since the renderer last took them.
+    dirty: Mutex<DirtyRegion>,
 }
 
 #[derive(Debug)]
@@ -93,6 +217,21 @@ pub(crate) struct TileCell {
     pub is_empty: bool,
 }
 
+impl TileCell {
+    /// Recompute `is_empty` from the pixels. With `release`, a fully transparent buffer is
+    /// dropped, which is only safe outside the background layer (a missing background
+    /// buffer reads as the paper color).
+    pub(crate) fn refresh_empty(&mut self, release: bool) {
+        let Some(data) = &self.data else {
+            return;
+        };
+        self.is_empty = data.iter().all(|&p| p == Color32::TRANSPARENT);
+        if self.is_empty && release {
+            self.data = None;
+        }
+    }
+}
+
 impl Canvas {
     /// Create a new canvas with a single background layer and configured tile size.
     pub fn new(width: usize, height: usize, clear_color: Color32, tile_size: usize) -> Self {
@@ -120,6 +259,9 @@ impl Canvas {
             clear_color: premultiply(clear_color),
             layers: vec![bg_layer, layer1],
             active_layer_idx: 1,
+            palette: Palette::default(),
+            composite_cache: Mutex::new(CompositeCache::default()),
+            dirty: Mutex::new(DirtyRegion::everything()),
         }
     }
 
@@ -130,6 +272,24 @@ impl Canvas {
         self.active_layer_idx = self.layers.len() - 1;
     }
 
+    /// Push a non-destructive adjustment layer on top of the stack and make it active.
+    pub fn add_adjustment_layer(&mut self, adjustment: Adjustment) {
+        let name = format!("{} {}", adjustment.kind.label(), self.layers.len() + 1);
+        let mut layer = Layer::new(name, self.width, self.height, self.tile_size);
+        layer.adjustment = Some(adjustment);
+        self.layers.push(layer);
+        self.active_layer_idx = self.layers.len() - 1;
+    }
+
+    /// Push an empty vector layer on top of the stack and make it active.
+    pub fn add_vector_layer(&mut self) {
+        let name = format!("Vector {}", self.layers.len() + 1);
+        let mut layer = Layer::new(name, self.width, self.height, self.tile_size);
+        layer.vector = Some(VectorLayer::default());
+        self.layers.push(layer);
+        self.active_layer_idx = self.layers.len() - 1;
+    }
+
     /// Current canvas width in pixels.
     pub fn width(&self) -> usize {
         self.width
@@ -154,9 +314,7 @@ impl Canvas {
         if self.active_layer_idx >= self.layers.len() {
             return None;
         }
-        let layer = &self.layers[self.active_layer_idx];
-        let tiles = layer.tiles.lock().unwrap();
-        tiles.get(&(tx, ty)).cloned()
+        self.layers[self.active_layer_idx].tiles.get((tx, ty))
     }
 
     /// Access a specific layer's tile by index (used for compositing).
@@ -164,9 +322,7 @@ impl Canvas {
         if layer_idx >= self.layers.len() {
             return None;
         }
-        let layer = &self.layers[layer_idx];
-        let tiles = layer.tiles.lock().unwrap();
-        tiles.get(&(tx, ty)).cloned()
+        self.layers[layer_idx].tiles.get((tx, ty))
     }
 
     /// Ensure the tile exists on a specific layer, initializing it if needed.
@@ -181,12 +337,7 @@ impl Canvas {
         }
         let layer = &self.layers[layer_idx];
         
-        let tile_arc = {
-            let mut tiles = layer.tiles.lock().unwrap();
-            tiles.entry((tx, ty))
-                .or_insert_with(|| Arc::new(Mutex::new(TileCell { data: None, is_empty: true })))
-                .clone()
-        };
+        let tile_arc = layer.tiles.get_or_insert((tx, ty));
 
         {
             let mut guard = tile_arc.lock().unwrap();
@@ -250,16 +401,6 @@ impl Canvas {
         self.ensure_layer_tile(layer_idx, tx, ty)
     }
 
-    /// Lock a tile in a specific layer only if it already exists; avoids allocating new data.
-    pub(crate) fn lock_layer_tile_if_exists(
-        &self,
-        layer_idx: usize,
-        tx: usize,
-        ty: usize,
-    ) -> Option<Arc<Mutex<TileCell>>> {
-        self.layer_tile_cell(layer_idx, tx as i32, ty as i32)
-    }
-
     /// Clone the raw pixel buffer for a tile in a given layer.
     pub fn get_layer_tile_data(
         &self,
@@ -281,6 +422,43 @@ impl Canvas {
             guard.is_empty = is_empty;
             guard.data = Some(data);
         }
+        self.layer_tiles_changed(layer_idx, &[(tx, ty)]);
+    }
+
+    /// Recheck whether a layer tile still holds any pixels, freeing its buffer if not.
+    pub(crate) fn refresh_tile_empty(&self, layer_idx: usize, tx: i32, ty: i32) {
+        if let Some(cell) = self.layer_tile_cell(layer_idx, tx, ty) {
+            cell.lock().unwrap().refresh_empty(layer_idx != 0);
+        }
+    }
+
+    /// Page the least recently looked-up layer tiles out to the scratch file until the pixels
+    /// left in memory fit in `budget_bytes`, then start a new paging epoch. Tiles looked up
+    /// since the previous pass stay in. The pass runs on `pool`, off the caller's thread, and
+    /// the returned channel gets how many tiles were paged out once it is done.
+    pub fn page_out_cold_tiles(&self, budget_bytes: usize, pool: &rayon::ThreadPool) -> mpsc::Receiver<usize> {
+        let stores: Vec<Arc<TileStore>> = self.layers.iter().map(|layer| layer.tiles.clone()).collect();
+        let tile_bytes = self.tile_size * self.tile_size * 4;
+        let (done, receiver) = mpsc::channel();
+        pool.spawn(move || {
+            let _ = done.send(page_out_stores(&stores, tile_bytes, budget_bytes));
+        });
+        receiver
+    }
+
+    /// Buffered tiles of a layer and whether each one is empty, for diagnostics.
+    pub fn allocated_tiles(&self, layer_idx: usize) -> Vec<((i32, i32), bool)> {
+        let Some(layer) = self.layers.get(layer_idx) else {
+            return Vec::new();
+        };
+        layer
+            .tiles
+            .entries()
+            .filter_map(|(coord, cell)| {
+                let guard = cell.lock().unwrap();
+                guard.data.as_ref().map(|_| (coord, guard.is_empty))
+            })
+            .collect()
     }
 
     /// Mark a tile as having content (not empty). Called after brush operations.
@@ -302,6 +480,19 @@ impl Canvas {
         out: &mut ColorImage,
         step: usize,
     ) {
+        self.write_region([x, y, w, h], out, step, None);
+    }
+
+    /// Composite tile (`tx`, `ty`) like [`Canvas::write_region_to_color_image`], with
+    /// `preview` shown over its layer.
+    pub fn write_tile_with_preview(&self, tx: usize, ty: usize, out: &mut ColorImage, step: usize, preview: Option<&LayerPreview>) {
+        let (x, y) = (tx * self.tile_size, ty * self.tile_size);
+        let (w, h) = (self.tile_size.min(self.width - x), self.tile_size.min(self.height - y));
+        self.write_region([x, y, w, h], out, step, preview);
+    }
+
+    /// Composite the region `[x, y, w, h]`. Only regions inside one tile show `preview`.
+    fn write_region(&self, [x, y, w, h]: [usize; 4], out: &mut ColorImage, step: usize, preview: Option<&LayerPreview>) {
         let _timer = ScopeTimer::new("region_to_color_image");
 
         let step = step.max(1);
@@ -324,14 +515,17 @@ impl Canvas {
             let tx = start_tx as i32;
             let ty = start_ty as i32;
 
-            // 1. Get Arcs (Locking the map briefly)
+            // 1. Get Arcs (Locking the map briefly); moved layers are gathered from the
+            // tiles their offset lands on this one
             let layer_arcs: Vec<Option<Arc<Mutex<TileCell>>>> = self
                 .layers
                 .iter()
-                .map(|layer| {
-                    let tiles = layer.tiles.lock().unwrap();
-                    tiles.get(&(tx, ty)).cloned()
-                })
+                .map(|layer| if layer.offset == (0, 0) { layer.tiles.get((tx, ty)) } else { None })
+                .collect();
+            let offset_tiles: Vec<Option<Vec<Color32>>> = self
+                .layers
+                .iter()
+                .map(|layer| if layer.offset == (0, 0) || !layer.visible { None } else { self.offset_tile(layer, tx, ty) })
                 .collect();
 
             // 2. Lock the Tiles (Holding locks for the render duration)
@@ -340,12 +534,56 @@ impl Canvas {
                 .map(|opt| opt.as_ref().map(|arc| arc.lock().unwrap()))
                 .collect();
 
-            // 3. Pre-convert all tiles to linear space to avoid repeated conversions
+            // A preview replaces the pixels of its layer right of its edge
+            let preview_tile = preview.and_then(|p| {
+                let data = p.tiles.get(&(tx, ty))?;
+                let from = (p.from_x - (tx as usize * self.tile_size) as f32).ceil().max(0.0) as usize;
+                (from < self.tile_size).then_some((p.layer_idx, data, from))
+            });
+
+            // 3. Reuse the flattened stacks around the active layer when nothing else changed.
+            // The cached stacks leave out the active layer, so they can't hold a preview.
+            let split = self.active_layer_idx;
+            let stack_key = self
+                .composite_split()
+                .filter(|&split| preview_tile.is_none_or(|(idx, _, _)| idx == split))
+                .map(|split| self.stack_key(split));
+            let mut cached = stack_key
+                .as_ref()
+                .and_then(|key| self.composite_cache.lock().unwrap().get(key, (tx, ty)));
+
+            // 4. Pre-convert tiles to linear space to avoid repeated conversions
+            // (only the active layer when the rest comes from the cache)
             let tile_pixel_count = self.tile_size * self.tile_size;
             let mut linear_tiles: Vec<Option<Vec<Rgba>>> = Vec::with_capacity(self.layers.len());
             
-            for opt_guard in layer_guards.iter() {
-                if let Some(guard) = opt_guard {
+            for (i, opt_guard) in layer_guards.iter().enumerate() {
+                let previewed = preview_tile.filter(|&(idx, _, _)| idx == i);
+                // Hidden layers and empty tiles never contribute, so skip converting them
+                let skip = (cached.is_some() && i != split)
+                    || !self.layers[i].visible
+                    || (previewed.is_none() && opt_guard.as_ref().is_some_and(|g| g.is_empty));
+                if skip {
+                    linear_tiles.push(None);
+                    continue;
+                }
+                if let Some((_, preview_data, from)) = previewed {
+                    let fallback = if i == 0 { self.clear_color } else { Color32::TRANSPARENT };
+                    let own = opt_guard.as_ref().and_then(|g| g.data.as_ref());
+                    let linear_data = (0..tile_pixel_count)
+                        .map(|idx| {
+                            let pixel = if idx % self.tile_size >= from {
+                                preview_data[idx]
+                            } else {
+                                own.map_or(fallback, |data| data[idx])
+                            };
+                            Rgba::from(pixel)
+                        })
+                        .collect();
+                    linear_tiles.push(Some(linear_data));
+                } else if let Some(data) = &offset_tiles[i] {
+                    linear_tiles.push(Some(data.iter().map(|&pixel| Rgba::from(pixel)).collect()));
+                } else if let Some(guard) = opt_guard {
                     if let Some(data) = &guard.data {
                         // Convert entire tile to linear space once
                         let mut linear_data = Vec::with_capacity(tile_pixel_count);
@@ -361,118 +599,99 @@ impl Canvas {
                 }
             }
 
-            // 4. Pre-calculate layer visibility and opacity to avoid lookups in the pixel loop
+            // 5. Pre-calculate layer visibility and opacity to avoid lookups in the pixel loop
             // Stores: (is_visible, opacity, has_data_guard_index, is_background, is_empty)
             let layer_props: Vec<(bool, f32, usize, bool, bool)> = layer_guards.iter().enumerate().map(|(i, opt_guard)| {
                 let is_visible = self.layers[i].visible && self.layers[i].opacity > 0.0;
-                let is_empty = opt_guard.as_ref().map_or(i != 0, |g| g.is_empty);
+                let is_empty = if preview_tile.is_some_and(|(idx, _, _)| idx == i) {
+                    false
+                } else if self.layers[i].offset != (0, 0) {
+                    offset_tiles[i].is_none()
+                } else {
+                    opt_guard.as_ref().map_or(i != 0, |g| g.is_empty)
+                };
                 (is_visible, self.layers[i].opacity, i, i == 0, is_empty)
             }).collect();
-            
-            // Pre-convert clear_color to linear space
-            let clear_color_linear = Rgba::from(self.clear_color);
-
-            if true { 
-                for dst_y in 0..dst_h {
-                    let global_y_start = y + dst_y * step;
-                    let row_start = dst_y * dst_w;
-
-                    for dst_x in 0..dst_w {
-                        let global_x_start = x + dst_x * step;
-
-                        if step == 1 {
-                            // --- FAST PATH (1:1 Rendering) ---
-                            let local_y = global_y_start % self.tile_size;
-                            let local_x = global_x_start % self.tile_size;
-                            let src_idx = local_y * self.tile_size + local_x;
 
-                            // Linear Accumulator (starts transparent)
-                            let mut composite = Rgba::from_rgba_premultiplied(0.0, 0.0, 0.0, 0.0);
+            if let (None, Some(key)) = (&cached, &stack_key) {
+                let flatten = |range: Range<usize>| -> Vec<Rgba> {
+                    (0..tile_pixel_count)
+                        .map(|src_idx| self.composite_layers(range.clone(), &linear_tiles, &layer_props, src_idx, Rgba::TRANSPARENT))
+                        .collect()
+                };
+                let tile = Arc::new(CompositeTile {
+                    below: flatten(0..split),
+                    above: flatten(split + 1..self.layers.len()),
+                });
+                self.composite_cache.lock().unwrap().insert(key, (tx, ty), tile.clone());
+                cached = Some(tile);
+            }
 
-                            for (i, (visible, opacity, _, is_bg, is_empty)) in layer_props.iter().enumerate() {
-                                if !visible || *is_empty { continue; }
+            let composite_at = |src_idx: usize| -> Rgba {
+                match &cached {
+                    Some(tile) => {
+                        let mid = self.composite_layers(split..split + 1, &linear_tiles, &layer_props, src_idx, tile.below[src_idx]);
+                        let above = tile.above[src_idx];
+                        above + mid * (1.0 - above.a())
+                    }
+                    None => self.composite_layers(0..self.layers.len(), &linear_tiles, &layer_props, src_idx, Rgba::TRANSPARENT),
+                }
+            };
 
-                                // Get pixel in linear space (already converted)
-                                let src = if let Some(linear_data) = &linear_tiles[i] {
-                                    linear_data[src_idx]
-                                } else if *is_bg {
-                                    clear_color_linear
-                                } else {
-                                    Rgba::TRANSPARENT
-                                };
+            for dst_y in 0..dst_h {
+                let global_y_start = y + dst_y * step;
+                let row_start = dst_y * dst_w;
 
-                                if src.a() == 0.0 { continue; }
+                for dst_x in 0..dst_w {
+                    let global_x_start = x + dst_x * step;
 
-                                // Apply Opacity and Blend (already in linear space)
-                                let src = if *opacity < 1.0 { src * *opacity } else { src };
-                                
-                                // Linear Blend: Src Over Composite
-                                composite = src + composite * (1.0 - src.a());
-                            }
-                            
-                            // 4. Convert Linear Float -> sRGB (Once at the end) - Fast LUT-based
-                            out.pixels[row_start + dst_x] = rgba_to_color32_fast(composite);
+                    if step == 1 {
+                        // --- FAST PATH (1:1 Rendering) ---
+                        let local_y = global_y_start % self.tile_size;
+                        let local_x = global_x_start % self.tile_size;
+                        let src_idx = local_y * self.tile_size + local_x;
 
-                        } else {
-                            // --- DOWNSAMPLING PATH (High Quality) ---
-                            let mut r_acc = 0.0;
-                            let mut g_acc = 0.0;
-                            let mut b_acc = 0.0;
-                            let mut a_acc = 0.0;
-                            let mut count = 0.0;
-
-                            for sy in 0..step {
-                                let global_y = global_y_start + sy;
-                                if global_y >= y + h { continue; }
-                                let local_y = global_y % self.tile_size;
-
-                                for sx in 0..step {
-                                    let global_x = global_x_start + sx;
-                                    if global_x >= x + w { continue; }
-                                    let local_x = global_x % self.tile_size;
-
-                                    let src_idx = local_y * self.tile_size + local_x;
-                                    
-                                    // Calculate the color for this sub-pixel using Linear Math
-                                    let mut sub_composite = Rgba::from_rgba_premultiplied(0.0, 0.0, 0.0, 0.0);
-
-                                    for (i, (visible, opacity, _, is_bg, is_empty)) in layer_props.iter().enumerate() {
-                                        if !visible || *is_empty { continue; }
-
-                                        // Get pixel in linear space (already converted)
-                                        let src = if let Some(linear_data) = &linear_tiles[i] {
-                                            linear_data[src_idx]
-                                        } else if *is_bg {
-                                            clear_color_linear
-                                        } else {
-                                            Rgba::TRANSPARENT
-                                        };
-
-                                        if src.a() == 0.0 { continue; }
-
-                                        // Apply Opacity and Blend (already in linear space)
-                                        let src = if *opacity < 1.0 { src * *opacity } else { src };
-                                        sub_composite = src + sub_composite * (1.0 - src.a());
-                                    }
+                        // Convert Linear Float -> sRGB (Once at the end) - Fast LUT-based
+                        out.pixels[row_start + dst_x] = rgba_to_color32_fast(composite_at(src_idx));
 
-                                    r_acc += sub_composite.r();
-                                    g_acc += sub_composite.g();
-                                    b_acc += sub_composite.b();
-                                    a_acc += sub_composite.a();
-                                    count += 1.0;
-                                }
+                    } else {
+                        // --- DOWNSAMPLING PATH (High Quality) ---
+                        let mut r_acc = 0.0;
+                        let mut g_acc = 0.0;
+                        let mut b_acc = 0.0;
+                        let mut a_acc = 0.0;
+                        let mut count = 0.0;
+
+                        for sy in 0..step {
+                            let global_y = global_y_start + sy;
+                            if global_y >= y + h { continue; }
+                            let local_y = global_y % self.tile_size;
+
+                            for sx in 0..step {
+                                let global_x = global_x_start + sx;
+                                if global_x >= x + w { continue; }
+                                let local_x = global_x % self.tile_size;
+
+                                // Calculate the color for this sub-pixel using Linear Math
+                                let sub_composite = composite_at(local_y * self.tile_size + local_x);
+
+                                r_acc += sub_composite.r();
+                                g_acc += sub_composite.g();
+                                b_acc += sub_composite.b();
+                                a_acc += sub_composite.a();
+                                count += 1.0;
                             }
+                        }
 
-                            if count > 0.0 {
-                                let inv = 1.0 / count;
-                                // Convert the averaged Linear result back to sRGB - Fast LUT-based
-                                out.pixels[row_start + dst_x] = rgba_to_color32_fast(Rgba::from_rgba_premultiplied(
-                                    r_acc * inv,
-                                    g_acc * inv,
-                                    b_acc * inv,
-                                    a_acc * inv
-                                ));
-                            }
+                        if count > 0.0 {
+                            let inv = 1.0 / count;
+                            // Convert the averaged Linear result back to sRGB - Fast LUT-based
+                            out.pixels[row_start + dst_x] = rgba_to_color32_fast(Rgba::from_rgba_premultiplied(
+                                r_acc * inv,
+                                g_acc * inv,
+                                b_acc * inv,
+                                a_acc * inv
+                            ));
                         }
                     }
                 }
@@ -482,11 +701,10 @@ impl Canvas {
 
         // --- FALLBACK (Multi-tile / Slow Path) ---
         // Optimization: Cache tiles per row to reduce HashMap lookups
+        let ts = self.tile_size as i32;
         for dst_y in 0..dst_h {
             let global_y = y + dst_y * step;
-            let ty = (global_y / self.tile_size) as i32;
-            let local_y = global_y % self.tile_size;
-            
+
             // Cache tile references for this row across all layers
             // Tuple: (tile_arc, cached_tx, is_empty)
             let mut row_tile_cache: Vec<Option<(Arc<Mutex<TileCell>>, i32, bool)>> = vec![None; self.layers.len()];
@@ -494,9 +712,6 @@ impl Canvas {
             let mut dst_x = 0;
             while dst_x < dst_w {
                 let global_x = x + dst_x * step;
-                let tx = (global_x / self.tile_size) as i32;
-                let local_x = global_x % self.tile_size;
-
                 let dst_start = dst_y * dst_w + dst_x;
 
                 let mut composite = Rgba::from_rgba_premultiplied(0.0, 0.0, 0.0, 0.0);
@@ -504,6 +719,11 @@ impl Canvas {
                 for (layer_idx, layer) in self.layers.iter().enumerate() {
                     if !layer.visible || layer.opacity <= 0.0 { continue; }
 
+                    // Moved layers are looked up where their offset puts this pixel
+                    let (lx, ly) = (global_x as i32 - layer.offset.0, global_y as i32 - layer.offset.1);
+                    let (tx, ty) = (lx.div_euclid(ts), ly.div_euclid(ts));
+                    let (local_x, local_y) = (lx.rem_euclid(ts) as usize, ly.rem_euclid(ts) as usize);
+
                     // Check cache first
                     let needs_lookup = row_tile_cache[layer_idx]
                         .as_ref()
@@ -517,6 +737,18 @@ impl Canvas {
                             });
                     }
 
+                    if let Some(adj) = &layer.adjustment {
+                        let mask = row_tile_cache[layer_idx].as_ref().and_then(|(cell, _, _)| {
+                            let guard = cell.lock().unwrap();
+                            guard
+                                .data
+                                .as_ref()
+                                .map(|data| Rgba::from(data[local_y * self.tile_size + local_x]).a())
+                        });
+                        composite = adj.apply(composite, layer.adjustment_amount(mask));
+                        continue;
+                    }
+
                     // Skip if tile is empty
                     if let Some((_, _, is_empty)) = &row_tile_cache[layer_idx] {
                         if *is_empty { continue; }
@@ -543,12 +775,7 @@ impl Canvas {
 
                     if pixel_c32 == Color32::TRANSPARENT { continue; }
 
-                    // Linear Blend
-                    let mut src = Rgba::from(pixel_c32);
-                    if layer.opacity < 1.0 {
-                        src = src * layer.opacity;
-                    }
-                    composite = src + composite * (1.0 - src.a());
+                    composite = layer.blend_over(Rgba::from(pixel_c32), composite);
                 }
 
                 out.pixels[dst_start] = rgba_to_color32_fast(composite);
@@ -557,72 +784,615 @@ impl Canvas {
         }
     }
 
-    /// Clear the active layer to the provided color (or transparent for non-background).
-    pub fn clear(&mut self, color: Color) {
-        self.clear_color = premultiply(color.to_color32());
-        if let Some(layer) = self.layers.get(self.active_layer_idx) {
-            let tiles = layer.tiles.lock().unwrap();
-            for tile_arc in tiles.values() {
-                let mut cell = tile_arc.lock().unwrap();
-                cell.data = None;
-                cell.is_empty = true;
+    /// Pixels of a moved layer that its offset puts on canvas tile (`tx`, `ty`), or `None`
+    /// when none of them are painted.
+    fn offset_tile(&self, layer: &Layer, tx: i32, ty: i32) -> Option<Vec<Color32>> {
+        let ts = self.tile_size as i32;
+        // Where the canvas tile starts in the layer's own pixels
+        let (gx, gy) = (tx * ts - layer.offset.0, ty * ts - layer.offset.1);
+        let mut out: Option<Vec<Color32>> = None;
+        for sty in gy.div_euclid(ts)..=(gy + ts - 1).div_euclid(ts) {
+            for stx in gx.div_euclid(ts)..=(gx + ts - 1).div_euclid(ts) {
+                let Some(cell) = layer.tiles.get((stx, sty)) else {
+                    continue;
+                };
+                let guard = cell.lock().unwrap();
+                let (Some(data), false) = (&guard.data, guard.is_empty) else {
+                    continue;
+                };
+                let out = out.get_or_insert_with(|| vec![Color32::TRANSPARENT; self.tile_size * self.tile_size]);
+                // Overlap with the source tile, in the canvas tile's pixels
+                let (x0, x1) = ((stx * ts - gx).max(0), (stx * ts + ts - gx).min(ts));
+                let (y0, y1) = ((sty * ts - gy).max(0), (sty * ts + ts - gy).min(ts));
+                let len = (x1 - x0) as usize;
+                for y in y0..y1 {
+                    let src = ((y + gy - sty * ts) * ts + x0 + gx - stx * ts) as usize;
+                    let dst = (y * ts + x0) as usize;
+                    out[dst..dst + len].copy_from_slice(&data[src..src + len]);
+                }
+            }
+        }
+        out
+    }
+
+    /// Average color of the pixels up to `radius` away from (`x`, `y`), clipped to the canvas.
+    /// Samples what is displayed, or only `layer_idx` when given, and averages in linear
+    /// premultiplied space so soft edges don't darken the result. Returns the straight,
+    /// opaque color, or `None` when everything sampled is transparent.
+    pub fn sample_color(&self, x: i32, y: i32, radius: usize, layer_idx: Option<usize>) -> Option<Color32> {
+        let r = radius as i32;
+        let (x0, y0) = ((x - r).max(0), (y - r).max(0));
+        let (x1, y1) = ((x + r + 1).min(self.width as i32), (y + r + 1).min(self.height as i32));
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+        let (w, h) = ((x1 - x0) as usize, (y1 - y0) as usize);
+        let pixels: Vec<Color32> = match layer_idx {
+            None => {
+                let mut img = ColorImage::new([w, h], Color32::TRANSPARENT);
+                self.write_region_to_color_image(x0 as usize, y0 as usize, w, h, &mut img, 1);
+                img.pixels
+            }
+            Some(idx) => {
+                let layer = self.layers.get(idx)?;
+                let ts = self.tile_size as i32;
+                (y0..y1)
+                    .flat_map(|py| (x0..x1).map(move |px| (px - layer.offset.0, py - layer.offset.1)))
+                    .map(|(lx, ly)| {
+                        let cell = layer.tiles.get((lx.div_euclid(ts), ly.div_euclid(ts)));
+                        let guard = cell.as_ref().map(|c| c.lock().unwrap());
+                        match guard.as_ref().and_then(|g| g.data.as_ref()) {
+                            Some(data) => data[(ly.rem_euclid(ts) * ts + lx.rem_euclid(ts)) as usize],
+                            None if idx == 0 => self.clear_color,
+                            None => Color32::TRANSPARENT,
+                        }
+                    })
+                    .collect()
+            }
+        };
+        let sum = pixels.iter().fold(Rgba::TRANSPARENT, |acc, &px| acc + Rgba::from(px));
+        let avg = sum * (1.0 / pixels.len() as f32);
+        if avg.a() <= 0.0 {
+            return None;
+        }
+        let inv = 1.0 / avg.a();
+        Some(Color32::from(Rgba::from_rgb(avg.r() * inv, avg.g() * inv, avg.b() * inv)))
+    }
+
+    /// Move a layer by (`dx`, `dy`) pixels by changing its offset, leaving its tiles alone.
+    /// The background layer doesn't move.
+    pub fn offset_layer(&mut self, layer_idx: usize, dx: i32, dy: i32) {
+        let Some(layer) = self.layers.get_mut(layer_idx).filter(|_| layer_idx != 0) else {
+            return;
+        };
+        layer.offset = (layer.offset.0 + dx, layer.offset.1 + dy);
+        self.invalidate_composite();
+    }
+
+    /// Move a layer's tiles (and vector paths) by its offset and reset the offset, so tile
+    /// coordinates are canvas coordinates again. The display doesn't change and no pixel is
+    /// lost, even ones moved off the canvas. Returns the offset that was baked, if any.
+    pub fn bake_layer_offset(&mut self, layer_idx: usize) -> Option<(i32, i32)> {
+        let offset = self.layers.get(layer_idx)?.offset;
+        if offset == (0, 0) {
+            return None;
+        }
+        let pixels = shift_tiles(&self.capture_layer_pixels(layer_idx), offset.0, offset.1, self.tile_size, Color32::TRANSPARENT);
+        let layer = &mut self.layers[layer_idx];
+        layer.tiles = Arc::default();
+        for (coord, data) in pixels {
+            let mut cell = TileCell { data: Some(data), is_empty: false };
+            cell.refresh_empty(true);
+            layer.tiles.insert(coord, cell);
+        }
+        if let Some(vector) = layer.vector.as_mut() {
+            let delta = Vec2::new(offset.0 as f32, offset.1 as f32);
+            vector.strokes.iter_mut().for_each(|stroke| stroke.translate(delta));
+        }
+        layer.offset = (0, 0);
+        self.invalidate_composite();
+        Some(offset)
+    }
+
+    /// Blend the layers in `range` over `composite` for one pixel of a single-tile region.
+    #[inline]
+    fn composite_layers(
+        &self,
+        range: Range<usize>,
+        linear_tiles: &[Option<Vec<Rgba>>],
+        layer_props: &[(bool, f32, usize, bool, bool)],
+        src_idx: usize,
+        mut composite: Rgba,
+    ) -> Rgba {
+        for i in range {
+            let (visible, _, _, is_bg, is_empty) = layer_props[i];
+            if !visible { continue; }
+
+            // Adjustment layers filter the composite instead of blending over it
+            if let Some(adj) = &self.layers[i].adjustment {
+                let mask = linear_tiles[i].as_ref().map(|d| d[src_idx].a());
+                composite = adj.apply(composite, self.layers[i].adjustment_amount(mask));
+                continue;
+            }
+            if is_empty { continue; }
+
+            // Get pixel in linear space (already converted)
+            let src = if let Some(linear_data) = &linear_tiles[i] {
+                linear_data[src_idx]
+            } else if is_bg {
+                Rgba::from(self.clear_color)
+            } else {
+                Rgba::TRANSPARENT
+            };
+
+            // Opacity and "over", in the linear space the tiles were converted to
+            composite = self.layers[i].blend_over(src, composite);
+        }
+        composite
+    }
+
+    /// Layer the composite cache splits the stack around, if caching pays off for this stack.
+    ///
+    /// The layers above can only be pre-flattened when plain "over" blending is
+    /// all that happens there; an adjustment above the split filters everything below it.
+    fn composite_split(&self) -> Option<usize> {
+        let split = self.active_layer_idx;
+        if self.layers.len() <= 2 || split >= self.layers.len() {
+            return None;
+        }
+        let adjustment_above = self.layers[split + 1..]
+            .iter()
+            .any(|l| l.visible && l.adjustment.is_some());
+        (!adjustment_above).then_some(split)
+    }
+
+    fn stack_key(&self, split: usize) -> StackKey {
+        StackKey {
+            split,
+            clear_color: self.clear_color,
+            layers: self
+                .layers
+                .iter()
+                .map(|l| LayerKey {
+                    id: l.id,
+                    visible: l.visible,
+                    opacity_bits: l.opacity.to_bits(),
+                    offset: l.offset,
+                    paint_behind: l.paint_behind,
+                    adjustment: l.adjustment.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Forget all flattened composites and redisplay everything. Needed after edits the
+    /// cache key can't see, such as pixels moving between tiles.
+    pub fn invalidate_composite(&self) {
+        self.composite_cache.lock().unwrap().clear();
+        self.mark_all_dirty();
+    }
+
+    /// Forget the flattened composites of specific tiles after pixels of a layer other
+    /// than the active one changed there, and redisplay them.
+    pub fn invalidate_composite_tiles(&self, coords: &[(i32, i32)]) {
+        let mut cache = self.composite_cache.lock().unwrap();
+        for &tile in coords {
+            cache.remove(tile);
+        }
+        self.mark_dirty_tiles(coords.iter().copied());
+    }
+
+    /// Record that pixels of `layer_idx` changed in these tiles. The flattened composite
+    /// only holds the layers around the active one, so it is dropped for other layers.
+    pub fn layer_tiles_changed(&self, layer_idx: usize, coords: &[(i32, i32)]) {
+        // A moved layer's tiles don't line up with the display's
+        if self.layers.get(layer_idx).is_some_and(|l| l.offset != (0, 0)) {
+            self.invalidate_composite();
+        } else if layer_idx == self.active_layer_idx {
+            self.mark_dirty_tiles(coords.iter().copied());
+        } else {
+            self.invalidate_composite_tiles(coords);
+        }
+    }
+
+    /// Record that every tile holding pixels of `layer_idx` changed, for edits made
+    /// straight to the layer's tiles such as filter previews or visibility toggles.
+    pub fn layer_changed(&self, layer_idx: usize) {
+        let coords: Vec<(i32, i32)> = self.allocated_tiles(layer_idx).into_iter().map(|(coord, _)| coord).collect();
+        self.layer_tiles_changed(layer_idx, &coords);
+    }
+
+    /// Redisplay the given tiles.
+    pub fn mark_dirty_tiles(&self, coords: impl IntoIterator<Item = (i32, i32)>) {
+        self.dirty.lock().unwrap().add_tiles(coords);
+    }
+
+    /// Redisplay the tiles overlapping the canvas-space rectangle from `min` to `max`.
+    pub fn mark_dirty_rect(&self, min: Vec2, max: Vec2) {
+        let ts = self.tile_size as i32;
+        let max_tx = self.tiles_x as i32 - 1;
+        let max_ty = self.tiles_y as i32 - 1;
+        let x0 = (min.x.floor() as i32).div_euclid(ts).max(0);
+        let y0 = (min.y.floor() as i32).div_euclid(ts).max(0);
+        let x1 = (max.x.ceil() as i32).div_euclid(ts).min(max_tx);
+        let y1 = (max.y.ceil() as i32).div_euclid(ts).min(max_ty);
+        if x0 > x1 || y0 > y1 {
+            return;
+        }
+        self.mark_dirty_tiles((y0..=y1).flat_map(|ty| (x0..=x1).map(move |tx| (tx, ty))));
+    }
+
+    /// Redisplay the whole canvas, for changes that reach tiles without storage or
+    /// only the way the canvas is displayed.
+    pub fn mark_all_dirty(&self) {
+        self.dirty.lock().unwrap().add_everything();
+    }
+
+    /// Everything changed since the last call, leaving nothing dirty.
+    pub fn take_dirty(&self) -> DirtyRegion {
+        std::mem::take(&mut *self.dirty.lock().unwrap())
+    }
+
+    /// Run `render` with the background layer hidden when `hide` is set, so the composite stays
+    /// transparent wherever no other layer painted.
+    pub fn with_background_hidden<R>(&mut self, hide: bool, render: impl FnOnce(&Canvas) -> R) -> R {
+        let Some(visible) = self.layers.first().map(|l| l.visible).filter(|_| hide) else {
+            return render(self);
+        };
+        self.layers[0].visible = false;
+        let result = render(self);
+        self.layers[0].visible = visible;
+        result
+    }
+
+    /// Drop the active layer's pixels, leaving the background color untouched.
+    /// Use `set_background_color` to change the paper color.
+    pub fn clear(&mut self) {
+        let Some(layer) = self.layers.get(self.active_layer_idx) else {
+            return;
+        };
+        // Background tiles without a buffer still show the paper color
+        let is_empty = self.active_layer_idx != 0;
+        let mut cleared = Vec::new();
+        for (coord, tile_arc) in layer.tiles.entries() {
+            let mut cell = tile_arc.lock().unwrap();
+            cell.data = None;
+            cell.is_empty = is_empty;
+            cleared.push(coord);
+        }
+        self.invalidate_composite_tiles(&cleared);
+    }
+
+    /// Swap in a new document background color (premultiplied), returning the previous one.
+    pub(crate) fn replace_clear_color(&mut self, color: Color32) -> Color32 {
+        if color != self.clear_color {
+            self.mark_all_dirty();
+        }
+        std::mem::replace(&mut self.clear_color, color)
+    }
+
+    /// Change the paper color of the background layer and record the prior state into `history`.
+    ///
+    /// Only pixels that still hold the old background color are repainted, so
+    /// anything drawn onto the background layer survives. Returns the tile
+    /// coordinates that changed; tiles without storage pick up the new color on their own.
+    pub fn set_background_color(&mut self, color: Color32, history: &mut UndoAction) -> Vec<(i32, i32)> {
+        let old = self.clear_color;
+        let new = premultiply(color);
+        if old == new {
+            return Vec::new();
+        }
+        let Some(background) = self.layers.first() else {
+            return Vec::new();
+        };
+
+        let tiles = background.tiles.entries();
+
+        let mut affected = Vec::new();
+        for ((tx, ty), tile_arc) in tiles {
+            let mut tile = tile_arc.lock().unwrap();
+            let Some(data) = tile.data.as_mut() else {
+                continue;
+            };
+            if !data.contains(&old) {
+                continue;
+            }
+            history.tiles.push(TileSnapshot {
+                tx,
+                ty,
+                layer_idx: 0,
+                x0: 0,
+                y0: 0,
+                width: self.tile_size,
+                height: self.tile_size,
+                data: data.clone(),
+            });
+            for px in data.iter_mut().filter(|px| **px == old) {
+                *px = new;
+            }
+            affected.push((tx, ty));
+        }
+
+        history.background = Some(old);
+        self.clear_color = new;
+        self.mark_all_dirty();
+        affected
+    }
+
+    /// Erase a layer (or only the selected area) and record the prior pixels into `history`.
+    /// Returns the tile coordinates that changed.
+    pub fn clear_layer(&self, layer_idx: usize, selection: Option<&SelectionManager>, history: &mut UndoAction) -> Vec<(i32, i32)> {
+        let Some(layer) = self.layers.get(layer_idx) else {
+            return Vec::new();
+        };
+        // The background falls back to the document color rather than transparency
+        let cleared = if layer_idx == 0 { self.clear_color } else { Color32::TRANSPARENT };
+        let tile_size = self.tile_size;
+
+        let tiles = layer.tiles.entries();
+
+        let mut affected = Vec::new();
+        for ((tx, ty), tile_arc) in tiles {
+            let mut tile = tile_arc.lock().unwrap();
+            let Some(data) = tile.data.as_mut() else {
+                continue;
+            };
+
+            let before = data.clone();
+            let mask = selection.map(|sel| selection_mask(sel, tx, ty, tile_size));
+            let changed = apply_masked(data, mask.as_deref(), |_, _| cleared);
+
+            if changed {
+                history.tiles.push(TileSnapshot {
+                    tx,
+                    ty,
+                    layer_idx,
+                    x0: 0,
+                    y0: 0,
+                    width: tile_size,
+                    height: tile_size,
+                    data: before,
+                });
+                affected.push((tx, ty));
+                tile.refresh_empty(layer_idx != 0);
             }
         }
+        self.layer_tiles_changed(layer_idx, &affected);
+        affected
+    }
+
+    /// Run `filter` over captured layer tiles (as from [`Canvas::capture_layer_pixels`]), only
+    /// inside the selection when there is one, and return the filtered copies. Reads nothing
+    /// from the canvas, so it can run on a background job; the result goes back in through
+    /// [`Canvas::set_layer_tile_data`].
+    pub fn filter_tiles<F>(
+        src_tiles: &HashMap<(i32, i32), Vec<Color32>>,
+        tile_size: usize,
+        selection: Option<&SelectionManager>,
+        filter: F,
+    ) -> HashMap<(i32, i32), Vec<Color32>>
+    where
+        F: Fn(i32, i32, Color32) -> Color32 + Sync,
+    {
+        src_tiles
+            .par_iter()
+            .map(|(&(tx, ty), src)| {
+                let mut out = src.clone();
+                let mask = selection.map(|sel| selection_mask(sel, tx, ty, tile_size));
+                apply_masked(&mut out, mask.as_deref(), |idx, old| {
+                    filter(tx * tile_size as i32 + (idx % tile_size) as i32, ty * tile_size as i32 + (idx / tile_size) as i32, old)
+                });
+                ((tx, ty), out)
+            })
+            .collect()
+    }
+
+    /// Per-pixel masks of `coverage` over the tiles touching `min..max`, clipped to a canvas of
+    /// `size`, for [`Canvas::fill_coverage`]. Tiles it misses entirely are left out. Reads
+    /// nothing from the canvas, so it can run on a background job.
+    pub fn coverage_masks<F>(min: Vec2, max: Vec2, size: (usize, usize), tile_size: usize, coverage: F) -> CoverageMasks
+    where
+        F: Fn(Vec2) -> f32 + Sync,
+    {
+        let x0 = min.x.floor().max(0.0) as i32;
+        let y0 = min.y.floor().max(0.0) as i32;
+        let x1 = (max.x.ceil() as i32).min(size.0 as i32 - 1);
+        let y1 = (max.y.ceil() as i32).min(size.1 as i32 - 1);
+        if x0 > x1 || y0 > y1 {
+            return Vec::new();
+        }
+
+        let ts = tile_size as i32;
+        let coords: Vec<(i32, i32)> = (y0 / ts..=y1 / ts)
+            .flat_map(|ty| (x0 / ts..=x1 / ts).map(move |tx| (tx, ty)))
+            .collect();
+        coords
+            .into_par_iter()
+            .filter_map(|(tx, ty)| {
+                let mut mask = vec![0.0; tile_size * tile_size];
+                let mut covered = false;
+                for (idx, m) in mask.iter_mut().enumerate() {
+                    let px = tx * ts + (idx % tile_size) as i32;
+                    let py = ty * ts + (idx / tile_size) as i32;
+                    if px < x0 || px > x1 || py < y0 || py > y1 {
+                        continue;
+                    }
+                    *m = coverage(Vec2::new(px as f32 + 0.5, py as f32 + 0.5)).min(1.0);
+                    covered |= *m > 0.0;
+                }
+                covered.then_some(((tx, ty), mask))
+            })
+            .collect()
+    }
+
+    /// Composite a solid color over a layer, weighted per pixel by `masks` from
+    /// [`Canvas::coverage_masks`]. Touched tiles are snapshotted into `history`; returns their coordinates.
+    pub fn fill_coverage(&self, layer_idx: usize, color: Color32, masks: &[((i32, i32), Vec<f32>)], history: &mut UndoAction) -> Vec<(i32, i32)> {
+        if layer_idx >= self.layers.len() {
+            return Vec::new();
+        }
+        let tile_size = self.tile_size;
+        let snapshots: Vec<TileSnapshot> = masks
+            .par_iter()
+            .filter_map(|((tx, ty), mask)| {
+                let tile_arc = self.ensure_layer_tile(layer_idx, *tx, *ty)?;
+                let mut tile = tile_arc.lock().unwrap();
+                let data = tile.data.as_mut()?;
+                let before = data.clone();
+                if !apply_masked(data, Some(mask), |_, old| alpha_over(color, old)) {
+                    return None;
+                }
+                tile.is_empty = false;
+                Some(TileSnapshot {
+                    tx: *tx,
+                    ty: *ty,
+                    layer_idx,
+                    x0: 0,
+                    y0: 0,
+                    width: tile_size,
+                    height: tile_size,
+                    data: before,
+                })
+            })
+            .collect();
+
+        let affected: Vec<(i32, i32)> = snapshots.iter().map(|s| (s.tx, s.ty)).collect();
+        history.tiles.extend(snapshots);
+        self.layer_tiles_changed(layer_idx, &affected);
+        affected
     }
 
     pub fn capture_layer_pixels(&self, layer_idx: usize) -> HashMap<(i32, i32), Vec<Color32>> {
         let mut pixels = HashMap::new();
         if let Some(layer) = self.layers.get(layer_idx) {
-            let tiles = layer.tiles.lock().unwrap();
-            for ((tx, ty), tile_arc) in tiles.iter() {
+            for (coord, tile_arc) in layer.tiles.entries() {
                 let guard = tile_arc.lock().unwrap();
                 if let Some(data) = &guard.data {
-                    pixels.insert((*tx, *ty), data.clone());
+                    pixels.insert(coord, data.clone());
                 }
             }
         }
         pixels
     }
 
-    pub fn preview_transform(&mut self, layer_idx: usize, src_tiles: &HashMap<(i32, i32), Vec<Color32>>, offset: Vec2, rotation: f32, scale: Vec2, center: Vec2) {
-        let tile_size = self.tile_size;
-        
-        // 1. Collect all source pixels from buffer
-        // Optimization: Pre-allocate with estimated capacity
-        let estimated_pixels = src_tiles.len() * tile_size * tile_size / 4; // Assume 25% fill
-        let mut src_pixels: HashMap<(i32, i32), Color32> = HashMap::with_capacity(estimated_pixels);
-        let mut src_bounds = eframe::egui::Rect::NOTHING;
-        let mut first = true;
+    /// A copy of the layer stack that shares this canvas's tiles instead of copying them, for
+    /// reading the composite on a background job. Pixel edits made meanwhile show up in it
+    /// tile by tile; layer changes don't. Vector paths are left out.
+    pub fn shared_view(&self) -> Canvas {
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| Layer {
+                name: layer.name.clone(),
+                visible: layer.visible,
+                opacity: layer.opacity,
+                locked: layer.locked,
+                paint_behind: layer.paint_behind,
+                adjustment: layer.adjustment.clone(),
+                vector: None,
+                label: layer.label,
+                offset: layer.offset,
+                tiles: layer.tiles.clone(),
+                id: layer.id,
+            })
+            .collect();
+        Canvas {
+            width: self.width,
+            height: self.height,
+            tile_size: self.tile_size,
+            tiles_x: self.tiles_x,
+            tiles_y: self.tiles_y,
+            clear_color: self.clear_color,
+            layers,
+            active_layer_idx: self.active_layer_idx,
+            palette: Palette::default(),
+            composite_cache: Mutex::new(CompositeCache::default()),
+            dirty: Mutex::new(DirtyRegion::everything()),
+        }
+    }
 
-        for ((tx, ty), data) in src_tiles {
-            let base_x = *tx * tile_size as i32;
-            let base_y = *ty * tile_size as i32;
-            
-            for py in 0..tile_size {
-                for px in 0..tile_size {
-                    let idx = py * tile_size + px;
-                    if data[idx].a() > 0 {
-                        let gx = base_x + px as i32;
-                        let gy = base_y + py as i32;
-                        
-                        src_pixels.insert((gx, gy), data[idx]);
-                        
-                        let pos = eframe::egui::pos2(gx as f32, gy as f32);
-                        if first {
-                            src_bounds = eframe::egui::Rect::from_min_max(pos, pos);
-                            first = false;
-                        } else {
-                            src_bounds.extend_with(pos);
-                        }
-                    }
+    /// Copy every layer (pixels and properties) into a named document snapshot.
+    pub fn snapshot(&self, name: String) -> CanvasSnapshot {
+        let layers = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(idx, layer)| LayerSnapshot {
+                name: layer.name.clone(),
+                visible: layer.visible,
+                opacity: layer.opacity,
+                locked: layer.locked,
+                paint_behind: layer.paint_behind,
+                adjustment: layer.adjustment.clone(),
+                vector: layer.vector.clone(),
+                label: layer.label,
+                offset: layer.offset,
+                tiles: self.capture_layer_pixels(idx),
+            })
+            .collect();
+        CanvasSnapshot {
+            name,
+            width: self.width,
+            height: self.height,
+            clear_color: self.clear_color,
+            layers,
+            active_layer_idx: self.active_layer_idx,
+        }
+    }
+
+    /// Bring the document back to a snapshot: its size, paper color and layer stack.
+    pub fn restore_snapshot(&mut self, snapshot: &CanvasSnapshot) {
+        self.width = snapshot.width;
+        self.height = snapshot.height;
+        self.tiles_x = snapshot.width.div_ceil(self.tile_size);
+        self.tiles_y = snapshot.height.div_ceil(self.tile_size);
+        self.clear_color = snapshot.clear_color;
+        self.restore_layers(snapshot);
+        self.invalidate_composite();
+    }
+
+    /// Replace only the layer stack with the contents of a snapshot. Animation frames use
+    /// this, since they share the document's size and paper color.
+    pub fn restore_layers(&mut self, snapshot: &CanvasSnapshot) {
+        self.layers = snapshot
+            .layers
+            .iter()
+            .map(|saved| {
+                let mut layer = Layer::new(saved.name.clone(), self.width, self.height, self.tile_size);
+                layer.visible = saved.visible;
+                layer.opacity = saved.opacity;
+                layer.locked = saved.locked;
+                layer.paint_behind = saved.paint_behind;
+                layer.adjustment = saved.adjustment.clone();
+                layer.vector = saved.vector.clone();
+                layer.label = saved.label;
+                layer.offset = saved.offset;
+                for (&coord, data) in &saved.tiles {
+                    let is_empty = data.iter().all(|&p| p == Color32::TRANSPARENT);
+                    layer.tiles.insert(coord, TileCell { data: Some(data.clone()), is_empty });
                 }
-            }
+                layer
+            })
+            .collect();
+        self.active_layer_idx = snapshot.active_layer_idx.min(self.layers.len().saturating_sub(1));
+    }
+
+    /// Re-render a floating layer from its captured pixels under the given transform.
+    /// Only the tiles the layer covered before and after are marked dirty.
+    pub fn preview_transform(&mut self, layer_idx: usize, src_tiles: &HashMap<(i32, i32), Vec<Color32>>, affine: Affine, interpolation: Interpolation) {
+        let Affine { offset, rotation, scale, center } = affine;
+        let tile_size = self.tile_size;
+        let ts = tile_size as i32;
+        if src_tiles.is_empty() {
+            return;
+        }
+
+        // 1. Source bounds from the captured tiles (sampling skips transparent pixels anyway)
+        let mut src_bounds = eframe::egui::Rect::NOTHING;
+        for &(tx, ty) in src_tiles.keys() {
+            src_bounds.extend_with(eframe::egui::pos2((tx * ts) as f32, (ty * ts) as f32));
+            src_bounds.extend_with(eframe::egui::pos2(((tx + 1) * ts) as f32, ((ty + 1) * ts) as f32));
         }
-        
-        if src_pixels.is_empty() { return; }
-        // Expand bounds slightly to cover the pixels fully
-        src_bounds.max.x += 1.0;
-        src_bounds.max.y += 1.0;
 
         // 2. Calculate destination bounds
         let corners = [
@@ -644,93 +1414,90 @@ impl Canvas {
             eframe::egui::pos2(rx + center.x + offset.x, ry + center.y + offset.y)
         };
         
-        let t_corners: Vec<eframe::egui::Pos2> = corners.iter().map(|&c| transform(c)).collect();
-        
-        let mut min_x = t_corners[0].x;
-        let mut min_y = t_corners[0].y;
-        let mut max_x = t_corners[0].x;
-        let mut max_y = t_corners[0].y;
-        
-        for c in &t_corners {
-            min_x = min_x.min(c.x);
-            min_y = min_y.min(c.y);
-            max_x = max_x.max(c.x);
-            max_y = max_y.max(c.y);
+        let mut dst_bounds = eframe::egui::Rect::NOTHING;
+        for c in corners {
+            dst_bounds.extend_with(transform(c));
         }
-        
-        let dst_min_x = min_x.floor() as i32;
-        let dst_min_y = min_y.floor() as i32;
-        let dst_max_x = max_x.ceil() as i32;
-        let dst_max_y = max_y.ceil() as i32;
 
-        // 3. Reverse mapping
-        let mut dst_tiles: HashMap<(i32, i32), Vec<Color32>> = HashMap::new();
-        
-        for y in dst_min_y..dst_max_y {
-            for x in dst_min_x..dst_max_x {
-                // Inverse transform
-                let dx = x as f32 - (center.x + offset.x);
-                let dy = y as f32 - (center.y + offset.y);
-                
-                // Inverse Rotate
-                let rx = dx * cos_r + dy * sin_r;
-                let ry = -dx * sin_r + dy * cos_r;
-                
-                // Inverse Scale
-                let sx = rx / scale.x;
-                let sy = ry / scale.y;
-                
-                let src_x = (sx + center.x).round() as i32;
-                let src_y = (sy + center.y).round() as i32;
-                
-                if let Some(pixel) = src_pixels.get(&(src_x, src_y)) {
-                    let ntx = x.div_euclid(tile_size as i32);
-                    let nty = y.div_euclid(tile_size as i32);
-                    
-                    let npx = (x - ntx * tile_size as i32) as usize;
-                    let npy = (y - nty * tile_size as i32) as usize;
+        let dst_min_x = dst_bounds.min.x.floor() as i32;
+        let dst_min_y = dst_bounds.min.y.floor() as i32;
+        let dst_max_x = dst_bounds.max.x.ceil() as i32;
+        let dst_max_y = dst_bounds.max.y.ceil() as i32;
 
-                    let dst_data = dst_tiles.entry((ntx, nty)).or_insert_with(|| vec![Color32::TRANSPARENT; tile_size * tile_size]);
-                    let dst_idx = npy * tile_size + npx;
-                    dst_data[dst_idx] = *pixel;
+        let fetch = |x: i32, y: i32| -> Option<Color32> {
+            let data = src_tiles.get(&(x.div_euclid(ts), y.div_euclid(ts)))?;
+            let idx = y.rem_euclid(ts) as usize * tile_size + x.rem_euclid(ts) as usize;
+            Some(data[idx]).filter(|c| c.a() > 0)
+        };
+
+        // 3. Reverse mapping, one destination tile per task
+        let dst_coords: Vec<(i32, i32)> = (dst_min_y.div_euclid(ts)..=(dst_max_y - 1).div_euclid(ts))
+            .flat_map(|ty| (dst_min_x.div_euclid(ts)..=(dst_max_x - 1).div_euclid(ts)).map(move |tx| (tx, ty)))
+            .collect();
+
+        let dst_tiles: Vec<((i32, i32), Vec<Color32>)> = dst_coords
+            .par_iter()
+            .filter_map(|&(ntx, nty)| {
+                let mut data = vec![Color32::TRANSPARENT; tile_size * tile_size];
+                let mut has_content = false;
+                for npy in 0..tile_size {
+                    let y = nty * ts + npy as i32;
+                    if y < dst_min_y || y >= dst_max_y {
+                        continue;
+                    }
+                    for npx in 0..tile_size {
+                        let x = ntx * ts + npx as i32;
+                        if x < dst_min_x || x >= dst_max_x {
+                            continue;
+                        }
+                        // Inverse transform
+                        let dx = x as f32 - (center.x + offset.x);
+                        let dy = y as f32 - (center.y + offset.y);
+
+                        // Inverse Rotate
+                        let rx = dx * cos_r + dy * sin_r;
+                        let ry = -dx * sin_r + dy * cos_r;
+
+                        // Inverse Scale
+                        let sx = rx / scale.x;
+                        let sy = ry / scale.y;
+
+                        if let Some(pixel) = sample_transformed(fetch, sx + center.x, sy + center.y, interpolation) {
+                            data[npy * tile_size + npx] = pixel;
+                            has_content = true;
+                        }
+                    }
                 }
-            }
-        }
+                has_content.then_some(((ntx, nty), data))
+            })
+            .collect();
 
         // 4. Apply back to layer (Clear first)
+        let mut changed = Vec::new();
         if let Some(layer) = self.layers.get(layer_idx) {
-            let mut tiles = layer.tiles.lock().unwrap();
-            
             // Clear existing tiles
-            for tile_arc in tiles.values() {
+            for (coord, tile_arc) in layer.tiles.entries() {
                 let mut cell = tile_arc.lock().unwrap();
-                cell.data = None;
+                if cell.data.take().is_some() {
+                    changed.push(coord);
+                }
                 cell.is_empty = true;
             }
 
             // Write destination pixels
             for ((tx, ty), data) in dst_tiles {
-                let tile_arc = tiles.entry((tx, ty)).or_insert_with(|| Arc::new(Mutex::new(TileCell { data: Some(vec![Color32::TRANSPARENT; tile_size * tile_size]), is_empty: true })));
+                let tile_arc = layer.tiles.get_or_insert((tx, ty));
                 let mut guard = tile_arc.lock().unwrap();
-                if guard.data.is_none() {
-                    guard.data = Some(vec![Color32::TRANSPARENT; tile_size * tile_size]);
-                }
-                
-                let mut has_content = false;
-                if let Some(target_data) = &mut guard.data {
-                    for i in 0..data.len() {
-                        if data[i].a() > 0 {
-                            target_data[i] = data[i];
-                            has_content = true;
-                        }
-                    }
-                }
-                guard.is_empty = !has_content;
+                guard.data = Some(data);
+                guard.is_empty = false;
+                changed.push((tx, ty));
             }
         }
+        self.layer_tiles_changed(layer_idx, &changed);
     }
 
-    pub fn apply_transform(&mut self, offset: Vec2, rotation: f32, scale: Vec2, center: Vec2, selection: Option<&crate::selection::SelectionManager>, history: Option<&mut UndoAction>) {
+    pub fn apply_transform(&mut self, affine: Affine, interpolation: Interpolation, selection: Option<&crate::selection::SelectionManager>, history: Option<&mut UndoAction>) {
+        let Affine { offset, rotation, scale, center } = affine;
         let layer_idx = self.active_layer_idx;
         let tile_size = self.tile_size;
         
@@ -741,12 +1508,11 @@ impl Canvas {
         let mut first = true;
 
         if let Some(layer) = self.layers.get(layer_idx) {
-            let tiles = layer.tiles.lock().unwrap();
-            for ((tx, ty), tile_arc) in tiles.iter() {
+            for ((tx, ty), tile_arc) in layer.tiles.entries() {
                 let guard = tile_arc.lock().unwrap();
                 if let Some(data) = &guard.data {
-                    let base_x = *tx * tile_size as i32;
-                    let base_y = *ty * tile_size as i32;
+                    let base_x = tx * tile_size as i32;
+                    let base_y = ty * tile_size as i32;
                     
                     for py in 0..tile_size {
                         for px in 0..tile_size {
@@ -843,10 +1609,7 @@ impl Canvas {
                 let sx = rx * inv_scale_x;
                 let sy = ry * inv_scale_y;
                 
-                let src_x = (sx + center.x).round() as i32;
-                let src_y = (sy + center.y).round() as i32;
-                
-                if let Some(pixel) = src_pixels.get(&(src_x, src_y)) {
+                if let Some(pixel) = sample_transformed(|x, y| src_pixels.get(&(x, y)).copied(), sx + center.x, sy + center.y, interpolation) {
                     let ntx = x.div_euclid(tile_size_i32);
                     let nty = y.div_euclid(tile_size_i32);
                     
@@ -855,14 +1618,14 @@ impl Canvas {
 
                     let dst_data = dst_tiles.entry((ntx, nty)).or_insert_with(|| vec![Color32::TRANSPARENT; tile_size * tile_size]);
                     let dst_idx = npy * tile_size + npx;
-                    dst_data[dst_idx] = *pixel;
+                    dst_data[dst_idx] = pixel;
                 }
             }
         }
 
         // 4. Apply back to layer
         if let Some(layer) = self.layers.get(layer_idx) {
-            let mut tiles = layer.tiles.lock().unwrap();
+            let tiles = &layer.tiles;
             
             // Record history
             if let Some(action) = history {
@@ -881,7 +1644,7 @@ impl Canvas {
                 }
                 
                 for (tx, ty) in affected_tiles {
-                    let data = if let Some(tile_arc) = tiles.get(&(tx, ty)) {
+                    let data = if let Some(tile_arc) = tiles.get((tx, ty)) {
                         let guard = tile_arc.lock().unwrap();
                         guard.data.clone().unwrap_or_else(|| vec![Color32::TRANSPARENT; tile_size * tile_size])
                     } else {
@@ -912,7 +1675,7 @@ impl Canvas {
             }
             
             for ((tx, ty), pixel_coords) in clear_ops {
-                if let Some(tile_arc) = tiles.get(&(tx, ty)) {
+                if let Some(tile_arc) = tiles.get((tx, ty)) {
                     let mut guard = tile_arc.lock().unwrap();
                     if let Some(data) = &mut guard.data {
                         for (px, py) in pixel_coords {
@@ -925,7 +1688,7 @@ impl Canvas {
 
             // Write destination pixels
             for ((tx, ty), data) in dst_tiles {
-                let tile_arc = tiles.entry((tx, ty)).or_insert_with(|| Arc::new(Mutex::new(TileCell { data: Some(vec![Color32::TRANSPARENT; tile_size * tile_size]), is_empty: true })));
+                let tile_arc = tiles.get_or_insert_with((tx, ty), || TileCell { data: Some(vec![Color32::TRANSPARENT; tile_size * tile_size]), is_empty: true });
                 let mut guard = tile_arc.lock().unwrap();
                 if guard.data.is_none() {
                     guard.data = Some(vec![Color32::TRANSPARENT; tile_size * tile_size]);
@@ -943,6 +1706,7 @@ impl Canvas {
                 guard.is_empty = !has_content;
             }
         }
+        self.mark_all_dirty();
     }
 
     pub fn get_content_bounds(&self, layer_idx: usize, selection: Option<&crate::selection::SelectionManager>) -> Option<eframe::egui::Rect> {
@@ -953,19 +1717,18 @@ impl Canvas {
         let mut found = false;
 
         if let Some(layer) = self.layers.get(layer_idx) {
-            let tiles = layer.tiles.lock().unwrap();
-            for ((tx, ty), tile_arc) in tiles.iter() {
+            for ((tx, ty), tile_arc) in layer.tiles.entries() {
                 let guard = tile_arc.lock().unwrap();
                 if let Some(data) = &guard.data {
                     for py in 0..self.tile_size {
                         for px in 0..self.tile_size {
                             let idx = py * self.tile_size + px;
                             if data[idx].a() > 0 {
-                                let gx = *tx * self.tile_size as i32 + px as i32;
-                                let gy = *ty * self.tile_size as i32 + py as i32;
+                                let gx = tx * self.tile_size as i32 + px as i32;
+                                let gy = ty * self.tile_size as i32 + py as i32;
 
                                 if let Some(sel) = selection {
-                                    if !sel.contains(Vec2::new(gx as f32, gy as f32)) {
+                                    if sel.coverage(Vec2::new(gx as f32 + 0.5, gy as f32 + 0.5)) <= 0.0 {
                                         continue;
                                     }
                                 }
@@ -992,6 +1755,104 @@ impl Canvas {
         }
     }
 
+    /// Bounds of the paint on every visible raster layer above the background, or `None`
+    /// when they hold nothing on the canvas.
+    ///
+    /// Tiles flagged empty are skipped, and tile columns and rows are only read inward from
+    /// each side until one holds a painted pixel, so most tiles are never scanned.
+    pub fn visible_content_bounds(&self) -> Option<eframe::egui::Rect> {
+        let mut cells: HashMap<(i32, i32), Vec<TileRef>> = HashMap::new();
+        for layer in self.layers.iter().skip(1).filter(|l| l.visible && l.adjustment.is_none()) {
+            for ((tx, ty), cell) in layer.tiles.entries() {
+                let on_canvas = tx >= 0 && ty >= 0 && (tx as usize) < self.tiles_x && (ty as usize) < self.tiles_y;
+                if on_canvas && !cell.lock().unwrap().is_empty {
+                    cells.entry((tx, ty)).or_default().push(cell);
+                }
+            }
+        }
+
+        let mut by_column: Vec<(i32, i32)> = cells.keys().copied().collect();
+        by_column.sort_unstable();
+        let mut by_row = by_column.clone();
+        by_row.sort_unstable_by_key(|&(tx, ty)| (ty, tx));
+        let columns: Vec<&[(i32, i32)]> = by_column.chunk_by(|a, b| a.0 == b.0).collect();
+        let rows: Vec<&[(i32, i32)]> = by_row.chunk_by(|a, b| a.1 == b.1).collect();
+
+        let mut extents = HashMap::new();
+        let mut first_painted = |groups: &mut dyn Iterator<Item = &&[(i32, i32)]>| -> Option<[i32; 4]> {
+            for group in groups {
+                let painted = group
+                    .iter()
+                    .filter_map(|&coord| *extents.entry(coord).or_insert_with(|| self.painted_extent(coord, &cells[&coord])))
+                    .reduce(union_extent);
+                if painted.is_some() {
+                    return painted;
+                }
+            }
+            None
+        };
+        let left = first_painted(&mut columns.iter())?[0];
+        let right = first_painted(&mut columns.iter().rev())?[2];
+        let top = first_painted(&mut rows.iter())?[1];
+        let bottom = first_painted(&mut rows.iter().rev())?[3];
+        Some(eframe::egui::Rect::from_min_max(
+            eframe::egui::pos2(left as f32, top as f32),
+            eframe::egui::pos2(right as f32, bottom as f32),
+        ))
+    }
+
+    /// Canvas pixel bounds `[min_x, min_y, max_x, max_y)` of the painted pixels in tile
+    /// `(tx, ty)` of the given layers.
+    fn painted_extent(&self, (tx, ty): (i32, i32), cells: &[TileRef]) -> Option<[i32; 4]> {
+        let ts = self.tile_size;
+        let (ox, oy) = (tx as usize * ts, ty as usize * ts);
+        let (w, h) = (ts.min(self.width - ox), ts.min(self.height - oy));
+        let mut extent: Option<[i32; 4]> = None;
+        for cell in cells {
+            let guard = cell.lock().unwrap();
+            let Some(data) = &guard.data else {
+                continue;
+            };
+            for y in 0..h {
+                let row = &data[y * ts..y * ts + w];
+                let Some(x0) = row.iter().position(|p| p.a() > 0) else {
+                    continue;
+                };
+                let x1 = row.iter().rposition(|p| p.a() > 0).unwrap_or(x0) + 1;
+                let found = [(ox + x0) as i32, (oy + y) as i32, (ox + x1) as i32, (oy + y + 1) as i32];
+                extent = Some(extent.map_or(found, |e| union_extent(e, found)));
+            }
+        }
+        extent
+    }
+
+    /// Resize the canvas to `width` x `height`, moving the pixel at (`x`, `y`) to the origin.
+    /// Pixels that end up off the canvas stay in their layers, so cropping back by the
+    /// opposite offset to the old size restores the document exactly.
+    pub fn crop(&mut self, x: i32, y: i32, width: usize, height: usize) {
+        let delta = Vec2::new(-x as f32, -y as f32);
+        for idx in 0..self.layers.len() {
+            // Background tiles without a buffer show the paper color
+            let fill = if idx == 0 { self.clear_color } else { Color32::TRANSPARENT };
+            let pixels = shift_tiles(&self.capture_layer_pixels(idx), -x, -y, self.tile_size, fill);
+            let layer = &mut self.layers[idx];
+            layer.tiles = Arc::default();
+            for (coord, data) in pixels {
+                let mut cell = TileCell { data: Some(data), is_empty: false };
+                cell.refresh_empty(idx != 0);
+                layer.tiles.insert(coord, cell);
+            }
+            if let Some(vector) = layer.vector.as_mut() {
+                vector.strokes.iter_mut().for_each(|stroke| stroke.translate(delta));
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.tiles_x = width.div_ceil(self.tile_size);
+        self.tiles_y = height.div_ceil(self.tile_size);
+        self.invalidate_composite();
+    }
+
     /// Merge the specified layer down into the layer below it.
     /// This combines their tile data according to the visible pixels and opacity.
     /// The upper layer (source) is removed after the merge.
@@ -1001,62 +1862,157 @@ impl Canvas {
         }
 
         let active_idx = self.active_layer_idx;
-        if active_idx >= self.layers.len() {
+        // Vector layers would redraw the lifted pixels from their paths
+        if self.layers.get(active_idx).is_none_or(|l| l.vector.is_some()) {
             return None;
         }
 
         // Create new layer
         let new_layer = Layer::new("Floating Selection".to_string(), self.width, self.height, self.tile_size);
         
-        let active_layer = &self.layers[active_idx];
-        let active_tiles_map = active_layer.tiles.lock().unwrap();
-        
-        let mut tiles_to_process = Vec::new();
-        for (&(tx, ty), tile_arc) in active_tiles_map.iter() {
-            tiles_to_process.push(((tx, ty), tile_arc.clone()));
-        }
-        drop(active_tiles_map);
-        
-        let mut new_layer_tiles = new_layer.tiles.lock().unwrap();
+        let tiles_to_process = self.layers[active_idx].tiles.entries();
         
         for ((tx, ty), tile_arc) in tiles_to_process {
             let mut tile = tile_arc.lock().unwrap();
             if let Some(data) = &mut tile.data {
-                let mut new_tile_data = vec![Color32::TRANSPARENT; self.tile_size * self.tile_size];
-                let mut has_content = false;
-
-                for y in 0..self.tile_size {
-                    for x in 0..self.tile_size {
-                        let px = tx * (self.tile_size as i32) + (x as i32);
-                        let py = ty * (self.tile_size as i32) + (y as i32);
-                        
-                        if selection.contains(Vec2::new(px as f32, py as f32)) {
-                            let idx = y * self.tile_size + x;
-                            let color = data[idx];
-                            if color != Color32::TRANSPARENT {
-                                new_tile_data[idx] = color;
-                                data[idx] = Color32::TRANSPARENT;
-                                has_content = true;
-                            }
+                // Partially covered edge pixels are split between the two layers; what stays
+                // behind is the original minus the lifted part, so the two add up to it again
+                // in [`Canvas::put_back_lifted`]
+                let mask = selection_mask(selection, tx, ty, self.tile_size);
+                let original = data.clone();
+                let mut lifted = vec![Color32::TRANSPARENT; self.tile_size * self.tile_size];
+                if apply_masked(&mut lifted, Some(&mask), |idx, _| original[idx]) {
+                    for (px, &part) in data.iter_mut().zip(&lifted) {
+                        if part.a() > 0 {
+                            *px = rgba_to_color32_fast(linear_difference(Rgba::from(*px), Rgba::from(part)));
                         }
                     }
-                }
-                
-                if has_content {
-                    let new_tile = Arc::new(Mutex::new(TileCell { data: Some(new_tile_data), is_empty: false }));
-                    new_layer_tiles.insert((tx, ty), new_tile);
+                    new_layer.tiles.insert((tx, ty), TileCell { data: Some(lifted), is_empty: false });
                 }
             }
         }
         
-        drop(new_layer_tiles);
-        
-        self.layers.push(new_layer);
-        self.active_layer_idx = self.layers.len() - 1;
+        // Insert directly above the source so merging down lands back on it
+        self.layers.insert(active_idx + 1, new_layer);
+        self.active_layer_idx = active_idx + 1;
         
         Some(self.active_layer_idx)
     }
 
+    /// Merge a layer lifted by [`Canvas::float_selection`] back into the layer below it without
+    /// moving it, adding the two in linear light. Edge pixels were split into two parts that
+    /// sum to the original, which compositing one over the other wouldn't give back.
+    pub fn put_back_lifted(&mut self, layer_idx: usize) {
+        if layer_idx == 0 || layer_idx >= self.layers.len() {
+            return;
+        }
+        let lifted = self.layers.remove(layer_idx);
+        let below = &self.layers[layer_idx - 1];
+        let mut changed = Vec::new();
+        for (coord, tile_arc) in lifted.tiles.entries() {
+            let guard = tile_arc.lock().unwrap();
+            let Some(parts) = guard.data.as_ref().filter(|_| !guard.is_empty) else {
+                continue;
+            };
+            let target = below.tiles.get_or_insert(coord);
+            let mut target = target.lock().unwrap();
+            let data = target.data.get_or_insert_with(|| vec![Color32::TRANSPARENT; self.tile_size * self.tile_size]);
+            for (px, &part) in data.iter_mut().zip(parts) {
+                if part.a() > 0 {
+                    *px = rgba_to_color32_fast(Rgba::from(*px) + Rgba::from(part));
+                }
+            }
+            target.refresh_empty(layer_idx - 1 != 0);
+            changed.push(coord);
+        }
+        self.active_layer_idx = self.active_layer_idx.min(self.layers.len() - 1);
+        self.invalidate_composite_tiles(&changed);
+    }
+
+    /// Lift every pixel of the active layer into a floating layer directly above it.
+    pub fn float_layer(&mut self) -> Option<usize> {
+        let active_idx = self.active_layer_idx;
+        let layer = self.layers.get(active_idx)?;
+        if layer.locked || layer.adjustment.is_some() || layer.vector.is_some() {
+            return None;
+        }
+
+        if layer.tiles.is_empty() {
+            return None;
+        }
+
+        let mut new_layer = Layer::new("Floating Layer".to_string(), self.width, self.height, self.tile_size);
+        new_layer.tiles = std::mem::take(&mut self.layers[active_idx].tiles);
+
+        self.layers.insert(active_idx + 1, new_layer);
+        self.active_layer_idx = active_idx + 1;
+
+        Some(self.active_layer_idx)
+    }
+
+    /// Place an image in a new floating layer above the active one, top-left corner at `origin`.
+    /// Pixels falling outside the canvas are dropped.
+    pub fn float_image(&mut self, image: &ColorImage, origin: (i32, i32)) -> Option<usize> {
+        if self.layers.is_empty() {
+            return None;
+        }
+        let new_layer = self.image_layer("Pasted Image".to_string(), image, origin)?;
+
+        let idx = (self.active_layer_idx + 1).min(self.layers.len());
+        self.layers.insert(idx, new_layer);
+        self.active_layer_idx = idx;
+
+        Some(idx)
+    }
+
+    /// Replace the pixels of a layer with `image` placed at the top-left corner, keeping its name.
+    /// Used when opening an image file as a document.
+    pub fn load_image_into_layer(&mut self, layer_idx: usize, image: &ColorImage) {
+        let Some(name) = self.layers.get(layer_idx).map(|l| l.name.clone()) else {
+            return;
+        };
+        let layer = self
+            .image_layer(name.clone(), image, (0, 0))
+            .unwrap_or_else(|| Layer::new(name, self.width, self.height, self.tile_size));
+        self.layers[layer_idx] = layer;
+    }
+
+    /// A new layer holding the non-transparent pixels of `image` at `origin`,
+    /// or `None` when nothing lands on the canvas.
+    fn image_layer(&self, name: String, image: &ColorImage, origin: (i32, i32)) -> Option<Layer> {
+        let [width, height] = image.size;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let ts = self.tile_size as i32;
+        let mut tiles: HashMap<(i32, i32), Vec<Color32>> = HashMap::new();
+        for (y, row) in image.pixels.chunks_exact(width).enumerate() {
+            let py = origin.1 + y as i32;
+            if py < 0 || py >= self.height as i32 {
+                continue;
+            }
+            for (x, &color) in row.iter().enumerate() {
+                let px = origin.0 + x as i32;
+                if px < 0 || px >= self.width as i32 || color == Color32::TRANSPARENT {
+                    continue;
+                }
+                let data = tiles
+                    .entry((px / ts, py / ts))
+                    .or_insert_with(|| vec![Color32::TRANSPARENT; self.tile_size * self.tile_size]);
+                data[((py % ts) * ts + px % ts) as usize] = color;
+            }
+        }
+        if tiles.is_empty() {
+            return None;
+        }
+        let new_layer = Layer::new(name, self.width, self.height, self.tile_size);
+        for (coord, data) in tiles {
+            new_layer.tiles.insert(coord, TileCell { data: Some(data), is_empty: false });
+        }
+        Some(new_layer)
+    }
+
     pub fn merge_layer_down(&mut self, layer_idx: usize) {
         if layer_idx == 0 || layer_idx >= self.layers.len() {
             return;
@@ -1064,16 +2020,21 @@ impl Canvas {
 
         // Remove the top layer (source)
         let top_layer = self.layers.remove(layer_idx);
-        
-        {
+        // Merged pixels aren't paths, so a vector layer below turns into a pixel layer
+        self.layers[layer_idx - 1].vector = None;
+        // The merged layer shows at full opacity, so bake the bottom layer's own opacity in first
+        self.fold_opacity(layer_idx - 1);
+
+        if !top_layer.visible {
+            // A hidden layer contributed nothing to the image; merging just drops it
+        } else if top_layer.adjustment.is_some() {
+            self.bake_adjustment(&top_layer, layer_idx - 1);
+        } else {
             // Get the bottom layer (destination)
             // Note: indices shifted after remove, so the layer that was at layer_idx - 1 is still at layer_idx - 1
             let bottom_layer = &mut self.layers[layer_idx - 1];
 
-            let top_tiles = top_layer.tiles.lock().unwrap();
-            let mut bottom_tiles = bottom_layer.tiles.lock().unwrap();
-
-            for ((tx, ty), top_tile_arc) in top_tiles.iter() {
+            for ((tx, ty), top_tile_arc) in top_layer.tiles.entries() {
                 let top_guard = top_tile_arc.lock().unwrap();
                 if let Some(top_data) = &top_guard.data {
                     // Skip empty top tiles
@@ -1082,38 +2043,28 @@ impl Canvas {
                     }
                     
                     // Ensure bottom tile exists
-                    let bottom_tile_arc = bottom_tiles
-                        .entry((*tx, *ty))
-                        .or_insert_with(|| Arc::new(Mutex::new(TileCell { data: None, is_empty: true })));
+                    let bottom_tile_arc = bottom_layer.tiles.get_or_insert((tx, ty));
                     
                     let mut bottom_guard = bottom_tile_arc.lock().unwrap();
                     
-                    // Initialize bottom data if missing
+                    // Initialize bottom data if missing; a missing background tile shows the paper color
                     if bottom_guard.data.is_none() {
-                         bottom_guard.data = Some(vec![Color32::TRANSPARENT; self.tile_size * self.tile_size]);
+                        let fill = if layer_idx - 1 == 0 { self.clear_color } else { Color32::TRANSPARENT };
+                        bottom_guard.data = Some(vec![fill; self.tile_size * self.tile_size]);
                     }
 
                     if let Some(bottom_data) = &mut bottom_guard.data {
-                        // Use SIMD batch processing for better performance
-                        let tile_len = bottom_data.len();
-                        
-                        // Apply opacity to source pixels and prepare for batch blend
-                        let mut src_with_opacity = vec![Color32::TRANSPARENT; tile_len];
-                        for i in 0..tile_len {
-                            src_with_opacity[i] = apply_opacity_scale(top_data[i], top_layer.opacity);
+                        // Blend in linear space exactly as the display does; pixels the top
+                        // layer leaves uncovered are kept bit for bit
+                        for (dst, &src) in bottom_data.iter_mut().zip(top_data.iter()) {
+                            if src.a() == 0 {
+                                continue;
+                            }
+                            *dst = rgba_to_color32_fast(top_layer.blend_over(Rgba::from(src), Rgba::from(*dst)));
                         }
-                        
-                        // Create temporary output buffer
-                        let mut blended = vec![Color32::TRANSPARENT; tile_len];
-                        
-                        // Batch blend using SIMD
-                        alpha_over_batch(&src_with_opacity, bottom_data, &mut blended);
-                        
-                        // Copy result back
-                        *bottom_data = blended;
-                        
+
                         // Update is_empty flag
-                        bottom_guard.is_empty = bottom_data.iter().all(|&p| p == Color32::TRANSPARENT);
+                        bottom_guard.refresh_empty(layer_idx - 1 != 0);
                     }
                 }
             }
@@ -1124,6 +2075,209 @@ impl Canvas {
             self.active_layer_idx = self.layers.len() - 1;
         }
     }
+
+    /// Scale a layer's pixels by its opacity and reset the opacity to 1, leaving the
+    /// composite unchanged. For the background the paper color is scaled too, since its
+    /// unpainted tiles show it.
+    fn fold_opacity(&mut self, idx: usize) {
+        let Some(layer) = self.layers.get_mut(idx) else {
+            return;
+        };
+        if layer.opacity >= 1.0 {
+            return;
+        }
+        let opacity = layer.opacity;
+        layer.opacity = 1.0;
+        if idx == 0 {
+            self.clear_color = rgba_to_color32_fast(Rgba::from(self.clear_color) * opacity);
+        }
+        for (_, tile_arc) in layer.tiles.entries() {
+            let mut guard = tile_arc.lock().unwrap();
+            if let Some(data) = guard.data.as_mut() {
+                for px in data.iter_mut() {
+                    *px = rgba_to_color32_fast(Rgba::from(*px) * opacity);
+                }
+            }
+            guard.refresh_empty(idx != 0);
+        }
+    }
+
+    /// Destructively apply an adjustment layer's filter to the pixels of the target layer.
+    fn bake_adjustment(&self, adjustment_layer: &Layer, target_idx: usize) {
+        let Some(adj) = &adjustment_layer.adjustment else {
+            return;
+        };
+        let Some(target) = self.layers.get(target_idx) else {
+            return;
+        };
+
+        for (coord, tile_arc) in target.tiles.entries() {
+            let mask_arc = adjustment_layer.tiles.get(coord);
+            let mask_guard = mask_arc.as_ref().map(|arc| arc.lock().unwrap());
+            let mask_data = mask_guard.as_ref().and_then(|g| g.data.as_ref());
+
+            let mut guard = tile_arc.lock().unwrap();
+            if let Some(data) = guard.data.as_mut() {
+                for (i, px) in data.iter_mut().enumerate() {
+                    let mask = mask_data.map(|m| Rgba::from(m[i]).a());
+                    let amount = adjustment_layer.adjustment_amount(mask);
+                    *px = rgba_to_color32_fast(adj.apply(Rgba::from(*px), amount));
+                }
+            }
+        }
+    }
+}
+
+/// The body of [`Canvas::page_out_cold_tiles`]. Tiles a stroke or the display holds at the
+/// time are skipped by [`TileStore::page_out`], so this is safe to run alongside them.
+fn page_out_stores(stores: &[Arc<TileStore>], tile_bytes: usize, budget_bytes: usize) -> usize {
+    let _timer = ScopeTimer::new("paging");
+    let epoch = tile_pager::epoch();
+    let mut resident: Vec<(usize, (i32, i32), u64)> = stores
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, tiles)| tiles.resident().into_iter().map(move |(coord, used)| (idx, coord, used)))
+        .collect();
+    let mut excess = (resident.len() * tile_bytes).saturating_sub(budget_bytes).div_ceil(tile_bytes);
+    let mut paged = 0;
+    if excess > 0 {
+        resident.sort_unstable_by_key(|&(_, _, used)| used);
+        for (idx, coord, _) in resident.into_iter().filter(|&(_, _, used)| used < epoch) {
+            if excess == 0 {
+                break;
+            }
+            if stores[idx].page_out(coord) {
+                excess -= 1;
+                paged += 1;
+            }
+        }
+    }
+    tile_pager::advance_epoch();
+    paged
+}
+
+/// Union of two `[min_x, min_y, max_x, max_y)` extents.
+fn union_extent(a: [i32; 4], b: [i32; 4]) -> [i32; 4] {
+    [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]
+}
+
+/// Move whole-tile pixel buffers by (`dx`, `dy`) pixels onto the tile grid. Parts of a
+/// destination tile that no source tile covers are `fill`.
+pub(crate) fn shift_tiles(tiles: &HashMap<(i32, i32), Vec<Color32>>, dx: i32, dy: i32, tile_size: usize, fill: Color32) -> HashMap<(i32, i32), Vec<Color32>> {
+    let ts = tile_size as i32;
+    let mut shifted: HashMap<(i32, i32), Vec<Color32>> = HashMap::new();
+    for (&(tx, ty), data) in tiles {
+        let (gx, gy) = (tx * ts + dx, ty * ts + dy);
+        for dty in gy.div_euclid(ts)..=(gy + ts - 1).div_euclid(ts) {
+            for dtx in gx.div_euclid(ts)..=(gx + ts - 1).div_euclid(ts) {
+                let dst = shifted.entry((dtx, dty)).or_insert_with(|| vec![fill; tile_size * tile_size]);
+                // Overlap with the destination tile, in its own pixels
+                let (x0, x1) = ((gx - dtx * ts).max(0), (gx + ts - dtx * ts).min(ts));
+                let (y0, y1) = ((gy - dty * ts).max(0), (gy + ts - dty * ts).min(ts));
+                let len = (x1 - x0) as usize;
+                for y in y0..y1 {
+                    let src = ((y + dty * ts - gy) * ts + x0 + dtx * ts - gx) as usize;
+                    let dst_start = (y * ts + x0) as usize;
+                    dst[dst_start..dst_start + len].copy_from_slice(&data[src..src + len]);
+                }
+            }
+        }
+    }
+    shifted
+}
+
+/// Look up a source pixel at a fractional position for the transform reverse mapping.
+fn sample_transformed(
+    fetch: impl Fn(i32, i32) -> Option<Color32>,
+    x: f32,
+    y: f32,
+    interpolation: Interpolation,
+) -> Option<Color32> {
+    match interpolation {
+        Interpolation::Nearest => fetch(x.round() as i32, y.round() as i32),
+        Interpolation::Bilinear => {
+            let x0 = x.floor();
+            let y0 = y.floor();
+            let fx = x - x0;
+            let fy = y - y0;
+            let (x0, y0) = (x0 as i32, y0 as i32);
+
+            let fetch = |px: i32, py: i32| {
+                fetch(px, py).map(Rgba::from).unwrap_or(Rgba::TRANSPARENT)
+            };
+            let top = fetch(x0, y0) * (1.0 - fx) + fetch(x0 + 1, y0) * fx;
+            let bottom = fetch(x0, y0 + 1) * (1.0 - fx) + fetch(x0 + 1, y0 + 1) * fx;
+            let blended = top * (1.0 - fy) + bottom * fy;
+            if blended.a() <= 0.0 {
+                None
+            } else {
+                Some(rgba_to_color32_fast(blended))
+            }
+        }
+    }
+}
+
+/// Per-pixel coverage of a selection over tile `(tx, ty)`, for [`apply_masked`].
+pub(crate) fn selection_mask(selection: &SelectionManager, tx: i32, ty: i32, tile_size: usize) -> Vec<f32> {
+    (0..tile_size * tile_size)
+        .map(|idx| {
+            let px = tx * tile_size as i32 + (idx % tile_size) as i32;
+            let py = ty * tile_size as i32 + (idx / tile_size) as i32;
+            selection.coverage(Vec2::new(px as f32 + 0.5, py as f32 + 0.5))
+        })
+        .collect()
+}
+
+/// Replace each pixel of a tile with `f(index, pixel)`, blended over the original by the
+/// pixel's `mask` coverage so feathered selection edges fade between the two. The blend is
+/// done in linear light, so a half-covered edge keeps its brightness. Pixels the mask leaves
+/// out are not passed to `f`; no mask covers the whole tile. Returns whether any pixel changed.
+pub(crate) fn apply_masked(tile: &mut [Color32], mask: Option<&[f32]>, mut f: impl FnMut(usize, Color32) -> Color32) -> bool {
+    let mut changed = false;
+    for (idx, px) in tile.iter_mut().enumerate() {
+        let coverage = mask.map_or(1.0, |mask| mask[idx]);
+        if coverage <= 0.0 {
+            continue;
+        }
+        let old = *px;
+        let processed = f(idx, old);
+        let new = if coverage >= 1.0 {
+            processed
+        } else {
+            rgba_to_color32_fast(Rgba::from(old) * (1.0 - coverage) + Rgba::from(processed) * coverage)
+        };
+        if new != old {
+            *px = new;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// `a - b` for premultiplied linear colors, where `b` is a part of `a`.
+fn linear_difference(a: Rgba, b: Rgba) -> Rgba {
+    Rgba::from_rgba_premultiplied(
+        (a.r() - b.r()).max(0.0),
+        (a.g() - b.g()).max(0.0),
+        (a.b() - b.b()).max(0.0),
+        (a.a() - b.a()).max(0.0),
+    )
+}
+
+/// Whether `color` is a valid premultiplied pixel. Layer pixels are premultiplied in linear
+/// light and stored gamma encoded (egui's convention), so a channel may read higher than the
+/// alpha byte, but never brighter than the alpha allows once decoded. One step covers rounding.
+pub fn is_premultiplied(color: Color32) -> bool {
+    let [r, g, b, a] = color.to_array();
+    let max = ecolor::gamma_u8_from_linear_f32(a as f32 / 255.0).saturating_add(1);
+    r <= max && g <= max && b <= max
+}
+
+/// Panic in debug builds when a pixel handed to or produced by a blend is not premultiplied.
+/// Straight colors reaching the canvas otherwise only show up later as halos around soft edges.
+#[inline]
+pub fn debug_assert_premultiplied(color: Color32, what: &str) {
+    debug_assert!(is_premultiplied(color), "{what} pixel is not premultiplied: {color:?}");
 }
 
 /// Erase blend mode: reduce destination alpha by the source alpha.
@@ -1142,6 +2296,84 @@ pub fn blend_erase(src: Color32, dst: Color32) -> Color32 {
     )
 }
 
+/// Soften blend mode: erase like `blend_erase`, but never take the alpha below `floor` (0..255).
+pub fn blend_soften(src: Color32, dst: Color32, floor: f32) -> Color32 {
+    debug_assert_premultiplied(dst, "soften destination");
+    let erased = blend_erase(src, dst);
+    if erased.a() as f32 >= floor {
+        return erased;
+    }
+    if dst.a() as f32 <= floor {
+        return dst;
+    }
+    // Scale the premultiplied pixel down to the floor instead
+    dst.gamma_multiply(floor / dst.a() as f32)
+}
+
+/// Multiply blend mode: darken the destination by the source color.
+pub fn blend_multiply(src: Color32, dst: Color32) -> Color32 {
+    blend_composite(src, dst, |cb, cs| [cb[0] * cs[0], cb[1] * cs[1], cb[2] * cs[2]])
+}
+
+/// Add (linear dodge) blend mode: sum the linear colors, clipped to white.
+pub fn blend_add(src: Color32, dst: Color32) -> Color32 {
+    blend_composite(src, dst, |cb, cs| {
+        [(cb[0] + cs[0]).min(1.0), (cb[1] + cs[1]).min(1.0), (cb[2] + cs[2]).min(1.0)]
+    })
+}
+
+/// Color blend mode: hue and saturation of the source with the luminosity of the destination.
+pub fn blend_color(src: Color32, dst: Color32) -> Color32 {
+    blend_composite(src, dst, |cb, cs| set_luminosity(cs, luminosity(cb)))
+}
+
+/// Behind blend mode: paint under the existing pixels, only showing through transparency.
+pub fn blend_behind(src: Color32, dst: Color32) -> Color32 {
+    alpha_over(dst, src)
+}
+
+/// Composite `src` over `dst` where the overlap takes the colour from `blend(backdrop, source)`.
+/// Both colours are passed to `blend` unpremultiplied, in linear space.
+#[inline]
+fn blend_composite(src: Color32, dst: Color32, blend: impl Fn([f32; 3], [f32; 3]) -> [f32; 3]) -> Color32 {
+    let s = Rgba::from(src).to_array();
+    let d = Rgba::from(dst).to_array();
+    let (sa, da) = (s[3], d[3]);
+    if sa <= 0.0 {
+        return dst;
+    }
+    if da <= 0.0 {
+        return src;
+    }
+
+    let cs = [s[0] / sa, s[1] / sa, s[2] / sa];
+    let cb = [d[0] / da, d[1] / da, d[2] / da];
+    let b = blend(cb, cs);
+    let mix = |i: usize| s[i] * (1.0 - da) + d[i] * (1.0 - sa) + sa * da * b[i];
+    rgba_to_color32_fast(Rgba::from_rgba_premultiplied(mix(0), mix(1), mix(2), sa + da * (1.0 - sa)))
+}
+
+#[inline]
+fn luminosity(c: [f32; 3]) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+/// Shift `c` to luminosity `l`, pulling out-of-range channels back toward gray.
+fn set_luminosity(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - luminosity(c);
+    let c = [c[0] + d, c[1] + d, c[2] + d];
+    let l = luminosity(c);
+    let min = c[0].min(c[1]).min(c[2]);
+    let max = c[0].max(c[1]).max(c[2]);
+    let scale = if min < 0.0 {
+        l / (l - min)
+    } else if max > 1.0 {
+        (1.0 - l) / (max - l)
+    } else {
+        1.0
+    };
+    c.map(|v| (l + (v - l) * scale).clamp(0.0, 1.0))
+}
 
 /// SIMD-optimized alpha blending for 4 pixels at once
 #[inline]
@@ -1218,6 +2450,13 @@ pub fn alpha_over_batch(src: &[Color32], dst: &[Color32], out: &mut [Color32]) {
         ];
         
         let result = alpha_over_simd_x4(src_chunk, dst_chunk);
+        if cfg!(debug_assertions) {
+            for j in 0..4 {
+                debug_assert_premultiplied(src_chunk[j], "source");
+                debug_assert_premultiplied(dst_chunk[j], "destination");
+                debug_assert_premultiplied(result[j], "blended");
+            }
+        }
         
         out[i] = result[0];
         out[i + 1] = result[1];
@@ -1229,6 +2468,8 @@ pub fn alpha_over_batch(src: &[Color32], dst: &[Color32], out: &mut [Color32]) {
     
     // Handle remaining pixels with scalar code
     for i in simd_len..len {
+        debug_assert_premultiplied(src[i], "source");
+        debug_assert_premultiplied(dst[i], "destination");
         out[i] = alpha_over(src[i], dst[i]);
     }
 }
@@ -1252,20 +2493,6 @@ pub fn alpha_over(src: Color32, dst: Color32) -> Color32 {
     rgba_to_color32_fast(Rgba::from_rgba_premultiplied(out_r, out_g, out_b, out_a))
 }
 
-#[inline]
-fn apply_opacity_scale(color: Color32, opacity_scale: f32) -> Color32 {
-    if opacity_scale >= 1.0 {
-        return color;
-    }
-    if opacity_scale <= 0.0 {
-        return Color32::TRANSPARENT;
-    }
-
-    // Convert to linear, multiply all channels (RGBA) by scale, convert back
-    let linear = Rgba::from(color) * opacity_scale;
-    Color32::from(linear)
-}
-
 fn premultiply(color: Color32) -> Color32 {
     let [r, g, b, a] = color.to_array();
     let linear = Rgba::from_rgba_unmultiplied(
@@ -1293,3 +2520,135 @@ fn unpremultiply(color: Color32) -> Color32 {
     // Convert back to sRGB u8
     Color32::from(Rgba::from_rgba_premultiplied(r, g, b, a))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color32 = Color32::from_rgba_premultiplied(200, 0, 0, 200);
+
+    #[test]
+    fn apply_masked_without_mask_replaces_every_pixel() {
+        let mut tile = vec![Color32::TRANSPARENT; 4];
+        assert!(apply_masked(&mut tile, None, |_, _| RED));
+        assert!(tile.iter().all(|&px| px == RED));
+    }
+
+    #[test]
+    fn apply_masked_skips_uncovered_pixels() {
+        let mut tile = vec![Color32::TRANSPARENT; 4];
+        let mask = [0.0, 1.0, 0.0, 1.0];
+        let mut seen = Vec::new();
+        apply_masked(&mut tile, Some(&mask), |idx, _| {
+            seen.push(idx);
+            RED
+        });
+        assert_eq!(seen, [1, 3]);
+        assert_eq!(tile, [Color32::TRANSPARENT, RED, Color32::TRANSPARENT, RED]);
+    }
+
+    #[test]
+    fn apply_masked_blends_partial_coverage() {
+        let mut tile = vec![Color32::TRANSPARENT];
+        apply_masked(&mut tile, Some(&[0.5]), |_, _| RED);
+        let [r, g, b, a] = tile[0].to_srgba_unmultiplied();
+        assert_eq!((g, b), (0, 0));
+        assert!((a as i32 - 100).abs() <= 1, "alpha {a}");
+        // Same color as the source, only less opaque
+        assert!((r as i32 - RED.to_srgba_unmultiplied()[0] as i32).abs() <= 2, "red {r}");
+    }
+
+    #[test]
+    fn apply_masked_half_clear_keeps_hue_and_brightness() {
+        let mut tile = vec![Color32::RED];
+        apply_masked(&mut tile, Some(&[0.5]), |_, _| Color32::TRANSPARENT);
+        let [r, g, b, a] = tile[0].to_srgba_unmultiplied();
+        assert!(r >= 254 && g == 0 && b == 0, "({r}, {g}, {b}) is no longer full red");
+        assert!((a as i32 - 128).abs() <= 1, "alpha {a}");
+    }
+
+    #[test]
+    fn apply_masked_reports_unchanged_tiles() {
+        let mut tile = vec![RED; 4];
+        assert!(!apply_masked(&mut tile, None, |_, old| old));
+        assert!(!apply_masked(&mut tile, Some(&[0.0; 4]), |_, _| Color32::TRANSPARENT));
+        assert_eq!(tile, [RED; 4]);
+    }
+
+    #[test]
+    fn lifting_a_feathered_selection_and_putting_it_back_changes_nothing() {
+        let mut canvas = Canvas::new(64, 64, Color32::WHITE, 64);
+        canvas.add_layer();
+        let layer_idx = canvas.active_layer_idx;
+        let pixels: Vec<Color32> = (0..64 * 64)
+            .map(|i| Color32::from_rgba_unmultiplied((i % 251) as u8, (i % 64 * 4) as u8, 90, if i % 3 == 0 { 255 } else { 140 }))
+            .collect();
+        canvas.layers[layer_idx].tiles.insert((0, 0), TileCell { data: Some(pixels.clone()), is_empty: false });
+
+        let mut selection = SelectionManager::new();
+        selection.replace_shape(Some(crate::selection::SelectionShape::Circle { center: Vec2::new(32.0, 32.0), radius: 20.0 }));
+        selection.set_feather(8.0);
+        let floating = canvas.float_selection(&selection).expect("the selection covers paint");
+        canvas.put_back_lifted(floating);
+
+        let restored = canvas.capture_layer_pixels(layer_idx).remove(&(0, 0)).expect("the tile is still there");
+        for (i, (&before, &after)) in pixels.iter().zip(&restored).enumerate() {
+            let off = before.to_array().iter().zip(after.to_array()).map(|(&a, b)| (a as i32 - b as i32).abs()).max();
+            assert!(off <= Some(1), "pixel {i} went from {before:?} to {after:?}");
+        }
+    }
+
+    #[test]
+    fn restoring_a_snapshot_brings_back_its_size_and_paper_color() {
+        let mut canvas = Canvas::new(100, 80, Color32::WHITE, 32);
+        let snapshot = canvas.snapshot("before".to_string());
+        canvas.replace_clear_color(Color32::BLACK);
+        canvas.crop(10, 20, 50, 40);
+
+        canvas.restore_snapshot(&snapshot);
+        assert_eq!((canvas.width(), canvas.height()), (100, 80));
+        assert_eq!((canvas.tiles_x, canvas.tiles_y), (4, 3));
+        assert_eq!(canvas.clear_color(), Color32::WHITE);
+    }
+
+    #[test]
+    fn merging_onto_a_faded_background_fades_the_paper_instead_of_filling_tiles() {
+        let mut canvas = Canvas::new(256, 256, Color32::WHITE, 32);
+        canvas.add_layer();
+        canvas.layers[0].opacity = 0.5;
+        canvas.layers[1].tiles.insert((1, 1), TileCell { data: Some(vec![RED; 32 * 32]), is_empty: false });
+
+        canvas.merge_layer_down(1);
+        assert_eq!(canvas.layers[0].opacity, 1.0);
+        assert_eq!(canvas.layers[0].tiles.entries().count(), 1);
+        assert_eq!(canvas.clear_color(), rgba_to_color32_fast(Rgba::from(Color32::WHITE) * 0.5));
+    }
+
+    #[test]
+    fn layer_previews_show_right_of_their_edge_without_touching_the_layer() {
+        let mut canvas = Canvas::new(64, 32, Color32::TRANSPARENT, 32);
+        canvas.add_layer();
+        canvas.layers[1].tiles.insert((0, 0), TileCell { data: Some(vec![RED; 32 * 32]), is_empty: false });
+        let filtered = HashMap::from([((0, 0), vec![Color32::BLUE; 32 * 32])]);
+        let preview = LayerPreview { layer_idx: 1, tiles: Arc::new(filtered), from_x: 9.5 };
+
+        let mut img = ColorImage::new([0, 0], Color32::TRANSPARENT);
+        canvas.write_tile_with_preview(0, 0, &mut img, 1, Some(&preview));
+        assert_eq!(img.pixels[9], RED);
+        assert_eq!(img.pixels[10], Color32::BLUE);
+        assert_eq!(canvas.get_layer_tile_data(1, 0, 0).unwrap()[10], RED);
+    }
+
+    #[test]
+    fn clearing_redisplays_the_cleared_tiles() {
+        let mut canvas = Canvas::new(64, 64, Color32::WHITE, 32);
+        canvas.add_layer();
+        canvas.active_layer_idx = 1;
+        canvas.layers[1].tiles.insert((1, 0), TileCell { data: Some(vec![RED; 32 * 32]), is_empty: false });
+        canvas.take_dirty();
+
+        canvas.clear();
+        assert_eq!(canvas.take_dirty().tiles().collect::<Vec<_>>(), [(1, 0)]);
+        assert!(canvas.get_layer_tile_data(1, 1, 0).is_none());
+    }
+}
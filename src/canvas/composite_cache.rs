@@ -0,0 +1,104 @@
+//! Per-tile flattened composites of the layers around the active one.
+//!
+//! While painting, only the active layer changes between repaints. Everything
+//! below it and everything above it can be flattened once per tile and reused,
+//! so recompositing a tile costs three blends instead of one per layer.
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use eframe::egui::{Color32, Rgba};
+
+use crate::canvas::adjustment::Adjustment;
+
+/// Memory the cached tiles may hold. Each tile is two linear float buffers of the tile size, so
+/// this fits 1024 tiles of 128 px but only 32 of 512 px.
+const CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Everything that affects the flattened stacks besides pixel content.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StackKey {
+    pub split: usize,
+    pub clear_color: Color32,
+    /// Every layer, bottom to top.
+    pub layers: Vec<LayerKey>,
+}
+
+/// Everything about one layer that affects the flattened stacks besides its pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LayerKey {
+    pub id: u64,
+    pub visible: bool,
+    pub opacity_bits: u32,
+    pub offset: (i32, i32),
+    pub paint_behind: bool,
+    /// Filter parameters of an adjustment layer, which change everything below it.
+    pub adjustment: Option<Adjustment>,
+}
+
+/// Flattened layers below and above the split layer for one tile, linear premultiplied.
+pub(crate) struct CompositeTile {
+    pub below: Vec<Rgba>,
+    pub above: Vec<Rgba>,
+}
+
+impl CompositeTile {
+    fn bytes(&self) -> usize {
+        (self.below.len() + self.above.len()) * size_of::<Rgba>()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CompositeCache {
+    key: Option<StackKey>,
+    tiles: HashMap<(i32, i32), Arc<CompositeTile>>,
+    /// Insertion order, oldest first, for eviction.
+    order: VecDeque<(i32, i32)>,
+    /// Memory held by `tiles`.
+    bytes: usize,
+}
+
+impl CompositeCache {
+    /// Cached tile for the given stack, dropping everything if the stack changed since it was built.
+    pub fn get(&mut self, key: &StackKey, tile: (i32, i32)) -> Option<Arc<CompositeTile>> {
+        if self.key.as_ref() != Some(key) {
+            self.clear();
+            self.key = Some(key.clone());
+            return None;
+        }
+        self.tiles.get(&tile).cloned()
+    }
+
+    pub fn insert(&mut self, key: &StackKey, tile: (i32, i32), composite: Arc<CompositeTile>) {
+        if self.key.as_ref() != Some(key) {
+            self.clear();
+            self.key = Some(key.clone());
+        }
+        self.bytes += composite.bytes();
+        match self.tiles.insert(tile, composite) {
+            Some(old) => self.bytes -= old.bytes(),
+            None => self.order.push_back(tile),
+        }
+        // Always keep the tile just inserted
+        while self.bytes > CACHE_BYTES && self.order.len() > 1 {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(old) = self.tiles.remove(&oldest) {
+                self.bytes -= old.bytes();
+            }
+        }
+    }
+
+    pub fn remove(&mut self, tile: (i32, i32)) {
+        if let Some(old) = self.tiles.remove(&tile) {
+            self.bytes -= old.bytes();
+            self.order.retain(|t| *t != tile);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+}
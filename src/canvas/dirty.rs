@@ -0,0 +1,46 @@
+//! Canvas areas whose displayed pixels are out of date.
+//!
+//! Canvas operations record what they change as they go, and the renderer takes the
+//! accumulated region once per frame to decide which screen tiles to upload again.
+use std::collections::HashSet;
+
+/// Tiles changed since the region was last taken, in tile coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct DirtyRegion {
+    /// Everything changed, as after a new document or a background color change.
+    all: bool,
+    tiles: HashSet<(usize, usize)>,
+}
+
+impl DirtyRegion {
+    /// A region covering the whole canvas.
+    pub fn everything() -> Self {
+        Self { all: true, tiles: HashSet::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.tiles.is_empty()
+    }
+
+    pub fn is_everything(&self) -> bool {
+        self.all
+    }
+
+    /// Add tiles, skipping coordinates left or above the canvas.
+    pub fn add_tiles(&mut self, coords: impl IntoIterator<Item = (i32, i32)>) {
+        if self.all {
+            return;
+        }
+        self.tiles.extend(coords.into_iter().filter(|&(tx, ty)| tx >= 0 && ty >= 0).map(|(tx, ty)| (tx as usize, ty as usize)));
+    }
+
+    pub fn add_everything(&mut self) {
+        self.all = true;
+        self.tiles.clear();
+    }
+
+    /// Changed tiles; empty when the region covers everything.
+    pub fn tiles(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.tiles.iter().copied()
+    }
+}
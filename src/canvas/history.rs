@@ -1,7 +1,12 @@
-use crate::canvas::canvas::Canvas;
+use crate::canvas::adjustment::Adjustment;
+use crate::canvas::canvas::{Canvas, LayerLabel};
+use crate::canvas::vector::{VectorLayer, VectorStroke};
 use crate::selection::SelectionShape;
 use crate::selection::transform::TransformInfo;
+use crate::utils::vector::Vec2;
 use eframe::egui::Color32;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Snapshot of a rectangular tile region prior to modification.
 pub struct TileSnapshot {
@@ -15,11 +20,165 @@ pub struct TileSnapshot {
     pub data: Vec<Color32>,
 }
 
+impl TileSnapshot {
+    /// The same region moved by (`dx`, `dy`) pixels, split along the tile grid it now straddles.
+    fn shifted(self, dx: i32, dy: i32, tile_size: usize) -> Vec<TileSnapshot> {
+        let ts = tile_size as i32;
+        let (gx, gy) = (self.tx * ts + self.x0 as i32 + dx, self.ty * ts + self.y0 as i32 + dy);
+        let (w, h) = (self.width as i32, self.height as i32);
+        if w == 0 || h == 0 {
+            return Vec::new();
+        }
+        let mut pieces = Vec::new();
+        for ty in gy.div_euclid(ts)..=(gy + h - 1).div_euclid(ts) {
+            for tx in gx.div_euclid(ts)..=(gx + w - 1).div_euclid(ts) {
+                // Overlap with this tile, in its own pixels
+                let (x0, x1) = ((gx - tx * ts).max(0), (gx + w - tx * ts).min(ts));
+                let (y0, y1) = ((gy - ty * ts).max(0), (gy + h - ty * ts).min(ts));
+                let width = (x1 - x0) as usize;
+                let mut data = Vec::with_capacity(width * (y1 - y0) as usize);
+                for y in y0..y1 {
+                    let src = ((y + ty * ts - gy) * w + x0 + tx * ts - gx) as usize;
+                    data.extend_from_slice(&self.data[src..src + width]);
+                }
+                pieces.push(TileSnapshot {
+                    tx,
+                    ty,
+                    layer_idx: self.layer_idx,
+                    x0: x0 as usize,
+                    y0: y0 as usize,
+                    width,
+                    height: (y1 - y0) as usize,
+                    data,
+                });
+            }
+        }
+        pieces
+    }
+}
+
+/// Canvas resize recorded by an undo action: the pixel to move to the origin and the
+/// size afterwards, as taken by [`Canvas::crop`].
+#[derive(Clone, Copy, Debug)]
+pub struct CanvasCrop {
+    pub x: i32,
+    pub y: i32,
+    pub width: usize,
+    pub height: usize,
+}
+
 /// Collection of tile snapshots captured during a single user operation.
 pub struct UndoAction {
+    /// Human-readable name shown in the history panel.
+    pub label: &'static str,
     pub tiles: Vec<TileSnapshot>,
     pub selection: Option<Option<SelectionShape>>,
     pub transform: Option<TransformInfo>,
+    /// Document background color to swap back in, for background color changes.
+    pub background: Option<Color32>,
+    /// Strokes of a vector layer to swap back in, by layer index.
+    pub vector: Option<(usize, Vec<VectorStroke>)>,
+    /// Canvas crop to apply, for trims. Everything else the history holds in canvas
+    /// coordinates must follow it, see [`History::shift`].
+    pub crop: Option<CanvasCrop>,
+    /// Steps of a multi-part operation, undone and redone along with this action.
+    pub composite: Option<CompositeAction>,
+    /// Layer index and the (`dx`, `dy`) pixels it was moved by, for layer moves.
+    pub offset: Option<(usize, (i32, i32))>,
+    /// When the action was pushed, counted across every history, so undo can tell layer
+    /// actions and document actions apart in time. Set by [`History::push_action`].
+    pub order: u64,
+}
+
+/// Actions that happened one after another but undo as one history entry, like the lift
+/// and placement of a transform session. Undo swaps the steps back last to first, redo
+/// replays them in order.
+pub struct CompositeAction {
+    pub steps: Vec<UndoAction>,
+}
+
+impl UndoAction {
+    /// An empty action named `label`, for the operation to fill in as it runs.
+    pub fn new(label: &'static str) -> Self {
+        Self { label, tiles: Vec::new(), selection: None, transform: None, background: None, vector: None, crop: None, composite: None, offset: None, order: 0 }
+    }
+
+    /// Snapshot every tile of `layer_idx` whose pixels differ from `before`, keeping the
+    /// `before` pixels so the change can be swapped back. Tiles missing from `before` were
+    /// transparent.
+    pub fn record_changed_tiles(&mut self, canvas: &Canvas, layer_idx: usize, before: &HashMap<(i32, i32), Vec<Color32>>) {
+        let tile_size = canvas.tile_size();
+        let after = canvas.capture_layer_pixels(layer_idx);
+        let blank = vec![Color32::TRANSPARENT; tile_size * tile_size];
+        let coords: std::collections::HashSet<(i32, i32)> = after.keys().chain(before.keys()).copied().collect();
+        for (tx, ty) in coords {
+            let old = before.get(&(tx, ty)).unwrap_or(&blank);
+            if after.get(&(tx, ty)).unwrap_or(&blank) == old {
+                continue;
+            }
+            self.tiles.push(TileSnapshot { tx, ty, layer_idx, x0: 0, y0: 0, width: tile_size, height: tile_size, data: old.clone() });
+        }
+    }
+
+    /// Move the stored tile regions and vector paths, and those of any steps, by
+    /// (`dx`, `dy`) pixels.
+    fn shift(&mut self, dx: i32, dy: i32, tile_size: usize) {
+        self.tiles = std::mem::take(&mut self.tiles).into_iter().flat_map(|s| s.shifted(dx, dy, tile_size)).collect();
+        if let Some((_, strokes)) = self.vector.as_mut() {
+            let delta = Vec2::new(dx as f32, dy as f32);
+            strokes.iter_mut().for_each(|stroke| stroke.translate(delta));
+        }
+        for step in self.composite.iter_mut().flat_map(|c| c.steps.iter_mut()) {
+            step.shift(dx, dy, tile_size);
+        }
+    }
+
+    /// Swap the stored tile regions with the canvas pixels, returning the touched tiles.
+    pub fn swap_tiles(&mut self, canvas: &mut Canvas) -> Vec<(i32, i32)> {
+        let mut affected = Vec::new();
+        for snapshot in &mut self.tiles {
+            let tile_size = canvas.tile_size();
+            canvas.ensure_layer_tile_exists_i32(snapshot.layer_idx, snapshot.tx, snapshot.ty);
+            if let Some(tile_arc) =
+                canvas.lock_layer_tile_i32(snapshot.layer_idx, snapshot.tx, snapshot.ty)
+            {
+                let mut tile = tile_arc.lock().unwrap();
+                // Ensure tile data exists
+                if tile.data.is_none() {
+                    tile.data = Some(vec![Color32::TRANSPARENT; tile_size * tile_size]);
+                }
+                let data = tile.data.as_mut().unwrap();
+
+                // Extract current region
+                let mut current_region =
+                    vec![Color32::TRANSPARENT; snapshot.width * snapshot.height];
+                for row in 0..snapshot.height {
+                    let src_start = (snapshot.y0 + row) * tile_size + snapshot.x0;
+                    let dst_start = row * snapshot.width;
+                    let len = snapshot.width;
+                    current_region[dst_start..dst_start + len]
+                        .copy_from_slice(&data[src_start..src_start + len]);
+                }
+
+                // Write stored snapshot into tile
+                for row in 0..snapshot.height {
+                    let dst_start = (snapshot.y0 + row) * tile_size + snapshot.x0;
+                    let src_start = row * snapshot.width;
+                    let len = snapshot.width;
+                    data[dst_start..dst_start + len]
+                        .copy_from_slice(&snapshot.data[src_start..src_start + len]);
+                }
+
+                // Store current region for redo/undo swap
+                snapshot.data = current_region;
+                tile.refresh_empty(snapshot.layer_idx != 0);
+                affected.push((snapshot.tx, snapshot.ty));
+            }
+            // Snapshots may belong to a layer other than the active one
+            canvas.layer_tiles_changed(snapshot.layer_idx, &[(snapshot.tx, snapshot.ty)]);
+        }
+        affected
+    }
 }
 
 /// Stack-based undo/redo manager that swaps tile buffers in place.
@@ -38,15 +197,52 @@ impl History {
     }
 
     /// Push a new action onto the undo stack and clear redo.
-    pub fn push_action(&mut self, action: UndoAction) {
+    pub fn push_action(&mut self, mut action: UndoAction) {
+        static NEXT_ORDER: AtomicU64 = AtomicU64::new(0);
+        action.order = NEXT_ORDER.fetch_add(1, Ordering::Relaxed);
         self.undo_stack.push(action);
         self.redo_stack.clear();
     }
 
+    /// Labels and push order of undoable actions, oldest first.
+    pub fn undo_labels(&self) -> impl DoubleEndedIterator<Item = (&'static str, u64)> + '_ {
+        self.undo_stack.iter().map(|a| (a.label, a.order))
+    }
+
+    /// Labels and push order of redoable actions, next-to-redo first.
+    pub fn redo_labels(&self) -> impl DoubleEndedIterator<Item = (&'static str, u64)> + '_ {
+        self.redo_stack.iter().rev().map(|a| (a.label, a.order))
+    }
+
+    /// Number of actions currently on the undo stack.
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    /// When the action the next undo (or, with `redo`, the next redo) swaps was pushed.
+    pub fn next_order(&self, redo: bool) -> Option<u64> {
+        let stack = if redo { &self.redo_stack } else { &self.undo_stack };
+        stack.last().map(|action| action.order)
+    }
+
+    /// The canvas crop the next undo (or, with `redo`, the next redo) applies, if any.
+    pub fn next_crop(&self, redo: bool) -> Option<CanvasCrop> {
+        let stack = if redo { &self.redo_stack } else { &self.undo_stack };
+        stack.last().and_then(|action| action.crop)
+    }
+
+    /// Move every stored tile region and vector path by (`dx`, `dy`) pixels, to follow a
+    /// crop of the canvas.
+    pub fn shift(&mut self, dx: i32, dy: i32, tile_size: usize) {
+        for action in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            action.shift(dx, dy, tile_size);
+        }
+    }
+
     /// Undo the latest action, returning tile coordinates that changed.
-    pub fn undo(&mut self, canvas: &Canvas, selection_manager: &mut crate::selection::SelectionManager, active_tool: &mut crate::app::tools::Tool) -> Vec<(i32, i32)> {
+    pub fn undo(&mut self, canvas: &mut Canvas, selection_manager: &mut crate::selection::SelectionManager, active_tool: &mut crate::app::tools::Tool) -> Vec<(i32, i32)> {
         if let Some(mut action) = self.undo_stack.pop() {
-            let tiles = self.swap_state(canvas, selection_manager, active_tool, &mut action);
+            let tiles = Self::swap_state(canvas, selection_manager, active_tool, &mut action, false);
             self.redo_stack.push(action);
             tiles
         } else {
@@ -55,9 +251,9 @@ impl History {
     }
 
     /// Redo the previously undone action, returning tile coordinates that changed.
-    pub fn redo(&mut self, canvas: &Canvas, selection_manager: &mut crate::selection::SelectionManager, active_tool: &mut crate::app::tools::Tool) -> Vec<(i32, i32)> {
+    pub fn redo(&mut self, canvas: &mut Canvas, selection_manager: &mut crate::selection::SelectionManager, active_tool: &mut crate::app::tools::Tool) -> Vec<(i32, i32)> {
         if let Some(mut action) = self.redo_stack.pop() {
-            let tiles = self.swap_state(canvas, selection_manager, active_tool, &mut action);
+            let tiles = Self::swap_state(canvas, selection_manager, active_tool, &mut action, true);
             self.undo_stack.push(action);
             tiles
         } else {
@@ -65,11 +261,39 @@ impl History {
         }
     }
 
-    /// Swap stored tile data with the canvas, producing a list of updated tiles.
-    fn swap_state(&self, canvas: &Canvas, selection_manager: &mut crate::selection::SelectionManager, active_tool: &mut crate::app::tools::Tool, action: &mut UndoAction) -> Vec<(i32, i32)> {
+    /// Swap stored tile data with the canvas, producing a list of updated tiles. `redo`
+    /// decides which way the steps of a composite action are walked.
+    fn swap_state(canvas: &mut Canvas, selection_manager: &mut crate::selection::SelectionManager, active_tool: &mut crate::app::tools::Tool, action: &mut UndoAction, redo: bool) -> Vec<(i32, i32)> {
         // Swap selection state
         if let Some(stored_selection) = &mut action.selection {
-            std::mem::swap(stored_selection, &mut selection_manager.current_shape);
+            *stored_selection = selection_manager.replace_shape(stored_selection.take());
+        }
+
+        // Swap the document background color
+        if let Some(stored_background) = &mut action.background {
+            *stored_background = canvas.replace_clear_color(*stored_background);
+        }
+
+        // Swap the paths of an edited vector layer
+        if let Some((strokes, layer)) = action
+            .vector
+            .as_mut()
+            .and_then(|(layer_idx, strokes)| Some((strokes, canvas.layers.get_mut(*layer_idx)?.vector.as_mut()?)))
+        {
+            std::mem::swap(strokes, &mut layer.strokes);
+        }
+
+        // Crop the canvas, keeping the way back
+        if let Some(crop) = action.crop.as_mut() {
+            let (width, height) = (canvas.width(), canvas.height());
+            canvas.crop(crop.x, crop.y, crop.width, crop.height);
+            *crop = CanvasCrop { x: -crop.x, y: -crop.y, width, height };
+        }
+
+        // Move the layer back, or again for a redo
+        if let Some((layer_idx, (dx, dy))) = action.offset {
+            let (dx, dy) = if redo { (dx, dy) } else { (-dx, -dy) };
+            canvas.offset_layer(layer_idx, dx, dy);
         }
 
         // Swap transform state
@@ -80,45 +304,39 @@ impl History {
             }
         }
 
-        let mut affected = Vec::new();
-        for snapshot in &mut action.tiles {
-            let tile_size = canvas.tile_size();
-            canvas.ensure_layer_tile_exists_i32(snapshot.layer_idx, snapshot.tx, snapshot.ty);
-            if let Some(tile_arc) =
-                canvas.lock_layer_tile_i32(snapshot.layer_idx, snapshot.tx, snapshot.ty)
-            {
-                let mut tile = tile_arc.lock().unwrap();
-                // Ensure tile data exists
-                if tile.data.is_none() {
-                    tile.data = Some(vec![Color32::TRANSPARENT; tile_size * tile_size]);
-                }
-                let data = tile.data.as_mut().unwrap();
-
-                // Extract current region
-                let mut current_region =
-                    vec![Color32::TRANSPARENT; snapshot.width * snapshot.height];
-                for row in 0..snapshot.height {
-                    let src_start = (snapshot.y0 + row) * tile_size + snapshot.x0;
-                    let dst_start = row * snapshot.width;
-                    let len = snapshot.width;
-                    current_region[dst_start..dst_start + len]
-                        .copy_from_slice(&data[src_start..src_start + len]);
-                }
-
-                // Write stored snapshot into tile
-                for row in 0..snapshot.height {
-                    let dst_start = (snapshot.y0 + row) * tile_size + snapshot.x0;
-                    let src_start = row * snapshot.width;
-                    let len = snapshot.width;
-                    data[dst_start..dst_start + len]
-                        .copy_from_slice(&snapshot.data[src_start..src_start + len]);
-                }
-
-                // Store current region for redo/undo swap
-                snapshot.data = current_region;
-                affected.push((snapshot.tx, snapshot.ty));
+        let mut affected = action.swap_tiles(canvas);
+        if let Some(composite) = action.composite.as_mut() {
+            let steps: Box<dyn Iterator<Item = &mut UndoAction>> =
+                if redo { Box::new(composite.steps.iter_mut()) } else { Box::new(composite.steps.iter_mut().rev()) };
+            for step in steps {
+                affected.extend(Self::swap_state(canvas, selection_manager, active_tool, step, redo));
             }
         }
         affected
     }
 }
+
+/// Pixel and property copy of a single layer inside a document snapshot.
+pub struct LayerSnapshot {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub locked: bool,
+    pub paint_behind: bool,
+    pub adjustment: Option<Adjustment>,
+    pub vector: Option<VectorLayer>,
+    pub label: LayerLabel,
+    pub offset: (i32, i32),
+    pub tiles: HashMap<(i32, i32), Vec<Color32>>,
+}
+
+/// Full copy of the document that can be restored without walking the undo stacks.
+pub struct CanvasSnapshot {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// Paper color shown where the background layer has no pixels.
+    pub clear_color: Color32,
+    pub layers: Vec<LayerSnapshot>,
+    pub active_layer_idx: usize,
+}
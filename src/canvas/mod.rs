@@ -1,3 +1,12 @@
 //! Canvas storage, compositing, and history helpers.
+pub mod adjustment;
+pub mod animation;
 pub mod canvas;
+pub mod composite_cache;
+pub mod dirty;
 pub mod history;
+pub mod noise;
+pub mod palette;
+pub(crate) mod tile_pager;
+pub(crate) mod tile_store;
+pub mod vector;
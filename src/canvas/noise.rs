@@ -0,0 +1,116 @@
+//! Hashed noise sampled in canvas space: per-pixel noise for the Add Noise filter and smooth
+//! value noise for brush textures. Values depend only on position and seed, so previews are
+//! stable and overlapping dabs line up without seams or image assets.
+use eframe::egui::Color32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseDistribution {
+    Uniform,
+    Gaussian,
+}
+
+impl NoiseDistribution {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NoiseDistribution::Uniform => "Uniform",
+            NoiseDistribution::Gaussian => "Gaussian",
+        }
+    }
+}
+
+/// Parameters for the Add Noise filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseSettings {
+    pub distribution: NoiseDistribution,
+    /// Same offset on all channels (grain) instead of independent per-channel noise.
+    pub monochrome: bool,
+    pub intensity: f32, // 0..1
+    pub seed: u32,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        Self {
+            distribution: NoiseDistribution::Gaussian,
+            monochrome: true,
+            intensity: 0.1,
+            seed: 1,
+        }
+    }
+}
+
+impl NoiseSettings {
+    /// Add noise to a premultiplied pixel. The result depends only on position and seed,
+    /// so re-running the filter for a preview is stable.
+    pub fn apply(&self, x: i32, y: i32, color: Color32) -> Color32 {
+        let a = color.a();
+        if a == 0 || self.intensity <= 0.0 {
+            return color;
+        }
+
+        let sample = |channel: u32| match self.distribution {
+            NoiseDistribution::Uniform => hash_unit(x, y, self.seed, channel) * 2.0 - 1.0,
+            NoiseDistribution::Gaussian => {
+                // Box-Muller, scaled so most samples land within -1..1
+                let u1 = hash_unit(x, y, self.seed, channel * 2).max(1e-7);
+                let u2 = hash_unit(x, y, self.seed, channel * 2 + 1);
+                (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos() / 3.0
+            }
+        };
+
+        let mono = if self.monochrome { sample(0) } else { 0.0 };
+        let alpha = a as f32 / 255.0;
+        let mut out = [color.r(), color.g(), color.b()];
+        for (channel, value) in out.iter_mut().enumerate() {
+            let n = if self.monochrome { mono } else { sample(channel as u32) };
+            let straight = *value as f32 / 255.0 / alpha;
+            let noisy = (straight + n * self.intensity).clamp(0.0, 1.0);
+            *value = (noisy * alpha * 255.0).round() as u8;
+        }
+        Color32::from_rgba_premultiplied(out[0], out[1], out[2], a)
+    }
+}
+
+/// Hash a pixel position, seed, and channel into a uniform value in 0..1.
+#[inline]
+fn hash_unit(x: i32, y: i32, seed: u32, channel: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ seed.wrapping_mul(0xcb1a_b31f)
+        ^ channel.wrapping_mul(0x1656_67b1);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    (h >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// Quintic fade, so the noise has no creases along lattice lines.
+#[inline]
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Value noise in [0, 1) with one lattice cell per unit, its lattice values from [`hash_unit`].
+pub fn value_noise(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let (u, v) = (fade(x - x0), fade(y - y0));
+    let lattice = |x: i32, y: i32| hash_unit(x, y, seed, 0);
+    let top = lattice(ix, iy) + (lattice(ix + 1, iy) - lattice(ix, iy)) * u;
+    let bottom = lattice(ix, iy + 1) + (lattice(ix + 1, iy + 1) - lattice(ix, iy + 1)) * u;
+    top + (bottom - top) * v
+}
+
+/// Value noise with `octaves` layers, each twice as fine and half as strong, kept in [0, 1).
+pub fn fractal_noise(x: f32, y: f32, octaves: u32) -> f32 {
+    let (mut sum, mut weight, mut total, mut freq) = (0.0, 1.0, 0.0, 1.0);
+    for octave in 0..octaves.max(1) {
+        sum += value_noise(x * freq, y * freq, octave) * weight;
+        total += weight;
+        weight *= 0.5;
+        freq *= 2.0;
+    }
+    sum / total
+}
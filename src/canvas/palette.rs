@@ -0,0 +1,56 @@
+//! Document palette used to constrain painting to a fixed set of colors.
+use eframe::egui::Color32;
+
+/// Largest palette an indexed PNG can hold.
+pub const MAX_COLORS: usize = 256;
+
+/// Colors the document may use. When `enabled`, every painted pixel snaps to one of them.
+#[derive(Clone, Debug, Default)]
+pub struct Palette {
+    pub colors: Vec<Color32>,
+    pub enabled: bool,
+}
+
+impl Palette {
+    /// The palette to apply while painting, if constraint mode is on and there are colors to snap to.
+    pub fn constraint(&self) -> Option<&Palette> {
+        (self.enabled && !self.colors.is_empty()).then_some(self)
+    }
+
+    /// Index of the palette entry closest to an opaque sRGB color.
+    pub fn nearest_index(&self, r: u8, g: u8, b: u8) -> Option<usize> {
+        self.colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| color_distance([r, g, b], [c.r(), c.g(), c.b()]))
+            .map(|(i, _)| i)
+    }
+
+    /// Snap a premultiplied pixel to the palette.
+    ///
+    /// Alpha is thresholded as well, since an indexed image has no partial coverage:
+    /// mostly transparent pixels are cleared and the rest become the nearest opaque entry.
+    #[inline]
+    pub fn constrain(&self, color: Color32) -> Color32 {
+        if color.a() < 128 {
+            return Color32::TRANSPARENT;
+        }
+        let [r, g, b, _] = color.to_srgba_unmultiplied();
+        match self.nearest_index(r, g, b) {
+            Some(i) => {
+                let c = self.colors[i];
+                Color32::from_rgb(c.r(), c.g(), c.b())
+            }
+            None => color,
+        }
+    }
+}
+
+/// Perceptually weighted "redmean" distance between two sRGB colors.
+fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    let rmean = (a[0] as i32 + b[0] as i32) / 2;
+    let dr = a[0] as i32 - b[0] as i32;
+    let dg = a[1] as i32 - b[1] as i32;
+    let db = a[2] as i32 - b[2] as i32;
+    (((512 + rmean) * dr * dr) / 256 + 4 * dg * dg + ((767 - rmean) * db * db) / 256) as u32
+}
@@ -0,0 +1,145 @@
+//! Scratch-file paging of layer tiles for documents bigger than memory.
+//!
+//! With paging on, the painter periodically hands the canvas a memory budget. Tiles with pixels
+//! that have gone longest without a lookup are written to a scratch file and dropped until
+//! the budget is met, and the tile store reads a tile back the next time it is looked up.
+//! Recency is counted in epochs: every paging pass starts a new one, and a lookup stamps the
+//! tile with the current epoch. Only tiles nobody else holds are paged out, so a caller never
+//! sees one vanish under it.
+use eframe::egui::Color32;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+static SCRATCH: Mutex<Option<ScratchFile>> = Mutex::new(None);
+
+/// Where a paged-out tile's pixels went.
+#[derive(Debug)]
+pub(crate) struct PagedTile {
+    offset: u64,
+    len: usize,
+    pub is_empty: bool,
+}
+
+/// Temporary file of fixed-size slots, reused as tiles come back in.
+struct ScratchFile {
+    file: File,
+    path: PathBuf,
+    /// Free slot offsets by slot size in bytes.
+    free: HashMap<usize, Vec<u64>>,
+    end: u64,
+    /// Bytes currently holding paged-out tiles.
+    used: u64,
+}
+
+impl ScratchFile {
+    fn create() -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("rusty-painter-{}.swap", std::process::id()));
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        // The open handle keeps the data reachable and the OS reclaims it when the app exits
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&path);
+        Ok(Self { file, path, free: HashMap::new(), end: 0, used: 0 })
+    }
+}
+
+/// Current recency epoch, stamped on tiles as they are looked up.
+pub(crate) fn epoch() -> u64 {
+    EPOCH.load(Ordering::Relaxed)
+}
+
+/// Start a new epoch, so tiles looked up from now on count as more recent than the rest.
+pub(crate) fn advance_epoch() -> u64 {
+    EPOCH.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Write a tile's pixels to the scratch file, or `None` when it can't be written.
+pub(crate) fn write(data: &[Color32], is_empty: bool) -> Option<PagedTile> {
+    let mut scratch = SCRATCH.lock().unwrap();
+    if scratch.is_none() {
+        match ScratchFile::create() {
+            Ok(file) => *scratch = Some(file),
+            Err(e) => {
+                log::error!("Failed to create the tile scratch file: {}", e);
+                return None;
+            }
+        }
+    }
+    let scratch = scratch.as_mut()?;
+    let bytes: Vec<u8> = data.iter().flat_map(|c| c.to_array()).collect();
+    let len = bytes.len();
+    let offset = scratch.free.get_mut(&len).and_then(Vec::pop).unwrap_or(scratch.end);
+    let written = scratch.file.seek(SeekFrom::Start(offset)).and_then(|_| scratch.file.write_all(&bytes));
+    if let Err(e) = written {
+        log::error!("Failed to page a tile out to {}: {}", scratch.path.display(), e);
+        if offset != scratch.end {
+            scratch.free.entry(len).or_default().push(offset);
+        }
+        return None;
+    }
+    scratch.end = scratch.end.max(offset + len as u64);
+    scratch.used += len as u64;
+    Some(PagedTile { offset, len, is_empty })
+}
+
+/// Read a paged-out tile back and free its slot. A tile that can't be read comes back
+/// transparent, with the error logged.
+pub(crate) fn read(tile: PagedTile) -> Vec<Color32> {
+    let mut bytes = vec![0u8; tile.len];
+    let mut guard = SCRATCH.lock().unwrap();
+    let result = match guard.as_mut() {
+        Some(scratch) => scratch.file.seek(SeekFrom::Start(tile.offset)).and_then(|_| scratch.file.read_exact(&mut bytes)),
+        None => Err(std::io::Error::other("no scratch file")),
+    };
+    if let Err(e) = result {
+        log::error!("Failed to read a paged-out tile back: {}", e);
+        bytes.fill(0);
+    }
+    drop(guard);
+    release(&tile);
+    bytes.chunks_exact(4).map(|p| Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3])).collect()
+}
+
+/// Free a paged-out tile's slot without reading it, for tiles that were replaced or dropped.
+pub(crate) fn release(tile: &PagedTile) {
+    if let Some(scratch) = SCRATCH.lock().unwrap().as_mut() {
+        scratch.free.entry(tile.len).or_default().push(tile.offset);
+        scratch.used = scratch.used.saturating_sub(tile.len as u64);
+    }
+}
+
+/// Bytes of tile pixels currently paged out.
+pub fn paged_bytes() -> u64 {
+    SCRATCH.lock().unwrap().as_ref().map_or(0, |s| s.used)
+}
+
+/// Delete the scratch file where the OS keeps it around after exit. Paged-out tiles are lost,
+/// so this is only for shutdown.
+pub fn remove_scratch_file() {
+    if let Some(scratch) = SCRATCH.lock().unwrap().take() {
+        let _ = std::fs::remove_file(&scratch.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_come_back_as_written() {
+        let data: Vec<Color32> = (0..64u8).map(|i| Color32::from_rgba_premultiplied(i, i / 2, i / 4, 255)).collect();
+        let tile = write(&data, false).expect("scratch file is writable");
+        assert!(!tile.is_empty);
+        assert!(paged_bytes() >= data.len() as u64 * 4);
+        assert_eq!(read(tile), data);
+
+        let blank = vec![Color32::TRANSPARENT; 16];
+        let tile = write(&blank, true).expect("scratch file is writable");
+        assert!(tile.is_empty);
+        assert_eq!(read(tile), blank);
+    }
+}
@@ -0,0 +1,236 @@
+//! Tile map of one layer, split into shards by tile coordinate.
+//!
+//! Parallel dabs and compositing look tiles up far more often than tiles are added or
+//! dropped, so lookups take a shared lock on a single shard and only structural changes
+//! write-lock the shard they touch. Each tile keeps its own mutex for its pixels.
+//!
+//! Tiles paged out to the scratch file (see [`crate::canvas::tile_pager`]) leave the shards
+//! and are read back in by whichever lookup asks for them first.
+use crate::canvas::canvas::TileCell;
+use crate::canvas::tile_pager::{self, PagedTile};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+const SHARDS: usize = 16;
+
+/// Shared handle to one tile's pixels.
+pub(crate) type TileRef = Arc<Mutex<TileCell>>;
+
+#[derive(Debug)]
+struct Entry {
+    tile: TileRef,
+    /// Paging epoch of the last lookup.
+    last_used: AtomicU64,
+}
+
+impl Entry {
+    fn new(cell: TileCell) -> Self {
+        Self { tile: Arc::new(Mutex::new(cell)), last_used: AtomicU64::new(tile_pager::epoch()) }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TileStore {
+    shards: [RwLock<HashMap<(i32, i32), Entry>>; SHARDS],
+    /// Tiles written out to the scratch file. Locked before any shard when both are needed.
+    paged: Mutex<HashMap<(i32, i32), PagedTile>>,
+    /// Length of `paged`, so lookups of missing tiles skip its lock when nothing is paged out.
+    paged_count: AtomicUsize,
+}
+
+impl Default for TileStore {
+    fn default() -> Self {
+        Self {
+            shards: std::array::from_fn(|_| RwLock::new(HashMap::new())),
+            paged: Mutex::new(HashMap::new()),
+            paged_count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Drop for TileStore {
+    fn drop(&mut self) {
+        for tile in self.paged.get_mut().unwrap().values() {
+            tile_pager::release(tile);
+        }
+    }
+}
+
+impl TileStore {
+    fn shard(&self, (tx, ty): (i32, i32)) -> &RwLock<HashMap<(i32, i32), Entry>> {
+        // Neighbouring tiles land in different shards, so one dab's tiles rarely share a lock
+        let hash = (tx as u32).wrapping_mul(0x9E37_79B1) ^ (ty as u32).wrapping_mul(0x85EB_CA77);
+        &self.shards[(hash >> 16) as usize % SHARDS]
+    }
+
+    pub(crate) fn get(&self, coord: (i32, i32)) -> Option<TileRef> {
+        if let Some(entry) = self.shard(coord).read().unwrap().get(&coord) {
+            entry.last_used.store(tile_pager::epoch(), Ordering::Relaxed);
+            return Some(entry.tile.clone());
+        }
+        if self.paged_count.load(Ordering::Acquire) == 0 {
+            return None;
+        }
+        self.page_in(coord)
+    }
+
+    /// Read a paged-out tile back into its shard. Another lookup may have beaten us to it.
+    fn page_in(&self, coord: (i32, i32)) -> Option<TileRef> {
+        let mut paged = self.paged.lock().unwrap();
+        let Some(tile) = paged.remove(&coord) else {
+            drop(paged);
+            return self.shard(coord).read().unwrap().get(&coord).map(|e| e.tile.clone());
+        };
+        let is_empty = tile.is_empty;
+        let cell = TileCell { data: Some(tile_pager::read(tile)), is_empty };
+        let entry = Entry::new(cell);
+        let tile = entry.tile.clone();
+        self.shard(coord).write().unwrap().insert(coord, entry);
+        // Only now, so a lookup that finds the shard empty still knows to wait on `paged`
+        self.paged_count.store(paged.len(), Ordering::Release);
+        Some(tile)
+    }
+
+    /// The tile at `coord`, adding `init()` there first if there is none.
+    pub(crate) fn get_or_insert_with(&self, coord: (i32, i32), init: impl FnOnce() -> TileCell) -> TileRef {
+        if let Some(tile) = self.get(coord) {
+            return tile;
+        }
+        let mut shard = self.shard(coord).write().unwrap();
+        shard.entry(coord).or_insert_with(|| Entry::new(init())).tile.clone()
+    }
+
+    /// The tile at `coord`, adding one without a pixel buffer if there is none.
+    pub(crate) fn get_or_insert(&self, coord: (i32, i32)) -> TileRef {
+        self.get_or_insert_with(coord, || TileCell { data: None, is_empty: true })
+    }
+
+    pub(crate) fn insert(&self, coord: (i32, i32), cell: TileCell) {
+        let mut paged = self.paged.lock().unwrap();
+        if let Some(old) = paged.remove(&coord) {
+            tile_pager::release(&old);
+            self.paged_count.store(paged.len(), Ordering::Release);
+        }
+        self.shard(coord).write().unwrap().insert(coord, Entry::new(cell));
+    }
+
+    /// Every tile with its coordinate, in no particular order. Tiles added meanwhile are missed.
+    /// Tiles in memory come first; paged-out tiles are read back one at a time as the
+    /// iteration reaches them, so stopping early leaves the rest on disk.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = ((i32, i32), TileRef)> + '_ {
+        // Holding `paged` keeps tiles from moving between it and the shards while both are listed
+        let paged = self.paged.lock().unwrap();
+        let resident: Vec<((i32, i32), TileRef)> = self
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.read().unwrap();
+                shard.iter().map(|(&coord, entry)| (coord, entry.tile.clone())).collect::<Vec<_>>()
+            })
+            .collect();
+        let paged_out: Vec<(i32, i32)> = paged.keys().copied().collect();
+        drop(paged);
+        resident
+            .into_iter()
+            .chain(paged_out.into_iter().filter_map(move |coord| self.page_in(coord).map(|tile| (coord, tile))))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.paged_count.load(Ordering::Acquire) == 0 && self.shards.iter().all(|shard| shard.read().unwrap().is_empty())
+    }
+
+    /// Tiles in memory that hold pixels, with the epoch of their last lookup. Tiles locked
+    /// elsewhere are skipped.
+    pub(crate) fn resident(&self) -> Vec<((i32, i32), u64)> {
+        let mut resident = Vec::new();
+        for shard in &self.shards {
+            for (&coord, entry) in shard.read().unwrap().iter() {
+                if entry.tile.try_lock().is_ok_and(|cell| cell.data.is_some()) {
+                    resident.push((coord, entry.last_used.load(Ordering::Relaxed)));
+                }
+            }
+        }
+        resident
+    }
+
+    /// Write the tile at `coord` to the scratch file and drop its pixels from memory. Returns
+    /// false, leaving the tile alone, while anyone else holds or has locked it.
+    pub(crate) fn page_out(&self, coord: (i32, i32)) -> bool {
+        let mut paged = self.paged.lock().unwrap();
+        let mut shard = self.shard(coord).write().unwrap();
+        let Some(entry) = shard.get(&coord) else {
+            return false;
+        };
+        // Lookups clone the handle under the shard lock, so nobody can pick it up meanwhile
+        if Arc::strong_count(&entry.tile) > 1 {
+            return false;
+        }
+        let written = match entry.tile.try_lock() {
+            Ok(cell) => cell.data.as_deref().and_then(|data| tile_pager::write(data, cell.is_empty)),
+            Err(_) => None,
+        };
+        let Some(tile) = written else {
+            return false;
+        };
+        shard.remove(&coord);
+        paged.insert(coord, tile);
+        self.paged_count.store(paged.len(), Ordering::Release);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::Color32;
+
+    fn cell(value: u8) -> TileCell {
+        TileCell { data: Some(vec![Color32::from_gray(value); 16]), is_empty: false }
+    }
+
+    #[test]
+    fn paged_out_tiles_read_back_on_lookup() {
+        let store = TileStore::default();
+        store.insert((1, 2), cell(10));
+        store.insert((3, 4), cell(20));
+        assert!(store.page_out((1, 2)));
+        assert_eq!(store.resident().len(), 1);
+        assert!(!store.is_empty());
+
+        let tile = store.get((1, 2)).expect("paged-out tile is still in the store");
+        assert_eq!(tile.lock().unwrap().data, cell(10).data);
+        assert_eq!(store.resident().len(), 2);
+    }
+
+    #[test]
+    fn entries_include_paged_out_tiles() {
+        let store = TileStore::default();
+        store.insert((0, 0), cell(1));
+        store.insert((0, 1), cell(2));
+        assert!(store.page_out((0, 1)));
+        let mut coords: Vec<(i32, i32)> = store.entries().map(|(coord, _)| coord).collect();
+        coords.sort_unstable();
+        assert_eq!(coords, [(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn held_tiles_stay_in_memory() {
+        let store = TileStore::default();
+        store.insert((0, 0), cell(1));
+        let held = store.get((0, 0));
+        assert!(!store.page_out((0, 0)));
+        drop(held);
+        assert!(store.page_out((0, 0)));
+    }
+
+    #[test]
+    fn inserting_over_a_paged_out_tile_replaces_it() {
+        let store = TileStore::default();
+        store.insert((0, 0), cell(1));
+        assert!(store.page_out((0, 0)));
+        store.insert((0, 0), cell(2));
+        let tile = store.get((0, 0)).expect("tile was inserted");
+        assert_eq!(tile.lock().unwrap().data, cell(2).data);
+    }
+}
@@ -0,0 +1,85 @@
+//! Vector layers keep every stroke as its path and brush so line work can be reshaped after it
+//! is painted. The layer's tiles hold the strokes' rasterization, redrawn whenever a path
+//! changes, so they composite like any other layer.
+use crate::brush_engine::brush::Brush;
+use crate::brush_engine::smoothing::StrokeSample;
+use crate::utils::vector::{Vec2, distance};
+
+/// Distance along the path, in canvas pixels, over which dragging a point bends its neighbours.
+pub const BEND_REACH: f32 = 24.0;
+pub const WIDTH_RANGE: std::ops::RangeInclusive<f32> = 0.1..=4.0;
+
+/// One brush stroke kept as its samples and the brush that painted them.
+#[derive(Clone, Debug)]
+pub struct VectorStroke {
+    /// Position, diameter and pressure of every sample.
+    pub points: Vec<StrokeSample>,
+    pub brush: Brush,
+    /// Multiplier on the recorded diameters.
+    pub width: f32,
+}
+
+impl VectorStroke {
+    pub fn new(points: Vec<StrokeSample>, brush: &Brush) -> Self {
+        let mut brush = brush.clone();
+        // Samples already hold canvas diameters, and the mask cache refills on first use
+        brush.brush_options.screen_size = false;
+        brush.dab_masks = Default::default();
+        Self { points, brush, width: 1.0 }
+    }
+
+    /// Move the whole path by `delta`.
+    pub fn translate(&mut self, delta: Vec2) {
+        for point in &mut self.points {
+            point.0 = point.0 + delta;
+        }
+    }
+
+    /// Move the sample at `idx` by `delta`, taking the samples around it along with a falloff
+    /// over [`BEND_REACH`] of path length so the line bends instead of kinking.
+    pub fn bend(&mut self, idx: usize, delta: Vec2) {
+        let Some(grabbed) = self.points.get(idx).map(|p| p.0) else {
+            return;
+        };
+        let weight = |along: f32| 0.5 + 0.5 * (std::f32::consts::PI * along / BEND_REACH).cos();
+        let mut shifts = vec![(idx, 1.0)];
+        for range in [(0..idx).rev().collect::<Vec<_>>(), (idx + 1..self.points.len()).collect()] {
+            let (mut along, mut prev) = (0.0, grabbed);
+            for i in range {
+                along += distance(prev, self.points[i].0);
+                prev = self.points[i].0;
+                if along >= BEND_REACH {
+                    break;
+                }
+                shifts.push((i, weight(along)));
+            }
+        }
+        for (i, amount) in shifts {
+            self.points[i].0 = self.points[i].0 + delta * amount;
+        }
+    }
+}
+
+/// Strokes of a vector layer, bottom first.
+#[derive(Clone, Debug, Default)]
+pub struct VectorLayer {
+    pub strokes: Vec<VectorStroke>,
+}
+
+impl VectorLayer {
+    /// Stroke and sample index closest to `pos` within `radius`; the topmost stroke wins ties.
+    pub fn hit_test(&self, pos: Vec2, radius: f32) -> Option<(usize, usize)> {
+        let mut best = None;
+        let mut best_dist = radius;
+        for (s, stroke) in self.strokes.iter().enumerate().rev() {
+            for (i, point) in stroke.points.iter().enumerate() {
+                let dist = distance(point.0, pos);
+                if dist < best_dist {
+                    best_dist = dist;
+                    best = Some((s, i));
+                }
+            }
+        }
+        best
+    }
+}
@@ -14,10 +14,15 @@ pub use app::state::{
 };
 pub use app::{PaintBackend, PainterApp, parse_backend_arg};
 
-/// Launch the native egui application.
+/// Launch the native egui application, or the headless stress test with `--bench`.
 fn main() -> eframe::Result<()> {
     env_logger::init();
 
+    if std::env::args().any(|arg| arg == "--bench") {
+        app::bench::run();
+        return Ok(());
+    }
+
     match parse_backend_arg() {
         PaintBackend::Cpu => {
             let options = eframe::NativeOptions {
@@ -25,12 +30,9 @@ fn main() -> eframe::Result<()> {
                 ..Default::default()
             };
             eframe::run_native(
-                "Rust Dab Painter",
+                app::painter::APP_TITLE,
                 options,
-                Box::new(|cc| {
-                    styling::apply_global_style(&cc.egui_ctx);
-                    Ok(Box::new(PainterApp::new(cc)))
-                }),
+                Box::new(|cc| Ok(Box::new(PainterApp::new(cc)))),
             )
         }
     }
@@ -1,5 +1,9 @@
 use eframe::egui::{self, Color32, Painter, Pos2, Stroke, Shape};
 use crate::utils::vector::Vec2;
+use spans::LassoSpans;
+use transform::Affine;
+use std::sync::OnceLock;
+mod spans;
 pub mod transform;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -7,6 +11,71 @@ pub enum SelectionType {
     Rectangle,
     Circle,
     Lasso,
+    /// Lasso built from clicked vertices instead of a freehand drag.
+    Polygon,
+}
+
+/// Where a stroked selection outline sits relative to the selection edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrokePosition {
+    Inside,
+    Center,
+    Outside,
+}
+
+impl StrokePosition {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StrokePosition::Inside => "Inside",
+            StrokePosition::Center => "Center",
+            StrokePosition::Outside => "Outside",
+        }
+    }
+}
+
+/// Modifier keys held while dragging out a new rectangle or circle selection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DragModifiers {
+    /// Keep the rectangle square. Circles are always round.
+    pub constrain: bool,
+    /// Grow the shape from where the drag started instead of from its corner.
+    pub from_center: bool,
+}
+
+/// Grid that new rectangle and circle selections and transform moves snap to, so fills and
+/// pasted pixels don't land on half pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PixelSnap {
+    #[default]
+    Off,
+    /// Whole pixels.
+    Pixels,
+    /// Even pixel boundaries, for pixel art drawn at 2x.
+    EvenPixels,
+}
+
+impl PixelSnap {
+    pub const ALL: [PixelSnap; 3] = [PixelSnap::Off, PixelSnap::Pixels, PixelSnap::EvenPixels];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PixelSnap::Off => "Off",
+            PixelSnap::Pixels => "Whole pixels",
+            PixelSnap::EvenPixels => "Even pixels",
+        }
+    }
+
+    pub fn snap(self, v: f32) -> f32 {
+        match self {
+            PixelSnap::Off => v,
+            PixelSnap::Pixels => v.round(),
+            PixelSnap::EvenPixels => (v / 2.0).round() * 2.0,
+        }
+    }
+
+    pub fn snap_point(self, p: Vec2) -> Vec2 {
+        Vec2::new(self.snap(p.x), self.snap(p.y))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -16,11 +85,171 @@ pub enum SelectionShape {
     Lasso { points: Vec<Vec2> },
 }
 
+impl SelectionShape {
+    fn contains(&self, p: Vec2) -> bool {
+        match self {
+            SelectionShape::Rectangle { start, end } => {
+                let x0 = start.x.min(end.x);
+                let x1 = start.x.max(end.x);
+                let y0 = start.y.min(end.y);
+                let y1 = start.y.max(end.y);
+                p.x >= x0 && p.x <= x1 && p.y >= y0 && p.y <= y1
+            }
+            SelectionShape::Circle { center, radius } => {
+                let dx = p.x - center.x;
+                let dy = p.y - center.y;
+                dx * dx + dy * dy <= radius * radius
+            }
+            SelectionShape::Lasso { points } => {
+                if points.len() < 3 { return false; }
+                let mut inside = false;
+                let mut j = points.len() - 1;
+                for i in 0..points.len() {
+                    if (points[i].y > p.y) != (points[j].y > p.y) &&
+                        p.x < (points[j].x - points[i].x) * (p.y - points[i].y) / (points[j].y - points[i].y) + points[i].x {
+                        inside = !inside;
+                    }
+                    j = i;
+                }
+                inside
+            }
+        }
+    }
+
+    /// Inside test answered from the lasso's row spans when they cover `p`.
+    fn inside(&self, p: Vec2, spans: Option<&LassoSpans>) -> bool {
+        spans.and_then(|spans| spans.contains(p)).unwrap_or_else(|| self.contains(p))
+    }
+
+    /// Distance to the edge, positive inside the shape and negative outside.
+    fn signed_distance(&self, p: Vec2, spans: Option<&LassoSpans>) -> f32 {
+        match self {
+            SelectionShape::Rectangle { start, end } => {
+                let dx = (start.x.min(end.x) - p.x).max(p.x - start.x.max(end.x));
+                let dy = (start.y.min(end.y) - p.y).max(p.y - start.y.max(end.y));
+                if dx <= 0.0 && dy <= 0.0 {
+                    -dx.max(dy)
+                } else {
+                    -Vec2::new(dx.max(0.0), dy.max(0.0)).length()
+                }
+            }
+            SelectionShape::Circle { center, radius } => *radius - (p - *center).length(),
+            SelectionShape::Lasso { .. } => {
+                let d = self.distance_to_edge(p);
+                if self.inside(p, spans) { d } else { -d }
+            }
+        }
+    }
+
+    /// Fraction of a 4x4 grid of sub-pixel samples around `p` that fall inside the shape.
+    fn supersampled_coverage(&self, p: Vec2, spans: Option<&LassoSpans>) -> f32 {
+        const N: usize = 4;
+        let mut inside = 0;
+        for sy in 0..N {
+            for sx in 0..N {
+                let ox = (sx as f32 + 0.5) / N as f32 - 0.5;
+                let oy = (sy as f32 + 0.5) / N as f32 - 0.5;
+                if self.inside(Vec2::new(p.x + ox, p.y + oy), spans) {
+                    inside += 1;
+                }
+            }
+        }
+        inside as f32 / (N * N) as f32
+    }
+
+    /// Axis-aligned bounds as (min, max).
+    fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            SelectionShape::Rectangle { start, end } => (
+                Vec2::new(start.x.min(end.x), start.y.min(end.y)),
+                Vec2::new(start.x.max(end.x), start.y.max(end.y)),
+            ),
+            SelectionShape::Circle { center, radius } => (
+                Vec2::new(center.x - radius, center.y - radius),
+                Vec2::new(center.x + radius, center.y + radius),
+            ),
+            SelectionShape::Lasso { points } => points.iter().fold(
+                (Vec2::new(f32::MAX, f32::MAX), Vec2::new(f32::MIN, f32::MIN)),
+                |(min, max), p| {
+                    (Vec2::new(min.x.min(p.x), min.y.min(p.y)), Vec2::new(max.x.max(p.x), max.y.max(p.y)))
+                },
+            ),
+        }
+    }
+
+    fn translate(&mut self, delta: Vec2) {
+        match self {
+            SelectionShape::Rectangle { start, end } => {
+                *start = *start + delta;
+                *end = *end + delta;
+            }
+            SelectionShape::Circle { center, .. } => *center = *center + delta,
+            SelectionShape::Lasso { points } => {
+                for p in points {
+                    *p = *p + delta;
+                }
+            }
+        }
+    }
+
+    /// Distance from a point inside the shape to its nearest edge.
+    fn distance_to_edge(&self, p: Vec2) -> f32 {
+        match self {
+            SelectionShape::Rectangle { start, end } => {
+                let x0 = start.x.min(end.x);
+                let x1 = start.x.max(end.x);
+                let y0 = start.y.min(end.y);
+                let y1 = start.y.max(end.y);
+                (p.x - x0).min(x1 - p.x).min(p.y - y0).min(y1 - p.y).max(0.0)
+            }
+            SelectionShape::Circle { center, radius } => (*radius - (p - *center).length()).max(0.0),
+            SelectionShape::Lasso { points } => {
+                let mut best = f32::MAX;
+                let mut j = points.len().saturating_sub(1);
+                for i in 0..points.len() {
+                    best = best.min(distance_to_segment(p, points[j], points[i]));
+                    j = i;
+                }
+                best
+            }
+        }
+    }
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len_sq = ab.x * ab.x + ab.y * ab.y;
+    if len_sq <= f32::EPSILON {
+        return (p - a).length();
+    }
+    let t = (((p.x - a.x) * ab.x + (p.y - a.y) * ab.y) / len_sq).clamp(0.0, 1.0);
+    (p - (a + ab * t)).length()
+}
+
+/// A selection stored on the document under a name so it can be restored later.
+#[derive(Clone, Debug)]
+pub struct SelectionChannel {
+    pub name: String,
+    shape: SelectionShape,
+    feather: f32,
+}
+
 pub struct SelectionManager {
-    pub current_shape: Option<SelectionShape>,
+    current_shape: Option<SelectionShape>,
     pub is_dragging: bool,
-    // For now we just visualize the creation. 
-    // In a full implementation we would have a committed mask here.
+    /// Width in pixels of the soft falloff inside the selection edge.
+    feather: f32,
+    /// Row spans of a lasso selection, built on the first pixel query after the selection changed.
+    lasso_spans: OnceLock<Option<LassoSpans>>,
+    /// Vertices of a polygonal lasso that is still being placed.
+    pub pending_polygon: Option<Vec<Vec2>>,
+    /// Last pointer position, used for the rubber-band segment while placing vertices.
+    pub polygon_cursor: Option<Vec2>,
+    /// Last pointer position while dragging the outline of an existing selection.
+    moving_from: Option<Vec2>,
+    /// Where the drag creating the current rectangle or circle started.
+    anchor: Vec2,
+    pub pixel_snap: PixelSnap,
 }
 
 impl SelectionManager {
@@ -28,35 +257,116 @@ impl SelectionManager {
         Self {
             current_shape: None,
             is_dragging: false,
+            feather: 0.0,
+            lasso_spans: OnceLock::new(),
+            pending_polygon: None,
+            polygon_cursor: None,
+            moving_from: None,
+            anchor: Vec2::new(0.0, 0.0),
+            pixel_snap: PixelSnap::Off,
         }
     }
 
     pub fn start_selection(&mut self, pos: Vec2, sel_type: SelectionType) {
         self.is_dragging = true;
+        if sel_type != SelectionType::Polygon {
+            self.cancel_polygon();
+        }
+        // Pressing inside the current selection drags its outline instead of starting a new one
+        if !self.is_placing_polygon() && self.has_selection() && self.contains(pos) {
+            self.moving_from = Some(pos);
+            return;
+        }
+        self.anchor = self.pixel_snap.snap_point(pos);
+        let anchor = self.anchor;
         match sel_type {
             SelectionType::Rectangle => {
-                self.current_shape = Some(SelectionShape::Rectangle { start: pos, end: pos });
+                *self.shape_mut() = Some(SelectionShape::Rectangle { start: anchor, end: anchor });
             }
             SelectionType::Circle => {
-                self.current_shape = Some(SelectionShape::Circle { center: pos, radius: 0.0 });
+                *self.shape_mut() = Some(SelectionShape::Circle { center: anchor, radius: 0.0 });
             }
             SelectionType::Lasso => {
-                self.current_shape = Some(SelectionShape::Lasso { points: vec![pos] });
+                *self.shape_mut() = Some(SelectionShape::Lasso { points: vec![pos] });
+            }
+            SelectionType::Polygon => {
+                // Vertices are placed with individual clicks rather than a drag
+                self.is_dragging = false;
+                self.add_polygon_vertex(pos);
             }
         }
     }
 
-    pub fn update_selection(&mut self, pos: Vec2) {
+    /// Drop a polygon vertex, starting a new polygon if none is being placed.
+    pub fn add_polygon_vertex(&mut self, pos: Vec2) {
+        let points = self.pending_polygon.get_or_insert_with(Vec::new);
+        if points.is_empty() {
+            self.current_shape = None;
+            self.lasso_spans = OnceLock::new();
+        }
+        // The second click of a double-click lands on the same spot
+        if points.last().is_some_and(|last| (*last - pos).length() < 2.0) {
+            return;
+        }
+        points.push(pos);
+    }
+
+    /// Remove the most recently placed vertex, cancelling when none are left.
+    pub fn remove_last_polygon_vertex(&mut self) {
+        if let Some(points) = &mut self.pending_polygon {
+            points.pop();
+            if points.is_empty() {
+                self.cancel_polygon();
+            }
+        }
+    }
+
+    /// Turn the placed vertices into the active selection (needs at least three).
+    pub fn close_polygon(&mut self) {
+        if let Some(points) = self.pending_polygon.take().filter(|points| points.len() >= 3) {
+            *self.shape_mut() = Some(SelectionShape::Lasso { points });
+        }
+        self.polygon_cursor = None;
+    }
+
+    pub fn cancel_polygon(&mut self) {
+        self.pending_polygon = None;
+        self.polygon_cursor = None;
+    }
+
+    pub fn is_placing_polygon(&self) -> bool {
+        self.pending_polygon.is_some()
+    }
+
+    pub fn update_selection(&mut self, pos: Vec2, modifiers: DragModifiers) {
         if !self.is_dragging {
             return;
         }
-        if let Some(shape) = &mut self.current_shape {
+        if let Some(from) = self.moving_from {
+            self.translate(pos - from);
+            self.moving_from = Some(pos);
+            return;
+        }
+        let (anchor, snap) = (self.anchor, self.pixel_snap);
+        let delta = snap.snap_point(pos) - anchor;
+        // Corner offset of the square the shape fits in
+        let side = delta.x.abs().max(delta.y.abs());
+        let square = Vec2::new(side.copysign(delta.x), side.copysign(delta.y));
+        if let Some(shape) = self.shape_mut() {
             match shape {
-                SelectionShape::Rectangle { start: _, end } => {
-                    *end = pos;
+                SelectionShape::Rectangle { start, end } => {
+                    let delta = if modifiers.constrain { square } else { delta };
+                    *start = if modifiers.from_center { anchor - delta } else { anchor };
+                    *end = anchor + delta;
                 }
                 SelectionShape::Circle { center, radius } => {
-                    *radius = (*center - pos).length();
+                    if modifiers.from_center {
+                        *center = anchor;
+                        *radius = snap.snap(delta.length());
+                    } else {
+                        *center = anchor + square * 0.5;
+                        *radius = side * 0.5;
+                    }
                 }
                 SelectionShape::Lasso { points } => {
                     // Add point if it's far enough from the last one to avoid too many points
@@ -72,49 +382,152 @@ impl SelectionManager {
         }
     }
 
+    /// Width and height of a rectangle or circle selection being dragged out.
+    /// Whether a drag is moving the selection outline rather than drawing a new one.
+    pub fn is_moving(&self) -> bool {
+        self.moving_from.is_some()
+    }
+
+    pub fn drag_size(&self) -> Option<Vec2> {
+        if !self.is_dragging || self.moving_from.is_some() {
+            return None;
+        }
+        match self.current_shape.as_ref()? {
+            SelectionShape::Rectangle { start, end } => Some(Vec2::new((end.x - start.x).abs(), (end.y - start.y).abs())),
+            SelectionShape::Circle { radius, .. } => Some(Vec2::new(radius * 2.0, radius * 2.0)),
+            SelectionShape::Lasso { .. } => None,
+        }
+    }
+
     pub fn end_selection(&mut self) {
         self.is_dragging = false;
+        self.moving_from = None;
+    }
+
+    /// Move the selection outline without touching any pixels.
+    pub fn translate(&mut self, delta: Vec2) {
+        if let Some(shape) = self.shape_mut() {
+            shape.translate(delta);
+        }
+    }
+
+    /// Capture the current selection, including its feather, as a named channel.
+    pub fn save_channel(&self, name: String) -> Option<SelectionChannel> {
+        let shape = self.current_shape.clone()?;
+        Some(SelectionChannel { name, shape, feather: self.feather })
+    }
+
+    /// A standalone copy of the current selection, for background jobs that query its coverage.
+    pub fn detached(&self) -> Option<SelectionManager> {
+        let channel = self.save_channel(String::new())?;
+        let mut copy = Self::new();
+        copy.load_channel(&channel);
+        Some(copy)
+    }
+
+    /// Replace the current selection with a stored channel.
+    pub fn load_channel(&mut self, channel: &SelectionChannel) {
+        self.clear_selection();
+        *self.shape_mut() = Some(channel.shape.clone());
+        self.set_feather(channel.feather);
     }
 
     pub fn clear_selection(&mut self) {
-        self.current_shape = None;
+        *self.shape_mut() = None;
         self.is_dragging = false;
+        self.moving_from = None;
+        self.cancel_polygon();
+    }
+
+    pub fn shape(&self) -> Option<&SelectionShape> {
+        self.current_shape.as_ref()
+    }
+
+    /// Put in a different selection shape, returning the one it replaces.
+    pub fn replace_shape(&mut self, shape: Option<SelectionShape>) -> Option<SelectionShape> {
+        std::mem::replace(self.shape_mut(), shape)
+    }
+
+    /// The shape for editing; drops the cached row spans, which no longer match it.
+    fn shape_mut(&mut self) -> &mut Option<SelectionShape> {
+        self.lasso_spans = OnceLock::new();
+        &mut self.current_shape
+    }
+
+    pub fn feather(&self) -> f32 {
+        self.feather
+    }
+
+    pub fn set_feather(&mut self, feather: f32) {
+        if feather != self.feather {
+            self.feather = feather;
+            // The spans mark pixels within the feather of the edge
+            self.lasso_spans = OnceLock::new();
+        }
+    }
+
+    /// Row spans of the current lasso, built on first use.
+    fn lasso_spans(&self) -> Option<&LassoSpans> {
+        self.lasso_spans
+            .get_or_init(|| match &self.current_shape {
+                Some(SelectionShape::Lasso { points }) if points.len() >= 3 => Some(LassoSpans::new(points, self.feather.max(1.0))),
+                _ => None,
+            })
+            .as_ref()
     }
 
     pub fn contains(&self, p: Vec2) -> bool {
-        if let Some(shape) = &self.current_shape {
-            match shape {
-                SelectionShape::Rectangle { start, end } => {
-                    let x0 = start.x.min(end.x);
-                    let x1 = start.x.max(end.x);
-                    let y0 = start.y.min(end.y);
-                    let y1 = start.y.max(end.y);
-                    p.x >= x0 && p.x <= x1 && p.y >= y0 && p.y <= y1
-                }
-                SelectionShape::Circle { center, radius } => {
-                    let dx = p.x - center.x;
-                    let dy = p.y - center.y;
-                    dx * dx + dy * dy <= radius * radius
-                }
-                SelectionShape::Lasso { points } => {
-                    if points.len() < 3 { return false; }
-                    let mut inside = false;
-                    let mut j = points.len() - 1;
-                    for i in 0..points.len() {
-                        if (points[i].y > p.y) != (points[j].y > p.y) &&
-                            p.x < (points[j].x - points[i].x) * (p.y - points[i].y) / (points[j].y - points[i].y) + points[i].x {
-                            inside = !inside;
-                        }
-                        j = i;
-                    }
-                    inside
-                }
-            }
-        } else {
-            true
+        match &self.current_shape {
+            Some(shape) => shape.inside(p, self.lasso_spans()),
+            None => true,
         }
     }
 
+    /// Anti-aliased coverage (0..1) of an outline of `width` pixels traced along the selection edge.
+    pub fn stroke_coverage(&self, p: Vec2, width: f32, position: StrokePosition) -> f32 {
+        let Some(shape) = &self.current_shape else {
+            return 0.0;
+        };
+        let d = shape.signed_distance(p, self.lasso_spans());
+        let (lo, hi) = match position {
+            StrokePosition::Inside => (0.0, width),
+            StrokePosition::Center => (-width * 0.5, width * 0.5),
+            StrokePosition::Outside => (-width, 0.0),
+        };
+        ((d - lo).min(hi - d) + 0.5).clamp(0.0, 1.0)
+    }
+
+    /// Pixel bounds of the selection grown by `margin`, as (min, max).
+    pub fn bounds(&self, margin: f32) -> Option<(Vec2, Vec2)> {
+        let (min, max) = self.current_shape.as_ref()?.bounds();
+        Some((
+            Vec2::new(min.x - margin, min.y - margin),
+            Vec2::new(max.x + margin, max.y + margin),
+        ))
+    }
+
+    /// Soft coverage (0..1) of the pixel centered at `p`: anti-aliased along the edge
+    /// and fading in over `feather` pixels.
+    pub fn coverage(&self, p: Vec2) -> f32 {
+        let Some(shape) = &self.current_shape else {
+            return 1.0;
+        };
+        let spans = self.lasso_spans();
+        // Pixels away from a lasso's outline (and its feather) are fully in or out
+        if let Some(spans) = spans.filter(|spans| !spans.near_edge(p)) {
+            return if shape.inside(p, Some(spans)) { 1.0 } else { 0.0 };
+        }
+        let d = shape.signed_distance(p, spans);
+        if d <= -1.0 {
+            return 0.0;
+        }
+        let edge = if d >= 1.0 { 1.0 } else { shape.supersampled_coverage(p, spans) };
+        if self.feather <= 0.0 {
+            return edge;
+        }
+        edge * (d / self.feather).clamp(0.0, 1.0)
+    }
+
     pub fn has_selection(&self) -> bool {
         self.current_shape.is_some()
     }
@@ -124,8 +537,8 @@ impl SelectionManager {
             let to_screen = |v: Vec2| -> Pos2 {
                 let mut p = v;
                 if let Some(info) = transform {
-                    if let Some(bounds) = info.bounds {
-                        let center = Vec2::new(bounds.center().x, bounds.center().y);
+                    if info.bounds.is_some() {
+                        let center = info.center();
                         let (sin_r, cos_r) = info.rotation.sin_cos();
                         
                         let dx = p.x - center.x;
@@ -195,10 +608,28 @@ impl SelectionManager {
                 }
             }
         }
+
+        if let Some(points) = &self.pending_polygon {
+            let to_screen = |v: Vec2| Pos2::new(offset.x + v.x * zoom, offset.y + v.y * zoom);
+            let mut line: Vec<Pos2> = points.iter().map(|p| to_screen(*p)).collect();
+            // Rubber band from the last vertex to the pointer
+            if let Some(cursor) = self.polygon_cursor {
+                line.push(to_screen(cursor));
+            }
+            if line.len() >= 2 {
+                painter.add(Shape::line(line.clone(), Stroke::new(1.0, Color32::BLACK)));
+                painter.add(Shape::dashed_line(&line, Stroke::new(1.0, Color32::WHITE), 5.0, 5.0));
+            }
+            for p in points {
+                let pos = to_screen(*p);
+                painter.circle(pos, 3.0, Color32::WHITE, Stroke::new(1.0, Color32::BLACK));
+            }
+        }
     }
 
-    pub fn apply_transform(&mut self, offset: Vec2, rotation: f32, scale: Vec2, center: Vec2) {
-        if let Some(shape) = &mut self.current_shape {
+    pub fn apply_transform(&mut self, affine: Affine) {
+        let Affine { offset, rotation, scale, center } = affine;
+        if let Some(shape) = self.shape_mut() {
             let (sin_r, cos_r) = rotation.sin_cos();
             
             let transform_point = |p: Vec2| -> Vec2 {
@@ -0,0 +1,116 @@
+//! Row-span cache of a lasso selection.
+//!
+//! A point-in-polygon test walks the whole outline, and masked painting asks about every
+//! pixel under a dab. The cache records where the outline crosses each eighth of a pixel
+//! row, and which stretches of each pixel row lie near the outline, so inside tests become
+//! a binary search and pixels away from the edge skip the distance pass altogether.
+use crate::utils::vector::Vec2;
+
+/// Sub-rows per pixel row. Pixel edges and centers and the 4x4 coverage samples all fall on one.
+const SUBROWS: f32 = 8.0;
+
+pub(crate) struct LassoSpans {
+    /// Sub-row index of `crossings[0]`; sub-row `k` sits at `y = k / SUBROWS`.
+    first_subrow: i64,
+    /// Sorted x positions where the outline crosses each sub-row.
+    crossings: Vec<Vec<f32>>,
+    /// Pixel row of `near[0]`.
+    first_row: i64,
+    /// Merged x ranges of each pixel row that lie within the build's `reach` of the outline.
+    near: Vec<Vec<(f32, f32)>>,
+}
+
+impl LassoSpans {
+    pub fn new(points: &[Vec2], reach: f32) -> Self {
+        let (min_y, max_y) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.y), hi.max(p.y)));
+        let edges = || (0..points.len()).map(|i| (points[(i + points.len() - 1) % points.len()], points[i]));
+
+        let first_subrow = (min_y * SUBROWS).floor() as i64 - 1;
+        let mut crossings = vec![Vec::new(); ((max_y * SUBROWS).ceil() as i64 + 2 - first_subrow).max(0) as usize];
+        for (a, b) in edges() {
+            let lo = (a.y.min(b.y) * SUBROWS).floor() as i64 - 1;
+            let hi = (a.y.max(b.y) * SUBROWS).ceil() as i64 + 1;
+            for k in lo.max(first_subrow)..=hi {
+                let y = k as f32 / SUBROWS;
+                // Same test and arithmetic as the polygon walk, so answers match it exactly
+                if (b.y > y) != (a.y > y) {
+                    let x = (a.x - b.x) * (y - b.y) / (a.y - b.y) + b.x;
+                    if let Some(row) = crossings.get_mut((k - first_subrow) as usize) {
+                        row.push(x);
+                    }
+                }
+            }
+        }
+        for row in &mut crossings {
+            row.sort_by(f32::total_cmp);
+        }
+
+        // Pad the bands a little so rounding never drops a pixel that is actually near
+        let pad = reach + 0.5;
+        let first_row = (min_y - pad).floor() as i64 - 1;
+        let mut near = vec![Vec::new(); ((max_y + pad).floor() as i64 + 2 - first_row).max(0) as usize];
+        for (a, b) in edges() {
+            let lo = (a.y.min(b.y) - pad).floor() as i64 - 1;
+            let hi = (a.y.max(b.y) + pad).floor() as i64 + 1;
+            for r in lo..=hi {
+                let Some((x0, x1)) = x_extent_in_band(a, b, r as f32 - pad, r as f32 + 1.0 + pad) else {
+                    continue;
+                };
+                if let Some(row) = near.get_mut((r - first_row) as usize) {
+                    row.push((x0 - pad, x1 + pad));
+                }
+            }
+        }
+        for row in &mut near {
+            row.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut merged: Vec<(f32, f32)> = Vec::with_capacity(row.len());
+            for &(x0, x1) in row.iter() {
+                match merged.last_mut() {
+                    Some(last) if x0 <= last.1 => last.1 = last.1.max(x1),
+                    _ => merged.push((x0, x1)),
+                }
+            }
+            *row = merged;
+        }
+
+        Self { first_subrow, crossings, first_row, near }
+    }
+
+    /// Whether `p` is inside the lasso, or `None` when `p.y` isn't on a cached sub-row.
+    pub fn contains(&self, p: Vec2) -> Option<bool> {
+        let k = p.y * SUBROWS;
+        if k.fract() != 0.0 || !k.is_finite() {
+            return None;
+        }
+        let Some(row) = usize::try_from(k as i64 - self.first_subrow).ok().and_then(|idx| self.crossings.get(idx)) else {
+            return Some(false);
+        };
+        // Inside when an odd number of crossings lie to the right
+        let right = row.len() - row.partition_point(|&x| x <= p.x);
+        Some(right % 2 == 1)
+    }
+
+    /// False only when `p` is at least `reach` away from the outline.
+    pub fn near_edge(&self, p: Vec2) -> bool {
+        let Some(row) = usize::try_from(p.y.floor() as i64 - self.first_row).ok().and_then(|idx| self.near.get(idx)) else {
+            return false;
+        };
+        let idx = row.partition_point(|&(x0, _)| x0 <= p.x);
+        idx > 0 && p.x <= row[idx - 1].1
+    }
+}
+
+/// X range of the part of segment `a`-`b` whose y lies in `y0..=y1`, if any.
+fn x_extent_in_band(a: Vec2, b: Vec2, y0: f32, y1: f32) -> Option<(f32, f32)> {
+    if a.y == b.y {
+        return (y0..=y1).contains(&a.y).then(|| (a.x.min(b.x), a.x.max(b.x)));
+    }
+    let t0 = (y0 - a.y) / (b.y - a.y);
+    let t1 = (y1 - a.y) / (b.y - a.y);
+    let (lo, hi) = (t0.min(t1).max(0.0), t0.max(t1).min(1.0));
+    if lo > hi {
+        return None;
+    }
+    let (xa, xb) = (a.x + (b.x - a.x) * lo, a.x + (b.x - a.x) * hi);
+    Some((xa.min(xb), xa.max(xb)))
+}
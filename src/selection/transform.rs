@@ -1,5 +1,12 @@
 use crate::utils::vector::Vec2;
-use eframe::egui::Rect;
+use eframe::egui::{Pos2, Rect, pos2};
+
+/// Radius of the drawn transform handles, in screen pixels.
+pub const HANDLE_RADIUS: f32 = 4.0;
+/// How close to a handle or the pivot a press has to land, in screen pixels.
+pub const HANDLE_HIT_RADIUS: f32 = 8.0;
+/// Presses this close to a corner but outside the box rotate, in screen pixels.
+pub const ROTATE_HIT_RADIUS: f32 = 28.0;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TransformState {
@@ -7,6 +14,39 @@ pub enum TransformState {
     Moving,
     Rotating,
     Scaling(usize), // Index of the handle (0-7)
+    MovingPivot,
+}
+
+/// Resampling filter used when transformed pixels land between source pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+}
+
+impl Interpolation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Interpolation::Nearest => "Nearest",
+            Interpolation::Bilinear => "Bilinear",
+        }
+    }
+}
+
+/// Placement of transformed pixels: scaled and rotated around `center`, then moved by `offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub offset: Vec2,
+    pub rotation: f32,
+    pub scale: Vec2,
+    pub center: Vec2,
+}
+
+impl Affine {
+    /// A plain move by `offset`.
+    pub fn translation(offset: Vec2) -> Self {
+        Self { offset, rotation: 0.0, scale: Vec2::new(1.0, 1.0), center: Vec2::new(0.0, 0.0) }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -16,6 +56,8 @@ pub struct TransformInfo {
     pub rotation: f32,
     pub scale: Vec2,
     pub bounds: Option<Rect>,
+    /// Custom point (in untransformed canvas space) that rotation and scale operate around.
+    pub pivot: Option<Vec2>,
     pub state: TransformState,
 }
 
@@ -27,95 +69,136 @@ impl Default for TransformInfo {
             rotation: 0.0,
             scale: Vec2 { x: 1.0, y: 1.0 },
             bounds: None,
+            pivot: None,
             state: TransformState::None,
         }
     }
 }
 
 impl TransformInfo {
-    pub fn hit_test(&self, pos: Vec2, zoom: f32) -> TransformState {
-        if let Some(bounds) = self.bounds {
-            let center = Vec2::new(bounds.center().x, bounds.center().y);
-
-            // Transform the bounds corners
-            let corners = [
-                bounds.min, // Top-Left
-                eframe::egui::pos2(bounds.center().x, bounds.min.y), // Top-Center
-                eframe::egui::pos2(bounds.max.x, bounds.min.y), // Top-Right
-                eframe::egui::pos2(bounds.max.x, bounds.center().y), // Right-Center
-                bounds.max, // Bottom-Right
-                eframe::egui::pos2(bounds.center().x, bounds.max.y), // Bottom-Center
-                eframe::egui::pos2(bounds.min.x, bounds.max.y), // Bottom-Left
-                eframe::egui::pos2(bounds.min.x, bounds.center().y), // Left-Center
-            ];
-
-            let (sin_r, cos_r) = self.rotation.sin_cos();
-            let handle_radius = 10.0 / zoom; // Adjust handle size by zoom
-
-            for (i, corner) in corners.iter().enumerate() {
-                // Apply transform to corner
-                let dx = corner.x - center.x;
-                let dy = corner.y - center.y;
-
-                let sx = dx * self.scale.x;
-                let sy = dy * self.scale.y;
-
-                let rx = sx * cos_r - sy * sin_r;
-                let ry = sx * sin_r + sy * cos_r;
-
-                let tx = rx + center.x + self.offset.x;
-                let ty = ry + center.y + self.offset.y;
-
-                let dist = ((pos.x - tx).powi(2) + (pos.y - ty).powi(2)).sqrt();
-                if dist < handle_radius {
-                    return TransformState::Scaling(i);
-                }
-            }
-
-            // Check if inside for moving
-            // Inverse transform the mouse pos to check against original AABB
-            let dx = pos.x - (center.x + self.offset.x);
-            let dy = pos.y - (center.y + self.offset.y);
-
-            let rx = dx * cos_r + dy * sin_r; // Inverse rotate
-            let ry = -dx * sin_r + dy * cos_r;
+    /// Pivot of the transform: the custom pivot if set, otherwise the bounds center.
+    pub fn center(&self) -> Vec2 {
+        match (self.pivot, self.bounds) {
+            (Some(pivot), _) => pivot,
+            (None, Some(bounds)) => Vec2::new(bounds.center().x, bounds.center().y),
+            (None, None) => Vec2::new(0.0, 0.0),
+        }
+    }
 
-            let sx = rx / self.scale.x; // Inverse scale
-            let sy = ry / self.scale.y;
+    /// The placement this transform applies, around its pivot.
+    pub fn affine(&self) -> Affine {
+        Affine { offset: self.offset, rotation: self.rotation, scale: self.scale, center: self.center() }
+    }
 
-            let lx = sx + center.x;
-            let ly = sy + center.y;
+    /// Map an untransformed canvas point through scale, rotation, and offset.
+    pub fn transform_point(&self, p: Vec2) -> Vec2 {
+        let center = self.center();
+        let (sin_r, cos_r) = self.rotation.sin_cos();
+        let sx = (p.x - center.x) * self.scale.x;
+        let sy = (p.y - center.y) * self.scale.y;
+        Vec2::new(
+            sx * cos_r - sy * sin_r + center.x + self.offset.x,
+            sx * sin_r + sy * cos_r + center.y + self.offset.y,
+        )
+    }
 
-            if bounds.contains(eframe::egui::pos2(lx, ly)) {
-                return TransformState::Moving;
-            }
+    /// Map a transformed canvas point back to untransformed space.
+    pub fn inverse_transform_point(&self, p: Vec2) -> Vec2 {
+        let center = self.center();
+        let (sin_r, cos_r) = self.rotation.sin_cos();
+        let dx = p.x - center.x - self.offset.x;
+        let dy = p.y - center.y - self.offset.y;
+        let rx = dx * cos_r + dy * sin_r;
+        let ry = -dx * sin_r + dy * cos_r;
+        Vec2::new(rx / self.scale.x + center.x, ry / self.scale.y + center.y)
+    }
 
-            // Check for rotation (outside corners)
-            for (i, corner) in corners.iter().enumerate() {
-                // Only corners: 0, 2, 4, 6
-                if i % 2 != 0 {
-                    continue;
-                }
+    /// Place the pivot under the transformed position `target` without moving the content.
+    pub fn move_pivot(&mut self, target: Vec2) {
+        let old = self.center();
+        let new = self.inverse_transform_point(target);
+        // Compensate the offset so R*S*(p - c) + c + offset stays the same for every p
+        let d = new - old;
+        let (sin_r, cos_r) = self.rotation.sin_cos();
+        let sx = d.x * self.scale.x;
+        let sy = d.y * self.scale.y;
+        self.offset = Vec2::new(
+            self.offset.x + sx * cos_r - sy * sin_r - d.x,
+            self.offset.y + sx * sin_r + sy * cos_r - d.y,
+        );
+        self.pivot = Some(new);
+    }
 
-                let dx = corner.x - center.x;
-                let dy = corner.y - center.y;
+    /// Handle `idx` (0-7, clockwise from the top-left corner) in untransformed canvas space.
+    pub fn handle_anchor(&self, idx: usize) -> Option<Pos2> {
+        let b = self.bounds?;
+        Some(match idx {
+            0 => b.min,
+            1 => pos2(b.center().x, b.min.y),
+            2 => pos2(b.max.x, b.min.y),
+            3 => pos2(b.max.x, b.center().y),
+            4 => b.max,
+            5 => pos2(b.center().x, b.max.y),
+            6 => pos2(b.min.x, b.max.y),
+            _ => pos2(b.min.x, b.center().y),
+        })
+    }
 
-                let sx = dx * self.scale.x;
-                let sy = dy * self.scale.y;
+    /// The eight handles after the transform, in canvas space.
+    pub fn handles(&self) -> Option<[Vec2; 8]> {
+        self.bounds?;
+        Some(std::array::from_fn(|i| {
+            let p = self.handle_anchor(i).unwrap_or_default();
+            self.transform_point(Vec2::new(p.x, p.y))
+        }))
+    }
 
-                let rx = sx * cos_r - sy * sin_r;
-                let ry = sx * sin_r + sy * cos_r;
+    /// Whether the edge handles are worth showing: they crowd the corners when the box is small on screen.
+    pub fn show_edge_handles(&self, to_screen: impl Fn(Vec2) -> Pos2) -> bool {
+        let Some(h) = self.handles() else {
+            return false;
+        };
+        let width = to_screen(h[0]).distance(to_screen(h[2]));
+        let height = to_screen(h[0]).distance(to_screen(h[6]));
+        width.min(height) > 4.0 * HANDLE_HIT_RADIUS
+    }
 
-                let tx = rx + center.x + self.offset.x;
-                let ty = ry + center.y + self.offset.y;
+    /// What a press at `screen_pos` grabs. `canvas_pos` is the same point in (unclamped) canvas
+    /// space and `to_screen` maps canvas points to the screen, so handle sizes stay constant
+    /// in screen pixels at any zoom or view rotation.
+    pub fn hit_test(&self, screen_pos: Pos2, canvas_pos: Vec2, to_screen: impl Fn(Vec2) -> Pos2) -> TransformState {
+        let Some(handles) = self.handles() else {
+            return TransformState::None;
+        };
+        let local = self.inverse_transform_point(canvas_pos);
+        let inside = self.bounds.is_some_and(|b| b.contains(pos2(local.x, local.y)));
+        // A box only a few pixels wide on screen is all handles, so its body wins there
+        let roomy = self.show_edge_handles(&to_screen);
+        if !roomy && inside {
+            return TransformState::Moving;
+        }
 
-                let dist = ((pos.x - tx).powi(2) + (pos.y - ty).powi(2)).sqrt();
-                if dist < handle_radius * 3.0 {
-                    // Larger radius for rotation
-                    return TransformState::Rotating;
-                }
+        let pivot = self.center() + self.offset;
+        if roomy && screen_pos.distance(to_screen(pivot)) < HANDLE_HIT_RADIUS {
+            return TransformState::MovingPivot;
+        }
+        for (i, &handle) in handles.iter().enumerate() {
+            if (roomy || i % 2 == 0) && screen_pos.distance(to_screen(handle)) < HANDLE_HIT_RADIUS {
+                return TransformState::Scaling(i);
             }
         }
+        if inside {
+            return TransformState::Moving;
+        }
+
+        // Just outside a corner rotates
+        let near_corner = handles
+            .iter()
+            .step_by(2)
+            .any(|&corner| screen_pos.distance(to_screen(corner)) < ROTATE_HIT_RADIUS);
+        if near_corner {
+            return TransformState::Rotating;
+        }
         TransformState::None
     }
 }
@@ -1,33 +1,29 @@
+pub mod theme;
+
 use eframe::egui;
+use theme::Theme;
 
-/// Apply a cohesive, modern dark theme with sharp accents and generous spacing.
-pub fn apply_global_style(ctx: &egui::Context) {
-    let mut visuals = egui::Visuals::dark();
-    visuals.panel_fill = egui::Color32::from_rgb(14, 16, 22);
-    visuals.window_fill = egui::Color32::from_rgb(18, 21, 30);
-    visuals.extreme_bg_color = egui::Color32::from_rgb(26, 30, 40);
-    visuals.widgets.inactive.bg_fill = egui::Color32::from_rgb(30, 34, 46);
-    visuals.widgets.hovered.bg_fill = egui::Color32::from_rgb(42, 64, 102);
-    visuals.widgets.active.bg_fill = egui::Color32::from_rgb(68, 118, 190);
-    visuals.widgets.inactive.fg_stroke.color = egui::Color32::from_rgb(220, 225, 235);
-    visuals.widgets.hovered.fg_stroke.color = egui::Color32::from_rgb(240, 244, 255);
-    visuals.selection.bg_fill = egui::Color32::from_rgb(90, 165, 255);
-    visuals.selection.stroke.color = egui::Color32::from_rgb(255, 255, 255);
+/// Apply `theme` with the app's sharp accents and generous spacing. Safe to call again at any
+/// time to switch themes live.
+pub fn apply_global_style(ctx: &egui::Context, theme: &Theme) {
+    let mut visuals = theme.visuals();
     visuals.window_rounding = egui::Rounding::same(12.0);
     visuals.widgets.inactive.rounding = egui::Rounding::same(10.0);
     visuals.widgets.hovered.rounding = egui::Rounding::same(10.0);
     visuals.widgets.active.rounding = egui::Rounding::same(10.0);
+    // Lighter shadows on light themes
+    let shadow_alpha = if visuals.dark_mode { 1.0 } else { 0.4 };
     visuals.popup_shadow = egui::Shadow {
         offset: egui::vec2(0.0, 6.0),
         blur: 24.0,
         spread: 0.0,
-        color: egui::Color32::from_rgba_premultiplied(0, 0, 0, 110),
+        color: egui::Color32::from_rgba_premultiplied(0, 0, 0, (110.0 * shadow_alpha) as u8),
     };
     visuals.window_shadow = egui::Shadow {
         offset: egui::vec2(0.0, 8.0),
         blur: 30.0,
         spread: 4.0,
-        color: egui::Color32::from_rgba_premultiplied(0, 0, 0, 140),
+        color: egui::Color32::from_rgba_premultiplied(0, 0, 0, (140.0 * shadow_alpha) as u8),
     };
 
     ctx.set_visuals(visuals);
@@ -38,4 +34,5 @@ pub fn apply_global_style(ctx: &egui::Context) {
     style.interaction.selectable_labels = true;
 
     ctx.set_style(style);
+    ctx.set_zoom_factor(theme.ui_scale);
 }
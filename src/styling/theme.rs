@@ -0,0 +1,210 @@
+//! UI theme: light, dark or custom colors, an accent, the UI scale and the canvas backdrop.
+//!
+//! Stored next to the brush tips as `key = value` lines, colors as `#RRGGBB`.
+use eframe::egui::{self, Color32};
+use std::path::Path;
+
+pub const FILE_NAME: &str = "theme.meta";
+pub const SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.75..=2.0;
+/// Accents offered as one-click swatches next to the color button.
+pub const ACCENT_PRESETS: [Color32; 6] = [
+    Color32::from_rgb(90, 165, 255),
+    Color32::from_rgb(60, 200, 180),
+    Color32::from_rgb(120, 200, 90),
+    Color32::from_rgb(255, 170, 60),
+    Color32::from_rgb(240, 90, 110),
+    Color32::from_rgb(170, 120, 255),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThemeMode {
+    Dark,
+    Light,
+    /// Panel and text colors picked by the user.
+    Custom,
+}
+
+impl ThemeMode {
+    pub const ALL: [Self; 3] = [Self::Dark, Self::Light, Self::Custom];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+            Self::Custom => "Custom",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::Light => "light",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub mode: ThemeMode,
+    /// Selection, hover and active widget color.
+    pub accent: Color32,
+    /// egui zoom factor applied to the whole UI.
+    pub ui_scale: f32,
+    /// Fill behind the canvas; `None` uses the panel color.
+    pub backdrop: Option<Color32>,
+    pub custom_panel: Color32,
+    pub custom_text: Color32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            mode: ThemeMode::Dark,
+            accent: ACCENT_PRESETS[0],
+            ui_scale: 1.0,
+            backdrop: None,
+            custom_panel: Color32::from_rgb(36, 32, 40),
+            custom_text: Color32::from_rgb(235, 228, 220),
+        }
+    }
+}
+
+impl Theme {
+    pub fn load(dir: &Path) -> Self {
+        let mut theme = Self::default();
+        let Ok(contents) = std::fs::read_to_string(dir.join(FILE_NAME)) else {
+            return theme;
+        };
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once(" = ") else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "mode" => {
+                    theme.mode = ThemeMode::ALL.into_iter().find(|m| m.key() == value).unwrap_or(theme.mode);
+                }
+                "accent" => theme.accent = parse_hex(value).unwrap_or(theme.accent),
+                "scale" => {
+                    let scale = value.parse().unwrap_or(theme.ui_scale);
+                    theme.ui_scale = scale.clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end());
+                }
+                "backdrop" => theme.backdrop = parse_hex(value),
+                "panel" => theme.custom_panel = parse_hex(value).unwrap_or(theme.custom_panel),
+                "text" => theme.custom_text = parse_hex(value).unwrap_or(theme.custom_text),
+                _ => {}
+            }
+        }
+        theme
+    }
+
+    pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+        let mut contents = format!(
+            "mode = {}\naccent = {}\nscale = {:.2}\npanel = {}\ntext = {}\n",
+            self.mode.key(),
+            hex(self.accent),
+            self.ui_scale,
+            hex(self.custom_panel),
+            hex(self.custom_text),
+        );
+        if let Some(backdrop) = self.backdrop {
+            contents.push_str(&format!("backdrop = {}\n", hex(backdrop)));
+        }
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(FILE_NAME), contents)
+    }
+
+    /// Whether the theme builds on egui's dark visuals.
+    fn is_dark(&self) -> bool {
+        match self.mode {
+            ThemeMode::Dark => true,
+            ThemeMode::Light => false,
+            ThemeMode::Custom => luminance(self.custom_panel) < 0.5,
+        }
+    }
+
+    pub fn visuals(&self) -> egui::Visuals {
+        let accent = self.accent;
+        let mut visuals = if self.is_dark() { egui::Visuals::dark() } else { egui::Visuals::light() };
+        match self.mode {
+            ThemeMode::Dark => {
+                visuals.panel_fill = Color32::from_rgb(14, 16, 22);
+                visuals.window_fill = Color32::from_rgb(18, 21, 30);
+                visuals.extreme_bg_color = Color32::from_rgb(26, 30, 40);
+                visuals.widgets.inactive.bg_fill = Color32::from_rgb(30, 34, 46);
+                visuals.widgets.inactive.fg_stroke.color = Color32::from_rgb(220, 225, 235);
+                visuals.widgets.hovered.fg_stroke.color = Color32::from_rgb(240, 244, 255);
+            }
+            ThemeMode::Light => {
+                visuals.panel_fill = Color32::from_rgb(236, 238, 243);
+                visuals.window_fill = Color32::from_rgb(246, 247, 250);
+                visuals.extreme_bg_color = Color32::from_rgb(255, 255, 255);
+                visuals.widgets.inactive.bg_fill = Color32::from_rgb(220, 224, 232);
+                visuals.widgets.inactive.fg_stroke.color = Color32::from_rgb(40, 44, 54);
+                visuals.widgets.hovered.fg_stroke.color = Color32::from_rgb(10, 12, 20);
+            }
+            ThemeMode::Custom => {
+                let (panel, text) = (self.custom_panel, self.custom_text);
+                visuals.panel_fill = panel;
+                visuals.window_fill = mix(panel, text, 0.03);
+                visuals.extreme_bg_color = mix(panel, text, 0.07);
+                visuals.widgets.inactive.bg_fill = mix(panel, text, 0.1);
+                visuals.widgets.inactive.fg_stroke.color = mix(text, panel, 0.1);
+                visuals.widgets.hovered.fg_stroke.color = text;
+                visuals.widgets.noninteractive.fg_stroke.color = mix(text, panel, 0.2);
+            }
+        }
+        if self.is_dark() {
+            visuals.widgets.hovered.bg_fill = scale(accent, 0.42);
+            visuals.widgets.active.bg_fill = scale(accent, 0.74);
+            visuals.selection.bg_fill = accent;
+            visuals.selection.stroke.color = Color32::WHITE;
+        } else {
+            visuals.widgets.hovered.bg_fill = mix(Color32::WHITE, accent, 0.3);
+            visuals.widgets.active.bg_fill = mix(Color32::WHITE, accent, 0.55);
+            visuals.selection.bg_fill = mix(Color32::WHITE, accent, 0.55);
+            visuals.selection.stroke.color = scale(accent, 0.45);
+        }
+        if self.mode == ThemeMode::Custom {
+            // egui's stock grays would clash with a tinted panel
+            visuals.faint_bg_color = mix(self.custom_panel, self.custom_text, 0.03);
+            visuals.widgets.inactive.weak_bg_fill = visuals.widgets.inactive.bg_fill;
+            visuals.widgets.hovered.weak_bg_fill = visuals.widgets.hovered.bg_fill;
+            visuals.widgets.active.weak_bg_fill = visuals.widgets.active.bg_fill;
+        }
+        visuals.hyperlink_color = accent;
+        visuals
+    }
+
+    /// Fill behind the canvas.
+    pub fn backdrop(&self) -> Color32 {
+        self.backdrop.unwrap_or_else(|| self.visuals().panel_fill)
+    }
+}
+
+fn mix(a: Color32, b: Color32, t: f32) -> Color32 {
+    let channel = |x: u8, y: u8| egui::lerp(f32::from(x)..=f32::from(y), t).round() as u8;
+    Color32::from_rgb(channel(a.r(), b.r()), channel(a.g(), b.g()), channel(a.b(), b.b()))
+}
+
+fn scale(color: Color32, factor: f32) -> Color32 {
+    mix(Color32::BLACK, color, factor)
+}
+
+fn luminance(color: Color32) -> f32 {
+    (0.2126 * f32::from(color.r()) + 0.7152 * f32::from(color.g()) + 0.0722 * f32::from(color.b())) / 255.0
+}
+
+fn hex(color: Color32) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b())
+}
+
+fn parse_hex(value: &str) -> Option<Color32> {
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
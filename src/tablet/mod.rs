@@ -1,16 +1,25 @@
 use octotablet::{
     builder::Builder,
-    events::{Event, ToolEvent},
+    events::{Event, FrameTimestamp, ToolEvent},
     tool,
 };
-use std::collections::HashMap;
+use eframe::egui;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+/// Furthest the device clock may drift from ours before it is re-anchored, in seconds.
+const MAX_CLOCK_DRIFT: f64 = 0.1;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TabletPhase {
     Down,
     Move,
     Up,
+    /// Pen in proximity but not touching; pressure is always 0.
+    Hover,
+    /// Pen left proximity or the tablet was removed.
+    Leave,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -19,13 +28,24 @@ pub struct TabletSample {
     pub pressure: f32,
     #[allow(dead_code)]
     pub is_eraser: bool,
+    /// From a stylus rather than a finger, puck, or the emulated mouse.
+    pub is_pen: bool,
     pub phase: TabletPhase,
+    /// Seconds since the bridge started, from the device's own timestamp when it sends one.
+    pub time: f64,
 }
 
 /// Minimal tablet bridge: pumps octotablet events and emits normalized samples.
 pub struct TabletInput {
     manager: octotablet::Manager,
     tool_types: HashMap<tool::ID, bool>, // is eraser
+    in_contact: HashSet<tool::ID>,
+    /// Styli currently in proximity of the tablet.
+    pens_near: HashSet<tool::ID>,
+    /// Clock sample times count from.
+    clock: Instant,
+    /// A device timestamp and the clock time it was seen at, to map the rest onto the clock.
+    device_anchor: Option<(FrameTimestamp, f64)>,
 }
 
 impl TabletInput {
@@ -43,6 +63,10 @@ impl TabletInput {
             Ok(Ok(manager)) => Some(Self {
                 manager,
                 tool_types: HashMap::new(),
+                in_contact: HashSet::new(),
+                pens_near: HashSet::new(),
+                clock: Instant::now(),
+                device_anchor: None,
             }),
             Ok(Err(e)) => {
                 log::error!("Failed to initialize tablet: {:?}", e);
@@ -62,38 +86,174 @@ impl TabletInput {
             Ok(evts) => evts,
             Err(_) => return out,
         };
+        let now = self.clock.elapsed().as_secs_f64();
+        // Samples since the last frame event, which carries their timestamp
+        let mut frame_start = 0;
         for event in events {
             if let Event::Tool { tool, event } = event {
                 let is_eraser = matches!(tool.tool_type, Some(tool::Type::Eraser));
+                // Tools that don't say what they are count as styli
+                let is_pen = !matches!(
+                    tool.tool_type,
+                    Some(tool::Type::Finger | tool::Type::Mouse | tool::Type::Lens | tool::Type::Emulated)
+                );
                 self.tool_types.entry(tool.id()).or_insert(is_eraser);
                 match event {
-                    ToolEvent::Down => out.push(TabletSample {
-                        pos: [0.0, 0.0],
-                        pressure: 1.0,
-                        is_eraser,
-                        phase: TabletPhase::Down,
-                    }),
-                    ToolEvent::Up | ToolEvent::Out | ToolEvent::Removed => out.push(TabletSample {
-                        pos: [0.0, 0.0],
-                        pressure: 0.0,
-                        is_eraser,
-                        phase: TabletPhase::Up,
-                    }),
+                    ToolEvent::In { .. } if is_pen => {
+                        self.pens_near.insert(tool.id());
+                    }
+                    ToolEvent::Down => {
+                        self.in_contact.insert(tool.id());
+                        out.push(TabletSample {
+                            pos: [0.0, 0.0],
+                            pressure: 1.0,
+                            is_eraser,
+                            is_pen,
+                            phase: TabletPhase::Down,
+                            time: now,
+                        });
+                    }
+                    ToolEvent::Up => {
+                        self.in_contact.remove(&tool.id());
+                        out.push(TabletSample {
+                            pos: [0.0, 0.0],
+                            pressure: 0.0,
+                            is_eraser,
+                            is_pen,
+                            phase: TabletPhase::Up,
+                            time: now,
+                        });
+                    }
+                    ToolEvent::Out | ToolEvent::Removed => {
+                        self.pens_near.remove(&tool.id());
+                        // Leaving proximity mid-stroke still ends the stroke
+                        if self.in_contact.remove(&tool.id()) {
+                            out.push(TabletSample {
+                                pos: [0.0, 0.0],
+                                pressure: 0.0,
+                                is_eraser,
+                                is_pen,
+                                phase: TabletPhase::Up,
+                                time: now,
+                            });
+                        }
+                        out.push(TabletSample {
+                            pos: [0.0, 0.0],
+                            pressure: 0.0,
+                            is_eraser,
+                            is_pen,
+                            phase: TabletPhase::Leave,
+                            time: now,
+                        });
+                    }
                     ToolEvent::Pose(mut pose) => {
+                        if is_pen {
+                            self.pens_near.insert(tool.id());
+                        }
                         pose.position = [pose.position[0] * scale, pose.position[1] * scale];
-                        let pressure = pose.pressure.get().unwrap_or(1.0);
-                        // Emit Move with real position; Down/Up already signaled separately.
+                        // Poses outside Down/Up are proximity hover
+                        let (pressure, phase) = if self.in_contact.contains(&tool.id()) {
+                            (pose.pressure.get().unwrap_or(1.0), TabletPhase::Move)
+                        } else {
+                            (0.0, TabletPhase::Hover)
+                        };
                         out.push(TabletSample {
                             pos: pose.position,
                             pressure,
                             is_eraser,
-                            phase: TabletPhase::Move,
+                            is_pen,
+                            phase,
+                            time: now,
                         });
                     }
+                    ToolEvent::Frame(timestamp) => {
+                        if let Some(timestamp) = timestamp {
+                            let time = device_time(&mut self.device_anchor, timestamp, now);
+                            for sample in &mut out[frame_start..] {
+                                sample.time = time;
+                            }
+                        }
+                        frame_start = out.len();
+                    }
                     _ => {}
                 }
             }
         }
         out
     }
+
+    /// Whether a stylus is hovering over or touching the tablet.
+    pub fn pen_in_proximity(&self) -> bool {
+        !self.pens_near.is_empty()
+    }
+}
+
+/// A device timestamp on the bridge's clock, re-anchoring `anchor` when the two disagree.
+/// Events are queued before we see them, so the device time may trail `now` a little but
+/// never lead it.
+fn device_time(anchor: &mut Option<(FrameTimestamp, f64)>, timestamp: FrameTimestamp, now: f64) -> f64 {
+    let mapped = match *anchor {
+        Some((base, base_time)) if timestamp >= base => base_time + (timestamp - base).as_secs_f64(),
+        _ => f64::INFINITY,
+    };
+    if mapped <= now && now - mapped < MAX_CLOCK_DRIFT {
+        return mapped;
+    }
+    *anchor = Some((timestamp, now));
+    now
+}
+
+/// Finger contacts for pen-only mode, so a palm or finger on a touch screen can pan instead
+/// of paint.
+///
+/// Touches that report a force are taken to be a pen (see [`PointerPressure`]); the rest are
+/// fingers.
+#[derive(Default)]
+pub struct FingerTouches {
+    down: BTreeSet<egui::TouchId>,
+}
+
+impl FingerTouches {
+    pub fn update(&mut self, events: &[egui::Event]) {
+        for event in events {
+            if let egui::Event::Touch { id, phase, force: None, .. } = event {
+                match phase {
+                    egui::TouchPhase::Start | egui::TouchPhase::Move => self.down.insert(*id),
+                    egui::TouchPhase::End | egui::TouchPhase::Cancel => self.down.remove(id),
+                };
+            }
+        }
+    }
+
+    pub fn any_down(&self) -> bool {
+        !self.down.is_empty()
+    }
+}
+
+/// Fallback pressure source for when the octotablet backend failed to start.
+///
+/// winit reports Windows Ink and other native pen input as touches carrying a
+/// normalized force, which egui forwards alongside the emulated pointer events.
+#[derive(Default)]
+pub struct PointerPressure {
+    force: Option<f32>,
+}
+
+impl PointerPressure {
+    /// Track the force of the latest pen contact in this frame's events.
+    pub fn update(&mut self, events: &[egui::Event]) {
+        for event in events {
+            if let egui::Event::Touch { phase, force, .. } = event {
+                self.force = match phase {
+                    egui::TouchPhase::Start | egui::TouchPhase::Move => *force,
+                    egui::TouchPhase::End | egui::TouchPhase::Cancel => None,
+                };
+            }
+        }
+    }
+
+    /// Pressure of the current contact, if the platform reports one.
+    pub fn pressure(&self) -> Option<f32> {
+        self.force
+    }
 }
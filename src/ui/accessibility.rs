@@ -0,0 +1,60 @@
+//! Keyboard focus and screen reader support for hand-painted widgets.
+//!
+//! egui puts every clickable widget in the Tab order and turns Enter/Space on a focused widget
+//! into a click. Widgets the panels paint themselves still need a name for AccessKit, a visible
+//! focus ring, and to claim the arrow keys they use so egui doesn't move focus with them.
+use eframe::egui;
+
+/// Outline `response` while it has keyboard focus.
+pub fn focus_ring(ui: &egui::Ui, response: &egui::Response) {
+    if response.has_focus() {
+        ui.painter().rect_stroke(response.rect.expand(2.0), 3.0, ui.visuals().selection.stroke);
+    }
+}
+
+/// Name a painted swatch or thumbnail that acts as a button.
+pub fn button(ui: &egui::Ui, response: &egui::Response, name: &str) {
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, ui.is_enabled(), name));
+    focus_ring(ui, response);
+}
+
+/// Name a painted item that is one choice out of several.
+pub fn selectable(ui: &egui::Ui, response: &egui::Response, selected: bool, name: &str) {
+    response.widget_info(|| {
+        egui::WidgetInfo::selected(egui::WidgetType::SelectableLabel, ui.is_enabled(), selected, name)
+    });
+    focus_ring(ui, response);
+}
+
+/// Replace the spoken name of an icon-only button, keeping the icon on screen.
+pub fn named(response: egui::Response, name: &str) -> egui::Response {
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, response.enabled(), name));
+    response
+}
+
+/// Replace the spoken name of an icon-only checkbox.
+pub fn named_checkbox(response: egui::Response, checked: bool, name: &str) -> egui::Response {
+    response.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, response.enabled(), checked, name));
+    response
+}
+
+/// Arrow presses on a focused widget along one axis: positive for Right/Down, negative for
+/// Left/Up. The widget keeps those keys instead of losing focus to its neighbours.
+pub fn arrow_steps(ui: &egui::Ui, response: &egui::Response, vertical: bool) -> i32 {
+    if !response.has_focus() {
+        return 0;
+    }
+    let filter = egui::EventFilter { horizontal_arrows: !vertical, vertical_arrows: vertical, ..Default::default() };
+    ui.memory_mut(|m| m.set_focus_lock_filter(response.id, filter));
+    let (back, forward) = if vertical {
+        (egui::Key::ArrowUp, egui::Key::ArrowDown)
+    } else {
+        (egui::Key::ArrowLeft, egui::Key::ArrowRight)
+    };
+    ui.input(|i| i32::from(i.key_pressed(forward)) - i32::from(i.key_pressed(back)))
+}
+
+/// What a color swatch is read out as.
+pub fn color_name(color: egui::Color32) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b())
+}
@@ -0,0 +1,72 @@
+use crate::PainterApp;
+use crate::app::state::BackgroundChoice;
+use crate::canvas::history::UndoAction;
+use crate::utils::i18n::tr;
+use eframe::egui::{self, Color32};
+
+/// Pending choice in the Background Color dialog.
+pub struct BackgroundColorDialog {
+    pub choice: BackgroundChoice,
+    pub custom: Color32,
+}
+
+impl BackgroundColorDialog {
+    /// Start from the document's current paper color.
+    pub fn new(current: Color32) -> Self {
+        let choice = match current {
+            Color32::TRANSPARENT => BackgroundChoice::Transparent,
+            Color32::WHITE => BackgroundChoice::White,
+            Color32::BLACK => BackgroundChoice::Black,
+            _ => BackgroundChoice::Custom,
+        };
+        Self { choice, custom: current }
+    }
+}
+
+/// Dialog that changes the document background color as one undo step.
+pub fn background_color_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    let Some(dialog) = &mut app.background_dialog else {
+        return;
+    };
+
+    let mut open = true;
+    let mut apply = false;
+    egui::Window::new(tr("Background Color"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut dialog.choice, BackgroundChoice::White, tr("White"));
+                ui.radio_value(&mut dialog.choice, BackgroundChoice::Black, tr("Black"));
+                ui.radio_value(&mut dialog.choice, BackgroundChoice::Transparent, tr("Transparent"));
+                ui.radio_value(&mut dialog.choice, BackgroundChoice::Custom, tr("Custom"));
+                if dialog.choice == BackgroundChoice::Custom {
+                    ui.color_edit_button_srgba(&mut dialog.custom);
+                }
+            });
+            ui.small(tr("Only the unpainted paper changes; strokes on the background layer are kept."));
+            ui.separator();
+            if ui.button(tr("Apply")).clicked() {
+                apply = true;
+            }
+        });
+
+    if apply {
+        let color = dialog.choice.color32(dialog.custom);
+        let mut action = UndoAction::new("Background color");
+        app.canvas.set_background_color(color, &mut action);
+        if action.background.is_some() {
+            if let Some(hist) = app.histories.get_mut(app.canvas.active_layer_idx) {
+                hist.push_action(action);
+            }
+            app.mark_untracked_change("background_color");
+            app.mark_all_tiles_dirty();
+        }
+        open = false;
+    }
+    if !open {
+        app.background_dialog = None;
+    }
+}
@@ -1,160 +1,252 @@
 use crate::brush_engine::brush::{Brush, BrushPreset};
-use crate::brush_engine::stroke::StrokeState;
-use crate::canvas::canvas::Canvas;
-use crate::canvas::history::UndoAction;
-use crate::utils::vector::Vec2;
+use crate::ui::brush_settings::render_preview;
+use crate::ui::accessibility;
+use crate::utils::i18n::tr;
 use eframe::egui;
 use eframe::egui::{Color32, TextureOptions};
 use rayon::ThreadPool;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+
+/// How the preset panel lays out its entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PresetView {
+    Grid,
+    List,
+}
 
 /// Displays available presets and lets the user apply one to the active brush.
+///
+/// Returns true when tags or favorites changed and should be saved.
 pub fn brush_list_panel(
     ui: &mut egui::Ui,
     brush: &mut Brush,
     presets: &mut Vec<BrushPreset>,
-    previews: &mut HashMap<String, egui::TextureHandle>,
+    previews: &mut HashMap<u64, egui::TextureHandle>,
     pool: &ThreadPool,
     show_modal: &mut bool,
     new_preset_name: &mut String,
-) {
+) -> bool {
     ui.set_min_width(200.0);
     let ctx = ui.ctx().clone();
+    let view_id = ui.make_persistent_id("preset_view");
+    let mut view = ui.data(|d| d.get_temp(view_id)).unwrap_or(PresetView::Grid);
 
     ui.horizontal(|ui| {
-        ui.heading("Presets");
+        ui.heading(tr("Presets"));
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-            if ui.button("+").clicked() {
+            if accessibility::named(ui.button("+"), tr("New Preset")).clicked() {
                 *show_modal = true;
-                *new_preset_name = "New Preset".to_string();
+                *new_preset_name = tr("New Preset").to_string();
             }
+            let response = ui.selectable_value(&mut view, PresetView::List, "☰");
+            accessibility::selectable(ui, &response, view == PresetView::List, tr("List view"));
+            response.on_hover_text(tr("List view"));
+            let response = ui.selectable_value(&mut view, PresetView::Grid, "▦");
+            accessibility::selectable(ui, &response, view == PresetView::Grid, tr("Grid view"));
+            response.on_hover_text(tr("Grid view"));
         });
     });
+    ui.data_mut(|d| d.insert_temp(view_id, view));
     ui.separator();
 
     // Modal for new preset
     if *show_modal {
-        egui::Window::new("Save Brush Preset")
+        egui::Window::new(tr("Save Brush Preset"))
             .collapsible(false)
             .resizable(false)
             .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
             .show(&ctx, |ui| {
-                ui.label("Preset Name:");
+                ui.label(tr("Preset Name:"));
                 ui.text_edit_singleline(new_preset_name);
                 ui.add_space(10.0);
                 ui.horizontal(|ui| {
-                    if ui.button("Cancel").clicked() {
+                    if ui.button(tr("Cancel")).clicked() {
                         *show_modal = false;
                     }
-                    if ui.button("Save").clicked() {
+                    if ui.button(tr("Save")).clicked() {
                         let name = if new_preset_name.trim().is_empty() {
-                            "Untitled Brush".to_string()
+                            tr("Untitled Brush").to_string()
                         } else {
                             new_preset_name.trim().to_string()
                         };
                         
-                        presets.push(BrushPreset {
-                            name,
-                            brush: brush.clone(),
-                        });
+                        presets.push(BrushPreset::new(name, brush.clone()));
                         *show_modal = false;
                     }
                 });
             });
     }
 
-    egui::ScrollArea::vertical().show(ui, |ui| {
-        ui.columns(3, |col| {
-            let mut idx = 0;
-            for preset in presets {
-                let column = &mut col[idx];
-                column.vertical(|ui| {
-                    let preview_size = 64.0; // Increased size for better visibility
-                    let (rect, response) = ui.allocate_exact_size(
-                        egui::vec2(preview_size, preview_size),
-                        egui::Sense::click(),
-                    );
-
-                    // Ensure preview exists
-                    let texture_id = if let Some(tex) = previews.get(&preset.name) {
-                        tex.id()
-                    } else {
-                        // Generate preview
-                        let tex = generate_preset_preview(&preset.brush, pool, &ctx);
-                        let id = tex.id();
-                        previews.insert(preset.name.clone(), tex);
-                        id
-                    };
-
-                    // Draw background
-                    ui.painter().rect_filled(rect, 2.0, Color32::from_gray(30));
-                    
-                    // Draw texture
-                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
-                    ui.painter().image(texture_id, rect, uv, Color32::WHITE);
-
-                    // Selection highlight
-                    // We don't strictly track which preset is "selected" in PainterApp yet,
-                    // but we could highlight if active brush matches preset?
-                    // For now just hover effect
-                    if response.hovered() {
-                         ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
-                    } else {
-                         ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, Color32::GRAY));
-                    }
+    // Search and favorites filter
+    let search_id = ui.make_persistent_id("preset_search");
+    let favorites_id = ui.make_persistent_id("preset_favorites_only");
+    let mut search: String = ui.data(|d| d.get_temp(search_id)).unwrap_or_default();
+    let mut favorites_only: bool = ui.data(|d| d.get_temp(favorites_id)).unwrap_or(false);
+    ui.horizontal(|ui| {
+        ui.toggle_value(&mut favorites_only, "★").on_hover_text(tr("Show favorites only"));
+        ui.add(egui::TextEdit::singleline(&mut search).hint_text(tr("Search name or tag")));
+    });
+    ui.data_mut(|d| {
+        d.insert_temp(search_id, search.clone());
+        d.insert_temp(favorites_id, favorites_only);
+    });
+    ui.add_space(4.0);
 
-                    let response = response.on_hover_text(&preset.name);
-                    if response.clicked() {
-                        let current_color = brush.brush_options.color;
-                        *brush = preset.brush.clone();
-                        brush.brush_options.color = current_color;
-                    }
-                    
-                    ui.label(egui::RichText::new(&preset.name).size(10.0).weak());
-                });
-                column.add_space(8.0);
-                idx += 1;
-                idx = idx % 3;
+    // Favorites first, otherwise keep the preset order
+    let mut visible: Vec<usize> = (0..presets.len())
+        .filter(|&i| presets[i].matches(&search) && (!favorites_only || presets[i].favorite))
+        .collect();
+    visible.sort_by_key(|&i| !presets[i].favorite);
+
+    let mut meta_changed = false;
+    egui::ScrollArea::vertical().show(ui, |ui| match view {
+        PresetView::Grid => {
+            ui.columns(3, |col| {
+                for (slot, &i) in visible.iter().enumerate() {
+                    let preset = &mut presets[i];
+                    let column = &mut col[slot % 3];
+                    column.vertical(|ui| {
+                        let preview_size = 64.0; // Increased size for better visibility
+                        let (rect, response) = ui.allocate_exact_size(
+                            egui::vec2(preview_size, preview_size),
+                            egui::Sense::click(),
+                        );
+                        let texture_id = preset_thumbnail(preset, previews, pool, &ctx);
+
+                        // Draw background
+                        ui.painter().rect_filled(rect, 2.0, Color32::from_gray(30));
+
+                        // Draw texture
+                        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                        ui.painter().image(texture_id, rect, uv, Color32::WHITE);
+
+                        // Selection highlight
+                        // We don't strictly track which preset is "selected" in PainterApp yet,
+                        // but we could highlight if active brush matches preset?
+                        // For now just hover effect
+                        if response.hovered() {
+                             ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
+                        } else {
+                             ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, Color32::GRAY));
+                        }
+                        if preset.favorite {
+                            ui.painter().text(
+                                rect.right_top() + egui::vec2(-3.0, 2.0),
+                                egui::Align2::RIGHT_TOP,
+                                "★",
+                                egui::FontId::proportional(12.0),
+                                Color32::GOLD,
+                            );
+                        }
+
+                        accessibility::button(ui, &response, &preset.name);
+                        let response = response.on_hover_text(preset_tooltip(preset));
+                        if response.clicked() {
+                            apply_preset(brush, preset);
+                        }
+                        response.context_menu(|ui| meta_changed |= preset_menu(ui, preset));
+
+                        ui.label(egui::RichText::new(&preset.name).size(10.0).weak());
+                    });
+                    column.add_space(8.0);
+                }
+            });
+        }
+        PresetView::List => {
+            for &i in &visible {
+                let preset = &mut presets[i];
+                let texture_id = preset_thumbnail(preset, previews, pool, &ctx);
+                let response = ui
+                    .horizontal(|ui| {
+                        let (rect, thumb) = ui.allocate_exact_size(egui::vec2(32.0, 32.0), egui::Sense::click());
+                        accessibility::button(ui, &thumb, &preset.name);
+                        ui.painter().rect_filled(rect, 2.0, Color32::from_gray(30));
+                        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                        ui.painter().image(texture_id, rect, uv, Color32::WHITE);
+                        let star = if preset.favorite { "★ " } else { "" };
+                        thumb | ui.selectable_label(false, format!("{}{}", star, preset.name))
+                    })
+                    .inner
+                    .on_hover_text(preset_tooltip(preset));
+                if response.clicked() {
+                    apply_preset(brush, preset);
+                }
+                response.context_menu(|ui| meta_changed |= preset_menu(ui, preset));
             }
-        });
+        }
     });
+    meta_changed
+}
+
+fn preset_tooltip(preset: &BrushPreset) -> String {
+    if preset.tags.is_empty() {
+        preset.name.clone()
+    } else {
+        format!("{}\nTags: {}", preset.name, preset.tags.join(", "))
+    }
+}
+
+/// Right-click menu for toggling the favorite star and editing tags.
+fn preset_menu(ui: &mut egui::Ui, preset: &mut BrushPreset) -> bool {
+    let mut changed = ui.checkbox(&mut preset.favorite, tr("Favorite")).changed();
+    ui.separator();
+
+    let mut removed = None;
+    for (i, tag) in preset.tags.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.small_button("×").on_hover_text(tr("Remove tag")).clicked() {
+                removed = Some(i);
+            }
+            ui.label(tag);
+        });
+    }
+    if let Some(i) = removed {
+        preset.tags.remove(i);
+        changed = true;
+    }
+
+    let new_tag_id = ui.make_persistent_id("preset_new_tag");
+    let mut new_tag: String = ui.data(|d| d.get_temp(new_tag_id)).unwrap_or_default();
+    let response = ui.add(egui::TextEdit::singleline(&mut new_tag).hint_text(tr("Add tag")));
+    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+        let tag = new_tag.trim().replace(',', " ");
+        if !tag.is_empty() && !preset.tags.contains(&tag) {
+            preset.tags.push(tag);
+            changed = true;
+        }
+        new_tag.clear();
+        response.request_focus();
+    }
+    ui.data_mut(|d| d.insert_temp(new_tag_id, new_tag));
+    changed
+}
+
+/// Load the preset into the active brush, keeping the current color.
+pub(crate) fn apply_preset(brush: &mut Brush, preset: &BrushPreset) {
+    let current_color = brush.brush_options.color;
+    *brush = preset.brush.clone();
+    brush.brush_options.color = current_color;
+}
+
+/// Cached thumbnail for a preset, rendered on first use and whenever its content changes.
+pub(crate) fn preset_thumbnail(
+    preset: &BrushPreset,
+    previews: &mut HashMap<u64, egui::TextureHandle>,
+    pool: &ThreadPool,
+    ctx: &egui::Context,
+) -> egui::TextureId {
+    previews
+        .entry(preset.thumbnail_key())
+        .or_insert_with(|| generate_preset_preview(&preset.brush, pool, ctx))
+        .id()
 }
 
 fn generate_preset_preview(brush_template: &Brush, pool: &ThreadPool, ctx: &egui::Context) -> egui::TextureHandle {
-    let w = 128;
-    let h = 128;
-    let canvas = Canvas::new(w, h, Color32::TRANSPARENT, 32);
-    
     let mut brush = brush_template.clone();
     // Normalize brush size for preview so huge brushes don't look weird
-    brush.brush_options.diameter = 20.0; 
+    brush.brush_options.diameter = 20.0;
     brush.brush_options.color = Color32::WHITE;
-    
-    let mut stroke = StrokeState::new();
-    let mut undo = UndoAction { tiles: Vec::new(), selection: None, transform: None };
-    let mut modified = HashSet::new();
-
-    // Draw S curve
-    let steps = 80;
-    let margin = 20.0;
-    let width = w as f32;
-    let height = h as f32;
-    let effective_w = width - 2.0 * margin;
-
-    for i in 0..=steps {
-        let t = i as f32 / steps as f32;
-        let x = margin + t * effective_w;
-        let phase = t * std::f32::consts::PI * 2.0;
-        let y = height * 0.5 + (phase.sin() * height * 0.3);
-        
-        let pressure = (t * std::f32::consts::PI).sin();
-        brush.brush_options.diameter = (20.0 * pressure).max(2.0);
-        
-        stroke.add_point(pool, &canvas, &mut brush, None, Vec2 { x, y }, &mut undo, &mut modified);
-    }
 
-    let mut image = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
-    canvas.write_region_to_color_image(0, 0, w, h, &mut image, 1);
-    
+    let image = render_preview(&mut brush, pool, [128, 128]);
     ctx.load_texture("preset_preview", image, TextureOptions::LINEAR)
 }
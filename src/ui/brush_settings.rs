@@ -1,27 +1,71 @@
 use crate::brush_engine::brush::{Brush, BrushType, StabilizerAlgorithm};
-use crate::brush_engine::stroke::StrokeState;
-use crate::brush_engine::brush_options::{BlendMode, PixelBrushShape};
+use crate::brush_engine::engine::StrokeTarget;
+use crate::brush_engine::brush_options::{BlendMode, Dither, PixelBrushShape};
+use crate::brush_engine::dab_mask;
 use crate::brush_engine::hardness::{CurvePoint, SoftnessCurve, SoftnessSelector};
 use crate::canvas::canvas::Canvas;
 use crate::canvas::history::UndoAction;
 use crate::utils::vector::Vec2;
+use crate::ui::accessibility;
+use crate::utils::i18n::tr;
 use eframe::egui::{self, Color32};
 use rayon::ThreadPool;
 use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 
 pub struct BrushPreviewState {
-    pub canvas: Canvas,
     pub texture: Option<egui::TextureHandle>,
+    /// Alpha across the middle of one dab, drawn beside the stroke.
+    pub plot_texture: Option<egui::TextureHandle>,
     pub dirty: bool,
+    /// Receives the stroke and cross-section images from the preview job currently running on the pool.
+    job: Option<Receiver<(egui::ColorImage, egui::ColorImage)>>,
 }
 
 impl Default for BrushPreviewState {
     fn default() -> Self {
         Self {
-            // Small canvas for preview
-            canvas: Canvas::new(200, 80, Color32::TRANSPARENT, 64),
             texture: None,
+            plot_texture: None,
             dirty: true,
+            job: None,
+        }
+    }
+}
+
+impl BrushPreviewState {
+    /// Swap in a finished preview and start the next one if the brush changed since.
+    ///
+    /// Only one job runs at a time, so a burst of slider changes collapses into
+    /// a single render of the latest settings once the running job finishes.
+    fn update(&mut self, brush: &Brush, pool: &Arc<ThreadPool>, ctx: &egui::Context) {
+        if let Some(job) = &self.job {
+            match job.try_recv() {
+                Ok((image, plot)) => {
+                    self.texture = Some(ctx.load_texture("brush_preview", image, egui::TextureOptions::NEAREST));
+                    self.plot_texture = Some(ctx.load_texture("brush_cross_section", plot, egui::TextureOptions::NEAREST));
+                    self.job = None;
+                }
+                Err(TryRecvError::Disconnected) => self.job = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        if self.dirty && self.job.is_none() {
+            self.dirty = false;
+            let (tx, rx) = mpsc::channel();
+            let mut brush = brush.clone();
+            let worker_pool = pool.clone();
+            let ctx = ctx.clone();
+            pool.spawn(move || {
+                let image = render_preview(&mut brush, &worker_pool, [200, 80]);
+                let plot = render_cross_section(&brush, [120, 80]);
+                if tx.send((image, plot)).is_ok() {
+                    ctx.request_repaint();
+                }
+            });
+            self.job = Some(rx);
         }
     }
 }
@@ -31,43 +75,51 @@ pub fn brush_settings_panel(
     ui: &mut egui::Ui,
     brush: &mut Brush,
     preview: &mut BrushPreviewState,
-    pool: &ThreadPool,
+    pool: &Arc<ThreadPool>,
     loaded_tips: &[(String, PixelBrushShape, Option<egui::TextureHandle>)],
 ) {
     let mut mask_dirty = false;
 
-    ui.heading("Brush Properties");
+    ui.heading(tr("Brush Properties"));
     ui.separator();
 
     // --- Preview Area ---
-    ui.collapsing("Preview", |ui| {
-        if preview.dirty {
-             render_preview(preview, brush, pool, ui.ctx());
-             preview.dirty = false;
-        }
-        
-        if let Some(texture) = &preview.texture {
-            ui.image((texture.id(), texture.size_vec2()));
-        }
+    ui.collapsing(tr("Preview"), |ui| {
+        preview.update(brush, pool, ui.ctx());
+
+        ui.horizontal_wrapped(|ui| {
+            if let Some(texture) = &preview.texture {
+                ui.image((texture.id(), texture.size_vec2()));
+            }
+            if let Some(texture) = &preview.plot_texture {
+                ui.image((texture.id(), texture.size_vec2()))
+                    .on_hover_text(tr("Dab cross-section: alpha through the middle of one dab, one bar per pixel."));
+            }
+        });
     });
     ui.separator();
     // --------------------
 
     ui.horizontal(|ui| {
-        ui.label("Type:");
-        if ui.selectable_value(&mut brush.brush_type, BrushType::Soft, "Soft").changed() { preview.dirty = true; }
-        if ui.selectable_value(&mut brush.brush_type, BrushType::Pixel, "Pixel").changed() { preview.dirty = true; }
+        ui.label(tr("Type:"));
+        if ui.selectable_value(&mut brush.brush_type, BrushType::Soft, tr("Soft")).changed() { preview.dirty = true; }
+        if ui.selectable_value(&mut brush.brush_type, BrushType::Pixel, tr("Pixel")).changed() { preview.dirty = true; }
     });
 
     ui.horizontal(|ui| {
-        ui.label("Mode:");
-        if ui.selectable_value(&mut brush.brush_options.blend_mode, BlendMode::Normal, "Normal").changed() { preview.dirty = true; }
-        if ui.selectable_value(&mut brush.brush_options.blend_mode, BlendMode::Eraser, "Eraser").changed() { preview.dirty = true; }
+        ui.label(tr("Mode:"));
+        egui::ComboBox::from_id_salt("brush_blend_mode")
+            .selected_text(tr(brush.brush_options.blend_mode.label()))
+            .show_ui(ui, |ui| {
+                for mode in BlendMode::ALL {
+                    if ui.selectable_value(&mut brush.brush_options.blend_mode, mode, tr(mode.label())).changed() { preview.dirty = true; }
+                }
+            });
     });
 
     ui.add_space(5.0);
     
-    ui.label("Brush Tip:");
+    ui.label(tr("Brush Tip:"));
     egui::ScrollArea::vertical().id_salt("pixel_tip_selector").max_height(120.0).show(ui, |ui| {
         ui.horizontal_wrapped(|ui| {
             let size = egui::vec2(32.0, 32.0);
@@ -77,7 +129,8 @@ pub fn brush_settings_panel(
             let is_selected = matches!(brush.brush_options.pixel_shape, PixelBrushShape::Circle);
             ui.painter().rect_stroke(rect, 1.0, (1.0, if is_selected { Color32::WHITE } else { Color32::GRAY }));
             ui.painter().circle_filled(rect.center(), 12.0, Color32::WHITE);
-            if response.on_hover_text("Circle").clicked() {
+            accessibility::selectable(ui, &response, is_selected, tr("Circle"));
+            if response.on_hover_text(tr("Circle")).clicked() {
                 brush.brush_options.pixel_shape = PixelBrushShape::Circle;
                 preview.dirty = true;
             }
@@ -87,7 +140,8 @@ pub fn brush_settings_panel(
             let is_selected = matches!(brush.brush_options.pixel_shape, PixelBrushShape::Square);
             ui.painter().rect_stroke(rect, 1.0, (1.0, if is_selected { Color32::WHITE } else { Color32::GRAY }));
             ui.painter().rect_filled(rect.shrink(4.0), 0.0, Color32::WHITE);
-            if response.on_hover_text("Square").clicked() {
+            accessibility::selectable(ui, &response, is_selected, tr("Square"));
+            if response.on_hover_text(tr("Square")).clicked() {
                 brush.brush_options.pixel_shape = PixelBrushShape::Square;
                 preview.dirty = true;
             }
@@ -100,7 +154,8 @@ pub fn brush_settings_panel(
                     
                     ui.painter().rect_stroke(rect, 1.0, (1.0, if is_selected { Color32::WHITE } else { Color32::GRAY }));
                     ui.painter().image(texture.id(), rect.shrink(2.0), egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
-                    
+                    accessibility::selectable(ui, &response, is_selected, name);
+
                     if response.on_hover_text(name).clicked() {
                         brush.brush_options.pixel_shape = shape.clone();
                         preview.dirty = true;
@@ -109,9 +164,28 @@ pub fn brush_settings_panel(
             }
         });
     });
+    if brush.brush_options.has_tip_image() {
+        if ui
+            .checkbox(&mut brush.brush_options.follow_direction, tr("Rotate tip to stroke direction"))
+            .on_hover_text(tr("Turn each dab to the direction the stroke is travelling, for ribbon and calligraphy strokes."))
+            .changed()
+        {
+            preview.dirty = true;
+        }
+        if ui
+            .add(egui::Slider::new(&mut brush.brush_options.random_rotation, 0.0..=180.0).suffix("°").text(tr("Random rotation")))
+            .changed()
+        {
+            preview.dirty = true;
+        }
+    }
     ui.add_space(5.0);
 
-    ui.label("Size:");
+    ui.horizontal(|ui| {
+        ui.label(tr("Size:"));
+        ui.checkbox(&mut brush.brush_options.screen_size, tr("In screen pixels"))
+            .on_hover_text(tr("Keep the brush the same size on screen at any zoom; converted when a stroke starts."));
+    });
     if ui
         .add(egui::Slider::new(&mut brush.brush_options.diameter, 1.0..=3000.0).logarithmic(true))
         .changed()
@@ -122,20 +196,46 @@ pub fn brush_settings_panel(
 
     if brush.brush_type == BrushType::Soft {
         ui.horizontal(|ui| {
-             ui.label("Softness:");
-             if ui.selectable_value(&mut brush.brush_options.softness_selector, SoftnessSelector::Gaussian, "Gaussian").changed() {
+             ui.label(tr("Softness:"));
+             if ui.selectable_value(&mut brush.brush_options.softness_selector, SoftnessSelector::Gaussian, tr("Gaussian")).changed() {
                  mask_dirty = true;
                  preview.dirty = true;
              }
-             if ui.selectable_value(&mut brush.brush_options.softness_selector, SoftnessSelector::Curve, "Curve").changed() {
+             if ui.selectable_value(&mut brush.brush_options.softness_selector, SoftnessSelector::Curve, tr("Curve")).changed() {
                  mask_dirty = true;
                  preview.dirty = true;
              }
         });
         
+        ui.horizontal(|ui| {
+            ui.label(tr("Dither:"));
+            for dither in [Dither::Off, Dither::Bayer, Dither::Noise] {
+                if ui.selectable_value(&mut brush.brush_options.dither, dither, tr(dither.label())).changed() {
+                    preview.dirty = true;
+                }
+            }
+        })
+        .response
+        .on_hover_text(tr("Paint the soft falloff with fully opaque pixels, for pixel art and 1-bit styles."));
+
+        if ui
+            .add(egui::Slider::new(&mut brush.brush_options.grain, 0.0..=1.0).text(tr("Grain")))
+            .on_hover_text(tr("Thin the paint with noise fixed to the canvas, for airbrush and paper grain."))
+            .changed()
+        {
+            preview.dirty = true;
+        }
+        if brush.brush_options.grain > 0.0
+            && ui
+                .add(egui::Slider::new(&mut brush.brush_options.grain_scale, 1.0..=64.0).logarithmic(true).suffix(" px").text(tr("Grain scale")))
+                .changed()
+        {
+            preview.dirty = true;
+        }
+
         match brush.brush_options.softness_selector {
             SoftnessSelector::Gaussian => {
-                ui.label("Hardness:");
+                ui.label(tr("Hardness:"));
                 if ui
                     .add(egui::Slider::new(&mut brush.brush_options.hardness, 0.0..=100.0))
                     .changed()
@@ -145,66 +245,80 @@ pub fn brush_settings_panel(
                 }
             }
             SoftnessSelector::Curve => {
-                 ui.label("Softness Curve:");
+                 ui.label(tr("Softness Curve:"));
                  if curve_editor(ui, &mut brush.brush_options.softness_curve) {
                      mask_dirty = true;
                      preview.dirty = true;
                  }
-                 ui.small("Double-click to add/remove points.");
+                 ui.small(tr("Double-click to add/remove points."));
             }
         }
     }
 
-    ui.label("Opacity:");
+    ui.label(tr("Opacity:"));
     if ui.add(egui::Slider::new(&mut brush.brush_options.opacity, 0.0..=1.0)).changed() { preview.dirty = true; }
 
-    ui.label("Flow:");
+    ui.label(tr("Flow:"));
     if ui.add(egui::Slider::new(&mut brush.brush_options.flow, 0.0..=100.0)).changed() { preview.dirty = true; }
+    ui.checkbox(&mut brush.brush_options.pressure_flow, tr("Pressure → flow"))
+        .on_hover_text(tr("Scale each dab's strength by pen pressure"));
 
-    ui.label("Spacing (%):");
+    ui.label(tr("Spacing (%):"));
     if ui.add(egui::Slider::new(&mut brush.brush_options.spacing, 1.0..=200.0)).changed() { preview.dirty = true; }
 
-    ui.label("Jitter (% of size):");
+    ui.checkbox(&mut brush.airbrush, tr("Airbrush"))
+        .on_hover_text(tr("Keep spraying while the pointer rests, so paint builds up the longer you hold"));
+    if brush.airbrush {
+        ui.add(egui::Slider::new(&mut brush.airbrush_rate, 1.0..=120.0).text(tr("Dabs per second")));
+    }
+
+    ui.label(tr("Jitter (% of size):"));
     if ui.add(egui::Slider::new(&mut brush.jitter, 0.0..=50.0)).changed() { preview.dirty = true; }
 
-    ui.label("Stabilizer:");
+    ui.label(tr("Stabilizer:"));
     ui.horizontal(|ui| {
-        if ui.selectable_value(&mut brush.stabilizer_algorithm, StabilizerAlgorithm::None, "None").changed() { preview.dirty = true; }
-        if ui.selectable_value(&mut brush.stabilizer_algorithm, StabilizerAlgorithm::Simple, "Simple").changed() { preview.dirty = true; }
-        if ui.selectable_value(&mut brush.stabilizer_algorithm, StabilizerAlgorithm::Dynamic, "Dynamic").changed() { preview.dirty = true; }
+        if ui.selectable_value(&mut brush.stabilizer_algorithm, StabilizerAlgorithm::None, tr("None")).changed() { preview.dirty = true; }
+        if ui.selectable_value(&mut brush.stabilizer_algorithm, StabilizerAlgorithm::Simple, tr("Simple")).changed() { preview.dirty = true; }
+        if ui.selectable_value(&mut brush.stabilizer_algorithm, StabilizerAlgorithm::Dynamic, tr("Dynamic")).changed() { preview.dirty = true; }
     });
 
     match brush.stabilizer_algorithm {
         StabilizerAlgorithm::None => {},
         StabilizerAlgorithm::Simple => {
-            if ui.add(egui::Slider::new(&mut brush.stabilizer, 0.0..=1.0).text("Strength")).changed() { preview.dirty = true; }
+            if ui.add(egui::Slider::new(&mut brush.stabilizer, 0.0..=1.0).text(tr("Strength"))).changed() { preview.dirty = true; }
         },
         StabilizerAlgorithm::Dynamic => {
-            if ui.add(egui::Slider::new(&mut brush.stabilizer_mass, 0.01..=1.0).text("Mass")).changed() { preview.dirty = true; }
-            if ui.add(egui::Slider::new(&mut brush.stabilizer_drag, 0.0..=1.0).text("Drag")).changed() { preview.dirty = true; }
+            if ui.add(egui::Slider::new(&mut brush.stabilizer_mass, 0.01..=1.0).text(tr("Mass"))).changed() { preview.dirty = true; }
+            if ui.add(egui::Slider::new(&mut brush.stabilizer_drag, 0.0..=1.0).text(tr("Drag"))).changed() { preview.dirty = true; }
         }
     }
+    ui.add(egui::Slider::new(&mut brush.post_smoothing, 0.0..=1.0).text(tr("Post-stroke smoothing")))
+        .on_hover_text(tr("Redraws each finished stroke along a fitted curve"));
 
     ui.separator();
-    if ui.checkbox(&mut brush.pixel_perfect, "Pixel Perfect Mode").changed() { preview.dirty = true; }
-    if ui.checkbox(&mut brush.anti_aliasing, "Anti-aliasing").changed() { preview.dirty = true; }
+    if ui.checkbox(&mut brush.pixel_perfect, tr("Pixel Perfect Mode")).changed() { preview.dirty = true; }
+    if ui.checkbox(&mut brush.anti_aliasing, tr("Anti-aliasing")).changed() { preview.dirty = true; }
 
     if mask_dirty {
         brush.is_changed = true;
     }
 }
 
-fn render_preview(state: &mut BrushPreviewState, brush: &mut Brush, pool: &ThreadPool, ctx: &egui::Context) {
-    // Clear canvas
-    state.canvas.clear(Color32::TRANSPARENT);
-    
-    let width = state.canvas.width() as f32;
-    let height = state.canvas.height() as f32;
+/// Paint the sample S-curve stroke onto a scratch canvas of the given size.
+///
+/// Shared by the settings preview strip and the preset thumbnails.
+pub(crate) fn render_preview(brush: &mut Brush, pool: &ThreadPool, size: [usize; 2]) -> egui::ColorImage {
+    let canvas = Canvas::new(size[0], size[1], Color32::TRANSPARENT, 64);
+
+    let width = canvas.width() as f32;
+    let height = canvas.height() as f32;
     
-    // Create a temporary stroke state
-    let mut stroke = StrokeState::new();
-    let mut undo_action = UndoAction { tiles: Vec::new(), selection: None, transform: None };
+    // Paint with the brush's own engine
+    let mut stroke = brush.engine.create();
+    let mut undo_action = UndoAction::new("Preview");
     let mut modified = HashSet::new();
+    let mut target = StrokeTarget { pool, canvas: &canvas, selection: None, undo_action: &mut undo_action, modified_tiles: &mut modified };
+    stroke.begin_stroke();
     
     // Draw an S curve with pressure
     // S curve: two cubic beziers or just a sine wave.
@@ -242,21 +356,54 @@ fn render_preview(state: &mut BrushPreviewState, brush: &mut Brush, pool: &Threa
         // Optional: apply to opacity
         // brush.brush_options.opacity = original_opacity * pressure;
         
-        stroke.add_point(pool, &state.canvas, brush, None, pos, &mut undo_action, &mut modified);
+        stroke.add_sample(&mut target, brush, pos);
     }
+    stroke.end_stroke(&mut target, brush);
     
     brush.brush_options.diameter = original_diameter;
     brush.brush_options.opacity = original_opacity;
     
     // Convert canvas to image
-    let mut image = egui::ColorImage::new([state.canvas.width(), state.canvas.height()], Color32::TRANSPARENT);
+    let mut image = egui::ColorImage::new([canvas.width(), canvas.height()], Color32::TRANSPARENT);
     // We reuse write_region_to_color_image with step=1 for full quality
-    state.canvas.write_region_to_color_image(0, 0, state.canvas.width(), state.canvas.height(), &mut image, 1);
-    
-    // Upload texture
-    state.texture = Some(ctx.load_texture("brush_preview", image, egui::TextureOptions::NEAREST));
+    canvas.write_region_to_color_image(0, 0, canvas.width(), canvas.height(), &mut image, 1);
+    image
 }
 
+/// Plot the alpha of each pixel across the middle of one dab, as bars from the bottom edge.
+///
+/// Dabs that fit get a whole number of columns per pixel so every step of the falloff shows;
+/// wider ones are sampled down to the plot width.
+fn render_cross_section(brush: &Brush, size: [usize; 2]) -> egui::ColorImage {
+    let anti_aliasing = brush.anti_aliasing && brush.brush_type == BrushType::Soft;
+    let profile = dab_mask::cross_section(&brush.brush_options, anti_aliasing);
+    let [width, height] = size;
+    let mut image = egui::ColorImage::new(size, Color32::from_gray(30));
+
+    let scale = (width / profile.len()).max(1);
+    let used = (profile.len() * scale).min(width);
+    let left = (width - used) / 2;
+    for y in [height / 2, height - 1] {
+        for x in 0..width {
+            image[(x, y)] = Color32::from_gray(70);
+        }
+    }
+    for column in 0..used {
+        let pixel = if profile.len() * scale <= width { column / scale } else { column * profile.len() / used };
+        let alpha = profile[pixel].clamp(0.0, 1.0);
+        let bar = (alpha * (height - 1) as f32).round() as usize;
+        if bar == 0 {
+            continue;
+        }
+        let x = left + column;
+        let top = height - 1 - bar;
+        for y in top + 1..height {
+            image[(x, y)] = Color32::from_rgb(60, 110, 150);
+        }
+        image[(x, top)] = Color32::LIGHT_BLUE;
+    }
+    image
+}
 
 fn curve_editor(ui: &mut egui::Ui, curve: &mut SoftnessCurve) -> bool {
     let mut changed = false;
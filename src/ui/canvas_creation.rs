@@ -2,6 +2,8 @@ use crate::{
     BackgroundChoice, CanvasUnit, ColorDepth, ColorModel, NewCanvasSettings, Orientation,
     PainterApp,
 };
+use crate::app::state::{TILE_SIZES, auto_tile_size};
+use crate::utils::i18n::{tr, trf};
 use eframe::egui;
 
 /// Modal dialog to configure and create a new canvas, inspired by Krita's new file window.
@@ -11,7 +13,8 @@ pub fn canvas_creation_modal(app: &mut PainterApp, ctx: &egui::Context) {
     }
 
     let mut open = app.show_new_canvas_modal;
-    egui::Window::new("New Canvas")
+    let thread_count = app.thread_count;
+    egui::Window::new(tr("New Canvas"))
         .open(&mut open)
         .collapsible(false)
         .resizable(false)
@@ -19,47 +22,47 @@ pub fn canvas_creation_modal(app: &mut PainterApp, ctx: &egui::Context) {
             let settings: &mut NewCanvasSettings = &mut app.new_canvas;
 
             ui.horizontal(|ui| {
-                ui.label("Name");
+                ui.label(tr("Name"));
                 ui.text_edit_singleline(&mut settings.name);
             });
 
             ui.separator();
-            ui.heading("Dimensions");
+            ui.heading(tr("Dimensions"));
             ui.horizontal(|ui| {
-                ui.label("Width");
+                ui.label(tr("Width"));
                 ui.add(
                     egui::DragValue::new(&mut settings.width)
                         .speed(1.0)
                         .range(1.0..=50000.0)
                         .suffix(settings.unit.label()),
                 );
-                ui.label("Height");
+                ui.label(tr("Height"));
                 ui.add(
                     egui::DragValue::new(&mut settings.height)
                         .speed(1.0)
                         .range(1.0..=50000.0)
                         .suffix(settings.unit.label()),
                 );
-                egui::ComboBox::from_label("Units")
-                    .selected_text(settings.unit.label())
+                egui::ComboBox::from_label(tr("Units"))
+                    .selected_text(tr(settings.unit.label()))
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut settings.unit, CanvasUnit::Pixels, "Pixels");
-                        ui.selectable_value(&mut settings.unit, CanvasUnit::Inches, "Inches");
+                        ui.selectable_value(&mut settings.unit, CanvasUnit::Pixels, tr("Pixels"));
+                        ui.selectable_value(&mut settings.unit, CanvasUnit::Inches, tr("Inches"));
                         ui.selectable_value(
                             &mut settings.unit,
                             CanvasUnit::Millimeters,
-                            "Millimeters",
+                            tr("Millimeters"),
                         );
                         ui.selectable_value(
                             &mut settings.unit,
                             CanvasUnit::Centimeters,
-                            "Centimeters",
+                            tr("Centimeters"),
                         );
                     });
             });
 
             ui.horizontal(|ui| {
-                ui.label("Resolution (DPI)");
+                ui.label(tr("Resolution (DPI)"));
                 ui.add(
                     egui::DragValue::new(&mut settings.resolution)
                         .speed(1.0)
@@ -67,13 +70,13 @@ pub fn canvas_creation_modal(app: &mut PainterApp, ctx: &egui::Context) {
                 );
                 let mut orientation_changed = false;
                 orientation_changed |= ui
-                    .selectable_value(&mut settings.orientation, Orientation::Portrait, "Portrait")
+                    .selectable_value(&mut settings.orientation, Orientation::Portrait, tr("Portrait"))
                     .changed();
                 orientation_changed |= ui
                     .selectable_value(
                         &mut settings.orientation,
                         Orientation::Landscape,
-                        "Landscape",
+                        tr("Landscape"),
                     )
                     .changed();
                 if orientation_changed {
@@ -82,38 +85,38 @@ pub fn canvas_creation_modal(app: &mut PainterApp, ctx: &egui::Context) {
             });
 
             ui.separator();
-            ui.heading("Color");
+            ui.heading(tr("Color"));
             ui.horizontal(|ui| {
-                ui.label("Background");
-                ui.radio_value(&mut settings.background, BackgroundChoice::White, "White");
-                ui.radio_value(&mut settings.background, BackgroundChoice::Black, "Black");
+                ui.label(tr("Background"));
+                ui.radio_value(&mut settings.background, BackgroundChoice::White, tr("White"));
+                ui.radio_value(&mut settings.background, BackgroundChoice::Black, tr("Black"));
                 ui.radio_value(
                     &mut settings.background,
                     BackgroundChoice::Transparent,
-                    "Transparent",
+                    tr("Transparent"),
                 );
-                ui.radio_value(&mut settings.background, BackgroundChoice::Custom, "Custom");
+                ui.radio_value(&mut settings.background, BackgroundChoice::Custom, tr("Custom"));
                 if settings.background == BackgroundChoice::Custom {
                     ui.color_edit_button_srgba(&mut settings.custom_bg);
                 }
             });
 
             ui.horizontal(|ui| {
-                ui.label("Color Model");
+                ui.label(tr("Color Model"));
                 egui::ComboBox::from_id_salt("color_model")
-                    .selected_text(match settings.color_model {
+                    .selected_text(tr(match settings.color_model {
                         ColorModel::Rgba => "RGBA",
                         ColorModel::Grayscale => "Grayscale",
-                    })
+                    }))
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut settings.color_model, ColorModel::Rgba, "RGBA");
+                        ui.selectable_value(&mut settings.color_model, ColorModel::Rgba, tr("RGBA"));
                         ui.selectable_value(
                             &mut settings.color_model,
                             ColorModel::Grayscale,
-                            "Grayscale",
+                            tr("Grayscale"),
                         );
                     });
-                ui.label("Depth");
+                ui.label(tr("Depth"));
                 egui::ComboBox::from_id_salt("color_depth")
                     .selected_text(match settings.color_depth {
                         ColorDepth::Bit8 => "8-bit integer",
@@ -124,37 +127,54 @@ pub fn canvas_creation_modal(app: &mut PainterApp, ctx: &egui::Context) {
                         ui.selectable_value(
                             &mut settings.color_depth,
                             ColorDepth::Bit8,
-                            "8-bit integer",
+                            tr("8-bit integer"),
                         );
                         ui.selectable_value(
                             &mut settings.color_depth,
                             ColorDepth::Bit16,
-                            "16-bit integer",
+                            tr("16-bit integer"),
                         );
                         ui.selectable_value(
                             &mut settings.color_depth,
                             ColorDepth::Float32,
-                            "32-bit float",
+                            tr("32-bit float"),
                         );
                     });
             });
             ui.weak(
-                "Grayscale paints in a single channel; CMYK converts selections into an on-screen approximation.",
+                tr("Grayscale paints in a single channel; CMYK converts selections into an on-screen approximation."),
             );
 
             let (px_w, px_h) = settings.dimensions_in_pixels();
-            ui.label(format!(
-                "Result: {} × {} px @ {:.0} dpi",
-                px_w, px_h, settings.resolution
+            let auto_size = auto_tile_size(px_w, px_h, thread_count);
+            ui.horizontal(|ui| {
+                ui.label(tr("Tile size"));
+                egui::ComboBox::from_id_salt("tile_size")
+                    .selected_text(match settings.tile_size {
+                        Some(size) => format!("{size} px"),
+                        None => trf("Automatic ({} px)", &[&auto_size]),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut settings.tile_size, None, trf("Automatic ({} px)", &[&auto_size]));
+                        for size in TILE_SIZES {
+                            ui.selectable_value(&mut settings.tile_size, Some(size), format!("{size} px"));
+                        }
+                    });
+            })
+            .response
+            .on_hover_text(tr("Canvas pixels are stored and redrawn in square tiles. Bigger tiles suit very large canvases and big brushes, smaller ones keep small strokes cheap."));
+            ui.label(trf(
+                "Result: {} × {} px @ {} dpi",
+                &[&px_w, &px_h, &format!("{:.0}", settings.resolution)],
             ));
 
             ui.separator();
             ui.horizontal(|ui| {
-                if ui.button("Create").clicked() {
+                if ui.button(tr("Create")).clicked() {
                     app.apply_new_canvas(ctx);
                     app.show_new_canvas_modal = false;
                 }
-                if ui.button("Cancel").clicked() {
+                if ui.button(tr("Cancel")).clicked() {
                     app.show_new_canvas_modal = false;
                 }
             });
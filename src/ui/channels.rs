@@ -0,0 +1,59 @@
+use crate::PainterApp;
+use crate::ui::accessibility;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+/// Dock tab storing named selections on the document and loading them back.
+pub fn channels_panel(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let mut load = None;
+    let mut delete = None;
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut app.new_channel_name)
+                .desired_width(140.0)
+                .hint_text(tr("Channel name")),
+        );
+        let has_selection = app.selection_manager.has_selection();
+        if ui
+            .add_enabled(has_selection, egui::Button::new(tr("💾 Save Selection")))
+            .clicked()
+        {
+            let name = if app.new_channel_name.trim().is_empty() {
+                trf("Selection {}", &[&(app.selection_channels.len() + 1)])
+            } else {
+                app.new_channel_name.trim().to_string()
+            };
+            if let Some(channel) = app.selection_manager.save_channel(name) {
+                app.selection_channels.push(channel);
+                app.new_channel_name.clear();
+            }
+        }
+    });
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            if app.selection_channels.is_empty() {
+                ui.label(egui::RichText::new(tr("No saved selections")).weak());
+            }
+            for (idx, channel) in app.selection_channels.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.button(tr("Load")).on_hover_text(tr("Replace the selection with this channel")).clicked() {
+                        load = Some(idx);
+                    }
+                    ui.add(egui::TextEdit::singleline(&mut channel.name).desired_width(120.0));
+                    if accessibility::named(ui.small_button("🗑"), tr("Delete")).clicked() {
+                        delete = Some(idx);
+                    }
+                });
+            }
+        });
+
+    if let Some(channel) = load.and_then(|idx| app.selection_channels.get(idx)) {
+        app.selection_manager.load_channel(channel);
+    }
+    if let Some(idx) = delete {
+        app.selection_channels.remove(idx);
+    }
+}
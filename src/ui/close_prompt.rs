@@ -0,0 +1,52 @@
+use crate::PainterApp;
+use crate::ui::export_modal::ExportScope;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+/// Asks what to do with unsaved changes when the window is closed. There is no project file,
+/// so saving means exporting the whole canvas; the window closes once that export succeeds.
+pub fn close_prompt_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    if !app.show_close_prompt {
+        return;
+    }
+
+    let mut open = true;
+    let mut save = false;
+    let mut discard = false;
+    egui::Window::new(tr("Unsaved Changes"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.label(trf("{} has changes that haven't been exported.", &[&app.document_name()]));
+            ui.label(egui::RichText::new(tr("Closing keeps a flattened copy for the start screen, but layers are lost.")).weak());
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button(tr("Save...")).clicked() {
+                    save = true;
+                }
+                if ui.button(tr("Discard")).clicked() {
+                    discard = true;
+                }
+                if ui.button(tr("Cancel")).clicked() {
+                    app.show_close_prompt = false;
+                }
+            });
+        });
+
+    if save {
+        app.show_close_prompt = false;
+        app.close_after_save = true;
+        app.open_export(ExportScope::Canvas);
+    }
+    if discard {
+        app.show_close_prompt = false;
+        app.unsaved_changes = false;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+    if !open {
+        app.show_close_prompt = false;
+    }
+}
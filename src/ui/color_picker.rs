@@ -1,19 +1,83 @@
 use crate::ColorModel;
 use crate::brush_engine::brush::Brush;
-use crate::utils::color::ColorManipulation;
+use crate::utils::color::{ColorManipulation, ColorNudge, Harmony, NudgeSpace, lab_to_srgb, srgb_to_lab};
+use crate::ui::accessibility;
+use crate::utils::i18n::{tr, trf};
 use eframe::egui;
 use egui::Color32;
 
 const TRI_SIDE: f32 = 200.0;
 const SLIDER_MIN: f32 = 160.0;
 const SLIDER_MAX: f32 = 320.0;
+const SWATCH_SIZE: f32 = 22.0;
+
+/// Color space the RGBA picker's controls work in. The brush color is always stored as `Color32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PickerSpace {
+    Hsv,
+    Hsl,
+    Lab,
+}
+
+impl PickerSpace {
+    const ALL: [PickerSpace; 3] = [PickerSpace::Hsv, PickerSpace::Hsl, PickerSpace::Lab];
+
+    fn label(self) -> &'static str {
+        match self {
+            PickerSpace::Hsv => "HSV",
+            PickerSpace::Hsl => "HSL",
+            PickerSpace::Lab => "Lab",
+        }
+    }
+}
 
+/// Lab a*/b* sliders span -LAB_AB_RANGE..LAB_AB_RANGE, which covers the sRGB gamut.
+const LAB_AB_RANGE: f32 = 110.0;
+
+/// Components are kept per space so dragging one slider does not make the others drift
+/// through 8-bit rounding, and so the hue survives grays.
 #[derive(Clone, Copy, Debug)]
 struct PickerState {
+    space: PickerSpace,
     hue: f32,
+    hsl: [f32; 3],
+    lab: [f32; 3],
     last_color: Color32,
 }
 
+impl PickerState {
+    fn new(color: Color32) -> Self {
+        let mut state = Self {
+            space: PickerSpace::Hsv,
+            hue: 0.0,
+            hsl: [0.0; 3],
+            lab: [0.0; 3],
+            last_color: color,
+        };
+        state.sync(color, None);
+        state
+    }
+
+    /// Take `color` as the current color and refresh every space except `edited`, which
+    /// already holds the values the user chose.
+    fn sync(&mut self, color: Color32, edited: Option<PickerSpace>) {
+        let (h, s, _, _) = color.to_hsva();
+        // Grays have no hue, keep the last one
+        let hue = if s > 0.0 { h } else { self.hue };
+        if edited != Some(PickerSpace::Hsv) {
+            self.hue = hue;
+        }
+        if edited != Some(PickerSpace::Hsl) {
+            let (_, s, l, _) = color.to_hsla();
+            self.hsl = [hue, s, l];
+        }
+        if edited != Some(PickerSpace::Lab) {
+            self.lab = srgb_to_lab(color);
+        }
+        self.last_color = color;
+    }
+}
+
 fn slider_width(ui: &egui::Ui) -> f32 {
     ui.available_width().clamp(SLIDER_MIN, SLIDER_MAX)
 }
@@ -43,11 +107,11 @@ fn gradient_slider(
     ui: &mut egui::Ui,
     width: f32,
     value: &mut f32,
-    label: &str,
+    label: &'static str,
     color_at: &dyn Fn(f32) -> Color32,
     checker: bool,
 ) -> bool {
-    ui.label(label);
+    ui.label(tr(label));
     let bar_height = 18.0;
     let (rect, response) =
         ui.allocate_exact_size(egui::vec2(width, bar_height), egui::Sense::click_and_drag());
@@ -100,6 +164,16 @@ fn gradient_slider(
         egui::Stroke::new(1.0, Color32::from_gray(40)),
     );
 
+    response.widget_info(|| egui::WidgetInfo::slider(ui.is_enabled(), f64::from(*value), tr(label)));
+    accessibility::focus_ring(ui, &response);
+    // Left/Right nudge a focused bar, Shift for bigger steps
+    let steps = accessibility::arrow_steps(ui, &response, false);
+    if steps != 0 {
+        let step = if ui.input(|i| i.modifiers.shift) { 0.1 } else { 0.01 };
+        *value = (*value + steps as f32 * step).clamp(0.0, 1.0);
+        return true;
+    }
+
     let pointer_down = ui.input(|i| i.pointer.primary_down());
     if (response.hovered() || response.dragged()) && pointer_down {
         if let Some(pos) = response.interact_pointer_pos() {
@@ -210,52 +284,119 @@ fn hsva_triangle(ui: &mut egui::Ui, hue: f32, sat: &mut f32, val: &mut f32, side
     changed
 }
 
-/// Interactive HSVA picker that updates the active brush.brush_options.color.
-pub fn color_picker_panel(ui: &mut egui::Ui, brush: &mut Brush, color_model: ColorModel) {
+/// Interactive HSV, HSL or Lab picker that updates the active brush.brush_options.color.
+pub fn color_picker_panel(ui: &mut egui::Ui, brush: &mut Brush, color_model: ColorModel, nudge: &mut ColorNudge) {
     let min_width = slider_width(ui);
     ui.set_min_width(min_width);
 
     let id = ui.id().with("color_picker_state");
-    let (mut hue, mut sat, mut val, mut alpha) = brush.brush_options.color.to_hsva();
-    let mut state = ui.ctx().data_mut(|d| {
-        d.get_temp::<PickerState>(id).unwrap_or(PickerState {
-            hue,
-            last_color: brush.brush_options.color,
-        })
-    });
-
-    if state.last_color != brush.brush_options.color {
-        let (nh, _, _, _) = brush.brush_options.color.to_hsva();
-        state.hue = nh;
-        state.last_color = brush.brush_options.color;
+    let color = brush.brush_options.color;
+    let mut state = ui.ctx().data_mut(|d| d.get_temp::<PickerState>(id).unwrap_or_else(|| PickerState::new(color)));
+    if state.last_color != color {
+        state.sync(color, None);
     }
 
-    hue = state.hue;
-
-    let mut apply_color = false;
-
     egui::ScrollArea::vertical()
         .auto_shrink([false; 2])
-        .show(ui, |ui| match color_model {
-            ColorModel::Rgba => {
-                apply_color = rgba_picker(ui, &mut hue, &mut sat, &mut val, &mut alpha);
-            }
-            ColorModel::Grayscale => {
-                if grayscale_picker(ui, brush) {
-                    let (h, _, _, _) = brush.brush_options.color.to_hsva();
-                    state.hue = h;
-                    state.last_color = brush.brush_options.color;
-                    ui.ctx().data_mut(|d| d.insert_temp(id, state));
+        .show(ui, |ui| {
+            match color_model {
+                ColorModel::Rgba => {
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Space"));
+                        for space in PickerSpace::ALL {
+                            ui.selectable_value(&mut state.space, space, tr(space.label()));
+                        }
+                    });
+                    let alpha = color.a() as f32 / 255.0;
+                    let picked = match state.space {
+                        PickerSpace::Hsv => {
+                            let (_, mut sat, mut val, mut alpha) = color.to_hsva();
+                            rgba_picker(ui, &mut state.hue, &mut sat, &mut val, &mut alpha)
+                                .then(|| Color32::from_hsva(state.hue, sat, val, alpha))
+                        }
+                        PickerSpace::Hsl => hsl_picker(ui, &mut state.hsl, alpha),
+                        PickerSpace::Lab => lab_picker(ui, &mut state.lab, alpha),
+                    };
+                    if let Some(picked) = picked {
+                        brush.brush_options.color = picked;
+                        state.sync(picked, Some(state.space));
+                    }
+                }
+                ColorModel::Grayscale => {
+                    if grayscale_picker(ui, brush) {
+                        state.sync(brush.brush_options.color, None);
+                    }
                 }
             }
+            nudge_controls(ui, brush, color_model, nudge);
         });
 
-    if apply_color {
-        brush.brush_options.color = Color32::from_hsva(hue, sat, val, alpha);
-        state.hue = hue;
-        state.last_color = brush.brush_options.color;
-        ui.ctx().data_mut(|d| d.insert_temp(id, state));
+    ui.ctx().data_mut(|d| d.insert_temp(id, state));
+}
+
+fn hsl_picker(ui: &mut egui::Ui, hsl: &mut [f32; 3], alpha: f32) -> Option<Color32> {
+    let width = slider_width(ui);
+    let [mut h, mut s, mut l] = *hsl;
+    let mut a = alpha;
+    let mut changed = false;
+
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, 32.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 4.0, Color32::from_hsla(h, s, l, 1.0));
+
+    changed |= gradient_slider(ui, width, &mut h, "Hue:", &|t| Color32::from_hsla(t, 1.0, 0.5, 1.0), false);
+    changed |= gradient_slider(ui, width, &mut s, "Saturation:", &|t| Color32::from_hsla(h, t, l, 1.0), false);
+    changed |= gradient_slider(ui, width, &mut l, "Lightness:", &|t| Color32::from_hsla(h, s, t, 1.0), false);
+    changed |= gradient_slider(ui, width, &mut a, "Opacity:", &|t| Color32::from_hsla(h, s, l, t), true);
+    changed |= harmony_swatches(ui, &mut h, &|hue| Color32::from_hsla(hue, s, l, 1.0));
+
+    if !changed {
+        return None;
     }
+    *hsl = [h, s, l];
+    Some(Color32::from_hsla(h, s, l, a))
+}
+
+/// CIELAB sliders. a* and b* reach colors outside sRGB, which are clipped when applied.
+fn lab_picker(ui: &mut egui::Ui, lab: &mut [f32; 3], alpha: f32) -> Option<Color32> {
+    let width = slider_width(ui);
+    let to_t = |v: f32| (v / LAB_AB_RANGE + 1.0) * 0.5;
+    let from_t = |t: f32| (t * 2.0 - 1.0) * LAB_AB_RANGE;
+    let [l, a_star, b_star] = *lab;
+    let (mut lt, mut at, mut bt) = (l / 100.0, to_t(a_star), to_t(b_star));
+    let mut alpha = alpha;
+    let mut changed = false;
+
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, 32.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 4.0, lab_to_srgb(*lab, 255));
+
+    changed |= gradient_slider(ui, width, &mut lt, "Lightness (L*):", &|t| lab_to_srgb([t * 100.0, a_star, b_star], 255), false);
+    changed |= gradient_slider(ui, width, &mut at, "Green–Red (a*):", &|t| lab_to_srgb([l, from_t(t), b_star], 255), false);
+    changed |= gradient_slider(ui, width, &mut bt, "Blue–Yellow (b*):", &|t| lab_to_srgb([l, a_star, from_t(t)], 255), false);
+    changed |= gradient_slider(ui, width, &mut alpha, "Opacity:", &|t| lab_to_srgb(*lab, 255).gamma_multiply(t), true);
+
+    // Harmonies turn the hue angle in the a*/b* plane and keep lightness and chroma
+    let chroma = a_star.hypot(b_star);
+    let mut angle = (b_star.atan2(a_star) / std::f32::consts::TAU).rem_euclid(1.0);
+    let ab_at = |turns: f32| {
+        let (sin, cos) = (turns * std::f32::consts::TAU).sin_cos();
+        (chroma * cos, chroma * sin)
+    };
+    if harmony_swatches(ui, &mut angle, &|turns| {
+        let (a, b) = ab_at(turns);
+        lab_to_srgb([l, a, b], 255)
+    }) {
+        (at, bt) = {
+            let (a, b) = ab_at(angle);
+            (to_t(a), to_t(b))
+        };
+        changed = true;
+    }
+
+    if !changed {
+        return None;
+    }
+    *lab = [lt * 100.0, from_t(at), from_t(bt)];
+    Some(lab_to_srgb(*lab, (alpha * 255.0).round() as u8))
 }
 
 fn grayscale_picker(ui: &mut egui::Ui, brush: &mut Brush) -> bool {
@@ -266,7 +407,7 @@ fn grayscale_picker(ui: &mut egui::Ui, brush: &mut Brush) -> bool {
     let mut alpha = brush.brush_options.color.a() as f32 / 255.0;
     let mut changed = false;
 
-    ui.label("Grayscale");
+    ui.label(tr("Grayscale"));
     changed |= gradient_slider(
         ui,
         width,
@@ -286,7 +427,7 @@ fn grayscale_picker(ui: &mut egui::Ui, brush: &mut Brush) -> bool {
 
     let mut preview = Color32::from_gray_alpha(value, alpha);
     ui.horizontal(|ui| {
-        ui.label("Preview");
+        ui.label(tr("Preview"));
         ui.color_edit_button_srgba(&mut preview);
     });
 
@@ -297,6 +438,43 @@ fn grayscale_picker(ui: &mut egui::Ui, brush: &mut Brush) -> bool {
     changed
 }
 
+/// Buttons for the warmer/cooler and lighter/darker shortcuts, plus their step sizes.
+fn nudge_controls(ui: &mut egui::Ui, brush: &mut Brush, color_model: ColorModel, nudge: &mut ColorNudge) {
+    egui::CollapsingHeader::new(tr("Nudge"))
+        .id_salt("color_nudge")
+        .show(ui, |ui| {
+            let color = brush.brush_options.color;
+            let mut nudged = None;
+            ui.horizontal(|ui| {
+                if color_model == ColorModel::Rgba {
+                    if ui.button(tr("Cooler")).clicked() {
+                        nudged = Some(nudge.apply(color, -1.0, 0.0));
+                    }
+                    if ui.button(tr("Warmer")).clicked() {
+                        nudged = Some(nudge.apply(color, 1.0, 0.0));
+                    }
+                }
+                if ui.button(tr("Darker")).clicked() {
+                    nudged = Some(nudge.apply(color, 0.0, -1.0));
+                }
+                if ui.button(tr("Lighter")).clicked() {
+                    nudged = Some(nudge.apply(color, 0.0, 1.0));
+                }
+            });
+            if let Some(color) = nudged {
+                brush.brush_options.color = color;
+            }
+            ui.horizontal(|ui| {
+                ui.label(tr("Space"));
+                for space in NudgeSpace::ALL {
+                    ui.selectable_value(&mut nudge.space, space, tr(space.label()));
+                }
+            });
+            ui.add(egui::Slider::new(&mut nudge.temperature_step, 0.005..=0.2).text(tr("Temperature step")));
+            ui.add(egui::Slider::new(&mut nudge.lightness_step, 0.005..=0.2).text(tr("Lightness step")));
+        });
+}
+
 #[allow(dead_code)]
 fn cmyk_picker(ui: &mut egui::Ui, brush: &mut Brush) {
     let width = slider_width(ui);
@@ -304,7 +482,7 @@ fn cmyk_picker(ui: &mut egui::Ui, brush: &mut Brush) {
     let mut changed = false;
     let mut color = Color32::from_cmyk(c, m, y, k, a);
 
-    ui.label("CMYK");
+    ui.label(tr("CMYK"));
     let (hue, mut sat, mut val, _) = color.to_hsva();
     let tri_side = ui.available_width().clamp(140.0, TRI_SIDE);
     changed |= hsva_triangle(ui, hue, &mut sat, &mut val, tri_side);
@@ -360,7 +538,7 @@ fn cmyk_picker(ui: &mut egui::Ui, brush: &mut Brush) {
 
     let mut preview = Color32::from_cmyk(c, m, y, k, a);
     ui.horizontal(|ui| {
-        ui.label("Preview");
+        ui.label(tr("Preview"));
         ui.color_edit_button_srgba(&mut preview);
     });
 
@@ -415,5 +593,46 @@ fn rgba_picker(
         true,
     );
 
+    let (sat, val) = (*sat, *val);
+    color_changed |= harmony_swatches(ui, hue, &|h| Color32::from_hsva(h, sat, val, 1.0));
+
     color_changed
 }
+
+/// Swatches for each harmony of the current hue. Clicking one moves the hue there; `color_at`
+/// renders a hue with the other components of the current color.
+fn harmony_swatches(ui: &mut egui::Ui, hue: &mut f32, color_at: &dyn Fn(f32) -> Color32) -> bool {
+    let mut picked = None;
+    egui::CollapsingHeader::new(tr("Harmonies"))
+        .id_salt("color_harmonies")
+        .show(ui, |ui| {
+            for harmony in Harmony::ALL {
+                ui.label(tr(harmony.label()));
+                ui.horizontal(|ui| {
+                    for (i, h) in harmony.hues(*hue).into_iter().enumerate() {
+                        let (rect, response) = ui.allocate_exact_size(egui::vec2(SWATCH_SIZE, SWATCH_SIZE), egui::Sense::click());
+                        ui.painter().rect_filled(rect, 3.0, color_at(h));
+                        // The base color is outlined so the scheme reads from it
+                        let stroke = if i == 0 || response.hovered() {
+                            egui::Stroke::new(2.0, ui.visuals().strong_text_color())
+                        } else {
+                            egui::Stroke::new(1.0, Color32::from_gray(80))
+                        };
+                        ui.painter().rect_stroke(rect, 3.0, stroke);
+                        let name = trf("Hue {}°", &[&format!("{:.0}", h * 360.0)]);
+                        accessibility::button(ui, &response, &name);
+                        if response.on_hover_text(name).clicked() {
+                            picked = Some(h);
+                        }
+                    }
+                });
+            }
+        });
+    match picked {
+        Some(h) if (h - *hue).abs() > f32::EPSILON => {
+            *hue = h;
+            true
+        }
+        _ => false,
+    }
+}
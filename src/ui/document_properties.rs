@@ -0,0 +1,48 @@
+use crate::PainterApp;
+use crate::app::document_info::format_time;
+use crate::utils::i18n::tr;
+use eframe::egui;
+
+/// File > Document Properties: title, author and description, saved into PNG and JPEG exports
+/// and the session file.
+pub fn document_properties_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    if !app.show_document_properties {
+        return;
+    }
+    let mut open = true;
+    let tile_size = app.canvas.tile_size();
+    let info = &mut app.document_info;
+    let mut edited = false;
+    egui::Window::new(tr("Document Properties"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Grid::new("document_properties").num_columns(2).show(ui, |ui| {
+                ui.label(tr("Title"));
+                edited |= ui.text_edit_singleline(&mut info.title).changed();
+                ui.end_row();
+                ui.label(tr("Author"));
+                edited |= ui.text_edit_singleline(&mut info.author).changed();
+                ui.end_row();
+                ui.label(tr("Description"));
+                edited |= ui.add(egui::TextEdit::multiline(&mut info.description).desired_rows(4)).changed();
+                ui.end_row();
+                ui.label(tr("Created"));
+                ui.label(format_time(info.created));
+                ui.end_row();
+                ui.label(tr("Modified"));
+                ui.label(format_time(info.modified));
+                ui.end_row();
+                ui.label(tr("Tile size"));
+                ui.label(format!("{tile_size} px"));
+                ui.end_row();
+            });
+            ui.small(tr("Stored as text chunks in PNG exports and as EXIF in JPEG exports."));
+        });
+    if edited {
+        app.mark_modified();
+    }
+    app.show_document_properties = open;
+}
@@ -1,7 +1,14 @@
 use crate::{
     PainterApp,
-    utils::exporter::{ExportFormat, save_color_image},
+    app::{document_info::DocumentInfo, recent_files},
+    canvas::{animation::FrameSources, palette::Palette},
+    utils::file_dialog,
+    utils::exporter::{
+        AnimationFormat, EXPORT_BAND_ROWS, ExportFormat, composite_band, save_animation, save_color_image_with_info, save_indexed_png,
+        save_png_bands, selection_image,
+    },
 };
+use crate::utils::i18n::{tr, trf};
 use eframe::egui;
 use eframe::egui::ColorImage;
 use std::path::{Path, PathBuf};
@@ -15,38 +22,78 @@ pub fn export_modal(app: &mut PainterApp, ctx: &egui::Context) {
     }
 
     let mut open = app.show_export_modal;
-    egui::Window::new("Export Canvas")
+    let dialog_dir = app.dialog_dir();
+    egui::Window::new(tr(app.export_settings.scope.title()))
         .open(&mut open)
         .collapsible(false)
         .resizable(false)
         .show(ctx, |ui| {
             let settings = &mut app.export_settings;
 
-            ui.horizontal(|ui| {
-                ui.label("Format");
-                egui::ComboBox::from_label("Format")
-                    .selected_text(settings.format.label())
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut settings.format, ExportFormat::PNG, "PNG");
-                        ui.selectable_value(&mut settings.format, ExportFormat::JPEG, "JPEG");
-                        ui.selectable_value(&mut settings.format, ExportFormat::TIFF, "TIFF");
+            match settings.scope {
+                ExportScope::Canvas => {
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Format"));
+                        egui::ComboBox::from_label(tr("Format"))
+                            .selected_text(tr(settings.format.label()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut settings.format, ExportFormat::PNG, tr("PNG"));
+                                ui.selectable_value(&mut settings.format, ExportFormat::JPEG, tr("JPEG"));
+                                ui.selectable_value(&mut settings.format, ExportFormat::TIFF, tr("TIFF"));
+                            });
                     });
-            });
+                }
+                ExportScope::Selection => {
+                    // Cut-outs need the alpha channel
+                    settings.format = ExportFormat::PNG;
+                    ui.label(tr("Pixels inside the selection, trimmed to its bounds, as a PNG."));
+                }
+                ExportScope::Animation => {
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Format"));
+                        egui::ComboBox::from_id_salt("animation_format")
+                            .selected_text(tr(settings.animation_format.label()))
+                            .show_ui(ui, |ui| {
+                                for format in [AnimationFormat::Gif, AnimationFormat::Apng, AnimationFormat::PngSequence] {
+                                    ui.selectable_value(&mut settings.animation_format, format, tr(format.label()));
+                                }
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Frame rate"));
+                        ui.add(egui::DragValue::new(&mut app.animation.fps).range(1.0..=60.0).suffix(" fps"));
+                    });
+                    if settings.animation_format != AnimationFormat::PngSequence {
+                        ui.checkbox(&mut settings.looping, tr("Loop"));
+                    }
+                    ui.label(format!("{} frames", app.animation.frame_count()));
+                }
+            }
+            if settings.scope != ExportScope::Animation && settings.format == ExportFormat::PNG {
+                let has_palette = !app.canvas.palette.colors.is_empty();
+                ui.add_enabled(has_palette, egui::Checkbox::new(&mut settings.indexed, tr("Indexed (document palette)")))
+                    .on_disabled_hover_text(tr("Add colors to the palette first."));
+            }
+            if settings.scope != ExportScope::Animation {
+                ui.add_enabled(settings.format != ExportFormat::JPEG, egui::Checkbox::new(&mut settings.transparent_background, tr("Transparent background")))
+                    .on_hover_text(tr("Leave out the background layer, for lineart and stickers."))
+                    .on_disabled_hover_text(tr("JPEG has no alpha channel."));
+            }
 
             ui.separator();
-            ui.heading("Destination");
+            ui.heading(tr("Destination"));
             ui.horizontal(|ui| {
-                ui.label("File");
+                ui.label(tr("File"));
                 let display = settings
                     .chosen_path
                     .as_ref()
                     .map(|p| p.display().to_string())
                     .unwrap_or_else(|| settings.default_file_name());
                 ui.monospace(display);
-                if ui.button("Choose...").clicked() {
-                    if let Some(path) = pick_file(&settings.default_file_name()) {
-                        settings.chosen_path = Some(path);
-                    }
+                if ui.button(tr("Choose...")).clicked()
+                    && let Some(path) = settings.pick_path(dialog_dir.as_deref())
+                {
+                    settings.chosen_path = Some(path);
                 }
             });
 
@@ -58,7 +105,7 @@ pub fn export_modal(app: &mut PainterApp, ctx: &egui::Context) {
                 ui.add(
                     egui::ProgressBar::new(app.export_progress)
                         .desired_width(200.0)
-                        .text("Exporting..."),
+                        .text(tr("Exporting...")),
                 );
             }
 
@@ -66,33 +113,99 @@ pub fn export_modal(app: &mut PainterApp, ctx: &egui::Context) {
             ui.horizontal(|ui| {
                 let disabled = app.export_in_progress;
                 if ui
-                    .add_enabled(!disabled, egui::Button::new("Export"))
+                    .add_enabled(!disabled, egui::Button::new(tr("Export")))
                     .clicked()
                 {
+                    // Ask where to save when no destination was chosen yet
+                    if settings.chosen_path.is_none() {
+                        let Some(path) = settings.pick_path(dialog_dir.as_deref()) else {
+                            return;
+                        };
+                        settings.chosen_path = Some(path);
+                    }
                     let target = settings.output_path();
                     let format = settings.format;
+                    let palette = (format == ExportFormat::PNG && settings.indexed && !app.canvas.palette.colors.is_empty())
+                        .then(|| app.canvas.palette.clone());
+                    let info = app.document_info.clone();
+                    let hide_background = settings.transparent_background && format != ExportFormat::JPEG;
 
                     // Flatten on the UI thread, then save on a worker thread to avoid blocking.
-                    let (w, h) = (app.canvas.width(), app.canvas.height());
-                    let mut img = ColorImage::new([w, h], egui::Color32::TRANSPARENT);
-                    app.canvas
-                        .write_region_to_color_image(0, 0, w, h, &mut img, 1);
+                    // Plain PNGs are flattened a band per frame and encoded as the bands arrive,
+                    // animation frames on the worker thread itself.
+                    let job = match settings.scope {
+                        ExportScope::Canvas if format == ExportFormat::PNG && palette.is_none() => {
+                            app.pending_recent = Some((target.clone(), recent_files::thumbnail(&app.canvas)));
+                            let (sender, bands) = mpsc::sync_channel(2);
+                            let size = (app.canvas.width(), app.canvas.height());
+                            app.export_bands = Some(ExportBands { sender, next_row: 0, pending: None, hide_background, size });
+                            ExportJob::Bands { bands, width: size.0, height: size.1, info }
+                        }
+                        ExportScope::Canvas => {
+                            app.pending_recent = Some((target.clone(), recent_files::thumbnail(&app.canvas)));
+                            let (w, h) = (app.canvas.width(), app.canvas.height());
+                            let mut img = ColorImage::new([w, h], egui::Color32::TRANSPARENT);
+                            app.canvas.with_background_hidden(hide_background, |canvas| {
+                                canvas.write_region_to_color_image(0, 0, w, h, &mut img, 1)
+                            });
+                            ExportJob::Image { img, format, palette, info }
+                        }
+                        ExportScope::Selection => match app
+                            .canvas
+                            .with_background_hidden(hide_background, |canvas| selection_image(canvas, &app.selection_manager))
+                        {
+                            Some(img) => ExportJob::Image { img, format, palette, info },
+                            None => {
+                                app.export_message = Some(tr("The selection doesn't cover any pixels").to_string());
+                                return;
+                            }
+                        },
+                        ExportScope::Animation => ExportJob::Animation {
+                            frames: app.animation.frame_sources(&app.canvas),
+                            format: settings.animation_format,
+                            fps: app.animation.fps,
+                            looping: settings.looping,
+                        },
+                    };
 
                     app.export_in_progress = true;
                     app.export_progress = 0.05;
-                    app.export_message = Some("Exporting...".to_string());
+                    app.export_message = Some(tr("Exporting...").to_string());
                     let (tx, rx) = mpsc::channel();
                     app.export_progress_rx = Some(rx);
+                    // Encoding runs on the background pool so anything parallel in it leaves strokes alone
+                    let pool = app.background_pool.clone();
                     app.export_task = Some(thread::spawn(move || {
                         let _ = tx.send(ExportProgress {
                             progress: 0.2,
-                            message: Some("Saving file...".to_string()),
+                            message: Some(tr("Saving file...").to_string()),
                         });
-                        let result =
-                            save_color_image(img, target.clone(), format).map(|_| target.clone());
+                        let result = pool.install(|| match job {
+                            ExportJob::Image { img, format, palette, info } => match &palette {
+                                Some(palette) => save_indexed_png(img, target.clone(), palette, &info),
+                                None => save_color_image_with_info(img, target.clone(), format, &info),
+                            },
+                            ExportJob::Bands { bands, width, height, info } => {
+                                save_png_bands(&target, width, height, &info, bands, |done| {
+                                    let _ = tx.send(ExportProgress {
+                                        progress: 0.2 + 0.8 * done,
+                                        message: None,
+                                    });
+                                })
+                            }
+                            ExportJob::Animation { frames, format, fps, looping } => {
+                                save_animation(&frames.render_all(1), &target, format, fps, looping, |done| {
+                                    let _ = tx.send(ExportProgress {
+                                        progress: 0.2 + 0.8 * done,
+                                        message: None,
+                                    });
+                                })
+                            }
+                        })
+                        .map(|_| target.clone());
                         match result {
                             Ok(path) => {
-                                let msg = format!("Saved to {}", path.display());
+                                let msg = trf("Saved to {}", &[&path.display()]);
                                 let _ = tx.send(ExportProgress {
                                     progress: 1.0,
                                     message: Some(msg.clone()),
@@ -100,7 +213,7 @@ pub fn export_modal(app: &mut PainterApp, ctx: &egui::Context) {
                                 Ok(msg)
                             }
                             Err(err) => {
-                                let msg = format!("Export failed: {err}");
+                                let msg = trf("Export failed: {}", &[&err]);
                                 let _ = tx.send(ExportProgress {
                                     progress: 1.0,
                                     message: Some(msg.clone()),
@@ -111,7 +224,7 @@ pub fn export_modal(app: &mut PainterApp, ctx: &egui::Context) {
                     }));
                 }
                 if ui
-                    .add_enabled(!disabled, egui::Button::new("Cancel"))
+                    .add_enabled(!disabled, egui::Button::new(tr("Cancel")))
                     .clicked()
                 {
                     app.show_export_modal = false;
@@ -122,10 +235,111 @@ pub fn export_modal(app: &mut PainterApp, ctx: &egui::Context) {
     app.show_export_modal = open;
 }
 
-fn pick_file(default_name: &str) -> Option<PathBuf> {
-    rfd::FileDialog::new()
-        .set_file_name(default_name)
-        .save_file()
+/// Pixels handed to the export worker.
+enum ExportJob {
+    Image {
+        img: ColorImage,
+        format: ExportFormat,
+        palette: Option<Palette>,
+        info: DocumentInfo,
+    },
+    /// A PNG of the canvas, whose rows arrive in bands from [`feed_export_bands`].
+    Bands {
+        bands: mpsc::Receiver<Vec<u8>>,
+        width: usize,
+        height: usize,
+        info: DocumentInfo,
+    },
+    Animation {
+        frames: FrameSources,
+        format: AnimationFormat,
+        fps: f32,
+        looping: bool,
+    },
+}
+
+/// The flattening side of a streamed PNG export: the canvas rows still to be sent to the
+/// encoder thread. The canvas must not change until it is done, so editing is locked
+/// meanwhile, see [`export_lock_overlay`].
+pub struct ExportBands {
+    sender: mpsc::SyncSender<Vec<u8>>,
+    next_row: usize,
+    /// A flattened band the encoder had no room for yet; it goes out before the next one is made.
+    pending: Option<Vec<u8>>,
+    hide_background: bool,
+    /// Canvas size the export started with.
+    size: (usize, usize),
+}
+
+/// Flatten the next band of a streamed export and hand it to the encoder without waiting:
+/// when the encoder is still busy the band is kept for the next frame. Dropping the sender
+/// after the last band, or when the encoder gave up, ends the export.
+pub fn feed_export_bands(app: &mut PainterApp, ctx: &egui::Context) {
+    let Some(stream) = &mut app.export_bands else {
+        return;
+    };
+    // A document replaced midway leaves the encoder short of rows, which fails the export
+    if stream.size != (app.canvas.width(), app.canvas.height()) {
+        app.export_bands = None;
+        return;
+    }
+    let band = match stream.pending.take() {
+        Some(band) => band,
+        None => {
+            let rows = EXPORT_BAND_ROWS.min(stream.size.1 - stream.next_row);
+            let y = stream.next_row;
+            stream.next_row += rows;
+            app.canvas.with_background_hidden(stream.hide_background, |canvas| composite_band(canvas, y, rows))
+        }
+    };
+    match stream.sender.try_send(band) {
+        Ok(()) if stream.next_row >= stream.size.1 => app.export_bands = None,
+        Ok(()) => {}
+        Err(mpsc::TrySendError::Full(band)) => stream.pending = Some(band),
+        Err(mpsc::TrySendError::Disconnected(_)) => app.export_bands = None,
+    }
+    ctx.request_repaint();
+}
+
+/// While a streamed export is still reading the canvas, cover the whole window with a layer
+/// that swallows pointer and keyboard input, so no panel, menu or canvas tool can edit the document halfway.
+pub fn export_lock_overlay(app: &PainterApp, ctx: &egui::Context) {
+    if app.export_bands.is_none() {
+        return;
+    }
+    let screen = ctx.screen_rect();
+    let area = egui::Area::new(egui::Id::new("export_lock"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(screen.min)
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(screen.size(), egui::Sense::click_and_drag());
+            // Take keyboard focus too, or a text field could still type into a layer name
+            response.request_focus();
+            ui.painter().rect_filled(rect, 0.0, egui::Color32::from_black_alpha(96));
+            let inner = egui::Rect::from_center_size(rect.center(), egui::vec2(240.0, 24.0));
+            ui.put(inner, egui::ProgressBar::new(app.export_progress).text(tr("Exporting...")));
+        });
+    ctx.move_to_top(area.response.layer_id);
+}
+
+/// What the export dialog writes out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportScope {
+    Canvas,
+    /// Only the selected pixels, trimmed to the selection.
+    Selection,
+    /// Every animation frame.
+    Animation,
+}
+
+impl ExportScope {
+    fn title(self) -> &'static str {
+        match self {
+            ExportScope::Canvas => "Export Canvas",
+            ExportScope::Selection => "Export Selection",
+            ExportScope::Animation => "Export Animation",
+        }
+    }
 }
 
 /// Export settings tracked by the app.
@@ -134,6 +348,14 @@ pub struct ExportSettings {
     pub format: ExportFormat,
     pub chosen_path: Option<PathBuf>,
     pub base_name: String,
+    /// Write PNGs as 8-bit indexed images using the document palette.
+    pub indexed: bool,
+    pub scope: ExportScope,
+    pub animation_format: AnimationFormat,
+    /// Repeat GIF and APNG animations forever instead of playing once.
+    pub looping: bool,
+    /// Leave out the background layer so unpainted areas export transparent.
+    pub transparent_background: bool,
 }
 
 impl ExportSettings {
@@ -142,27 +364,64 @@ impl ExportSettings {
             format: ExportFormat::PNG,
             chosen_path: None,
             base_name: "export".to_string(),
+            indexed: false,
+            scope: ExportScope::Canvas,
+            animation_format: AnimationFormat::Gif,
+            looping: true,
+            transparent_background: false,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.scope {
+            ExportScope::Animation => self.animation_format.extension(),
+            _ => self.format.extension(),
+        }
+    }
+
+    /// Name and extensions of the file type being written, for the save dialog's filter.
+    fn filter(&self) -> (&'static str, &'static [&'static str]) {
+        match (self.scope, self.format) {
+            (ExportScope::Animation, _) => match self.animation_format {
+                AnimationFormat::Gif => ("GIF", &["gif"]),
+                AnimationFormat::Apng | AnimationFormat::PngSequence => ("PNG", &["png", "apng"]),
+            },
+            (_, ExportFormat::PNG) => ("PNG", &["png"]),
+            (_, ExportFormat::JPEG) => ("JPEG", &["jpg", "jpeg"]),
+            (_, ExportFormat::TIFF) => ("TIFF", &["tiff", "tif"]),
         }
     }
 
+    /// Ask where to export with the native save dialog, starting in `dir`.
+    fn pick_path(&self, dir: Option<&Path>) -> Option<PathBuf> {
+        let (name, extensions) = self.filter();
+        file_dialog::save_file(dir, &self.default_file_name(), name, extensions)
+    }
+
     pub fn default_file_name(&self) -> String {
-        format!("{}.{}", self.base_name, self.format.extension())
+        let base = match self.scope {
+            ExportScope::Canvas => self.base_name.as_str(),
+            ExportScope::Selection => "selection",
+            ExportScope::Animation => "animation",
+        };
+        format!("{}.{}", base, self.extension())
     }
 
     pub fn output_path(&self) -> PathBuf {
         if let Some(path) = &self.chosen_path {
-            ensure_extension(path.clone(), self.format.extension())
+            ensure_extension(path.clone(), self.filter().1)
         } else {
             Path::new(&self.default_file_name()).to_path_buf()
         }
     }
 }
 
-fn ensure_extension(mut path: PathBuf, ext: &str) -> PathBuf {
+/// Keep any of `extensions` the path already has, else switch it to the first one.
+fn ensure_extension(mut path: PathBuf, extensions: &[&str]) -> PathBuf {
     match path.extension().and_then(|e| e.to_str()) {
-        Some(current) if current.eq_ignore_ascii_case(ext) => path,
+        Some(current) if extensions.iter().any(|ext| current.eq_ignore_ascii_case(ext)) => path,
         _ => {
-            path.set_extension(ext);
+            path.set_extension(extensions[0]);
             path
         }
     }
@@ -0,0 +1,71 @@
+use crate::PainterApp;
+use crate::app::favorites;
+use crate::ui::brush_list::{apply_preset, preset_thumbnail};
+use crate::ui::accessibility;
+use crate::utils::i18n::tr;
+use eframe::egui::{self, Color32};
+
+const SLOT_SIZE: f32 = 24.0;
+
+/// Strip under the top bar with the starred brush presets and pinned colors, one click each.
+pub fn favorites_bar(app: &mut PainterApp, ctx: &egui::Context) {
+    if !app.show_favorites_bar {
+        return;
+    }
+    egui::TopBottomPanel::top("favorites_bar").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("★");
+            let mut any_preset = false;
+            for preset in app.presets.iter().filter(|p| p.favorite) {
+                any_preset = true;
+                let texture = preset_thumbnail(preset, &mut app.preset_previews, &app.pool, ctx);
+                let (rect, response) = ui.allocate_exact_size(egui::vec2(SLOT_SIZE, SLOT_SIZE), egui::Sense::click());
+                ui.painter().rect_filled(rect, 2.0, Color32::from_gray(30));
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                ui.painter().image(texture, rect, uv, Color32::WHITE);
+                accessibility::button(ui, &response, &preset.name);
+                if response.on_hover_text(&preset.name).clicked() {
+                    apply_preset(&mut app.brush, preset);
+                }
+            }
+            if !any_preset {
+                ui.label(egui::RichText::new(tr("Star presets to pin them here")).weak());
+            }
+
+            ui.separator();
+            let mut remove = None;
+            for (idx, &color) in app.favorite_colors.iter().enumerate() {
+                let (rect, response) = ui.allocate_exact_size(egui::vec2(SLOT_SIZE, SLOT_SIZE), egui::Sense::click());
+                let selected = app.brush.brush_options.color == color;
+                ui.painter().rect_filled(rect, 2.0, color);
+                ui.painter().rect_stroke(rect, 2.0, (1.0, if selected { Color32::WHITE } else { Color32::from_gray(60) }));
+                accessibility::selectable(ui, &response, selected, &accessibility::color_name(color));
+                let response = response.on_hover_text(tr("Click to use, right-click to unpin"));
+                if response.clicked() {
+                    app.brush.brush_options.color = color;
+                }
+                if response.secondary_clicked() {
+                    remove = Some(idx);
+                }
+            }
+            let mut changed = false;
+            if let Some(idx) = remove {
+                app.favorite_colors.remove(idx);
+                changed = true;
+            }
+            let current = app.brush.brush_options.color;
+            let response = ui.add_enabled(!app.favorite_colors.contains(&current), egui::Button::new("+"));
+            if accessibility::named(response, tr("Pin the current color"))
+                .on_hover_text(tr("Pin the current color"))
+                .clicked()
+            {
+                app.favorite_colors.push(current);
+                changed = true;
+            }
+            if changed {
+                favorites::save(&app.brushes_path, &app.favorite_colors)
+                    .unwrap_or_else(|e| log::error!("Failed to save favorite colors: {:?}", e));
+            }
+        });
+    });
+}
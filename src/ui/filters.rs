@@ -0,0 +1,396 @@
+use crate::PainterApp;
+use crate::app::render_helper::CanvasView;
+use crate::canvas::canvas::{Canvas, LayerPreview};
+use crate::canvas::adjustment::{Adjustment, AdjustmentKind, GradientMap, GradientStop};
+use crate::canvas::noise::{NoiseDistribution, NoiseSettings};
+use crate::ui::accessibility;
+use crate::utils::i18n::{tr, trf};
+use crate::utils::vector::Vec2;
+use eframe::egui;
+use eframe::egui::{Color32, Rgba};
+use std::collections::HashMap;
+use std::sync::{Arc, mpsc};
+
+/// Pixels of layer tiles by tile coordinate.
+type LayerTiles = HashMap<(i32, i32), Vec<Color32>>;
+
+/// Destructive filters that can be previewed on the active layer before applying.
+#[derive(Clone)]
+pub enum Filter {
+    /// Any adjustment-layer filter baked straight into the pixels.
+    Adjustment(Adjustment),
+    Noise(NoiseSettings),
+}
+
+impl Filter {
+    pub fn gradient_map() -> Self {
+        Filter::Adjustment(Adjustment::new(AdjustmentKind::default_gradient_map()))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Filter::Adjustment(adj) => adj.kind.label(),
+            Filter::Noise(_) => "Add Noise",
+        }
+    }
+
+    /// Compute the filtered value of one premultiplied pixel at canvas position (x, y).
+    fn apply(&self, x: i32, y: i32, color: Color32) -> Color32 {
+        match self {
+            Filter::Adjustment(adj) => Color32::from(adj.apply(Rgba::from(color), 1.0)),
+            Filter::Noise(noise) => noise.apply(x, y, color),
+        }
+    }
+
+    /// Parameter widgets for the filter; returns true when anything changed.
+    fn editor(&mut self, ui: &mut egui::Ui) -> bool {
+        match self {
+            Filter::Adjustment(adj) => crate::ui::layers::adjustment_kind_editor(ui, &mut adj.kind),
+            Filter::Noise(noise) => noise_editor(ui, noise),
+        }
+    }
+}
+
+/// An open filter dialog: the filter being tuned plus the untouched layer pixels it reads from.
+pub struct FilterDialog {
+    pub filter: Filter,
+    pub preview: bool,
+    /// Canvas x of the before/after line when comparing; the filter previews right of it.
+    pub split: Option<f32>,
+    /// Screen rect of the split line's drag handle, so pressing it doesn't paint.
+    split_handle: Option<egui::Rect>,
+    layer_idx: usize,
+    original: Arc<LayerTiles>,
+    /// The latest filtered pixels. The display shows them over the layer, which keeps its
+    /// own pixels until the filter is applied.
+    filtered: Option<Arc<LayerTiles>>,
+    dirty: bool,
+    /// Filtered pixels on their way from the background pool.
+    job: Option<mpsc::Receiver<LayerTiles>>,
+    /// Apply was pressed; the filter is baked in as soon as the final pixels arrive.
+    applying: bool,
+}
+
+/// Open a filter dialog for the active layer, capturing its pixels for preview and undo.
+pub(crate) fn open_filter(app: &mut PainterApp, filter: Filter) {
+    if app.filter_dialog.is_some() {
+        return;
+    }
+    let layer_idx = app.canvas.active_layer_idx;
+    let Some(layer) = app.canvas.layers.get(layer_idx) else {
+        return;
+    };
+    if layer.locked || layer.adjustment.is_some() || layer.vector.is_some() {
+        return;
+    }
+    app.bake_layer_offset(layer_idx);
+    if layer_idx == 0 {
+        // Untouched background tiles are implicit; materialize them so they get filtered too
+        for ty in 0..app.tiles_y {
+            for tx in 0..app.tiles_x {
+                app.canvas.ensure_layer_tile_exists(0, tx, ty);
+            }
+        }
+    }
+    let original = app.canvas.capture_layer_pixels(layer_idx);
+    app.filter_dialog = Some(FilterDialog {
+        filter,
+        preview: true,
+        split: None,
+        split_handle: None,
+        layer_idx,
+        original: Arc::new(original),
+        filtered: None,
+        dirty: true,
+        job: None,
+        applying: false,
+    });
+}
+
+impl FilterDialog {
+    /// What the display shows over the layer: the filtered pixels, right of the split line
+    /// when comparing.
+    pub fn layer_preview(&self) -> Option<LayerPreview> {
+        let tiles = self.filtered.clone().filter(|_| self.preview)?;
+        Some(LayerPreview { layer_idx: self.layer_idx, tiles, from_x: self.split.unwrap_or(f32::NEG_INFINITY) })
+    }
+}
+
+/// Show the layer's own pixels again, or start filtering them on the background pool;
+/// [`poll_filter`] hands the filtered pixels to the display once they are ready.
+fn render_filter(app: &mut PainterApp, dialog: &mut FilterDialog, filtered: bool) {
+    if filtered {
+        let filter = dialog.filter.clone();
+        let original = dialog.original.clone();
+        let selection = app.selection_manager.detached();
+        let tile_size = app.canvas.tile_size();
+        let (done, receiver) = mpsc::channel();
+        // A job for settings changed since is simply dropped: its send fails
+        app.background_pool.spawn(move || {
+            let tiles = Canvas::filter_tiles(&original, tile_size, selection.as_ref(), |x, y, c| filter.apply(x, y, c));
+            let _ = done.send(tiles);
+        });
+        dialog.job = Some(receiver);
+    } else {
+        dialog.job = None;
+        if dialog.filtered.take().is_some() {
+            app.mark_all_tiles_dirty();
+        }
+    }
+    dialog.dirty = false;
+}
+
+/// Hand a finished background filter job to the display.
+fn poll_filter(app: &mut PainterApp, dialog: &mut FilterDialog) {
+    let Some(job) = &dialog.job else {
+        return;
+    };
+    match job.try_recv() {
+        Ok(tiles) => {
+            dialog.filtered = Some(Arc::new(tiles));
+            dialog.job = None;
+            app.mark_all_tiles_dirty();
+        }
+        Err(mpsc::TryRecvError::Empty) => {}
+        Err(mpsc::TryRecvError::Disconnected) => dialog.job = None,
+    }
+}
+
+/// Bake the filtered pixels into the layer and record the original pixels as one undo step.
+fn commit_filter(app: &mut PainterApp, dialog: FilterDialog) {
+    let Some(filtered) = dialog.filtered else {
+        return;
+    };
+    for ((tx, ty), data) in Arc::unwrap_or_clone(filtered) {
+        app.canvas.set_layer_tile_data(dialog.layer_idx, tx, ty, data);
+    }
+    let tile_size = app.canvas.tile_size();
+    let tiles = Arc::unwrap_or_clone(dialog.original)
+        .into_iter()
+        .map(|((tx, ty), data)| crate::canvas::history::TileSnapshot {
+            tx,
+            ty,
+            layer_idx: dialog.layer_idx,
+            x0: 0,
+            y0: 0,
+            width: tile_size,
+            height: tile_size,
+            data,
+        })
+        .collect();
+    let action = crate::canvas::history::UndoAction { tiles, ..crate::canvas::history::UndoAction::new(dialog.filter.label()) };
+    if let Some(hist) = app.histories.get_mut(dialog.layer_idx) {
+        hist.push_action(action);
+    }
+    app.mark_untracked_change("filter");
+}
+
+/// Dialog for the open filter with live preview on the canvas.
+pub fn filter_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    let Some(mut dialog) = app.filter_dialog.take() else {
+        return;
+    };
+
+    let mut open = true;
+    let mut apply = false;
+    let mut cancel = false;
+    egui::Window::new(tr(dialog.filter.label()))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            if dialog.applying {
+                ui.disable();
+            }
+            if dialog.filter.editor(ui) {
+                dialog.dirty = true;
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut dialog.preview, tr("Preview")).changed() {
+                    dialog.dirty = true;
+                }
+                let mut split = dialog.split.is_some();
+                let response = ui.add_enabled(dialog.preview, egui::Checkbox::new(&mut split, tr("Split view")));
+                if response.on_hover_text(tr("Drag the line on the canvas to compare before and after")).changed() {
+                    dialog.split = split.then(|| app.canvas.width() as f32 * 0.5);
+                    app.mark_all_tiles_dirty();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button(tr("Apply")).clicked() {
+                    apply = true;
+                }
+                if ui.button(tr("Cancel")).clicked() {
+                    cancel = true;
+                }
+                if dialog.job.is_some() {
+                    ui.spinner();
+                }
+            });
+        });
+
+    if cancel || (!open && !dialog.applying) {
+        render_filter(app, &mut dialog, false);
+        return;
+    }
+    if apply {
+        dialog.applying = true;
+        if dialog.dirty || (dialog.filtered.is_none() && dialog.job.is_none()) {
+            render_filter(app, &mut dialog, true);
+        }
+    } else if dialog.dirty {
+        let preview = dialog.preview;
+        render_filter(app, &mut dialog, preview);
+    }
+    poll_filter(app, &mut dialog);
+    if dialog.applying && dialog.job.is_none() {
+        commit_filter(app, dialog);
+        return;
+    }
+    if dialog.job.is_some() {
+        ctx.request_repaint();
+    }
+    app.filter_dialog = Some(dialog);
+}
+
+/// Before/after line of a split filter preview, with a handle that drags it across the canvas.
+pub fn split_overlay(ui: &mut egui::Ui, app: &mut PainterApp, view: &CanvasView) {
+    let Some(split) = app.filter_dialog.as_ref().filter(|d| d.preview).and_then(|d| d.split) else {
+        return;
+    };
+    let height = app.canvas.height() as f32;
+    let top = app.canvas_to_screen(Vec2::new(split, 0.0), view.origin, view.canvas_center);
+    let bottom = app.canvas_to_screen(Vec2::new(split, height), view.origin, view.canvas_center);
+    let painter = ui.painter_at(view.response.rect);
+    painter.line_segment([top, bottom], egui::Stroke::new(3.0, Color32::BLACK));
+    painter.line_segment([top, bottom], egui::Stroke::new(1.0, Color32::WHITE));
+
+    let font = egui::FontId::proportional(12.0);
+    for (label, side, align) in [("Before", -1.0, egui::Align2::RIGHT_TOP), ("After", 1.0, egui::Align2::LEFT_TOP)] {
+        let anchor = top + egui::vec2(side * 6.0, 6.0);
+        painter.text(anchor + egui::vec2(1.0, 1.0), align, tr(label), font.clone(), Color32::BLACK);
+        painter.text(anchor, align, tr(label), font.clone(), Color32::WHITE);
+    }
+
+    let center = top.lerp(bottom, 0.5);
+    let handle = egui::Rect::from_center_size(center, egui::vec2(16.0, 28.0));
+    let response = ui.interact(handle, ui.id().with("filter_split"), egui::Sense::drag());
+    let fill = if response.hovered() || response.dragged() { ui.visuals().selection.bg_fill } else { Color32::WHITE };
+    painter.rect(handle.shrink(2.0), 4.0, fill, egui::Stroke::new(1.0, Color32::BLACK));
+    if response.hovered() || response.dragged() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+    }
+    let dragged_to = response.dragged().then(|| response.interact_pointer_pos()).flatten();
+    let x = dragged_to.map(|pos| app.screen_to_canvas_unclamped(pos, view.origin, view.canvas_center).x);
+    let width = app.canvas.width() as f32;
+    let Some(dialog) = app.filter_dialog.as_mut() else {
+        return;
+    };
+    dialog.split_handle = Some(handle);
+    if let Some(x) = x {
+        let x = x.clamp(0.0, width);
+        dialog.split = Some(x);
+        redisplay_columns(app, split, x);
+    }
+}
+
+/// Redisplay the tiles the split line crossed moving from canvas x `from` to `to`.
+fn redisplay_columns(app: &mut PainterApp, from: f32, to: f32) {
+    let tile_size = app.canvas.tile_size() as f32;
+    let first = (from.min(to) / tile_size).floor() as usize;
+    let last = (from.max(to) / tile_size).floor() as usize;
+    for ty in 0..app.tiles_y {
+        for tx in first..=last {
+            if let Some(tile) = app.tile_mut(tx, ty) {
+                tile.dirty = true;
+            }
+        }
+    }
+}
+
+/// Whether `pos` is on the split preview's handle.
+pub fn covers(app: &PainterApp, pos: egui::Pos2) -> bool {
+    app.filter_dialog.as_ref().and_then(|d| d.split.and(d.split_handle)).is_some_and(|rect| rect.contains(pos))
+}
+
+/// Gradient preview strip plus per-stop color and position controls; returns true when changed.
+pub(crate) fn gradient_map_editor(ui: &mut egui::Ui, map: &mut GradientMap) -> bool {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width().min(240.0), 16.0), egui::Sense::hover());
+    let segments = 64;
+    for i in 0..segments {
+        let t0 = i as f32 / segments as f32;
+        let t1 = (i + 1) as f32 / segments as f32;
+        let [r, g, b] = map.sample((t0 + t1) * 0.5);
+        let x0 = egui::lerp(rect.x_range(), t0);
+        let x1 = egui::lerp(rect.x_range(), t1);
+        ui.painter().rect_filled(
+            egui::Rect::from_x_y_ranges(x0..=x1, rect.y_range()),
+            0.0,
+            Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8),
+        );
+    }
+
+    let mut stops = map.stops().to_vec();
+    let mut changed = false;
+    let mut remove = None;
+    let can_remove = stops.len() > 2;
+    for (i, stop) in stops.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= ui.color_edit_button_srgb(&mut stop.color).changed();
+            changed |= ui.add(egui::Slider::new(&mut stop.pos, 0.0..=1.0)).changed();
+            if can_remove && accessibility::named(ui.small_button("🗑"), tr("Remove")).clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        stops.remove(i);
+        changed = true;
+    }
+    if ui.button(tr("+ Stop")).clicked() {
+        // Drop the new stop into the middle of the widest gap
+        let mut positions: Vec<f32> = stops.iter().map(|s| s.pos).collect();
+        positions.sort_by(f32::total_cmp);
+        let (a, b) = positions
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .max_by(|x, y| (x.1 - x.0).total_cmp(&(y.1 - y.0)))
+            .unwrap_or((0.0, 1.0));
+        let pos = (a + b) * 0.5;
+        let [r, g, bl] = map.sample(pos);
+        stops.push(GradientStop {
+            pos,
+            color: [(r * 255.0) as u8, (g * 255.0) as u8, (bl * 255.0) as u8],
+        });
+        changed = true;
+    }
+
+    if changed {
+        map.edit_stops(|s| *s = stops);
+    }
+    changed
+}
+
+/// Distribution, channel mode, intensity, and seed controls for Add Noise.
+fn noise_editor(ui: &mut egui::Ui, noise: &mut NoiseSettings) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        for distribution in [NoiseDistribution::Uniform, NoiseDistribution::Gaussian] {
+            changed |= ui
+                .selectable_value(&mut noise.distribution, distribution, tr(distribution.label()))
+                .changed();
+        }
+    });
+    changed |= ui.checkbox(&mut noise.monochrome, tr("Monochrome")).changed();
+    changed |= ui.add(egui::Slider::new(&mut noise.intensity, 0.0..=1.0).text(tr("Intensity"))).changed();
+    ui.horizontal(|ui| {
+        ui.label(trf("Seed {}", &[&noise.seed]));
+        if ui.button(tr("🎲 Reseed")).clicked() {
+            noise.seed = rand::random();
+            changed = true;
+        }
+    });
+    changed
+}
@@ -1,29 +1,78 @@
 use crate::PainterApp;
+use crate::app::navigation::{NavAction, NavTrigger, NavigationBindings};
+use crate::app::state::ROTATION_SNAP_DEGREES;
+use crate::styling::theme::{ACCENT_PRESETS, SCALE_RANGE, ThemeMode};
+use crate::ui::accessibility;
+use crate::utils::profiler;
+use crate::utils::i18n::{self, tr, trf};
 use eframe::egui;
-use rayon::ThreadPoolBuilder;
+use crate::app::painter::build_pool;
 
 /// Panel with app-wide toggles that affect rendering performance and controls.
 pub fn general_settings_panel(app: &mut PainterApp, ui: &mut egui::Ui) {
-    ui.checkbox(&mut app.use_masked_brush, "Use masked brush (fast)");
-    ui.checkbox(&mut app.disable_lod, "High quality zoom out (slower)");
+    language_combo(app, ui);
+    theme_settings(app, ui);
+    ui.separator();
+    ui.checkbox(&mut app.use_masked_brush, tr("Use masked brush (fast)"));
+    ui.checkbox(&mut app.disable_lod, tr("High quality zoom out (slower)"));
+    ui.checkbox(&mut app.show_tile_overlay, tr("Show tile allocation overlay"))
+        .on_hover_text(tr("Green tiles hold pixels, orange tiles are allocated but empty."));
+    ui.checkbox(&mut app.clamp_strokes_to_canvas, tr("Clamp strokes to canvas edge"))
+        .on_hover_text(tr("When off, strokes continue past the edge and dabs are clipped to the canvas."));
+    ui.checkbox(&mut app.pen_only, tr("Pen only (palm rejection)"))
+        .on_hover_text(tr("While a stylus is near the tablet, mouse and touch presses don't draw. Fingers still pan the view."));
     let threads_changed = ui
-        .add(egui::Slider::new(&mut app.thread_count, 1..=app.max_threads).text("Brush threads"))
+        .add(egui::Slider::new(&mut app.thread_count, 1..=app.max_threads).text(tr("Interactive threads")))
+        .on_hover_text(tr("Threads for strokes, brush previews and redrawing the canvas."))
         .changed();
     if threads_changed {
-        if let Ok(pool) = ThreadPoolBuilder::new()
-            .num_threads(app.thread_count)
-            .build()
-        {
-            app.pool = pool;
+        match build_pool(app.thread_count, "interactive") {
+            Ok(pool) => app.pool = std::sync::Arc::new(pool),
+            Err(e) => log::error!("Failed to resize the interactive thread pool: {}", e),
+        }
+    }
+    let background_changed = ui
+        .add(egui::Slider::new(&mut app.background_thread_count, 1..=app.max_threads).text(tr("Background threads")))
+        .on_hover_text(tr("Threads for filters, stroking selections, the histogram and exports, kept separate so long jobs never slow down strokes."))
+        .changed();
+    if background_changed {
+        match build_pool(app.background_thread_count, "background") {
+            Ok(pool) => app.background_pool = std::sync::Arc::new(pool),
+            Err(e) => log::error!("Failed to resize the background thread pool: {}", e),
         }
     }
+    ui.checkbox(&mut app.tile_paging, tr("Page cold tiles to disk"))
+        .on_hover_text(tr("For documents larger than memory: layer tiles left alone the longest are written to a scratch file once the budget is exceeded, and read back when needed."));
+    if app.tile_paging {
+        ui.add(egui::Slider::new(&mut app.tile_paging_budget_mb, 256..=65_536).logarithmic(true).suffix(" MB").text(tr("Tiles kept in memory")));
+        let paged_mb = crate::canvas::tile_pager::paged_bytes() as f64 / (1 << 20) as f64;
+        ui.label(trf("Paged out: {} MB", &[&format!("{paged_mb:.0}")]));
+    }
+    let hud_toggled = ui
+        .checkbox(&mut app.show_profiler_hud, tr("Show profiler HUD"))
+        .on_hover_text(tr("Frame time and per-stage timings from the built-in profiler, drawn over the canvas."))
+        .changed();
+    if hud_toggled {
+        profiler::set_enabled(app.show_profiler_hud);
+    }
+    ui.separator();
+    ui.label(tr("Viewport:"));
+    filter_combo(ui, "Zoomed in (above 100%)", &mut app.view_settings.zoom_in_filter);
+    filter_combo(ui, "Zoomed out (100% and below)", &mut app.view_settings.zoom_out_filter);
+    ui.checkbox(&mut app.view_settings.snap_rotation, trf("Snap rotation to {}° steps", &[&ROTATION_SNAP_DEGREES]));
+    ui.checkbox(&mut app.view_settings.smooth_rotated_edges, tr("Smooth rotated canvas edges"))
+        .on_hover_text(tr("Feather the canvas border by a pixel while the view is rotated."));
+    ui.add(egui::Slider::new(&mut app.view_settings.keep_canvas_visible, 0.0..=512.0).suffix(" px").text(tr("Keep canvas in view")))
+        .on_hover_text(tr("Panning and zooming always leave this much of the canvas on screen; 0 lets it scroll away. Home recenters it."));
     ui.separator();
-    ui.label("Controls:");
-    ui.label("Left click: Paint");
-    ui.label("C: Clear Canvas");
+    navigation_settings(app, ui);
+    ui.separator();
+    ui.label(tr("Controls:"));
+    ui.label(tr("Left click: Paint"));
+    ui.label(tr("C: Clear Canvas"));
     
     ui.separator();
-    if ui.button("Open Brush Folder").clicked() {
+    if ui.button(tr("Open Brush Folder")).clicked() {
         let _ = app.brushes_path.canonicalize().map(|path| {
             #[cfg(target_os = "linux")]
             let _ = std::process::Command::new("xdg-open").arg(path).spawn();
@@ -33,12 +82,161 @@ pub fn general_settings_panel(app: &mut PainterApp, ui: &mut egui::Ui) {
             let _ = std::process::Command::new("open").arg(path).spawn();
         });
     }
-    if ui.button("Refresh Brushes").clicked() {
+    if ui.button(tr("Import Brush Tips...")).clicked() {
+        let ctx = ui.ctx().clone();
+        app.import_brush_tips(&ctx);
+    }
+    if ui.button(tr("Refresh Brushes")).clicked() {
         let ctx = ui.ctx().clone();
         app.load_brush_tips(ctx);
     }
 }
 
+/// Switches the UI language right away and remembers it for the next launch.
+fn language_combo(app: &PainterApp, ui: &mut egui::Ui) {
+    let languages = i18n::languages();
+    let current = i18n::language();
+    let current_name = languages.iter().find(|(code, _)| *code == current).map_or(current, |(_, name)| name);
+    let mut selected = current;
+    ui.horizontal(|ui| {
+        ui.label(tr("Language"));
+        egui::ComboBox::from_id_salt("ui_language")
+            .selected_text(current_name)
+            .show_ui(ui, |ui| {
+                for &(code, native) in &languages {
+                    ui.selectable_value(&mut selected, code, native);
+                }
+            });
+    });
+    if selected != current {
+        i18n::set_language(selected);
+        i18n::save(&app.brushes_path).unwrap_or_else(|e| log::error!("Failed to save the language: {:?}", e));
+    }
+}
+
+/// Theme, accent, UI scale and canvas backdrop, applied as soon as they change.
+fn theme_settings(app: &mut PainterApp, ui: &mut egui::Ui) {
+    let theme = &mut app.theme;
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(tr("Theme"));
+        egui::ComboBox::from_id_salt("ui_theme")
+            .selected_text(tr(theme.mode.label()))
+            .show_ui(ui, |ui| {
+                for mode in ThemeMode::ALL {
+                    changed |= ui.selectable_value(&mut theme.mode, mode, tr(mode.label())).changed();
+                }
+            });
+    });
+    if theme.mode == ThemeMode::Custom {
+        ui.horizontal(|ui| {
+            ui.label(tr("Panels"));
+            changed |= ui.color_edit_button_srgba(&mut theme.custom_panel).changed();
+            ui.label(tr("Text"));
+            changed |= ui.color_edit_button_srgba(&mut theme.custom_text).changed();
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label(tr("Accent"));
+        changed |= ui.color_edit_button_srgba(&mut theme.accent).changed();
+        for accent in ACCENT_PRESETS {
+            let (rect, response) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::click());
+            ui.painter().rect_filled(rect, 3.0, accent);
+            if theme.accent == accent {
+                ui.painter().rect_stroke(rect.expand(1.0), 3.0, ui.visuals().widgets.hovered.fg_stroke);
+            }
+            accessibility::selectable(ui, &response, theme.accent == accent, &accessibility::color_name(accent));
+            if response.clicked() {
+                theme.accent = accent;
+                changed = true;
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut custom_backdrop = theme.backdrop.is_some();
+        if ui.checkbox(&mut custom_backdrop, tr("Canvas backdrop")).changed() {
+            theme.backdrop = custom_backdrop.then(|| theme.backdrop());
+            changed = true;
+        }
+        if let Some(backdrop) = &mut theme.backdrop {
+            changed |= ui.color_edit_button_srgba(backdrop).changed();
+        }
+    });
+    // Rescaling while dragging would move the slider out from under the pointer
+    let response = ui.add(egui::Slider::new(&mut theme.ui_scale, SCALE_RANGE).step_by(0.05).text(tr("UI scale")));
+    changed |= response.drag_stopped() || (response.changed() && !response.dragged());
+
+    if changed {
+        crate::styling::apply_global_style(ui.ctx(), theme);
+        theme.save(&app.brushes_path).unwrap_or_else(|e| log::error!("Failed to save the theme: {:?}", e));
+    }
+}
+
+/// Pan, rotate and zoom drag bindings, saved as soon as they change.
+fn navigation_settings(app: &mut PainterApp, ui: &mut egui::Ui) {
+    ui.label(tr("Navigation:"));
+    let navigation = &mut app.navigation;
+    let mut changed = false;
+    let mut remove = None;
+    egui::Grid::new("navigation_bindings").num_columns(3).show(ui, |ui| {
+        for (idx, (trigger, action)) in navigation.bindings.iter_mut().enumerate() {
+            egui::ComboBox::from_id_salt(("nav_action", idx))
+                .selected_text(tr(action.label()))
+                .show_ui(ui, |ui| {
+                    for choice in NavAction::ALL {
+                        changed |= ui.selectable_value(action, choice, tr(choice.label())).changed();
+                    }
+                });
+            egui::ComboBox::from_id_salt(("nav_trigger", idx))
+                .selected_text(trigger.label())
+                .width(160.0)
+                .show_ui(ui, |ui| {
+                    for choice in NavTrigger::CHOICES {
+                        changed |= ui.selectable_value(trigger, choice, choice.label()).changed();
+                    }
+                });
+            if accessibility::named(ui.small_button("🗑"), tr("Remove")).clicked() {
+                remove = Some(idx);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(idx) = remove {
+        navigation.bindings.remove(idx);
+        changed = true;
+    }
+    ui.horizontal(|ui| {
+        if ui.button(tr("Add Binding")).clicked() {
+            navigation.bindings.push((NavTrigger::CHOICES[0], NavAction::Pan));
+            changed = true;
+        }
+        if ui.button(tr("Reset")).clicked() {
+            *navigation = NavigationBindings::default();
+            changed = true;
+        }
+    });
+    if changed {
+        navigation.save(&app.brushes_path).unwrap_or_else(|e| log::error!("Failed to save navigation bindings: {:?}", e));
+    }
+}
+
+fn filter_combo(ui: &mut egui::Ui, label: &'static str, filter: &mut egui::TextureFilter) {
+    let name = |f: egui::TextureFilter| match f {
+        egui::TextureFilter::Nearest => tr("Nearest (sharp pixels)"),
+        egui::TextureFilter::Linear => tr("Linear (smooth)"),
+    };
+    ui.horizontal(|ui| {
+        ui.label(tr(label));
+        egui::ComboBox::from_id_salt(label)
+            .selected_text(name(*filter))
+            .show_ui(ui, |ui| {
+                for f in [egui::TextureFilter::Nearest, egui::TextureFilter::Linear] {
+                    ui.selectable_value(filter, f, name(f));
+                }
+            });
+    });
+}
+
 /// Modal window that captures focus for general settings.
 pub fn general_settings_modal(app: &mut PainterApp, ctx: &egui::Context) {
     if !app.show_general_settings {
@@ -46,7 +244,7 @@ pub fn general_settings_modal(app: &mut PainterApp, ctx: &egui::Context) {
     }
 
     let mut open = app.show_general_settings;
-    egui::Window::new("General Settings")
+    egui::Window::new(tr("General Settings"))
         .open(&mut open)
         .collapsible(false)
         .resizable(false)
@@ -0,0 +1,325 @@
+use crate::PainterApp;
+use crate::utils::vector::Vec2;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui::{self, Color32, ColorImage, Rgba};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::sync::mpsc;
+
+const BINS: usize = 64;
+/// Canvas tiles are sampled every `TILE_STEP` pixels, so one tile holds at most 256 samples.
+const TILE_STEP: usize = 4;
+/// Upper bound on the pixels sampled from a selection.
+const SELECTION_SAMPLES: usize = 256 * 1024;
+/// Minimum seconds between recomputations while painting.
+const REFRESH_INTERVAL: f64 = 0.25;
+
+const RED: usize = 0;
+const GREEN: usize = 1;
+const BLUE: usize = 2;
+const LUMA: usize = 3;
+
+type TileBins = [[u16; BINS]; 4];
+/// Counts of display tiles by index, as a sampling job sends them back.
+type SampledTiles = Vec<(usize, Box<TileBins>)>;
+
+/// Counts per channel (red, green, blue, luminance) of the opaque-weighted samples.
+#[derive(Clone)]
+struct Bins {
+    counts: [[u32; BINS]; 4],
+    samples: u32,
+}
+
+impl Bins {
+    fn new() -> Self {
+        Self { counts: [[0; BINS]; 4], samples: 0 }
+    }
+
+    fn add_pixel(&mut self, px: Color32) {
+        if px.a() == 0 {
+            return;
+        }
+        let [r, g, b, _] = px.to_srgba_unmultiplied();
+        let luma = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+        for (channel, value) in [(RED, r), (GREEN, g), (BLUE, b), (LUMA, luma.round() as u8)] {
+            self.counts[channel][value as usize * BINS / 256] += 1;
+        }
+        self.samples += 1;
+    }
+
+    fn add_tile(&mut self, tile: &TileBins) {
+        for (total, counts) in self.counts.iter_mut().zip(tile) {
+            for (t, &c) in total.iter_mut().zip(counts) {
+                *t += c as u32;
+            }
+        }
+        self.samples += tile[LUMA].iter().map(|&c| c as u32).sum::<u32>();
+    }
+
+    /// Mean of a channel in 0..=255, using the bin centers.
+    fn mean(&self, channel: usize) -> f32 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        let sum: f32 = self.counts[channel]
+            .iter()
+            .enumerate()
+            .map(|(bin, &c)| bin_center(bin) * c as f32)
+            .sum();
+        sum / self.samples as f32
+    }
+
+    fn median(&self, channel: usize) -> f32 {
+        let half = self.samples.div_ceil(2);
+        let mut seen = 0;
+        for (bin, &c) in self.counts[channel].iter().enumerate() {
+            seen += c;
+            if seen >= half && seen > 0 {
+                return bin_center(bin);
+            }
+        }
+        0.0
+    }
+}
+
+fn bin_center(bin: usize) -> f32 {
+    (bin as f32 + 0.5) * 256.0 / BINS as f32
+}
+
+/// Cached histogram of the displayed composite. Per-tile counts are kept so that a
+/// stroke only resamples the tiles it touched.
+pub struct HistogramState {
+    tiles: Vec<Option<Box<TileBins>>>,
+    totals: Bins,
+    stale: bool,
+    last_refresh: f64,
+    /// Selection bounds the totals were computed for, `None` for the whole canvas.
+    selection_bounds: Option<(Vec2, Vec2)>,
+    /// Tile counts being sampled on the background pool.
+    job: Option<mpsc::Receiver<SampledTiles>>,
+    /// Tiles that changed while `job` was sampling them; its counts for them are dropped.
+    changed_during_job: Vec<usize>,
+}
+
+impl Default for HistogramState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistogramState {
+    pub fn new() -> Self {
+        Self {
+            tiles: Vec::new(),
+            totals: Bins::new(),
+            stale: true,
+            last_refresh: f64::NEG_INFINITY,
+            selection_bounds: None,
+            job: None,
+            changed_during_job: Vec::new(),
+        }
+    }
+
+    /// Forget the counts of a display tile whose pixels changed.
+    pub fn invalidate_tile(&mut self, idx: usize) {
+        if let Some(tile) = self.tiles.get_mut(idx) {
+            *tile = None;
+        }
+        if self.job.is_some() {
+            self.changed_during_job.push(idx);
+        }
+        self.stale = true;
+    }
+
+    /// Take in the counts of a finished sampling job. Returns whether one is still running.
+    fn poll(&mut self) -> bool {
+        let Some(job) = &self.job else {
+            return false;
+        };
+        let sampled = match job.try_recv() {
+            Ok(sampled) => sampled,
+            Err(mpsc::TryRecvError::Empty) => return true,
+            Err(mpsc::TryRecvError::Disconnected) => Vec::new(),
+        };
+        self.job = None;
+        let changed = std::mem::take(&mut self.changed_during_job);
+        for (idx, counts) in sampled {
+            if !changed.contains(&idx) && idx < self.tiles.len() {
+                self.tiles[idx] = Some(counts);
+            }
+        }
+        self.totals = Bins::new();
+        for tile in self.tiles.iter().flatten() {
+            self.totals.add_tile(tile);
+        }
+        false
+    }
+
+    fn refresh(&mut self, app: &PainterApp) {
+        let selection_bounds = app.selection_manager.bounds(0.0);
+        if selection_bounds.is_some() {
+            self.totals = selection_bins(app).unwrap_or_else(Bins::new);
+        } else {
+            self.refresh_tiles(app);
+        }
+        self.selection_bounds = selection_bounds;
+        self.stale = false;
+    }
+
+    /// Start sampling the tiles without counts on the background pool; [`Self::poll`] adds
+    /// them to the totals once they are in.
+    fn refresh_tiles(&mut self, app: &PainterApp) {
+        if self.tiles.len() != app.tiles.len() {
+            self.tiles = vec![None; app.tiles.len()];
+        }
+        let missing: Vec<(usize, usize, usize)> = (0..self.tiles.len())
+            .filter(|&i| self.tiles[i].is_none())
+            .map(|i| (i, app.tiles[i].tx, app.tiles[i].ty))
+            .collect();
+        let canvas = app.canvas.shared_view();
+        // Counts follow what is displayed, including a filter being previewed
+        let preview = app.filter_dialog.as_ref().and_then(|dialog| dialog.layer_preview());
+        let (done, receiver) = mpsc::channel();
+        app.background_pool.spawn(move || {
+            let sampled: SampledTiles = missing
+                .into_par_iter()
+                .map(|(idx, tx, ty)| {
+                    let mut img = ColorImage::new([0, 0], Color32::TRANSPARENT);
+                    canvas.write_tile_with_preview(tx, ty, &mut img, TILE_STEP, preview.as_ref());
+                    let mut bins = Bins::new();
+                    for &px in &img.pixels {
+                        bins.add_pixel(px);
+                    }
+                    let mut counts = Box::new([[0u16; BINS]; 4]);
+                    for (dst, src) in counts.iter_mut().zip(&bins.counts) {
+                        for (d, &s) in dst.iter_mut().zip(src) {
+                            *d = s as u16;
+                        }
+                    }
+                    (idx, counts)
+                })
+                .collect();
+            let _ = done.send(sampled);
+        });
+        self.job = Some(receiver);
+    }
+}
+
+/// Sample the composite inside the selection, skipping pixels less than half covered.
+fn selection_bins(app: &PainterApp) -> Option<Bins> {
+    let canvas = &app.canvas;
+    let selection = &app.selection_manager;
+    let (min, max) = selection.bounds(0.0)?;
+    let x0 = min.x.floor().max(0.0) as usize;
+    let y0 = min.y.floor().max(0.0) as usize;
+    let x1 = (max.x.ceil().max(0.0) as usize).min(canvas.width());
+    let y1 = (max.y.ceil().max(0.0) as usize).min(canvas.height());
+    if x0 >= x1 || y0 >= y1 {
+        return None;
+    }
+    let (w, h) = (x1 - x0, y1 - y0);
+    let step = ((w * h) as f32 / SELECTION_SAMPLES as f32).sqrt().ceil().max(1.0) as usize;
+    let mut img = ColorImage::new([0, 0], Color32::TRANSPARENT);
+    canvas.write_region_to_color_image(x0, y0, w, h, &mut img, step);
+    let out_w = img.size[0];
+    let mut bins = Bins::new();
+    for (i, &px) in img.pixels.iter().enumerate() {
+        let p = Vec2::new((x0 + (i % out_w) * step) as f32 + 0.5, (y0 + (i / out_w) * step) as f32 + 0.5);
+        if selection.coverage(p) >= 0.5 {
+            bins.add_pixel(px);
+        }
+    }
+    Some(bins)
+}
+
+/// Dock tab with RGB and luminance histograms of the canvas, or of the selection when there is one.
+pub fn histogram_panel(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let now = ui.input(|i| i.time);
+    let mut state = std::mem::take(&mut app.histogram);
+    let sampling = state.poll();
+    if sampling {
+        ui.ctx().request_repaint();
+    }
+    let selection_changed = state.selection_bounds != app.selection_manager.bounds(0.0);
+    if (state.stale || selection_changed) && !sampling {
+        if now - state.last_refresh >= REFRESH_INTERVAL {
+            state.refresh(app);
+            state.last_refresh = now;
+        } else {
+            ui.ctx().request_repaint_after(std::time::Duration::from_secs_f64(REFRESH_INTERVAL));
+        }
+    }
+
+    let source = tr(if state.selection_bounds.is_some() { "Selection" } else { "Canvas" });
+    ui.label(trf("{} ({} samples)", &[&source, &state.totals.samples]));
+
+    let show_rgb_id = ui.id().with("histogram_show_rgb");
+    let mut show_rgb = ui.data(|d| d.get_temp::<bool>(show_rgb_id)).unwrap_or(true);
+    ui.checkbox(&mut show_rgb, tr("Show RGB channels"));
+    ui.data_mut(|d| d.insert_temp(show_rgb_id, show_rgb));
+
+    let width = ui.available_width().max(BINS as f32);
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, 100.0), egui::Sense::hover());
+    draw_histogram(ui.painter(), rect, &state.totals, show_rgb);
+
+    ui.separator();
+    let totals = &state.totals;
+    egui::Grid::new("histogram_stats").num_columns(3).show(ui, |ui| {
+        ui.label("");
+        ui.label(tr("Mean"));
+        ui.label(tr("Median"));
+        ui.end_row();
+        for (name, channel) in [("Red", RED), ("Green", GREEN), ("Blue", BLUE), ("Luminance", LUMA)] {
+            ui.label(tr(name));
+            ui.label(format!("{:.0}", totals.mean(channel)));
+            ui.label(format!("{:.0}", totals.median(channel)));
+            ui.end_row();
+        }
+    });
+    if totals.samples > 0 {
+        let mean = Color32::from_rgb(
+            totals.mean(RED).round() as u8,
+            totals.mean(GREEN).round() as u8,
+            totals.mean(BLUE).round() as u8,
+        );
+        ui.horizontal(|ui| {
+            ui.label(tr("Average color"));
+            let (swatch, _) = ui.allocate_exact_size(egui::vec2(32.0, 16.0), egui::Sense::hover());
+            ui.painter().rect_filled(swatch, 2.0, mean);
+        });
+    }
+    app.histogram = state;
+}
+
+/// Luminance as a filled area with the RGB channels drawn as lines on top, scaled to the tallest bin.
+fn draw_histogram(painter: &egui::Painter, rect: egui::Rect, bins: &Bins, show_rgb: bool) {
+    painter.rect_filled(rect, 2.0, Color32::from_gray(24));
+    let channels: &[usize] = if show_rgb { &[LUMA, RED, GREEN, BLUE] } else { &[LUMA] };
+    let peak = channels
+        .iter()
+        .flat_map(|&c| bins.counts[c].iter())
+        .copied()
+        .max()
+        .unwrap_or(0);
+    if peak == 0 {
+        return;
+    }
+    let bin_w = rect.width() / BINS as f32;
+    let height = |count: u32| rect.height() * count as f32 / peak as f32;
+
+    for (bin, &count) in bins.counts[LUMA].iter().enumerate() {
+        let x = rect.left() + bin as f32 * bin_w;
+        let bar = egui::Rect::from_min_max(egui::pos2(x, rect.bottom() - height(count)), egui::pos2(x + bin_w, rect.bottom()));
+        painter.rect_filled(bar, 0.0, Color32::from_gray(150));
+    }
+    if !show_rgb {
+        return;
+    }
+    for (channel, color) in [(RED, Color32::RED), (GREEN, Color32::GREEN), (BLUE, Rgba::from_rgb(0.2, 0.4, 1.0).into())] {
+        let points = bins.counts[channel]
+            .iter()
+            .enumerate()
+            .map(|(bin, &count)| egui::pos2(rect.left() + (bin as f32 + 0.5) * bin_w, rect.bottom() - height(count)))
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color.gamma_multiply(0.8))));
+    }
+}
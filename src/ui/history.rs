@@ -0,0 +1,98 @@
+use crate::PainterApp;
+use crate::ui::accessibility;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+/// Dock tab listing the active layer's undo history and stored document snapshots.
+pub fn history_panel(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let mut undo_steps = 0;
+    let mut redo_steps = 0;
+    let mut restore = None;
+    let mut delete = None;
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            ui.heading(tr("Snapshots"));
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut app.new_snapshot_name)
+                        .desired_width(140.0)
+                        .hint_text(tr("Snapshot name")),
+                );
+                if ui.button(tr("📷 Take")).clicked() {
+                    let name = if app.new_snapshot_name.trim().is_empty() {
+                        trf("Snapshot {}", &[&(app.snapshots.len() + 1)])
+                    } else {
+                        app.new_snapshot_name.trim().to_string()
+                    };
+                    app.take_snapshot(name);
+                    app.new_snapshot_name.clear();
+                }
+            });
+            for (idx, snapshot) in app.snapshots.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(&snapshot.name)
+                        .on_hover_text(tr("Restore this snapshot"))
+                        .clicked()
+                    {
+                        restore = Some(idx);
+                    }
+                    if accessibility::named(ui.small_button("🗑"), tr("Delete")).clicked() {
+                        delete = Some(idx);
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.heading(tr("Actions"));
+            let Some(history) = app.histories.get(app.canvas.active_layer_idx) else {
+                return;
+            };
+            // Document steps like trims are undone from any layer, in order with its own
+            let document = &app.document_history;
+            let mut undo_labels: Vec<_> = history.undo_labels().chain(document.undo_labels()).collect();
+            undo_labels.sort_by_key(|&(_, order)| order);
+            let mut redo_labels: Vec<_> = history.redo_labels().chain(document.redo_labels()).collect();
+            redo_labels.sort_by_key(|&(_, order)| order);
+
+            let undo_len = undo_labels.len();
+            if ui
+                .selectable_label(undo_len == 0, egui::RichText::new(tr("Start")).weak())
+                .clicked()
+            {
+                undo_steps = undo_len;
+            }
+            for (idx, (label, _)) in undo_labels.into_iter().enumerate() {
+                let is_current = idx + 1 == undo_len;
+                if ui.selectable_label(is_current, tr(label)).clicked() {
+                    undo_steps = undo_len - (idx + 1);
+                }
+            }
+            for (idx, (label, _)) in redo_labels.into_iter().enumerate() {
+                if ui
+                    .selectable_label(false, egui::RichText::new(tr(label)).weak().italics())
+                    .clicked()
+                {
+                    redo_steps = idx + 1;
+                }
+            }
+        });
+
+    for _ in 0..undo_steps {
+        app.undo();
+    }
+    for _ in 0..redo_steps {
+        app.redo();
+    }
+    if let Some(idx) = restore {
+        app.restore_snapshot(idx);
+    }
+    if let Some(idx) = delete {
+        app.snapshots.remove(idx);
+    }
+    if undo_steps > 0 || redo_steps > 0 || restore.is_some() {
+        ui.ctx().request_repaint();
+    }
+}
@@ -1,41 +1,122 @@
 use crate::PainterApp;
+use crate::canvas::adjustment::{Adjustment, AdjustmentKind};
+use crate::canvas::canvas::LayerLabel;
+use crate::ui::accessibility;
+use crate::utils::i18n::tr;
 use eframe::egui;
 
 /// Sidebar that manages the canvas layer stack.
 pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp) {
     let mut add_layer = false;
+    let mut add_vector_layer = false;
+    let mut add_adjustment: Option<AdjustmentKind> = None;
     let mut to_delete = None;
     let mut active_idx = app.canvas.active_layer_idx;
+    let mut clicked = None;
+    // Row to move keyboard focus to after an arrow key changed the active layer
+    let mut focus_row = None;
+    let mut merge_selected = false;
+    let mut delete_selected = false;
     let mut needs_refresh = false;
+    // Layer properties changed here rather than through the painter's layer operations
+    let mut edited = false;
+    let mut label_pick: Option<(usize, LayerLabel)> = None;
+    let selection = app.layer_selection();
     let mut item_rects: Vec<(usize, egui::Rect)> = Vec::new();
 
     egui::ScrollArea::vertical()
         .auto_shrink([false; 2])
         .show(ui, |ui| {
             ui.horizontal(|ui| {
-                if ui.button("New Layer").clicked() {
+                if ui.button(tr("New Layer")).clicked() {
                     add_layer = true;
                 }
+                if ui.button(tr("New Vector Layer")).clicked() {
+                    add_vector_layer = true;
+                }
+                ui.menu_button(tr("New Adjustment"), |ui| {
+                    for kind in [
+                        AdjustmentKind::default_brightness_contrast(),
+                        AdjustmentKind::default_hue_saturation(),
+                        AdjustmentKind::default_levels(),
+                        AdjustmentKind::default_gradient_map(),
+                    ] {
+                        if ui.button(tr(kind.label())).clicked() {
+                            add_adjustment = Some(kind);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+            if selection.len() > 1 {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} layers selected", selection.len()));
+                    if ui.button(tr("Merge")).clicked() {
+                        merge_selected = true;
+                    }
+                    if ui.button(tr("Delete")).clicked() {
+                        delete_selected = true;
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label(tr("Show"));
+                egui::ComboBox::from_id_salt("layer_label_filter")
+                    .selected_text(match app.layer_label_filter {
+                        Some(label) => label_text(label),
+                        None => egui::RichText::new(tr("All layers")),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut app.layer_label_filter, None, tr("All layers"));
+                        for label in LayerLabel::ALL {
+                            ui.selectable_value(&mut app.layer_label_filter, Some(label), label_text(label));
+                        }
+                    });
             });
             ui.separator();
 
-            // Iterate in reverse so top layers are at the top of the list
-            for i in (0..app.canvas.layers.len()).rev() {
+            // Top layers first, leaving out layers the label filter hides
+            let len = app.canvas.layers.len();
+            let filter = app.layer_label_filter;
+            let shown: Vec<usize> = (0..len)
+                .rev()
+                .filter(|&i| filter.is_none_or(|label| app.canvas.layers[i].label == label))
+                .collect();
+            if shown.is_empty() {
+                ui.label(egui::RichText::new(tr("No layers carry this label.")).weak());
+            }
+            for (row, &i) in shown.iter().enumerate() {
                 let mut vis_changed = false;
                 let mut opacity_released = false;
+                let mut opacity_changed = false;
                 let mut delete_clicked = false;
                 ui.horizontal(|ui| {
                     let layer = &mut app.canvas.layers[i];
-                    if ui.checkbox(&mut layer.visible, "").changed() {
+                    let response = ui.checkbox(&mut layer.visible, "");
+                    if accessibility::named_checkbox(response, layer.visible, tr("Visible")).changed() {
                         vis_changed = true;
                     }
-                    ui.checkbox(&mut layer.locked, "🔒");
+                    let response = ui.checkbox(&mut layer.locked, "🔒");
+                    edited |= accessibility::named_checkbox(response, layer.locked, tr("Lock layer")).changed();
+                    let response = ui.checkbox(&mut layer.paint_behind, "⬇");
+                    edited |= accessibility::named_checkbox(response, layer.paint_behind, tr("Paint behind"))
+                        .on_hover_text(tr("Paint behind: strokes only fill transparent pixels"))
+                        .changed();
 
                     let is_active = i == active_idx;
+                    let is_selected = selection.contains(&i);
                     let desired = egui::vec2(ui.available_width() - 40.0, 60.0);
-                    let (rect, block_response) =
-                        ui.allocate_exact_size(desired, egui::Sense::click_and_drag());
+                    let (rect, _) = ui.allocate_exact_size(desired, egui::Sense::hover());
+                    // Stable id so focus can be handed back to the row by index
+                    let block_response = ui.interact(rect, row_id(i), egui::Sense::click_and_drag());
                     item_rects.push((i, rect));
+                    accessibility::selectable(ui, &block_response, is_active, &layer.name);
+                    // Up/Down on a focused row walk the listed layers, drawn top layer first
+                    match accessibility::arrow_steps(ui, &block_response, true) {
+                        -1 if row > 0 => focus_row = Some(shown[row - 1]),
+                        1 if row + 1 < shown.len() => focus_row = Some(shown[row + 1]),
+                        _ => {}
+                    }
 
                     let fill = app
                         .layer_ui_colors
@@ -43,9 +124,16 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                         .copied()
                         .unwrap_or(ui.visuals().extreme_bg_color);
                     ui.painter().rect_filled(rect.shrink(2.0), 6.0, fill);
+                    if let Some(color) = layer.label.color() {
+                        let strip = egui::Rect::from_min_size(rect.min + egui::vec2(2.0, 2.0), egui::vec2(5.0, rect.height() - 4.0));
+                        ui.painter().rect_filled(strip, 2.0, color);
+                    }
                     if is_active {
                         let stroke = egui::Stroke::new(2.0, ui.visuals().selection.bg_fill);
                         ui.painter().rect_stroke(rect.shrink(1.0), 8.0, stroke);
+                    } else if is_selected {
+                        let stroke = egui::Stroke::new(1.0, ui.visuals().selection.bg_fill);
+                        ui.painter().rect_stroke(rect.shrink(1.0), 8.0, stroke);
                     }
 
                     #[allow(deprecated)]
@@ -59,19 +147,32 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                     if is_active {
                         let resp = content.add(
                             egui::TextEdit::singleline(&mut layer.name)
+                                .id(name_id(i))
                                 .desired_width(field_width - 140.0)
-                                .hint_text("Layer name"),
+                                .hint_text(tr("Layer name")),
                         );
                         if resp.clicked() {
-                            active_idx = i;
+                            clicked = Some(i);
+                        }
+                        edited |= resp.changed();
+                        // Enter finishes the rename and returns to the row
+                        if resp.lost_focus() && content.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            content.memory_mut(|m| m.request_focus(row_id(i)));
                         }
                     } else {
+                        let label = if layer.adjustment.is_some() {
+                            format!("◐ {}", layer.name)
+                        } else if layer.vector.is_some() {
+                            format!("✒ {}", layer.name)
+                        } else {
+                            layer.name.clone()
+                        };
                         let resp = content.add_sized(
                             egui::vec2(field_width - 140.0, 24.0),
-                            egui::Label::new(layer.name.clone()),
+                            egui::Label::new(label),
                         );
                         if resp.clicked() {
-                            active_idx = i;
+                            clicked = Some(i);
                         }
                     }
 
@@ -79,10 +180,12 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                         .add(egui::Slider::new(&mut layer.opacity, 0.0..=1.0).show_value(false));
                     opacity_released =
                         response.drag_stopped() || (response.changed() && !response.dragged());
+                    opacity_changed = response.changed();
+                    edited |= opacity_changed || vis_changed;
 
                     if let Some(color) = app.layer_ui_colors.get_mut(i) {
                         if content.color_edit_button_srgba(color).clicked() {
-                            active_idx = i;
+                            clicked = Some(i);
                         }
                     }
 
@@ -90,13 +193,17 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                         content.add_space(30.0);
                         let response =
                             content.add_sized(egui::vec2(20.0, 24.0), egui::Button::new("🗑"));
-                        if response.clicked() {
+                        if accessibility::named(response, tr("Delete layer")).clicked() {
                             delete_clicked = true;
                         }
                     }
 
                     if block_response.clicked() {
-                        active_idx = i;
+                        clicked = Some(i);
+                        // Enter on a focused row starts renaming it
+                        if block_response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            ui.memory_mut(|m| m.request_focus(name_id(i)));
+                        }
                     }
 
                     if block_response.drag_started() {
@@ -116,7 +223,11 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                                         target = *idx;
                                     }
                                 }
-                                app.reorder_layers(from, target);
+                                if selection.len() > 1 && selection.contains(&from) {
+                                    app.move_layer_block(&selection, target);
+                                } else {
+                                    app.reorder_layers(from..from + 1, target);
+                                }
                                 needs_refresh = true;
                                 active_idx = app.canvas.active_layer_idx;
                             }
@@ -124,26 +235,62 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                     }
 
                     block_response.context_menu(|ui| {
+                        ui.menu_button(tr("Color label"), |ui| {
+                            for label in LayerLabel::ALL {
+                                if ui.selectable_label(app.canvas.layers[i].label == label, label_text(label)).clicked() {
+                                    label_pick = Some((i, label));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
                         if let Some(color) = app.layer_ui_colors.get_mut(i) {
-                            ui.menu_button("Layer color", |ui| {
+                            ui.menu_button(tr("Layer color"), |ui| {
                                 ui.color_edit_button_srgba(color);
                             });
                         }
                     });
                 });
 
-                if vis_changed {
+                // Edits on a selected layer carry over to the rest of the selection
+                let bulk_edit = selection.len() > 1 && selection.contains(&i);
+                if bulk_edit && (vis_changed || opacity_changed) {
+                    let (visible, opacity) = (app.canvas.layers[i].visible, app.canvas.layers[i].opacity);
+                    for &idx in &selection {
+                        let layer = &mut app.canvas.layers[idx];
+                        layer.visible = visible;
+                        layer.opacity = opacity;
+                    }
+                }
+                if bulk_edit && (vis_changed || opacity_released) {
+                    needs_refresh = true;
+                } else if vis_changed {
                     needs_refresh = true;
-                    app.mark_layer_tiles_with_data_dirty(i);
+                    if app.canvas.layers[i].adjustment.is_some() {
+                        app.mark_all_tiles_dirty();
+                    } else {
+                        app.canvas.layer_changed(i);
+                    }
                 }
-                if opacity_released {
+                if opacity_released && !bulk_edit {
                     needs_refresh = true;
-                    app.mark_layer_tiles_with_data_dirty(i);
+                    if app.canvas.layers[i].adjustment.is_some() {
+                        app.mark_all_tiles_dirty();
+                    } else {
+                        app.canvas.layer_changed(i);
+                    }
                 }
                 if delete_clicked {
                     to_delete = Some(i);
                 }
             }
+            // Labelling a selected layer labels the whole selection
+            if let Some((i, label)) = label_pick {
+                let targets = if selection.contains(&i) { selection.clone() } else { [i].into() };
+                for idx in targets {
+                    app.canvas.layers[idx].label = label;
+                }
+                edited = true;
+            }
 
             if let Some(drag_idx) = app.layer_dragging {
                 if let Some(pointer) = ctx.input(|i| i.pointer.hover_pos()) {
@@ -169,12 +316,15 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
                             6.0,
                             egui::Stroke::new(2.0, ui.visuals().selection.bg_fill),
                         );
-                        let name = app
-                            .canvas
-                            .layers
-                            .get(drag_idx)
-                            .map(|l| l.name.as_str())
-                            .unwrap_or("Layer");
+                        let name = if selection.len() > 1 && selection.contains(&drag_idx) {
+                            format!("{} layers", selection.len())
+                        } else {
+                            app.canvas
+                                .layers
+                                .get(drag_idx)
+                                .map(|l| l.name.clone())
+                                .unwrap_or_else(|| tr("Layer").to_string())
+                        };
                         ui.painter().text(
                             ghost_rect.left_top() + egui::vec2(12.0, 18.0),
                             egui::Align2::LEFT_TOP,
@@ -187,41 +337,155 @@ pub fn layers_panel(ctx: &egui::Context, ui: &mut egui::Ui, app: &mut PainterApp
             }
         });
 
-    if add_layer {
-        app.canvas.add_layer();
+    if let Some(i) = clicked {
+        let modifiers = ctx.input(|i| i.modifiers);
+        active_idx = select_layer(app, active_idx, i, modifiers);
+    }
+    if let Some(i) = focus_row {
+        let modifiers = ctx.input(|i| i.modifiers);
+        active_idx = select_layer(app, active_idx, i, modifiers);
+        ctx.memory_mut(|m| m.request_focus(row_id(i)));
+    }
+
+    if let Some(adj) = app
+        .canvas
+        .layers
+        .get_mut(active_idx)
+        .and_then(|l| l.adjustment.as_mut())
+    {
+        ui.separator();
+        if adjustment_editor(ui, adj) {
+            needs_refresh = true;
+            edited = true;
+        }
+    }
+
+    if let Some(kind) = add_adjustment {
+        app.mark_untracked_change(&format!("add_adjustment_layer {}", kind.label()));
+        app.canvas.add_adjustment_layer(Adjustment::new(kind));
         app.histories.push(crate::canvas::history::History::new());
         app.layer_caches.push(std::collections::HashMap::new());
         app.layer_cache_dirty.push(std::collections::HashSet::new());
         app.layer_ui_colors.push(egui::Color32::from_gray(40));
         active_idx = app.canvas.layers.len().saturating_sub(1);
+        app.selected_layers.clear();
+        needs_refresh = true;
+        edited = true;
+    }
+
+    if add_layer {
+        app.add_layer();
+        active_idx = app.canvas.layers.len().saturating_sub(1);
+    }
+
+    if add_vector_layer {
+        app.add_vector_layer();
+        active_idx = app.canvas.layers.len().saturating_sub(1);
     }
 
     if let Some(idx) = to_delete {
         if idx < app.canvas.layers.len() {
-            app.mark_layer_tiles_with_data_dirty(idx);
-            app.canvas.layers.remove(idx);
-            if idx < app.histories.len() {
-                app.histories.remove(idx);
-            }
-            if idx < app.layer_caches.len() {
-                app.layer_caches.remove(idx);
-            }
-            if idx < app.layer_cache_dirty.len() {
-                app.layer_cache_dirty.remove(idx);
-            }
-            if idx < app.layer_ui_colors.len() {
-                app.layer_ui_colors.remove(idx);
-            }
-            if active_idx >= app.canvas.layers.len() {
-                active_idx = app.canvas.layers.len().saturating_sub(1);
-            }
+            app.canvas.active_layer_idx = active_idx;
+            app.remove_layer(idx);
+            active_idx = app.canvas.active_layer_idx;
             needs_refresh = true;
         }
     }
 
     app.canvas.active_layer_idx = active_idx;
+    if merge_selected {
+        app.merge_selected_layers();
+        needs_refresh = true;
+    }
+    if delete_selected {
+        app.delete_selected_layers();
+        needs_refresh = true;
+    }
     if needs_refresh {
         app.mark_all_tiles_dirty();
         ctx.request_repaint();
     }
+    if edited {
+        app.mark_untracked_change("layer_properties");
+    }
+}
+
+/// Menu text for a color label: its swatch color, or plain text for no label.
+fn label_text(label: LayerLabel) -> egui::RichText {
+    match label.color() {
+        Some(color) => egui::RichText::new(format!("● {}", tr(label.label()))).color(color),
+        None => egui::RichText::new(tr("No label")),
+    }
+}
+
+fn row_id(layer: usize) -> egui::Id {
+    egui::Id::new(("layer_row", layer))
+}
+
+fn name_id(layer: usize) -> egui::Id {
+    egui::Id::new(("layer_name", layer))
+}
+
+/// Apply a click on layer `i`: Ctrl toggles it in the selection, Shift selects the range
+/// from the active layer, a plain click selects just that layer. Returns the new active layer.
+fn select_layer(app: &mut PainterApp, active_idx: usize, i: usize, modifiers: egui::Modifiers) -> usize {
+    if modifiers.shift {
+        let (lo, hi) = (active_idx.min(i), active_idx.max(i));
+        app.selected_layers = (lo..=hi).collect();
+        return i;
+    }
+    if !modifiers.command {
+        app.selected_layers.clear();
+        return i;
+    }
+
+    app.selected_layers.insert(active_idx);
+    if i != active_idx && app.selected_layers.remove(&i) {
+        return active_idx;
+    }
+    if i == active_idx && app.selected_layers.len() > 1 {
+        // Deselecting the active layer hands focus to the topmost remaining one
+        app.selected_layers.remove(&i);
+        return app.selected_layers.last().copied().unwrap_or(i);
+    }
+    app.selected_layers.insert(i);
+    i
+}
+
+/// Parameter sliders for the active adjustment layer; returns true when anything changed.
+fn adjustment_editor(ui: &mut egui::Ui, adj: &mut Adjustment) -> bool {
+    ui.label(egui::RichText::new(tr(adj.kind.label())).strong());
+    let mut changed = adjustment_kind_editor(ui, &mut adj.kind);
+    changed |= ui
+        .checkbox(&mut adj.use_mask, tr("Restrict to painted mask"))
+        .on_hover_text(tr("Paint on this layer to choose where the adjustment applies."))
+        .changed();
+    changed
+}
+
+/// Parameter widgets for one adjustment filter; returns true when anything changed.
+pub(crate) fn adjustment_kind_editor(ui: &mut egui::Ui, kind: &mut AdjustmentKind) -> bool {
+    let mut changed = false;
+    match kind {
+        AdjustmentKind::BrightnessContrast { brightness, contrast } => {
+            changed |= ui.add(egui::Slider::new(brightness, -1.0..=1.0).text(tr("Brightness"))).changed();
+            changed |= ui.add(egui::Slider::new(contrast, -1.0..=1.0).text(tr("Contrast"))).changed();
+        }
+        AdjustmentKind::HueSaturation { hue, saturation, lightness } => {
+            changed |= ui.add(egui::Slider::new(hue, -180.0..=180.0).text(tr("Hue"))).changed();
+            changed |= ui.add(egui::Slider::new(saturation, -1.0..=1.0).text(tr("Saturation"))).changed();
+            changed |= ui.add(egui::Slider::new(lightness, -1.0..=1.0).text(tr("Lightness"))).changed();
+        }
+        AdjustmentKind::Levels { in_black, in_white, gamma, out_black, out_white } => {
+            changed |= ui.add(egui::Slider::new(in_black, 0.0..=1.0).text(tr("Input black"))).changed();
+            changed |= ui.add(egui::Slider::new(in_white, 0.0..=1.0).text(tr("Input white"))).changed();
+            changed |= ui.add(egui::Slider::new(gamma, 0.1..=10.0).logarithmic(true).text(tr("Gamma"))).changed();
+            changed |= ui.add(egui::Slider::new(out_black, 0.0..=1.0).text(tr("Output black"))).changed();
+            changed |= ui.add(egui::Slider::new(out_white, 0.0..=1.0).text(tr("Output white"))).changed();
+        }
+        AdjustmentKind::GradientMap(map) => {
+            changed |= crate::ui::filters::gradient_map_editor(ui, map);
+        }
+    }
+    changed
 }
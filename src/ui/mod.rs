@@ -1,9 +1,29 @@
 //! egui panels that configure the canvas, brushes, colors, and layers.
+pub mod accessibility;
+pub mod background_color;
 pub mod brush_list;
 pub mod brush_settings;
 pub mod canvas_creation;
+pub mod channels;
+pub mod close_prompt;
 pub mod color_picker;
+pub mod document_properties;
 pub mod export_modal;
+pub mod favorites_bar;
+pub mod filters;
 pub mod general_settings;
+pub mod histogram;
+pub mod history;
 pub mod layers;
+pub mod overlay_guides;
+pub mod palette;
+pub mod profiler_hud;
+pub mod rulers;
+pub mod start_screen;
+pub mod statistics;
+pub mod stroke_selection;
+pub mod timeline;
+pub mod tool_options;
 pub mod top_bar;
+pub mod trim;
+pub mod view;
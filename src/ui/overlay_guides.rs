@@ -0,0 +1,122 @@
+use crate::PainterApp;
+use crate::app::render_helper::CanvasView;
+use crate::app::state::OverlayGuides;
+use crate::utils::i18n::tr;
+use crate::utils::vector::Vec2;
+use eframe::egui::{self, Color32};
+
+/// Shade over the canvas outside the crop preview.
+const LETTERBOX: Color32 = Color32::from_black_alpha(150);
+
+/// Thirds, center cross, safe frames and crop preview over the canvas, following zoom and
+/// view rotation. Drawn on the viewport only, so exports never see them.
+pub fn overlay_guides(ui: &egui::Ui, app: &PainterApp, view: &CanvasView) {
+    let guides = &app.view_settings.overlays;
+    if !guides.any() {
+        return;
+    }
+    let (w, h) = (app.canvas.width() as f32, app.canvas.height() as f32);
+    let (fx, fy, fw, fh) = guides.frame(w, h);
+    let painter = ui.painter();
+    let to_screen = |x: f32, y: f32| app.canvas_to_screen(Vec2::new(x, y), view.origin, view.canvas_center);
+    // A dark line under a light one stays visible over any artwork
+    let line = |a: (f32, f32), b: (f32, f32), color: Color32| {
+        let (a, b) = (to_screen(a.0, a.1), to_screen(b.0, b.1));
+        painter.line_segment([a, b], egui::Stroke::new(3.0, Color32::from_black_alpha(90)));
+        painter.line_segment([a, b], egui::Stroke::new(1.0, color));
+    };
+    let frame = |inset: f32, color: Color32| {
+        let (x0, y0) = (fx + fw * inset, fy + fh * inset);
+        let (x1, y1) = (fx + fw * (1.0 - inset), fy + fh * (1.0 - inset));
+        let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+        for i in 0..4 {
+            line(corners[i], corners[(i + 1) % 4], color);
+        }
+    };
+
+    if guides.aspect.is_some() {
+        // Two bars across the sides the crop leaves out
+        let bars = if fw < w {
+            [(0.0, 0.0, fx, h), (fx + fw, 0.0, w - fx - fw, h)]
+        } else {
+            [(0.0, 0.0, w, fy), (0.0, fy + fh, w, h - fy - fh)]
+        };
+        for (x, y, bw, bh) in bars {
+            if bw <= 0.0 || bh <= 0.0 {
+                continue;
+            }
+            let points = vec![to_screen(x, y), to_screen(x + bw, y), to_screen(x + bw, y + bh), to_screen(x, y + bh)];
+            painter.add(egui::Shape::convex_polygon(points, LETTERBOX, egui::Stroke::NONE));
+        }
+        frame(0.0, Color32::WHITE);
+    }
+    let guide_color = Color32::from_white_alpha(200);
+    if guides.thirds {
+        for t in [1.0 / 3.0, 2.0 / 3.0] {
+            line((fx + fw * t, fy), (fx + fw * t, fy + fh), guide_color);
+            line((fx, fy + fh * t), (fx + fw, fy + fh * t), guide_color);
+        }
+    }
+    if guides.center {
+        let (cx, cy) = (fx + fw / 2.0, fy + fh / 2.0);
+        let arm = fw.min(fh) * 0.05;
+        line((cx - arm, cy), (cx + arm, cy), guide_color);
+        line((cx, cy - arm), (cx, cy + arm), guide_color);
+    }
+    if guides.safe_areas {
+        frame(guides.action_safe, Color32::from_rgb(120, 220, 120));
+        frame(guides.title_safe, Color32::from_rgb(240, 200, 80));
+    }
+}
+
+/// Toggles for each guide and the crop ratios, shared by the View menu and the View panel.
+pub fn overlay_guides_toggles(ui: &mut egui::Ui, guides: &mut OverlayGuides) {
+    ui.checkbox(&mut guides.thirds, tr("Rule of thirds"));
+    ui.checkbox(&mut guides.center, tr("Center cross"));
+    ui.checkbox(&mut guides.safe_areas, tr("Safe areas"))
+        .on_hover_text(tr("Action-safe (green) and title-safe (yellow) frames."));
+    ui.horizontal(|ui| {
+        ui.label(tr("Crop preview"));
+        egui::ComboBox::from_id_salt("overlay_aspect")
+            .selected_text(aspect_label(guides.aspect))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut guides.aspect, None, tr("None"));
+                for (label, ratio) in OverlayGuides::ASPECT_PRESETS {
+                    ui.selectable_value(&mut guides.aspect, Some(ratio), label);
+                }
+            });
+    });
+}
+
+/// Dock section of the View panel: the toggles plus the safe-frame insets and a custom ratio.
+pub fn overlay_guides_settings(ui: &mut egui::Ui, guides: &mut OverlayGuides) {
+    overlay_guides_toggles(ui, guides);
+    if guides.safe_areas {
+        let mut action = guides.action_safe * 100.0;
+        let mut title = guides.title_safe * 100.0;
+        ui.add(egui::Slider::new(&mut action, 0.0..=25.0).suffix("%").text(tr("Action-safe margin")));
+        ui.add(egui::Slider::new(&mut title, 0.0..=25.0).suffix("%").text(tr("Title-safe margin")));
+        guides.action_safe = action / 100.0;
+        guides.title_safe = title / 100.0;
+    }
+    if let Some((aw, ah)) = &mut guides.aspect {
+        ui.horizontal(|ui| {
+            ui.label(tr("Ratio"));
+            ui.add(egui::DragValue::new(aw).range(0.1..=100.0).speed(0.05).max_decimals(2));
+            ui.label(":");
+            ui.add(egui::DragValue::new(ah).range(0.1..=100.0).speed(0.05).max_decimals(2));
+        });
+    }
+    ui.label(egui::RichText::new(tr("Guides show in the viewport only and are never exported.")).weak());
+}
+
+/// Preset name for a crop ratio, or the ratio itself when it is custom.
+fn aspect_label(aspect: Option<(f32, f32)>) -> String {
+    let Some(ratio) = aspect else {
+        return tr("None").to_string();
+    };
+    match OverlayGuides::ASPECT_PRESETS.iter().find(|(_, r)| *r == ratio) {
+        Some((label, _)) => label.to_string(),
+        None => format!("{}:{}", ratio.0, ratio.1),
+    }
+}
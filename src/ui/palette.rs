@@ -0,0 +1,66 @@
+use crate::PainterApp;
+use crate::canvas::palette::MAX_COLORS;
+use crate::ui::accessibility;
+use crate::utils::i18n::tr;
+use eframe::egui::{self, Color32};
+
+/// Dock tab for editing the document palette and toggling palette-constrained painting.
+pub fn palette_panel(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let palette = &mut app.canvas.palette;
+    ui.checkbox(&mut palette.enabled, tr("Constrain painting to palette"))
+        .on_hover_text(tr("Every painted pixel snaps to the nearest palette color and becomes fully opaque or clear."));
+
+    ui.horizontal(|ui| {
+        let full = palette.colors.len() >= MAX_COLORS;
+        if ui
+            .add_enabled(!full, egui::Button::new(tr("+ Add brush color")))
+            .clicked()
+        {
+            let [r, g, b, _] = app.brush.brush_options.color.to_srgba_unmultiplied();
+            let color = Color32::from_rgb(r, g, b);
+            if !palette.colors.contains(&color) {
+                palette.colors.push(color);
+            }
+        }
+        if ui
+            .add_enabled(!palette.colors.is_empty(), egui::Button::new(tr("Clear")))
+            .clicked()
+        {
+            palette.colors.clear();
+        }
+    });
+    ui.label(format!("{} / {} colors", palette.colors.len(), MAX_COLORS));
+    ui.separator();
+
+    let mut remove = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing = egui::vec2(2.0, 2.0);
+                for (i, color) in palette.colors.iter_mut().enumerate() {
+                    let (rect, response) = ui.allocate_exact_size(egui::vec2(20.0, 20.0), egui::Sense::click());
+                    ui.painter().rect_filled(rect, 2.0, *color);
+                    let stroke_color = if response.hovered() { Color32::WHITE } else { Color32::from_gray(60) };
+                    ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, stroke_color));
+                    accessibility::button(ui, &response, &accessibility::color_name(*color));
+
+                    // Click picks the swatch as brush color, right-click edits or removes it
+                    if response.clicked() {
+                        let alpha = app.brush.brush_options.color.a();
+                        app.brush.brush_options.color = Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), alpha);
+                    }
+                    response.context_menu(|ui| {
+                        ui.color_edit_button_srgba(color);
+                        if ui.button(tr("Remove")).clicked() {
+                            remove = Some(i);
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+        });
+    if let Some(i) = remove {
+        palette.colors.remove(i);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::PainterApp;
+use crate::utils::i18n::{tr, trf};
+use crate::utils::profiler;
+use eframe::egui::{self, Color32};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Frames kept for the rolling averages.
+const HISTORY: usize = 120;
+const BAR_HEIGHT: f32 = 16.0;
+const FLAME_WIDTH: f32 = 320.0;
+/// The stages called out at the top of the HUD and the profiler scopes that time them.
+const HEADLINE: [(&str, &str); 5] = [
+    ("Input", "input"),
+    ("Dabs", "dab"),
+    // Soft dabs are only queued above and painted here, a segment at a time
+    ("Dab batches", "dab batch"),
+    ("Composite", "region_to_color_image"),
+    ("Upload", "texture_set"),
+];
+
+struct ScopeHistory {
+    parent: Option<&'static str>,
+    /// Milliseconds spent in the scope per frame, newest last.
+    ms: VecDeque<f32>,
+}
+
+/// Rolling per-frame timings of the `ScopeTimer` scopes, for the debug HUD.
+#[derive(Default)]
+pub struct ProfilerHud {
+    frame_ms: VecDeque<f32>,
+    scopes: BTreeMap<&'static str, ScopeHistory>,
+}
+
+impl ProfilerHud {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold everything timed since the last call in as one frame that took `dt` seconds.
+    pub fn record_frame(&mut self, dt: f32) {
+        push_capped(&mut self.frame_ms, dt * 1000.0);
+        let report = profiler::take_report();
+        for scope in self.scopes.values_mut() {
+            push_capped(&mut scope.ms, 0.0);
+        }
+        for entry in report {
+            let scope = self.scopes.entry(entry.name).or_insert_with(|| ScopeHistory {
+                parent: entry.parent,
+                // Frames before the scope first showed up count as zero
+                ms: std::iter::repeat_n(0.0, self.frame_ms.len()).collect(),
+            });
+            scope.parent = scope.parent.or(entry.parent);
+            if let Some(last) = scope.ms.back_mut() {
+                *last = entry.total.as_secs_f32() * 1000.0;
+            }
+        }
+    }
+
+    fn average(&self, name: &str) -> f32 {
+        self.scopes.get(name).map_or(0.0, |s| mean(&s.ms))
+    }
+
+    fn peak(&self, name: &str) -> f32 {
+        self.scopes.get(name).map_or(0.0, |s| s.ms.iter().copied().fold(0.0, f32::max))
+    }
+
+    /// Scopes in depth-first order with their depth, roots sorted by name.
+    fn tree(&self) -> Vec<(&'static str, usize)> {
+        fn visit(hud: &ProfilerHud, parent: Option<&'static str>, depth: usize, out: &mut Vec<(&'static str, usize)>) {
+            for (&name, scope) in &hud.scopes {
+                if scope.parent == parent && !out.iter().any(|&(n, _)| n == name) {
+                    out.push((name, depth));
+                    visit(hud, Some(name), depth + 1, out);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        visit(self, None, 0, &mut out);
+        out
+    }
+}
+
+fn push_capped(values: &mut VecDeque<f32>, value: f32) {
+    values.push_back(value);
+    while values.len() > HISTORY {
+        values.pop_front();
+    }
+}
+
+fn mean(values: &VecDeque<f32>) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+/// Overlay in the top-right corner with frame time, the main pipeline stages and a
+/// flame-style breakdown of every profiler scope, averaged over the last frames.
+pub fn profiler_hud(app: &PainterApp, ctx: &egui::Context) {
+    if !app.show_profiler_hud {
+        return;
+    }
+    let hud = &app.profiler_hud;
+    let frame = mean(&hud.frame_ms);
+    egui::Area::new(egui::Id::new("profiler_hud"))
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-12.0, 64.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).multiply_with_opacity(0.9).show(ui, |ui| {
+                ui.set_width(FLAME_WIDTH);
+                let fps = if frame > 0.0 { 1000.0 / frame } else { 0.0 };
+                ui.strong(trf(
+                    "Frame {} ms ({} fps), CPU {} ms",
+                    &[&format!("{frame:.2}"), &format!("{fps:.0}"), &format!("{:.2}", hud.average("frame"))],
+                ));
+                egui::Grid::new("profiler_hud_headline").num_columns(3).show(ui, |ui| {
+                    for (label, scope) in HEADLINE {
+                        ui.label(tr(label));
+                        ui.monospace(format!("{:7.2} ms", hud.average(scope)));
+                        ui.weak(trf("peak {}", &[&format!("{:.2}", hud.peak(scope))]));
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                flame(ui, hud, frame);
+                ui.weak(trf("Averages over the last {} frames; time per frame summed across threads.", &[&hud.frame_ms.len()]));
+            });
+        });
+    ctx.request_repaint();
+}
+
+/// One row per nesting depth; each scope's bar is as wide as its share of the frame and sits
+/// inside its parent's bar, like a flame graph.
+fn flame(ui: &mut egui::Ui, hud: &ProfilerHud, frame: f32) {
+    let tree = hud.tree();
+    let depth = tree.iter().map(|&(_, d)| d + 1).max().unwrap_or(0);
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(FLAME_WIDTH, depth as f32 * BAR_HEIGHT), egui::Sense::hover());
+    let roots: f32 = tree.iter().filter(|&&(_, d)| d == 0).map(|&(n, _)| hud.average(n)).sum();
+    let scale = FLAME_WIDTH / frame.max(roots).max(1e-3);
+
+    let painter = ui.painter_at(rect);
+    let font = egui::FontId::monospace(10.0);
+    // Next free x in each parent's bar (and along the root row)
+    let mut cursor: BTreeMap<Option<&'static str>, f32> = BTreeMap::new();
+    let mut starts: BTreeMap<&'static str, f32> = BTreeMap::new();
+    let hover = ui.ctx().pointer_hover_pos();
+    let mut hovered = None;
+    for (i, &(name, d)) in tree.iter().enumerate() {
+        let parent = hud.scopes.get(name).and_then(|s| s.parent);
+        let parent_start = parent.and_then(|p| starts.get(p).copied()).unwrap_or(0.0);
+        let x = *cursor.entry(parent).or_insert(parent_start);
+        let width = hud.average(name) * scale;
+        cursor.insert(parent, x + width);
+        starts.insert(name, x);
+
+        let bar = egui::Rect::from_min_size(
+            rect.min + egui::vec2(x, d as f32 * BAR_HEIGHT),
+            egui::vec2(width.max(1.0), BAR_HEIGHT - 1.0),
+        );
+        let hue = (i as f32 * 0.13).fract();
+        let color = Color32::from(egui::ecolor::Hsva::new(0.05 + hue * 0.12, 0.7, 0.85, 1.0));
+        painter.rect_filled(bar, 2.0, color);
+        let label = format!("{name} {:.2}", hud.average(name));
+        if width > 40.0 {
+            painter.text(bar.left_center() + egui::vec2(3.0, 0.0), egui::Align2::LEFT_CENTER, &label, font.clone(), Color32::BLACK);
+        }
+        if hover.is_some_and(|p| bar.contains(p)) {
+            hovered = Some(label);
+        }
+    }
+    ui.monospace(hovered.unwrap_or_default());
+    // Slowest scopes as a list, for bars too thin to label
+    let mut rows: Vec<_> = tree.iter().map(|&(name, d)| (name, d, hud.average(name))).collect();
+    rows.sort_by(|a, b| b.2.total_cmp(&a.2));
+    egui::Grid::new("profiler_hud_scopes").num_columns(2).show(ui, |ui| {
+        for (name, d, ms) in rows.into_iter().take(8) {
+            ui.monospace(format!("{}{name}", "  ".repeat(d)));
+            ui.monospace(format!("{ms:7.2} ms"));
+            ui.end_row();
+        }
+    });
+}
@@ -0,0 +1,148 @@
+use crate::PainterApp;
+use crate::app::render_helper::CanvasView;
+use crate::app::state::Guide;
+use crate::utils::i18n::tr;
+use crate::utils::vector::Vec2;
+use eframe::egui::{self, Color32, Pos2, Rect};
+
+/// Thickness of the rulers in screen pixels.
+pub const RULER_SIZE: f32 = 18.0;
+/// Labelled ticks are kept at least this far apart on screen.
+const MIN_LABEL_SPACING: f32 = 60.0;
+const GUIDE_COLOR: Color32 = Color32::from_rgb(0, 200, 220);
+
+/// Rulers along the top and left of the viewport in the chosen unit, a marker at the pointer,
+/// and guides dragged out of them. Dragging a guide back onto its ruler removes it.
+pub fn rulers(ui: &mut egui::Ui, app: &mut PainterApp, view: &CanvasView) {
+    draw_guides(ui.painter(), app, view);
+    if !app.view_settings.show_rulers {
+        return;
+    }
+
+    let area = view.response.rect;
+    let top = Rect::from_min_max(area.min + egui::vec2(RULER_SIZE, 0.0), egui::pos2(area.max.x, area.min.y + RULER_SIZE));
+    let left = Rect::from_min_max(area.min + egui::vec2(0.0, RULER_SIZE), egui::pos2(area.min.x + RULER_SIZE, area.max.y));
+    let corner = Rect::from_min_size(area.min, egui::vec2(RULER_SIZE, RULER_SIZE));
+    let hover = ui.ctx().pointer_hover_pos();
+
+    let painter = ui.painter();
+    let bg = ui.visuals().extreme_bg_color;
+    let fg = ui.visuals().text_color();
+    for rect in [top, left, corner] {
+        painter.rect_filled(rect, 0.0, bg);
+    }
+    painter.text(corner.center(), egui::Align2::CENTER_CENTER, app.view_settings.ruler_unit.label(), egui::FontId::proportional(9.0), fg);
+
+    if app.rotation.abs() < 1e-4 {
+        let per_unit = app.view_settings.ruler_unit.pixels_per_unit(app.document_dpi);
+        draw_ticks(painter, top, false, view.origin.x, app.zoom * per_unit, fg);
+        draw_ticks(painter, left, true, view.origin.y, app.zoom * per_unit, fg);
+        if let Some(pos) = hover {
+            let marker = egui::Stroke::new(1.0, GUIDE_COLOR);
+            painter.vline(pos.x.clamp(top.left(), top.right()), top.y_range(), marker);
+            painter.hline(left.x_range(), pos.y.clamp(left.top(), left.bottom()), marker);
+        }
+    } else {
+        painter.text(top.left_center() + egui::vec2(6.0, 0.0), egui::Align2::LEFT_CENTER, tr("Rulers follow the unrotated view"), egui::FontId::proportional(10.0), fg);
+    }
+
+    // Guides: pressing a ruler grabs the guide under the pointer or starts a new one
+    for (rect, vertical) in [(top, false), (left, true)] {
+        let response = ui.interact(rect, ui.id().with(("ruler", vertical)), egui::Sense::drag());
+        let pressed = response.drag_started().then(|| response.interact_pointer_pos()).flatten();
+        if let Some(pos) = pressed {
+            let canvas = app.screen_to_canvas_unclamped(pos, view.origin, view.canvas_center);
+            let position = if vertical { canvas.x } else { canvas.y };
+            let tolerance = 4.0 / app.zoom;
+            let existing = app
+                .guides
+                .iter()
+                .position(|g| g.vertical == vertical && (g.position - position).abs() < tolerance);
+            app.dragging_guide = Some(existing.unwrap_or_else(|| {
+                app.guides.push(Guide { vertical, position });
+                app.guides.len() - 1
+            }));
+        }
+    }
+    let Some(idx) = app.dragging_guide else {
+        return;
+    };
+    let (pressed, pointer) = ui.input(|i| (i.pointer.primary_down(), i.pointer.interact_pos()));
+    if let (true, Some(pos)) = (pressed, pointer) {
+        let canvas = app.screen_to_canvas_unclamped(pos, view.origin, view.canvas_center);
+        if let Some(guide) = app.guides.get_mut(idx) {
+            guide.position = if guide.vertical { canvas.x } else { canvas.y }.round();
+        }
+        ui.ctx().request_repaint();
+    } else {
+        // Released over its own ruler: throw the guide away
+        let vertical = app.guides.get(idx).is_some_and(|g| g.vertical);
+        let ruler = if vertical { left } else { top };
+        if pointer.is_some_and(|p| ruler.contains(p)) && idx < app.guides.len() {
+            app.guides.remove(idx);
+        }
+        app.dragging_guide = None;
+    }
+}
+
+/// Lines across the canvas for each guide, following zoom and view rotation.
+fn draw_guides(painter: &egui::Painter, app: &PainterApp, view: &CanvasView) {
+    let (w, h) = (app.canvas.width() as f32, app.canvas.height() as f32);
+    let stroke = egui::Stroke::new(1.0, GUIDE_COLOR);
+    for guide in &app.guides {
+        let (a, b) = if guide.vertical {
+            (Vec2::new(guide.position, 0.0), Vec2::new(guide.position, h))
+        } else {
+            (Vec2::new(0.0, guide.position), Vec2::new(w, guide.position))
+        };
+        let to_screen = |p: Vec2| app.canvas_to_screen(p, view.origin, view.canvas_center);
+        painter.line_segment([to_screen(a), to_screen(b)], stroke);
+    }
+}
+
+/// Ticks and labels along one ruler. `screen_per_unit` is how many screen pixels one unit spans
+/// and `origin` is where canvas coordinate 0 falls on the ruler's axis.
+fn draw_ticks(painter: &egui::Painter, rect: Rect, vertical: bool, origin: f32, screen_per_unit: f32, color: Color32) {
+    let step = nice_step(MIN_LABEL_SPACING / screen_per_unit);
+    let (start, end) = if vertical { (rect.top(), rect.bottom()) } else { (rect.left(), rect.right()) };
+    let first = ((start - origin) / screen_per_unit / step).floor() as i64;
+    let last = ((end - origin) / screen_per_unit / step).ceil() as i64;
+    let painter = painter.with_clip_rect(rect);
+    let stroke = egui::Stroke::new(1.0, color.gamma_multiply(0.6));
+    for i in first..=last {
+        let value = i as f32 * step;
+        // Minor ticks at a tenth of the labelled step
+        for minor in 0..10 {
+            let at = origin + (value + minor as f32 * step / 10.0) * screen_per_unit;
+            let length = if minor == 0 { RULER_SIZE } else if minor == 5 { RULER_SIZE * 0.5 } else { RULER_SIZE * 0.25 };
+            let (a, b) = if vertical {
+                (Pos2::new(rect.right() - length, at), Pos2::new(rect.right(), at))
+            } else {
+                (Pos2::new(at, rect.bottom() - length), Pos2::new(at, rect.bottom()))
+            };
+            painter.line_segment([a, b], stroke);
+        }
+        let label = format!("{}", (value * 1000.0).round() / 1000.0);
+        let at = origin + value * screen_per_unit;
+        if vertical {
+            painter.text(Pos2::new(rect.left() + 2.0, at + 2.0), egui::Align2::LEFT_TOP, label, egui::FontId::proportional(9.0), color);
+        } else {
+            painter.text(Pos2::new(at + 2.0, rect.top() + 1.0), egui::Align2::LEFT_TOP, label, egui::FontId::proportional(9.0), color);
+        }
+    }
+}
+
+/// Smallest 1, 2 or 5 times a power of ten that is at least `min`.
+fn nice_step(min: f32) -> f32 {
+    let magnitude = 10f32.powf(min.max(1e-6).log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&s| s >= min)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Whether `pos` lies on one of the rulers, which take presses away from the canvas.
+pub fn covers(app: &PainterApp, area: Rect, pos: Pos2) -> bool {
+    app.view_settings.show_rulers && area.contains(pos) && (pos.x < area.min.x + RULER_SIZE || pos.y < area.min.y + RULER_SIZE)
+}
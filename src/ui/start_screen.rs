@@ -0,0 +1,97 @@
+use crate::PainterApp;
+use crate::app::recent_files::THUMBNAIL_SIZE;
+use crate::app::state::CANVAS_PRESETS;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+const CARD_WIDTH: f32 = THUMBNAIL_SIZE as f32 + 16.0;
+
+/// Shown at launch until a document is created, opened or restored.
+pub fn start_screen(app: &mut PainterApp, ctx: &egui::Context) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.add_space(16.0);
+            ui.heading(tr("Rusty Painter"));
+            ui.add_space(8.0);
+
+            ui.horizontal(|ui| {
+                if ui.button(tr("Open File...")).clicked() {
+                    app.open_image_dialog(ctx);
+                }
+                let session = app.session_path();
+                if ui
+                    .add_enabled(session.exists(), egui::Button::new(tr("Restore Last Session")))
+                    .on_hover_text(tr("Reopen the canvas as it was when the app last closed (flattened)"))
+                    .clicked()
+                {
+                    app.restore_session(ctx);
+                }
+                let actions = app.journal.recoverable().len().saturating_sub(1);
+                if actions > 0
+                    && ui
+                        .button(trf("Recover Unsaved Work ({} actions)", &[&actions]))
+                        .on_hover_text(tr("The app did not close cleanly last time: reopen its document and replay the journaled strokes and layer changes"))
+                        .clicked()
+                {
+                    app.recover_from_journal(ctx);
+                }
+                if let Some(stop) = app.journal.recovery_stop() {
+                    ui.label(egui::RichText::new(trf("Recovery stops before {}, which can't be replayed.", &[&stop])).weak());
+                }
+            });
+
+            ui.separator();
+            ui.strong(tr("New Canvas"));
+            ui.horizontal_wrapped(|ui| {
+                for (name, width, height) in CANVAS_PRESETS {
+                    if ui.button(format!("{name}\n{width} × {height}")).clicked() {
+                        app.new_canvas_with_size(ctx, width, height);
+                    }
+                }
+                if ui.button(tr("Custom...")).clicked() {
+                    app.show_new_canvas_modal = true;
+                }
+            });
+
+            ui.separator();
+            ui.strong(tr("Recent Files"));
+            if app.recent_files.entries().is_empty() {
+                ui.label(egui::RichText::new(tr("Opened and exported images show up here")).weak());
+            }
+            recent_files_grid(app, ctx, ui);
+        });
+    });
+}
+
+/// Thumbnails of the recent files, clicking one opens it.
+fn recent_files_grid(app: &mut PainterApp, ctx: &egui::Context, ui: &mut egui::Ui) {
+    let mut open = None;
+    ui.horizontal_wrapped(|ui| {
+        for path in app.recent_files.entries().to_vec() {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let response = ui
+                .allocate_ui(egui::vec2(CARD_WIDTH, CARD_WIDTH + 20.0), |ui| {
+                    ui.set_width(CARD_WIDTH);
+                    ui.vertical_centered(|ui| {
+                        let slot = egui::vec2(THUMBNAIL_SIZE as f32, THUMBNAIL_SIZE as f32);
+                        let (rect, _) = ui.allocate_exact_size(slot, egui::Sense::hover());
+                        ui.painter().rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+                        if let Some(texture) = app.recent_files.thumbnail(ctx, &path) {
+                            let size = texture.size_vec2() * (slot.x / texture.size_vec2().max_elem());
+                            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                            ui.painter().image(texture.id(), egui::Rect::from_center_size(rect.center(), size), uv, egui::Color32::WHITE);
+                        }
+                        ui.add(egui::Label::new(name).truncate());
+                    });
+                })
+                .response
+                .interact(egui::Sense::click());
+            if response.on_hover_text(path.display().to_string()).clicked() {
+                open = Some(path);
+            }
+        }
+    });
+    if let Some(path) = open {
+        app.open_image(ctx, &path);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::PainterApp;
+use crate::app::document_info::format_duration;
+use crate::utils::i18n::tr;
+use eframe::egui;
+
+/// File > Statistics: painting time, strokes, undos and dabs of the open document, saved with
+/// the other document properties.
+pub fn statistics_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    if !app.show_statistics {
+        return;
+    }
+    let mut open = true;
+    let stats = &app.document_info.stats;
+    egui::Window::new(tr("Statistics"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Grid::new("document_statistics").num_columns(2).show(ui, |ui| {
+                ui.label(tr("Time painted"));
+                ui.label(format_duration(stats.painting_time));
+                ui.end_row();
+                ui.label(tr("Strokes"));
+                ui.label(stats.strokes.to_string());
+                ui.end_row();
+                ui.label(tr("Undos"));
+                ui.label(stats.undos.to_string());
+                ui.end_row();
+                ui.label(tr("Dabs"));
+                ui.label(stats.dabs.to_string());
+                ui.end_row();
+            });
+            ui.small(tr("Time painted counts only while a stroke is in progress. Stored with the document properties in PNG exports."));
+        });
+    app.show_statistics = open;
+}
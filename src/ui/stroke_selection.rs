@@ -0,0 +1,146 @@
+use crate::PainterApp;
+use crate::canvas::canvas::{Canvas, CoverageMasks};
+use crate::canvas::history::UndoAction;
+use crate::selection::StrokePosition;
+use crate::utils::i18n::tr;
+use eframe::egui;
+use eframe::egui::Color32;
+use std::sync::mpsc;
+
+/// Options for rasterizing the selection outline.
+pub struct StrokeSelectionSettings {
+    pub width: f32,
+    pub color: Color32,
+    pub position: StrokePosition,
+}
+
+/// Coverage of a selection stroke being traced on the background pool, and where it goes.
+pub struct StrokeSelectionJob {
+    layer_idx: usize,
+    color: Color32,
+    masks: mpsc::Receiver<CoverageMasks>,
+}
+
+impl Default for StrokeSelectionSettings {
+    fn default() -> Self {
+        Self {
+            width: 4.0,
+            color: Color32::BLACK,
+            position: StrokePosition::Center,
+        }
+    }
+}
+
+/// Dialog that traces the current selection edge onto the active layer.
+pub fn stroke_selection_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    poll_stroke_selection(app, ctx);
+    if !app.show_stroke_selection {
+        return;
+    }
+
+    let mut open = app.show_stroke_selection;
+    let mut apply = false;
+    egui::Window::new(tr("Stroke Selection"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            let settings = &mut app.stroke_selection;
+            ui.add(egui::Slider::new(&mut settings.width, 1.0..=200.0).logarithmic(true).text(tr("Width")));
+            ui.horizontal(|ui| {
+                ui.label(tr("Color"));
+                ui.color_edit_button_srgba(&mut settings.color);
+            });
+            ui.horizontal(|ui| {
+                for position in [StrokePosition::Inside, StrokePosition::Center, StrokePosition::Outside] {
+                    ui.selectable_value(&mut settings.position, position, tr(position.label()));
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                let busy = app.stroke_selection_job.is_some();
+                if ui
+                    .add_enabled(app.selection_manager.has_selection() && !busy, egui::Button::new(tr("Stroke")))
+                    .clicked()
+                {
+                    apply = true;
+                }
+                if busy {
+                    ui.spinner();
+                }
+            });
+        });
+
+    if apply {
+        stroke_selection(app);
+        open = false;
+    }
+    app.show_stroke_selection = open;
+}
+
+/// Paint a finished selection stroke onto its layer as one undo step.
+fn poll_stroke_selection(app: &mut PainterApp, ctx: &egui::Context) {
+    let Some(job) = &app.stroke_selection_job else {
+        return;
+    };
+    let masks = match job.masks.try_recv() {
+        Ok(masks) => masks,
+        Err(mpsc::TryRecvError::Empty) => {
+            ctx.request_repaint();
+            return;
+        }
+        Err(mpsc::TryRecvError::Disconnected) => Vec::new(),
+    };
+    let Some(job) = app.stroke_selection_job.take() else {
+        return;
+    };
+    // The layer may have been locked or turned into something else meanwhile
+    let paintable = app
+        .canvas
+        .layers
+        .get(job.layer_idx)
+        .is_some_and(|l| !l.locked && l.adjustment.is_none() && l.vector.is_none());
+    if !paintable || masks.is_empty() {
+        return;
+    }
+    app.bake_layer_offset(job.layer_idx);
+    let mut action = UndoAction::new("Stroke selection");
+    let canvas = &app.canvas;
+    app.pool.install(|| canvas.fill_coverage(job.layer_idx, job.color, &masks, &mut action));
+    if action.tiles.is_empty() {
+        return;
+    }
+    if let Some(hist) = app.histories.get_mut(job.layer_idx) {
+        hist.push_action(action);
+    }
+    app.mark_untracked_change("stroke_selection");
+}
+
+/// Start tracing the selection boundary for the active layer on the background pool;
+/// [`poll_stroke_selection`] paints it in once the coverage is ready.
+fn stroke_selection(app: &mut PainterApp) {
+    let layer_idx = app.canvas.active_layer_idx;
+    let Some(layer) = app.canvas.layers.get(layer_idx) else {
+        return;
+    };
+    if layer.locked || layer.adjustment.is_some() || layer.vector.is_some() {
+        return;
+    }
+    let settings = &app.stroke_selection;
+    let Some((min, max)) = app.selection_manager.bounds(settings.width + 1.0) else {
+        return;
+    };
+    let Some(selection) = app.selection_manager.detached() else {
+        return;
+    };
+
+    let (width, position) = (settings.width, settings.position);
+    let size = (app.canvas.width(), app.canvas.height());
+    let tile_size = app.canvas.tile_size();
+    let (done, masks) = mpsc::channel();
+    app.background_pool.spawn(move || {
+        let _ = done.send(Canvas::coverage_masks(min, max, size, tile_size, |p| selection.stroke_coverage(p, width, position)));
+    });
+    app.stroke_selection_job = Some(StrokeSelectionJob { layer_idx, color: settings.color, masks });
+}
@@ -0,0 +1,147 @@
+use crate::PainterApp;
+use crate::ui::export_modal::ExportScope;
+use crate::ui::accessibility;
+use crate::utils::i18n::tr;
+use eframe::egui;
+use std::sync::mpsc;
+
+/// Longest edge of the playback preview in pixels.
+const PREVIEW_SIZE: usize = 256;
+
+/// Downsampled frames looping in the timeline while playing.
+pub struct Playback {
+    /// Frames being flattened on the background pool, until they arrive as `frames`.
+    job: Option<mpsc::Receiver<Vec<egui::ColorImage>>>,
+    frames: Vec<egui::TextureHandle>,
+    started: f64,
+}
+
+impl Playback {
+    fn new(app: &PainterApp) -> Self {
+        let longest = app.canvas.width().max(app.canvas.height());
+        let step = longest.div_ceil(PREVIEW_SIZE).max(1);
+        let sources = app.animation.frame_sources(&app.canvas);
+        let (done, receiver) = mpsc::channel();
+        app.background_pool.spawn(move || {
+            let _ = done.send(sources.render_all(step));
+        });
+        Self {
+            job: Some(receiver),
+            frames: Vec::new(),
+            started: 0.0,
+        }
+    }
+
+    /// Upload the frames once the background job has flattened them; playback starts then.
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.job else {
+            return;
+        };
+        match job.try_recv() {
+            Ok(images) => {
+                self.frames = images
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, img)| ctx.load_texture(format!("playback_frame_{idx}"), img, egui::TextureOptions::LINEAR))
+                    .collect();
+                self.started = ctx.input(|i| i.time);
+                self.job = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.job = None,
+        }
+    }
+}
+
+/// Dock tab for flipbook animation: frame strip, onion skin settings, playback and export.
+pub fn timeline_panel(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let frame_count = app.animation.frame_count();
+    let current = app.animation.current();
+    let can_switch = app.can_switch_frames();
+
+    egui::ScrollArea::horizontal().id_salt("timeline_frames").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            for idx in 0..frame_count {
+                let label = egui::SelectableLabel::new(idx == current, format!("{}", idx + 1));
+                if ui.add_enabled(can_switch, label).clicked() {
+                    app.switch_frame(idx);
+                }
+            }
+        });
+    });
+
+    ui.horizontal(|ui| {
+        ui.add_enabled_ui(can_switch, |ui| {
+            let response = accessibility::named(ui.button("◀"), tr("Previous frame"));
+            if response.on_hover_text(tr("Previous frame")).clicked() && current > 0 {
+                app.switch_frame(current - 1);
+            }
+            let response = accessibility::named(ui.button("▶"), tr("Next frame"));
+            if response.on_hover_text(tr("Next frame")).clicked() {
+                app.switch_frame(current + 1);
+            }
+            let response = accessibility::named(ui.button("➕"), tr("Add a blank frame"));
+            if response.on_hover_text(tr("Add a blank frame")).clicked() {
+                app.add_frame(false);
+            }
+            let response = accessibility::named(ui.button("⧉"), tr("Duplicate this frame"));
+            if response.on_hover_text(tr("Duplicate this frame")).clicked() {
+                app.add_frame(true);
+            }
+            let response = ui.add_enabled(frame_count > 1, egui::Button::new("🗑"));
+            if accessibility::named(response, tr("Delete this frame"))
+                .on_hover_text(tr("Delete this frame"))
+                .clicked()
+            {
+                app.delete_frame();
+            }
+        });
+    });
+
+    ui.separator();
+    let before = app.animation.onion;
+    let onion = &mut app.animation.onion;
+    ui.checkbox(&mut onion.enabled, tr("Onion skin"));
+    ui.add_enabled_ui(onion.enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(tr("Before"));
+            ui.add(egui::DragValue::new(&mut onion.before).range(0..=5));
+            ui.label(tr("After"));
+            ui.add(egui::DragValue::new(&mut onion.after).range(0..=5));
+        });
+        ui.add(egui::Slider::new(&mut onion.opacity, 0.05..=1.0).text(tr("Opacity")));
+    });
+    if app.animation.onion != before {
+        app.animation.refresh_onion();
+        app.mark_all_tiles_dirty();
+    }
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut app.animation.fps).range(1.0..=60.0).suffix(" fps"));
+        let label = if app.playback.is_some() { "⏹ Stop" } else { "⏵ Play" };
+        // Stopping just drops the rendered frames
+        if ui.button(label).clicked() && app.playback.take().is_none() {
+            app.playback = Some(Playback::new(app));
+        }
+    });
+    if let Some(playback) = &mut app.playback {
+        playback.poll(ui.ctx());
+        if playback.job.is_some() {
+            ui.spinner();
+        } else if !playback.frames.is_empty() {
+            let elapsed = ui.input(|i| i.time) - playback.started;
+            let idx = (elapsed * app.animation.fps as f64) as usize % playback.frames.len();
+            let texture = &playback.frames[idx];
+            let size = texture.size_vec2();
+            let scale = (ui.available_width() / size.x).min(1.0);
+            ui.image((texture.id(), size * scale));
+        }
+        ui.ctx().request_repaint();
+    }
+
+    ui.separator();
+    if ui.button(tr("Export Animation...")).clicked() {
+        app.open_export(ExportScope::Animation);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::PainterApp;
+use crate::app::state::SampleSize;
+use crate::app::tools::Tool;
+use crate::canvas::vector::WIDTH_RANGE;
+use crate::selection::{PixelSnap, SelectionType};
+use crate::selection::transform::{Interpolation, TransformInfo};
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+/// Context strip under the top bar showing the options of the active tool.
+pub fn tool_options_bar(app: &mut PainterApp, ctx: &egui::Context) {
+    egui::TopBottomPanel::top("tool_options").show(ctx, |ui| {
+        ui.horizontal(|ui| match app.active_tool {
+            Tool::Brush => brush_options(ui, app),
+            Tool::Select(current) => select_options(ui, app, current),
+            Tool::Transform(_) => transform_options(ui, app),
+            Tool::EditPath => path_options(ui, app),
+            Tool::Move => move_options(ui, app),
+            Tool::Eyedropper => eyedropper_options(ui, app),
+        });
+    });
+}
+
+fn brush_options(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let options = &mut app.brush.brush_options;
+    let mut changed = false;
+    ui.label(tr("Size"));
+    changed |= ui
+        .add(egui::Slider::new(&mut options.diameter, 1.0..=3000.0).logarithmic(true))
+        .changed();
+    ui.label(tr("Opacity"));
+    changed |= ui.add(egui::Slider::new(&mut options.opacity, 0.0..=1.0)).changed();
+    ui.label(tr("Flow"));
+    changed |= ui.add(egui::Slider::new(&mut options.flow, 0.0..=100.0)).changed();
+    if changed {
+        app.brush.is_changed = true;
+        app.brush_preview.dirty = true;
+    }
+}
+
+fn select_options(ui: &mut egui::Ui, app: &mut PainterApp, current: SelectionType) {
+    ui.label(tr("Mode"));
+    for (sel_type, label) in [
+        (SelectionType::Rectangle, "⬚ Rect"),
+        (SelectionType::Circle, "◯ Circle"),
+        (SelectionType::Lasso, "〰 Lasso"),
+        (SelectionType::Polygon, "⬠ Polygon"),
+    ] {
+        if ui.selectable_label(current == sel_type, tr(label)).clicked() {
+            app.active_tool = Tool::Select(sel_type);
+        }
+    }
+    ui.separator();
+    ui.label(tr("Feather"));
+    let mut feather = app.selection_manager.feather();
+    if ui.add(egui::Slider::new(&mut feather, 0.0..=100.0).suffix(" px")).changed() {
+        app.selection_manager.set_feather(feather);
+    }
+    if matches!(current, SelectionType::Rectangle | SelectionType::Circle) {
+        ui.separator();
+        pixel_snap_combo(ui, app);
+    }
+    ui.separator();
+    if ui
+        .add_enabled(app.selection_manager.has_selection(), egui::Button::new(tr("Deselect")))
+        .clicked()
+    {
+        app.selection_manager.clear_selection();
+    }
+}
+
+fn eyedropper_options(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let settings = &mut app.eyedropper;
+    ui.label(tr("Sample"));
+    egui::ComboBox::from_id_salt("eyedropper_size")
+        .selected_text(tr(settings.size.label()))
+        .show_ui(ui, |ui| {
+            for size in SampleSize::ALL {
+                ui.selectable_value(&mut settings.size, size, tr(size.label()));
+            }
+        });
+    ui.separator();
+    ui.checkbox(&mut settings.all_layers, tr("Sample all layers"))
+        .on_hover_text(tr("Pick the color shown on screen; untick to pick from the active layer only."));
+}
+
+fn move_options(ui: &mut egui::Ui, app: &PainterApp) {
+    let Some(layer) = app.canvas.layers.get(app.canvas.active_layer_idx) else {
+        return;
+    };
+    ui.label(trf("Offset: {}, {} px", &[&layer.offset.0, &layer.offset.1]));
+    ui.separator();
+    ui.label(tr("Drag to move the selected layers by whole pixels; the background stays put"));
+}
+
+fn path_options(ui: &mut egui::Ui, app: &mut PainterApp) {
+    let layer_idx = app.canvas.active_layer_idx;
+    let Some(vector) = app.canvas.layers.get_mut(layer_idx).and_then(|l| l.vector.as_mut()) else {
+        ui.label(tr("Pick a vector layer to edit its strokes"));
+        return;
+    };
+    ui.label(trf("{} strokes", &[&vector.strokes.len()]));
+    ui.separator();
+    let selected = app.path_selection.filter(|&(layer, _)| layer == layer_idx).map(|(_, stroke)| stroke);
+    let Some((idx, mut width)) = selected.and_then(|i| vector.strokes.get(i).map(|s| (i, s.width))) else {
+        ui.label(tr("Click a stroke to select it, drag its points to reshape it"));
+        return;
+    };
+    ui.label(tr("Width"));
+    let response = ui.add(egui::Slider::new(&mut width, WIDTH_RANGE).logarithmic(true).suffix("×"));
+    if response.changed() {
+        app.path_edit_before.get_or_insert_with(|| vector.strokes.clone());
+        vector.strokes[idx].width = width;
+    }
+    let mut commit = None;
+    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+        commit = app.path_edit_before.take().map(|before| (before, "Stroke width"));
+    }
+    if ui.button(tr("Delete Stroke")).clicked() {
+        let before = vector.strokes.clone();
+        vector.strokes.remove(idx);
+        app.path_selection = None;
+        commit = Some((before, "Delete stroke"));
+    }
+    if let Some((before, label)) = commit {
+        app.commit_vector_edit(before, label);
+    }
+}
+
+fn transform_options(ui: &mut egui::Ui, app: &mut PainterApp) {
+    ui.label(tr("Interpolation"));
+    egui::ComboBox::from_id_salt("transform_interpolation")
+        .selected_text(tr(app.transform_interpolation.label()))
+        .show_ui(ui, |ui| {
+            for mode in [Interpolation::Nearest, Interpolation::Bilinear] {
+                if ui.selectable_value(&mut app.transform_interpolation, mode, tr(mode.label())).changed() {
+                    app.transform_preview_pending = app.floating_layer_idx.is_some();
+                }
+            }
+        });
+    ui.separator();
+    pixel_snap_combo(ui, app);
+    ui.separator();
+    if let Tool::Transform(info) = &mut app.active_tool {
+        ui.label(trf(
+            "Offset {}, {}  Rotation {}°  Scale {} × {}",
+            &[
+                &format!("{:.0}", info.offset.x),
+                &format!("{:.0}", info.offset.y),
+                &format!("{:.1}", info.rotation.to_degrees()),
+                &format!("{:.2}", info.scale.x),
+                &format!("{:.2}", info.scale.y),
+            ],
+        ));
+        if ui.button(tr("Reset")).clicked() {
+            let bounds = info.bounds;
+            *info = TransformInfo { bounds, ..TransformInfo::default() };
+            app.transform_preview_pending = app.floating_layer_idx.is_some();
+        }
+    }
+    ui.separator();
+    let floating = app.floating_layer_idx.is_some();
+    if ui
+        .add_enabled(floating, egui::Button::new(tr("✔ Apply")))
+        .on_hover_text(tr("Place the transformed pixels (Enter)"))
+        .clicked()
+    {
+        app.commit_floating();
+    }
+    if ui
+        .add_enabled(floating, egui::Button::new(tr("✖ Cancel")))
+        .on_hover_text(tr("Put the pixels back where they were (Escape)"))
+        .clicked()
+    {
+        app.cancel_floating();
+    }
+    if floating {
+        ui.weak(tr("Switching tools also applies"));
+    }
+}
+
+/// Grid that new rectangle and circle selections and transform moves snap to.
+fn pixel_snap_combo(ui: &mut egui::Ui, app: &mut PainterApp) {
+    ui.label(tr("Snap"));
+    let snap = &mut app.selection_manager.pixel_snap;
+    egui::ComboBox::from_id_salt("pixel_snap")
+        .selected_text(tr(snap.label()))
+        .show_ui(ui, |ui| {
+            for mode in PixelSnap::ALL {
+                ui.selectable_value(snap, mode, tr(mode.label()));
+            }
+        })
+        .response
+        .on_hover_text(tr("Keep selection edges and moves on the pixel grid"));
+}
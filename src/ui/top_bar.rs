@@ -1,12 +1,153 @@
 use crate::PainterApp;
-use crate::app::tools::Tool;
+use crate::app::keybindings::{Command, Keybindings};
+use crate::app::recent_files;
+use crate::app::tools::{Tool, ToolKey};
 use crate::selection::SelectionType;
+use crate::ui::background_color::BackgroundColorDialog;
+use crate::ui::export_modal::ExportScope;
+use crate::ui::trim::TrimDialog;
+use crate::utils::color::ColorDeficiency;
+use crate::utils::i18n::{tr, trf};
 use eframe::egui;
 
 pub fn top_bar(app: &mut PainterApp, ctx: &egui::Context) {
     egui::TopBottomPanel::top("quick_settings").show(ctx, |ui| {
         ui.horizontal(|ui| {
-            ui.selectable_value(&mut app.active_tool, Tool::Brush, "🖌 Brush");
+            ui.menu_button(tr("File"), |ui| {
+                if ui.button(tr("New Canvas...")).clicked() {
+                    app.new_canvas.sync_from_canvas(&app.canvas);
+                    app.new_canvas.color_model = app.color_model;
+                    app.show_new_canvas_modal = true;
+                    ui.close_menu();
+                }
+                if ui.button(tr("Open Image...")).clicked() {
+                    ui.close_menu();
+                    app.open_image_dialog(ctx);
+                }
+                ui.add_enabled_ui(!app.recent_files.entries().is_empty(), |ui| {
+                    ui.menu_button(tr("Open Recent"), |ui| recent_files_menu(app, ctx, ui));
+                });
+                ui.separator();
+                if ui.button(tr("Export...")).clicked() {
+                    app.open_export(ExportScope::Canvas);
+                    ui.close_menu();
+                }
+                if ui.button(tr("Document Properties...")).clicked() {
+                    app.show_document_properties = true;
+                    ui.close_menu();
+                }
+                if ui.button(tr("Statistics...")).clicked() {
+                    app.show_statistics = true;
+                    ui.close_menu();
+                }
+            });
+
+            ui.menu_button(tr("Edit"), |ui| {
+                for (label, command) in [
+                    ("Undo", Command::Undo),
+                    ("Redo", Command::Redo),
+                    ("Clear Layer", Command::ClearLayer),
+                    ("Deselect", Command::Deselect),
+                    ("Copy Merged", Command::CopyMerged),
+                    ("Paste Image", Command::PasteImage),
+                ] {
+                    let mut button = egui::Button::new(tr(label));
+                    if let Some(shortcut) = app.keybindings.shortcut_text(ctx, command) {
+                        button = button.shortcut_text(shortcut);
+                    }
+                    if ui.add(button).clicked() {
+                        app.run_command(command);
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                if ui
+                    .add_enabled(app.selection_manager.has_selection(), egui::Button::new(tr("Stroke Selection...")))
+                    .clicked()
+                {
+                    app.stroke_selection.color = app.brush.brush_options.color;
+                    app.show_stroke_selection = true;
+                    ui.close_menu();
+                }
+                let mut export_selection = egui::Button::new(tr("Export Selection..."));
+                if let Some(shortcut) = app.keybindings.shortcut_text(ctx, Command::ExportSelection) {
+                    export_selection = export_selection.shortcut_text(shortcut);
+                }
+                if ui.add_enabled(app.selection_manager.has_selection(), export_selection).clicked() {
+                    app.run_command(Command::ExportSelection);
+                    ui.close_menu();
+                }
+                if ui.button(tr("Background Color...")).clicked() {
+                    app.background_dialog = Some(BackgroundColorDialog::new(app.canvas.clear_color()));
+                    ui.close_menu();
+                }
+            });
+
+            ui.menu_button(tr("Image"), |ui| {
+                if ui
+                    .add_enabled(app.can_trim(), egui::Button::new(tr("Trim...")))
+                    .on_disabled_hover_text(tr("Animations can't be trimmed, since undoing a trim couldn't restore the other frames"))
+                    .clicked()
+                {
+                    app.bake_layer_offsets();
+                    app.trim_dialog = Some(TrimDialog::new(app));
+                    ui.close_menu();
+                }
+            });
+
+            ui.menu_button(tr("View"), |ui| {
+                let mut recenter = egui::Button::new(tr("Recenter Canvas"));
+                if let Some(shortcut) = app.keybindings.shortcut_text(ctx, Command::RecenterView) {
+                    recenter = recenter.shortcut_text(shortcut);
+                }
+                if ui.add(recenter).clicked() {
+                    app.run_command(Command::RecenterView);
+                    ui.close_menu();
+                }
+                ui.checkbox(&mut app.show_favorites_bar, tr("Favorites Bar"));
+                ui.checkbox(&mut app.view_settings.wrap_preview, tr("Wrap Preview (Offset 50%)"))
+                    .on_hover_text(tr("Roll the view by half the canvas to check tileable seams"));
+                ui.checkbox(&mut app.view_settings.show_rulers, tr("Rulers"));
+                ui.menu_button(tr("Overlay Guides"), |ui| {
+                    crate::ui::overlay_guides::overlay_guides_toggles(ui, &mut app.view_settings.overlays);
+                });
+                if ui.add_enabled(!app.guides.is_empty(), egui::Button::new(tr("Clear Guides"))).clicked() {
+                    app.guides.clear();
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(!app.floating_panels.is_empty(), egui::Button::new(tr("Dock Floating Panels")))
+                    .clicked()
+                {
+                    for panel in app.floating_panels.clone() {
+                        crate::app::layout::dock_panel(app, panel);
+                    }
+                    ui.close_menu();
+                }
+                ui.menu_button(tr("Color Deficiency Preview"), |ui| {
+                    for mode in ColorDeficiency::ALL {
+                        let selected = app.view_settings.color_deficiency == mode;
+                        if ui.radio(selected, tr(mode.label())).clicked() {
+                            app.set_color_deficiency(mode);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+
+            ui.menu_button(tr("Filters"), |ui| {
+                if ui.button(tr("Gradient Map...")).clicked() {
+                    crate::ui::filters::open_filter(app, crate::ui::filters::Filter::gradient_map());
+                    ui.close_menu();
+                }
+                if ui.button(tr("Add Noise...")).clicked() {
+                    crate::ui::filters::open_filter(app, crate::ui::filters::Filter::Noise(Default::default()));
+                    ui.close_menu();
+                }
+            });
+
+            ui.selectable_value(&mut app.active_tool, Tool::Brush, tr("🖌 Brush"))
+                .on_hover_text(tool_key_hint(&app.keybindings, ToolKey::Brush));
 
             let is_select = matches!(app.active_tool, Tool::Select(_));
             let current_select_type = if let Tool::Select(t) = app.active_tool {
@@ -16,20 +157,21 @@ pub fn top_bar(app: &mut PainterApp, ctx: &egui::Context) {
             };
 
             ui.menu_button(
-                if is_select {
+                tr(if is_select {
                     match current_select_type {
                         SelectionType::Rectangle => "⬚ Rect",
                         SelectionType::Circle => "◯ Circle",
                         SelectionType::Lasso => "〰 Lasso",
+                        SelectionType::Polygon => "⬠ Polygon",
                     }
                 } else {
                     "Select"
-                },
+                }),
                 |ui| {
                     if ui
                         .selectable_label(
                             is_select && current_select_type == SelectionType::Rectangle,
-                            "Rectangle",
+                            tr("Rectangle"),
                         )
                         .clicked()
                     {
@@ -39,7 +181,7 @@ pub fn top_bar(app: &mut PainterApp, ctx: &egui::Context) {
                     if ui
                         .selectable_label(
                             is_select && current_select_type == SelectionType::Circle,
-                            "Circle",
+                            tr("Circle"),
                         )
                         .clicked()
                     {
@@ -49,32 +191,51 @@ pub fn top_bar(app: &mut PainterApp, ctx: &egui::Context) {
                     if ui
                         .selectable_label(
                             is_select && current_select_type == SelectionType::Lasso,
-                            "Lasso",
+                            tr("Lasso"),
                         )
                         .clicked()
                     {
                         app.active_tool = Tool::Select(SelectionType::Lasso);
                         ui.close_menu();
                     }
+                    if ui
+                        .selectable_label(
+                            is_select && current_select_type == SelectionType::Polygon,
+                            tr("Polygon"),
+                        )
+                        .clicked()
+                    {
+                        app.active_tool = Tool::Select(SelectionType::Polygon);
+                        ui.close_menu();
+                    }
                 },
-            );
+            )
+            .response
+            .on_hover_text(tool_key_hint(&app.keybindings, ToolKey::Select));
 
-            if ui.selectable_label(matches!(app.active_tool, Tool::Transform(_)), "Transform").clicked() {
+            if ui
+                .selectable_label(matches!(app.active_tool, Tool::Transform(_)), tr("Transform"))
+                .on_hover_text(tool_key_hint(&app.keybindings, ToolKey::Transform))
+                .clicked()
+            {
                 app.active_tool = Tool::Transform(crate::selection::transform::TransformInfo::default());
             }
+            ui.selectable_value(&mut app.active_tool, Tool::EditPath, tr("✒ Path"))
+                .on_hover_text(tool_key_hint(&app.keybindings, ToolKey::Path));
+            ui.selectable_value(&mut app.active_tool, Tool::Move, tr("✋ Move"))
+                .on_hover_text(tool_key_hint(&app.keybindings, ToolKey::Move));
+            ui.selectable_value(&mut app.active_tool, Tool::Eyedropper, tr("💧 Eyedropper"))
+                .on_hover_text(tool_key_hint(&app.keybindings, ToolKey::Eyedropper));
 
-            if ui.button("New Canvas").clicked() {
+            if ui.button(tr("New Canvas")).clicked() {
                 app.new_canvas.sync_from_canvas(&app.canvas);
                 app.new_canvas.color_model = app.color_model;
                 app.show_new_canvas_modal = true;
             }
-            ui.add(egui::Slider::new(&mut app.brush.brush_options.diameter, 1.0..=3000.0));
-            if ui.button("Export").clicked() {
-                app.export_settings.chosen_path = None;
-                app.export_message = None;
-                app.show_export_modal = true;
+            if ui.button(tr("Export")).clicked() {
+                app.open_export(ExportScope::Canvas);
             }
-            if ui.button("Settings").clicked() {
+            if ui.button(tr("Settings")).clicked() {
                 app.show_general_settings = true;
                 ctx.request_repaint();
             }
@@ -82,3 +243,35 @@ pub fn top_bar(app: &mut PainterApp, ctx: &egui::Context) {
         });
     });
 }
+
+/// Hover text naming the key that switches to a tool.
+fn tool_key_hint(keybindings: &Keybindings, tool: ToolKey) -> String {
+    match keybindings.key(Command::SwitchTool(tool)) {
+        Some(key) => trf("{}: tap to switch, hold to use until released", &[&key.name()]),
+        None => String::new(),
+    }
+}
+
+/// Recent files with their thumbnails; clicking one opens it.
+fn recent_files_menu(app: &mut PainterApp, ctx: &egui::Context, ui: &mut egui::Ui) {
+    let thumb_size = recent_files::THUMBNAIL_SIZE as f32 / 2.0;
+    for path in app.recent_files.entries().to_vec() {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let button = match app.recent_files.thumbnail(ctx, &path) {
+            Some(texture) => {
+                let size = texture.size_vec2() * (thumb_size / texture.size_vec2().max_elem());
+                egui::Button::image_and_text((texture.id(), size), name)
+            }
+            None => egui::Button::new(name),
+        };
+        if ui.add(button).on_hover_text(path.display().to_string()).clicked() {
+            ui.close_menu();
+            app.open_image(ctx, &path);
+        }
+    }
+    ui.separator();
+    if ui.button(tr("Clear Recent")).clicked() {
+        app.recent_files.clear();
+        ui.close_menu();
+    }
+}
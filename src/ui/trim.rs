@@ -0,0 +1,60 @@
+use crate::PainterApp;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+/// Pending settings in the Trim dialog.
+pub struct TrimDialog {
+    /// Pixels kept around the paint on each side.
+    pub padding: usize,
+    /// Paint bounds of the visible layers when the dialog opened.
+    bounds: Option<egui::Rect>,
+}
+
+impl TrimDialog {
+    pub fn new(app: &PainterApp) -> Self {
+        Self { padding: 0, bounds: app.canvas.visible_content_bounds() }
+    }
+}
+
+/// Dialog that crops the canvas to the paint on the visible layers as one undo step.
+pub fn trim_modal(app: &mut PainterApp, ctx: &egui::Context) {
+    // Frames can be added while the dialog is open
+    let trimmable = app.can_trim();
+    let Some(dialog) = &mut app.trim_dialog else {
+        return;
+    };
+
+    let canvas_rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(app.canvas.width() as f32, app.canvas.height() as f32));
+    let mut open = true;
+    let mut apply = false;
+    egui::Window::new(tr("Trim"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            let Some(bounds) = dialog.bounds else {
+                ui.label(tr("The visible layers are empty."));
+                return;
+            };
+            ui.add(egui::Slider::new(&mut dialog.padding, 0..=512).text(tr("Padding")));
+            let result = bounds.expand(dialog.padding as f32).intersect(canvas_rect);
+            ui.label(trf("New size: {} × {} px", &[&result.width(), &result.height()]));
+            ui.separator();
+            if !trimmable {
+                ui.label(tr("Animations can't be trimmed, since undoing a trim couldn't restore the other frames"));
+            }
+            if ui.add_enabled(trimmable && result != canvas_rect, egui::Button::new(tr("Trim"))).clicked() {
+                apply = true;
+            }
+        });
+
+    if apply {
+        let padding = dialog.padding;
+        app.trim_canvas(padding);
+        open = false;
+    }
+    if !open {
+        app.trim_dialog = None;
+    }
+}
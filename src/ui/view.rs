@@ -0,0 +1,69 @@
+use crate::PainterApp;
+use crate::app::state::{CanvasUnit, ROTATION_SNAP_DEGREES};
+use crate::utils::color::ColorDeficiency;
+use crate::utils::i18n::{tr, trf};
+use eframe::egui;
+
+/// Dock tab with the viewport rotation controls and display previews.
+pub fn view_panel(ui: &mut egui::Ui, app: &mut PainterApp) {
+    // Show the angle in (-180, 180] regardless of how far the view was spun
+    let mut degrees = app.rotation.to_degrees().rem_euclid(360.0);
+    if degrees > 180.0 {
+        degrees -= 360.0;
+    }
+    ui.horizontal(|ui| {
+        ui.label(tr("Rotation"));
+        let changed = ui
+            .add(egui::DragValue::new(&mut degrees).range(-180.0..=180.0).speed(1.0).suffix("°"))
+            .changed();
+        if changed {
+            app.rotation = degrees.to_radians();
+        }
+        if ui.button(tr("Reset")).on_hover_text(tr("Rotate the view back to 0°")).clicked() {
+            app.rotation = 0.0;
+        }
+    });
+    ui.checkbox(&mut app.view_settings.snap_rotation, trf("Snap rotation to {}°", &[&ROTATION_SNAP_DEGREES]))
+        .on_hover_text(tr("Applies while rotating with the middle mouse button."));
+    ui.checkbox(&mut app.view_settings.wrap_preview, tr("Wrap preview (offset 50%)"))
+        .on_hover_text(tr("Show the canvas rolled by half its width and height so tileable seams meet in the middle. Pixels are not changed; painting is paused while it is on."));
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut app.view_settings.show_rulers, tr("Rulers"));
+        egui::ComboBox::from_id_salt("ruler_unit")
+            .selected_text(tr(app.view_settings.ruler_unit.label()))
+            .show_ui(ui, |ui| {
+                for unit in CanvasUnit::ALL {
+                    ui.selectable_value(&mut app.view_settings.ruler_unit, unit, tr(unit.label()));
+                }
+            });
+    });
+    ui.label(trf("Document resolution: {} dpi", &[&format!("{:.0}", app.document_dpi)]))
+        .on_hover_text(tr("Physical units on the rulers are converted with this resolution."));
+    ui.horizontal(|ui| {
+        ui.label(trf("Guides: {}", &[&app.guides.len()]));
+        if ui.add_enabled(!app.guides.is_empty(), egui::Button::new(tr("Clear"))).clicked() {
+            app.guides.clear();
+        }
+    });
+    ui.label(egui::RichText::new(tr("Drag from a ruler to add a guide, drag it back to remove it.")).weak());
+
+    ui.separator();
+    ui.label(tr("Overlay guides"));
+    crate::ui::overlay_guides::overlay_guides_settings(ui, &mut app.view_settings.overlays);
+
+    ui.separator();
+    let mut mode = app.view_settings.color_deficiency;
+    ui.horizontal(|ui| {
+        ui.label(tr("Color deficiency"));
+        egui::ComboBox::from_id_salt("color_deficiency")
+            .selected_text(tr(mode.label()))
+            .show_ui(ui, |ui| {
+                for m in ColorDeficiency::ALL {
+                    ui.selectable_value(&mut mode, m, tr(m.label()));
+                }
+            });
+    });
+    app.set_color_deficiency(mode);
+}
@@ -0,0 +1,27 @@
+//! Access to images on the system clipboard.
+use eframe::egui::{Color32, ColorImage};
+
+/// Read the image currently on the system clipboard, if any.
+pub fn read_image() -> Result<ColorImage, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    let image = clipboard.get_image().map_err(|e| e.to_string())?;
+    if image.bytes.len() != image.width * image.height * 4 {
+        return Err("Unexpected clipboard image layout".to_string());
+    }
+
+    // Clipboard pixels are straight-alpha RGBA
+    let pixels = image
+        .bytes
+        .chunks_exact(4)
+        .map(|p| Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3]))
+        .collect();
+    Ok(ColorImage { size: [image.width, image.height], pixels })
+}
+
+/// Put an image on the system clipboard, replacing what was there.
+pub fn write_image(image: &ColorImage) -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(|px| px.to_srgba_unmultiplied()).collect();
+    let data = arboard::ImageData { width: image.size[0], height: image.size[1], bytes: bytes.into() };
+    clipboard.set_image(data).map_err(|e| e.to_string())
+}
@@ -1,5 +1,6 @@
-use eframe::egui::Color32;
+use eframe::egui::{Color32, Rgba};
 
+#[allow(dead_code)]
 pub type Color = Color32;
 
 pub trait ColorManipulation {
@@ -9,7 +10,11 @@ pub trait ColorManipulation {
     fn from_hsva(h: f32, s: f32, v: f32, a: f32) -> Self;
     fn to_hsva(self) -> (f32, f32, f32, f32);
 
+    fn from_hsla(h: f32, s: f32, l: f32, a: f32) -> Self;
+    fn to_hsla(self) -> (f32, f32, f32, f32);
+
     fn from_gray_alpha(value: f32, a: f32) -> Self;
+    #[allow(dead_code)]
     fn to_color32(self) -> Color32;
 }
 
@@ -115,6 +120,26 @@ impl ColorManipulation for Color32 {
         (h, s, v, a as f32 / 255.0)
     }
 
+    fn from_hsla(h: f32, s: f32, l: f32, a: f32) -> Self {
+        let s = s.clamp(0.0, 1.0);
+        let l = l.clamp(0.0, 1.0);
+        // HSL and HSV share the hue; only the saturation/brightness parametrisation differs
+        let v = l + s * l.min(1.0 - l);
+        let sv = if v <= f32::EPSILON { 0.0 } else { 2.0 * (1.0 - l / v) };
+        Self::from_hsva(h, sv, v, a)
+    }
+
+    fn to_hsla(self) -> (f32, f32, f32, f32) {
+        let (h, sv, v, a) = self.to_hsva();
+        let l = v * (1.0 - sv / 2.0);
+        let s = if l <= f32::EPSILON || l >= 1.0 - f32::EPSILON {
+            0.0
+        } else {
+            (v - l) / l.min(1.0 - l)
+        };
+        (h, s, l, a)
+    }
+
     fn from_gray_alpha(value: f32, a: f32) -> Self {
         let v = clamp_to_u8(value);
         Color32::from_rgba_unmultiplied(v, v, v, clamp_to_u8(a))
@@ -124,3 +149,235 @@ impl ColorManipulation for Color32 {
         self
     }
 }
+
+/// Classic color wheel schemes built from one base hue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Harmony {
+    Complementary,
+    Analogous,
+    Triadic,
+    SplitComplementary,
+}
+
+impl Harmony {
+    pub const ALL: [Harmony; 4] = [
+        Harmony::Complementary,
+        Harmony::Analogous,
+        Harmony::Triadic,
+        Harmony::SplitComplementary,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Harmony::Complementary => "Complementary",
+            Harmony::Analogous => "Analogous",
+            Harmony::Triadic => "Triadic",
+            Harmony::SplitComplementary => "Split-complementary",
+        }
+    }
+
+    /// Offsets of the other members of the scheme from the base hue, in turns (0..1).
+    pub fn hue_offsets(self) -> &'static [f32] {
+        match self {
+            Harmony::Complementary => &[0.5],
+            Harmony::Analogous => &[-1.0 / 12.0, 1.0 / 12.0],
+            Harmony::Triadic => &[1.0 / 3.0, 2.0 / 3.0],
+            Harmony::SplitComplementary => &[5.0 / 12.0, 7.0 / 12.0],
+        }
+    }
+
+    /// The hues of the scheme, starting with `hue` itself.
+    pub fn hues(self, hue: f32) -> Vec<f32> {
+        std::iter::once(hue)
+            .chain(self.hue_offsets().iter().map(|offset| (hue + offset).rem_euclid(1.0)))
+            .collect()
+    }
+}
+
+/// Color space the warmer/cooler and lighter/darker nudges work in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NudgeSpace {
+    /// Turns the hue towards orange or blue and changes the HSV value.
+    Hsv,
+    /// Shifts towards yellow-red or blue in CIELAB and changes the perceptual lightness.
+    Lab,
+}
+
+impl NudgeSpace {
+    pub const ALL: [NudgeSpace; 2] = [NudgeSpace::Hsv, NudgeSpace::Lab];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NudgeSpace::Hsv => "HSV",
+            NudgeSpace::Lab => "Lab",
+        }
+    }
+}
+
+/// Step sizes for nudging the brush color without opening the picker.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorNudge {
+    pub space: NudgeSpace,
+    /// Fraction of the hue circle (HSV) or of the b* range (Lab) moved per nudge.
+    pub temperature_step: f32,
+    /// Fraction of the value (HSV) or L* (Lab) range moved per nudge.
+    pub lightness_step: f32,
+}
+
+impl Default for ColorNudge {
+    fn default() -> Self {
+        Self {
+            space: NudgeSpace::Hsv,
+            temperature_step: 0.03,
+            lightness_step: 0.05,
+        }
+    }
+}
+
+/// Hues the temperature nudges head for, in turns.
+const WARM_HUE: f32 = 30.0 / 360.0;
+const COOL_HUE: f32 = 210.0 / 360.0;
+
+impl ColorNudge {
+    /// Apply `warmth` temperature steps (negative for cooler) and `lightness` lightness steps
+    /// (negative for darker). Opacity is kept.
+    pub fn apply(&self, color: Color32, warmth: f32, lightness: f32) -> Color32 {
+        match self.space {
+            NudgeSpace::Hsv => {
+                let (mut h, s, mut v, a) = color.to_hsva();
+                if warmth != 0.0 {
+                    let target = if warmth > 0.0 { WARM_HUE } else { COOL_HUE };
+                    // Shortest way round the wheel, stopping at the target
+                    let diff = (target - h + 0.5).rem_euclid(1.0) - 0.5;
+                    let step = (self.temperature_step * warmth.abs()).min(diff.abs());
+                    h = (h + step * diff.signum()).rem_euclid(1.0);
+                }
+                v = (v + self.lightness_step * lightness).clamp(0.0, 1.0);
+                Color32::from_hsva(h, s, v, a)
+            }
+            NudgeSpace::Lab => {
+                let [mut l, mut a_star, mut b_star] = srgb_to_lab(color);
+                // Warm is mostly yellow with a little red; cool mirrors it
+                b_star += self.temperature_step * warmth * 100.0;
+                a_star += self.temperature_step * warmth * 25.0;
+                l = (l + self.lightness_step * lightness * 100.0).clamp(0.0, 100.0);
+                lab_to_srgb([l, a_star, b_star], color.a())
+            }
+        }
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    clamp_to_u8(c)
+}
+
+/// D65 reference white.
+const WHITE_XYZ: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+/// CIELAB (D65) of an sRGB color, ignoring alpha.
+pub fn srgb_to_lab(color: Color32) -> [f32; 3] {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+    let xyz = [
+        0.4124 * r + 0.3576 * g + 0.1805 * b,
+        0.2126 * r + 0.7152 * g + 0.0722 * b,
+        0.0193 * r + 0.1192 * g + 0.9505 * b,
+    ];
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let [fx, fy, fz] = [0, 1, 2].map(|i| f(xyz[i] / WHITE_XYZ[i]));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// sRGB color for a CIELAB (D65) value, clipped to the gamut.
+pub fn lab_to_srgb(lab: [f32; 3], alpha: u8) -> Color32 {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+    let f_inv = |t: f32| if t.powi(3) > 0.008856 { t.powi(3) } else { (t - 16.0 / 116.0) / 7.787 };
+    let [x, y, z] = [fx, fy, fz].map(f_inv);
+    let (x, y, z) = (x * WHITE_XYZ[0], y * WHITE_XYZ[1], z * WHITE_XYZ[2]);
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    Color32::from_rgba_unmultiplied(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), alpha)
+}
+
+/// Display-only simulation of color vision deficiencies, for checking palette readability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorDeficiency {
+    #[default]
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+    /// Luminance only, to check value contrast.
+    Grayscale,
+}
+
+impl ColorDeficiency {
+    pub const ALL: [ColorDeficiency; 5] = [
+        ColorDeficiency::None,
+        ColorDeficiency::Protanopia,
+        ColorDeficiency::Deuteranopia,
+        ColorDeficiency::Tritanopia,
+        ColorDeficiency::Grayscale,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorDeficiency::None => "Off",
+            ColorDeficiency::Protanopia => "Protanopia (no red cones)",
+            ColorDeficiency::Deuteranopia => "Deuteranopia (no green cones)",
+            ColorDeficiency::Tritanopia => "Tritanopia (no blue cones)",
+            ColorDeficiency::Grayscale => "Grayscale (luminance)",
+        }
+    }
+
+    /// Linear RGB transform; the dichromacy matrices are Machado et al. (2009) at full severity.
+    fn matrix(self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorDeficiency::None => None,
+            ColorDeficiency::Protanopia => Some([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ]),
+            ColorDeficiency::Deuteranopia => Some([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ]),
+            ColorDeficiency::Tritanopia => Some([
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ]),
+            ColorDeficiency::Grayscale => Some([[0.2126, 0.7152, 0.0722]; 3]),
+        }
+    }
+
+    /// Simulate the deficiency on premultiplied pixels in place.
+    pub fn apply(self, pixels: &mut [Color32]) {
+        let Some(m) = self.matrix() else {
+            return;
+        };
+        for px in pixels.iter_mut() {
+            if px.a() == 0 {
+                continue;
+            }
+            // The transform is linear, so it can run on premultiplied values directly
+            let c = Rgba::from(*px);
+            let a = c.a();
+            let rgb = [c.r(), c.g(), c.b()];
+            let out = m.map(|row| (row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]).clamp(0.0, a));
+            *px = Color32::from(Rgba::from_rgba_premultiplied(out[0], out[1], out[2], a));
+        }
+    }
+}
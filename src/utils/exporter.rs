@@ -1,7 +1,13 @@
+use crate::app::document_info::DocumentInfo;
 use crate::canvas::canvas::Canvas;
+use crate::canvas::palette::{MAX_COLORS, Palette};
+use crate::selection::SelectionManager;
+use crate::utils::vector::Vec2;
 use eframe::egui::Color32;
 use eframe::egui::ColorImage;
 use image::ImageFormat;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -48,6 +54,44 @@ pub fn export_canvas(canvas: &Canvas, path: &Path, format: ExportFormat) -> Resu
     save_color_image(img, path, format)
 }
 
+/// Composite the pixels inside the selection, trimmed to the area it covers.
+///
+/// Alpha is scaled by the selection's anti-aliased (and feathered) coverage.
+/// Returns `None` when there is no selection or it lies outside the canvas.
+pub fn selection_image(canvas: &Canvas, selection: &SelectionManager) -> Option<ColorImage> {
+    let (min, max) = selection.bounds(1.0)?;
+    let x0 = min.x.floor().max(0.0) as usize;
+    let y0 = min.y.floor().max(0.0) as usize;
+    let x1 = (max.x.ceil().max(0.0) as usize).min(canvas.width());
+    let y1 = (max.y.ceil().max(0.0) as usize).min(canvas.height());
+    if x0 >= x1 || y0 >= y1 {
+        return None;
+    }
+
+    let (w, h) = (x1 - x0, y1 - y0);
+    let coverage: Vec<f32> = (0..w * h)
+        .map(|i| selection.coverage(Vec2::new((x0 + i % w) as f32 + 0.5, (y0 + i / w) as f32 + 0.5)))
+        .collect();
+
+    // Trim to the pixels the selection actually reaches
+    let covered = |x: usize, y: usize| coverage[y * w + x] > 0.0;
+    let rows: Vec<usize> = (0..h).filter(|&y| (0..w).any(|x| covered(x, y))).collect();
+    let cols: Vec<usize> = (0..w).filter(|&x| (0..h).any(|y| covered(x, y))).collect();
+    let (&top, &bottom) = (rows.first()?, rows.last()?);
+    let (&left, &right) = (cols.first()?, cols.last()?);
+
+    let (out_w, out_h) = (right - left + 1, bottom - top + 1);
+    let mut img = ColorImage::new([out_w, out_h], Color32::TRANSPARENT);
+    canvas.write_region_to_color_image(x0 + left, y0 + top, out_w, out_h, &mut img, 1);
+    for y in 0..out_h {
+        for x in 0..out_w {
+            let px = &mut img.pixels[y * out_w + x];
+            *px = px.gamma_multiply(coverage[(top + y) * w + left + x]);
+        }
+    }
+    Some(img)
+}
+
 /// Save a precomputed color image to disk.
 pub fn save_color_image(
     img: ColorImage,
@@ -71,3 +115,252 @@ pub fn save_color_image(
     rgba.save_with_format(path, format.image_format())
         .map_err(|e| e.to_string())
 }
+
+/// Save a color image with the document properties embedded: text chunks in a PNG, an EXIF
+/// block in a JPEG. TIFF files are written without them.
+pub fn save_color_image_with_info(
+    img: ColorImage,
+    path: impl Into<PathBuf>,
+    format: ExportFormat,
+    info: &DocumentInfo,
+) -> Result<(), String> {
+    let path = path.into();
+    match format {
+        ExportFormat::PNG => {
+            let [width, height] = img.size;
+            let mut writer = rgba_png_writer(&path, width, height, info)?;
+            writer.write_image_data(&rgba_bytes(&img)).map_err(|e| e.to_string())
+        }
+        ExportFormat::JPEG => {
+            use image::ImageEncoder;
+            let [width, height] = img.size;
+            let rgba = image::RgbaImage::from_raw(width as u32, height as u32, rgba_bytes(&img))
+                .ok_or_else(|| "Failed to build RGBA image".to_string())?;
+            let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+            let mut encoder = image::codecs::jpeg::JpegEncoder::new(std::io::BufWriter::new(file));
+            encoder.set_exif_metadata(info.exif()).map_err(|e| e.to_string())?;
+            image::DynamicImage::ImageRgba8(rgba)
+                .write_with_encoder(encoder)
+                .map_err(|e| e.to_string())
+        }
+        ExportFormat::TIFF => save_color_image(img, path, format),
+    }
+}
+
+/// An 8-bit RGBA PNG file with the document properties, ready for its pixel rows.
+fn rgba_png_writer(path: &Path, width: usize, height: usize, info: &DocumentInfo) -> Result<png::Writer<BufWriter<File>>, String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    add_text_chunks(&mut encoder, info)?;
+    encoder.write_header().map_err(|e| e.to_string())
+}
+
+/// Rows of the canvas composited and encoded at a time by a streamed PNG export.
+pub const EXPORT_BAND_ROWS: usize = 256;
+
+/// Flatten `rows` canvas rows starting at `y` into unpremultiplied RGBA bytes, one band of
+/// a streamed export.
+pub fn composite_band(canvas: &Canvas, y: usize, rows: usize) -> Vec<u8> {
+    let mut img = ColorImage::new([canvas.width(), rows], Color32::TRANSPARENT);
+    canvas.write_region_to_color_image(0, y, canvas.width(), rows, &mut img, 1);
+    rgba_bytes(&img)
+}
+
+/// Encode a PNG from bands of RGBA rows, top to bottom, as they arrive, so exporting a large
+/// canvas never holds the whole flattened image. `progress` gets the fraction of rows written
+/// after each band.
+pub fn save_png_bands(
+    path: &Path,
+    width: usize,
+    height: usize,
+    info: &DocumentInfo,
+    bands: impl IntoIterator<Item = Vec<u8>>,
+    mut progress: impl FnMut(f32),
+) -> Result<(), String> {
+    let mut stream = rgba_png_writer(path, width, height, info)?.into_stream_writer().map_err(|e| e.to_string())?;
+    let total = width * height * 4;
+    let mut written = 0;
+    for band in bands {
+        stream.write_all(&band).map_err(|e| e.to_string())?;
+        written += band.len();
+        progress(written as f32 / total as f32);
+    }
+    if written != total {
+        return Err("The export stopped before the last row".to_string());
+    }
+    stream.finish().map_err(|e| e.to_string())
+}
+
+/// Latin-1 text goes in `tEXt` chunks, anything else in UTF-8 `iTXt` chunks.
+fn add_text_chunks<W: std::io::Write>(encoder: &mut png::Encoder<W>, info: &DocumentInfo) -> Result<(), String> {
+    for (keyword, text) in info.text_chunks() {
+        let added = if text.chars().all(|c| (c as u32) < 256) {
+            encoder.add_text_chunk(keyword.to_string(), text)
+        } else {
+            encoder.add_itxt_chunk(keyword.to_string(), text)
+        };
+        added.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Save the image as an 8-bit indexed PNG using the document palette.
+///
+/// Pixels are snapped to the nearest palette entry; when any pixel is mostly
+/// transparent an extra fully transparent entry is appended for it.
+pub fn save_indexed_png(img: ColorImage, path: impl Into<PathBuf>, palette: &Palette, info: &DocumentInfo) -> Result<(), String> {
+    if palette.colors.is_empty() {
+        return Err("The document palette is empty".to_string());
+    }
+    let width = img.size[0];
+    let height = img.size[1];
+
+    let transparent_index = palette.colors.len();
+    let mut has_transparency = false;
+    let indices: Vec<u8> = img
+        .pixels
+        .iter()
+        .map(|px| {
+            let [r, g, b, a] = px.to_srgba_unmultiplied();
+            if a < 128 {
+                has_transparency = true;
+                transparent_index as u8
+            } else {
+                palette.nearest_index(r, g, b).unwrap_or(0) as u8
+            }
+        })
+        .collect();
+    if has_transparency && transparent_index >= MAX_COLORS {
+        return Err(format!("Indexed PNG with transparency needs at most {} palette colors", MAX_COLORS - 1));
+    }
+
+    let mut plte: Vec<u8> = palette.colors.iter().flat_map(|c| [c.r(), c.g(), c.b()]).collect();
+    let file = std::fs::File::create(path.into()).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    if has_transparency {
+        plte.extend_from_slice(&[0, 0, 0]);
+        // Only the trailing entry is transparent
+        let mut trns = vec![255u8; transparent_index];
+        trns.push(0);
+        encoder.set_trns(trns);
+    }
+    encoder.set_palette(plte);
+    add_text_chunks(&mut encoder, info)?;
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&indices).map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    PngSequence,
+}
+
+impl AnimationFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "GIF",
+            AnimationFormat::Apng => "APNG",
+            AnimationFormat::PngSequence => "PNG sequence",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng | AnimationFormat::PngSequence => "png",
+        }
+    }
+}
+
+/// Save animation frames played at `fps`, calling `progress` with the finished fraction after each frame.
+/// With `looping` off, GIF and APNG play once and stop on the last frame.
+pub fn save_animation(
+    frames: &[ColorImage],
+    path: &Path,
+    format: AnimationFormat,
+    fps: f32,
+    looping: bool,
+    progress: impl FnMut(f32),
+) -> Result<(), String> {
+    if frames.is_empty() {
+        return Err("There are no frames to export".to_string());
+    }
+    match format {
+        AnimationFormat::Gif => save_gif(frames, path, fps, looping, progress),
+        AnimationFormat::Apng => save_apng(frames, path, fps, looping, progress),
+        AnimationFormat::PngSequence => save_png_sequence(frames, path, progress),
+    }
+}
+
+/// Numbered PNGs next to `path` (`name_0001.png`, `name_0002.png`, ...).
+fn save_png_sequence(frames: &[ColorImage], path: &Path, mut progress: impl FnMut(f32)) -> Result<(), String> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("frame");
+    for (idx, frame) in frames.iter().enumerate() {
+        let target = path.with_file_name(format!("{}_{:04}.png", stem, idx + 1));
+        save_color_image(frame.clone(), target, ExportFormat::PNG)?;
+        progress((idx + 1) as f32 / frames.len() as f32);
+    }
+    Ok(())
+}
+
+/// GIF with a palette quantized per frame (NeuQuant). GIF only knows fully opaque or
+/// fully transparent pixels, so soft edges are thresholded.
+fn save_gif(frames: &[ColorImage], path: &Path, fps: f32, looping: bool, mut progress: impl FnMut(f32)) -> Result<(), String> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    use image::{Delay, Frame};
+
+    // Speed 10 is the quantizer's suggested balance between quality and time
+    const QUANTIZE_SPEED: i32 = 10;
+
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = GifEncoder::new_with_speed(std::io::BufWriter::new(file), QUANTIZE_SPEED);
+    // Without the loop extension viewers play the animation once
+    if looping {
+        encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+    }
+    let delay = Delay::from_numer_denom_ms(1000, fps.max(1.0).round() as u32);
+    for (idx, frame) in frames.iter().enumerate() {
+        let rgba = image::RgbaImage::from_raw(frame.size[0] as u32, frame.size[1] as u32, rgba_bytes(frame))
+            .ok_or_else(|| "Failed to build RGBA image".to_string())?;
+        encoder
+            .encode_frame(Frame::from_parts(rgba, 0, 0, delay))
+            .map_err(|e| e.to_string())?;
+        progress((idx + 1) as f32 / frames.len() as f32);
+    }
+    Ok(())
+}
+
+/// Animated PNG, which keeps full 8-bit alpha unlike GIF.
+fn save_apng(frames: &[ColorImage], path: &Path, fps: f32, looping: bool, mut progress: impl FnMut(f32)) -> Result<(), String> {
+    let [width, height] = frames[0].size;
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Zero plays means loop forever
+    encoder
+        .set_animated(frames.len() as u32, if looping { 0 } else { 1 })
+        .map_err(|e| e.to_string())?;
+    let delay_ms = (1000.0 / fps.max(1.0)).round() as u16;
+    encoder.set_frame_delay(delay_ms, 1000).map_err(|e| e.to_string())?;
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    for (idx, frame) in frames.iter().enumerate() {
+        writer.write_image_data(&rgba_bytes(frame)).map_err(|e| e.to_string())?;
+        progress((idx + 1) as f32 / frames.len() as f32);
+    }
+    writer.finish().map_err(|e| e.to_string())
+}
+
+fn rgba_bytes(img: &ColorImage) -> Vec<u8> {
+    img.pixels.iter().flat_map(|px| px.to_srgba_unmultiplied()).collect()
+}
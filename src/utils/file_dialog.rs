@@ -0,0 +1,37 @@
+//! Native open and save dialogs, with the extension filters the app reads and writes.
+use std::path::{Path, PathBuf};
+
+/// Image formats that open as a document.
+pub const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "tif", "tiff", "bmp", "gif", "webp"];
+/// Image formats picked up as brush tips from the brushes folder.
+pub const BRUSH_TIP_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+fn dialog(dir: Option<&Path>) -> rfd::FileDialog {
+    let dialog = rfd::FileDialog::new();
+    match dir {
+        Some(dir) => dialog.set_directory(dir),
+        None => dialog,
+    }
+}
+
+/// Ask for an image to open, starting in `dir`.
+pub fn open_image(dir: Option<&Path>) -> Option<PathBuf> {
+    dialog(dir).add_filter("Images", &IMAGE_EXTENSIONS).pick_file()
+}
+
+/// Ask for images to add as brush tips.
+pub fn open_brush_tips(dir: Option<&Path>) -> Option<Vec<PathBuf>> {
+    dialog(dir).add_filter("Brush tips", &BRUSH_TIP_EXTENSIONS).pick_files()
+}
+
+/// Ask where to save a file of the `filter` type, suggesting `file_name` in `dir`.
+pub fn save_file(dir: Option<&Path>, file_name: &str, filter: &str, extensions: &[&str]) -> Option<PathBuf> {
+    dialog(dir).set_file_name(file_name).add_filter(filter, extensions).save_file()
+}
+
+/// The user's Pictures folder, when there is one.
+pub fn pictures_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    let pictures = PathBuf::from(home).join("Pictures");
+    pictures.is_dir().then_some(pictures)
+}
@@ -0,0 +1,105 @@
+//! UI translations.
+//!
+//! Strings are looked up by their English text, so panels read `tr("Layers")` and English
+//! needs no catalog. Each other language is a `lang/<code>.lang` file compiled into the
+//! binary: a `language = <native name>` line followed by `English text = translation` lines,
+//! with `\n` for line breaks and `#` comments. Strings missing from a catalog stay English.
+//!
+//! The chosen language is kept in `language.meta` next to the brush tips.
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const FILE_NAME: &str = "language.meta";
+const ENGLISH: (&str, &str) = ("en", "English");
+/// Code and contents of every bundled catalog. Add new translations here.
+const BUNDLED: [(&str, &str); 1] = [("fr", include_str!("../../lang/fr.lang"))];
+
+struct Catalog {
+    code: &'static str,
+    name: &'static str,
+    strings: HashMap<&'static str, &'static str>,
+}
+
+/// Index into `catalogs()` plus one; zero is English.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+fn catalogs() -> &'static [Catalog] {
+    static CATALOGS: OnceLock<Vec<Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| BUNDLED.iter().map(|&(code, contents)| parse(code, contents)).collect())
+}
+
+fn parse(code: &'static str, contents: &'static str) -> Catalog {
+    let mut catalog = Catalog { code, name: code, strings: HashMap::new() };
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(" = ") else {
+            log::warn!("Ignoring malformed line in {code}.lang: {line}");
+            continue;
+        };
+        // Catalogs are parsed once, so unescaped copies live for the whole run
+        let unescape = |s: &'static str| -> &'static str {
+            if s.contains("\\n") { Box::leak(s.replace("\\n", "\n").into_boxed_str()) } else { s }
+        };
+        if key == "language" {
+            catalog.name = value;
+        } else {
+            catalog.strings.insert(unescape(key), unescape(value));
+        }
+    }
+    catalog
+}
+
+/// The current language's text for `english`, or `english` itself when it has none.
+pub fn tr(english: &'static str) -> &'static str {
+    match CURRENT.load(Ordering::Relaxed).checked_sub(1) {
+        Some(idx) => catalogs()[idx].strings.get(english).copied().unwrap_or(english),
+        None => english,
+    }
+}
+
+/// [`tr`] for text with `{}` placeholders, filled with `args` in order.
+pub fn trf(english: &'static str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut parts = tr(english).split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    let mut args = args.iter();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// Code and native name of every available language, English first.
+pub fn languages() -> Vec<(&'static str, &'static str)> {
+    std::iter::once(ENGLISH).chain(catalogs().iter().map(|c| (c.code, c.name))).collect()
+}
+
+/// Code of the current language.
+pub fn language() -> &'static str {
+    languages()[CURRENT.load(Ordering::Relaxed)].0
+}
+
+/// Switch the UI language; unknown codes fall back to English.
+pub fn set_language(code: &str) {
+    let idx = catalogs().iter().position(|c| c.code == code).map_or(0, |i| i + 1);
+    CURRENT.store(idx, Ordering::Relaxed);
+}
+
+/// Apply the language saved in `dir`, if any.
+pub fn load(dir: &Path) {
+    if let Ok(code) = std::fs::read_to_string(dir.join(FILE_NAME)) {
+        set_language(code.trim());
+    }
+}
+
+pub fn save(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(FILE_NAME), format!("{}\n", language()))
+}
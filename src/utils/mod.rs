@@ -1,5 +1,8 @@
 //! Small utility helpers shared across the app.
+pub mod clipboard;
 pub mod color;
 pub mod exporter;
+pub mod file_dialog;
+pub mod i18n;
 pub mod profiler;
 pub mod vector;
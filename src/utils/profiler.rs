@@ -1,23 +1,69 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Mutex, OnceLock};
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// Off by default; the `--bench` mode and the profiler HUD switch it on to collect timings.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Scopes currently open on this thread, innermost last, to find each scope's parent.
+    static OPEN_SCOPES: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Start or stop collecting timings for all scopes.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
 
 /// Minimal scope timer used to gather aggregated metrics with low overhead.
 pub struct ScopeTimer {
     name: &'static str,
     start: Instant,
     enabled: bool,
+    /// Enclosing scope on the same thread, `None` for roots and detached timers.
+    parent: Option<&'static str>,
+    nested: bool,
 }
 
 impl ScopeTimer {
-    /// Start a new timer for the given label; disabled when `ENABLE` is false.
+    /// Start a new timer for the given label; a cheap no-op unless profiling is enabled.
+    /// Scopes opened while this one is alive on the same thread are recorded as its children.
     pub fn new(name: &'static str) -> Self {
-        // Toggle profiling here; when disabled this becomes a cheap no-op.
-        const ENABLE: bool = false;
+        let enabled = is_enabled();
+        let parent = if enabled {
+            OPEN_SCOPES.with(|open| {
+                let mut open = open.borrow_mut();
+                let parent = open.last().copied();
+                open.push(name);
+                parent
+            })
+        } else {
+            None
+        };
+        Self {
+            name,
+            start: Instant::now(),
+            enabled,
+            parent,
+            nested: enabled,
+        }
+    }
+
+    /// A timer that outlives the frame it starts in (e.g. a whole stroke), so it takes no
+    /// part in the parent/child nesting.
+    pub fn detached(name: &'static str) -> Self {
         Self {
             name,
             start: Instant::now(),
-            enabled: ENABLE,
+            enabled: is_enabled(),
+            parent: None,
+            nested: false,
         }
     }
 }
@@ -28,14 +74,19 @@ impl Drop for ScopeTimer {
             return;
         }
         let elapsed = self.start.elapsed();
+        if self.nested {
+            OPEN_SCOPES.with(|open| {
+                let mut open = open.borrow_mut();
+                if let Some(idx) = open.iter().rposition(|&n| n == self.name) {
+                    open.remove(idx);
+                }
+            });
+        }
         let stats = STATS.get_or_init(Default::default);
         let mut stats = stats.lock().unwrap();
         let entry = stats.entry(self.name).or_insert_with(Stats::new);
         entry.update(elapsed);
-        let _ = entry.avg();
-
-        // Logging disabled to keep hot paths free of stdout overhead.
-        let _ = (elapsed, entry); // keep stats updated without printing
+        entry.parent = entry.parent.or(self.parent);
     }
 }
 
@@ -46,6 +97,7 @@ struct Stats {
     count: u128,
     max: std::time::Duration,
     last: std::time::Duration,
+    parent: Option<&'static str>,
 }
 
 impl Stats {
@@ -73,3 +125,35 @@ impl Stats {
 }
 
 static STATS: OnceLock<Mutex<HashMap<&'static str, Stats>>> = OnceLock::new();
+
+/// Aggregated timings of one scope label.
+#[derive(Clone, Debug)]
+pub struct ScopeReport {
+    pub name: &'static str,
+    pub parent: Option<&'static str>,
+    pub count: u128,
+    pub total: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+}
+
+/// Timings gathered since the last call, sorted by label. Clears the counters.
+pub fn take_report() -> Vec<ScopeReport> {
+    let Some(stats) = STATS.get() else {
+        return Vec::new();
+    };
+    let mut stats = stats.lock().unwrap();
+    let mut report: Vec<ScopeReport> = stats
+        .drain()
+        .map(|(name, s)| ScopeReport {
+            name,
+            parent: s.parent,
+            count: s.count,
+            total: Duration::from_nanos(s.total as u64),
+            avg: s.avg(),
+            max: s.max,
+        })
+        .collect();
+    report.sort_by_key(|r| r.name);
+    report
+}